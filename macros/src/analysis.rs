@@ -0,0 +1,434 @@
+//! Static checks run on a grammar rule at macro time, to catch grammars that would hang or
+//! overflow the stack at runtime.
+//!
+//! Each `#[parser]` invocation only sees one enum, so other rules are opaque here. They are assumed
+//! to be non-empty and to terminate, which means these checks never reject a valid grammar, but
+//! can miss problems that span multiple rules.
+
+use proc_macro2::{Ident, TokenStream as TokenStream2};
+use quote::quote;
+use crate::common::{ParceMacroError, RangeRuleMax};
+use crate::parser::{ParserPattern, VariantInfo, VariantFields};
+
+/// Rejects rules that can never finish matching, and repetitions that could loop forever
+/// without consuming any lexemes.
+pub(crate) fn check_termination(grammar: &Ident, variants: &[VariantInfo]) -> Result<(), ParceMacroError> {
+    if !variants.is_empty() && !fixed_point(variants, |info, this| terminates(&info.pattern, info, grammar, this)) {
+        return Err(ParceMacroError(
+            Box::new(grammar.clone()),
+            format!("{} can never finish matching: every production requires another {} unconditionally", grammar, grammar)
+        ));
+    }
+
+    let self_nullable = fixed_point(variants, |info, this| nullable(&info.pattern, info, grammar, this));
+    for info in variants {
+        check_repetitions(&info.pattern, info, grammar, self_nullable)?;
+    }
+    Ok(())
+}
+
+/// Evaluates a property of the rule that depends on itself, by iterating from `false` until
+/// it stops changing.
+fn fixed_point(variants: &[VariantInfo], property: impl Fn(&VariantInfo, bool) -> bool) -> bool {
+    let mut this = false;
+    loop {
+        let next = variants.iter().any(|info| property(info, this));
+        if next == this {
+            return this;
+        }
+        this = next;
+    }
+}
+
+/// Whether a pattern element refers back to the rule being analysed, either through a `#Rule`
+/// reference or through a field whose (unwrapped) type is the rule.
+fn is_self_reference(pattern: &ParserPattern, info: &VariantInfo, grammar: &Ident) -> bool {
+    use ParserPattern::*;
+
+    let ty = match pattern {
+        Rule(name) => return name == "Self" || *grammar == name.split("::").last().unwrap_or(name),
+        BareUnnamedField(n) => match &info.fields {
+            VariantFields::Unnamed(fields) => fields.get(*n).cloned(),
+            _ => None
+        },
+        BareNamedField(name) => info.fields.search_named(name).ok(),
+        _ => None
+    };
+    match ty {
+        Some(syn::Type::Path(syn::TypePath { path, .. })) => {
+            path.segments.last().map(|seg| seg.ident == *grammar || seg.ident == "Self").unwrap_or(false)
+        }
+        _ => false
+    }
+}
+
+/// Whether the pattern can finish matching, given whether the rule itself can.
+fn terminates(pattern: &ParserPattern, info: &VariantInfo, grammar: &Ident, this: bool) -> bool {
+    use ParserPattern::*;
+
+    if is_self_reference(pattern, info, grammar) {
+        return this;
+    }
+    match pattern {
+        AssignUnnamedField(_, p) | AssignNamedField(_, p) | Plus(p) => terminates(p, info, grammar, this),
+        And(ps) => ps.iter().all(|p| terminates(p, info, grammar, this)),
+        Or(ps) => ps.iter().any(|p| terminates(p, info, grammar, this)),
+        Star(_) | Question(_) => true,
+        Range(p, min, _) => *min == 0 || terminates(p, info, grammar, this),
+        Lexeme(_) | Keyword(_, _) | Char(_) | Class(_) | Rule(_) | BareUnnamedField(_) | BareNamedField(_) | Dot => true
+    }
+}
+
+/// Whether the pattern can match without consuming any lexemes, given whether the rule itself can.
+fn nullable(pattern: &ParserPattern, info: &VariantInfo, grammar: &Ident, this: bool) -> bool {
+    use ParserPattern::*;
+
+    if is_self_reference(pattern, info, grammar) {
+        return this;
+    }
+    match pattern {
+        AssignUnnamedField(_, p) | AssignNamedField(_, p) | Plus(p) => nullable(p, info, grammar, this),
+        And(ps) => ps.iter().all(|p| nullable(p, info, grammar, this)),
+        Or(ps) => ps.iter().any(|p| nullable(p, info, grammar, this)),
+        Star(_) | Question(_) => true,
+        Range(p, min, _) => *min == 0 || nullable(p, info, grammar, this),
+        Lexeme(_) | Keyword(_, _) | Char(_) | Class(_) | Rule(_) | BareUnnamedField(_) | BareNamedField(_) | Dot => false
+    }
+}
+
+fn check_repetitions(pattern: &ParserPattern, info: &VariantInfo, grammar: &Ident, self_nullable: bool) -> Result<(), ParceMacroError> {
+    use ParserPattern::*;
+
+    match pattern {
+        Star(p) | Plus(p) | Range(p, _, RangeRuleMax::Infinite) => {
+            if nullable(p, info, grammar, self_nullable) {
+                return Err(ParceMacroError(
+                    Box::new(info.ident.clone()),
+                    "the pattern inside this repetition can match nothing, so the repetition would loop forever".to_string()
+                ));
+            }
+            check_repetitions(p, info, grammar, self_nullable)
+        }
+        AssignUnnamedField(_, p) | AssignNamedField(_, p) | Question(p) | Range(p, _, _) => check_repetitions(p, info, grammar, self_nullable),
+        And(ps) | Or(ps) => {
+            for p in ps {
+                check_repetitions(p, info, grammar, self_nullable)?;
+            }
+            Ok(())
+        }
+        Lexeme(_) | Keyword(_, _) | Char(_) | Class(_) | Rule(_) | BareUnnamedField(_) | BareNamedField(_) | Dot => Ok(())
+    }
+}
+
+/// The lexemes a pattern can start with, as far as one rule can see. Other rules are opaque, so
+/// they add nothing.
+#[derive(Default, Clone)]
+struct Start {
+    lexemes: Vec<String>,
+    /// Can start with any lexeme, because of a `.`
+    any: bool
+}
+
+impl Start {
+    fn extend(&mut self, other: &Start) {
+        for lexeme in &other.lexemes {
+            if !self.lexemes.contains(lexeme) {
+                self.lexemes.push(lexeme.clone());
+            }
+        }
+        self.any |= other.any;
+    }
+
+    /// The lexemes both sets contain, in the same form as `parce::report::Conflict::shared`.
+    fn shared(&self, other: &Start) -> Vec<String> {
+        let mut shared: Vec<String> = match (self.any, other.any) {
+            (true, _) => other.lexemes.clone(),
+            (false, true) => self.lexemes.clone(),
+            (false, false) => self.lexemes.iter().filter(|l| other.lexemes.contains(l)).cloned().collect()
+        };
+        shared.sort();
+        if self.any && other.any {
+            shared.push(".".to_string());
+        }
+        shared
+    }
+}
+
+/// Everything a rule needs to know to find where its own patterns would make the parser speculate.
+struct Determinism<'a> {
+    grammar: &'a Ident,
+    info: &'a VariantInfo,
+    self_nullable: bool
+}
+
+impl Determinism<'_> {
+    fn start(&self, pattern: &ParserPattern) -> Start {
+        use ParserPattern::*;
+
+        let mut start = Start::default();
+        if is_self_reference(pattern, self.info, self.grammar) {
+            return start;
+        }
+        match pattern {
+            Lexeme(l) | Keyword(l, _) | Class(l) => start.lexemes.push(l.clone()),
+            Char(c) => start.lexemes.push(format!("{:?}", c)),
+            Dot => start.any = true,
+            Rule(_) | BareUnnamedField(_) | BareNamedField(_) => {}
+            AssignUnnamedField(_, p) | AssignNamedField(_, p) | Star(p) | Plus(p) | Question(p) | Range(p, _, _) => {
+                start = self.start(p)
+            }
+            And(ps) => {
+                for p in ps {
+                    start.extend(&self.start(p));
+                    if !nullable(p, self.info, self.grammar, self.self_nullable) {
+                        break;
+                    }
+                }
+            }
+            Or(ps) => {
+                for p in ps {
+                    start.extend(&self.start(p));
+                }
+            }
+        }
+        start
+    }
+
+    /// What could be next when choosing whether to take `pattern`, given what can come `after` it.
+    fn lookahead(&self, pattern: &ParserPattern, after: &Start) -> Start {
+        let mut start = self.start(pattern);
+        if nullable(pattern, self.info, self.grammar, self.self_nullable) {
+            start.extend(after);
+        }
+        start
+    }
+
+    /// Finds the first choice inside `pattern` where more than one option can take the same lexeme.
+    fn check(&self, pattern: &ParserPattern, after: &Start) -> Result<(), ParceMacroError> {
+        use ParserPattern::*;
+
+        let error = |what: String, shared: Vec<String>| Err(ParceMacroError(
+            Box::new(self.info.ident.clone()),
+            format!("{} is #[deterministic], but {} can both start with {}", self.grammar, what, shared.join(", "))
+        ));
+        match pattern {
+            AssignUnnamedField(_, p) | AssignNamedField(_, p) => self.check(p, after),
+            And(ps) => {
+                let mut rest = after.clone();
+                for p in ps.iter().rev() {
+                    self.check(p, &rest)?;
+                    rest = self.lookahead(p, &rest);
+                }
+                Ok(())
+            }
+            Or(ps) => {
+                let lookaheads: Vec<_> = ps.iter().map(|p| self.lookahead(p, after)).collect();
+                for i in 0..ps.len() {
+                    for j in i+1..ps.len() {
+                        let shared = lookaheads[i].shared(&lookaheads[j]);
+                        if !shared.is_empty() {
+                            return error(format!("alternatives {} and {} of an or group in {}", i, j, self.info.ident), shared);
+                        }
+                    }
+                }
+                ps.iter().try_for_each(|p| self.check(p, after))
+            }
+            Star(p) | Plus(p) | Question(p) | Range(p, _, _) => {
+                let once = matches!(pattern, Question(_) | Range(_, 1, RangeRuleMax::Fixed) | Range(_, _, RangeRuleMax::Some(1)));
+                let fixed = matches!(pattern, Range(_, _, RangeRuleMax::Fixed));
+                let again = if once {
+                    after.clone()
+                } else {
+                    let mut again = self.start(p);
+                    again.extend(after);
+                    again
+                };
+                if !fixed {
+                    let shared = self.start(p).shared(after);
+                    if !shared.is_empty() {
+                        return error(format!("repeating and moving past a repetition in {}", self.info.ident), shared);
+                    }
+                }
+                self.check(p, &again)
+            }
+            Lexeme(_) | Keyword(_, _) | Char(_) | Class(_) | Rule(_) | BareUnnamedField(_) | BareNamedField(_) | Dot => Ok(())
+        }
+    }
+}
+
+/// Rejects `#[deterministic]` rules where two alternatives can start with the same lexeme, so the
+/// parser would have to follow both until one fails.
+///
+/// Only lexemes named in this rule can be compared, since other rules are opaque here. Conflicts
+/// involving other rules are found by `parce::report::determinism_report`.
+pub(crate) fn check_deterministic(grammar: &Ident, variants: &[VariantInfo]) -> Result<(), ParceMacroError> {
+    let self_nullable = fixed_point(variants, |info, this| nullable(&info.pattern, info, grammar, this));
+    let checks: Vec<_> = variants.iter().map(|info| Determinism { grammar, info, self_nullable }).collect();
+    let starts: Vec<_> = checks.iter().map(|check| check.lookahead(&check.info.pattern, &Start::default())).collect();
+    for i in 0..variants.len() {
+        for j in i+1..variants.len() {
+            let shared = starts[i].shared(&starts[j]);
+            if !shared.is_empty() {
+                return Err(ParceMacroError(
+                    Box::new(variants[j].ident.clone()),
+                    format!(
+                        "{} is #[deterministic], but productions {} and {} can both start with {}",
+                        grammar, variants[i].ident, variants[j].ident, shared.join(", ")
+                    )
+                ));
+            }
+        }
+    }
+    for check in &checks {
+        check.check(&check.info.pattern, &Start::default())?;
+    }
+    Ok(())
+}
+
+/// Generates the `parce::report::Pattern` expression describing a pattern, for grammar-wide
+/// analysis at runtime.
+pub(crate) fn describe(pattern: &ParserPattern, info: &VariantInfo) -> Result<TokenStream2, ParceMacroError> {
+    use ParserPattern::*;
+
+    let rule = |ty: TokenStream2| quote! { parce::report::Pattern::Rule(<#ty as parce::internal_prelude::Parseable>::rule_info) };
+    let repeat = |p: &ParserPattern, min: usize, max: Option<usize>| -> Result<TokenStream2, ParceMacroError> {
+        let inner = describe(p, info)?;
+        let max = match max {
+            Some(m) => quote! { Some(#m) },
+            None => quote! { None }
+        };
+        Ok(quote! { parce::report::Pattern::Repeat(&#inner, #min, #max) })
+    };
+
+    Ok(match pattern {
+        Lexeme(name) => quote! { parce::report::Pattern::Lexeme(#name) },
+        Keyword(name, text) => quote! { parce::report::Pattern::Keyword(#name, #text) },
+        Char(c) => {
+            let text = format!("{:?}", c);
+            quote! { parce::report::Pattern::Text(#text) }
+        }
+        Class(class) => quote! { parce::report::Pattern::Text(#class) },
+        Dot => quote! { parce::report::Pattern::Dot },
+        Rule(name) => {
+            let path: syn::Path = match syn::parse_str(name) {
+                Ok(p) => p,
+                Err(_) => return Err(ParceMacroError(Box::new(info.ident.clone()), format!("{} is not a valid rule path", name)))
+            };
+            rule(quote! { #path })
+        }
+        BareUnnamedField(n) => match &info.fields {
+            VariantFields::Unnamed(fields) => match fields.get(*n) {
+                Some(ty) => rule(quote! { #ty }),
+                None => return Err(ParceMacroError(Box::new(info.ident.clone()), format!("production has fewer than {} fields", n)))
+            },
+            _ => return Err(ParceMacroError(Box::new(info.ident.clone()), "variant does not have unnamed fields".to_string()))
+        },
+        BareNamedField(name) => {
+            let ty = info.fields.search_named(name)?;
+            rule(quote! { #ty })
+        }
+        AssignUnnamedField(_, p) | AssignNamedField(_, p) => describe(p, info)?,
+        And(ps) => {
+            let ps = ps.iter().map(|p| describe(p, info)).collect::<Result<Vec<_>, _>>()?;
+            quote! { parce::report::Pattern::And(&[#(#ps),*]) }
+        }
+        Or(ps) => {
+            let ps = ps.iter().map(|p| describe(p, info)).collect::<Result<Vec<_>, _>>()?;
+            quote! { parce::report::Pattern::Or(&[#(#ps),*]) }
+        }
+        Star(p) => repeat(p, 0, None)?,
+        Plus(p) => repeat(p, 1, None)?,
+        Question(p) => repeat(p, 0, Some(1))?,
+        Range(p, min, RangeRuleMax::Fixed) => repeat(p, *min, Some(*min))?,
+        Range(p, min, RangeRuleMax::Infinite) => repeat(p, *min, None)?,
+        Range(p, min, RangeRuleMax::Some(max)) => repeat(p, *min, Some(*max))?
+    })
+}
+
+/// What one state of a route matches directly. These are recorded while generating the commands
+/// for each route, and turned into the expected-lexeme tables once every route is numbered.
+#[derive(Default)]
+pub(crate) struct StateExpectation {
+    pub lexemes: Vec<String>,
+    pub any: bool,
+    /// Types of the other rules spawned by this state.
+    pub rules: Vec<TokenStream2>,
+    /// Routes of this rule spawned by this state.
+    pub routes: Vec<usize>,
+    /// The state can be passed without matching anything, like a `*` or `?`.
+    pub optional: bool,
+    /// The only lexeme the state can use, if it checks for one and dies on everything else.
+    pub guard: Option<String>
+}
+
+#[derive(Default)]
+struct Resolved {
+    lexemes: Vec<String>,
+    any: bool,
+    rules: Vec<TokenStream2>,
+    can_end: bool
+}
+
+impl Resolved {
+    fn extend(&mut self, other: Resolved) {
+        for lexeme in other.lexemes {
+            if !self.lexemes.contains(&lexeme) {
+                self.lexemes.push(lexeme);
+            }
+        }
+        for rule in other.rules {
+            if !self.rules.iter().any(|r| r.to_string() == rule.to_string()) {
+                self.rules.push(rule);
+            }
+        }
+        self.any |= other.any;
+        self.can_end |= other.can_end;
+    }
+}
+
+/// Everything an automaton on `route` in `state` could match next. Routes only spawn routes
+/// numbered after themselves, so this always terminates.
+fn resolve(routes: &[Vec<StateExpectation>], route: usize, state: usize) -> Resolved {
+    let expectation = match routes[route].get(state) {
+        Some(e) => e,
+        None => return Resolved { can_end: true, ..Default::default() }
+    };
+    let mut result = Resolved {
+        lexemes: expectation.lexemes.clone(),
+        any: expectation.any,
+        rules: expectation.rules.clone(),
+        can_end: false
+    };
+    let mut passes = expectation.optional;
+    for sub_route in &expectation.routes {
+        let mut sub = resolve(routes, *sub_route, 0);
+        passes |= sub.can_end;
+        sub.can_end = false;
+        result.extend(sub);
+    }
+    if passes {
+        result.extend(resolve(routes, route, state + 1));
+    }
+    result
+}
+
+/// Generates the `[route][state]` table of `parce::report::Expected` for a rule. Other rules are
+/// assumed to always consume at least one lexeme, like in [check_termination].
+pub(crate) fn expectation_tables(routes: &[Vec<StateExpectation>]) -> TokenStream2 {
+    let tables = (0..routes.len()).map(|route| {
+        let states = (0..routes[route].len()).map(|state| {
+            let Resolved { mut lexemes, any, rules, can_end } = resolve(routes, route, state);
+            lexemes.sort();
+            quote! {
+                parce::report::Expected {
+                    lexemes: &[#(#lexemes),*],
+                    any: #any,
+                    rules: &[#(<#rules as parce::internal_prelude::Parseable>::rule_info),*],
+                    can_end: #can_end
+                }
+            }
+        });
+        quote! { &[#(#states),*] }
+    });
+    quote! { &[#(#tables),*] }
+}
@@ -1,12 +1,62 @@
+use proc_macro2::TokenStream as TokenStream2;
 use quote::ToTokens;
 use syn::Attribute;
+use syn::spanned::Spanned;
+use crate::discriminants::strip_paths;
+
+/// A hash of `item`'s tokens, without its doc comments or visibility, with the same FNV-1a hash as
+/// `parce::fingerprint::fnv1a`. Tokens are hashed one at a time, so it doesn't depend on how the token
+/// stream would be printed.
+pub(crate) fn fingerprint(item: &syn::ItemEnum) -> u64 {
+    use proc_macro2::{Delimiter, TokenStream, TokenTree};
+
+    fn add(state: &mut u64, text: &str) {
+        for byte in text.bytes().chain(std::iter::once(0)) {
+            *state ^= byte as u64;
+            *state = state.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn hash(tokens: TokenStream, state: &mut u64) {
+        for token in tokens {
+            match token {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::None => ("", "")
+                    };
+                    add(state, open);
+                    hash(group.stream(), state);
+                    add(state, close);
+                }
+                other => add(state, &other.to_string())
+            }
+        }
+    }
+
+    let mut item = item.clone();
+    item.vis = syn::Visibility::Inherited;
+    let is_doc = |attr: &Attribute| attr.path.is_ident("doc");
+    item.attrs.retain(|a| !is_doc(a));
+    for variant in &mut item.variants {
+        variant.attrs.retain(|a| !is_doc(a));
+        for field in &mut variant.fields {
+            field.attrs.retain(|a| !is_doc(a));
+        }
+    }
+    let mut state = 0xcbf2_9ce4_8422_2325;
+    hash(item.to_token_stream(), &mut state);
+    state
+}
 
 pub(crate) struct ParceMacroError(pub Box<dyn ToTokens>, pub String);
 
 pub(crate) fn has_attr(s: &str, attrs: &Vec<Attribute>) -> bool {
     for attr in attrs {
         if let Some(ident) = attr.path.get_ident() {
-            if ident.to_string() == s {
+            if *ident == s {
                 return true;
             }
         }
@@ -17,7 +67,7 @@ pub(crate) fn has_attr(s: &str, attrs: &Vec<Attribute>) -> bool {
 pub(crate) fn get_attr<'a>(s: &str, attrs: &'a Vec<Attribute>) -> Option<&'a Attribute> {
     for attr in attrs {
         if let Some(ident) = attr.path.get_ident() {
-            if ident.to_string() == s {
+            if *ident == s {
                 return Some(attr);
             }
         }
@@ -28,7 +78,7 @@ pub(crate) fn get_attr<'a>(s: &str, attrs: &'a Vec<Attribute>) -> Option<&'a Att
 pub(crate) fn get_attr_mut<'a>(s: &str, attrs: &'a mut Vec<Attribute>) -> Option<&'a mut Attribute> {
     for attr in attrs {
         if let Some(ident) = attr.path.get_ident() {
-            if ident.to_string() == s {
+            if *ident == s {
                 return Some(attr);
             }
         }
@@ -54,43 +104,338 @@ pub(crate) fn get_ident_list(s: &str, attrs: &Vec<Attribute>) -> Option<Vec<Stri
             }
             Some(list)
         },
-        _ => return None
+        _ => None
+    }
+}
+
+/// Removes every `#[sync(...)]` attribute from `attrs`, and returns the lexemes they list.
+#[cfg(feature = "parser")]
+pub(crate) fn take_sync(attrs: &mut Vec<Attribute>) -> Result<Vec<syn::Ident>, ParceMacroError> {
+    let mut sync = vec![];
+    let mut error = None;
+    attrs.retain(|attr| {
+        if !attr.path.is_ident("sync") {
+            return true;
+        }
+        match attr.parse_args_with(syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated) {
+            Ok(lexemes) => sync.extend(lexemes),
+            Err(_) => error = Some(ParceMacroError(Box::new(attr.clone()), "sync must be a list of lexemes, like #[sync(Semicolon, RBrace)]".to_string()))
+        }
+        false
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(sync)
     }
 }
 
-pub(crate) fn get_lexer_ident(meta: &syn::Meta) -> Option<syn::Ident> {
-    match meta {
-        syn::Meta::Path(path) => {
-            match path.get_ident() {
-                Some(i) => Some(i.clone()),
-                None => None
+/// Removes every `#[no_skip]` attribute from `attrs`, and returns whether there were any.
+#[cfg(feature = "parser")]
+pub(crate) fn take_no_skip(attrs: &mut Vec<Attribute>) -> bool {
+    let before = attrs.len();
+    attrs.retain(|attr| !attr.path.is_ident("no_skip"));
+    attrs.len() != before
+}
+
+/// Removes every `#[deterministic]` attribute from `attrs`, and returns whether there were any.
+#[cfg(feature = "parser")]
+pub(crate) fn take_deterministic(attrs: &mut Vec<Attribute>) -> bool {
+    let before = attrs.len();
+    attrs.retain(|attr| !attr.path.is_ident("deterministic"));
+    attrs.len() != before
+}
+
+/// Removes every `#[inline_rule]` attribute from `attrs`, and returns whether there were any.
+#[cfg(feature = "parser")]
+pub(crate) fn take_inline_rule(attrs: &mut Vec<Attribute>) -> bool {
+    let before = attrs.len();
+    attrs.retain(|attr| !attr.path.is_ident("inline_rule"));
+    attrs.len() != before
+}
+
+/// Removes `#[prefer_shift]` and `#[resolve(...)]` from `attrs`, and returns the name of the
+/// `parce::report::Resolve` variant they ask for.
+#[cfg(feature = "parser")]
+pub(crate) fn take_resolve(attrs: &mut Vec<Attribute>) -> Result<Option<&'static str>, ParceMacroError> {
+    let mut resolve = None;
+    let mut error = None;
+    attrs.retain(|attr| {
+        let found = if attr.path.is_ident("prefer_shift") {
+            Some("Longest")
+        } else if attr.path.is_ident("resolve") {
+            match attr.parse_args::<syn::Ident>() {
+                Ok(id) if id == "longest" => Some("Longest"),
+                Ok(id) if id == "shortest" => Some("Shortest"),
+                _ => {
+                    error = Some(ParceMacroError(Box::new(attr.clone()), "expected #[resolve(longest)] or #[resolve(shortest)]".to_string()));
+                    None
+                }
             }
+        } else {
+            return true;
+        };
+        if found.is_some() && resolve.is_some() && resolve != found {
+            error = Some(ParceMacroError(Box::new(attr.clone()), "conflicting resolve attributes".to_string()));
         }
-        _ => None
+        resolve = resolve.or(found);
+        false
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(resolve)
     }
 }
 
-pub(crate) fn get_lexer_path(meta: &syn::Meta) -> Option<syn::Path> {
-    match meta {
-        syn::Meta::Path(path) => Some(path.clone()),
-        _ => None
+/// The version, features, and flags a lexeme or production needs, from `#[since(...)]`,
+/// `#[cfg_grammar(...)]`, and `#[flag(...)]`. Becomes a `parce::version::Gate`.
+#[derive(Debug, Default)]
+pub(crate) struct Gate {
+    pub since: Vec<u32>,
+    pub features: Vec<String>,
+    pub flags: Vec<String>
+}
+
+impl quote::ToTokens for Gate {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let (since, features, flags) = (&self.since, &self.features, &self.flags);
+        tokens.extend(quote::quote! {
+            parce::version::Gate { since: &[#(#since),*], features: &[#(#features),*], flags: &[#(#flags),*] }
+        });
     }
 }
 
+/// Removes `#[deprecated_syntax(message = "...")]` from `attrs`, and returns its message.
+pub(crate) fn take_deprecated_syntax(attrs: &mut Vec<Attribute>) -> Result<Option<String>, ParceMacroError> {
+    let mut message = None;
+    let mut error = None;
+    attrs.retain(|attr| {
+        if !attr.path.is_ident("deprecated_syntax") {
+            return true;
+        }
+        match attr.parse_meta() {
+            _ if message.is_some() => error = Some(ParceMacroError(Box::new(attr.clone()), "only one #[deprecated_syntax(...)] is allowed".to_string())),
+            Ok(syn::Meta::List(list)) => match list.nested.iter().collect::<Vec<_>>().as_slice() {
+                [syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue { path, lit: syn::Lit::Str(s), .. }))] if path.is_ident("message") => {
+                    message = Some(s.value());
+                }
+                _ => error = Some(ParceMacroError(Box::new(attr.clone()), "expected #[deprecated_syntax(message = \"...\")]".to_string()))
+            },
+            _ => error = Some(ParceMacroError(Box::new(attr.clone()), "expected #[deprecated_syntax(message = \"...\")]".to_string()))
+        }
+        false
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(message)
+    }
+}
+
+/// Removes every `#[since(...)]`, `#[cfg_grammar(...)]`, and `#[flag(...)]` attribute from `attrs`, and returns
+/// what they need, if there were any. A version is either numbers, like `#[since(1, 2)]`, or a
+/// string of them separated by dots, like `#[since("1.2")]`.
+pub(crate) fn take_gate(attrs: &mut Vec<Attribute>) -> Result<Option<Gate>, ParceMacroError> {
+    use syn::ext::IdentExt;
+
+    let mut gate: Option<Gate> = None;
+    let mut error = None;
+    attrs.retain(|attr| {
+        if attr.path.is_ident("since") {
+            let since = attr.parse_args_with(syn::punctuated::Punctuated::<syn::Lit, syn::Token![,]>::parse_terminated).ok().and_then(|lits| {
+                let mut since = vec![];
+                for lit in lits {
+                    match lit {
+                        syn::Lit::Int(int) => since.push(int.base10_parse().ok()?),
+                        syn::Lit::Str(s) => for part in s.value().split('.') {
+                            since.push(part.trim().parse().ok()?);
+                        },
+                        _ => return None
+                    }
+                }
+                Some(since).filter(|since| !since.is_empty())
+            });
+            match (since, &gate) {
+                (_, Some(old)) if !old.since.is_empty() => error = Some(ParceMacroError(Box::new(attr.clone()), "only one #[since(...)] is allowed".to_string())),
+                (Some(since), _) => gate.get_or_insert_with(Gate::default).since = since,
+                (None, _) => error = Some(ParceMacroError(Box::new(attr.clone()), "since must be a version, like #[since(2018)] or #[since(\"1.2\")]".to_string()))
+            }
+        } else if attr.path.is_ident("cfg_grammar") || attr.path.is_ident("flag") {
+            let parsed = attr.parse_args_with(|input: syn::parse::ParseStream| {
+                syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated_with(input, syn::Ident::parse_any)
+            });
+            let names = parsed.ok().filter(|names| !names.is_empty()).map(|names| names.iter().map(|n| n.to_string()).collect::<Vec<_>>());
+            match (names, attr.path.is_ident("flag")) {
+                (Some(features), false) => gate.get_or_insert_with(Gate::default).features.extend(features),
+                (Some(flags), true) => gate.get_or_insert_with(Gate::default).flags.extend(flags),
+                (None, false) => error = Some(ParceMacroError(Box::new(attr.clone()), "cfg_grammar must be a list of features, like #[cfg_grammar(async)]".to_string())),
+                (None, true) => error = Some(ParceMacroError(Box::new(attr.clone()), "flag must be a list of flags, like #[flag(json5)]".to_string()))
+            }
+        } else {
+            return true;
+        }
+        false
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(gate)
+    }
+}
+
+/// Arguments to the lexer macro: the lexer's name and the names of any more lexers, then
+/// optionally `derive(...)` and `vis = ...` for the generated items.
+pub(crate) struct LexerArgs {
+    /// The arguments as they were written, for passing them on when the lexer has to be expanded
+    /// again.
+    pub tokens: TokenStream2,
+    pub ident: syn::Ident,
+    /// More lexers for the same lexemes, which lex the ones marked `#[lexers(...)]` with their
+    /// names.
+    pub also: Vec<syn::Ident>,
+    pub derives: Vec<syn::Path>,
+    pub vis: Option<syn::Visibility>
+}
+
+impl syn::parse::Parse for LexerArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let tokens = input.fork().parse()?;
+        let ident = input.parse().map_err(|e| syn::Error::new(e.span(), "lexer name must be specified"))?;
+        let mut args = LexerArgs {
+            tokens,
+            ident,
+            also: vec![],
+            derives: vec![],
+            vis: None
+        };
+        while !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let key: syn::Ident = input.parse()?;
+            if input.is_empty() || input.peek(syn::Token![,]) {
+                args.also.push(key);
+            } else if key == "derive" {
+                let content;
+                syn::parenthesized!(content in input);
+                args.derives.extend(content.parse_terminated::<_, syn::Token![,]>(syn::Path::parse_mod_style)?);
+            } else if key == "vis" {
+                input.parse::<syn::Token![=]>()?;
+                args.vis = Some(input.parse()?);
+            } else {
+                return Err(syn::Error::new(key.span(), format!("unknown lexer argument {}, expected another lexer name, derive(...), or vis = ...", key)));
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Arguments to the parser macro: the path to the lexer, then optionally `owned` or `borrowed` to
+/// choose whether assigned fields own their text or borrow it from the input.
+#[cfg(feature = "parser")]
+pub(crate) struct ParserArgs {
+    pub lexer: syn::Path,
+    pub borrowed: bool,
+    pub constructors: bool
+}
+
+#[cfg(feature = "parser")]
+impl syn::parse::Parse for ParserArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let lexer = input.parse().map_err(|e| syn::Error::new(e.span(), "lexer name must be specified"))?;
+        let mut args = ParserArgs {
+            lexer,
+            borrowed: false,
+            constructors: false
+        };
+        while !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let key: syn::Ident = input.parse()?;
+            if key == "owned" {
+                args.borrowed = false;
+            } else if key == "borrowed" {
+                args.borrowed = true;
+            } else if key == "constructors" {
+                args.constructors = true;
+            } else {
+                return Err(syn::Error::new(key.span(), format!("unknown parser argument {}, expected owned, borrowed, or constructors", key)));
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// The text of a variant's pattern, with the paths to lexemes replaced by their names.
 pub(crate) fn get_pattern(variant: &syn::Variant) -> Result<String, ParceMacroError> {
+    Ok(strip_paths(&pattern_text(variant)?).0)
+}
+
+/// The paths to lexemes in a variant's pattern, which the generated code uses so the compiler
+/// checks them. Paths in a `p!(...)` keep their own spans; paths in a string get the string's.
+pub(crate) fn lexeme_paths(variant: &syn::Variant) -> Result<Vec<syn::Path>, ParceMacroError> {
     match &variant.discriminant {
-        Some((_, syn::Expr::Lit(syn::ExprLit {
+        Some((_, syn::Expr::Macro(syn::ExprMacro { mac, .. }))) if crate::tokens::is_pattern_macro(mac) => {
+            Ok(crate::tokens::token_paths(mac.tokens.clone()))
+        }
+        Some((_, expr)) => {
+            let span = expr.span();
+            strip_paths(&pattern_text(variant)?).1.iter().map(|path| {
+                let tokens: TokenStream2 = path.parse().map_err(|_| ParceMacroError(Box::new(expr.clone()), format!("{} is not a valid path", path)))?;
+                let tokens: TokenStream2 = tokens.into_iter().map(|mut token| {
+                    token.set_span(span);
+                    token
+                }).collect();
+                syn::parse2(tokens).map_err(|_| ParceMacroError(Box::new(expr.clone()), format!("{} is not a valid path", path)))
+            }).collect()
+        }
+        None => Ok(vec![])
+    }
+}
+
+fn pattern_text(variant: &syn::Variant) -> Result<String, ParceMacroError> {
+    match &variant.discriminant {
+        Some((_, expr)) => expr_pattern(expr),
+        None => Err(ParceMacroError(Box::new(variant.clone()), "discriminant must a str literal or a p!(...) pattern".to_string()))
+    }
+}
+
+/// The text of a pattern written as a string, a character, or a `p!(...)`.
+fn expr_pattern(expr: &syn::Expr) -> Result<String, ParceMacroError> {
+    match expr {
+        // A pattern passed through a macro_rules macro, like in pattern!.
+        syn::Expr::Group(syn::ExprGroup { expr, .. }) => expr_pattern(expr),
+        syn::Expr::Lit(syn::ExprLit {
             lit: syn::Lit::Str(lit_str), ..
-        }))) => Ok(lit_str.value()),
-        Some((_, syn::Expr::Lit(syn::ExprLit {
+        }) => Ok(lit_str.value()),
+        syn::Expr::Lit(syn::ExprLit {
             lit: syn::Lit::Char(lit_char), ..
-        }))) => Ok(format!("'{}'", lit_char.value())),
+        }) => Ok(format!("'{}'", lit_char.value())),
+        syn::Expr::Macro(syn::ExprMacro { mac, .. }) if crate::tokens::is_pattern_macro(mac) => {
+            crate::tokens::token_pattern(mac.tokens.clone())
+        }
         _ => {
-            return Err(ParceMacroError(Box::new(variant.clone()), "discriminant must a str literal".to_string()));
+            Err(ParceMacroError(Box::new(expr.clone()), "discriminant must a str literal or a p!(...) pattern".to_string()))
         }
     }
 }
 
+/// Removes the `#[pattern_constant(NAME = "...")]` attributes added by the macros that
+/// `parce::pattern!` defines, and returns the constants' names and patterns.
+pub(crate) fn take_pattern_constants(attrs: &mut Vec<Attribute>) -> Result<Vec<(String, String)>, ParceMacroError> {
+    let mut constants = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("pattern_constant")) {
+        let (name, expr) = attr.parse_args_with(|input: syn::parse::ParseStream| {
+            let name: syn::Ident = input.parse()?;
+            input.parse::<syn::Token![=]>()?;
+            Ok((name, input.parse::<syn::Expr>()?))
+        }).map_err(|e| ParceMacroError(Box::new(attr.clone()), e.to_string()))?;
+        constants.push((name.to_string(), strip_paths(&expr_pattern(&expr)?).0));
+    }
+    attrs.retain(|attr| !attr.path.is_ident("pattern_constant"));
+    Ok(constants)
+}
+
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub(crate) enum RangeRuleMax {
     Infinite,
@@ -98,6 +443,27 @@ pub(crate) enum RangeRuleMax {
     Some(usize)
 }
 
+/// The ranges of characters a `[...]` class matches, sorted and not overlapping, with the class
+/// syntax of the regex crate. `None` if the class isn't valid.
+pub(crate) fn class_ranges(class: &str) -> Option<Vec<(char, char)>> {
+    use regex_syntax::hir::{Class, HirKind};
+
+    match regex_syntax::Parser::new().parse(class).ok()?.kind() {
+        HirKind::Class(Class::Unicode(class)) => Some(class.ranges().iter().map(|range| (range.start(), range.end())).collect()),
+        // A class that matches nothing is simplified to an empty byte class.
+        HirKind::Class(Class::Bytes(class)) if class.ranges().is_empty() => Some(vec![]),
+        // A class of one character is simplified to that character.
+        HirKind::Literal(literal) => {
+            let mut chars = std::str::from_utf8(&literal.0).ok()?.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(vec![(c, c)]),
+                _ => None
+            }
+        }
+        _ => None
+    }
+}
+
 lazy_static::lazy_static! {
     pub(crate) static ref COUNT_PARSER: regex::Regex = regex::Regex::new(r"\{(\d+),?(\d+)?\}").unwrap();
 }
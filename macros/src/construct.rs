@@ -0,0 +1,69 @@
+//! Generates constructor functions for the variants of a rule, for `#[parser(Lexer, constructors)]`.
+
+use proc_macro2::{Ident, TokenStream as TokenStream2};
+use quote::{quote, format_ident};
+
+/// Converts a variant name like `BinaryOp` or `HTTPGet` to `binary_op` or `http_get`. Names that
+/// turn into keywords, like `If`, become raw identifiers.
+fn snake_case(ident: &Ident) -> Ident {
+    let chars: Vec<char> = ident.to_string().chars().collect();
+    let mut name = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let after_lower = !chars[i - 1].is_uppercase() && chars[i - 1] != '_';
+            let before_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase()) && chars[i - 1].is_uppercase();
+            if after_lower || before_lower {
+                name.push('_');
+            }
+        }
+        name.extend(c.to_lowercase());
+    }
+    match syn::parse_str::<Ident>(&name) {
+        Ok(ident) => ident,
+        Err(_) => Ident::new_raw(&name, ident.span())
+    }
+}
+
+pub(crate) fn constructors(input: &syn::ItemEnum) -> TokenStream2 {
+    let enum_ident = &input.ident;
+    let vis = &input.vis;
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+
+    let functions = input.variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let name = snake_case(ident);
+        let doc = format!("Builds a [`{}::{}`].", enum_ident, ident);
+        let (params, body) = match &variant.fields {
+            syn::Fields::Unit => (vec![], quote! { #enum_ident::#ident }),
+            syn::Fields::Unnamed(fields) => {
+                let names: Vec<_> = (0..fields.unnamed.len()).map(|i| format_ident!("field_{}", i)).collect();
+                let params = fields.unnamed.iter().zip(&names).map(|(field, name)| {
+                    let ty = &field.ty;
+                    quote! { #name: impl Into<#ty> }
+                }).collect();
+                (params, quote! { #enum_ident::#ident(#(#names.into()),*) })
+            }
+            syn::Fields::Named(fields) => {
+                let names: Vec<_> = fields.named.iter().map(|f| f.ident.clone().unwrap()).collect();
+                let params = fields.named.iter().zip(&names).map(|(field, name)| {
+                    let ty = &field.ty;
+                    quote! { #name: impl Into<#ty> }
+                }).collect();
+                (params, quote! { #enum_ident::#ident { #(#names: #names.into()),* } })
+            }
+        };
+        quote! {
+            #[doc = #doc]
+            #vis fn #name(#(#params),*) -> Self {
+                #body
+            }
+        }
+    });
+
+    quote! {
+        #[allow(dead_code, clippy::too_many_arguments)]
+        impl #impl_generics #enum_ident #ty_generics {
+            #(#functions)*
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use crate::common::{ParceMacroError, RangeRuleMax};
+use crate::common::{ParceMacroError, RangeRuleMax, class_ranges};
 use crate::lexer::LexerPattern;
+#[cfg(feature = "parser")]
 use crate::parser::ParserPattern;
 use crate::COUNT_PARSER;
 
@@ -12,7 +13,7 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
     let mut group_depth = 0;
     let mut class_depth = 0;
     let mut splits = vec![-1_i32];
-    while i < s.len() {
+    while i < chars.len() {
         match chars[i] {
             '(' if !in_string && class_depth == 0 => group_depth += 1,
             ')' if !in_string && class_depth == 0 => group_depth -= 1,
@@ -29,10 +30,10 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
         i += 1;
     }
     if splits.len() > 1 {
-        splits.push(s.len() as i32);
+        splits.push(chars.len() as i32);
         let mut options = vec![];
         for j in 0..splits.len()-1 {
-            options.push(lexer_discriminant(s[(splits[j]+1) as usize..splits[j+1] as usize].to_string())?);
+            options.push(lexer_discriminant(chars[(splits[j]+1) as usize..splits[j+1] as usize].iter().collect())?);
         }
         return Ok(LexerPattern::Or(options));
     }
@@ -40,15 +41,15 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
     // Generate other lexemes. If just one return it, otherwise return And.
     let mut result = vec![];
     i = 0;
-    while i < s.len() {
+    while i < chars.len() {
         match chars[i] {
             '\'' => {
                 let mut j = i + 1;
-                while j < s.len() && chars[j] != '\'' && chars[j-1] != '\\' {
+                while j < chars.len() && chars[j] != '\'' && chars[j-1] != '\\' {
                     j += 1;
                 }
-                if j != s.len() {
-                    result.push(LexerPattern::Literal(s[i+1..j].to_string()));
+                if j != chars.len() {
+                    result.push(LexerPattern::Literal(chars[i+1..j].iter().collect::<String>()));
                     i = j;
                 } else {
                     return Err(ParceMacroError(Box::new(s), "reached end of pattern before string was closed".to_string()));
@@ -57,7 +58,7 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
             '[' => {
                 let mut j = i + 1;
                 let mut class_depth = 1;
-                while j < s.len() {
+                while j < chars.len() {
                     match chars[j] {
                         '[' if chars[j-1] != '\\' => class_depth += 1,
                         ']' if chars[j-1] != '\\' => {
@@ -70,8 +71,12 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
                     }
                     j += 1;
                 }
-                if j != s.len() {
-                    result.push(LexerPattern::Class(s[i..j+1].to_string()));
+                if j != chars.len() {
+                    let class = chars[i..j+1].iter().collect::<String>();
+                    if class_ranges(&class).is_none() {
+                        return Err(ParceMacroError(Box::new(s), format!("{} is not a valid regex class", class)));
+                    }
+                    result.push(LexerPattern::Class(class));
                     i = j;
                 }
             }
@@ -80,7 +85,7 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
                 let mut class_depth: u32 = 0;
                 let mut group_depth: u32 = 1;
                 let mut in_string = false;
-                while j < s.len() {
+                while j < chars.len() {
                     match chars[j] {
                         '(' if !in_string && class_depth == 0 => group_depth += 1,
                         ')' if !in_string && class_depth == 0 => {
@@ -98,8 +103,8 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
                     }
                     j += 1;
                 }
-                if j != s.len() {
-                    result.push(lexer_discriminant(s[i+1..j].to_string())?);
+                if j != chars.len() {
+                    result.push(lexer_discriminant(chars[i+1..j].iter().collect::<String>())?);
                     i = j;
                 } else {
                     return Err(ParceMacroError(Box::new(s), "reached end of string before () group was closed".to_string()));
@@ -109,14 +114,15 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
                 match result.pop() {
                     Some(prev) => {
                         let mut j = i + 1;
-                        while j < s.len() {
+                        while j < chars.len() {
                             match chars[j] {
                                 '}' => break,
                                 _ => j += 1
                             }
                         }
-                        if j != s.len() {
-                            let captures = COUNT_PARSER.captures(&s[i..j + 1]);
+                        if j != chars.len() {
+                            let counter: String = chars[i..j + 1].iter().collect();
+                            let captures = COUNT_PARSER.captures(&counter);
                             match captures {
                                 Some(cap) => {
                                     result.push(
@@ -126,7 +132,7 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
                                             match cap.get(2) {
                                                 Some(s) => RangeRuleMax::Some(s.as_str().parse().unwrap()),
                                                 None => {
-                                                    if s[i..j+1].contains(',') {
+                                                    if counter.contains(',') {
                                                         RangeRuleMax::Infinite
                                                     } else {
                                                         RangeRuleMax::Fixed
@@ -146,10 +152,11 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
             }
             c if c.is_alphabetic() => {
                 let mut j = i + 1;
-                while j < s.len() && chars[j].is_alphanumeric() {
+                while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
                     j += 1;
                 }
-                result.push(LexerPattern::Lexeme(s[i..j].to_string()));
+                let name: String = chars[i..j].iter().collect();
+                result.push(unicode_fragment(&name).unwrap_or(LexerPattern::Lexeme(name)));
                 i = j - 1;
             }
             '.' => result.push(LexerPattern::Dot),
@@ -170,7 +177,7 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
         }
         i += 1;
     }
-    if result.len() == 0 {
+    if result.is_empty() {
         Err(ParceMacroError(Box::new(s), "this shouldn't be possible".to_string()))
     } else if result.len() == 1 {
         Ok(result.remove(0))
@@ -179,14 +186,204 @@ pub(crate) fn lexer_discriminant(s: String) -> Result<LexerPattern, ParceMacroEr
     }
 }
 
+/// Replaces the paths to lexemes in a pattern, like `crate::tokens::Integer`, with the lexemes'
+/// names. Returns the pattern with the names, and the paths in order. Paths to rules, after a `#`,
+/// are left alone, since they are already used as paths.
+pub(crate) fn strip_paths(s: &str) -> (String, Vec<String>) {
+    let chars: Vec<char> = s.chars().collect();
+    let mut result = String::new();
+    let mut paths = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let segment_start = |i: usize| chars.get(i).is_some_and(|c| c.is_alphabetic() || *c == '_');
+        let global = chars[i] == ':' && chars.get(i + 1) == Some(&':') && segment_start(i + 2);
+        match chars[i] {
+            '\'' | '"' | '[' => {
+                let close = if chars[i] == '[' { ']' } else { chars[i] };
+                let mut j = i + 1;
+                while j < chars.len() && chars[j] != close {
+                    if chars[j] == '\\' {
+                        j += 1;
+                    }
+                    j += 1;
+                }
+                let end = j.min(chars.len() - 1);
+                result.extend(&chars[i..=end]);
+                i = end + 1;
+            }
+            c if (c.is_alphabetic() || c == '_' || global) && !(i > 0 && (chars[i-1].is_alphanumeric() || chars[i-1] == '_')) => {
+                let mut j = i;
+                let mut last;
+                loop {
+                    if chars[j] == ':' {
+                        j += 2;
+                    }
+                    last = j;
+                    while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
+                        j += 1;
+                    }
+                    if !(chars.get(j) == Some(&':') && chars.get(j + 1) == Some(&':') && segment_start(j + 2)) {
+                        break;
+                    }
+                }
+                let rule = i > 0 && chars[i-1] == '#';
+                if last > i && !rule && chars[last].is_uppercase() {
+                    paths.push(chars[i..j].iter().collect());
+                    result.extend(&chars[last..j]);
+                } else {
+                    result.extend(&chars[i..j]);
+                }
+                i = j;
+            }
+            c => {
+                result.push(c);
+                i += 1;
+            }
+        }
+    }
+    (result, paths)
+}
+
+/// Replaces the names in a pattern, outside of literals and classes, with the text `replace`
+/// gives back for them. Names it gives back `None` for are left alone.
+pub(crate) fn replace_names(s: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\'' | '"' | '[' => {
+                let close = if chars[i] == '[' { ']' } else { chars[i] };
+                let mut j = i + 1;
+                while j < chars.len() && chars[j] != close {
+                    if chars[j] == '\\' {
+                        j += 1;
+                    }
+                    j += 1;
+                }
+                let end = j.min(chars.len() - 1);
+                result.extend(&chars[i..=end]);
+                i = end + 1;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut j = i + 1;
+                while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
+                    j += 1;
+                }
+                let name: String = chars[i..j].iter().collect();
+                match replace(&name) {
+                    Some(text) => result.push_str(&text),
+                    None => result.push_str(&name)
+                }
+                i = j;
+            }
+            c => {
+                result.push(c);
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Whether `name` in a lexer pattern names a constant defined with `parce::pattern!`, instead of
+/// a lexeme, if there's no lexeme with that name. Constants are named in upper snake case, like
+/// `DIGITS`, and the built-in fragments aren't constants.
+pub(crate) fn is_constant_name(name: &str) -> bool {
+    name.chars().any(char::is_uppercase)
+        && !name.chars().any(char::is_lowercase)
+        && unicode_fragment(name).is_none()
+}
+
+/// The built-in fragment named `name`, which can be used in any lexer pattern like a lexeme.
+fn unicode_fragment(name: &str) -> Option<LexerPattern> {
+    use LexerPattern::*;
+
+    let class = |class: &str| Class(class.to_string());
+    Some(match name {
+        "XID_START" => class(r"[\p{XID_Start}]"),
+        "XID_CONTINUE" => class(r"[\p{XID_Continue}]"),
+        // The default identifier of UAX #31 (R1), without medial characters.
+        "XID_IDENT" => And(vec![class(r"[\p{XID_Start}]"), Star(Box::new(class(r"[\p{XID_Continue}]")))]),
+        "WHITESPACE_UNICODE" => class(r"[\p{White_Space}]"),
+        // The whitespace UAX #31 recommends for the syntax of programming languages (R3).
+        "PATTERN_WHITESPACE" => class(r"[\p{Pattern_White_Space}]"),
+        // A line break as defined by UTS #18 (RL1.6), with "\r\n" as one break.
+        "LINE_TERMINATOR" => Or(vec![Literal("\r\n".to_string()), class(r"[\n\x0B\x0C\r\x{85}\x{2028}\x{2029}]")]),
+        _ => return None
+    })
+}
+
+#[cfg(feature = "parser")]
+/// If `chars[i]` starts a quoted literal or a character class in a parser pattern, finds the
+/// character that closes it. Backslashes escape the next character.
+fn char_pattern_end(chars: &[char], i: usize) -> Option<usize> {
+    let close = match chars[i] {
+        '\'' => '\'',
+        '[' => ']',
+        _ => return None
+    };
+    let mut j = i + 1;
+    while j < chars.len() {
+        match chars[j] {
+            '\\' => j += 1,
+            c if c == close => return Some(j),
+            _ => {}
+        }
+        j += 1;
+    }
+    None
+}
+
+#[cfg(feature = "parser")]
+/// Removes the cut (`!`) from a production's pattern, if it has one. Returns the pattern without
+/// it, and the part of the pattern before it.
+pub(crate) fn split_cut(s: &str) -> Result<(String, Option<String>), ParceMacroError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut cut = None;
+    let mut has_or = false;
+    let mut group_depth = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\'' | '[' => i = char_pattern_end(&chars, i).unwrap_or(i),
+            '(' => group_depth += 1,
+            ')' => group_depth -= 1,
+            '|' if group_depth == 0 => has_or = true,
+            '!' if group_depth > 0 => return Err(ParceMacroError(Box::new(s.to_string()), "a cut (!) can't be inside a group".to_string())),
+            '!' if cut.is_some() => return Err(ParceMacroError(Box::new(s.to_string()), "a production can only have one cut (!)".to_string())),
+            '!' => cut = Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    let cut = match cut {
+        Some(cut) => cut,
+        None => return Ok((s.to_string(), None))
+    };
+    if has_or {
+        return Err(ParceMacroError(Box::new(s.to_string()), "a cut (!) can't be used with a top-level |, put the alternatives in their own productions".to_string()));
+    }
+    let before: String = chars[..cut].iter().collect();
+    let after: String = chars[cut + 1..].iter().collect();
+    if before.trim().is_empty() || after.trim().is_empty() {
+        return Err(ParceMacroError(Box::new(s.to_string()), "a cut (!) has to be between two parts of a production".to_string()));
+    }
+    Ok((format!("{} {}", before, after), Some(before)))
+}
+
+#[cfg(feature = "parser")]
 pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError> {
     let chars: Vec<char> = s.chars().collect();
+    // Positions are in chars, so text is cut from chars rather than from the bytes of s.
+    let slice = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
 
     let mut i = 0;
     let mut splits = vec![-1];
     let mut group_depth = 0;
-    while i < s.len() {
+    while i < chars.len() {
         match chars[i] {
+            '\'' | '[' => i = char_pattern_end(&chars, i).unwrap_or(i),
             '(' => group_depth += 1,
             ')' => group_depth -= 1,
             '|' if group_depth == 0 => {
@@ -197,31 +394,32 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
         i += 1;
     }
     if splits.len() > 1 {
-        splits.push(s.len() as i32);
+        splits.push(chars.len() as i32);
         let mut options = vec![];
         for j in 0..splits.len()-1 {
-            options.push(parser_pattern(s[(splits[j]+1) as usize..splits[j+1] as usize].to_string())?);
+            options.push(parser_pattern(slice((splits[j]+1) as usize, splits[j+1] as usize))?);
         }
         return Ok(ParserPattern::Or(options));
     }
 
     let mut result = vec![];
     i = 0;
-    while i < s.len() {
+    while i < chars.len() {
         match chars[i] {
             '#' => {
                 let mut j = i + 1;
-                while j < s.len() && (chars[j].is_alphanumeric() || chars[j] == ':') {
+                while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == ':') {
                     j += 1;
                 }
-                result.push(ParserPattern::Rule(s[i+1..j].to_string()));
+                result.push(ParserPattern::Rule(slice(i+1, j)));
                 i = j - 1;
             }
             '(' => {
                 let mut j = i + 1;
                 let mut group_depth: u32 = 1;
-                while j < s.len() {
+                while j < chars.len() {
                     match chars[j] {
+                        '\'' | '[' => j = char_pattern_end(&chars, j).unwrap_or(j),
                         '(' => group_depth += 1,
                         ')' => {
                             group_depth -= 1;
@@ -233,8 +431,8 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
                     }
                     j += 1;
                 }
-                if j != s.len() {
-                    result.push(parser_pattern(s[i+1..j].to_string())?);
+                if j != chars.len() {
+                    result.push(parser_pattern(slice(i+1, j))?);
                     i = j;
                 } else {
                     return Err(ParceMacroError(Box::new(s), "reached end of string before () group was closed".to_string()));
@@ -242,19 +440,36 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
             }
             c if c.is_alphabetic() => {
                 let mut j = i + 1;
-                while j < s.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
+                while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
                     j += 1;
                 }
-                let name = s[i..j].to_string();
+                let name = slice(i, j);
                 if c.is_uppercase() {
-                    result.push(ParserPattern::Lexeme(name));
-                    i = j - 1;
+                    if j + 1 < chars.len() && chars[j] == '(' && (chars[j+1] == '\'' || chars[j+1] == '"') {
+                        let quote = chars[j+1];
+                        let mut k = j + 2;
+                        while k < chars.len() && chars[k] != quote {
+                            k += 1;
+                        }
+                        if k + 1 >= chars.len() || chars[k+1] != ')' {
+                            return Err(ParceMacroError(Box::new(s.clone()), format!("expected {}({}...{}) to be closed", name, quote, quote)));
+                        }
+                        let text = slice(j+2, k);
+                        if text.is_empty() || text.contains(['(', ')', '|', '\'', '"']) {
+                            return Err(ParceMacroError(Box::new(s.clone()), format!("{:?} is not valid soft keyword text", text)));
+                        }
+                        result.push(ParserPattern::Keyword(name, text));
+                        i = k + 1;
+                    } else {
+                        result.push(ParserPattern::Lexeme(name));
+                        i = j - 1;
+                    }
                 } else {
-                    if j < s.len() - 1 && chars[j] == '=' {
+                    if j < chars.len() - 1 && chars[j] == '=' {
                         let mut k = j + 2;
                         match chars[j + 1] {
                             c if c.is_alphabetic() || c == '#' => {
-                                while k < s.len() {
+                                while k < chars.len() {
                                     if chars[k].is_alphabetic() {
                                         k += 1;
                                     } else {
@@ -262,10 +477,15 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
                                     }
                                 }
                             }
+                            '\'' | '[' => match char_pattern_end(&chars, j + 1) {
+                                Some(end) => k = end + 1,
+                                None => return Err(ParceMacroError(Box::new(s.clone()), format!("{} was never closed", chars[j + 1])))
+                            },
                             '(' => {
                                 let mut group_depth = 1;
-                                while k < s.len() {
+                                while k < chars.len() {
                                     match chars[k] {
+                                        '\'' | '[' => k = char_pattern_end(&chars, k).unwrap_or(k),
                                         '(' => group_depth += 1,
                                         ')' => {
                                             group_depth -= 1;
@@ -277,10 +497,12 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
                                     }
                                     k += 1;
                                 }
+                                // Include the closing paren.
+                                k += 1;
                             }
                             other => return Err(ParceMacroError(Box::new(s.clone()), format!("'{}' is not valid after =", other)))
                         }
-                        result.push(ParserPattern::AssignNamedField(name, Box::new(parser_pattern(s[j+1..k].to_string())?)));
+                        result.push(ParserPattern::AssignNamedField(name, Box::new(parser_pattern(slice(j+1, k))?)));
                         i = k - 1;
                     } else {
                         result.push(ParserPattern::BareNamedField(name));
@@ -290,18 +512,19 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
             }
             c if c.is_numeric() => {
                 let mut j = i + 1;
-                while j < s.len() && chars[j].is_numeric() {
+                while j < chars.len() && chars[j].is_numeric() {
                     j += 1;
                 }
-                let name = match s[i..j].parse() {
+                let name = match slice(i, j).parse() {
                     Ok(n) => n,
                     Err(_) => panic!("how even")
                 };
-                if j < s.len() && chars[j] == '=' {
+                if j < chars.len() && chars[j] == '=' {
                     let mut k = j + 1;
                     let mut group_depth: u32 = 0;
-                    while k < s.len() {
+                    while k < chars.len() {
                         match chars[k] {
+                            '\'' | '[' => k = char_pattern_end(&chars, k).unwrap_or(k),
                             '(' => group_depth += 1,
                             ')' => group_depth -= 1,
                             c if c.is_whitespace() && group_depth == 0 => break,
@@ -309,7 +532,7 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
                         }
                         k += 1;
                     }
-                    result.push(ParserPattern::AssignUnnamedField(name, Box::new(parser_pattern(s[j+1..k].to_string())?)));
+                    result.push(ParserPattern::AssignUnnamedField(name, Box::new(parser_pattern(slice(j+1, k))?)));
                     i = k - 1;
                 } else {
                     result.push(ParserPattern::BareUnnamedField(name));
@@ -320,14 +543,15 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
                 match result.pop() {
                     Some(prev) => {
                         let mut j = i + 1;
-                        while j < s.len() {
+                        while j < chars.len() {
                             match chars[j] {
                                 '}' => break,
                                 _ => j += 1
                             }
                         }
-                        if j != s.len() {
-                            let captures = COUNT_PARSER.captures(&s[i..j + 1]);
+                        if j != chars.len() {
+                            let range = slice(i, j + 1);
+                            let captures = COUNT_PARSER.captures(&range);
                             match captures {
                                 Some(cap) => {
                                     result.push(
@@ -337,7 +561,7 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
                                             match cap.get(2) {
                                                 Some(s) => RangeRuleMax::Some(s.as_str().parse().unwrap()),
                                                 None => {
-                                                    if s[i..j+1].contains(',') {
+                                                    if range.contains(',') {
                                                         RangeRuleMax::Infinite
                                                     } else {
                                                         RangeRuleMax::Fixed
@@ -355,6 +579,33 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
                     None => return Err(ParceMacroError(Box::new(s), "{} was applied to nothing".to_string()))
                 }
             }
+            '\'' => match char_pattern_end(&chars, i) {
+                Some(end) => {
+                    let mut literal = vec![];
+                    let mut j = i + 1;
+                    while j < end {
+                        if chars[j] == '\\' {
+                            j += 1;
+                        }
+                        literal.push(ParserPattern::Char(chars[j]));
+                        j += 1;
+                    }
+                    match literal.len() {
+                        0 => return Err(ParceMacroError(Box::new(s), "'' doesn't match anything".to_string())),
+                        1 => result.push(literal.remove(0)),
+                        _ => result.push(ParserPattern::And(literal))
+                    }
+                    i = end;
+                }
+                None => return Err(ParceMacroError(Box::new(s), "reached end of string before ' literal was closed".to_string()))
+            },
+            '[' => match char_pattern_end(&chars, i) {
+                Some(end) => {
+                    result.push(ParserPattern::Class(slice(i, end + 1)));
+                    i = end;
+                }
+                None => return Err(ParceMacroError(Box::new(s), "reached end of string before [] class was closed".to_string()))
+            },
             '.' => result.push(ParserPattern::Dot),
             '*' => match result.pop() {
                 Some(l) => result.push(ParserPattern::Star(Box::new(l))),
@@ -373,7 +624,7 @@ pub(crate) fn parser_pattern(s: String) -> Result<ParserPattern, ParceMacroError
         }
         i += 1;
     }
-    if result.len() == 0 {
+    if result.is_empty() {
         Err(ParceMacroError(Box::new(s), "this shouldn't be possible".to_string()))
     } else if result.len() == 1 {
         Ok(result.remove(0))
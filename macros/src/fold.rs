@@ -0,0 +1,92 @@
+//! Generates the Foldable impl of a rule, which rebuilds each variant with its rule fields passed
+//! through a folder. Only rules that own their data get one.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, format_ident};
+use crate::parser::{ParserPattern, VariantInfo};
+
+/// Names of the fields a pattern fills with other rules (instead of with text).
+fn rule_fields(pattern: &ParserPattern, unnamed: &mut Vec<usize>, named: &mut Vec<String>) {
+    use ParserPattern::*;
+    match pattern {
+        BareUnnamedField(n) => unnamed.push(*n),
+        BareNamedField(name) => named.push(name.clone()),
+        And(patterns) | Or(patterns) => {
+            for p in patterns {
+                rule_fields(p, unnamed, named);
+            }
+        }
+        Star(p) | Plus(p) | Question(p) | Range(p, _, _) => rule_fields(p, unnamed, named),
+        Lexeme(_) | Keyword(_, _) | Char(_) | Class(_) | Rule(_) | Dot | AssignUnnamedField(_, _) | AssignNamedField(_, _) => {}
+    }
+}
+
+/// The expression folding `value` of type `ty`. Boxes, vecs, options, and recovered fields are
+/// folded through.
+fn fold_value(ty: &syn::Type, value: TokenStream2) -> TokenStream2 {
+    if let syn::Type::Path(syn::TypePath { path, .. }) = ty {
+        if let Some(seg) = path.segments.last() {
+            if let syn::PathArguments::AngleBracketed(args) = &seg.arguments {
+                if let (1, Some(syn::GenericArgument::Type(inner))) = (args.args.len(), args.args.first()) {
+                    let folded = fold_value(inner, quote! { child });
+                    if seg.ident == "Box" {
+                        let folded = fold_value(inner, quote! { *#value });
+                        return quote! { Box::new(#folded) };
+                    } else if seg.ident == "Vec" {
+                        return quote! { #value.into_iter().map(|child| #folded).collect() };
+                    } else if seg.ident == "Option" || seg.ident == "Recovered" {
+                        return quote! { #value.map(|child| #folded) };
+                    }
+                }
+            }
+        }
+    }
+    quote! { folder.fold(#value) }
+}
+
+pub(crate) fn foldable(input: &syn::ItemEnum, variants: &[VariantInfo]) -> TokenStream2 {
+    let enum_ident = &input.ident;
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+
+    let arms = input.variants.iter().zip(variants).map(|(variant, info)| {
+        let ident = &variant.ident;
+        let (mut unnamed, mut named) = (vec![], vec![]);
+        rule_fields(&info.pattern, &mut unnamed, &mut named);
+        match &variant.fields {
+            syn::Fields::Unit => quote! { #enum_ident::#ident => #enum_ident::#ident, },
+            syn::Fields::Unnamed(fields) => {
+                let bindings: Vec<_> = (0..fields.unnamed.len()).map(|i| format_ident!("field_{}", i)).collect();
+                let values = fields.unnamed.iter().zip(&bindings).enumerate().map(|(i, (field, binding))| {
+                    if unnamed.contains(&i) {
+                        fold_value(&field.ty, quote! { #binding })
+                    } else {
+                        quote! { #binding }
+                    }
+                });
+                quote! { #enum_ident::#ident(#(#bindings),*) => #enum_ident::#ident(#(#values),*), }
+            }
+            syn::Fields::Named(fields) => {
+                let names: Vec<_> = fields.named.iter().map(|f| f.ident.clone().unwrap()).collect();
+                let values = fields.named.iter().zip(&names).map(|(field, name)| {
+                    if named.contains(&name.to_string()) {
+                        fold_value(&field.ty, quote! { #name })
+                    } else {
+                        quote! { #name }
+                    }
+                });
+                quote! { #enum_ident::#ident { #(#names),* } => #enum_ident::#ident { #(#names: #values),* }, }
+            }
+        }
+    });
+
+    quote! {
+        impl #impl_generics parce::parser::fold::Foldable for #enum_ident #ty_generics {
+            #[allow(unused_variables, clippy::redundant_closure)]
+            fn fold_children<F: parce::parser::fold::Fold + ?Sized>(self, folder: &mut F) -> Self {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    }
+}
@@ -5,7 +5,9 @@ use inflector::Inflector;
 use check_keyword::CheckKeyword;
 use std::collections::HashMap;
 use crate::common::*;
-use crate::discriminants::lexer_discriminant;
+use crate::discriminants::{is_constant_name, lexer_discriminant, replace_names};
+use syn::spanned::Spanned;
+use crate::overlap::overlaps;
 
 #[derive(Debug)]
 struct VariantInfo {
@@ -14,10 +16,33 @@ struct VariantInfo {
     pattern: String,
     fragment: bool,
     skip: bool,
-    set_mode: Option<String>
+    ignore_case: bool,
+    categories: Vec<String>,
+    set_mode: Option<String>,
+    gate: Option<Gate>,
+    /// The message of its `#[deprecated_syntax(...)]` attribute.
+    deprecated: Option<String>,
+    /// The lexers the lexeme is in, from `#[lexers(...)]`, or `None` if it's in all of them.
+    lexers: Option<Vec<String>>,
+    /// Whether the lexeme is `#[bom]` or `#[shebang]`, which are only matched at the start of the
+    /// input, before anything else.
+    prologue: bool
 }
 
-pub(crate) fn lexer(lexer_ident: Ident, mut input: syn::ItemEnum) -> Result<TokenStream2, ParceMacroError> {
+impl VariantInfo {
+    fn in_lexer(&self, lexer: &Ident) -> bool {
+        self.lexers.as_ref().is_none_or(|lexers| lexers.iter().any(|l| lexer == l))
+    }
+}
+
+/// The patterns of the `#[bom]` and `#[shebang]` lexemes, in the order they're matched.
+const PROLOGUE: [(&str, &str); 2] = [("bom", "'\u{feff}'"), ("shebang", "'#!' [^\r\n]*")];
+
+pub(crate) fn lexer(args: LexerArgs, mut input: syn::ItemEnum) -> Result<TokenStream2, ParceMacroError> {
+    let lexer_idents: Vec<Ident> = std::iter::once(args.ident).chain(args.also).collect();
+    let fingerprint = fingerprint(&input);
+    let original = input.clone();
+    let constants = take_pattern_constants(&mut input.attrs)?;
 
     let modes = if let Some(idents) = get_ident_list("modes", &input.attrs) {
         if idents.len() < 2 {
@@ -28,11 +53,26 @@ pub(crate) fn lexer(lexer_ident: Ident, mut input: syn::ItemEnum) -> Result<Toke
         vec![String::from("Default")]
     };
 
-    let visibility = input.vis.clone();
+    let visibility = args.vis.unwrap_or_else(|| input.vis.clone());
+    let lexer_ignore_case = has_attr("ignore_case", &input.attrs);
+    // The lexer enum derives these already, and deriving them twice doesn't compile.
+    let extra_derives: Vec<syn::Path> = args.derives.into_iter()
+        .filter(|path| !path.segments.last().is_some_and(|last| ["Debug", "Eq", "PartialEq", "Copy", "Clone"].iter().any(|d| last.ident == d)))
+        .collect();
 
     let mut variant_info = vec![];
+    let mut paths = vec![];
     let mut current_modes = vec![modes.first().unwrap().clone()];
     for variant in &mut input.variants {
+        let prologue = PROLOGUE.iter().find(|(attr, _)| has_attr(attr, &variant.attrs));
+        if let Some((attr, pattern)) = prologue {
+            if variant.discriminant.is_some() {
+                return Err(ParceMacroError(Box::new(variant.clone()), format!("#[{}] lexemes have a built-in pattern, and can't have their own", attr)));
+            }
+            if variant_info.iter().any(|info: &VariantInfo| info.prologue && info.pattern == *pattern) {
+                return Err(ParceMacroError(Box::new(variant.clone()), format!("only one lexeme can be #[{}]", attr)));
+            }
+        }
         let info = VariantInfo {
             modes: match get_ident_list("mode", &variant.attrs) {
                 Some(m) => {
@@ -47,8 +87,19 @@ pub(crate) fn lexer(lexer_ident: Ident, mut input: syn::ItemEnum) -> Result<Toke
                 None => current_modes.clone()
             },
             ident: variant.ident.clone(),
-            pattern: get_pattern(&variant)?,
+            pattern: match prologue {
+                Some((_, pattern)) => pattern.to_string(),
+                None => get_pattern(variant)?
+            },
             fragment: has_attr("frag", &variant.attrs),
+            ignore_case: lexer_ignore_case || has_attr("ignore_case", &variant.attrs),
+            categories: match get_ident_list("category", &variant.attrs) {
+                Some(list) if !list.is_empty() => list,
+                _ if has_attr("category", &variant.attrs) => {
+                    return Err(ParceMacroError(Box::new(variant.clone()), "category must be a list of names, like #[category(Keyword)]".to_string()));
+                }
+                _ => vec![]
+            },
             skip: {
                 let skip = get_attr_mut("skip", &mut variant.attrs);
                 if let Some(attr) = skip {
@@ -71,46 +122,89 @@ pub(crate) fn lexer(lexer_ident: Ident, mut input: syn::ItemEnum) -> Result<Toke
                     Some(result)
                 }
                 None => None
-            }
+            },
+            gate: take_gate(&mut variant.attrs)?,
+            deprecated: take_deprecated_syntax(&mut variant.attrs)?,
+            lexers: match get_ident_list("lexers", &variant.attrs) {
+                Some(list) => {
+                    if let Some(unknown) = list.iter().find(|l| !lexer_idents.iter().any(|ident| ident == *l)) {
+                        return Err(ParceMacroError(Box::new(variant.clone()), format!("lexer {} was not declared", unknown)));
+                    }
+                    Some(list)
+                }
+                None => None
+            },
+            prologue: prologue.is_some()
         };
         variant_info.push(info);
+        paths.extend(lexeme_paths(variant)?);
         variant.discriminant = None;
     }
 
+    // Constants from parce::pattern! are filled in one at a time. The macro a constant defines
+    // expands to this lexer again, with the constant's pattern added as an attribute.
+    let names: Vec<String> = variant_info.iter().map(|info| info.ident.to_string()).collect();
+    let constant = |name: &str| match names.iter().any(|n| n == name) {
+        true => None,
+        false => constants.iter().find(|(n, _)| n == name).map(|(_, pattern)| format!("({})", pattern))
+    };
+    for info in &mut variant_info {
+        for _ in 0..=constants.len() {
+            info.pattern = replace_names(&info.pattern, constant);
+        }
+        let mut unresolved = None;
+        replace_names(&info.pattern, |name| {
+            if unresolved.is_none() && is_constant_name(name) && !names.iter().any(|n| n == name) {
+                unresolved = Some(name.to_string());
+            }
+            None
+        });
+        match unresolved {
+            Some(name) if constant(&name).is_some() => {
+                return Err(ParceMacroError(Box::new(info.ident.clone()), format!("pattern constant {} refers to itself", name)));
+            }
+            Some(name) => {
+                let span = original.variants.iter()
+                    .find(|variant| variant.ident == info.ident)
+                    .and_then(|variant| variant.discriminant.as_ref())
+                    .map_or_else(proc_macro2::Span::call_site, |(_, expr)| expr.span());
+                let constant = Ident::new(&name, span);
+                let args = &args.tokens;
+                return Ok(quote! {
+                    #constant! { [#[parce::prelude::lexer(#args)]] #original }
+                });
+            }
+            None => {}
+        }
+    }
+
     let ident = input.ident.clone();
     let mode_idents: Vec<_> = modes.iter().map(|mode| format_ident!("{}", mode.to_class_case().into_safe())).collect();
 
     let mut pattern_matchers = vec![];
-    let mut statics: Vec<TokenStream2> = vec![];
-    let mut no_skip = vec![];
+    let mut skipped = vec![];
+    let mut output_lexemes = vec![];
     let mut mode_setters = vec![];
-    let mut mode_checks = HashMap::<String, TokenStream2>::new();
-    for mode in &modes {
-        mode_checks.insert(mode.clone(), quote! {});
-    }
+    // Each lexeme's check in each of its modes, which every lexer it's in puts together.
+    let mut mode_checks: Vec<(&VariantInfo, &String, TokenStream2)> = vec![];
     for info in &variant_info {
         let lexeme_ident = info.ident.clone();
         let fn_ident = format_ident!("{}", info.ident.to_string().to_snake_case().into_safe());
-        let (matcher, stat) = match gen_matchers(info.pattern.clone()) {
-            Ok((m, s)) => (m, s),
-            Err(e) => {
-                return Err(e);
-            }
-        };
+        let matcher = gen_matchers(info.pattern.clone(), info.ignore_case)?;
+        let matcher_doc = format!(" Every length `{}` = \"{}\" can match from `start`.", lexeme_ident, info.pattern);
         pattern_matchers.push(
             quote! {
-                fn #fn_ident(s: &str, mut start: usize) -> TinyVec<[usize;2]> {
+                #[doc = #matcher_doc]
+                #[allow(dead_code)]
+                fn #fn_ident<C: Cursor>(cursor: &mut C, mut start: usize) -> TinyVec<[usize;2]> {
                     #matcher
                 }
             }
         );
-        statics.push(stat);
-        if !info.fragment {
+        if !info.fragment && !info.prologue {
             for mode in &info.modes {
-                let acc = mode_checks[mode].clone();
-                mode_checks.insert(mode.clone(), quote! {
-                    #acc
-                    for length in #fn_ident(s, start) {
+                let check = quote! {
+                    for length in #fn_ident(&mut cursor, start) {
                         match longest {
                             Some((lexeme, longest_len)) if longest_len < length => {
                                 longest = Some((#ident::#lexeme_ident, length))
@@ -119,129 +213,389 @@ pub(crate) fn lexer(lexer_ident: Ident, mut input: syn::ItemEnum) -> Result<Toke
                             _ => {}
                         }
                     }
-                });
+                };
+                // Lexemes that aren't in the current version don't match, so others can instead.
+                let check = match &info.gate {
+                    Some(gate) => quote! {
+                        if parce::version::enabled(&#gate) {
+                            #check
+                        }
+                    },
+                    None => check
+                };
+                mode_checks.push((info, mode, check));
             }
         }
-        if !info.skip {
-            no_skip.push(quote! {#ident::#lexeme_ident});
+        if info.skip || info.prologue {
+            skipped.push(quote! {#ident::#lexeme_ident});
+        } else if !info.fragment {
+            output_lexemes.push(info);
         }
         if let Some(mode) = &info.set_mode {
-            let mode_ident = format_ident!("{}", mode);
-            mode_setters.push(
-                quote! {
-                    #ident::#lexeme_ident => self = #lexer_ident::#mode_ident,
+            mode_setters.push((lexeme_ident, format_ident!("{}", mode)));
+        }
+    }
+
+    // The prologue lexemes are tried once each, in a fixed order, before the input's first lexeme.
+    let prologue_checks: Vec<_> = PROLOGUE.iter().filter_map(|(_, pattern)| {
+        let info = variant_info.iter().find(|info| info.prologue && info.pattern == *pattern)?;
+        let lexeme_ident = &info.ident;
+        let fn_ident = format_ident!("{}", info.ident.to_string().to_snake_case().into_safe());
+        Some((info, quote! {
+            if let Some(&len) = #fn_ident(&mut cursor, start).iter().max() {
+                if keep_skipped {
+                    result.push(SpannedLexeme { data: #ident::#lexeme_ident, start, len });
                 }
-            )
+                self = parce::internal_prelude::Lexer::next_mode(self, #ident::#lexeme_ident);
+                start += len;
+            }
+        }))
+    }).collect();
+
+    let default_mode = format_ident!("{}", modes.first().unwrap().clone());
+
+    if variant_info.len() > u16::MAX as usize {
+        return Err(ParceMacroError(Box::new(ident.clone()), format!("lexers can have at most {} lexemes", u16::MAX)));
+    }
+    let kind_count = variant_info.len() as u16;
+    let kind_ids = 0..kind_count;
+
+    let mut literal_texts = vec![];
+    let mut literal_idents = vec![];
+    let mut folded_texts = vec![];
+    let mut folded_idents = vec![];
+    for info in variant_info.iter().filter(|info| !info.fragment && !info.prologue) {
+        if let LexerPattern::Literal(text) = lexer_discriminant(info.pattern.clone())? {
+            if info.ignore_case {
+                folded_texts.push(text.to_lowercase());
+                folded_idents.push(info.ident.clone());
+            }
+            if !literal_texts.contains(&text) {
+                literal_texts.push(text);
+                literal_idents.push(info.ident.clone());
+            }
         }
     }
+    let ignore_case_idents: Vec<_> = variant_info.iter().filter(|info| info.ignore_case).map(|info| &info.ident).collect();
+    let is_skipped = if skipped.is_empty() {
+        quote! { false }
+    } else {
+        quote! { matches!(self, #(#skipped)|*) }
+    };
+    let ignores_case = if ignore_case_idents.is_empty() {
+        quote! { false }
+    } else {
+        quote! { matches!(self, #(#ident::#ignore_case_idents)|*) }
+    };
+    let variant_idents: Vec<_> = variant_info.iter().map(|info| info.ident.clone()).collect();
 
-    let mut non_fragment_checks = vec![];
-    for (key, value) in mode_checks {
-        let mode_ident = format_ident!("{}", key);
-        non_fragment_checks.push(quote! {
-            #lexer_ident::#mode_ident => {
-                #value
+    // Categories are listed in the order they first appear, and each gets an `is_` predicate.
+    let mut categories: Vec<&String> = vec![];
+    for category in variant_info.iter().flat_map(|info| &info.categories) {
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    let lexeme_visibility = &input.vis;
+    let category_predicates = categories.iter().map(|category| {
+        let fn_ident = format_ident!("is_{}", category.to_snake_case());
+        let members = variant_info.iter().filter(|info| info.categories.contains(category)).map(|info| &info.ident);
+        let doc = format!(" Whether the lexeme was declared `#[category({})]`.", category);
+        quote! {
+            #[doc = #doc]
+            #[allow(dead_code)]
+            #lexeme_visibility fn #fn_ident(&self) -> bool {
+                matches!(self, #(#ident::#members)|*)
             }
-        });
+        }
+    });
+    let categorized: Vec<_> = variant_info.iter().filter(|info| !info.categories.is_empty()).collect();
+    let categorized_idents = categorized.iter().map(|info| &info.ident);
+    let categorized_names = categorized.iter().map(|info| &info.categories);
+    // Paired delimiters, from every #[pair(Opener, Closer)] on the enum.
+    let mut pairs = vec![];
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("pair")) {
+        let pair = attr.parse_args_with(syn::punctuated::Punctuated::<Ident, syn::Token![,]>::parse_terminated);
+        match pair.map(|pair| pair.into_iter().collect::<Vec<_>>()).as_deref() {
+            Ok([opener, closer]) => {
+                if let Some(unknown) = [opener, closer].into_iter().find(|lexeme| !names.iter().any(|name| *lexeme == name)) {
+                    return Err(ParceMacroError(Box::new(unknown.clone()), format!("lexeme {} was not declared", unknown)));
+                }
+                pairs.push(quote! { (#ident::#opener, #ident::#closer) });
+            }
+            _ => return Err(ParceMacroError(Box::new(attr.clone()), "pair must name an opening and a closing lexeme, like #[pair(LBrace, RBrace)]".to_string()))
+        }
     }
+    let deprecated: Vec<_> = variant_info.iter().filter(|info| info.deprecated.is_some()).collect();
+    let deprecated_idents = deprecated.iter().map(|info| &info.ident);
+    let deprecated_messages = deprecated.iter().filter_map(|info| info.deprecated.as_ref());
 
-    let default_mode = format_ident!("{}", modes.first().unwrap().clone());
+    // Paths to lexemes in the patterns are only checked by the compiler.
+    let path_check = match paths.is_empty() {
+        true => quote! {},
+        false => quote! { const _: &[#ident] = &[#(#paths),*]; }
+    };
 
-    let submission = format_ident!("{}ParserSubmission", lexer_ident);
+    // Each lexer gets its own enum of modes, and only lexes the lexemes that are in it.
+    let mut lexers = vec![];
+    for (i, lexer_ident) in lexer_idents.iter().enumerate() {
+        let overlap_matrix = if has_attr("overlap_matrix", &input.attrs) {
+            let mut patterns = HashMap::new();
+            for info in &variant_info {
+                patterns.insert(info.ident.to_string(), lexer_discriminant(info.pattern.clone())?);
+            }
+            let candidates: Vec<(String, Vec<String>)> = variant_info.iter()
+                .filter(|info| !info.fragment && !info.prologue && info.in_lexer(lexer_ident))
+                .map(|info| (info.ident.to_string(), info.modes.clone()))
+                .collect();
+            let entries = overlaps(&candidates, &patterns).into_iter().map(|overlap| {
+                let (first, second, example) = (overlap.first, overlap.second, overlap.example);
+                quote! {
+                    parce::lexer::LexemeOverlap {
+                        first: #first,
+                        second: #second,
+                        example: #example
+                    }
+                }
+            });
+            quote! {
+                /// Pairs of lexemes that can match the same input, generated by `#[overlap_matrix]`.
+                #[allow(dead_code)]
+                #visibility const OVERLAPS: &'static [parce::lexer::LexemeOverlap] = &[#(#entries),*];
+            }
+        } else {
+            quote! {}
+        };
 
-    Ok(quote! {
-        #[derive(parce::internal_prelude::RemoveLexerAttributes, Debug, Eq, PartialEq, Copy, Clone)]
-        #[allow(dead_code)]
-        #input
 
-        impl parce::internal_prelude::Lexeme for #ident {}
+        // With the inventory feature, parsers register themselves with their lexer through these, so the
+        // lexer can list its rules.
+        let submission = format_ident!("{}ParserSubmission", lexer_ident);
+        let (registration, registered_rules) = if cfg!(feature = "inventory") {
+            (quote! {
+                #[doc(hidden)]
+                #visibility struct #submission(pub &'static parce::parser::RuleTable<#ident>);
+                parce::internal_prelude::inventory::collect!(#submission);
+            }, quote! {
+                fn registered_rules() -> std::vec::Vec<&'static parce::report::RuleInfo> {
+                    parce::internal_prelude::inventory::iter::<#submission>
+                        .into_iter()
+                        .map(|submission| (submission.0.rule_info)())
+                        .collect()
+                }
+            })
+        } else {
+            (quote! {}, quote! {})
+        };
 
-        #visibility struct #submission(
-            pub core::any::TypeId,
-            pub fn(u32, u32, parce::internal_prelude::SpannedLexeme<#ident>) -> parce::internal_prelude::ArrayVec<[parce::internal_prelude::AutomatonCommand; 3]>,
-            pub fn(u32, u32) -> bool
-        );
-        parce::internal_prelude::inventory::collect!(#submission);
+        // Modes are visited in declaration order, so the expansion is the same every build.
+        let non_fragment_checks = modes.iter().map(|mode| {
+            let mode_ident = format_ident!("{}", mode);
+            let checks = mode_checks.iter()
+                .filter(|(info, m, _)| *m == mode && info.in_lexer(lexer_ident))
+                .map(|(_, _, check)| check);
+            quote! {
+                #lexer_ident::#mode_ident => {
+                    #(#checks)*
+                }
+            }
+        });
+        let prologue_checks = prologue_checks.iter().filter(|(info, _)| info.in_lexer(lexer_ident)).map(|(_, check)| check);
+        let mode_setters = mode_setters.iter().map(|(lexeme_ident, mode_ident)| quote! {
+            #ident::#lexeme_ident => #lexer_ident::#mode_ident,
+        });
+        let output_lexemes = output_lexemes.iter().filter(|info| info.in_lexer(lexer_ident)).map(|info| info.ident.to_string());
+        // The first lexer keeps the fingerprint it would have on its own.
+        let fingerprint = match i {
+            0 => quote! { #fingerprint },
+            _ => {
+                let name = lexer_ident.to_string();
+                quote! { parce::fingerprint::fnv1a(#fingerprint, #name.as_bytes()) }
+            }
+        };
 
-        #[derive(Debug, Eq, PartialEq, Copy, Clone)]
-        #visibility enum #lexer_ident {
-            #(#mode_idents),*
-        }
+        lexers.push(quote! {
+            #registration
 
-        impl Default for #lexer_ident {
-            fn default() -> Self {
-                #lexer_ident::#default_mode
+            #[derive(Debug, Eq, PartialEq, Copy, Clone #(, #extra_derives)*)]
+            #visibility enum #lexer_ident {
+                #(#mode_idents),*
             }
-        }
 
-        impl std::fmt::Display for #lexer_ident {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                match self {
-                    #(#lexer_ident::#mode_idents => write!(f, #modes)),*
+            impl #lexer_ident {
+                #[allow(dead_code)]
+                #visibility const fn new() -> Self {
+                    #lexer_ident::#default_mode
                 }
-            }
-        }
 
-        impl parce::internal_prelude::Lexer for #lexer_ident {
-            type Lexemes = #ident;
+                /// Lexes the input, leaving out skipped lexemes unless `keep_skipped` is set. The `#[bom]`
+                /// and `#[shebang]` lexemes are only looked for if the input is at its `beginning`.
+                fn lex_with<C: parce::internal_prelude::Cursor, B: parce::internal_prelude::LexemeBuffer<#ident>>(mut self, mut cursor: C, result: &mut B, keep_skipped: bool, beginning: bool) -> Result<(), parce::error::ParceError> {
+                    use parce::internal_prelude::*;
+                    use parce::error::{ParceError, ParceErrorInfo};
 
-            fn lex(mut self, s: &str) -> Result<Vec<parce::internal_prelude::SpannedLexeme<#ident>>, parce::error::ParceError> {
-                use parce::internal_prelude::*;
-                use parce::error::{ParceError, ParceErrorInfo};
+                    #(#pattern_matchers)*
 
-                fn dedup_tiny(tiny: &mut TinyVec<[usize; 2]>) {
-                    if let Some(mut i) = tiny.len().checked_sub(1) {
-                        while i > 0 {
-                            if tiny[i] == tiny[i - 1] {
-                                tiny.remove(i);
+                    let mut start = 0;
+                    if beginning {
+                        #(#prologue_checks)*
+                    }
+                    loop {
+                        cursor.rewind(start);
+                        if cursor.peek().is_none() {
+                            break;
+                        }
+                        let mut longest: Option<(#ident, usize)> = None;
+
+                        match self {
+                            #(#non_fragment_checks)*
+                        }
+
+                        match longest {
+                            Some((data, len)) if len > 0 => {
+                                if keep_skipped || !data.is_skipped() {
+                                    result.push(
+                                        SpannedLexeme {
+                                            data,
+                                            start,
+                                            len
+                                        }
+                                    );
+                                }
+                                self = parce::internal_prelude::Lexer::next_mode(self, data);
+                                start += len;
                             }
-                            i -= 1;
+                            _ => return Err(ParceError {
+                                input: cursor.input(),
+                                start,
+                                info: ParceErrorInfo::lex(self.to_string())
+                            })
                         }
                     }
+                    Ok(())
                 }
 
-                #(#pattern_matchers)*
-                lazy_static! {
-                    #(#statics)*
-                }
+                #overlap_matrix
+            }
 
-                let mut result = vec![];
-                let mut start = 0;
-                while start < s.len() {
-                    let mut longest: Option<(#ident, usize)> = None;
+            impl Default for #lexer_ident {
+                fn default() -> Self {
+                    #lexer_ident::new()
+                }
+            }
 
+            impl std::fmt::Display for #lexer_ident {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                     match self {
-                        #(#non_fragment_checks)*
+                        #(#lexer_ident::#mode_idents => write!(f, #modes)),*
                     }
+                }
+            }
 
-                    match longest {
-                        Some((data, len)) if len > 0 => {
-                            match data {
-                                #(#no_skip)|* => result.push(
-                                    SpannedLexeme {
-                                        data,
-                                        start,
-                                        len
-                                    }
-                                ),
-                                _ => {}
-                            }
-                            match data {
-                                #(#mode_setters)*
-                                _ => {}
-                            }
-                            start += len;
-                        }
-                        _ => return Err(ParceError {
-                            input: s.to_string(),
-                            start,
-                            info: ParceErrorInfo::lex(self.to_string())
-                        })
+            impl parce::internal_prelude::Lexer for #lexer_ident {
+                type Lexemes = #ident;
+
+                const LEXEMES: &'static [&'static str] = &[#(#output_lexemes),*];
+
+                const FINGERPRINT: u64 = #fingerprint;
+
+                #registered_rules
+
+                fn lex_cursor_into<C: parce::internal_prelude::Cursor, B: parce::internal_prelude::LexemeBuffer<#ident>>(self, cursor: C, result: &mut B) -> Result<(), parce::error::ParceError> {
+                    self.lex_with(cursor, result, false, true)
+                }
+
+                fn lex_all_cursor_into<C: parce::internal_prelude::Cursor, B: parce::internal_prelude::LexemeBuffer<#ident>>(self, cursor: C, result: &mut B) -> Result<(), parce::error::ParceError> {
+                    self.lex_with(cursor, result, true, true)
+                }
+
+                fn lex_rest_cursor_into<C: parce::internal_prelude::Cursor, B: parce::internal_prelude::LexemeBuffer<#ident>>(self, cursor: C, result: &mut B) -> Result<(), parce::error::ParceError> {
+                    self.lex_with(cursor, result, true, false)
+                }
+
+                fn next_mode(self, lexeme: #ident) -> Self {
+                    match lexeme {
+                        #(#mode_setters)*
+                        _ => self
                     }
                 }
-                Ok(result)
+            }
+        });
+    }
+
+    Ok(quote! {
+        #[derive(parce::internal_prelude::RemoveLexerAttributes, Debug, Eq, PartialEq, Copy, Clone)]
+        #[allow(dead_code)]
+        #input
+
+        #path_check
+
+        impl parce::internal_prelude::Lexeme for #ident {
+            const KIND_COUNT: u16 = #kind_count;
+            const PAIRS: &'static [(Self, Self)] = &[#(#pairs),*];
+
+            fn kind_id(&self) -> u16 {
+                *self as u16
+            }
+
+            fn from_kind_id(id: u16) -> Option<Self> {
+                match id {
+                    #(#kind_ids => Some(#ident::#variant_idents),)*
+                    _ => None
+                }
+            }
+
+            fn ignores_case(&self) -> bool {
+                #ignores_case
+            }
+
+            fn is_skipped(&self) -> bool {
+                #is_skipped
+            }
+
+            fn categories(&self) -> &'static [&'static str] {
+                match self {
+                    #(#ident::#categorized_idents => &[#(#categorized_names),*],)*
+                    #[allow(unreachable_patterns)]
+                    _ => &[]
+                }
+            }
+
+            fn deprecation(&self) -> Option<&'static str> {
+                match self {
+                    #(#ident::#deprecated_idents => Some(#deprecated_messages),)*
+                    #[allow(unreachable_patterns)]
+                    _ => None
+                }
             }
         }
+
+        impl #ident {
+            #(#category_predicates)*
+        }
+
+        impl std::str::FromStr for #ident {
+            type Err = parce::lexer::UnknownLiteral;
+
+            /// Finds the lexeme whose pattern is exactly the literal `s`.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    #(#literal_texts => Ok(#ident::#literal_idents),)*
+                    #(_ if s.to_lowercase() == #folded_texts => Ok(#ident::#folded_idents),)*
+                    _ => Err(parce::lexer::UnknownLiteral(s.to_string()))
+                }
+            }
+        }
+
+        impl std::convert::TryFrom<&str> for #ident {
+            type Error = parce::lexer::UnknownLiteral;
+
+            fn try_from(s: &str) -> Result<Self, Self::Error> {
+                s.parse()
+            }
+        }
+
+        #(#lexers)*
     })
 }
 
@@ -260,14 +614,16 @@ pub(crate) enum LexerPattern {
 }
 
 impl LexerPattern {
-    fn to_matcher(&self) -> (TokenStream2, TokenStream2) {
+    /// With `ignore_case`, literals and classes match characters in any case. Nested lexemes keep
+    /// their own setting.
+    fn to_matcher(&self, ignore_case: bool) -> TokenStream2 {
         use LexerPattern::*;
         // Heres the deal
         // Given a rule, produce a matcher
         // the matcher is an expression, that produces Some(len)
         // where len is the length of the match if it finds a match
         // or none it it doesn't
-        // match on the string s, starting at index start
+        // match on the cursor, starting at index start
         // start is mutable, you may need to mutate it, but
         // must be returned to its initial value before the matcher
         // exits. This is so that star/plus/question play nicely with
@@ -275,30 +631,56 @@ impl LexerPattern {
         // using ? produces return statements on intermediate failures
         // which we don't want
         match self {
+            Literal(s) if ignore_case => {
+                // The input can be a different length than the pattern, since some characters
+                // change length in UTF-8 when their case changes.
+                quote! {
+                    cursor.rewind(start);
+                    let mut matched = true;
+                    for expected in #s.chars() {
+                        match cursor.peek() {
+                            Some(c) if c == expected || c.to_lowercase().eq(expected.to_lowercase()) => cursor.advance(),
+                            _ => {
+                                matched = false;
+                                break;
+                            }
+                        }
+                    }
+                    if matched {
+                        tiny_vec!([usize;2] => cursor.mark() - start)
+                    } else {
+                        tiny_vec!([usize;2])
+                    }
+                }
+            }
             Literal(s) => {
                 let len = s.len();
-                (quote! {
-                    if s.len() >= start+#len && &s[start..start+#len] == #s {
+                quote! {
+                    cursor.rewind(start);
+                    let mut matched = true;
+                    for expected in #s.chars() {
+                        if cursor.peek() == Some(expected) {
+                            cursor.advance();
+                        } else {
+                            matched = false;
+                            break;
+                        }
+                    }
+                    if matched {
                         tiny_vec!([usize;2] => #len)
                     } else {
                         tiny_vec!([usize;2])
                     }
-                }, quote! {})
+                }
             }
             Lexeme(l) => {
                 let lexeme_fn_ident = format_ident!("{}", l.to_snake_case().into_safe());
-                (quote! {
-                    #lexeme_fn_ident(s, start)
-                }, quote! {})
+                quote! {
+                    #lexeme_fn_ident(cursor, start)
+                }
             }
             And(v) => {
-                let matchers_and_statics: Vec<(TokenStream2, TokenStream2)> = v.iter().map(|rule| rule.to_matcher()).collect();
-                let mut matchers = Vec::with_capacity(matchers_and_statics.len());
-                let mut statics = Vec::with_capacity(matchers_and_statics.len());
-                for (m, s) in matchers_and_statics {
-                    matchers.push(m);
-                    statics.push(s);
-                }
+                let matchers: Vec<TokenStream2> = v.iter().map(|rule| rule.to_matcher(ignore_case)).collect();
 
                 // pay attention now
                 let mut brute_force_it = quote! {
@@ -315,7 +697,7 @@ impl LexerPattern {
                         }
                     }
                 }
-                (quote! {
+                quote! {
                     let old_start = start;
                     let mut results = tiny_vec!([usize;2]);
                     {#brute_force_it}
@@ -323,17 +705,11 @@ impl LexerPattern {
                     dedup_tiny(&mut results);
                     start = old_start;
                     results
-                }, quote! { #(#statics)* })
+                }
             }
             Or(v) => {
-                let matchers_and_statics: Vec<(TokenStream2, TokenStream2)> = v.iter().map(|rule| rule.to_matcher()).collect();
-                let mut matchers = Vec::with_capacity(matchers_and_statics.len());
-                let mut statics = Vec::with_capacity(matchers_and_statics.len());
-                for (m, s) in matchers_and_statics {
-                    matchers.push(m);
-                    statics.push(s);
-                }
-                (quote! {
+                let matchers: Vec<TokenStream2> = v.iter().map(|rule| rule.to_matcher(ignore_case)).collect();
+                quote! {
                     let mut results = tiny_vec!([usize;2]);
                     #(
                         results.extend({#matchers});
@@ -341,53 +717,65 @@ impl LexerPattern {
                     results.sort_unstable();
                     dedup_tiny(&mut results);
                     results
-                }, quote! { #(#statics)* })
+                }
             }
             Class(s) => {
-                use std::collections::hash_map::DefaultHasher;
-                use std::hash::{Hash, Hasher};
-
-                let mut hasher = DefaultHasher::new();
-                s.hash(&mut hasher);
+                let s = &if ignore_case { format!("(?i){}", s) } else { s.clone() };
+                let ranges = class_ranges(s).expect("class was validated by the discriminant parser");
 
-                let static_ident = format_ident!("CLASS_STATIC_{}", hasher.finish());
-                (quote! {
-                    if s.len() > start && #static_ident.is_match(&s[start..start+1]) {
-                        tiny_vec!([usize;2] => 1)
-                    } else {
-                        tiny_vec!([usize;2])
+                // Membership is decided here, at compile time: a const bitmask for ASCII, and a sorted
+                // table of ranges for everything else, searched by class_contains.
+                let mut ascii_table: u128 = 0;
+                for &(first, last) in &ranges {
+                    for c in (first as u32)..=(last as u32).min(127) {
+                        ascii_table |= 1 << c;
                     }
-                }, quote! {
-                    static ref #static_ident: Regex = Regex::new(#s)
-                        .expect(&format!("{} is not a valid regex class", #s));
-                })
+                }
+                let (firsts, lasts): (Vec<char>, Vec<char>) = ranges.into_iter()
+                    .filter(|&(_, last)| !last.is_ascii())
+                    .map(|(first, last)| (first.max('\u{80}'), last))
+                    .unzip();
+                quote! {
+                    const ASCII_TABLE: u128 = #ascii_table;
+                    const NON_ASCII: &[(char, char)] = &[#((#firsts, #lasts)),*];
+                    cursor.rewind(start);
+                    match cursor.peek() {
+                        Some(c) if c.is_ascii() && ASCII_TABLE & (1 << c as u32) != 0 => {
+                            tiny_vec!([usize;2] => 1)
+                        }
+                        Some(c) if !c.is_ascii() && class_contains(NON_ASCII, c) => {
+                            tiny_vec!([usize;2] => c.len_utf8())
+                        }
+                        _ => tiny_vec!([usize;2])
+                    }
+                }
             }
             Star(r) => {
-                let (matcher, stat) = r.to_matcher();
+                let matcher = r.to_matcher(ignore_case);
                 let infinite = match_infinite(matcher);
-                (quote! {
+                quote! {
                     let old_start = start;
                     let mut results = tiny_vec!([usize;2] => 0);
                     let mut i = 0;
                     #infinite;
                     start = old_start;
                     results
-                }, stat)
+                }
             }
             Plus(r) => {
-                let (matcher, stat) = r.to_matcher();
+                let matcher = r.to_matcher(ignore_case);
                 let infinite = match_infinite(matcher);
-                (quote! {
+                quote! {
                     let old_start = start;
                     let mut results = tiny_vec!([usize;2]);
                     let mut i: i32 = -1;
                     #infinite
                     start = old_start;
                     results
-                }, stat)
+                }
             }
             Range(r, min, max) => {
-                let (matcher, stat) = r.to_matcher();
+                let matcher = r.to_matcher(ignore_case);
                 let mut require_min = quote! {results.push(start - old_start)};
                 for _ in 0..*min {
                     require_min = quote! {
@@ -437,7 +825,7 @@ impl LexerPattern {
                         }
                     },
                 };
-                (quote! {
+                quote! {
                     let old_start = start;
                     let mut results = tiny_vec!([usize;2]);
                     #require_min
@@ -446,25 +834,25 @@ impl LexerPattern {
                     #rest
                     start = old_start;
                     results
-                }, stat)
+                }
             }
             Question(r) => {
-                let (matcher, stat) = r.to_matcher();
-                (quote! {
+                let matcher = r.to_matcher(ignore_case);
+                quote! {
                     let mut results = {#matcher};
                     results.insert(0, 0);
                     dedup_tiny(&mut results);
                     results
-                }, stat)
+                }
             }
             Dot => {
-                (quote! {
-                    if s.len() > start {
-                        tiny_vec!([usize;2] => 1)
-                    } else {
-                        tiny_vec!([usize;2])
+                quote! {
+                    cursor.rewind(start);
+                    match cursor.peek() {
+                        Some(c) => tiny_vec!([usize;2] => c.len_utf8()),
+                        None => tiny_vec!([usize;2])
                     }
-                }, quote! {})
+                }
             }
         }
     }
@@ -491,7 +879,51 @@ fn match_infinite(matcher: TokenStream2) -> TokenStream2 {
     }
 }
 
-fn gen_matchers(s: String) -> Result<(TokenStream2, TokenStream2), ParceMacroError> {
+fn gen_matchers(s: String, ignore_case: bool) -> Result<TokenStream2, ParceMacroError> {
     let rule = lexer_discriminant(s)?;
-    Ok(rule.to_matcher())
+    Ok(rule.to_matcher(ignore_case))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(args: &str, input: &str) -> String {
+        let args: LexerArgs = syn::parse_str(args).unwrap();
+        let input: syn::ItemEnum = syn::parse_str(input).unwrap();
+        match lexer(args, input) {
+            Ok(tokens) => tokens.to_string(),
+            Err(ParceMacroError(_, message)) => panic!("{}", message)
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let input = r#"
+            #[modes(Outside, Inside, Comment)]
+            enum Lexemes {
+                Word = "[a-z]+",
+                #[set_mode(Inside)] Open = '(',
+                #[mode(Inside)] Number = "[0-9]+",
+                #[set_mode(Comment)] Hash = '#',
+                #[mode(Comment)] Text = "[^ ]+",
+                #[mode(Outside, Inside, Comment)] #[skip] Space = ' '
+            }
+        "#;
+        let first = expand("MyLexer", input);
+        for _ in 0..8 {
+            assert_eq!(expand("MyLexer", input), first);
+        }
+        assert!(!first.contains("Regex"));
+    }
+
+    #[test]
+    fn class_tables() {
+        assert_eq!(class_ranges("[c-ea]"), Some(vec![('a', 'a'), ('c', 'e')]));
+        assert_eq!(class_ranges("[a]"), Some(vec![('a', 'a')]));
+        assert_eq!(class_ranges("(?i)[a]"), Some(vec![('A', 'A'), ('a', 'a')]));
+        assert_eq!(class_ranges(r"[^\x00-\x{10FFFF}]"), Some(vec![]));
+        assert_eq!(class_ranges("[z-a]"), None);
+        assert_eq!(class_ranges("[ab]+"), None);
+    }
 }
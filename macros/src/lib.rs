@@ -2,6 +2,14 @@
 //! Documentation for these macros are placed on the re-exports in the main crate,
 //! because they generate code that depends on the main crate, meaning doc-tests cannot
 //! be run here.
+//!
+//! The expansion only depends on the input: rules, routes, states, and generated names are numbered
+//! in declaration order, and nothing is iterated in hash order, so every build produces the same
+//! code. The `deterministic` tests check this.
+//!
+//! The generated functions, tables, and routes are labelled with doc attributes naming the rule,
+//! production, and pattern they come from, so the output of `cargo expand` can be read against the
+//! grammar.
 
 use proc_macro::{TokenStream};
 use syn::parse_macro_input;
@@ -9,37 +17,41 @@ use proc_macro_error::{proc_macro_error, abort};
 use quote::quote;
 
 mod lexer;
+#[cfg(feature = "parser")]
 mod parser;
 mod common;
 mod discriminants;
+#[cfg(feature = "parser")]
+mod analysis;
+mod overlap;
+mod tokens;
+#[cfg(feature = "parser")]
+mod fold;
+#[cfg(feature = "parser")]
+mod construct;
+#[cfg(feature = "parser")]
+mod validate;
 
 use common::*;
 
 #[proc_macro_error]
 #[proc_macro_attribute]
 pub fn lexer(args: TokenStream, input: TokenStream) -> TokenStream {
-    let args = parse_macro_input!(args as syn::Meta);
-    let lexer_ident = match get_lexer_ident(&args) {
-        Some(id) => id,
-        None => abort!(args, "lexer name must be specified")
-    };
+    let args = parse_macro_input!(args as LexerArgs);
     let input = parse_macro_input!(input as syn::ItemEnum);
-    match lexer::lexer(lexer_ident, input) {
+    match lexer::lexer(args, input) {
         Ok(s) => s.into(),
         Err(ParceMacroError(who, message)) => abort!(who, message)
     }
 }
 
+#[cfg(feature = "parser")]
 #[proc_macro_error]
 #[proc_macro_attribute]
 pub fn parser(args: TokenStream, input: TokenStream) -> TokenStream {
-    let args = parse_macro_input!(args as syn::Meta);
-    let lexer_ident = match get_lexer_path(&args) {
-        Some(id) => id,
-        None => abort!(args, "lexer name must be specified")
-    };
+    let args = parse_macro_input!(args as ParserArgs);
     let input = parse_macro_input!(input as syn::ItemEnum);
-    match parser::parser(lexer_ident, input) {
+    match parser::parser(args, input) {
         Ok(s) => s.into(),
         Err(ParceMacroError(who, message)) => abort!(who, message)
     }
@@ -49,7 +61,7 @@ pub fn parser(args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// Its a little hacky, I know, but its simpler than manually removing all of these attributes
 /// in the main macro.
-#[proc_macro_derive(RemoveLexerAttributes, attributes(skip, frag, set_mode, mode, modes))]
+#[proc_macro_derive(RemoveLexerAttributes, attributes(skip, frag, set_mode, mode, modes, lexers, overlap_matrix, ignore_case, category, bom, shebang, pair))]
 pub fn lex_attributes(_input: TokenStream) -> TokenStream {
     (quote! {}).into()
 }
@@ -0,0 +1,245 @@
+//! Finds pairs of lexemes that can match the same input, for the `#[overlap_matrix]` lexer attribute.
+//!
+//! Each lexeme's pattern is compiled to a small NFA, and pairs of NFAs are run side by side over a
+//! sample alphabet (printable ASCII, whitespace, and every character used in a literal) to find the
+//! shortest string both accept. Character classes are only tested against that alphabet, so overlaps
+//! that can only happen on other unicode characters aren't found.
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use crate::common::{RangeRuleMax, class_ranges};
+use crate::lexer::LexerPattern;
+
+/// Stop searching a pair after visiting this many states, to keep compile times bounded.
+const SEARCH_LIMIT: usize = 20_000;
+
+enum Edge {
+    Char(char),
+    Class(Vec<(char, char)>),
+    Any
+}
+
+impl Edge {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Edge::Char(e) => *e == c,
+            Edge::Class(ranges) => ranges.iter().any(|&(first, last)| first <= c && c <= last),
+            Edge::Any => true
+        }
+    }
+}
+
+#[derive(Default)]
+struct Nfa {
+    /// Outgoing transitions of each state. `None` is an epsilon transition.
+    states: Vec<Vec<(Option<Edge>, usize)>>,
+    accept: usize
+}
+
+impl Nfa {
+    fn add_state(&mut self) -> usize {
+        self.states.push(vec![]);
+        self.states.len() - 1
+    }
+
+    fn link(&mut self, from: usize, edge: Option<Edge>, to: usize) {
+        self.states[from].push((edge, to));
+    }
+
+    /// Adds the pattern starting at state `from`, and returns the state it ends at. Returns `None`
+    /// if the pattern is recursive, which an NFA can't represent.
+    fn build(&mut self, pattern: &LexerPattern, from: usize, lexemes: &HashMap<String, LexerPattern>, stack: &mut Vec<String>) -> Option<usize> {
+        use LexerPattern::*;
+
+        Some(match pattern {
+            Literal(s) => {
+                let mut at = from;
+                for c in s.chars() {
+                    let next = self.add_state();
+                    self.link(at, Some(Edge::Char(c)), next);
+                    at = next;
+                }
+                at
+            }
+            Class(s) => {
+                let next = self.add_state();
+                self.link(from, Some(Edge::Class(class_ranges(s)?)), next);
+                next
+            }
+            Dot => {
+                let next = self.add_state();
+                self.link(from, Some(Edge::Any), next);
+                next
+            }
+            Lexeme(name) => {
+                if stack.contains(name) {
+                    return None;
+                }
+                stack.push(name.clone());
+                let end = self.build(lexemes.get(name)?, from, lexemes, stack)?;
+                stack.pop();
+                end
+            }
+            And(ps) => {
+                let mut at = from;
+                for p in ps {
+                    at = self.build(p, at, lexemes, stack)?;
+                }
+                at
+            }
+            Or(ps) => {
+                let end = self.add_state();
+                for p in ps {
+                    let start = self.add_state();
+                    self.link(from, None, start);
+                    let branch_end = self.build(p, start, lexemes, stack)?;
+                    self.link(branch_end, None, end);
+                }
+                end
+            }
+            Star(p) => self.star(p, from, lexemes, stack)?,
+            Plus(p) => {
+                let once = self.build(p, from, lexemes, stack)?;
+                self.star(p, once, lexemes, stack)?
+            }
+            Question(p) => {
+                let end = self.build(p, from, lexemes, stack)?;
+                self.link(from, None, end);
+                end
+            }
+            Range(p, min, max) => {
+                let mut at = from;
+                for _ in 0..*min {
+                    at = self.build(p, at, lexemes, stack)?;
+                }
+                match max {
+                    RangeRuleMax::Fixed => at,
+                    RangeRuleMax::Infinite => self.star(p, at, lexemes, stack)?,
+                    RangeRuleMax::Some(max) => {
+                        let end = self.add_state();
+                        self.link(at, None, end);
+                        for _ in *min..*max {
+                            at = self.build(p, at, lexemes, stack)?;
+                            self.link(at, None, end);
+                        }
+                        end
+                    }
+                }
+            }
+        })
+    }
+
+    fn star(&mut self, pattern: &LexerPattern, from: usize, lexemes: &HashMap<String, LexerPattern>, stack: &mut Vec<String>) -> Option<usize> {
+        let hub = self.add_state();
+        self.link(from, None, hub);
+        let start = self.add_state();
+        self.link(hub, None, start);
+        let end = self.build(pattern, start, lexemes, stack)?;
+        self.link(end, None, hub);
+        Some(hub)
+    }
+
+    fn closure(&self, mut set: BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut todo: Vec<usize> = set.iter().copied().collect();
+        while let Some(state) = todo.pop() {
+            for (edge, to) in &self.states[state] {
+                if edge.is_none() && set.insert(*to) {
+                    todo.push(*to);
+                }
+            }
+        }
+        set
+    }
+
+    fn step(&self, set: &BTreeSet<usize>, c: char) -> BTreeSet<usize> {
+        let mut next = BTreeSet::new();
+        for state in set {
+            for (edge, to) in &self.states[*state] {
+                if let Some(edge) = edge {
+                    if edge.matches(c) {
+                        next.insert(*to);
+                    }
+                }
+            }
+        }
+        self.closure(next)
+    }
+}
+
+/// Compiles a lexeme to an NFA, or returns `None` if it is recursive.
+fn compile(name: &str, lexemes: &HashMap<String, LexerPattern>) -> Option<Nfa> {
+    let mut nfa = Nfa::default();
+    let start = nfa.add_state();
+    nfa.accept = nfa.build(lexemes.get(name)?, start, lexemes, &mut vec![name.to_string()])?;
+    Some(nfa)
+}
+
+/// Returns the shortest non-empty string both NFAs accept, if there is one.
+fn shortest_common(a: &Nfa, b: &Nfa, alphabet: &[char]) -> Option<String> {
+    let start = (a.closure(BTreeSet::from([0])), b.closure(BTreeSet::from([0])));
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([(start, String::new())]);
+    while let Some(((sa, sb), text)) = queue.pop_front() {
+        if !text.is_empty() && sa.contains(&a.accept) && sb.contains(&b.accept) {
+            return Some(text);
+        }
+        if seen.len() > SEARCH_LIMIT || !seen.insert((sa.clone(), sb.clone())) {
+            continue;
+        }
+        for c in alphabet {
+            let (na, nb) = (a.step(&sa, *c), b.step(&sb, *c));
+            if !na.is_empty() && !nb.is_empty() {
+                let mut next = text.clone();
+                next.push(*c);
+                queue.push_back(((na, nb), next));
+            }
+        }
+    }
+    None
+}
+
+fn literal_chars(pattern: &LexerPattern, alphabet: &mut BTreeSet<char>) {
+    use LexerPattern::*;
+
+    match pattern {
+        Literal(s) => alphabet.extend(s.chars()),
+        And(ps) | Or(ps) => ps.iter().for_each(|p| literal_chars(p, alphabet)),
+        Star(p) | Plus(p) | Question(p) | Range(p, _, _) => literal_chars(p, alphabet),
+        Class(_) | Lexeme(_) | Dot => {}
+    }
+}
+
+/// One entry of the matrix: two lexemes (in declaration order), and an example both match.
+pub(crate) struct Overlap {
+    pub first: String,
+    pub second: String,
+    pub example: String
+}
+
+/// Finds every pair of `candidates` that can match the same string. `lexemes` must contain the
+/// patterns of all lexemes, including fragments, so nested lexemes can be expanded.
+pub(crate) fn overlaps(candidates: &[(String, Vec<String>)], lexemes: &HashMap<String, LexerPattern>) -> Vec<Overlap> {
+    let mut alphabet: BTreeSet<char> = (' '..='~').collect();
+    alphabet.extend(['\t', '\n', '\r']);
+    for pattern in lexemes.values() {
+        literal_chars(pattern, &mut alphabet);
+    }
+    let alphabet: Vec<char> = alphabet.into_iter().collect();
+
+    let nfas: Vec<Option<Nfa>> = candidates.iter().map(|(name, _)| compile(name, lexemes)).collect();
+    let mut result = vec![];
+    for i in 0..candidates.len() {
+        for j in i+1..candidates.len() {
+            let shares_mode = candidates[i].1.iter().any(|m| candidates[j].1.contains(m));
+            if let (true, Some(a), Some(b)) = (shares_mode, &nfas[i], &nfas[j]) {
+                if let Some(example) = shortest_common(a, b, &alphabet) {
+                    result.push(Overlap {
+                        first: candidates[i].0.clone(),
+                        second: candidates[j].0.clone(),
+                        example
+                    });
+                }
+            }
+        }
+    }
+    result
+}
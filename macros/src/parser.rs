@@ -4,57 +4,145 @@ use super::ParceMacroError;
 use crate::common::*;
 use quote::{quote, format_ident};
 use syn::Path;
+use syn::visit_mut::VisitMut;
 use std::iter::FromIterator;
+use std::cell::RefCell;
+use std::rc::Rc;
 use crate::common::RangeRuleMax;
-use crate::discriminants::parser_pattern;
+use crate::discriminants::{parser_pattern, split_cut};
+use crate::analysis::{check_deterministic, check_termination, describe, expectation_tables, StateExpectation};
 
-struct VariantInfo {
-    ident: Ident,
-    pattern: ParserPattern,
-    fields: VariantFields
+pub(crate) struct VariantInfo {
+    pub ident: Ident,
+    pub pattern: ParserPattern,
+    /// The part of the pattern before its cut (`!`), if it has one. The cut itself is removed from
+    /// [pattern](Self::pattern).
+    pub cut: Option<ParserPattern>,
+    pub fields: VariantFields,
+    /// The fields with a `Recovered` type, by name, or by index for unnamed fields.
+    pub recovered: Vec<String>,
+    /// Every other rule spawned by the whole enum so far, shared between its variants. A spawn
+    /// command names a rule by its place in this list, which becomes the table's `uses`.
+    pub uses: Rc<RefCell<Vec<TokenStream2>>>
 }
 
-enum VariantFields {
+impl VariantInfo {
+    /// The number a spawn command uses for `rule`: one more than its index in [uses](Self::uses).
+    /// 0 stands for the rule being generated.
+    pub fn use_rule(&self, rule: &TokenStream2) -> u16 {
+        let mut uses = self.uses.borrow_mut();
+        let index = match uses.iter().position(|r| r.to_string() == rule.to_string()) {
+            Some(index) => index,
+            None => {
+                uses.push(rule.clone());
+                uses.len() - 1
+            }
+        };
+        index as u16 + 1
+    }
+}
+
+pub(crate) enum VariantFields {
     Unit,
     Unnamed(Vec<syn::Type>),
     Named(Vec<(Ident, syn::Type)>)
 }
 
 impl VariantFields {
-    fn search_named(&self, name: &String) -> Result<syn::Type, ParceMacroError> {
-        return match self {
+    pub fn search_named(&self, name: &String) -> Result<syn::Type, ParceMacroError> {
+        match self {
             VariantFields::Named(v) => {
                 for (id, ty) in v {
-                    if id.to_string() == name.to_string() {
+                    if *id == *name {
                         return Ok(ty.clone())
                     }
                 }
                 Err(ParceMacroError(Box::new(name.clone()), format!("field {} was not found in variant", name)))
             }
-            _ => Err(ParceMacroError(Box::new(name.clone()), format!("variant does not have named fields")))
+            _ => Err(ParceMacroError(Box::new(name.clone()), "variant does not have named fields".to_string()))
         }
     }
 }
 
-pub(crate) fn parser(lexer: syn::Path, mut input: syn::ItemEnum) -> Result<TokenStream2, ParceMacroError> {
+pub(crate) fn parser(args: ParserArgs, mut input: syn::ItemEnum) -> Result<TokenStream2, ParceMacroError> {
+    let lexer = args.lexer;
+    let enum_ident = input.ident.clone();
+    let fingerprint = fingerprint(&input);
+
+    // Borrowed rules implement Parseable on the 'static version of the enum (so they have a TypeId),
+    // and hand out the version borrowing the input from assemble.
+    let lifetime = match (args.borrowed, input.generics.params.len(), input.generics.lifetimes().next()) {
+        (false, 0, _) => None,
+        (false, _, _) => return Err(ParceMacroError(
+            Box::new(input.generics.clone()),
+            "rules can't have generic parameters. To borrow fields from the input, use #[parser(Lexer, borrowed)]".to_string()
+        )),
+        (true, 1, Some(def)) => Some(def.lifetime.clone()),
+        (true, _, _) => return Err(ParceMacroError(
+            Box::new(enum_ident.clone()),
+            "borrowed rules must have exactly one lifetime parameter (for the input text), and no other generics".to_string()
+        ))
+    };
+    let (self_ty, output_ty) = match &lifetime {
+        Some(_) => (quote! { #enum_ident<'static> }, quote! { #enum_ident<'t> }),
+        None => (quote! { #enum_ident }, quote! { Self })
+    };
+    let field_type = |ty: syn::Type| -> Result<syn::Type, ParceMacroError> {
+        let mut ty = unwrap_type(ty)?;
+        if let Some(lifetime) = &lifetime {
+            StaticLifetime(lifetime).visit_type_mut(&mut ty);
+        }
+        Ok(ty)
+    };
+
+    let rule_sync = take_sync(&mut input.attrs)?;
+    let rule_no_skip = take_no_skip(&mut input.attrs);
+    let rule_resolve = take_resolve(&mut input.attrs)?;
+    let deterministic = take_deterministic(&mut input.attrs);
+    let inline_rule = take_inline_rule(&mut input.attrs);
+    let mut resolves = vec![];
+    let mut no_skips = vec![];
+    let mut deprecations = vec![];
+    let mut gates = vec![];
     let mut variants = vec![];
+    let mut sources = vec![];
+    let mut paths = vec![];
+    let uses = Rc::new(RefCell::new(vec![]));
     for variant in &mut input.variants {
+        sources.push(get_pattern(variant)?);
+        paths.extend(lexeme_paths(variant)?);
+        // Recovery skips a broken rule without knowing which production it was, so only the
+        // rule's anchors are ever used.
+        if let Some(attr) = get_attr("sync", &variant.attrs) {
+            return Err(ParceMacroError(Box::new(attr.clone()), "#[sync(...)] goes on the whole rule, not on a production".to_string()));
+        }
+        resolves.push(format_ident!("{}", take_resolve(&mut variant.attrs)?.or(rule_resolve).unwrap_or("Unspecified")));
+        no_skips.push(take_no_skip(&mut variant.attrs) || rule_no_skip);
+        deprecations.push(take_deprecated_syntax(&mut variant.attrs)?);
+        gates.push(take_gate(&mut variant.attrs)?);
+        let (pattern, cut) = split_cut(&get_pattern(variant)?)?;
         variants.push(
             VariantInfo {
-                pattern: parser_pattern(get_pattern(&variant)?)?,
+                pattern: parser_pattern(pattern)?,
+                cut: cut.map(parser_pattern).transpose()?,
                 ident: variant.ident.clone(),
+                uses: uses.clone(),
+                recovered: variant.fields.iter().enumerate()
+                    .filter(|(_, field)| is_recovered(&field.ty))
+                    .map(|(i, field)| field.ident.as_ref().map(|id| id.to_string()).unwrap_or_else(|| i.to_string()))
+                    .collect(),
                 fields: match variant.fields.clone() {
                     syn::Fields::Unnamed(syn::FieldsUnnamed {unnamed, ..}) => {
                         let mut fields = Vec::with_capacity(unnamed.len());
                         for field in unnamed {
-                            fields.push(unwrap_type(field.ty.clone())?);
+                            fields.push(field_type(field.ty.clone())?);
                         }
                         VariantFields::Unnamed(fields)
                     }
                     syn::Fields::Named(syn::FieldsNamed {named, ..}) => {
                         let mut fields = Vec::with_capacity(named.len());
                         for field in named {
-                            fields.push((field.ident.clone().unwrap(), unwrap_type(field.ty.clone())?));
+                            fields.push((field.ident.clone().unwrap(), field_type(field.ty.clone())?));
                         }
                         VariantFields::Named(fields)
                     }
@@ -65,7 +153,71 @@ pub(crate) fn parser(lexer: syn::Path, mut input: syn::ItemEnum) -> Result<Token
         variant.discriminant = None;
     }
 
-    let enum_ident = input.ident.clone();
+    check_termination(&enum_ident, &variants)?;
+    if let (true, Some(gated)) = (inline_rule, variants.iter().zip(&gates).find(|(_, gate)| gate.is_some())) {
+        return Err(ParceMacroError(
+            Box::new(gated.0.ident.clone()),
+            "productions of an #[inline_rule] can't have #[since], #[cfg_grammar], or #[flag]".to_string()
+        ));
+    }
+    let inline = match inline_rule {
+        true => inline_table(&enum_ident, &variants, &no_skips)?,
+        false => vec![]
+    };
+    if deterministic {
+        check_deterministic(&enum_ident, &variants)?;
+    }
+    // Folding can swap in nodes of any type by downcasting, which needs them to be 'static.
+    let foldable = match lifetime {
+        Some(_) => quote! {},
+        None => crate::fold::foldable(&input, &variants)
+    };
+    let validate = crate::validate::validate(&input, &variants, &lexer);
+    let constructors = match args.constructors {
+        true => crate::construct::constructors(&input),
+        false => quote! {}
+    };
+
+    let enum_name = enum_ident.to_string();
+    let mut production_infos = vec![];
+    for (((variant, resolve), no_skip), deprecated) in variants.iter().zip(&resolves).zip(&no_skips).zip(&deprecations) {
+        let name = variant.ident.to_string();
+        let pattern = describe(&variant.pattern, variant)?;
+        // The production has passed its cut once it reaches the state after everything before it.
+        let cut = match &variant.cut {
+            Some(before) => {
+                let state = before.to_matchers(&enum_ident, &lexer, variant, 0, variants.len(), EndBehavior::NotLast)?.states as u32;
+                quote! { Some(#state) }
+            }
+            None => quote! { None }
+        };
+        let deprecated = match deprecated {
+            Some(message) => quote! { Some(#message) },
+            None => quote! { None }
+        };
+        production_infos.push(quote! {
+            parce::report::Production {
+                name: #name,
+                pattern: #pattern,
+                resolve: parce::report::Resolve::#resolve,
+                no_skip: #no_skip,
+                cut: #cut,
+                deprecated: #deprecated
+            }
+        });
+    }
+    // Naming every sync lexeme as a variant makes typos a compile error, pointing at the typo.
+    let sync_lexemes = &rule_sync;
+    let is_sync = match rule_sync.is_empty() {
+        true => quote! {},
+        false => quote! {
+            fn is_sync(lexeme: <#lexer as parce::internal_prelude::Lexer>::Lexemes) -> bool {
+                #(lexeme == <#lexer as parce::internal_prelude::Lexer>::Lexemes::#rule_sync)||*
+            }
+        }
+    };
+    let rule_sync = rule_sync.iter().map(|l| l.to_string());
+
     let num_productions = variants.len();
     let num_prod_index = syn::Index::from(num_productions);
 
@@ -73,32 +225,64 @@ pub(crate) fn parser(lexer: syn::Path, mut input: syn::ItemEnum) -> Result<Token
     let mut end_route_matchers = vec![];
     let mut route_assemblers = vec![];
     let mut next_route = num_productions;
-    for (i,variant) in variants.into_iter().enumerate() {
+    let mut main_expectations = vec![];
+    let mut extra_expectations = vec![];
+    // Whether each route repeats, in the same order as the expectations.
+    let mut repeats = vec![];
+    let mut extra_repeats = vec![];
+    let mut route_infos = vec![];
+    let mut extra_route_infos = vec![];
+    let mut recovery = vec![];
+    // Gated productions are left out of shared prefixes, so the parser can drop them by route.
+    let unshared: Vec<bool> = no_skips.iter().zip(&gates).map(|(&no_skip, gate)| no_skip || gate.is_some()).collect();
+    let prefixes = shared_prefixes(&variants, &unshared);
+    for (i, (variant, source)) in variants.iter().zip(&sources).enumerate() {
         let MatcherOutput {
             main_route,
             end_route,
             extra_routes,
             assembler,
             produced,
+            expectations,
             ..
-        } = variant.pattern.to_matchers(&enum_ident, &lexer, &variant, 0, next_route, EndBehavior::Last)?;
+        } = variant.pattern.to_matchers(&enum_ident, &lexer, variant, 0, next_route, EndBehavior::Last)?;
 
+        // Routes are labelled with doc attributes, so they show up as comments in `cargo expand`.
+        let main_doc = format!(" Route {}: `{}::{}` = \"{}\"", i, enum_ident, variant.ident, source);
+
+        // A production that declared victory without dying (because a trailing `?` or `*` could
+        // still match more) is left one state past its end, where it has nothing left to match.
         let iu32 = syn::Index::from(i);
         route_matchers.push(quote! {
+            #[doc = #main_doc]
             #iu32 => match state {
                 #main_route
-                other => panic!("state {} out of bounds", other)
+                _ => array_vec!([AutomatonCommand; 3] => Die)
             }
         });
 
         end_route_matchers.push(quote! {
+            #[doc = #main_doc]
             #iu32 => match state {
                 #end_route
-                other => panic!("state {} out of bounds", other)
+                _ => false
             }
         });
 
-        for (extra_route, extra_end_route, cycle) in extra_routes {
+        main_expectations.push(expectations);
+        repeats.push(false);
+        route_infos.push(quote! {
+            parce::report::Route { production: #i, pattern: #source }
+        });
+        for (extra_route, extra_end_route, cycle, expectations, part) in extra_routes {
+            if part == RECOVERY_PART {
+                recovery.push(next_route as u32);
+            }
+            extra_expectations.push(expectations);
+            extra_repeats.push(cycle.is_some());
+            extra_route_infos.push(quote! {
+                parce::report::Route { production: #i, pattern: #part }
+            });
             let next_u32 = syn::Index::from(next_route);
             let modulus = match cycle {
                 Some(n) => {
@@ -107,13 +291,16 @@ pub(crate) fn parser(lexer: syn::Path, mut input: syn::ItemEnum) -> Result<Token
                 },
                 None => quote! {}
             };
+            let extra_doc = format!(" Route {}: \"{}\" in `{}::{}`", next_route, part, enum_ident, variant.ident);
             let result = quote! {
+                #[doc = #extra_doc]
                 #next_u32 => match state #modulus {
                     #extra_route
                     other => panic!("state {} out of bounds", other)
                 }
             };
             let end_result = quote! {
+                #[doc = #extra_doc]
                 #next_u32 => match state #modulus {
                     #extra_end_route
                     other => panic!("state {} out of bounds", other)
@@ -127,41 +314,181 @@ pub(crate) fn parser(lexer: syn::Path, mut input: syn::ItemEnum) -> Result<Token
         route_assemblers.push({
             let ident = variant.ident.clone();
             // TODO check fields are correct
-            match variant.fields {
+            match &variant.fields {
                 VariantFields::Unit => quote! {
+                    #[doc = #main_doc]
                     #iu32 => {
                         { #assembler }
-                        Self::#ident
+                        #enum_ident::#ident
                     }
                 },
                 VariantFields::Unnamed(_fields) => quote! {
+                    #[doc = #main_doc]
                     #iu32 => {
                         let (#(#produced,)*) = { #assembler };
-                        Self::#ident(#(#produced),*)
+                        #enum_ident::#ident(#(#produced),*)
                     }
                 },
                 VariantFields::Named(_) => quote! {
+                    #[doc = #main_doc]
                     #iu32 => {
                         let (#(#produced,)*) = { #assembler };
-                        Self::#ident { #(#produced),* }
+                        #enum_ident::#ident { #(#produced),* }
                     }
                 }
             }
         });
     }
 
+    main_expectations.extend(extra_expectations);
+    repeats.extend(extra_repeats);
+    route_infos.extend(extra_route_infos);
+
+    // Each group of productions with a shared prefix gets one more route, which only matches the
+    // prefix. The parser moves it onto the productions' own routes when it is done.
+    let mut shared = vec![];
+    for (productions, len) in &prefixes {
+        let variant = &variants[productions.start];
+        let parts = match &variant.pattern {
+            ParserPattern::And(parts) => &parts[..*len],
+            _ => unreachable!("only sequences have shared prefixes")
+        };
+        let mut main_route = quote! {};
+        let mut end_route = quote! {};
+        let mut expectations = vec![];
+        for (state, part) in parts.iter().enumerate() {
+            let output = part.to_matchers(&enum_ident, &lexer, variant, state, next_route, EndBehavior::NotLast)?;
+            main_route.extend(output.main_route);
+            end_route.extend(output.end_route);
+            expectations.extend(output.expectations);
+        }
+        let pattern = parts.iter().map(|part| part.to_string()).collect::<Vec<_>>().join(" ");
+        let doc = format!(
+            " Route {}: \"{}\" shared by `{}::{}` through `{}::{}`",
+            next_route, pattern, enum_ident, variant.ident, enum_ident, variants[productions.end - 1].ident
+        );
+        let route = syn::Index::from(next_route);
+        route_matchers.push(quote! {
+            #[doc = #doc]
+            #route => match state {
+                #main_route
+                _ => array_vec!([AutomatonCommand; 3] => Die)
+            }
+        });
+        end_route_matchers.push(quote! {
+            #[doc = #doc]
+            #route => match state {
+                #end_route
+                _ => false
+            }
+        });
+        main_expectations.push(expectations);
+        repeats.push(false);
+        let first = productions.start;
+        route_infos.push(quote! {
+            parce::report::Route { production: #first, pattern: #pattern }
+        });
+        let (start, end, len) = (productions.start as u32, productions.end as u32, *len as u32);
+        shared.push(quote! {
+            parce::internal_prelude::SharedPrefix { route: #route, productions: #start..#end, len: #len }
+        });
+        next_route += 1;
+    }
+    let expected = expectation_tables(&main_expectations);
+    let guards = main_expectations.iter().zip(&repeats).map(|(states, repeats)| {
+        let states = states.iter().map(|state| match &state.guard {
+            Some(lexeme) => {
+                let ident = format_ident!("{}", lexeme);
+                quote! { Some(KindSet::of(&[<#lexer as Lexer>::Lexemes::#ident])) }
+            }
+            None => quote! { None }
+        });
+        quote! {
+            RouteGuard { repeats: #repeats, states: vec![#(#states),*] }
+        }
+    });
+    let uses = uses.borrow().clone();
+    let gates = gates.iter().enumerate().filter_map(|(i, gate)| gate.as_ref().map(|gate| {
+        let i = i as u32;
+        quote! { (#i, #gate) }
+    }));
+
     let mut parser_submission = lexer.clone();
     let last_ident = parser_submission.segments.last().unwrap().ident.clone();
     parser_submission.segments.last_mut().unwrap().ident = format_ident!("{}ParserSubmission", last_ident);
 
+    let commands_doc = format!(" [Parseable::commands](parce::parser::Parseable::commands) for the routes of `{}`, one arm per route.", enum_ident);
+    let last_commands_doc = format!(" [Parseable::last_commands](parce::parser::Parseable::last_commands) for the routes of `{}`.", enum_ident);
+    let reachable_doc = format!(" The tables of `{}` and every rule it uses, found once on first use.", enum_ident);
+    let guards_doc = format!(" The guards of the routes of `{}`, built once on first use.", enum_ident);
+
+    // With the inventory feature, rules register with their lexer so it can list them.
+    let registration = if cfg!(feature = "inventory") {
+        quote! {
+            parce::internal_prelude::inventory::submit! {
+                #parser_submission(<#self_ty as parce::internal_prelude::Parseable>::table())
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Borrowed rules can't come from a temporary string, so they only get the ParseSession methods.
+    let from_str = match lifetime {
+        Some(_) => quote! {},
+        None => quote! {
+            impl std::str::FromStr for #enum_ident {
+                type Err = parce::error::ParceError;
+
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    use parce::parser::Parse;
+                    s.parse_all()
+                }
+            }
+        }
+    };
+
     Ok(quote! {
         #[derive(Debug, PartialEq)]
         #input
 
-        parce::internal_prelude::inventory::submit! {
-            #parser_submission(
-                core::any::TypeId::of::<#enum_ident>(),
-                |route: u32, mut state: u32, lexeme: parce::internal_prelude::SpannedLexeme<<#lexer as parce::internal_prelude::Lexer>::Lexemes>| -> parce::internal_prelude::ArrayVec<[parce::internal_prelude::AutomatonCommand; 3]> {
+        #registration
+
+        #[allow(clippy::modulo_one, clippy::unused_unit)]
+        impl parce::internal_prelude::Parseable for #self_ty {
+            type Lexer = #lexer;
+            type Output<'t> = #output_ty;
+            const PRODUCTIONS: u32 = #num_prod_index;
+            const GRAMMAR: parce::report::GrammarInfo = parce::report::GrammarInfo {
+                root: <#self_ty as parce::internal_prelude::Parseable>::rule_info,
+                lexemes: <#lexer as parce::internal_prelude::Lexer>::LEXEMES,
+                lexer_fingerprint: <#lexer as parce::internal_prelude::Lexer>::FINGERPRINT
+            };
+            const FINGERPRINT: u64 = parce::fingerprint::combine_fingerprints(<#lexer as parce::internal_prelude::Lexer>::FINGERPRINT, #fingerprint);
+
+            fn default_lexer() -> #lexer {
+                #lexer::default()
+            }
+            fn rule_info() -> &'static parce::report::RuleInfo {
+                let _: &[<#lexer as parce::internal_prelude::Lexer>::Lexemes] = &[#(<#lexer as parce::internal_prelude::Lexer>::Lexemes::#sync_lexemes),*];
+                let _: &[<#lexer as parce::internal_prelude::Lexer>::Lexemes] = &[#(#paths),*];
+                static INFO: parce::report::RuleInfo = parce::report::RuleInfo {
+                    name: #enum_name,
+                    module: module_path!(),
+                    productions: &[#(#production_infos),*],
+                    expected: #expected,
+                    routes: &[#(#route_infos),*],
+                    sync: &[#(#rule_sync),*],
+                    deterministic: #deterministic,
+                    fingerprint: #fingerprint
+                };
+                &INFO
+            }
+            #is_sync
+            fn table() -> &'static parce::internal_prelude::RuleTable<<#lexer as parce::internal_prelude::Lexer>::Lexemes> {
+                #[doc = #commands_doc]
+                #[allow(clippy::modulo_one, unused_doc_comments)]
+                fn commands(route: u32, mut state: u32, lexeme: parce::internal_prelude::SpannedLexeme<<#lexer as parce::internal_prelude::Lexer>::Lexemes>, text: &str) -> parce::internal_prelude::ArrayVec<[parce::internal_prelude::AutomatonCommand; 3]> {
                     use parce::internal_prelude::*;
                     use AutomatonCommand::*;
 
@@ -169,8 +496,10 @@ pub(crate) fn parser(lexer: syn::Path, mut input: syn::ItemEnum) -> Result<Token
                         #(#route_matchers)*
                         other => panic!("route {} out of bounds", other)
                     }
-                },
-                |route: u32, mut state: u32| -> bool {
+                }
+                #[doc = #last_commands_doc]
+                #[allow(clippy::modulo_one, unused_doc_comments)]
+                fn last_commands(route: u32, mut state: u32) -> bool {
                     use parce::internal_prelude::*;
                     use AutomatonCommand::*;
 
@@ -179,94 +508,262 @@ pub(crate) fn parser(lexer: syn::Path, mut input: syn::ItemEnum) -> Result<Token
                         other => panic!("route {} out of bounds", other)
                     }
                 }
-            )
-        }
-
-        impl parce::internal_prelude::Parseable for #enum_ident {
-            type Lexer = #lexer;
-            const PRODUCTIONS: u32 = #num_prod_index;
-
-            fn default_lexer() -> Box<Self::Lexer> {
-                Box::new(#lexer::default())
-            }
-            fn commands(rule: parce::internal_prelude::Rule, route: u32, mut state: u32, lexeme: parce::internal_prelude::SpannedLexeme<<#lexer as parce::internal_prelude::Lexer>::Lexemes>) -> parce::internal_prelude::ArrayVec<[parce::internal_prelude::AutomatonCommand; 3]> {
-                use parce::internal_prelude::*;
-                use AutomatonCommand::*;
-
-                dbg!((route, state, lexeme));
-
-                if rule == Rule::of::<#enum_ident>() {
-                    let result = match route {
-                        #(#route_matchers)*
-                        other => panic!("route {} out of bounds", other)
-                    };
-                    dbg!(result)
-                } else {
-                    for submission in inventory::iter::<#parser_submission> {
-                        if rule == submission.0 {
-                            return submission.1(route, state, lexeme);
-                        }
+                #[doc = #reachable_doc]
+                fn reachable() -> &'static parce::internal_prelude::RuleTables<<#lexer as parce::internal_prelude::Lexer>::Lexemes> {
+                    parce::internal_prelude::lazy_static! {
+                        static ref REACHABLE: parce::internal_prelude::RuleTables<<#lexer as parce::internal_prelude::Lexer>::Lexemes> =
+                            parce::internal_prelude::RuleTable::collect_reachable(<#self_ty as parce::internal_prelude::Parseable>::table());
                     }
-                    panic!("rule number {:?} not found", rule);
+                    &REACHABLE
                 }
-            }
-            fn last_commands(rule: parce::internal_prelude::Rule, route: u32, mut state: u32) -> bool {
-                use parce::internal_prelude::*;
-                use AutomatonCommand::*;
-
-                dbg!((route, state));
+                #[doc = #guards_doc]
+                fn guards() -> &'static [parce::internal_prelude::RouteGuard] {
+                    use parce::internal_prelude::*;
 
-                if rule == Rule::of::<#enum_ident>() {
-                    let result = match route {
-                        #(#end_route_matchers)*
-                        other => panic!("route {} out of bounds", other)
-                    };
-                    dbg!(result)
-                } else {
-                    for submission in inventory::iter::<#parser_submission> {
-                        if rule == submission.0 {
-                            return submission.2(route, state);
-                        }
+                    lazy_static! {
+                        static ref GUARDS: Vec<RouteGuard> = vec![#(#guards),*];
                     }
-                    panic!("rule number {:?} not found", rule);
+                    &GUARDS
                 }
+                static TABLE: parce::internal_prelude::RuleTable<<#lexer as parce::internal_prelude::Lexer>::Lexemes> = parce::internal_prelude::RuleTable {
+                    rule: parce::internal_prelude::Rule::of::<#self_ty>,
+                    uses: &[#(<#uses as parce::internal_prelude::Parseable>::table),*],
+                    reachable,
+                    commands,
+                    last_commands,
+                    inline: &[#(#inline),*],
+                    prefixes: &[#(#shared),*],
+                    recovery: &[#(#recovery),*],
+                    gates: &[#(#gates),*],
+                    guards,
+                    rule_info: <#self_ty as parce::internal_prelude::Parseable>::rule_info
+                };
+                &TABLE
             }
-            fn assemble(auto: parce::internal_prelude::Rawtomaton, lexemes: &[parce::internal_prelude::SpannedLexeme<<#lexer as parce::internal_prelude::Lexer>::Lexemes>], text: &str) -> Result<(usize, Self), parce::error::ParceError> {
+            #[allow(unused_doc_comments)]
+            fn assemble<'t>(auto: parce::internal_prelude::Rawtomaton, lexemes: &[parce::internal_prelude::SpannedLexeme<<#lexer as parce::internal_prelude::Lexer>::Lexemes>], text: &'t str, spans: &mut Vec<parce::parser::RuleSpan>) -> Result<(usize, #output_ty), parce::error::ParceError> {
                 use parce::internal_prelude::*;
 
-                unsafe {
-                    let rule = (**auto).rule;
-                    if rule == Rule::of::<#enum_ident>() {
-                        let mut consumed = 0;
-                        let mut recruits = 0;
-                        let result = match (**auto).route {
-                            #(#route_assemblers)*
-                            other => panic!("route {} out of bounds, shouldn't be possible", other)
-                        };
-                        Ok((consumed, result))
-                    } else {
-                        unreachable!()
-                    }
-                }
+                // Counts lexemes from the start of this rule, not the whole input.
+                let mut consumed = 0;
+                let rule_start = auto.automaton().lexeme_start;
+                let mut recruits = 0;
+                #[allow(unused_mut)]
+                let mut child_spans: Vec<parce::parser::RuleSpan> = vec![];
+                let result = match auto.automaton().route {
+                    #(#route_assemblers)*
+                    other => panic!("route {} out of bounds, shouldn't be possible", other)
+                };
+                let first = parce::parser::next_seen(auto, lexemes, rule_start);
+                spans.push(parce::parser::RuleSpan::assembled(Self::rule_info(), auto.automaton().route, lexemes, first, (rule_start + consumed).saturating_sub(first), text, child_spans));
+                Ok((consumed, result))
+            }
+        }
+
+        #from_str
+
+        #foldable
+        #validate
+        #constructors
+    })
+}
+
+/// The entries of [RuleTable::inline](parce::parser::RuleTable::inline) for an `#[inline_rule]`.
+/// Each production has to be exactly one other rule, so the production's automaton has nothing to
+/// do but wait for that rule.
+fn inline_table(grammar: &Ident, variants: &[VariantInfo], no_skips: &[bool]) -> Result<Vec<TokenStream2>, ParceMacroError> {
+    use ParserPattern::*;
+
+    variants.iter().zip(no_skips).map(|(variant, &no_skip)| {
+        let rule = match (&variant.pattern, &variant.fields) {
+            (Rule(name), _) => {
+                let ident = format_ident!("{}", name);
+                quote! { #ident }
+            }
+            (BareUnnamedField(n), VariantFields::Unnamed(fields)) if fields.len() > *n => {
+                let ty = &fields[*n];
+                quote! { #ty }
+            }
+            (BareNamedField(name), fields) => {
+                let ty = fields.search_named(name)?;
+                quote! { #ty }
             }
+            _ => return Err(ParceMacroError(
+                Box::new(grammar.clone()),
+                format!("production {} of an #[inline_rule] must be a single rule, like \"0\" or \"#Rule\"", variant.ident)
+            ))
+        };
+        if variant.cut.is_some() || no_skip || !variant.recovered.is_empty() {
+            return Err(ParceMacroError(
+                Box::new(grammar.clone()),
+                format!("production {} of an #[inline_rule] can't have a cut, #[no_skip], or a Recovered field", variant.ident)
+            ));
         }
+        let used = variant.use_rule(&rule);
+        Ok(quote! { (#used, <#rule as parce::internal_prelude::Parseable>::PRODUCTIONS) })
+    }).collect()
+}
+
+/// Finds the groups of consecutive productions that start with the same lexemes, for
+/// [SharedPrefix](parce::parser::SharedPrefix), and how many lexemes each group shares. The last
+/// part of a production is never shared, so every production still has something of its own to
+/// match after the prefix. Productions marked in `unshared` are left out.
+fn shared_prefixes(variants: &[VariantInfo], unshared: &[bool]) -> Vec<(std::ops::Range<usize>, usize)> {
+    use ParserPattern::*;
 
-        impl std::str::FromStr for #enum_ident {
-            type Err = parce::error::ParceError;
+    let leading: Vec<Vec<String>> = variants.iter().zip(unshared).map(|(variant, &unshared)| match &variant.pattern {
+        And(parts) if variant.cut.is_none() && !unshared => parts[..parts.len() - 1].iter()
+            .take_while(|part| matches!(part, Lexeme(_) | Keyword(..) | Char(_) | Class(_) | Dot))
+            .map(|part| part.to_string())
+            .collect(),
+        _ => vec![]
+    }).collect();
 
-            fn from_str(s: &str) -> Result<Self, Self::Err> {
-                use parce::parser::Parse;
-                s.parse_all()
+    let mut groups = vec![];
+    let mut first = 0;
+    while first < leading.len() {
+        let mut len = leading[first].len();
+        let mut end = first + 1;
+        while end < leading.len() {
+            let shared = leading[first].iter().zip(&leading[end]).take_while(|(a, b)| a == b).count().min(len);
+            if shared == 0 {
+                break;
             }
+            len = shared;
+            end += 1;
         }
-    })
+        if end - first > 1 {
+            groups.push((first..end, len));
+        }
+        first = end;
+    }
+    groups
+}
+
+/// Replaces a borrowed rule's lifetime with `'static` in a field type, so the type can be named
+/// inside the rule's Parseable impl.
+struct StaticLifetime<'a>(&'a syn::Lifetime);
+
+impl VisitMut for StaticLifetime<'_> {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut syn::Lifetime) {
+        if lifetime == self.0 {
+            *lifetime = syn::Lifetime::new("'static", lifetime.span());
+        }
+    }
+}
+
+/// Converts the assembled rule `ident` into the type of its field. This is only `unsafe` when parce
+/// has a [NodeArena](parce::parser::arena::NodeArena) for reference fields, which it doesn't with
+/// the `safe` feature.
+fn into_field(ident: &Ident) -> TokenStream2 {
+    #[cfg(not(feature = "safe"))]
+    return quote! { unsafe { parce::internal_prelude::IntoField::into_field(#ident, text) }? };
+    #[cfg(feature = "safe")]
+    return quote! { parce::internal_prelude::IntoField::into_field(#ident, text)? };
+}
+
+/// Whether a field's type has a `Recovered` among the wrappers [unwrap_type] takes off, like
+/// `Vec<Recovered<Statement>>`.
+fn is_recovered(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(syn::TypeReference {elem, ..}) => is_recovered(elem),
+        syn::Type::Path(syn::TypePath {path, ..}) => path.segments.first().is_some_and(|seg| {
+            let inner = match &seg.arguments {
+                syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) if args.len() == 1 => match args.first() {
+                    Some(syn::GenericArgument::Type(inner)) => Some(inner),
+                    _ => None
+                },
+                _ => None
+            };
+            seg.ident == "Recovered" || ((seg.ident == "Vec" || seg.ident == "Option" || seg.ident == "Box") && inner.is_some_and(is_recovered))
+        }),
+        _ => false
+    }
+}
+
+/// The matcher for a bare field of type `Recovered<T>`, where `ty` is `T`. Besides the automata for
+/// `T`, the state spawns one on an extra route, which skips lexemes up to and including the next of
+/// `T`'s `#[sync]` anchors. The assembler tells which one won by its route.
+fn recovered_field(ty: &syn::Type, ident: &Ident, info: &VariantInfo, first_state: usize, next_route: usize, on_victory: TokenStream2) -> MatcherOutput {
+    let first_state_u32 = syn::Index::from(first_state);
+    let next_u32 = syn::Index::from(next_route);
+    let use_number = info.use_rule(&quote! { #ty });
+    let into_field = into_field(&format_ident!("value"));
+    MatcherOutput {
+        main_route: quote! {
+            #first_state_u32 => array_vec!([AutomatonCommand; 3] => Spawn {
+                rule: #use_number,
+                route: 0,
+                how_many: <#ty as Parseable>::PRODUCTIONS,
+                on_victory: #on_victory
+            }, Spawn {
+                rule: 0,
+                route: #next_u32,
+                how_many: 1,
+                on_victory: #on_victory
+            }, Die),
+        },
+        states: 1,
+        expectations: vec![StateExpectation {
+            rules: vec![quote! { #ty }],
+            ..Default::default()
+        }],
+        extra_routes: vec![(
+            quote! {
+                0 => if <#ty as Parseable>::is_sync(lexeme.data) {
+                    array_vec!([AutomatonCommand; 3] => Victory, Die)
+                } else {
+                    array_vec!([AutomatonCommand; 3] => Advance)
+                },
+            },
+            quote! {
+                0 => true,
+            },
+            Some(1),
+            vec![StateExpectation {
+                any: true,
+                ..Default::default()
+            }],
+            RECOVERY_PART.to_string()
+        )],
+        end_route: quote! {
+            #first_state_u32 => false,
+        },
+        assembler: quote! {
+            consumed = parce::parser::next_seen(auto, lexemes, rule_start + consumed) - rule_start;
+            let child = auto.automaton().children[recruits];
+            let #ident = if child.automaton().rule == auto.automaton().rule && child.automaton().route == #next_u32 {
+                // The state is one past the lexemes skipped, or two past at the end of the input.
+                let count = (child.automaton().state as usize).min(lexemes.len() - rule_start - consumed);
+                let error = parce::parser::recovery::ErrorNode::skipped::<#ty>(lexemes, rule_start + consumed, count, text, &mut child_spans);
+                consumed += count;
+                parce::parser::recovery::Recovered::Error(error)
+            } else {
+                let (more_consumed, value) = <#ty as Parseable>::assemble(child, lexemes, text, &mut child_spans)?;
+                consumed += more_consumed;
+                parce::parser::recovery::Recovered::Parsed(#into_field)
+            };
+            recruits += 1;
+            (#ident,)
+        },
+        produced: vec![ident.clone()],
+    }
 }
 
 fn unwrap_type(mut ty: syn::Type) -> Result<syn::Type, ParceMacroError> {
-    while let syn::Type::Path(syn::TypePath {ref path, ..}) = ty {
+    loop {
+        let path = match ty {
+            syn::Type::Path(syn::TypePath {ref path, ..}) => path,
+            // References to rules are allocated in a NodeArena. `&str` is an assigned field, and stays.
+            syn::Type::Reference(syn::TypeReference {ref elem, ..}) if !matches!(&**elem, syn::Type::Path(p) if p.path.is_ident("str")) => {
+                ty = (**elem).clone();
+                continue;
+            }
+            _ => break
+        };
         if let Some(seg) = path.segments.first() {
             let id = seg.ident.clone();
-            if id.to_string() == "Vec" || id.to_string() == "Option" || id.to_string() == "Box" {
+            if id == "Vec" || id == "Option" || id == "Box" || id == "Recovered" {
                 ty = match &path.segments.first().unwrap().arguments {
                     syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) => {
                         if args.len() == 1 {
@@ -290,9 +787,35 @@ fn unwrap_type(mut ty: syn::Type) -> Result<syn::Type, ParceMacroError> {
     Ok(ty)
 }
 
+/// The expression that turns the text matched by an assigned field into the field's value. `&str`
+/// fields borrow the text directly, and everything else goes through [FromStr](std::str::FromStr).
+fn assigned_value(ty: Option<&syn::Type>) -> TokenStream2 {
+    match ty {
+        Some(syn::Type::Reference(syn::TypeReference { elem, .. })) if matches!(&**elem, syn::Type::Path(p) if p.path.is_ident("str")) => {
+            quote! { &text[start..end] }
+        }
+        _ => quote! {
+            match text[start..end].parse() {
+                Ok(res) => res,
+                Err(e) => return Err(parce::error::ParceError {
+                    input: text.to_string(),
+                    start,
+                    info: parce::error::ParceErrorInfo::Assemble
+                })
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum ParserPattern {
     Lexeme(String),
+    /// A lexeme that only matches when its text is exactly the given string, for soft keywords.
+    Keyword(String, String),
+    /// Any lexeme whose text is exactly this character, from a quoted literal.
+    Char(char),
+    /// Any lexeme whose text is a single character in this class, like `[a-z]`.
+    Class(String),
     Rule(String),
     BareUnnamedField(usize),
     AssignUnnamedField(usize, Box<ParserPattern>),
@@ -307,10 +830,58 @@ pub(crate) enum ParserPattern {
     Range(Box<ParserPattern>, usize, RangeRuleMax),
 }
 
+impl std::fmt::Display for ParserPattern {
+    /// Writes the pattern back out in the syntax it was parsed from, without extra whitespace.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ParserPattern::*;
+
+        match self {
+            Lexeme(l) => write!(f, "{}", l),
+            Keyword(l, text) => write!(f, "{}('{}')", l, text),
+            Char(c) => write!(f, "{:?}", c),
+            Class(class) => write!(f, "{}", class),
+            Rule(name) => write!(f, "#{}", name),
+            BareUnnamedField(n) => write!(f, "{}", n),
+            AssignUnnamedField(n, p) => write!(f, "{}={}", n, grouped(p)),
+            BareNamedField(name) => write!(f, "{}", name),
+            AssignNamedField(name, p) => write!(f, "{}={}", name, grouped(p)),
+            And(ps) => write!(f, "{}", ps.iter().map(|p| match p {
+                Or(_) => grouped(p),
+                _ => p.to_string()
+            }).collect::<Vec<_>>().join(" ")),
+            Or(ps) => write!(f, "{}", ps.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(" | ")),
+            Dot => write!(f, "."),
+            Star(p) => write!(f, "{}*", grouped(p)),
+            Plus(p) => write!(f, "{}+", grouped(p)),
+            Question(p) => write!(f, "{}?", grouped(p)),
+            Range(p, min, RangeRuleMax::Fixed) => write!(f, "{}{{{}}}", grouped(p), min),
+            Range(p, min, RangeRuleMax::Some(max)) => write!(f, "{}{{{},{}}}", grouped(p), min, max),
+            Range(p, min, RangeRuleMax::Infinite) => write!(f, "{}{{{},}}", grouped(p), min)
+        }
+    }
+}
+
+/// Writes a pattern so an operator can be put after it, in parentheses if it needs them.
+fn grouped(p: &ParserPattern) -> String {
+    match p {
+        ParserPattern::And(_) | ParserPattern::Or(_) => format!("({})", p),
+        _ => p.to_string()
+    }
+}
+
+/// The part of the pattern named by the extra route of a recovered field, which is how the tables
+/// find those routes.
+const RECOVERY_PART: &str = "<error>";
+
 struct MatcherOutput {
     main_route: TokenStream2,
     states: usize,
-    extra_routes: Vec<(TokenStream2, TokenStream2, Option<usize>)>,
+    /// What each state matches, for the expected-lexeme tables. One entry per state.
+    expectations: Vec<StateExpectation>,
+    /// Main route, end route, cycle length, per-state expectations, and the part of the pattern
+    /// matched, of each extra route.
+    #[allow(clippy::type_complexity)]
+    extra_routes: Vec<(TokenStream2, TokenStream2, Option<usize>, Vec<StateExpectation>, String)>,
     end_route: TokenStream2,
     assembler: TokenStream2,
     produced: Vec<Ident>,
@@ -340,33 +911,71 @@ impl ParserPattern {
         let first_state_u32 = syn::Index::from(first_state);
         let next_u32 = syn::Index::from(next_route);
 
+        // Patterns that match exactly one lexeme, when `condition` holds. If the condition checks
+        // for a lexeme, it is also the state's guard.
+        let single = |condition: TokenStream2, expected: String, guarded: bool| {
+            let success = match end_behavior {
+                Last => quote! { Victory, Die },
+                NotLast => quote! { Advance },
+                Reset => quote! { Victory }
+            };
+            MatcherOutput {
+                main_route: quote! {
+                    #first_state_u32 => if #condition {
+                        array_vec!([AutomatonCommand; 3] => #success)
+                    } else {
+                        array_vec!([AutomatonCommand; 3] => Die)
+                    },
+                },
+                states: 1,
+                expectations: vec![StateExpectation {
+                    guard: guarded.then(|| expected.clone()),
+                    lexemes: vec![expected],
+                    ..Default::default()
+                }],
+                extra_routes: vec![],
+                end_route: quote! {
+                    #first_state_u32 => false,
+                },
+                assembler: quote! {
+                    consumed = parce::parser::next_seen(auto, lexemes, rule_start + consumed) - rule_start + 1;
+                },
+                produced: vec![],
+            }
+        };
+
         Ok(match self {
             Lexeme(name) => {
                 let ident = format_ident!("{}", name);
-                let success = match end_behavior {
-                    Last => quote! { Victory, Die },
-                    NotLast => quote! { Advance },
-                    Reset => quote! { Victory }
+                single(quote! { lexeme == <#lexer as parce::internal_prelude::Lexer>::Lexemes::#ident }, name.clone(), true)
+            }
+            Keyword(name, keyword) => {
+                let ident = format_ident!("{}", name);
+                single(quote! {
+                    lexeme == <#lexer as parce::internal_prelude::Lexer>::Lexemes::#ident
+                        && text.get(lexeme.start..lexeme.start + lexeme.len) == Some(#keyword)
+                }, name.clone(), true)
+            }
+            Char(c) => {
+                let c_str = c.to_string();
+                single(quote! { text.get(lexeme.start..lexeme.start + lexeme.len) == Some(#c_str) }, format!("{:?}", c), false)
+            }
+            Class(class) => {
+                let (firsts, lasts): (Vec<char>, Vec<char>) = match class_ranges(class) {
+                    Some(ranges) => ranges.into_iter().unzip(),
+                    None => return Err(ParceMacroError(Box::new(info.ident.clone()), format!("{} is not a valid character class", class)))
                 };
-                MatcherOutput {
-                    main_route: quote! {
-                        #first_state_u32 => if lexeme == <#lexer as parce::internal_prelude::Lexer>::Lexemes::#ident {
-                            array_vec!([AutomatonCommand; 3] => #success)
-                        } else {
-                            array_vec!([AutomatonCommand; 3] => Die)
-                        },
-                    },
-                    states: 1,
-                    extra_routes: vec![],
-                    end_route: quote! {
-                        #first_state_u32 => false,
-                    },
-                    assembler: quote! { consumed += 1; },
-                    produced: vec![],
-                }
+                single(quote! {
+                    {
+                        const CLASS: &[(char, char)] = &[#((#firsts, #lasts)),*];
+                        let mut chars = text.get(lexeme.start..lexeme.start + lexeme.len).unwrap_or("").chars();
+                        matches!((chars.next(), chars.next()), (Some(c), None) if class_contains(CLASS, c))
+                    }
+                }, class.clone(), false)
             }
             Rule(name) => {
                 let r = format_ident!("{}", name);
+                let use_number = info.use_rule(&quote! { #r });
                 let on_victory = match end_behavior {
                     Last => quote! { Continuation::PassDie },
                     NotLast => quote! { Continuation::Advance },
@@ -375,19 +984,24 @@ impl ParserPattern {
                 MatcherOutput {
                     main_route: quote! {
                         #first_state_u32 => array_vec!([AutomatonCommand; 3] => Spawn {
-                            rule: Rule::of::<#r>(),
+                            rule: #use_number,
                             route: 0,
                             how_many: <#r as Parseable>::PRODUCTIONS,
                             on_victory: #on_victory
                         }, Die),
                     },
                     states: 1,
+                    expectations: vec![StateExpectation {
+                        rules: vec![quote! { #r }],
+                        ..Default::default()
+                    }],
                     extra_routes: vec![],
                     end_route: quote! {
                         #first_state_u32 => false,
                     },
                     assembler: quote! {
-                        let (more_consumed, _) = #r::assemble((**auto).children[recruits], lexemes, text)?;
+                        consumed = parce::parser::next_seen(auto, lexemes, rule_start + consumed) - rule_start;
+                        let (more_consumed, _) = <#r as Parseable>::assemble(auto.automaton().children[recruits], lexemes, text, &mut child_spans)?;
                         consumed += more_consumed;
                         recruits += 1;
                     },
@@ -405,30 +1019,40 @@ impl ParserPattern {
                     _ => return Err(ParceMacroError(Box::new(info.ident.clone()), "variant does not have unnamed fields".to_string()))
                 };
                 let ident = format_ident!("unnamed_field_{}", syn::Index::from(*n));
+                let into_field = into_field(&ident);
                 let on_victory = match end_behavior {
                     Last => quote! { Continuation::PassDie },
                     NotLast => quote! { Continuation::Advance },
                     Reset => quote! { Continuation::PassAdvance }
                 };
+                if info.recovered.contains(&n.to_string()) {
+                    return Ok(recovered_field(r, &ident, info, first_state, next_route, on_victory));
+                }
+                let use_number = info.use_rule(&quote! { #r });
                 MatcherOutput {
                     main_route: quote! {
                         #first_state_u32 => array_vec!([AutomatonCommand; 3] => Spawn {
-                            rule: Rule::of::<#r>(),
+                            rule: #use_number,
                             route: 0,
                             how_many: <#r as Parseable>::PRODUCTIONS,
                             on_victory: #on_victory
                         }, Die),
                     },
                     states: 1,
+                    expectations: vec![StateExpectation {
+                        rules: vec![quote! { #r }],
+                        ..Default::default()
+                    }],
                     extra_routes: vec![],
                     end_route: quote! {
                         #first_state_u32 => false,
                     },
                     assembler: quote! {
-                        let (more_consumed, #ident) = #r::assemble((**auto).children[recruits], lexemes, text)?;
+                        consumed = parce::parser::next_seen(auto, lexemes, rule_start + consumed) - rule_start;
+                        let (more_consumed, #ident) = <#r as Parseable>::assemble(auto.automaton().children[recruits], lexemes, text, &mut child_spans)?;
                         consumed += more_consumed;
                         recruits += 1;
-                        (#ident.into(),)
+                        (#into_field,)
                     },
                     produced: vec![ident],
                 }
@@ -441,37 +1065,51 @@ impl ParserPattern {
                     Reset => quote! { Continuation::PassAdvance }
                 };
                 let ident = format_ident!("{}", id);
+                if info.recovered.contains(id) {
+                    return Ok(recovered_field(&ty, &ident, info, first_state, next_route, on_victory));
+                }
+                let use_number = info.use_rule(&quote! { #ty });
+                let into_field = into_field(&ident);
                 MatcherOutput {
                     main_route: quote! {
                         #first_state_u32 => array_vec!([AutomatonCommand; 3] => Spawn {
-                            rule: Rule::of::<#ty>(),
+                            rule: #use_number,
                             route: 0,
                             how_many: <#ty as Parseable>::PRODUCTIONS,
                             on_victory: #on_victory
                         }, Die),
                     },
                     states: 1,
+                    expectations: vec![StateExpectation {
+                        rules: vec![quote! { #ty }],
+                        ..Default::default()
+                    }],
                     extra_routes: vec![],
                     end_route: quote! {
                         #first_state_u32 => false,
                     },
                     assembler: quote! {
-                        let (more_consumed, #ident) = #ty::assemble((**auto).children[recruits], lexemes, text)?;
+                        consumed = parce::parser::next_seen(auto, lexemes, rule_start + consumed) - rule_start;
+                        let (more_consumed, #ident) = <#ty as Parseable>::assemble(auto.automaton().children[recruits], lexemes, text, &mut child_spans)?;
                         consumed += more_consumed;
                         recruits += 1;
-                        (#ident.into(),)
+                        (#into_field,)
                     },
                     produced: vec![ident],
                 }
             }
             AssignUnnamedField(n, rule) => {
                 let output = rule.to_matchers(grammar, lexer, info, first_state, next_route, end_behavior)?;
+                let value = assigned_value(match &info.fields {
+                    VariantFields::Unnamed(fields) => fields.get(*n),
+                    _ => None
+                });
                 let extra_produced = output.produced;
                 let ident = format_ident!("unnamed_field_{}", syn::Index::from(*n));
                 let mut produced = extra_produced.clone();
                 produced.insert(0, ident);
                 let assembler = output.assembler;
-                let assign = if extra_produced.len() == 0 {
+                let assign = if extra_produced.is_empty() {
                     quote! {
                         { #assembler }
                     }
@@ -482,18 +1120,11 @@ impl ParserPattern {
                 };
                 MatcherOutput {
                     assembler: quote! {
-                        let start = dbg!(lexemes[consumed].start);
+                        consumed = parce::parser::next_seen(auto, lexemes, rule_start + consumed) - rule_start;
+                        let start = lexemes[rule_start + consumed].start;
                         #assign
-                        let end = lexemes[consumed-1].start + lexemes[consumed-1].len;
-                        (
-                            match text[start..end].parse() {
-                                Ok(res) => res,
-                                Err(e) => return Err(parce::error::ParceError {
-                                    input: text.to_string(),
-                                    start,
-                                    info: parce::error::ParceErrorInfo::Assemble
-                                })
-                            }, #(#extra_produced),*)
+                        let end = lexemes[rule_start + consumed - 1].start + lexemes[rule_start + consumed - 1].len;
+                        (#value, #(#extra_produced),*)
                     },
                     produced,
                     ..output
@@ -501,12 +1132,13 @@ impl ParserPattern {
             }
             AssignNamedField(s, rule) => {
                 let output = rule.to_matchers(grammar, lexer, info, first_state, next_route, end_behavior)?;
+                let value = assigned_value(info.fields.search_named(s).ok().as_ref());
                 let extra_produced = output.produced;
                 let ident = format_ident!("{}", s);
                 let mut produced = extra_produced.clone();
                 produced.insert(0, ident);
                 let assembler = output.assembler;
-                let assign = if extra_produced.len() == 0 {
+                let assign = if extra_produced.is_empty() {
                     quote! {
                         { #assembler }
                     }
@@ -517,18 +1149,11 @@ impl ParserPattern {
                 };
                 MatcherOutput {
                     assembler: quote! {
-                        let start = dbg!(lexemes[consumed].start);
+                        consumed = parce::parser::next_seen(auto, lexemes, rule_start + consumed) - rule_start;
+                        let start = lexemes[rule_start + consumed].start;
                         #assign
-                        let end = lexemes[consumed-1].start + lexemes[consumed-1].len;
-                        (
-                            match text[start..end].parse() {
-                                Ok(res) => res,
-                                Err(e) => return Err(parce::error::ParceError {
-                                    input: text.to_string(),
-                                    start,
-                                    info: parce::error::ParceErrorInfo::Assemble
-                                })
-                            }, #(#extra_produced),*)
+                        let end = lexemes[rule_start + consumed - 1].start + lexemes[rule_start + consumed - 1].len;
+                        (#value, #(#extra_produced),*)
                     },
                     produced,
                     ..output
@@ -540,6 +1165,7 @@ impl ParserPattern {
                 let mut end_route = quote! {};
                 let mut state = first_state;
                 let mut main_route = quote! {};
+                let mut expectations = vec![];
                 let mut produced = vec![vec![]];
                 let mut assemblers = vec![];
                 for (i, rule) in rules.iter().enumerate() {
@@ -551,6 +1177,7 @@ impl ParserPattern {
                     )?;
                     next_route += output.extra_routes.len();
                     state += output.states;
+                    expectations.extend(output.expectations);
                     extra_routes.extend(output.extra_routes);
                     let next_matcher = output.main_route;
                     main_route = quote! {
@@ -564,7 +1191,7 @@ impl ParserPattern {
                     };
                     let new_assembler = output.assembler;
                     let new_produced = output.produced;
-                    assemblers.push(if new_produced.len() == 0 {
+                    assemblers.push(if new_produced.is_empty() {
                         quote! {
                             { #new_assembler }
                         }
@@ -579,6 +1206,7 @@ impl ParserPattern {
                 MatcherOutput {
                     main_route,
                     states: state - first_state,
+                    expectations,
                     extra_routes,
                     end_route,
                     assembler: quote! {
@@ -604,9 +1232,9 @@ impl ParserPattern {
                     let output = rule.to_matchers(grammar, lexer, info, 0, next_extra_route, EndBehavior::Last)?;
                     next_extra_route += output.extra_routes.len();
                     if end_behavior == Reset {
-                        routes.push((output.main_route, output.end_route, Some(output.states)));
+                        routes.push((output.main_route, output.end_route, Some(output.states), output.expectations, rule.to_string()));
                     } else {
-                        routes.push((output.main_route, output.end_route, None));
+                        routes.push((output.main_route, output.end_route, None, output.expectations, rule.to_string()));
                     }
                     extra_routes.extend(output.extra_routes);
                     let new_assembler = output.assembler;
@@ -618,9 +1246,9 @@ impl ParserPattern {
                         }
                     });
                     if i == 0 {
-                        produced = std::collections::HashSet::from_iter(output.produced.into_iter());
+                        produced = std::collections::HashSet::from_iter(output.produced);
                     } else {
-                        let set = std::collections::HashSet::<Ident>::from_iter(output.produced.into_iter());
+                        let set = std::collections::HashSet::<Ident>::from_iter(output.produced);
                         if set != produced {
                             return Err(ParceMacroError(Box::new(info.ident.clone()), "not all possibilites in this pattern assign to the same fields in the enum variant".to_string()));
                         }
@@ -630,20 +1258,24 @@ impl ParserPattern {
                 MatcherOutput {
                     main_route: quote! {
                         #first_state_u32 => array_vec!([AutomatonCommand; 3] => Spawn {
-                            rule: Rule::of::<#grammar>(),
+                            rule: 0,
                             route: #next_u32,
                             how_many: #rules_len,
                             on_victory: #on_victory
                         }, Die),
                     },
                     states: 1,
+                    expectations: vec![StateExpectation {
+                        routes: (next_route..next_route + rules.len()).collect(),
+                        ..Default::default()
+                    }],
                     extra_routes: routes,
                     end_route: quote! {
                         #first_state_u32 => false,
                     },
                     assembler: quote! {
-                        let auto = (**auto).children[recruits];
-                        match (**auto).route {
+                        let auto = auto.automaton().children[recruits];
+                        match auto.automaton().route {
                             #(#assemblers)*
                             other => panic!("route {} out of bounds, this is an internal error", other)
                         }
@@ -667,12 +1299,16 @@ impl ParserPattern {
                         #first_state_u32 => array_vec!([AutomatonCommand; 3] => #success),
                     },
                     states: 1,
+                    expectations: vec![StateExpectation {
+                        any: true,
+                        ..Default::default()
+                    }],
                     extra_routes: vec![],
                     end_route: quote! {
                         #first_state_u32 => false,
                     },
                     assembler: quote! {
-                        consumed += 1;
+                        consumed = parce::parser::next_seen(auto, lexemes, rule_start + consumed) - rule_start + 1;
                     },
                     produced: vec![]
                 }
@@ -689,7 +1325,8 @@ enum RepetitionOperator {
     Range(usize, RangeRuleMax)
 }
 
-fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, grammar: &Ident, lexer: &Path, info: &VariantInfo, first_state: usize, next_route: usize, end_behavior: EndBehavior) -> Result<MatcherOutput, ParceMacroError> {
+#[allow(clippy::too_many_arguments)]
+fn repetition_operator(rule: &ParserPattern, op: RepetitionOperator, grammar: &Ident, lexer: &Path, info: &VariantInfo, first_state: usize, next_route: usize, end_behavior: EndBehavior) -> Result<MatcherOutput, ParceMacroError> {
     use RepetitionOperator::*;
 
     let first_state_u32 = syn::Index::from(first_state);
@@ -711,6 +1348,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
             let mut state = 0;
             let mut main_route = quote! {};
             let mut end_route = quote! {};
+            let mut expectations = vec![];
             for _ in 0..(start - 1) {
                 let output = rule.to_matchers(
                     grammar, lexer,
@@ -719,6 +1357,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                     EndBehavior::NotLast
                 )?;
                 state += output.states;
+                expectations.extend(output.expectations);
                 let next_matcher = output.main_route;
                 let next_end_matcher = output.end_route;
                 main_route = quote! {
@@ -738,6 +1377,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
             )?;
             let next_matcher = output.main_route;
             let next_end_matcher = output.end_route;
+            expectations.extend(output.expectations);
             (output.states, vec![MatcherOutput {
                 main_route: quote! {
                     #main_route
@@ -748,6 +1388,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                     #next_end_matcher
                 },
                 states: output.states * start,
+                expectations,
                 ..output
             }])
         }
@@ -755,6 +1396,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
             let mut state = 0;
             let mut main_route1 = quote! {};
             let mut end_route1 = quote! {};
+            let mut expectations1 = vec![];
             for _ in 0..(start - 2) {
                 let output = rule.to_matchers(
                     grammar, lexer,
@@ -763,6 +1405,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                     EndBehavior::NotLast
                 )?;
                 state += output.states;
+                expectations1.extend(output.expectations);
                 let next_matcher = output.main_route;
                 main_route1 = quote! {
                     #main_route1
@@ -783,6 +1426,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
             let next_matcher = output.main_route;
             let next_end_matcher = output.end_route;
             let cycle_length = output.states;
+            expectations1.extend(output.expectations);
             let mut outputs = vec![MatcherOutput {
                 main_route: quote! {
                     #main_route1
@@ -793,11 +1437,13 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                     #next_end_matcher
                 },
                 states: output.states * start,
+                expectations: expectations1,
                 ..output
             }];
             state = 0;
             let mut main_route2 = quote! {};
             let mut end_route2 = quote! {};
+            let mut expectations2 = vec![];
             if let RangeRuleMax::Some(max) = max {
                 for _ in start..max {
                     let output = rule.to_matchers(
@@ -807,6 +1453,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                         EndBehavior::Reset
                     )?;
                     state += output.states;
+                    expectations2.extend(output.expectations);
                     let next_matcher = output.main_route;
                     main_route2 = quote! {
                         #main_route2
@@ -826,6 +1473,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                 )?;
                 let next_matcher = output.main_route;
                 let next_end_matcher = output.end_route;
+                expectations2.extend(output.expectations);
                 outputs.push(MatcherOutput {
                     main_route: quote! {
                         #main_route2
@@ -836,6 +1484,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                         #end_route2
                         #next_end_matcher
                     },
+                    expectations: expectations2,
                     ..output
                 });
             } else {
@@ -852,14 +1501,14 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
 
     let cycle_length_u32 = syn::Index::from(cycle_length);
 
-    let produced = outputs.get(0).unwrap().produced.clone();
+    let produced = outputs.first().unwrap().produced.clone();
     let produced_temps: Vec<_> = produced.iter().map(|id| format_ident!("{}_temp", id.to_string())).collect();
-    let interior_assembler = outputs.get(0).unwrap().assembler.clone();
+    let interior_assembler = outputs.first().unwrap().assembler.clone();
 
     let (init, receiver, assign) = match (op, produced.is_empty()) {
         (_, true) => (quote! {}, quote! { { #interior_assembler } }, quote! {}),
         (Question, _) => (quote! { #(let mut #produced = None;)* }, quote! { let (#(#produced_temps,)*) = { #interior_assembler }; }, quote! { #(#produced = Some(#produced_temps);)* }),
-        (_,_) => (quote! { #(let mut #produced = Vec::with_capacity(((**auto).state / #cycle_length_u32) as usize);)* },
+        (_,_) => (quote! { #(let mut #produced = Vec::with_capacity((auto.automaton().state / #cycle_length_u32) as usize);)* },
                   quote! { let (#(#produced_temps,)*) = { #interior_assembler }; },
                   quote! { #(#produced.push(#produced_temps);)* })
     };
@@ -885,20 +1534,39 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
         Star | Question | Plus | Range(_, RangeRuleMax::Fixed) => 1,
         _ => 2
     };
+    let expectations = match op {
+        Star | Question | Plus | Range(_, RangeRuleMax::Fixed) => vec![StateExpectation {
+            routes: vec![next_route],
+            optional: matches!(op, Star | Question),
+            ..Default::default()
+        }],
+        _ => vec![
+            StateExpectation {
+                routes: vec![next_route],
+                ..Default::default()
+            },
+            StateExpectation {
+                routes: vec![next_route + 1],
+                optional: true,
+                ..Default::default()
+            }
+        ]
+    };
 
     let (extra_routes, assembler) = match op {
         Star | Question => {
             let output = outputs.remove(0);
-            let mut extra = vec![(output.main_route, output.end_route, Some(cycle_length))];
+            let mut extra = vec![(output.main_route, output.end_route, Some(cycle_length), output.expectations, rule.to_string())];
             extra.extend(output.extra_routes);
             (extra, quote! {
                 #init
-                if recruits < (**auto).children.len() {
-                    let auto = (**auto).children[recruits];
-                    if (**auto).route == #next_route_u32 && (**auto).lexeme_start == consumed {
+                if recruits < auto.automaton().children.len() {
+                    let next = parce::parser::next_seen(auto, lexemes, rule_start + consumed);
+                    let auto = auto.automaton().children[recruits];
+                    if auto.automaton().route == #next_route_u32 && auto.automaton().lexeme_start == next {
                         {
                             let mut recruits = 0;
-                            for _ in 0..((**auto).state / #cycle_length_u32) {
+                            for _ in 0..(auto.automaton().state / #cycle_length_u32) {
                                 #receiver
                                 #assign
                             }
@@ -911,14 +1579,14 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
         }
         o@(Plus | Range(_, RangeRuleMax::Fixed)) => {
             let output = outputs.remove(0);
-            let mut extra = vec![(output.main_route, output.end_route, if o == Plus { Some(cycle_length) } else { None })];
+            let mut extra = vec![(output.main_route, output.end_route, if o == Plus { Some(cycle_length) } else { None }, output.expectations, rule.to_string())];
             extra.extend(output.extra_routes);
             (extra, quote! {
                 #init
                 {
-                    let auto = (**auto).children[recruits];
+                    let auto = auto.automaton().children[recruits];
                     let mut recruits = 0;
-                    for _ in 0..((**auto).state / #cycle_length_u32) {
+                    for _ in 0..(auto.automaton().state / #cycle_length_u32) {
                         #receiver
                         #assign
                     }
@@ -927,19 +1595,27 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                 #returns
             })
         }
-        Range(_, max) => {
+        Range(min, max) => {
             let output1 = outputs.remove(0);
             let output2 = outputs.remove(0);
-            let mut extra = vec![(output1.main_route, output1.end_route, None), (output2.main_route, output2.end_route, if max == RangeRuleMax::Infinite { Some(cycle_length) } else { None })];
+            // Both routes match part of the range, so they're both named after all of it.
+            let range = match max {
+                RangeRuleMax::Some(max) => format!("{}{{{},{}}}", grouped(rule), min, max),
+                _ => format!("{}{{{},}}", grouped(rule), min)
+            };
+            let mut extra = vec![
+                (output1.main_route, output1.end_route, None, output1.expectations, range.clone()),
+                (output2.main_route, output2.end_route, if max == RangeRuleMax::Infinite { Some(cycle_length) } else { None }, output2.expectations, range)
+            ];
             extra.extend(output1.extra_routes);
             extra.extend(output2.extra_routes);
             (extra, quote! {
                 #init
                 for _ in 0..2 {
                     {
-                        let auto = (**auto).children[recruits];
+                        let auto = auto.automaton().children[recruits];
                         let mut recruits = 0;
-                        for _ in 0..((**auto).state / #cycle_length_u32) {
+                        for _ in 0..(auto.automaton().state / #cycle_length_u32) {
                             #receiver
                             #assign
                         }
@@ -959,7 +1635,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                     #first_state_u32 => {
                         array_vec!([AutomatonCommand; 3] =>
                             Spawn {
-                                rule: Rule::of::<#grammar>(),
+                                rule: 0,
                                 route: #next_route_u32,
                                 how_many: 1,
                                 on_victory: #on_victory
@@ -974,7 +1650,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                     #first_state_u32 => {
                         array_vec!([AutomatonCommand; 3] =>
                             Spawn {
-                                rule: Rule::of::<#grammar>(),
+                                rule: 0,
                                 route: #next_route_u32,
                                 how_many: 1,
                                 on_victory: Continuation::Advance
@@ -985,7 +1661,7 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
                     #second_state_u32 => {
                         array_vec!([AutomatonCommand; 3] =>
                             Spawn {
-                                rule: Rule::of::<#grammar>(),
+                                rule: 0,
                                 route: #second_route_u32,
                                 how_many: 1,
                                 on_victory: #on_victory
@@ -997,15 +1673,21 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
             }
         },
         states,
+        expectations,
         extra_routes,
-        end_route: match op {
-            Star | Question => quote! {
+        end_route: match (op, end_behavior) {
+            // Skipping it at the end of the input leaves the automaton on the next state, which has
+            // to be able to end too.
+            (Star | Question, EndBehavior::NotLast) => quote! {
+                #first_state_u32 => last_commands(route, state + 1),
+            },
+            (Star | Question, _) => quote! {
                 #first_state_u32 => true,
             },
-            Plus | Range(_, RangeRuleMax::Fixed) => quote! {
+            (Plus | Range(_, RangeRuleMax::Fixed), _) => quote! {
                 #first_state_u32 => false,
             },
-            Range(_,_) => quote! {
+            (Range(_,_), _) => quote! {
                 #first_state_u32 => false,
                 #second_state_u32 => false,
             }
@@ -1015,3 +1697,60 @@ fn repetition_operator(rule: &Box<ParserPattern>, op: RepetitionOperator, gramma
     })
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(args: &str, input: &str) -> String {
+        let args: ParserArgs = syn::parse_str(args).unwrap();
+        let input: syn::ItemEnum = syn::parse_str(input).unwrap();
+        match parser(args, input) {
+            Ok(tokens) => tokens.to_string(),
+            Err(ParceMacroError(_, message)) => panic!("{}", message)
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let input = r#"
+            enum Expr {
+                Num(u32) = "Number",
+                Call(String, Vec<Expr>) = "0=Word Open 1* Close",
+                Choice = "(Open | Hash (Word | Number)+){2,3}"
+            }
+        "#;
+        let first = expand("MyLexer, constructors", input);
+        for _ in 0..8 {
+            assert_eq!(expand("MyLexer, constructors", input), first);
+        }
+    }
+
+    #[test]
+    fn deterministic_attribute() {
+        let error = |input: &str| {
+            let args: ParserArgs = syn::parse_str("MyLexer").unwrap();
+            match parser(args, syn::parse_str(input).unwrap()) {
+                Ok(_) => None,
+                Err(ParceMacroError(_, message)) => Some(message)
+            }
+        };
+
+        assert_eq!(error(r#"#[deterministic] enum E { A = "Open Word", B = "Close Word", C(First) = "0", D(Second) = "0" }"#), None);
+        assert_eq!(
+            error(r#"#[deterministic] enum E { A = "Open? Word", B = "Word" }"#).unwrap(),
+            "E is #[deterministic], but productions A and B can both start with Word"
+        );
+        assert_eq!(
+            error(r#"#[deterministic] enum E { A = "Open (Word | . Close)" }"#).unwrap(),
+            "E is #[deterministic], but alternatives 0 and 1 of an or group in A can both start with Word"
+        );
+        assert_eq!(
+            error(r#"#[deterministic] enum E { A = "Open Word* Word" }"#).unwrap(),
+            "E is #[deterministic], but repeating and moving past a repetition in A can both start with Word"
+        );
+        // Fixed counts don't have a choice to make, and other rules are only checked at runtime.
+        assert_eq!(error(r#"#[deterministic] enum E { A = "Word{2} Word", B(Other) = "0" }"#), None);
+        assert_eq!(error(r#"enum E { A = "Word", B = "Word" }"#), None);
+    }
+}
@@ -0,0 +1,197 @@
+//! Turns a pattern written as Rust tokens, with `p!(...)`, into the text of the same pattern, so the
+//! rest of the macros only deal with one syntax.
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use crate::common::ParceMacroError;
+
+/// Whether `mac` is a `p!(...)` pattern, under any path ending in `p`.
+pub(crate) fn is_pattern_macro(mac: &syn::Macro) -> bool {
+    mac.path.segments.last().is_some_and(|segment| segment.ident == "p")
+}
+
+/// The text of the pattern in the tokens of a `p!(...)`.
+///
+/// Identifiers and integers are copied as they are, string and character literals become quoted
+/// literals, `(...)` are groups, `[...]` are character classes, and `{...}` are counts. The
+/// operators are copied too, and `=`, `#`, and the postfix operators are attached to their
+/// neighbours, so `0 = Number *` means `0=Number*`.
+pub(crate) fn token_pattern(tokens: TokenStream) -> Result<String, ParceMacroError> {
+    let mut pattern = Pattern::default();
+    let trees: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut i = 0;
+    while i < trees.len() {
+        match &trees[i] {
+            TokenTree::Ident(ident) => {
+                pattern.push(&ident.to_string(), false);
+                // A soft keyword, like Ident("await").
+                if let Some(TokenTree::Group(group)) = trees.get(i + 1) {
+                    if group.delimiter() == Delimiter::Parenthesis {
+                        if let Some(text) = single_string(group.stream()) {
+                            pattern.push(&format!("('{}')", text), true);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            TokenTree::Punct(punct) => match punct.as_char() {
+                '=' => {
+                    pattern.push("=", true);
+                    pattern.glue = true;
+                }
+                '#' => {
+                    pattern.push("#", false);
+                    pattern.glue = true;
+                }
+                ':' => {
+                    pattern.push(":", true);
+                    pattern.glue = true;
+                }
+                c @ ('*' | '+' | '?') => pattern.push(&c.to_string(), true),
+                c => pattern.push(&c.to_string(), false)
+            },
+            TokenTree::Literal(literal) => match syn::Lit::new(literal.clone()) {
+                syn::Lit::Str(s) => pattern.push(&quoted(&s.value()), false),
+                syn::Lit::Char(c) => pattern.push(&quoted(&c.value().to_string()), false),
+                syn::Lit::Int(n) if n.suffix().is_empty() => pattern.push(n.base10_digits(), false),
+                _ => return Err(ParceMacroError(Box::new(literal.clone()), "only string, character, and integer literals can be used in p!(...)".to_string()))
+            },
+            TokenTree::Group(group) => match group.delimiter() {
+                Delimiter::Parenthesis => pattern.push(&format!("({})", token_pattern(group.stream())?), false),
+                Delimiter::Bracket => {
+                    let class = single_string(group.stream()).unwrap_or_else(|| joined(group.stream()));
+                    pattern.push(&format!("[{}]", class), false);
+                }
+                Delimiter::Brace => pattern.push(&format!("{{{}}}", joined(group.stream())), true),
+                Delimiter::None => pattern.push(&token_pattern(group.stream())?, false)
+            }
+        }
+        i += 1;
+    }
+    if pattern.text.is_empty() {
+        return Err(ParceMacroError(Box::new(trees.into_iter().collect::<TokenStream>()), "p!() needs a pattern".to_string()));
+    }
+    Ok(pattern.text)
+}
+
+/// The paths to lexemes in the tokens of a `p!(...)`, like `crate::tokens::Integer`, with the
+/// spans they were written with. Paths to rules, after a `#`, aren't included.
+pub(crate) fn token_paths(tokens: TokenStream) -> Vec<syn::Path> {
+    let trees: Vec<TokenTree> = tokens.into_iter().collect();
+    let separator = |i: usize| matches!((trees.get(i), trees.get(i + 1), trees.get(i + 2)), (
+        Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b)), Some(TokenTree::Ident(_))
+    ) if a.as_char() == ':' && b.as_char() == ':');
+    let mut paths = vec![];
+    let mut i = 0;
+    while i < trees.len() {
+        match &trees[i] {
+            TokenTree::Group(group) if matches!(group.delimiter(), Delimiter::Parenthesis | Delimiter::None) => {
+                paths.extend(token_paths(group.stream()));
+                i += 1;
+            }
+            _ if matches!(trees[i], TokenTree::Ident(_)) || separator(i) => {
+                let mut j = if separator(i) { i + 3 } else { i + 1 };
+                while separator(j) {
+                    j += 3;
+                }
+                let rule = i > 0 && matches!(&trees[i - 1], TokenTree::Punct(p) if p.as_char() == '#');
+                let lexeme = matches!(&trees[j - 1], TokenTree::Ident(ident) if ident.to_string().starts_with(char::is_uppercase));
+                if j - i > 1 && !rule && lexeme {
+                    let path: TokenStream = trees[i..j].iter().cloned().collect();
+                    paths.extend(syn::parse2::<syn::Path>(path).ok());
+                }
+                i = j;
+            }
+            _ => i += 1
+        }
+    }
+    paths
+}
+
+#[derive(Default)]
+struct Pattern {
+    text: String,
+    /// Whether the next piece is attached to the last one.
+    glue: bool
+}
+
+impl Pattern {
+    fn push(&mut self, piece: &str, attached: bool) {
+        if !self.text.is_empty() && !attached && !self.glue {
+            self.text.push(' ');
+        }
+        self.text.push_str(piece);
+        self.glue = false;
+    }
+}
+
+/// The value of `tokens` if they are a single string literal.
+fn single_string(tokens: TokenStream) -> Option<String> {
+    let mut trees = tokens.into_iter();
+    match (trees.next(), trees.next()) {
+        (Some(TokenTree::Literal(literal)), None) => match syn::Lit::new(literal) {
+            syn::Lit::Str(s) => Some(s.value()),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// The text of `tokens` without any spaces, like `a-z` for the tokens of `[a-z]`.
+fn joined(tokens: TokenStream) -> String {
+    tokens.to_string().chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// `text` as a quoted literal. Quotes and backslashes are matched with classes instead of
+/// escapes, since lexer literals don't have escapes.
+fn quoted(text: &str) -> String {
+    if !text.contains(['\'', '\\']) {
+        return format!("'{}'", text);
+    }
+    let pieces: Vec<String> = text.chars().map(|c| match c {
+        '\'' => r"[\x27]".to_string(),
+        '\\' => r"[\x5C]".to_string(),
+        c => format!("'{}'", c)
+    }).collect();
+    format!("({})", pieces.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::ToTokens;
+    use crate::discriminants::strip_paths;
+
+    fn text(tokens: &str) -> String {
+        match token_pattern(tokens.parse().unwrap()) {
+            Ok(text) => text,
+            Err(ParceMacroError(_, message)) => panic!("{}", message)
+        }
+    }
+
+    #[test]
+    fn same_as_strings() {
+        assert_eq!(text(r#"' ' | "\t" | "ab""#), "' ' | '\t' | 'ab'");
+        assert_eq!(text("[a-z_] [0-9]{2,} A? (B | C)*"), "[a-z_] [0-9]{2,} A? (B | C)*");
+        assert_eq!(text(r#"[" \n"]+"#), "[ \n]+");
+        assert_eq!(text("name = Ident 0 = #Rule args = (A B)* ! Ident(\"await\")"), "name=Ident 0=#Rule args=(A B)* ! Ident('await')");
+        assert_eq!(text(r#""it's" '\\'"#), r"('i' 't' [\x27] 's') ([\x5C])");
+        assert!(token_pattern("1.5".parse().unwrap()).is_err());
+        assert!(token_pattern("".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn paths() {
+        let tokens = "0 = crate::tokens::Integer (::lexemes::Plus | Plus)* #ast::Expr Ident(\"x\") crate::field";
+        let paths: Vec<String> = token_paths(tokens.parse().unwrap()).iter()
+            .map(|path| path.to_token_stream().to_string().replace(' ', ""))
+            .collect();
+        assert_eq!(paths, vec!["crate::tokens::Integer", "::lexemes::Plus"]);
+
+        let text = text(tokens);
+        assert_eq!(text, "0=crate::tokens::Integer (::lexemes::Plus | Plus)* #ast::Expr Ident('x') crate::field");
+        let (stripped, paths) = strip_paths(&text);
+        assert_eq!(stripped, "0=Integer (Plus | Plus)* #ast::Expr Ident('x') crate::field");
+        assert_eq!(paths, vec!["crate::tokens::Integer", "::lexemes::Plus"]);
+        assert_eq!(strip_paths("'a::B' [a::B] A::B").0, "'a::B' [a::B] B");
+    }
+}
@@ -0,0 +1,258 @@
+//! Generates the Validate impl of a rule, which checks each field of a node against what the
+//! production's pattern could have filled it with.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, format_ident};
+use syn::Path;
+use crate::common::RangeRuleMax;
+use crate::parser::{ParserPattern, VariantInfo};
+
+/// How a pattern fills one field.
+#[derive(Default)]
+struct Fills {
+    /// Whether the field is filled by another rule, instead of by text.
+    rule: bool,
+    /// The patterns the field is assigned from.
+    assigned: Vec<String>,
+    /// The assignments that are a single lexeme, which can be checked by lexing the text again, with
+    /// the text of the lexeme if it is a keyword.
+    lexemes: Vec<(String, Option<String>)>
+}
+
+/// The fewest and most times `pattern` fills the field `field` (a name, or an index for unnamed
+/// fields), with no most if it is `None`. What fills it is added to `out`.
+fn fills(pattern: &ParserPattern, field: &str, out: &mut Fills) -> (usize, Option<usize>) {
+    use ParserPattern::*;
+    let times = |(min, max): (usize, Option<usize>), by_min: usize, by_max: Option<usize>| {
+        (min * by_min, match (max, by_max) {
+            (Some(0), _) | (_, Some(0)) => Some(0),
+            (Some(max), Some(by_max)) => Some(max * by_max),
+            _ => None
+        })
+    };
+    match pattern {
+        BareUnnamedField(n) if n.to_string() == field => {
+            out.rule = true;
+            (1, Some(1))
+        }
+        BareNamedField(name) if name == field => {
+            out.rule = true;
+            (1, Some(1))
+        }
+        AssignUnnamedField(n, p) if n.to_string() == field => {
+            assigned(p, out);
+            (1, Some(1))
+        }
+        AssignNamedField(name, p) if name == field => {
+            assigned(p, out);
+            (1, Some(1))
+        }
+        And(patterns) => patterns.iter().fold((0, Some(0)), |(min, max), p| {
+            let (p_min, p_max) = fills(p, field, out);
+            (min + p_min, max.zip(p_max).map(|(a, b)| a + b))
+        }),
+        Or(patterns) => patterns.iter().map(|p| fills(p, field, out)).reduce(|(min, max), (p_min, p_max)| {
+            (min.min(p_min), max.zip(p_max).map(|(a, b)| a.max(b)))
+        }).unwrap_or((0, Some(0))),
+        Star(p) => times(fills(p, field, out), 0, None),
+        Plus(p) => times(fills(p, field, out), 1, None),
+        Question(p) => times(fills(p, field, out), 0, Some(1)),
+        Range(p, min, max) => {
+            let by_max = match max {
+                RangeRuleMax::Infinite => None,
+                RangeRuleMax::Fixed => Some(*min),
+                RangeRuleMax::Some(max) => Some(*max)
+            };
+            times(fills(p, field, out), *min, by_max)
+        }
+        _ => (0, Some(0))
+    }
+}
+
+fn assigned(pattern: &ParserPattern, out: &mut Fills) {
+    out.assigned.push(pattern.to_string());
+    match pattern {
+        ParserPattern::Lexeme(name) => out.lexemes.push((name.clone(), None)),
+        ParserPattern::Keyword(name, text) => out.lexemes.push((name.clone(), Some(text.clone()))),
+        _ => {}
+    }
+}
+
+/// The type inside `ty`, if it is `wrapper<inner>`.
+fn inner<'a>(ty: &'a syn::Type, wrapper: &str) -> Option<&'a syn::Type> {
+    if let syn::Type::Path(syn::TypePath { path, .. }) = ty {
+        if let Some(seg) = path.segments.last() {
+            if let syn::PathArguments::AngleBracketed(args) = &seg.arguments {
+                if let (true, 1, Some(syn::GenericArgument::Type(inner))) = (seg.ident == wrapper, args.args.len(), args.args.first()) {
+                    return Some(inner);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn is_text(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(syn::TypeReference { elem, .. }) => matches!(&**elem, syn::Type::Path(p) if p.path.is_ident("str")),
+        syn::Type::Path(p) => p.path.is_ident("String"),
+        _ => false
+    }
+}
+
+/// Everything the code checking one field needs to know.
+struct Field<'a> {
+    fills: &'a Fills,
+    rule: &'a str,
+    production: &'a str,
+    lexer: &'a Path
+}
+
+impl Field<'_> {
+    /// The code adding a violation of `kind` for the part of the field named by `label`.
+    fn violation(&self, label: TokenStream2, kind: TokenStream2) -> TokenStream2 {
+        let (rule, production) = (self.rule, self.production);
+        quote! {
+            violations.push(Violation { path: path.clone(), rule: #rule, production: #production, field: #label, kind: #kind });
+        }
+    }
+}
+
+/// The code checking `value` (a reference to a `ty`), which is the field or part of it named by
+/// the `String` expression `label`. `depth` keeps the names of nested loops apart.
+fn check(ty: &syn::Type, value: TokenStream2, label: TokenStream2, field: &Field, depth: usize) -> TokenStream2 {
+    let item = format_ident!("item_{}", depth);
+    let index = format_ident!("index_{}", depth);
+    if let Some(inner) = inner(ty, "Vec") {
+        let checked = check(inner, quote! { #item }, quote! { format!("{}[{}]", #label, #index) }, field, depth + 1);
+        quote! {
+            for (#index, #item) in #value.iter().enumerate() {
+                #checked
+            }
+        }
+    } else if let Some(inner) = inner(ty, "Option") {
+        let checked = check(inner, quote! { #item }, label, field, depth + 1);
+        quote! {
+            if let Some(#item) = #value {
+                #checked
+            }
+        }
+    } else if let Some(inner) = inner(ty, "Box") {
+        check(inner, quote! { &**#value }, label, field, depth)
+    } else if let Some(inner) = inner(ty, "Recovered") {
+        let checked = check(inner, quote! { #item }, label.clone(), field, depth + 1);
+        let violation = field.violation(label, quote! { ViolationKind::Error(error.clone()) });
+        quote! {
+            match #value {
+                parce::parser::recovery::Recovered::Parsed(#item) => { #checked }
+                parce::parser::recovery::Recovered::Error(error) => { #violation }
+            }
+        }
+    } else if field.fills.rule {
+        let value = match ty {
+            syn::Type::Reference(_) => quote! { *#value },
+            _ => value
+        };
+        let production = field.production;
+        quote! {
+            path.push(format!("{}.{}", #production, #label));
+            Validate::validate_into(#value, path, violations);
+            path.pop();
+        }
+    } else if is_text(ty) && !field.fills.lexemes.is_empty() && field.fills.lexemes.len() == field.fills.assigned.len() {
+        let lexer = field.lexer;
+        let matches = field.fills.lexemes.iter().map(|(name, keyword)| match keyword {
+            Some(keyword) => quote! { text == #keyword },
+            None => {
+                let ident = format_ident!("{}", name);
+                quote! { lexes_as(#lexer::default(), text, <#lexer as parce::internal_prelude::Lexer>::Lexemes::#ident) }
+            }
+        });
+        let mut lexemes = field.fills.assigned.clone();
+        lexemes.dedup();
+        let lexemes = lexemes.join(" or ");
+        let violation = field.violation(label, quote! { ViolationKind::Text { value: text.to_string(), lexeme: #lexemes } });
+        quote! {
+            let text: &str = AsRef::<str>::as_ref(#value);
+            if !(#(#matches)||*) {
+                #violation
+            }
+        }
+    } else {
+        quote! {}
+    }
+}
+
+pub(crate) fn validate(input: &syn::ItemEnum, variants: &[VariantInfo], lexer: &Path) -> TokenStream2 {
+    let enum_ident = &input.ident;
+    let enum_name = enum_ident.to_string();
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+
+    let arms = input.variants.iter().zip(variants).map(|(variant, info)| {
+        let ident = &variant.ident;
+        let production = ident.to_string();
+        let (pattern, bindings): (TokenStream2, Vec<_>) = match &variant.fields {
+            syn::Fields::Unit => (quote! { #enum_ident::#ident }, vec![]),
+            syn::Fields::Unnamed(fields) => {
+                let bindings: Vec<_> = (0..fields.unnamed.len()).map(|i| format_ident!("field_{}", i)).collect();
+                let checked = fields.unnamed.iter().zip(&bindings).enumerate()
+                    .map(|(i, (field, binding))| (i.to_string(), &field.ty, binding.clone()))
+                    .collect();
+                (quote! { #enum_ident::#ident(#(#bindings),*) }, checked)
+            }
+            syn::Fields::Named(fields) => {
+                let names: Vec<_> = fields.named.iter().map(|f| f.ident.clone().unwrap()).collect();
+                let checked = fields.named.iter().zip(&names)
+                    .map(|(field, name)| (name.to_string(), &field.ty, name.clone()))
+                    .collect();
+                (quote! { #enum_ident::#ident { #(#names),* } }, checked)
+            }
+        };
+        let checks = bindings.into_iter().map(|(name, ty, binding)| {
+            let mut fills_out = Fills::default();
+            let (min, max) = fills(&info.pattern, &name, &mut fills_out);
+            let field = Field { fills: &fills_out, rule: &enum_name, production: &production, lexer };
+            let count = match (inner(ty, "Vec"), inner(ty, "Option")) {
+                (Some(_), _) => Some(quote! { #binding.len() }),
+                (_, Some(_)) => Some(quote! { #binding.is_some() as usize }),
+                _ => None
+            };
+            let count = count.filter(|_| min > 0 || max.is_some()).map(|len| {
+                let (wrong, max) = match max {
+                    Some(max) => (quote! { !(#min..=#max).contains(&len) }, quote! { Some(#max) }),
+                    None => (quote! { len < #min }, quote! { None })
+                };
+                let violation = field.violation(quote! { #name.to_string() }, quote! { ViolationKind::Count { len, min: #min, max: #max } });
+                quote! {
+                    let len = #len;
+                    if #wrong {
+                        #violation
+                    }
+                }
+            });
+            let checked = check(ty, quote! { #binding }, quote! { #name.to_string() }, &field, 0);
+            quote! {
+                #count
+                #checked
+            }
+        });
+        quote! {
+            #pattern => {
+                #(#checks)*
+            }
+        }
+    });
+
+    quote! {
+        impl #impl_generics parce::parser::validate::Validate for #enum_ident #ty_generics {
+            #[allow(unused_variables)]
+            fn validate_into(&self, path: &mut Vec<String>, violations: &mut Vec<parce::parser::validate::Violation>) {
+                use parce::parser::validate::*;
+
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Lexing and parsing throughput on a few representative grammars. Run with `cargo bench`.
+//!
+//! Each grammar gets a lexing benchmark and a parsing benchmark on the same generated input, so a
+//! regression can be pinned on the lexer or the automaton engine. The assembly grammar is made of
+//! wide choices, so comparing it with and without `--features wide-automata` shows what spilling
+//! the automata's small vectors costs.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use parce::prelude::*;
+use parce::parser::Parseable;
+
+mod json {
+    use parce::prelude::*;
+
+    #[lexer(JsonLexer)]
+    pub enum JsonLexeme {
+        LBrace = '{',
+        RBrace = '}',
+        LBracket = '[',
+        RBracket = ']',
+        Colon = ':',
+        Comma = ',',
+        True = "'true'",
+        False = "'false'",
+        Null = "'null'",
+        Str = r#" '"' ([^"\x5c] | [\x5c] .)* '"' "#,
+        Number = "'-'? Digit+ ('.' Digit+)?",
+        #[frag] Digit = "[0-9]",
+        #[skip] Space = "[ \t\n\r]"
+    }
+
+    #[parser(JsonLexer)]
+    pub enum Value {
+        Object = "LBrace (#Member (Comma #Member)*)? RBrace",
+        Array = "LBracket (#Value (Comma #Value)*)? RBracket",
+        Str = "Str",
+        Number = "Number",
+        True = "True",
+        False = "False",
+        Null = "Null"
+    }
+
+    #[parser(JsonLexer)]
+    pub enum Member {
+        Member = "Str Colon #Value"
+    }
+
+    /// An array of `n` small records.
+    pub fn input(n: usize) -> String {
+        let records: Vec<String> = (0..n).map(|i| format!(
+            r#"{{"id": {}, "name": "item \"{}\"", "price": {}.5, "tags": ["a", "b"], "active": true, "parent": null}}"#,
+            i, i, i * 3
+        )).collect();
+        format!("[{}]", records.join(",\n"))
+    }
+}
+
+mod c_like {
+    use parce::prelude::*;
+
+    #[lexer(CLexer)]
+    pub enum CLexeme {
+        Int = "'int'",
+        If = "'if'",
+        Else = "'else'",
+        While = "'while'",
+        Return = "'return'",
+        Ident = "Letter (Letter | Digit)*",
+        Number = "Digit+",
+        Op = "'+' | '-' | '*' | '/' | '<' | '=='",
+        Assign = '=',
+        LParen = '(',
+        RParen = ')',
+        LBrace = '{',
+        RBrace = '}',
+        Semi = ';',
+        Comma = ',',
+        #[frag] Letter = "[a-zA-Z_]",
+        #[frag] Digit = "[0-9]",
+        #[skip] Space = "[ \t\n\r]",
+        #[skip] Comment = "'//' [^\n]*"
+    }
+
+    #[parser(CLexer)]
+    pub enum Program {
+        Functions = "#Function*"
+    }
+
+    #[parser(CLexer)]
+    pub enum Function {
+        Function = "Int Ident LParen (Int Ident (Comma Int Ident)*)? RParen #Block"
+    }
+
+    #[parser(CLexer)]
+    pub enum Block {
+        Block = "LBrace #Statement* RBrace"
+    }
+
+    #[parser(CLexer)]
+    pub enum Statement {
+        Declare = "Int Ident (Assign #Expr)? Semi",
+        Set = "Ident Assign #Expr Semi",
+        If = "If LParen #Expr RParen #Block (Else #Block)?",
+        While = "While LParen #Expr RParen #Block",
+        Return = "Return #Expr Semi"
+    }
+
+    #[parser(CLexer)]
+    pub enum Expr {
+        Expr = "#Term (Op #Term)*"
+    }
+
+    #[parser(CLexer)]
+    pub enum Term {
+        Number = "Number",
+        Variable = "Ident",
+        Call = "Ident LParen (#Expr (Comma #Expr)*)? RParen",
+        Group = "LParen #Expr RParen"
+    }
+
+    /// `n` small functions.
+    pub fn input(n: usize) -> String {
+        (0..n).map(|i| format!("
+            // function number {i}
+            int f{i}(int a, int b) {{
+                int total = 0;
+                while (a < b) {{
+                    total = total + a * {i};
+                    a = a + 1;
+                }}
+                if (total == 0) {{ return f{i}(b, a); }} else {{ return (total - b) / 2; }}
+            }}
+        ", i = i)).collect()
+    }
+}
+
+mod brainfuck {
+    use parce::prelude::*;
+
+    #[lexer(BrainfuckLexer)]
+    pub enum BrainfuckLexeme {
+        Inc = '+',
+        Dec = '-',
+        Left = '<',
+        Right = '>',
+        Output = '.',
+        Input = ',',
+        Open = '[',
+        Close = ']',
+        #[skip] Space = "[ \t\n\r]"
+    }
+
+    #[parser(BrainfuckLexer)]
+    pub enum Program {
+        Program = "#Instruction*"
+    }
+
+    #[parser(BrainfuckLexer)]
+    pub enum Instruction {
+        Inc = "Inc",
+        Dec = "Dec",
+        Left = "Left",
+        Right = "Right",
+        Output = "Output",
+        Input = "Input",
+        Loop = "Open #Instruction* Close"
+    }
+
+    /// `n` copies of a hello world program.
+    pub fn input(n: usize) -> String {
+        "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.\n"
+            .repeat(n)
+    }
+}
+
+mod assembly {
+    use parce::prelude::*;
+
+    #[lexer(AssemblyLexer)]
+    pub enum AssemblyLexeme {
+        Mnemonic = "[a-z]+",
+        Register = "'r' Digit",
+        Number = "'#' Digit+",
+        Comma = ',',
+        Newline = '\n',
+        #[frag] Digit = "[0-9]",
+        #[skip] Space = "[ \t]"
+    }
+
+    #[parser(AssemblyLexer)]
+    pub enum Program {
+        Program = "#Instruction*"
+    }
+
+    /// Every line is a choice between ten productions, and every operand between three, which is
+    /// wider than the automata store inline without the `wide-automata` feature.
+    #[parser(AssemblyLexer)]
+    pub enum Instruction {
+        Mov = "Mnemonic('mov') #Operand Comma #Operand Newline",
+        Add = "Mnemonic('add') #Operand Comma #Operand Newline",
+        Sub = "Mnemonic('sub') #Operand Comma #Operand Newline",
+        Mul = "Mnemonic('mul') #Operand Comma #Operand Newline",
+        And = "Mnemonic('and') #Operand Comma #Operand Newline",
+        Or = "Mnemonic('or') #Operand Comma #Operand Newline",
+        Jmp = "Mnemonic('jmp') #Operand Newline",
+        Push = "Mnemonic('push') #Operand Newline",
+        Pop = "Mnemonic('pop') #Operand Newline",
+        Ret = "Mnemonic('ret') Newline"
+    }
+
+    #[parser(AssemblyLexer)]
+    pub enum Operand {
+        Register = "Register",
+        Immediate = "Number",
+        Label = "Mnemonic"
+    }
+
+    /// `n` copies of a small routine.
+    pub fn input(n: usize) -> String {
+        "mov r1, #10\nadd r1, r2\npush r1\nmul r2, #3\nand r2, r1\nor r3, r2\npop r1\nsub r1, #1\njmp loop\nret\n"
+            .repeat(n)
+    }
+}
+
+fn bench_grammar<O: Parseable>(c: &mut Criterion, name: &str, input: &'static str) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_with_input(BenchmarkId::new("lex", input.len()), input, |b, input| {
+        b.iter(|| O::default_lexer().lex(input).unwrap())
+    });
+    group.bench_with_input(BenchmarkId::new("parse", input.len()), input, |b, input| {
+        let mut session = ParseSession::new(O::default_lexer());
+        b.iter(|| session.parse_all::<O>(input).unwrap())
+    });
+    group.finish();
+}
+
+fn engine(c: &mut Criterion) {
+    bench_grammar::<json::Value>(c, "json", Box::leak(json::input(50).into_boxed_str()));
+    bench_grammar::<c_like::Program>(c, "c_like", Box::leak(c_like::input(20).into_boxed_str()));
+    bench_grammar::<brainfuck::Program>(c, "brainfuck", Box::leak(brainfuck::input(20).into_boxed_str()));
+    bench_grammar::<assembly::Program>(c, "assembly", Box::leak(assembly::input(50).into_boxed_str()));
+}
+
+criterion_group!(benches, engine);
+criterion_main!(benches);
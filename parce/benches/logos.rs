@@ -0,0 +1,107 @@
+//! A generated lexer against an equivalent logos lexer, on the same JSON input. Run with
+//! `cargo bench --features logos --bench logos`.
+//!
+//! Both lexers produce the same lexemes, and the logos one is wrapped with `logos_lexer!`, so the
+//! last benchmark shows what parsing costs on top of the faster lexer.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use parce::prelude::*;
+use parce::lexer::logos::Logos;
+
+#[lexer(JsonLexer)]
+pub enum JsonLexeme {
+    LBrace = '{',
+    RBrace = '}',
+    LBracket = '[',
+    RBracket = ']',
+    Colon = ':',
+    Comma = ',',
+    True = "'true'",
+    False = "'false'",
+    Null = "'null'",
+    Str = r#" '"' ([^"\x5c] | [\x5c] .)* '"' "#,
+    Number = "'-'? Digit+ ('.' Digit+)?",
+    #[frag] Digit = "[0-9]",
+    #[skip] Space = "[ \t\n\r]"
+}
+
+#[derive(Logos, Debug, Copy, Clone, Eq, PartialEq)]
+#[logos(skip r"[ \t\n\r]+")]
+pub enum JsonToken {
+    #[token("{")]
+    LBrace,
+    #[token("}")]
+    RBrace,
+    #[token("[")]
+    LBracket,
+    #[token("]")]
+    RBracket,
+    #[token(":")]
+    Colon,
+    #[token(",")]
+    Comma,
+    #[token("true")]
+    True,
+    #[token("false")]
+    False,
+    #[token("null")]
+    Null,
+    #[regex(r#""([^"\\]|\\.)*""#)]
+    Str,
+    #[regex(r"-?[0-9]+(\.[0-9]+)?")]
+    Number
+}
+
+parce::logos_lexer!(pub JsonTokenLexer for JsonToken {
+    LBrace, RBrace, LBracket, RBracket, Colon, Comma, True, False, Null, Str, Number
+});
+
+#[parser(JsonTokenLexer)]
+pub enum Value {
+    Object = "LBrace (#Member (Comma #Member)*)? RBrace",
+    Array = "LBracket (#Value (Comma #Value)*)? RBracket",
+    Str = "Str",
+    Number = "Number",
+    True = "True",
+    False = "False",
+    Null = "Null"
+}
+
+#[parser(JsonTokenLexer)]
+pub enum Member {
+    Member = "Str Colon #Value"
+}
+
+/// An array of `n` small records.
+fn input(n: usize) -> String {
+    let records: Vec<String> = (0..n).map(|i| format!(
+        r#"{{"id": {}, "name": "item \"{}\"", "price": {}.5, "tags": ["a", "b"], "active": true, "parent": null}}"#,
+        i, i, i * 3
+    )).collect();
+    format!("[{}]", records.join(",\n"))
+}
+
+fn logos(c: &mut Criterion) {
+    let input = input(50);
+    assert_eq!(
+        JsonLexer::default().lex(&input).unwrap().iter().map(|l| (l.start, l.len)).collect::<Vec<_>>(),
+        JsonTokenLexer.lex(&input).unwrap().iter().map(|l| (l.start, l.len)).collect::<Vec<_>>()
+    );
+
+    let mut group = c.benchmark_group("logos");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_with_input(BenchmarkId::new("lex_parce", input.len()), &input, |b, input| {
+        b.iter(|| JsonLexer::default().lex(input).unwrap())
+    });
+    group.bench_with_input(BenchmarkId::new("lex_logos", input.len()), &input, |b, input| {
+        b.iter(|| JsonTokenLexer.lex(input).unwrap())
+    });
+    group.bench_with_input(BenchmarkId::new("parse_logos", input.len()), &input, |b, input| {
+        let mut session = ParseSession::new(JsonTokenLexer);
+        b.iter(|| session.parse_all::<Value>(input).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, logos);
+criterion_main!(benches);
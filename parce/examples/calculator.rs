@@ -0,0 +1,188 @@
+//! An arithmetic calculator, and the regression test for how grammars express precedence and
+//! associativity.
+//!
+//! Run it with `cargo run --example calculator -- '<expression>'`, or without an argument to
+//! evaluate a few samples. `cargo test` runs the tests at the bottom.
+//!
+//! The engine doesn't support left-recursive rules (a rule like `Sum = "Sum Plus Term"` never
+//! finishes matching), and has no precedence or associativity annotations, so precedence comes from
+//! layering the rules, loosest first: a [Sum] is made of [Product]s, which are made of [Unary]
+//! expressions, and so on down to [Atom]. Left-associative operators are repetitions, folded from
+//! the left when evaluating. Right-associative ones, like `^`, recurse on their right-hand side.
+
+use parce::prelude::*;
+
+#[lexer(CalcLexer)]
+pub enum CalcLexeme {
+    Number = "Digit+ ('.' Digit+)?",
+    Plus = '+',
+    Minus = '-',
+    Star = '*',
+    Slash = '/',
+    Caret = '^',
+    LParen = '(',
+    RParen = ')',
+    #[frag] Digit = "[0-9]",
+    #[skip] Space = "[ \t]"
+}
+
+/// Addition and subtraction, the loosest operators. Left-associative.
+#[parser(CalcLexer)]
+pub enum Sum {
+    Sum(Box<Product>, Vec<SumTail>) = "0 1*"
+}
+
+#[parser(CalcLexer)]
+pub enum SumTail {
+    Add(Product) = "Plus 0",
+    Sub(Product) = "Minus 0"
+}
+
+/// Multiplication and division. Left-associative.
+#[parser(CalcLexer)]
+pub enum Product {
+    Product(Box<Unary>, Vec<ProductTail>) = "0 1*"
+}
+
+#[parser(CalcLexer)]
+pub enum ProductTail {
+    Mul(Unary) = "Star 0",
+    Div(Unary) = "Slash 0"
+}
+
+/// Negation. Binds looser than `^`, so `-2^2` is `-(2^2)`.
+#[parser(CalcLexer)]
+pub enum Unary {
+    Neg(Box<Unary>) = "Minus 0",
+    Power(Power) = "0"
+}
+
+/// Exponentiation, the tightest operator. Right-associative, and its exponent can be negated.
+#[parser(CalcLexer)]
+pub enum Power {
+    Power(Atom, Option<Box<Unary>>) = "0 (Caret 1)?"
+}
+
+#[parser(CalcLexer)]
+pub enum Atom {
+    Number(f64) = "0=Number",
+    Group(Box<Sum>) = "LParen 0 RParen"
+}
+
+impl Sum {
+    pub fn eval(&self) -> f64 {
+        let Sum::Sum(first, rest) = self;
+        rest.iter().fold(first.eval(), |acc, tail| match tail {
+            SumTail::Add(p) => acc + p.eval(),
+            SumTail::Sub(p) => acc - p.eval()
+        })
+    }
+}
+
+impl Product {
+    pub fn eval(&self) -> f64 {
+        let Product::Product(first, rest) = self;
+        rest.iter().fold(first.eval(), |acc, tail| match tail {
+            ProductTail::Mul(u) => acc * u.eval(),
+            ProductTail::Div(u) => acc / u.eval()
+        })
+    }
+}
+
+impl Unary {
+    pub fn eval(&self) -> f64 {
+        match self {
+            Unary::Neg(inner) => -inner.eval(),
+            Unary::Power(power) => power.eval()
+        }
+    }
+}
+
+impl Power {
+    pub fn eval(&self) -> f64 {
+        let Power::Power(base, exponent) = self;
+        match exponent {
+            Some(exponent) => base.eval().powf(exponent.eval()),
+            None => base.eval()
+        }
+    }
+}
+
+impl Atom {
+    pub fn eval(&self) -> f64 {
+        match self {
+            Atom::Number(n) => *n,
+            Atom::Group(inner) => inner.eval()
+        }
+    }
+}
+
+const SAMPLES: &[&str] = &["1 + 2 * 3", "(1 + 2) * 3", "10 - 4 - 3", "2 ^ 3 ^ 2", "-2 ^ 2"];
+
+fn main() {
+    let inputs = match std::env::args().nth(1) {
+        Some(input) => vec![input],
+        None => SAMPLES.iter().map(|s| s.to_string()).collect()
+    };
+    for input in inputs {
+        match input.parse::<Sum>() {
+            Ok(expr) => println!("{} = {}", input, expr.eval()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> f64 {
+        input.parse::<Sum>().unwrap().eval()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("2 + 3 * 4"), 14.0);
+        assert_eq!(eval("2 * 3 + 4"), 10.0);
+        assert_eq!(eval("(2 + 3) * 4"), 20.0);
+        assert_eq!(eval("2 * 3 ^ 2"), 18.0);
+        assert_eq!(eval("-2 ^ 2"), -4.0);
+        assert_eq!(eval("(-2) ^ 2"), 4.0);
+        assert_eq!(eval("1.5 * -2"), -3.0);
+    }
+
+    #[test]
+    fn associativity() {
+        assert_eq!(eval("1 - 2 - 3"), -4.0);
+        assert_eq!(eval("16 / 4 / 2"), 2.0);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("2 ^ -1"), 0.5);
+        assert_eq!(eval("- - 3"), 3.0);
+
+        // The tree keeps the operands of a chain flat, in input order.
+        let Sum::Sum(_, rest) = "1 - 2 + 3".parse::<Sum>().unwrap();
+        assert!(matches!(rest.as_slice(), [SumTail::Sub(_), SumTail::Add(_)]));
+    }
+
+    #[test]
+    fn long_chains() {
+        let chain = vec!["1"; 200].join(" - ");
+        assert_eq!(eval(&chain), -198.0);
+        let nested = format!("{}7{}", "(".repeat(50), ")".repeat(50));
+        assert_eq!(eval(&nested), 7.0);
+        assert_eq!(eval(&format!("2{}", " ^ 1".repeat(100))), 2.0);
+    }
+
+    #[test]
+    fn errors() {
+        // An operator without a right-hand side is reported at the operator.
+        assert_eq!("1 + ".parse::<Sum>().unwrap_err().start, 2);
+        assert_eq!("1 * * 2".parse::<Sum>().unwrap_err().start, 2);
+        assert!("(1 + 2".parse::<Sum>().is_err());
+        assert!("1 + 2)".parse::<Sum>().is_err());
+        assert_eq!("1 % 2".parse::<Sum>().unwrap_err().start, 2);
+    }
+}
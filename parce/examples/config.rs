@@ -0,0 +1,188 @@
+//! An INI-like config format, with every kind of error reported by line and column.
+//!
+//! Run it with `cargo run --example config -- <path>`, or without an argument to load a sample
+//! file. `cargo test` runs the tests at the bottom, which check where lex, parse, and conversion
+//! errors are reported in a multi-line file, and how they're rendered.
+//!
+//! ```text
+//! # Comments run to the end of the line.
+//! name = "demo"
+//!
+//! [server]
+//! port = 8080
+//! debug = true
+//! ```
+
+use parce::prelude::*;
+use parce::convert::{IntLiteral, JsonString};
+
+#[lexer(ConfigLexer)]
+pub enum ConfigLexeme {
+    LBracket = '[',
+    RBracket = ']',
+    Equals = '=',
+    Newline = '\n',
+    Bool = "'true' | 'false'",
+    Key = "Letter (Letter | Digit | '-' | '.')*",
+    Int = "'-'? Digit+",
+    Str = r#" '"' ([^"\x5c\n] | [\x5c] .)* '"' "#,
+    #[frag] Letter = "[a-zA-Z_]",
+    #[frag] Digit = "[0-9]",
+    #[skip] Space = "[ \t\r]",
+    #[skip] Comment = "'#' [^\n]*"
+}
+
+/// A whole file: the entries before the first section header, then the sections.
+#[parser(ConfigLexer)]
+pub enum Config {
+    Config { globals: Vec<Entry>, sections: Vec<Section> } = "Newline* globals* sections*"
+}
+
+/// A `[name]` header and the entries under it.
+#[parser(ConfigLexer)]
+pub enum Section {
+    Section { name: String, entries: Vec<Entry> } = "LBracket name=Key RBracket Newline+ entries*"
+}
+
+/// One `key = value` line, and any blank lines after it.
+#[parser(ConfigLexer)]
+pub enum Entry {
+    Entry { key: String, value: ConfigValue } = "key=Key Equals value Newline+"
+}
+
+#[parser(ConfigLexer)]
+pub enum ConfigValue {
+    Str(JsonString) = "0=Str",
+    Int(IntLiteral<i64>) = "0=Int",
+    Bool(bool) = "0=Bool"
+}
+
+impl Config {
+    /// Looks up `key` in `section`, or among the entries before the first section if `section` is
+    /// `None`. If the key is set more than once, the last one wins.
+    pub fn get(&self, section: Option<&str>, key: &str) -> Option<&ConfigValue> {
+        let Config::Config { globals, sections } = self;
+        let entries = match section {
+            None => globals,
+            Some(section) => sections.iter().rev()
+                .map(|Section::Section { name, entries }| (name, entries))
+                .find(|(name, _)| *name == section)?.1
+        };
+        entries.iter().rev()
+            .find(|Entry::Entry { key: k, .. }| k == key)
+            .map(|Entry::Entry { value, .. }| value)
+    }
+}
+
+/// Loads a config file, with a newline added to the end if it's missing.
+///
+/// The literals are checked before parsing, so every bad number or escape in the file is
+/// reported at once. Otherwise the first lex or parse error is reported.
+pub fn load(text: &str) -> Result<Config, Vec<ParceError>> {
+    let mut text = text.to_string();
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    let lexemes = ConfigLexer::default().lex(&text).map_err(|e| vec![e])?;
+    let mut errors = parce::convert::check::<IntLiteral<i64>, _>(&lexemes, &text, &[ConfigLexeme::Int]);
+    errors.extend(parce::convert::check::<JsonString, _>(&lexemes, &text, &[ConfigLexeme::Str]));
+    if !errors.is_empty() {
+        errors.sort_by_key(|e| e.start);
+        return Err(errors);
+    }
+    text.parse().map_err(|e| vec![e])
+}
+
+const SAMPLE: &str = r#"# A sample config.
+name = "demo"
+retries = 3
+
+[server]
+host = "localhost"
+port = 8080
+debug = true
+
+[paths]
+log = "C:\\logs\\demo.log"
+"#;
+
+fn main() {
+    let text = match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(path).expect("couldn't read the config file"),
+        None => SAMPLE.to_string()
+    };
+    match load(&text) {
+        Ok(config) => println!("{:#?}", config),
+        Err(errors) => {
+            for e in errors {
+                let (line, col) = e.line_col();
+                eprintln!("error at {}:{}\n{}\n", line, col, e);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parce::error::ParceErrorInfo;
+
+    fn locations(text: &str) -> Vec<(usize, usize)> {
+        load(text).unwrap_err().iter().map(|e| e.line_col()).collect()
+    }
+
+    #[test]
+    fn sample() {
+        let config = load(SAMPLE).unwrap();
+        assert_eq!(config.get(None, "name"), Some(&ConfigValue::Str(JsonString("demo".to_string()))));
+        assert_eq!(config.get(None, "retries"), Some(&ConfigValue::Int(IntLiteral(3))));
+        assert_eq!(config.get(Some("server"), "port"), Some(&ConfigValue::Int(IntLiteral(8080))));
+        assert_eq!(config.get(Some("server"), "debug"), Some(&ConfigValue::Bool(true)));
+        assert_eq!(config.get(Some("paths"), "log"), Some(&ConfigValue::Str(JsonString(r"C:\logs\demo.log".to_string()))));
+        assert_eq!(config.get(Some("server"), "name"), None);
+        assert_eq!(config.get(Some("missing"), "port"), None);
+
+        // No trailing newline, and a key set twice.
+        let config = load("[a]\nx = 1\nx = 2").unwrap();
+        assert_eq!(config.get(Some("a"), "x"), Some(&ConfigValue::Int(IntLiteral(2))));
+    }
+
+    #[test]
+    fn lex_errors() {
+        let errors = load("a = 1\n[b]\nc = 'single'\n").unwrap_err();
+        assert!(matches!(errors[0].info, ParceErrorInfo::Lex { .. }));
+        assert_eq!(errors[0].line_col(), (3, 5));
+
+        // Strings can't span lines.
+        assert_eq!(locations("a = \"one\ntwo\"\n"), vec![(1, 5)]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let errors = load("a = 1\n\n[server\nport = 1\n").unwrap_err();
+        // An unclosed header is reported on its line.
+        assert!(matches!(errors[0].info, ParceErrorInfo::Parse { .. }));
+        assert_eq!(errors[0].line_col().0, 3);
+
+        assert_eq!(locations("[a]\nport 8080\n").len(), 1);
+        assert_eq!(locations("[a]\nx = \n").len(), 1);
+    }
+
+    #[test]
+    fn conversion_errors() {
+        let text = "a = 99999999999999999999\n[b]\nc = \"ok\"\nd = \"bad \\q escape\"\n";
+        let errors = load(text).unwrap_err();
+        assert!(errors.iter().all(|e| matches!(e.info, ParceErrorInfo::Convert { .. })));
+        assert_eq!(errors.iter().map(|e| e.line_col()).collect::<Vec<_>>(), vec![(1, 5), (4, 10)]);
+    }
+
+    #[test]
+    fn rendering() {
+        let errors = load("[server]\nport = 80\nhost = \"a\\qb\"\n").unwrap_err();
+        assert_eq!(
+            errors[0].display_plain().to_string(),
+            "Conversion Error: unknown escape \\q\nLine 3: host = \"a\\qb\"\n                 ^"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! A complete JSON lexer and parser, built into a tree of Rust values.
+//!
+//! Run it with `cargo run --example json -- '<json>'`, or without an argument to parse a sample
+//! document. `cargo test` runs the tests at the bottom, which check escapes, numbers, nesting,
+//! and how errors are reported and recovered from.
+
+use parce::prelude::*;
+use parce::convert::JsonString;
+
+#[lexer(JsonLexer)]
+pub enum JsonLexeme {
+    LBrace = '{',
+    RBrace = '}',
+    LBracket = '[',
+    RBracket = ']',
+    Colon = ':',
+    Comma = ',',
+    True = "'true'",
+    False = "'false'",
+    Null = "'null'",
+    Str = r#" '"' ([^"\x5c] | [\x5c] .)* '"' "#,
+    Number = "'-'? ('0' | NonZero Digit*) ('.' Digit+)? (('e' | 'E') ('+' | '-')? Digit+)?",
+    #[frag] NonZero = "[1-9]",
+    #[frag] Digit = "[0-9]",
+    #[skip] Space = "[ \t\n\r]"
+}
+
+/// Any JSON value.
+#[parser(JsonLexer)]
+pub enum Value {
+    Object(Option<Members>) = "LBrace 0? RBrace",
+    Array(Option<Elements>) = "LBracket 0? RBracket",
+    Str(JsonString) = "0=Str",
+    Number(f64) = "0=Number",
+    True = "True",
+    False = "False",
+    Null = "Null"
+}
+
+/// The members of a non-empty object. Separators are their own rule, so a trailing comma can't
+/// match.
+#[parser(JsonLexer)]
+pub enum Members {
+    Members(Member, Vec<NextMember>) = "0 1*"
+}
+
+#[parser(JsonLexer)]
+pub enum NextMember {
+    Next(Member) = "Comma 0"
+}
+
+/// One `"key": value` pair of an object.
+#[parser(JsonLexer)]
+pub enum Member {
+    Member { key: JsonString, value: Box<Value> } = "key=Str Colon value"
+}
+
+/// The elements of a non-empty array.
+#[parser(JsonLexer)]
+pub enum Elements {
+    Elements(Box<Value>, Vec<NextElement>) = "0 1*"
+}
+
+#[parser(JsonLexer)]
+pub enum NextElement {
+    Next(Value) = "Comma 0"
+}
+
+impl Value {
+    /// The key-value pairs of an object, in order. Empty if this isn't an object.
+    pub fn members(&self) -> Vec<(&str, &Value)> {
+        match self {
+            Value::Object(Some(Members::Members(first, rest))) => std::iter::once(first)
+                .chain(rest.iter().map(|NextMember::Next(m)| m))
+                .map(|Member::Member { key, value }| (&**key, &**value))
+                .collect(),
+            _ => vec![]
+        }
+    }
+
+    /// The elements of an array, in order. Empty if this isn't an array.
+    pub fn elements(&self) -> Vec<&Value> {
+        match self {
+            Value::Array(Some(Elements::Elements(first, rest))) => std::iter::once(&**first)
+                .chain(rest.iter().map(|NextElement::Next(v)| v))
+                .collect(),
+            _ => vec![]
+        }
+    }
+
+    /// Looks up `key` in an object. `None` if this isn't an object or doesn't have the key.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.members().into_iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+}
+
+const SAMPLE: &str = r#"{
+    "name": "parce",
+    "version": 0.1,
+    "keywords": ["parser", "lexer", "grammar"],
+    "escaped": "tab\there é",
+    "nested": {"empty": {}, "list": [[], [null, true, false]]}
+}"#;
+
+fn main() {
+    let input = std::env::args().nth(1).unwrap_or_else(|| SAMPLE.to_string());
+    match input.parse::<Value>() {
+        Ok(value) => println!("{:#?}", value),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::Str(JsonString(s.to_string()))
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!("true".parse(), Ok(Value::True));
+        assert_eq!("null".parse(), Ok(Value::Null));
+        assert_eq!("-0".parse(), Ok(Value::Number(0.0)));
+        assert_eq!("12.5e-1".parse(), Ok(Value::Number(1.25)));
+        assert_eq!("1E3".parse(), Ok(Value::Number(1000.0)));
+        assert!("01".parse::<Value>().is_err());
+        assert!("1.".parse::<Value>().is_err());
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(r#""a\"b\\c\/d""#.parse(), Ok(string(r#"a"b\c/d"#)));
+        assert_eq!(r#""\n\té😀""#.parse(), Ok(string("\n\t\u{e9}\u{1f600}")));
+        assert!(r#""\x41""#.parse::<Value>().is_err());
+    }
+
+    #[test]
+    fn nesting() {
+        let value: Value = SAMPLE.parse().unwrap();
+        let keys: Vec<_> = value.members().iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, vec!["name", "version", "keywords", "escaped", "nested"]);
+        assert_eq!(value.get("name"), Some(&string("parce")));
+        assert_eq!(value.get("version"), Some(&Value::Number(0.1)));
+        assert_eq!(value.get("keywords").unwrap().elements(), vec![&string("parser"), &string("lexer"), &string("grammar")]);
+        assert_eq!(value.get("escaped"), Some(&string("tab\there \u{e9}")));
+
+        let nested = value.get("nested").unwrap();
+        assert_eq!(nested.get("empty"), Some(&Value::Object(None)));
+        let list = nested.get("list").unwrap().elements();
+        assert_eq!(list[0], &Value::Array(None));
+        assert_eq!(list[1].elements(), vec![&Value::Null, &Value::True, &Value::False]);
+        assert_eq!(nested.get("missing"), None);
+
+        let deep = format!("{}1{}", "[".repeat(50), "]".repeat(50));
+        assert!(deep.parse::<Value>().is_ok());
+    }
+
+    #[test]
+    fn errors() {
+        // Trailing commas and missing values are parse errors, reported at the offending lexeme.
+        assert_eq!("[1, 2,]".parse::<Value>().unwrap_err().start, 6);
+        assert_eq!(r#"{"a": }"#.parse::<Value>().unwrap_err().start, 6);
+        assert!("[1, 2".parse::<Value>().is_err());
+
+        // Characters that aren't JSON are lex errors.
+        assert_eq!("[1, 'two']".parse::<Value>().unwrap_err().start, 4);
+
+        // A bad escape is only found when the string is converted, which stops at the first one.
+        // Checking the lexemes first finds all of them, at their exact positions.
+        let text = r#"["ok", "\q", "\u12", "fine"]"#;
+        let lexemes = JsonLexer::default().lex(text).unwrap();
+        let errors = parce::convert::check::<JsonString, _>(&lexemes, text, &[JsonLexeme::Str]);
+        assert_eq!(errors.iter().map(|e| e.start).collect::<Vec<_>>(), vec![8, 14]);
+    }
+
+    #[test]
+    fn recovery() {
+        // One value per line: a broken line is reported, and parsing picks up after it.
+        let lines = "{\"id\": 1}\n{\"id\" 2}\n{\"id\": 3}";
+        let mut session = ParseSession::new(JsonLexer::default()).with_diagnostics(100);
+        let values: Vec<_> = session.parse_stream_of::<Value>(lines).collect();
+        let ids: Vec<_> = values.iter()
+            .filter_map(|v| v.as_ref().ok()?.get("id"))
+            .collect();
+        assert!(values.iter().any(|v| v.is_err()));
+        assert_eq!(ids.first(), Some(&&Value::Number(1.0)));
+        assert_eq!(ids.last(), Some(&&Value::Number(3.0)));
+        assert_eq!(session.diagnostics()[0].start, 16);
+    }
+}
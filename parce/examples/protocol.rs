@@ -0,0 +1,163 @@
+//! A line-based key-value protocol, parsed frame by frame as bytes arrive in arbitrary chunks.
+//!
+//! Run it with `cargo run --example protocol`, and type commands like `SET name "parce"` or
+//! `GET name` on stdin. `cargo test` runs the tests at the bottom, which feed a script through
+//! every chunk size and check that the frames don't depend on where the chunks were split.
+//!
+//! Parce lexes whole inputs, not partial ones, so a [Framer] buffers the bytes until a frame is
+//! complete (here, until a newline), then lexes and parses just that frame. A token split between
+//! two chunks, or even a character split between its UTF-8 bytes, is put back together before the
+//! lexer sees it. The framer reads from [std::io::Read]; an async reader would call
+//! [Framer::push] with each chunk the same way.
+
+use parce::prelude::*;
+use parce::error::ParceErrorInfo;
+use parce::convert::JsonString;
+use std::io::Read;
+
+#[lexer(ProtocolLexer)]
+pub enum ProtocolLexeme {
+    Ping = "'PING'",
+    Get = "'GET'",
+    Set = "'SET'",
+    Del = "'DEL'",
+    Key = "[a-zA-Z_:]+",
+    Str = r#" '"' ([^"\x5c] | [\x5c] .)* '"' "#,
+    #[skip] Space = "[ \t\r]"
+}
+
+/// One command. The newline that ends it is removed by the [Framer].
+#[parser(ProtocolLexer)]
+pub enum Frame {
+    Ping = "Ping",
+    Get { key: String } = "Get key=Key",
+    Set { key: String, value: JsonString } = "Set key=Key value=Str",
+    Del(Vec<Name>) = "Del 0+"
+}
+
+#[parser(ProtocolLexer)]
+pub enum Name {
+    Name(String) = "0=Key"
+}
+
+/// Splits a stream of bytes into frames and parses each one as soon as it's complete.
+pub struct Framer {
+    pending: Vec<u8>,
+    session: ParseSession<ProtocolLexer>
+}
+
+impl Default for Framer {
+    fn default() -> Self {
+        Framer {
+            pending: vec![],
+            session: ParseSession::new(ProtocolLexer::default())
+        }
+    }
+}
+
+impl Framer {
+    /// Adds a chunk of bytes, and parses every frame it completes. A frame that isn't valid UTF-8,
+    /// or doesn't parse, is returned as an error, and the frames after it are still parsed.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Result<Frame, ParceError>> {
+        self.pending.extend_from_slice(chunk);
+        let mut frames = vec![];
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            frames.push(self.parse(&line[..end]));
+        }
+        frames
+    }
+
+    /// The bytes of the frame that hasn't been finished by a newline yet.
+    pub fn pending(&self) -> &[u8] {
+        &self.pending
+    }
+
+    fn parse(&mut self, line: &[u8]) -> Result<Frame, ParceError> {
+        match std::str::from_utf8(line) {
+            Ok(text) => self.session.parse_all::<Frame>(text),
+            Err(e) => Err(ParceError {
+                input: String::from_utf8_lossy(line).into_owned(),
+                start: e.valid_up_to(),
+                info: ParceErrorInfo::convert("invalid UTF-8".to_string())
+            })
+        }
+    }
+}
+
+/// Reads `reader` to the end in chunks of up to `chunk_size` bytes, and parses every frame in it.
+/// An unfinished frame at the end is ignored, like a connection that closed mid-command.
+pub fn read_frames(mut reader: impl Read, chunk_size: usize) -> std::io::Result<Vec<Result<Frame, ParceError>>> {
+    let mut framer = Framer::default();
+    let mut buffer = vec![0; chunk_size];
+    let mut frames = vec![];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            return Ok(frames);
+        }
+        frames.extend(framer.push(&buffer[..n]));
+    }
+}
+
+fn main() {
+    let mut framer = Framer::default();
+    let mut buffer = [0; 64];
+    let stdin = std::io::stdin();
+    let mut stdin = stdin.lock();
+    while let Ok(n @ 1..) = stdin.read(&mut buffer) {
+        for frame in framer.push(&buffer[..n]) {
+            match frame {
+                Ok(frame) => println!("{:?}", frame),
+                Err(e) => eprintln!("{}", e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "PING\r\nSET greeting \"h\u{e9}llo \\\"w\u{f6}rld\\\"\"\nGET greeting\nDEL a b:c\n";
+
+    fn expected() -> Vec<Result<Frame, ParceError>> {
+        vec![
+            Ok(Frame::Ping),
+            Ok(Frame::Set { key: "greeting".to_string(), value: JsonString("h\u{e9}llo \"w\u{f6}rld\"".to_string()) }),
+            Ok(Frame::Get { key: "greeting".to_string() }),
+            Ok(Frame::Del(vec![Name::Name("a".to_string()), Name::Name("b:c".to_string())]))
+        ]
+    }
+
+    #[test]
+    fn any_chunk_size() {
+        // Chunks of one and two bytes split the multi-byte characters in the SET frame.
+        for size in 1..=SCRIPT.len() {
+            assert_eq!(read_frames(SCRIPT.as_bytes(), size).unwrap(), expected(), "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn incremental() {
+        let mut framer = Framer::default();
+        assert_eq!(framer.push(b"GE"), vec![]);
+        assert_eq!(framer.push(b"T ke"), vec![]);
+        assert_eq!(framer.pending(), b"GET ke");
+        assert_eq!(framer.push(b"y\nPING\nSET"), vec![Ok(Frame::Get { key: "key".to_string() }), Ok(Frame::Ping)]);
+        assert_eq!(framer.pending(), b"SET");
+    }
+
+    #[test]
+    fn bad_frames() {
+        let input = b"GET\nPING\nSET a \xff\nNOPE!\nDEL x\nGET unfinished";
+        let frames = read_frames(&input[..], 3).unwrap();
+        assert_eq!(frames.len(), 5);
+        assert!(frames[0].is_err());
+        assert_eq!(frames[1], Ok(Frame::Ping));
+        assert_eq!(frames[2].as_ref().unwrap_err().start, 6);
+        assert!(matches!(frames[2].as_ref().unwrap_err().info, ParceErrorInfo::Convert { .. }));
+        assert!(matches!(frames[3].as_ref().unwrap_err().info, ParceErrorInfo::Lex { .. }));
+        assert_eq!(frames[4], Ok(Frame::Del(vec![Name::Name("x".to_string())])));
+    }
+}
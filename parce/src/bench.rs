@@ -0,0 +1,124 @@
+//! Contains a small harness for measuring how fast a lexer or grammar runs, for the `bench`
+//! feature.
+//!
+//! The criterion benchmarks in `benches/` are the real regression suite (run them with
+//! `cargo bench`). This module is for quick numbers from inside an application or a test, on the
+//! user's own grammars and inputs.
+//!
+//! ```
+//! use parce::prelude::*;
+//! use parce::bench::lex_throughput;
+//!
+//! #[lexer(MyLexer)]
+//! enum MyLexemes {
+//!     Word = "[a-z]+",
+//!     #[skip] Space = ' '
+//! }
+//!
+//! let input = "lorem ipsum dolor sit amet ".repeat(100);
+//! let throughput = lex_throughput(MyLexer::default(), &input, 10).unwrap();
+//! assert_eq!(throughput.bytes, input.len() * 10);
+//! println!("{}", throughput);
+//! ```
+
+use crate::lexer::Lexer;
+use crate::lexer::cursor::StrCursor;
+use crate::error::ParceError;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "parser")]
+use crate::parser::{Parseable, ParseSession};
+
+/// How much input was processed in how long, as measured by [lex_throughput] or
+/// [parse_throughput].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Throughput {
+    /// The total size of the input processed, over all iterations.
+    pub bytes: usize,
+    /// How many times the input was processed.
+    pub iterations: u32,
+    /// The total time all iterations took.
+    pub elapsed: Duration
+}
+
+impl Throughput {
+    /// Megabytes (10^6 bytes) of input processed per second.
+    pub fn megabytes_per_second(&self) -> f64 {
+        self.bytes as f64 / 1e6 / self.elapsed.as_secs_f64()
+    }
+
+    /// Iterations (whole inputs lexed or parsed) per second.
+    pub fn iterations_per_second(&self) -> f64 {
+        self.iterations as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for Throughput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} MB/s, {:.1} iterations/s", self.megabytes_per_second(), self.iterations_per_second())
+    }
+}
+
+/// Lexes `input` `iterations` times, reusing one buffer, and reports how long it took. Returns the
+/// error if the input doesn't lex.
+pub fn lex_throughput<L: Lexer>(lexer: L, input: &str, iterations: u32) -> Result<Throughput, ParceError> {
+    let mut buffer = vec![];
+    lexer.lex_cursor_into(StrCursor::new(input), &mut buffer)?;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        buffer.clear();
+        lexer.lex_cursor_into(StrCursor::new(input), &mut buffer)?;
+    }
+    Ok(Throughput {
+        bytes: input.len() * iterations as usize,
+        iterations,
+        elapsed: start.elapsed()
+    })
+}
+
+/// Parses all of `input` as an `O` `iterations` times, reusing one [ParseSession], and reports how
+/// long it took. Returns the error if the input doesn't parse.
+#[cfg(feature = "parser")]
+pub fn parse_throughput<O: Parseable>(input: &str, iterations: u32) -> Result<Throughput, ParceError> {
+    let mut session = ParseSession::new(O::default_lexer());
+    session.parse_all::<O>(input)?;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        session.parse_all::<O>(input)?;
+    }
+    Ok(Throughput {
+        bytes: input.len() * iterations as usize,
+        iterations,
+        elapsed: start.elapsed()
+    })
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(BenchLexer)]
+    enum BenchLexeme {
+        A = 'a',
+        B = 'b',
+        #[skip] Space = ' '
+    }
+
+    #[parser(BenchLexer)]
+    enum Pairs {
+        Pairs = "(A B)*"
+    }
+
+    #[test]
+    fn throughput() {
+        let result = parse_throughput::<Pairs>("ab ab ab", 3).unwrap();
+        assert_eq!((result.bytes, result.iterations), (24, 3));
+        assert!(result.megabytes_per_second() > 0.0);
+
+        assert!(parse_throughput::<Pairs>("aa", 3).is_err());
+        assert!(lex_throughput(BenchLexer::default(), "abc", 3).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Contains [Escapes], a configurable decoder for escape sequences in string literals, and field
+//! types that use it.
+
+use std::str::FromStr;
+use crate::error::ParceError;
+use crate::lexer::{Lexeme, SpannedLexeme};
+
+/// Decodes the escape sequences in string literals.
+///
+/// Start from one of the presets ([rust](Escapes::rust), [c](Escapes::c), [json](Escapes::json)),
+/// or from [new](Escapes::new) and add the escapes the language has. Backslash is always the
+/// escape character.
+///
+/// ```
+/// use parce::convert::{Escapes, EscapeErrorKind};
+///
+/// assert_eq!(Escapes::rust().decode(r#""tab\there \u{1F980}""#), Ok("tab\there 🦀".to_string()));
+/// assert_eq!(Escapes::json().decode(r#""\ud83e\udd80""#), Ok("🦀".to_string()));
+///
+/// let error = Escapes::rust().decode(r#""bad \q""#).unwrap_err();
+/// assert_eq!((error.start, error.len), (5, 2));
+/// assert_eq!(error.kind, EscapeErrorKind::Unknown('q'));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Escapes {
+    quotes: Vec<char>,
+    simple: Vec<(char, char)>,
+    hex: bool,
+    unicode_braced: bool,
+    unicode_fixed: bool,
+    line_continuation: bool
+}
+
+impl Escapes {
+    /// A decoder that doesn't strip quotes or know any escapes.
+    pub fn new() -> Self {
+        Escapes::default()
+    }
+
+    /// Rust string literals: `\n \r \t \0 \\ \' \"`, `\x7F`, `\u{10FFFF}`, and a backslash at the
+    /// end of a line skipping the line break and the next line's indentation.
+    pub fn rust() -> Self {
+        Escapes::new()
+            .quote('"')
+            .simple_escapes(&[('n', '\n'), ('r', '\r'), ('t', '\t'), ('0', '\0'), ('\\', '\\'), ('\'', '\''), ('"', '"')])
+            .hex()
+            .unicode_braced()
+            .line_continuation()
+    }
+
+    /// C string literals: `\a \b \f \n \r \t \v \0 \\ \' \" \?`, `\xFF`, and `\uFFFF`.
+    pub fn c() -> Self {
+        Escapes::new()
+            .quote('"')
+            .simple_escapes(&[
+                ('a', '\u{7}'), ('b', '\u{8}'), ('f', '\u{C}'), ('n', '\n'), ('r', '\r'), ('t', '\t'),
+                ('v', '\u{B}'), ('0', '\0'), ('\\', '\\'), ('\'', '\''), ('"', '"'), ('?', '?')
+            ])
+            .hex()
+            .unicode_fixed()
+    }
+
+    /// JSON strings: `\" \\ \/ \b \f \n \r \t`, and `\uFFFF` with surrogate pairs.
+    pub fn json() -> Self {
+        Escapes::new()
+            .quote('"')
+            .simple_escapes(&[('"', '"'), ('\\', '\\'), ('/', '/'), ('b', '\u{8}'), ('f', '\u{C}'), ('n', '\n'), ('r', '\r'), ('t', '\t')])
+            .unicode_fixed()
+    }
+
+    /// If the text starts and ends with `quote`, they are removed before decoding.
+    pub fn quote(mut self, quote: char) -> Self {
+        self.quotes.push(quote);
+        self
+    }
+
+    /// `\` followed by the first character of each pair decodes to the second.
+    pub fn simple_escapes(mut self, escapes: &[(char, char)]) -> Self {
+        self.simple.extend_from_slice(escapes);
+        self
+    }
+
+    /// `\x` followed by exactly two hex digits.
+    pub fn hex(mut self) -> Self {
+        self.hex = true;
+        self
+    }
+
+    /// `\u{...}` with one to six hex digits.
+    pub fn unicode_braced(mut self) -> Self {
+        self.unicode_braced = true;
+        self
+    }
+
+    /// `\u` followed by exactly four hex digits. A UTF-16 surrogate pair written as two of these
+    /// decodes to one character.
+    pub fn unicode_fixed(mut self) -> Self {
+        self.unicode_fixed = true;
+        self
+    }
+
+    /// A backslash right before a line break skips the line break and any whitespace after it.
+    pub fn line_continuation(mut self) -> Self {
+        self.line_continuation = true;
+        self
+    }
+
+    /// Decodes `text`, removing its quotes if it has them. Error positions are indices into `text`.
+    pub fn decode(&self, text: &str) -> Result<String, EscapeError> {
+        let quoted = text.len() >= 2 && self.quotes.iter().any(|q| text.starts_with(*q) && text.ends_with(*q));
+        let offset = if quoted { text.chars().next().unwrap().len_utf8() } else { 0 };
+        let body = if quoted {
+            &text[offset..text.len() - text.chars().last().unwrap().len_utf8()]
+        } else {
+            text
+        };
+
+        let mut result = String::with_capacity(body.len());
+        let mut chars = body.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if c != '\\' {
+                result.push(c);
+                continue;
+            }
+            let error = |end: usize, kind| EscapeError { start: offset + start, len: end - start, kind };
+            let (i, e) = match chars.next() {
+                Some(next) => next,
+                None => return Err(error(body.len(), EscapeErrorKind::Unterminated))
+            };
+            let after = i + e.len_utf8();
+            if let Some((_, decoded)) = self.simple.iter().find(|(from, _)| *from == e) {
+                result.push(*decoded);
+            } else if e == 'x' && self.hex {
+                let digits = body.get(after..after + 2).filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()));
+                match digits {
+                    Some(d) => {
+                        result.push(u8::from_str_radix(d, 16).unwrap() as char);
+                        chars.nth(1);
+                    }
+                    None => return Err(error(after, EscapeErrorKind::InvalidHex))
+                }
+            } else if e == 'u' && self.unicode_braced && body[after..].starts_with('{') {
+                let close = match body[after..].find('}') {
+                    Some(close) => after + close,
+                    None => return Err(error(after, EscapeErrorKind::InvalidUnicode))
+                };
+                let digits = &body[after + 1..close];
+                let end = close + 1;
+                let value = if (1..=6).contains(&digits.len()) { u32::from_str_radix(digits, 16).ok() } else { None };
+                match value.and_then(char::from_u32) {
+                    Some(c) => result.push(c),
+                    None => return Err(error(end, EscapeErrorKind::InvalidUnicode))
+                }
+                while chars.peek().is_some_and(|(i, _)| *i < end) {
+                    chars.next();
+                }
+            } else if e == 'u' && self.unicode_fixed {
+                let code = |at: usize| body.get(at..at + 4)
+                    .filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()))
+                    .map(|d| u32::from_str_radix(d, 16).unwrap());
+                let mut end = after + 4;
+                let decoded = match code(after) {
+                    Some(high @ 0xD800..=0xDBFF) => {
+                        let low = body[end..].strip_prefix("\\u").and_then(|_| code(end + 2)).filter(|l| (0xDC00..=0xDFFF).contains(l));
+                        low.and_then(|low| {
+                            end += 6;
+                            char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                        })
+                    }
+                    Some(c) => char::from_u32(c),
+                    None => None
+                };
+                match decoded {
+                    Some(c) => result.push(c),
+                    None => return Err(error(end.min(body.len()), EscapeErrorKind::InvalidUnicode))
+                }
+                while chars.peek().is_some_and(|(i, _)| *i < end) {
+                    chars.next();
+                }
+            } else if (e == '\n' || e == '\r') && self.line_continuation {
+                while chars.peek().is_some_and(|(_, c)| c.is_whitespace()) {
+                    chars.next();
+                }
+            } else {
+                return Err(error(after, EscapeErrorKind::Unknown(e)));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Decodes every lexeme whose kind is in `kinds`, and returns an error for each one with a bad
+    /// escape, pointing at the escape. Useful to report every bad literal in an input at once,
+    /// instead of stopping at the first.
+    pub fn check<L: Lexeme>(&self, lexemes: &[SpannedLexeme<L>], text: &str, kinds: &[L]) -> Vec<ParceError> {
+        super::check_with(lexemes, text, kinds, |s| self.decode(s).map(|_| ()))
+    }
+}
+
+/// A bad escape sequence, found by [Escapes::decode].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EscapeError {
+    /// Where the escape starts, at its backslash.
+    pub start: usize,
+    /// The length of the bad escape in bytes.
+    pub len: usize,
+    /// What's wrong with it.
+    pub kind: EscapeErrorKind
+}
+
+/// The kinds of [EscapeError].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EscapeErrorKind {
+    /// A backslash followed by a character that isn't a known escape.
+    Unknown(char),
+    /// A `\x` escape without two hex digits.
+    InvalidHex,
+    /// A `\u` escape that is malformed, or isn't a valid character.
+    InvalidUnicode,
+    /// A backslash at the very end of the text.
+    Unterminated
+}
+
+impl std::fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            EscapeErrorKind::Unknown(c) => write!(f, "unknown escape \\{}", c.escape_debug()),
+            EscapeErrorKind::InvalidHex => write!(f, "\\x must be followed by two hex digits"),
+            EscapeErrorKind::InvalidUnicode => write!(f, "invalid unicode escape"),
+            EscapeErrorKind::Unterminated => write!(f, "the text ends in the middle of an escape")
+        }
+    }
+}
+
+impl std::error::Error for EscapeError {}
+
+impl super::ConvertError for EscapeError {
+    fn start(&self) -> usize {
+        self.start
+    }
+}
+
+macro_rules! decoded_string {
+    ($(#[$doc:meta])* $name:ident = $escapes:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+        pub struct $name(pub String);
+
+        impl FromStr for $name {
+            type Err = EscapeError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Escapes::$escapes().decode(s).map($name)
+            }
+        }
+
+        impl std::ops::Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+decoded_string! {
+    /// A string decoded with [Escapes::rust], for use as an assigned field on a string literal lexeme.
+    ///
+    #[cfg_attr(feature = "parser", doc = "```")]
+    #[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+    /// use parce::prelude::*;
+    /// use parce::convert::RustString;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Str = r#" '"' ([^"\x5c] | [\x5c] .)* '"' "#,
+    ///     #[skip] Space = ' '
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Literal {
+    ///     Str(RustString) = "0=Str"
+    /// }
+    ///
+    /// let parsed: Literal = r#""line\nbreak""#.parse().unwrap();
+    /// assert_eq!(parsed, Literal::Str(RustString("line\nbreak".to_string())));
+    /// ```
+    RustString = rust
+}
+
+decoded_string! {
+    /// A string decoded with [Escapes::c].
+    CString = c
+}
+
+decoded_string! {
+    /// A string decoded with [Escapes::json].
+    JsonString = json
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let rust = Escapes::rust();
+        assert_eq!(rust.decode(r#""a\\b\"c\x41\u{41}""#), Ok("a\\b\"cAA".to_string()));
+        assert_eq!(rust.decode("\"one \\\n    two\""), Ok("one two".to_string()));
+        assert_eq!(rust.decode("unquoted\\t"), Ok("unquoted\t".to_string()));
+        assert_eq!(rust.decode(r#""\x4""#).unwrap_err(), EscapeError { start: 1, len: 2, kind: EscapeErrorKind::InvalidHex });
+        assert_eq!(rust.decode(r#""\u{110000}""#).unwrap_err(), EscapeError { start: 1, len: 10, kind: EscapeErrorKind::InvalidUnicode });
+        assert_eq!(rust.decode(r#""\u{41""#).unwrap_err().kind, EscapeErrorKind::InvalidUnicode);
+        assert_eq!(rust.decode(r#"ab\"#).unwrap_err(), EscapeError { start: 2, len: 1, kind: EscapeErrorKind::Unterminated });
+
+        let c = Escapes::c();
+        assert_eq!(c.decode(r#""\a\?é""#), Ok("\u{7}?é".to_string()));
+        assert_eq!(c.decode(r#""\u{41}""#).unwrap_err().kind, EscapeErrorKind::InvalidUnicode);
+
+        let json = Escapes::json();
+        assert_eq!(json.decode(r#""\/A""#), Ok("/A".to_string()));
+        assert_eq!(json.decode(r#""\ud83e""#).unwrap_err(), EscapeError { start: 1, len: 6, kind: EscapeErrorKind::InvalidUnicode });
+        assert_eq!(json.decode(r#""\x41""#).unwrap_err().kind, EscapeErrorKind::Unknown('x'));
+
+        let none = Escapes::new().quote('\'');
+        assert_eq!(none.decode("'it'"), Ok("it".to_string()));
+        assert_eq!(none.decode("\"it\""), Ok("\"it\"".to_string()));
+    }
+
+    #[lexer(EscapeLexer)]
+    enum EscapeLexemes {
+        Str = r#" '"' ([^"\x5c] | [\x5c] .)* '"' "#,
+        #[skip] Space = ' '
+    }
+
+    #[test]
+    fn check() {
+        let text = r#""fine" "bad \q" "\u{0}""#;
+        let lexemes = EscapeLexer::default().lex(text).unwrap();
+        let errors = Escapes::rust().check(&lexemes, text, &[EscapeLexemes::Str]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].start, 12);
+        assert_eq!(errors[0].info, crate::error::ParceErrorInfo::Convert { message: "unknown escape \\q".to_string() });
+    }
+}
@@ -0,0 +1,161 @@
+//! Contains [Symbol], an interned string for use as the type of identifier fields, and the
+//! [Interner] that owns the strings.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// A string stored once in an [Interner], and referred to by a small id. Symbols are cheap to copy,
+/// hash, and compare, which matters in large trees full of identifiers.
+///
+/// Assigned fields of this type are interned into the current thread's interner. A
+/// [ParseSession](crate::parser::ParseSession) with its own interner, added with
+/// [with_interner](crate::parser::ParseSession::with_interner), makes that the current one while it
+/// parses, so every tree it produces shares one table.
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// use parce::prelude::*;
+/// use parce::convert::{Interner, Symbol};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Ident = "[a-z]+",
+///     Eq = '=',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Assign {
+///     Assign { target: Symbol, value: Symbol } = "target=Ident Eq value=Ident"
+/// }
+///
+/// let mut session = ParseSession::new(MyLexer::default()).with_interner(Interner::default());
+/// let first = session.parse_all::<Assign>("x = y").unwrap();
+/// let second = session.parse_all::<Assign>("y = x").unwrap();
+///
+/// let (Assign::Assign { target: x, value: y }, Assign::Assign { target: y2, value: x2 }) = (first, second);
+/// assert_eq!((x, y), (x2, y2));
+///
+/// let interner = session.interner().unwrap();
+/// assert_eq!(interner.resolve(x), Some("x"));
+/// assert_eq!(interner.len(), 2);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// The symbol's index in its interner, counting from 0 in the order strings were first interned.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(with_current(|interner| interner.intern(s)))
+    }
+}
+
+/// A table of interned strings.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Interner {
+    strings: Vec<Box<str>>,
+    ids: HashMap<Box<str>, Symbol>
+}
+
+impl Interner {
+    /// Returns the symbol for `s`, adding it if it's new.
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(symbol) = self.ids.get(s) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(s.into());
+        self.ids.insert(s.into(), symbol);
+        symbol
+    }
+
+    /// Returns the symbol for `s` without adding it.
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        self.ids.get(s).copied()
+    }
+
+    /// The string of a symbol from this interner.
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.index()).map(|s| &**s)
+    }
+
+    /// The number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether nothing has been interned.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+/// Runs `f` on the current thread's interner, which [Symbol] fields are interned into. Symbols from
+/// parses that didn't use a session with its own interner are resolved here.
+pub fn with_current<R>(f: impl FnOnce(&mut Interner) -> R) -> R {
+    CURRENT.with(|current| f(&mut current.borrow_mut()))
+}
+
+/// Swaps `interner` with the current thread's interner.
+fn swap_current(interner: &mut Interner) {
+    CURRENT.with(|current| std::mem::swap(interner, &mut current.borrow_mut()));
+}
+
+/// Makes `interner` the current thread's interner until the guard is dropped, even if the parse
+/// using it panics. Then the current interner goes back, and `interner` gets the symbols added
+/// meanwhile.
+#[cfg_attr(not(feature = "parser"), allow(dead_code))]
+pub(crate) fn enter(interner: &mut Interner) -> InternerGuard<'_> {
+    swap_current(interner);
+    InternerGuard(interner)
+}
+
+/// Swaps the interners back when dropped.
+pub(crate) struct InternerGuard<'a>(&'a mut Interner);
+
+impl Drop for InternerGuard<'_> {
+    fn drop(&mut self) {
+        swap_current(self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern() {
+        let mut interner = Interner::default();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        assert_ne!(a, b);
+        assert_eq!(interner.intern("a"), a);
+        assert_eq!(interner.get("b"), Some(b));
+        assert_eq!(interner.get("c"), None);
+        assert_eq!(interner.resolve(b), Some("b"));
+        assert_eq!(interner.resolve(Symbol(2)), None);
+        assert_eq!(interner.len(), 2);
+
+        let parsed: Symbol = "thread".parse().unwrap();
+        assert_eq!(with_current(|i| i.resolve(parsed).map(str::to_string)), Some("thread".to_string()));
+
+        let entered = enter(&mut interner);
+        assert_eq!("b".parse(), Ok(b));
+        drop(entered);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(with_current(|i| i.get("b")), None);
+    }
+}
@@ -0,0 +1,47 @@
+//! Helpers for turning the text of lexemes into values, for use as the types of assigned fields.
+//!
+//! Assigned fields are built with [FromStr], so every type here implements it.
+
+use std::str::FromStr;
+use crate::error::{ParceError, ParceErrorInfo};
+use crate::lexer::{Lexeme, SpannedLexeme};
+
+pub mod escape;
+pub mod intern;
+pub mod number;
+
+pub use escape::{Escapes, EscapeError, EscapeErrorKind, RustString, CString, JsonString};
+pub use intern::{Interner, Symbol};
+pub use number::{parse_int, parse_float, Integer, Floating, IntLiteral, FloatLiteral, NumberError, NumberErrorKind};
+
+/// An error from converting the text of one lexeme, which knows where in the lexeme it happened.
+pub trait ConvertError: std::error::Error {
+    /// The index in the lexeme's text where the problem starts.
+    fn start(&self) -> usize;
+}
+
+/// Converts every lexeme whose kind is in `kinds` to a `T`, and returns an error for each one that
+/// fails, pointing at the problem inside the lexeme.
+///
+/// A failed conversion during parsing only reports where the field starts, and stops at the first
+/// one. Running this on the lexemes first reports every bad literal, at the exact position.
+pub fn check<T, L>(lexemes: &[SpannedLexeme<L>], text: &str, kinds: &[L]) -> Vec<ParceError>
+    where T: FromStr, T::Err: ConvertError, L: Lexeme {
+    check_with(lexemes, text, kinds, |s| s.parse::<T>().map(|_| ()))
+}
+
+pub(crate) fn check_with<L: Lexeme, E: ConvertError>(
+    lexemes: &[SpannedLexeme<L>],
+    text: &str,
+    kinds: &[L],
+    convert: impl Fn(&str) -> Result<(), E>
+) -> Vec<ParceError> {
+    lexemes.iter()
+        .filter(|l| kinds.contains(&l.data))
+        .filter_map(|l| convert(text.get(l.range())?).err().map(|e| ParceError {
+            input: text.to_string(),
+            start: l.start + e.start(),
+            info: ParceErrorInfo::convert(e.to_string())
+        }))
+        .collect()
+}
@@ -0,0 +1,306 @@
+//! Contains parsers for integer and float literals in the shapes most languages use, and field
+//! types that use them.
+
+use std::str::FromStr;
+
+/// The integer types [parse_int] can produce.
+pub trait Integer: TryFrom<u128> + TryFrom<i128> + Copy {
+    /// The type's name, which is also the suffix [IntLiteral] accepts.
+    const NAME: &'static str;
+}
+
+/// The float types [parse_float] can produce.
+pub trait Floating: FromStr + Copy {
+    /// The type's name, which is also the suffix [FloatLiteral] accepts.
+    const NAME: &'static str;
+    /// Whether the value is infinite, which means the literal was too big.
+    fn is_infinite(self) -> bool;
+}
+
+macro_rules! integers {
+    ($($t:ident),*) => {$(
+        impl Integer for $t {
+            const NAME: &'static str = stringify!($t);
+        }
+    )*};
+}
+
+integers!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+macro_rules! floats {
+    ($($t:ident),*) => {$(
+        impl Floating for $t {
+            const NAME: &'static str = stringify!($t);
+            fn is_infinite(self) -> bool {
+                $t::is_infinite(self)
+            }
+        }
+    )*};
+}
+
+floats!(f32, f64);
+
+/// Parses an integer literal, and returns it with its suffix.
+///
+/// The literal can have a `+` or `-` sign, a `0x`, `0o`, or `0b` prefix, and `_` between digits.
+/// Anything after the digits that starts with a letter is the suffix, like `u8` in `0xffu8`.
+///
+/// ```
+/// use parce::convert::{parse_int, NumberErrorKind};
+///
+/// assert_eq!(parse_int::<u32>("1_000"), Ok((1000, "")));
+/// assert_eq!(parse_int::<i8>("-0x80"), Ok((-128, "")));
+/// assert_eq!(parse_int::<u8>("0b1010_1010u8"), Ok((0b1010_1010, "u8")));
+///
+/// let error = parse_int::<u8>("256").unwrap_err();
+/// assert_eq!(error.kind, NumberErrorKind::Overflow);
+/// ```
+pub fn parse_int<T: Integer>(text: &str) -> Result<(T, &str), NumberError> {
+    let (negative, unsigned) = sign(text);
+    let start = text.len() - unsigned.len();
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x" | "0X") => (16, &unsigned[2..]),
+        Some("0o" | "0O") => (8, &unsigned[2..]),
+        Some("0b" | "0B") => (2, &unsigned[2..]),
+        _ => (10, unsigned)
+    };
+    let digits_start = text.len() - digits.len();
+    let end = digits.find(|c: char| !c.is_digit(radix) && c != '_').unwrap_or(digits.len());
+    let suffix = &digits[end..];
+    let digits = &digits[..end];
+
+    if let Some(c) = suffix.chars().next().filter(|c| !c.is_alphabetic()) {
+        return Err(NumberError { start: digits_start + end, len: c.len_utf8(), kind: NumberErrorKind::InvalidDigit(c) });
+    }
+    if !digits.chars().any(|c| c != '_') {
+        return Err(NumberError { start: 0, len: text.len(), kind: NumberErrorKind::Empty });
+    }
+
+    let overflow = NumberError { start, len: text.len() - suffix.len() - start, kind: NumberErrorKind::Overflow };
+    let mut value: u128 = 0;
+    for d in digits.chars().filter_map(|c| c.to_digit(radix)) {
+        value = value.checked_mul(radix as u128)
+            .and_then(|v| v.checked_add(d as u128))
+            .ok_or(overflow)?;
+    }
+    let result = if negative {
+        let negated = if value == i128::MIN.unsigned_abs() {
+            i128::MIN
+        } else {
+            -i128::try_from(value).map_err(|_| overflow)?
+        };
+        T::try_from(negated).ok()
+    } else {
+        T::try_from(value).ok()
+    };
+    result.map(|v| (v, suffix)).ok_or(overflow)
+}
+
+/// Parses a float literal, and returns it with its suffix.
+///
+/// The literal can have a `+` or `-` sign, `_` between digits, a fractional part, and an exponent
+/// like `e-3`. Anything after that which starts with a letter is the suffix, like `f32` in `1.5f32`.
+///
+/// ```
+/// use parce::convert::{parse_float, NumberErrorKind};
+///
+/// assert_eq!(parse_float::<f64>("1_000.5e-1"), Ok((100.05, "")));
+/// assert_eq!(parse_float::<f32>("2f32"), Ok((2.0, "f32")));
+/// assert_eq!(parse_float::<f32>("1e39").unwrap_err().kind, NumberErrorKind::Overflow);
+/// ```
+pub fn parse_float<T: Floating>(text: &str) -> Result<(T, &str), NumberError> {
+    let (_, unsigned) = sign(text);
+    let start = text.len() - unsigned.len();
+    let bytes = unsigned.as_bytes();
+    let digits = |mut i: usize| {
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+            i += 1;
+        }
+        i
+    };
+
+    let mut end = digits(0);
+    let whole = end;
+    if bytes.get(end) == Some(&b'.') && bytes.get(end + 1).is_some_and(u8::is_ascii_digit) {
+        end = digits(end + 1);
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let signed = matches!(bytes.get(end + 1), Some(b'+' | b'-')) as usize;
+        if bytes.get(end + 1 + signed).is_some_and(u8::is_ascii_digit) {
+            end = digits(end + 1 + signed);
+        }
+    }
+    let suffix = &unsigned[end..];
+
+    if let Some(c) = suffix.chars().next().filter(|c| !c.is_alphabetic()) {
+        return Err(NumberError { start: start + end, len: c.len_utf8(), kind: NumberErrorKind::InvalidDigit(c) });
+    }
+    if !unsigned[..whole].chars().any(|c| c != '_') {
+        return Err(NumberError { start: 0, len: text.len(), kind: NumberErrorKind::Empty });
+    }
+
+    let cleaned: String = text[..start + end].chars().filter(|c| *c != '_').collect();
+    let value: T = cleaned.parse().map_err(|_| NumberError { start: 0, len: text.len(), kind: NumberErrorKind::Empty })?;
+    if value.is_infinite() {
+        return Err(NumberError { start, len: end, kind: NumberErrorKind::Overflow });
+    }
+    Ok((value, suffix))
+}
+
+/// Splits off a leading sign, and returns whether it was negative.
+fn sign(text: &str) -> (bool, &str) {
+    match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text)
+    }
+}
+
+/// A numeric literal that couldn't be parsed, found by [parse_int] or [parse_float].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct NumberError {
+    /// Where the problem starts in the literal.
+    pub start: usize,
+    /// The length of the problem in bytes.
+    pub len: usize,
+    /// What the problem is.
+    pub kind: NumberErrorKind
+}
+
+/// The kinds of [NumberError].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NumberErrorKind {
+    /// The literal has no digits.
+    Empty,
+    /// A character that isn't a digit in the literal's base, and doesn't start a suffix.
+    InvalidDigit(char),
+    /// The value doesn't fit in the type.
+    Overflow,
+    /// The suffix isn't the name of the type being parsed.
+    Suffix
+}
+
+impl std::fmt::Display for NumberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            NumberErrorKind::Empty => write!(f, "number has no digits"),
+            NumberErrorKind::InvalidDigit(c) => write!(f, "invalid digit {:?} in number", c),
+            NumberErrorKind::Overflow => write!(f, "number is too large for its type"),
+            NumberErrorKind::Suffix => write!(f, "number has the wrong suffix for its type")
+        }
+    }
+}
+
+impl std::error::Error for NumberError {}
+
+impl super::ConvertError for NumberError {
+    fn start(&self) -> usize {
+        self.start
+    }
+}
+
+/// Checks that a suffix is either missing or the name of the type.
+fn expect_suffix(text: &str, suffix: &str, name: &str) -> Result<(), NumberError> {
+    if suffix.is_empty() || suffix == name {
+        Ok(())
+    } else {
+        Err(NumberError { start: text.len() - suffix.len(), len: suffix.len(), kind: NumberErrorKind::Suffix })
+    }
+}
+
+/// An integer parsed with [parse_int], for use as an assigned field. A suffix is allowed if it's
+/// the type's name, like `u8` on an `IntLiteral<u8>`.
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// use parce::prelude::*;
+/// use parce::convert::{check, IntLiteral};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Int = "'0x'? [0-9a-fA-F_]+ ('u8')?",
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Byte {
+///     Byte(IntLiteral<u8>) = "0=Int"
+/// }
+///
+/// assert_eq!("0xff".parse(), Ok(Byte::Byte(IntLiteral(255))));
+/// assert_eq!("1_0u8".parse(), Ok(Byte::Byte(IntLiteral(10))));
+/// assert!(("256".parse() as Result<Byte, _>).is_err());
+///
+/// // Check the literals first to report where an overflow is, and all of them at once.
+/// let text = "1 300 2 1000";
+/// let lexemes = MyLexer::default().lex(text).unwrap();
+/// let errors = check::<IntLiteral<u8>, _>(&lexemes, text, &[MyLexemes::Int]);
+/// assert_eq!(errors.iter().map(|e| e.start).collect::<Vec<_>>(), vec![2, 8]);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Ord, PartialOrd)]
+pub struct IntLiteral<T>(pub T);
+
+impl<T: Integer> FromStr for IntLiteral<T> {
+    type Err = NumberError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, suffix) = parse_int(s)?;
+        expect_suffix(s, suffix, T::NAME)?;
+        Ok(IntLiteral(value))
+    }
+}
+
+/// A float parsed with [parse_float], for use as an assigned field. A suffix is allowed if it's
+/// the type's name, like `f32` on a `FloatLiteral<f32>`.
+#[derive(Debug, Copy, Clone, PartialEq, Default, PartialOrd)]
+pub struct FloatLiteral<T>(pub T);
+
+impl<T: Floating> FromStr for FloatLiteral<T> {
+    type Err = NumberError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, suffix) = parse_float(s)?;
+        expect_suffix(s, suffix, T::NAME)?;
+        Ok(FloatLiteral(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ints() {
+        assert_eq!(parse_int::<u8>("0"), Ok((0, "")));
+        assert_eq!(parse_int::<u8>("+0o17"), Ok((15, "")));
+        assert_eq!(parse_int::<i64>("0X_7F_"), Ok((127, "")));
+        assert_eq!(parse_int::<i128>("-170141183460469231731687303715884105728"), Ok((i128::MIN, "")));
+        assert_eq!(parse_int::<u128>("340282366920938463463374607431768211455"), Ok((u128::MAX, "")));
+        assert_eq!(parse_int::<u32>("12abc"), Ok((12, "abc")));
+
+        assert_eq!(parse_int::<u128>("340282366920938463463374607431768211456"),
+                   Err(NumberError { start: 0, len: 39, kind: NumberErrorKind::Overflow }));
+        assert_eq!(parse_int::<u8>("-1"), Err(NumberError { start: 1, len: 1, kind: NumberErrorKind::Overflow }));
+        assert_eq!(parse_int::<i8>("-129i8"), Err(NumberError { start: 1, len: 3, kind: NumberErrorKind::Overflow }));
+        assert_eq!(parse_int::<u8>("0b102"), Err(NumberError { start: 4, len: 1, kind: NumberErrorKind::InvalidDigit('2') }));
+        assert_eq!(parse_int::<u8>("0x"), Err(NumberError { start: 0, len: 2, kind: NumberErrorKind::Empty }));
+        assert_eq!(parse_int::<u8>("_"), Err(NumberError { start: 0, len: 1, kind: NumberErrorKind::Empty }));
+
+        assert_eq!("7u16".parse(), Ok(IntLiteral(7u16)));
+        assert_eq!("7u8".parse::<IntLiteral<u16>>(), Err(NumberError { start: 1, len: 2, kind: NumberErrorKind::Suffix }));
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(parse_float::<f64>("1"), Ok((1.0, "")));
+        assert_eq!(parse_float::<f64>("-1.5E+2"), Ok((-150.0, "")));
+        assert_eq!(parse_float::<f64>("3.f64"), Err(NumberError { start: 1, len: 1, kind: NumberErrorKind::InvalidDigit('.') }));
+        assert_eq!(parse_float::<f64>("3e"), Ok((3.0, "e")));
+        assert_eq!(parse_float::<f64>(".5"), Err(NumberError { start: 0, len: 2, kind: NumberErrorKind::Empty }));
+        assert_eq!(parse_float::<f64>("-1e400"), Err(NumberError { start: 1, len: 5, kind: NumberErrorKind::Overflow }));
+
+        assert_eq!("0.25f32".parse(), Ok(FloatLiteral(0.25f32)));
+        assert_eq!("0.25".parse(), Ok(FloatLiteral(0.25f64)));
+        assert_eq!("1f32".parse::<FloatLiteral<f64>>().unwrap_err().kind, NumberErrorKind::Suffix);
+    }
+}
@@ -1,6 +1,9 @@
 //! Errors generated by the lexer and parser.
 
+use std::collections::HashMap;
 use std::fmt::Formatter;
+use colored::{Color, Colorize};
+use crate::span::Span;
 
 /// Error struct for all runtime errors in the lexing and parsing process.
 ///
@@ -24,10 +27,21 @@ pub enum ParceErrorInfo {
     },
     /// Error occurred in the parsing phase, with a given failure.
     Parse {
-        failure: ParsePhaseFailure
+        failure: ParsePhaseFailure,
+        /// The rules being parsed when the failure happened, outermost first. Empty if the
+        /// failure didn't happen inside a rule.
+        stack: Vec<&'static str>
     },
     /// Error occurred in the assembly phase
-    Assemble
+    Assemble,
+    /// Error reported by a [Preprocessor](crate::preprocess::Preprocessor) before lexing.
+    Preprocess {
+        message: String
+    },
+    /// Error reported by a [convert](crate::convert) helper, like a bad escape in a string literal.
+    Convert {
+        message: String
+    }
 }
 
 impl ParceErrorInfo {
@@ -37,16 +51,29 @@ impl ParceErrorInfo {
             mode
         }
     }
-    /// Creates a new parse variant
+    /// Creates a new parse variant, with an empty rule stack
     pub fn parse(failure: ParsePhaseFailure) -> ParceErrorInfo {
         ParceErrorInfo::Parse {
-            failure
+            failure,
+            stack: vec![]
         }
     }
     /// Creates a new assemble variant
     pub fn assemble() -> ParceErrorInfo {
         ParceErrorInfo::Assemble
     }
+    /// Creates a new preprocess variant
+    pub fn preprocess(message: String) -> ParceErrorInfo {
+        ParceErrorInfo::Preprocess {
+            message
+        }
+    }
+    /// Creates a new convert variant
+    pub fn convert(message: String) -> ParceErrorInfo {
+        ParceErrorInfo::Convert {
+            message
+        }
+    }
 }
 
 /// Possible failure modes during the parsing phase
@@ -60,47 +87,594 @@ pub enum ParsePhaseFailure {
     /// use all of the lexemes in the input.
     LeftoverLexemes,
     /// Input vec of lexemes was empty.
-    NothingToParse
+    NothingToParse,
+    /// The automata took more memory than the limit set with
+    /// [ParseSession::with_memory_limit](crate::parser::ParseSession::with_memory_limit). The error
+    /// is at the lexeme being parsed when the limit was passed.
+    ResourceExhausted,
+    /// The parse wasn't done by the deadline of
+    /// [ParseSession::parse_with_deadline](crate::parser::ParseSession::parse_with_deadline). The
+    /// error is at the lexeme being parsed when the deadline was noticed.
+    Timeout,
+    /// The tree nested productions deeper than the limit set with
+    /// [ParseSession::with_depth_limit](crate::parser::ParseSession::with_depth_limit). The error is
+    /// at the production that went too deep.
+    DepthLimit,
+    /// The tree had more productions than the limit set with
+    /// [ParseSession::with_node_limit](crate::parser::ParseSession::with_node_limit). The error is
+    /// at the first production past the limit.
+    NodeLimit,
+    /// The input ended inside a pair of delimiters declared with `#[pair(...)]`, and would parse if
+    /// they were closed. The error is at the opener. See
+    /// [Paired Delimiters](crate::prelude::lexer#paired-delimiters).
+    Unclosed {
+        /// The name of the opening lexeme.
+        opener: Box<str>,
+        /// The name of the closing lexeme it needs.
+        closer: Box<str>
+    },
+    /// A rule with reference fields was parsed without a
+    /// [NodeArena](crate::parser::arena::NodeArena) to put its children in. Parse it with
+    /// [ParseSession::parse_all_in](crate::parser::ParseSession::parse_all_in).
+    NoArena
 }
 
-impl std::fmt::Display for ParceError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        use colored::Colorize;
+impl ParsePhaseFailure {
+    /// The message describing the failure.
+    pub fn message(&self) -> Message {
+        match self {
+            ParsePhaseFailure::InputEndedTooSoon => Message::InputEndedTooSoon,
+            ParsePhaseFailure::NoMatches => Message::NoMatches,
+            ParsePhaseFailure::LeftoverLexemes => Message::LeftoverLexemes,
+            ParsePhaseFailure::NothingToParse => Message::NothingToParse,
+            ParsePhaseFailure::ResourceExhausted => Message::ResourceExhausted,
+            ParsePhaseFailure::Timeout => Message::Timeout,
+            ParsePhaseFailure::DepthLimit => Message::DepthLimit,
+            ParsePhaseFailure::NodeLimit => Message::NodeLimit,
+            ParsePhaseFailure::Unclosed { .. } => Message::Unclosed,
+            ParsePhaseFailure::NoArena => Message::NoArena
+        }
+    }
+
+    /// The arguments of the [message](Self::message).
+    pub fn args(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            ParsePhaseFailure::Unclosed { opener, closer } => vec![("opener", &**opener), ("closer", &**closer)],
+            _ => vec![]
+        }
+    }
+}
+
+/// A piece of the text of a diagnostic, which a [MessageCatalog] can translate.
+///
+/// Every message has an [id](Self::id) that won't change between versions of parce, so catalogs
+/// can be keyed by it, and an [English](Self::english) template. Templates name their arguments
+/// in braces, like `{mode}`, and translations must use the same names.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Message {
+    /// `Lexer Error`, the heading of a lexing error.
+    LexError,
+    /// `Lexer Mode`, labelling the lexer's mode in a lexing error.
+    LexerMode,
+    /// `Parser Error`, the heading of a parsing error.
+    ParseError,
+    /// `While parsing`, labelling the rules being parsed.
+    WhileParsing,
+    /// `Preprocessor Error`, the heading of a preprocessing error.
+    PreprocessError,
+    /// `Conversion Error`, the heading of a conversion error.
+    ConvertError,
+    /// `Assembler Error`, the heading of an assembly error.
+    AssembleError,
+    /// `Input`, labelling the input when it is one line.
+    Input,
+    /// `Line {line}`, labelling one line of the input.
+    Line,
+    /// No lexeme matched, for [ParceErrorInfo::Lex].
+    NoLexemeMatched,
+    /// A field's text couldn't be converted to its type, for [ParceErrorInfo::Assemble].
+    FieldNotConverted,
+    /// For [ParsePhaseFailure::InputEndedTooSoon].
+    InputEndedTooSoon,
+    /// For [ParsePhaseFailure::NoMatches].
+    NoMatches,
+    /// For [ParsePhaseFailure::LeftoverLexemes].
+    LeftoverLexemes,
+    /// For [ParsePhaseFailure::NothingToParse].
+    NothingToParse,
+    /// For [ParsePhaseFailure::ResourceExhausted].
+    ResourceExhausted,
+    /// For [ParsePhaseFailure::Timeout].
+    Timeout,
+    /// For [ParsePhaseFailure::DepthLimit].
+    DepthLimit,
+    /// For [ParsePhaseFailure::NodeLimit].
+    NodeLimit,
+    /// `unclosed {opener} opened here`, for [ParsePhaseFailure::Unclosed].
+    Unclosed,
+    /// For [ParsePhaseFailure::NoArena].
+    NoArena,
+    /// `expected {expected}, found {found}`, for a [recovered](crate::parser::recovery) error.
+    ExpectedFound,
+    /// `end of input`, as what was found when the input ran out.
+    EndOfInput
+}
+
+impl Message {
+    /// Every message.
+    pub const ALL: &'static [Message] = &[
+        Message::LexError, Message::LexerMode, Message::ParseError, Message::WhileParsing,
+        Message::PreprocessError, Message::ConvertError, Message::AssembleError, Message::Input, Message::Line,
+        Message::NoLexemeMatched, Message::FieldNotConverted, Message::InputEndedTooSoon, Message::NoMatches, Message::LeftoverLexemes,
+        Message::NothingToParse, Message::ResourceExhausted, Message::Timeout, Message::DepthLimit,
+        Message::NodeLimit, Message::Unclosed, Message::NoArena,
+        Message::ExpectedFound, Message::EndOfInput
+    ];
+
+    /// The message's id, which stays the same between versions.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Message::LexError => "lex-error",
+            Message::LexerMode => "lexer-mode",
+            Message::ParseError => "parse-error",
+            Message::WhileParsing => "while-parsing",
+            Message::PreprocessError => "preprocess-error",
+            Message::ConvertError => "convert-error",
+            Message::AssembleError => "assemble-error",
+            Message::Input => "input",
+            Message::Line => "line",
+            Message::NoLexemeMatched => "lex.no-lexeme-matched",
+            Message::FieldNotConverted => "assemble.field-not-converted",
+            Message::InputEndedTooSoon => "parse.input-ended-too-soon",
+            Message::NoMatches => "parse.no-matches",
+            Message::LeftoverLexemes => "parse.leftover-lexemes",
+            Message::NothingToParse => "parse.nothing-to-parse",
+            Message::ResourceExhausted => "parse.resource-exhausted",
+            Message::Timeout => "parse.timeout",
+            Message::DepthLimit => "parse.depth-limit",
+            Message::NodeLimit => "parse.node-limit",
+            Message::Unclosed => "parse.unclosed",
+            Message::NoArena => "parse.no-arena",
+            Message::ExpectedFound => "expected-found",
+            Message::EndOfInput => "end-of-input"
+        }
+    }
+
+    /// The message in English, which is used when a catalog doesn't have it.
+    pub fn english(&self) -> &'static str {
+        match self {
+            Message::LexError => "Lexer Error",
+            Message::LexerMode => "Lexer Mode",
+            Message::ParseError => "Parser Error",
+            Message::WhileParsing => "While parsing",
+            Message::PreprocessError => "Preprocessor Error",
+            Message::ConvertError => "Conversion Error",
+            Message::AssembleError => "Assembler Error",
+            Message::Input => "Input",
+            Message::Line => "Line {line}",
+            Message::NoLexemeMatched => "no possible lexemes matched this input",
+            Message::FieldNotConverted => "the matched text could not be converted to the field's type",
+            Message::InputEndedTooSoon => "the input ended before any production could finish",
+            Message::NoMatches => "no productions matched this input",
+            Message::LeftoverLexemes => "the longest match did not use all of the input",
+            Message::NothingToParse => "there was nothing to parse",
+            Message::ResourceExhausted => "the parse used more memory than it was allowed",
+            Message::Timeout => "the parse took longer than it was allowed",
+            Message::DepthLimit => "the rules were nested deeper than they were allowed",
+            Message::NodeLimit => "the input had more rules than it was allowed",
+            Message::Unclosed => "unclosed {opener} opened here, the input ended before its {closer}",
+            Message::NoArena => "rules with reference fields have to be parsed with ParseSession::parse_all_in",
+            Message::ExpectedFound => "expected {expected}, found {found}",
+            Message::EndOfInput => "end of input"
+        }
+    }
+
+    /// The message with an [id](Self::id) of `id`.
+    pub fn from_id(id: &str) -> Option<Message> {
+        Message::ALL.iter().copied().find(|m| m.id() == id)
+    }
+}
+
+/// Where the text of diagnostics comes from, so applications can show them in their users'
+/// language. [English] is the default, and is used for any message a catalog doesn't have.
+///
+/// A map from [message ids](Message::id) to templates is a catalog, so translations can be
+/// loaded from a file:
+///
+/// ```
+/// use std::collections::HashMap;
+/// use parce::error::{Message, MessageCatalog, ParceError, ParceErrorInfo, ParsePhaseFailure};
+///
+/// let mut german = HashMap::new();
+/// german.insert("parse-error".to_string(), "Syntaxfehler".to_string());
+/// german.insert("parse.no-matches".to_string(), "keine Regel passt auf diese Eingabe".to_string());
+/// german.insert("input".to_string(), "Eingabe".to_string());
+///
+/// let error = ParceError {
+///     input: "a b".to_string(),
+///     start: 2,
+///     info: ParceErrorInfo::parse(ParsePhaseFailure::NoMatches)
+/// };
+/// assert_eq!(
+///     error.display_with(&german).plain().to_string(),
+///     "Syntaxfehler: keine Regel passt auf diese Eingabe\nEingabe: a b\n           ^"
+/// );
+/// assert_eq!(german.format(Message::Line, &[("line", "3")]), "Line 3");
+/// ```
+pub trait MessageCatalog {
+    /// The template for `message`, or `None` to use the [English](Message::english) one.
+    fn template(&self, message: Message) -> Option<&str>;
+
+    /// The text of `message`, with each `{name}` in its template replaced by the value of `name`
+    /// in `args`.
+    fn format(&self, message: Message, args: &[(&str, &str)]) -> String {
+        let mut text = self.template(message).unwrap_or_else(|| message.english()).to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+}
+
+/// The catalog of English messages, which has no templates of its own.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct English;
+
+impl MessageCatalog for English {
+    fn template(&self, _message: Message) -> Option<&str> {
+        None
+    }
+}
+
+impl MessageCatalog for HashMap<String, String> {
+    fn template(&self, message: Message) -> Option<&str> {
+        self.get(message.id()).map(String::as_str)
+    }
+}
+
+impl MessageCatalog for HashMap<&str, &str> {
+    fn template(&self, message: Message) -> Option<&str> {
+        self.get(message.id()).copied()
+    }
+}
+
+/// What the column in [ParceError::line_col_with] counts, and how wide a tab is.
+///
+/// Editors, terminals, and protocols don't agree on columns: most editors count characters,
+/// compilers often count bytes, the Language Server Protocol counts UTF-16 code units, and a tab
+/// can be one column or jump to the next tab stop. Pick the preset that matches where the column
+/// will be shown. Columns are counted from 1 in all of them.
+///
+/// ```
+/// use parce::error::{Columns, ParceError, ParceErrorInfo};
+///
+/// let error = ParceError {
+///     input: "\t🦀 x".to_string(),
+///     start: 6,
+///     info: ParceErrorInfo::lex("Default".to_string())
+/// };
+/// assert_eq!(error.line_col(), (1, 4));
+/// assert_eq!(error.line_col_with(Columns::bytes()), (1, 7));
+/// assert_eq!(error.line_col_with(Columns::utf16()), (1, 5));
+/// assert_eq!(error.line_col_with(Columns::chars().tab_width(4)), (1, 7));
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Columns {
+    unit: ColumnUnit,
+    tab_width: usize
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum ColumnUnit {
+    Chars,
+    Bytes,
+    Utf16
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns::chars()
+    }
+}
+
+impl Columns {
+    /// Each character is one column. This is what [ParceError::line_col] uses.
+    pub fn chars() -> Self {
+        Columns { unit: ColumnUnit::Chars, tab_width: 1 }
+    }
+
+    /// Each byte of UTF-8 is one column.
+    pub fn bytes() -> Self {
+        Columns { unit: ColumnUnit::Bytes, tab_width: 1 }
+    }
+
+    /// Each UTF-16 code unit is one column, so characters outside the Basic Multilingual Plane
+    /// take two. This is how the Language Server Protocol counts, though it counts from 0.
+    pub fn utf16() -> Self {
+        Columns { unit: ColumnUnit::Utf16, tab_width: 1 }
+    }
+
+    /// A tab moves to the next column after a multiple of `width`, like a terminal with tab stops
+    /// every `width` columns, instead of counting as one character.
+    ///
+    /// # Panics
+    ///
+    /// If `width` is 0.
+    pub fn tab_width(mut self, width: usize) -> Self {
+        assert!(width > 0, "tab width must be at least 1");
+        self.tab_width = width;
+        self
+    }
+
+    /// The column just after `line`, counted from 1.
+    fn after(&self, line: &str) -> usize {
+        line.chars().fold(0, |column, c| match c {
+            '\t' => (column / self.tab_width + 1) * self.tab_width,
+            c => column + match self.unit {
+                ColumnUnit::Chars => 1,
+                ColumnUnit::Bytes => c.len_utf8(),
+                ColumnUnit::Utf16 => c.len_utf16()
+            }
+        }) + 1
+    }
+}
+
+impl ParceError {
+    /// The line and column of [start](Self::start), both counted from 1. Lines are separated by
+    /// `\n`, and columns count characters, not bytes. Use [line_col_with](Self::line_col_with)
+    /// to count them another way.
+    ///
+    /// ```
+    /// use parce::error::{ParceError, ParceErrorInfo};
+    ///
+    /// let error = ParceError {
+    ///     input: "first\nsécond".to_string(),
+    ///     start: 9,
+    ///     info: ParceErrorInfo::lex("Default".to_string())
+    /// };
+    /// assert_eq!(error.line_col(), (2, 3));
+    /// ```
+    pub fn line_col(&self) -> (usize, usize) {
+        self.line_col_with(Columns::chars())
+    }
+
+    /// The line and column of [start](Self::start), with columns counted as `columns` says.
+    pub fn line_col_with(&self, columns: Columns) -> (usize, usize) {
+        let before = &self.input[..floor_boundary(&self.input, self.start)];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (before.matches('\n').count() + 1, columns.after(&before[line_start..]))
+    }
+
+    /// Displays the error with the text from `catalog`, instead of in English.
+    pub fn display_with<'a>(&'a self, catalog: &'a dyn MessageCatalog) -> Localized<'a> {
+        Localized { error: self, catalog, colored: true }
+    }
+
+    /// Displays the error in English without terminal colors, for logs and tests.
+    pub fn display_plain(&self) -> Localized<'_> {
+        self.display_with(&English).plain()
+    }
+
+    /// Writes the line around [start](Self::start) with a caret pointing at it. If the input has
+    /// more than one line, the line number is written instead of `Input`.
+    fn write_input(&self, f: &mut Formatter<'_>, catalog: &dyn MessageCatalog, colored: bool) -> std::fmt::Result {
+        let at = floor_boundary(&self.input, self.start);
+        let line_start = self.input[..at].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.input[at..].find('\n').map_or(self.input.len(), |i| at + i);
+        let line = self.input[line_start..line_end].trim_end_matches('\r');
+        let at = (at - line_start).min(line.len());
+
+        let start = floor_boundary(line, at.saturating_sub(10));
+        let end = floor_boundary(line, at + 10);
+        let before = format!("{}{}", if start != 0 { "..." } else { "" }, &line[start..at]);
+        let after = format!("{}{}", &line[at..end], if end != line.len() { "..." } else { "" });
+        let label = if line_start == 0 && line_end == self.input.len() {
+            format!("{}: ", catalog.format(Message::Input, &[]))
+        } else {
+            format!("{}: ", catalog.format(Message::Line, &[("line", &self.line_col().0.to_string())]))
+        };
+
+        write!(
+            f,
+            "{}{}{}\n{}{}",
+            label,
+            before,
+            painted(after, Color::Red, colored),
+            " ".repeat(label.chars().count()) + &before.chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect::<String>(),
+            painted("^".to_string(), Color::Red, colored),
+        )
+    }
+}
+
+/// `text` in `color`, or unchanged if `colored` is false.
+fn painted(text: String, color: Color, colored: bool) -> String {
+    if colored {
+        text.color(color).to_string()
+    } else {
+        text
+    }
+}
+
+/// The largest character boundary in `s` that isn't after `index`.
+fn floor_boundary(s: &str, index: usize) -> usize {
+    let mut index = index.min(s.len());
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// A [ParceError] displayed with the text of a [MessageCatalog]. Made by
+/// [display_with](ParceError::display_with).
+pub struct Localized<'a> {
+    error: &'a ParceError,
+    catalog: &'a dyn MessageCatalog,
+    colored: bool
+}
 
-        match &self.info {
+impl Localized<'_> {
+    /// Displays the error without terminal colors, whatever [colored] would decide for the
+    /// terminal.
+    pub fn plain(mut self) -> Self {
+        self.colored = false;
+        self
+    }
+}
+
+impl std::fmt::Display for Localized<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let text = |message: Message| self.catalog.format(message, &[]);
+        let paint = |text: String, color: Color| painted(text, color, self.colored);
+        match &self.error.info {
             ParceErrorInfo::Lex {mode} => {
-                let start = self.start.saturating_sub(10);
-                let end = std::cmp::min(self.input.len(), self.start + 10);
-                let short = format!("{}{}{}",
-                                    if start != 0 {
-                                        "..."
-                                    } else {
-                                        ""
-                                    },
-                                    &self.input[start..end],
-                                    if end != self.input.len() {
-                                        "..."
-                                    } else {
-                                        ""
-                                    }
-                );
-
-                write!(
+                writeln!(
                     f,
-                    "Lexer Error: {}\nLexer Mode: {}\nInput: {}{}\n{}{}",
-                    "no possible lexemes matched this input".red(),
-                    mode.bright_blue(),
-                    &short[..self.start - if start != 0 { start - 3 } else { 0 }],
-                    &short[(self.start - if start != 0 { start - 3 } else { 0 })..].red(),
-                    " ".repeat(7 + self.start - if start != 0 { start - 3 } else { 0 }),
-                    "^".red(),
-                )
+                    "{}: {}\n{}: {}",
+                    text(Message::LexError),
+                    paint(text(Message::NoLexemeMatched), Color::Red),
+                    text(Message::LexerMode),
+                    paint(mode.clone(), Color::BrightBlue)
+                )?;
+            }
+            ParceErrorInfo::Parse {failure, stack} => {
+                let message = self.catalog.format(failure.message(), &failure.args());
+                writeln!(f, "{}: {}", text(Message::ParseError), paint(message, Color::Red))?;
+                if !stack.is_empty() {
+                    writeln!(f, "{}: {}", text(Message::WhileParsing), paint(stack.join(" → "), Color::BrightBlue))?;
+                }
+            }
+            ParceErrorInfo::Preprocess {message} => {
+                writeln!(f, "{}: {}", text(Message::PreprocessError), paint(message.clone(), Color::Red))?;
+            }
+            ParceErrorInfo::Convert {message} => {
+                writeln!(f, "{}: {}", text(Message::ConvertError), paint(message.clone(), Color::Red))?;
+            }
+            ParceErrorInfo::Assemble => {
+                writeln!(f, "{}: {}", text(Message::AssembleError), paint(text(Message::FieldNotConverted), Color::Red))?;
             }
-            _ => todo!()
         }
+        self.error.write_input(f, self.catalog, self.colored)
+    }
+}
+
+impl std::fmt::Display for ParceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.display_with(&English).fmt(f)
     }
 }
 
 impl std::error::Error for ParceError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
-}
\ No newline at end of file
+}
+
+/// A warning about input that lexed and parsed, but used a lexeme or production marked
+/// `#[deprecated_syntax(message = "...")]`. Collected by a [ParseSession](crate::parser::ParseSession),
+/// see [warnings](crate::parser::ParseSession::warnings).
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ParceWarning {
+    /// The name of the lexeme, like `Var`, or of the rule and production, like `Expr::Call`.
+    pub name: String,
+    /// The message given to `#[deprecated_syntax(...)]`.
+    pub message: &'static str,
+    /// The part of the input that matched it.
+    pub span: Span
+}
+
+impl std::fmt::Display for ParceWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}..{} is deprecated: {}", self.name, self.span.start, self.span.end, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str, start: usize) -> ParceError {
+        ParceError {
+            input: input.to_string(),
+            start,
+            info: ParceErrorInfo::parse(ParsePhaseFailure::NoMatches)
+        }
+    }
+
+    #[test]
+    fn line_col() {
+        assert_eq!(error("abc", 0).line_col(), (1, 1));
+        assert_eq!(error("abc", 3).line_col(), (1, 4));
+        assert_eq!(error("a\nbc\n", 3).line_col(), (2, 2));
+        assert_eq!(error("a\nbc\n", 5).line_col(), (3, 1));
+        assert_eq!(error("é\néé", 5).line_col(), (2, 2));
+    }
+
+    #[test]
+    fn columns() {
+        let utf16 = Columns::utf16();
+        assert_eq!(error("aé🦀b", 7).line_col_with(Columns::bytes()), (1, 8));
+        assert_eq!(error("aé🦀b", 7).line_col_with(utf16), (1, 5));
+        assert_eq!(error("x\naé🦀b", 9).line_col_with(utf16), (2, 5));
+
+        let tabs = Columns::chars().tab_width(4);
+        assert_eq!(error("\tx", 1).line_col_with(tabs), (1, 5));
+        assert_eq!(error("abc\tx", 4).line_col_with(tabs), (1, 5));
+        assert_eq!(error("abcd\tx", 5).line_col_with(tabs), (1, 9));
+        assert_eq!(error("\t\tx", 2).line_col_with(tabs), (1, 9));
+        assert_eq!(error("é\tx", 3).line_col_with(Columns::bytes().tab_width(4)), (1, 5));
+        assert_eq!(error("\tx", 1).line_col_with(Columns::default()), (1, 2));
+    }
+
+    #[test]
+    fn rendering() {
+        let input = |e: ParceError| e.display_plain().to_string().lines().skip(1).collect::<Vec<_>>().join("\n");
+
+        assert_eq!(input(error("one two", 4)), "Input: one two\n           ^");
+        assert_eq!(input(error("one\ntwo three\r\nfour", 8)), "Line 2: two three\n            ^");
+        assert_eq!(input(error("one\ntwo\n", 7)), "Line 2: two\n           ^");
+        assert_eq!(
+            input(error("0123456789abcdefghijklmnopqrstuvwxyz", 20)),
+            "Input: ...abcdefghijklmnopqrst...\n                    ^"
+        );
+        assert_eq!(input(error("ééééé x", 11)), "Input: ééééé x\n             ^");
+        assert_eq!(input(error("\ta\tb", 3)), "Input: \ta\tb\n       \t \t^");
+    }
+
+    #[test]
+    fn localized() {
+        let mut french = HashMap::new();
+        french.insert("parse-error", "Erreur d'analyse");
+        french.insert("parse.input-ended-too-soon", "l'entrée s'est terminée trop tôt");
+        french.insert("while-parsing", "Pendant l'analyse de");
+        french.insert("line", "Ligne n°{line}");
+
+        let error = ParceError {
+            input: "un\ndeux".to_string(),
+            start: 7,
+            info: ParceErrorInfo::Parse { failure: ParsePhaseFailure::InputEndedTooSoon, stack: vec!["Liste"] }
+        };
+        assert_eq!(
+            error.display_with(&french).plain().to_string(),
+            "Erreur d'analyse: l'entrée s'est terminée trop tôt\nPendant l'analyse de: Liste\nLigne n°2: deux\n               ^"
+        );
+        assert_eq!(error.display_with(&English).to_string(), error.to_string());
+
+        let error = ParceError {
+            input: "999".to_string(),
+            start: 0,
+            info: ParceErrorInfo::assemble()
+        };
+        assert_eq!(
+            error.display_plain().to_string(),
+            "Assembler Error: the matched text could not be converted to the field's type\nInput: 999\n       ^"
+        );
+    }
+
+    #[test]
+    fn message_ids() {
+        for message in Message::ALL {
+            assert_eq!(Message::from_id(message.id()), Some(*message));
+        }
+        assert_eq!(Message::from_id("parse.no-matches"), Some(Message::NoMatches));
+        assert_eq!(English.format(Message::ExpectedFound, &[("expected", "Ident"), ("found", "`1`")]), "expected Ident, found `1`");
+    }
+}
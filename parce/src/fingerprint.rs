@@ -0,0 +1,39 @@
+//! The hash that fingerprints are made of, like [Lexer::FINGERPRINT](crate::lexer::Lexer::FINGERPRINT)
+//! and [GrammarInfo::fingerprint](crate::report::GrammarInfo::fingerprint).
+//!
+//! Fingerprints are 64-bit [FNV-1a](http://www.isthe.com/chongo/tech/comp/fnv/) hashes, which are
+//! simple enough to compute in `const` items and in the macros, and don't change between builds,
+//! platforms or versions of Rust, unlike the hashers in `std`. They aren't made to resist
+//! deliberate collisions.
+
+/// The hash of nothing, which every fingerprint starts from.
+pub const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Adds `bytes` to the hash `hash`.
+pub const fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+/// Adds the fingerprint `next` to the hash `hash`.
+pub const fn combine_fingerprints(hash: u64, next: u64) -> u64 {
+    fnv1a(hash, &next.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable() {
+        assert_eq!(fnv1a(FNV_OFFSET, b""), FNV_OFFSET);
+        assert_eq!(fnv1a(FNV_OFFSET, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(fnv1a(FNV_OFFSET, b"foo"), b"bar"), fnv1a(FNV_OFFSET, b"foobar"));
+        assert_ne!(combine_fingerprints(FNV_OFFSET, 1), combine_fingerprints(FNV_OFFSET, 2));
+    }
+}
@@ -6,12 +6,13 @@
 //! and instead using `use parce::prelude::*` should be sufficient.
 
 pub use lazy_static::lazy_static;
-pub use regex::Regex;
 pub use tinyvec::{tiny_vec, array_vec, TinyVec, ArrayVec};
+#[cfg(feature = "inventory")]
 pub use inventory;
 
-pub use crate::parser::{*, automata::*};
-pub use crate::lexer::*;
+#[cfg(feature = "parser")]
+pub use crate::parser::{*, automata::*, arena::IntoField};
+pub use crate::lexer::{*, cursor::*};
 
 pub use core::any::TypeId as Rule;
 
@@ -0,0 +1,204 @@
+//! Contains [LineLexer], which keeps the lexemes of a file with a checkpoint at every line, so
+//! lexing can resume from the line of an edit instead of the start of the file.
+
+use crate::error::ParceError;
+use super::{Lexeme, Lexer, SpannedLexeme};
+use super::cursor::StrCursor;
+
+/// Where lexing can resume for one line, and the state to resume in.
+///
+/// The lexer's whole state is its mode, so that is all a checkpoint holds. A lexeme that starts
+/// on an earlier line can run onto this one, like a block comment, so lexing resumes at the first
+/// lexeme boundary at or after the start of the line, not always at the line start itself.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct LineCheckpoint<L: Lexer> {
+    /// The byte offset of the first character of the line.
+    pub line_start: usize,
+    /// The byte offset to resume lexing at: the start of the first lexeme that starts on or after
+    /// the line, or the end of the input if there isn't one.
+    pub resume: usize,
+    /// The mode of the lexer at [resume](Self::resume).
+    pub mode: L
+}
+
+/// The lexemes of one input, with a [LineCheckpoint] for each line, for editors that re-lex a file
+/// as it is edited.
+///
+/// Lines are counted from 0, and start at the beginning of the input and after every `\n`. After
+/// an edit, [relex](Self::relex) lexes again from the last checkpoint before the edit, and keeps the
+/// lexemes and checkpoints before it. Skipped lexemes are left out of the
+/// [lexemes](Self::lexemes), like [lex](Lexer::lex), but they still count as boundaries and can
+/// change modes.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::checkpoint::LineLexer;
+///
+/// #[lexer(MyLexer)]
+/// #[modes(Code, Comment)]
+/// enum MyLexemes {
+///     Word = "[a-z]+",
+///     #[set_mode(Comment)] Open = "'/*'",
+///     #[skip] Space = "[ \n]",
+///
+///     #[mode(Comment)]
+///     #[set_mode(Code)] Close = "'*/'",
+///     Text = "[^*]+"
+/// }
+///
+/// let text = "a /* one\ntwo */ b\nc\n";
+/// let mut lines = LineLexer::lex(MyLexer::default(), text).unwrap();
+/// assert_eq!(lines.lexemes().len(), 6);
+///
+/// // The second line starts inside the comment's text, so it resumes after it, in comment mode.
+/// let second = lines.checkpoint(1).unwrap();
+/// assert_eq!((second.line_start, second.resume, second.mode), (9, 13, MyLexer::Comment));
+///
+/// // Editing the last line only lexes it again.
+/// let edited = "a /* one\ntwo */ b\nc d\n";
+/// lines.relex(edited, 19).unwrap();
+/// let words: Vec<_> = lines.lexemes().iter().filter(|l| l.is(MyLexemes::Word)).map(|l| l.text(edited)).collect();
+/// assert_eq!(words, vec!["a", "b", "c", "d"]);
+/// assert_eq!(lines.lexemes(), &MyLexer::default().lex(edited).unwrap()[..]);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LineLexer<L: Lexer> {
+    lexemes: Vec<SpannedLexeme<L::Lexemes>>,
+    checkpoints: Vec<LineCheckpoint<L>>
+}
+
+impl<L: Lexer> LineLexer<L> {
+    /// Lexes all of `text`, starting in mode `lexer`.
+    pub fn lex(lexer: L, text: &str) -> Result<Self, ParceError> {
+        let mut lines = LineLexer {
+            lexemes: vec![],
+            checkpoints: vec![]
+        };
+        lines.resume(text, LineCheckpoint { line_start: 0, resume: 0, mode: lexer }, 0)?;
+        Ok(lines)
+    }
+
+    /// The lexemes of the input, not including skipped lexemes.
+    pub fn lexemes(&self) -> &[SpannedLexeme<L::Lexemes>] {
+        &self.lexemes
+    }
+
+    /// The checkpoints of every line, in order.
+    pub fn checkpoints(&self) -> &[LineCheckpoint<L>] {
+        &self.checkpoints
+    }
+
+    /// The checkpoint of line `line`, counted from 0.
+    pub fn checkpoint(&self, line: usize) -> Option<&LineCheckpoint<L>> {
+        self.checkpoints.get(line)
+    }
+
+    /// Lexes `text` again from the checkpoint of line `line`, or from the last line if there
+    /// aren't that many. `text` must be the same as the old input before that checkpoint's
+    /// [resume](LineCheckpoint::resume) offset.
+    ///
+    /// If lexing fails, the error is relative to the whole of `text`, and nothing is changed.
+    pub fn relex_from(&mut self, line: usize, text: &str) -> Result<(), ParceError> {
+        let line = line.min(self.checkpoints.len() - 1);
+        self.resume(text, self.checkpoints[line], line)
+    }
+
+    /// Lexes `text` again after an edit at byte `edit`, from the last checkpoint at or before it.
+    /// `text` must be the same as the old input before `edit`.
+    pub fn relex(&mut self, text: &str, edit: usize) -> Result<(), ParceError> {
+        let line = self.checkpoints.partition_point(|checkpoint| checkpoint.resume <= edit).max(1) - 1;
+        self.relex_from(line, text)
+    }
+
+    /// Lexes `text` from `checkpoint`, which is for line `line`, and replaces everything from there on.
+    fn resume(&mut self, text: &str, checkpoint: LineCheckpoint<L>, line: usize) -> Result<(), ParceError> {
+        let LineCheckpoint { line_start, resume, mut mode } = checkpoint;
+        let mut lexemes = vec![];
+        let lexed = match resume {
+            0 => mode.lex_all_cursor_into(StrCursor::new(text), &mut lexemes),
+            _ => mode.lex_rest_cursor_into(StrCursor::new(&text[resume..]), &mut lexemes)
+        };
+        lexed.map_err(|e| ParceError {
+            input: text.to_string(),
+            start: e.start + resume,
+            info: e.info
+        })?;
+
+        self.lexemes.truncate(self.lexemes.partition_point(|l| l.start < resume));
+        self.checkpoints.truncate(line);
+        let mut line_starts = std::iter::once(line_start)
+            .chain(text[line_start..].match_indices('\n').map(|(i, _)| line_start + i + 1))
+            .peekable();
+        for mut lexeme in lexemes {
+            lexeme.start += resume;
+            while let Some(line_start) = line_starts.next_if(|&s| s <= lexeme.start) {
+                self.checkpoints.push(LineCheckpoint { line_start, resume: lexeme.start, mode });
+            }
+            mode = mode.next_mode(lexeme.data);
+            if !lexeme.is_skipped() {
+                self.lexemes.push(lexeme);
+            }
+        }
+        for line_start in line_starts {
+            self.checkpoints.push(LineCheckpoint { line_start, resume: text.len(), mode });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(StringLexer)]
+    #[modes(Code, Str)]
+    enum StringLexemes {
+        Ident = "[a-z]+",
+        #[set_mode(Str)] Quote = '"',
+        #[skip] Space = "[ \n]",
+
+        #[mode(Str)]
+        #[set_mode(Code)] EndQuote = '"',
+        Chars = "[^\"]+"
+    }
+
+    #[test]
+    fn relex_matches_full_lex() {
+        let old = "a \"b\nc\" d\ne\n\"f\n";
+        let new = "a \"b\nc\" d\nxe\n\"f\n";
+        let fresh = LineLexer::lex(StringLexer::default(), new).unwrap();
+        assert_eq!(fresh.checkpoints().len(), 5);
+        assert_eq!(fresh.checkpoint(1), Some(&LineCheckpoint { line_start: 5, resume: 6, mode: StringLexer::Str }));
+        assert_eq!(fresh.checkpoint(4), Some(&LineCheckpoint { line_start: 16, resume: 16, mode: StringLexer::Str }));
+
+        // The texts agree up to byte 10, so an edit anywhere up to there can be relexed.
+        for edit in 0..=10 {
+            let mut lines = LineLexer::lex(StringLexer::default(), old).unwrap();
+            lines.relex(new, edit).unwrap();
+            assert_eq!(lines, fresh, "edit at {}", edit);
+        }
+        for line in 0..3 {
+            let mut lines = LineLexer::lex(StringLexer::default(), old).unwrap();
+            lines.relex_from(line, new).unwrap();
+            assert_eq!(lines, fresh, "line {}", line);
+        }
+
+        // Appending only lexes the last line again, even when asked for a line past the end.
+        let mut lines = LineLexer::lex(StringLexer::default(), new).unwrap();
+        let appended = format!("{}\"g", new);
+        lines.relex_from(10, &appended).unwrap();
+        assert_eq!(lines, LineLexer::lex(StringLexer::default(), &appended).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let mut lines = LineLexer::lex(StringLexer::default(), "a\nb\n").unwrap();
+        let before = lines.clone();
+        let error = lines.relex("a\nb\n#", 4).unwrap_err();
+        assert_eq!((error.start, error.input.as_str()), (4, "a\nb\n#"));
+        assert_eq!(lines, before);
+        assert!(LineLexer::lex(StringLexer::default(), "a\n#").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Contains the [Cursor] trait that generated lexers read their input through, and the
+//! implementations of it for string slices and character iterators.
+
+/// A position-aware reader over the input of a lexer.
+///
+/// The generated lexers never index into their input directly. Instead they peek and advance
+/// through a cursor, and use marks to rewind back to earlier positions when a pattern fails to
+/// match. This means the same generated matcher can lex contiguous strings, ropes, chunked buffers,
+/// and streaming input, as long as the input can be wrapped in a cursor.
+///
+/// Marks are byte offsets from the beginning of the input, so the `start` and `len` of each
+/// [SpannedLexeme](crate::lexer::SpannedLexeme) are the same regardless of which cursor was used.
+pub trait Cursor {
+    /// Returns the character at the current position without consuming it, or `None` if the
+    /// input has ended.
+    fn peek(&mut self) -> Option<char>;
+
+    /// Moves past the character at the current position. Does nothing if the input has ended.
+    fn advance(&mut self);
+
+    /// Returns the current position, which can be passed to [Cursor::rewind] later.
+    fn mark(&self) -> usize;
+
+    /// Moves the cursor back to a position previously returned by [Cursor::mark].
+    fn rewind(&mut self, mark: usize);
+
+    /// Returns all of the input the cursor has read so far. Used to fill in
+    /// [ParceError::input](crate::error::ParceError::input) when lexing fails.
+    fn input(&self) -> String;
+}
+
+/// A [Cursor] over a contiguous string slice.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct StrCursor<'a> {
+    s: &'a str,
+    pos: usize
+}
+
+impl<'a> StrCursor<'a> {
+    /// Creates a new cursor at the start of `s`.
+    pub fn new(s: &'a str) -> StrCursor<'a> {
+        StrCursor {
+            s,
+            pos: 0
+        }
+    }
+}
+
+impl<'a> From<&'a str> for StrCursor<'a> {
+    fn from(s: &'a str) -> Self {
+        StrCursor::new(s)
+    }
+}
+
+impl Cursor for StrCursor<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn advance(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn mark(&self) -> usize {
+        self.pos
+    }
+
+    fn rewind(&mut self, mark: usize) {
+        self.pos = mark;
+    }
+
+    fn input(&self) -> String {
+        self.s.to_string()
+    }
+}
+
+/// A [Cursor] over any iterator of characters.
+///
+/// Characters are pulled from the iterator lazily and buffered, so that the cursor can rewind
+/// to any earlier position.
+#[derive(Debug, Clone)]
+pub struct IterCursor<I: Iterator<Item = char>> {
+    iter: I,
+    buffer: String,
+    pos: usize
+}
+
+impl<I: Iterator<Item = char>> IterCursor<I> {
+    /// Creates a new cursor at the start of `iter`.
+    pub fn new(iter: I) -> IterCursor<I> {
+        IterCursor {
+            iter,
+            buffer: String::new(),
+            pos: 0
+        }
+    }
+}
+
+impl<I: Iterator<Item = char>> Cursor for IterCursor<I> {
+    fn peek(&mut self) -> Option<char> {
+        if self.pos == self.buffer.len() {
+            self.buffer.push(self.iter.next()?);
+        }
+        self.buffer[self.pos..].chars().next()
+    }
+
+    fn advance(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn mark(&self) -> usize {
+        self.pos
+    }
+
+    fn rewind(&mut self, mark: usize) {
+        self.pos = mark;
+    }
+
+    fn input(&self) -> String {
+        self.buffer.clone()
+    }
+}
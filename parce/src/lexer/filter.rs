@@ -0,0 +1,133 @@
+//! Contains the [TokenFilter] trait, for transforming the lexemes between the lexer and the parser.
+
+use super::{Lexeme, SpannedLexeme};
+
+/// A pass over the lexemes after lexing and before parsing, which can drop, change, or insert
+/// lexemes. Filters are added to a [ParseSession](crate::parser::ParseSession) with
+/// [with_filter](crate::parser::ParseSession::with_filter), and run in the order they were added,
+/// each one seeing the output of the one before it.
+///
+/// [filter](TokenFilter::filter) is called once for each lexeme, in order. Whatever it pushes to
+/// `out` is passed on, so pushing nothing drops the lexeme, and pushing more than one inserts new
+/// lexemes. Inserted lexemes don't have to match any text; a zero-length span at the right position
+/// keeps error messages pointing at the right place. Newline handling with [NewlinePolicy](super::newline::NewlinePolicy)
+/// is built on this.
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// use parce::prelude::*;
+/// use parce::lexer::SpannedLexeme;
+/// use parce::lexer::filter::TokenFilter;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Ident = "[a-z]+",
+///     Semicolon = ';',
+///     LBrace = '{',
+///     RBrace = '}',
+///     #[skip] Space = ' '
+/// }
+///
+/// /// Allows leaving out the semicolon after the last statement in a block.
+/// struct OptionalSemicolon;
+///
+/// impl TokenFilter<MyLexemes> for OptionalSemicolon {
+///     fn filter(&mut self, lexeme: SpannedLexeme<MyLexemes>, _ahead: &[SpannedLexeme<MyLexemes>], _text: &str, out: &mut Vec<SpannedLexeme<MyLexemes>>) {
+///         if *lexeme == MyLexemes::RBrace && out.last().is_some_and(|l| *l == MyLexemes::Ident) {
+///             out.push(SpannedLexeme { data: MyLexemes::Semicolon, start: lexeme.start, len: 0 });
+///         }
+///         out.push(lexeme);
+///     }
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Block {
+///     Block = "LBrace (Ident Semicolon)* RBrace"
+/// }
+///
+/// let mut session = ParseSession::new(MyLexer::default()).with_filter(OptionalSemicolon);
+/// assert_eq!(session.parse_all::<Block>("{ a; b }"), Ok(Block::Block));
+/// ```
+pub trait TokenFilter<L: Lexeme>: Send {
+    /// Handles one lexeme, pushing whatever should replace it to `out`. `ahead` holds the rest of
+    /// the lexemes this filter hasn't seen yet (wrap it in a [TokenCursor](super::lookahead::TokenCursor)
+    /// to look ahead by hand), and `text` is the whole input.
+    fn filter(&mut self, lexeme: SpannedLexeme<L>, ahead: &[SpannedLexeme<L>], text: &str, out: &mut Vec<SpannedLexeme<L>>);
+
+    /// Called after the last lexeme, to push anything still pending. The same filter is used for
+    /// every input in a session, so this should also reset any state kept between lexemes.
+    fn finish(&mut self, _text: &str, _out: &mut Vec<SpannedLexeme<L>>) {}
+
+    /// Runs the filter over all of `lexemes`, replacing them with the output.
+    fn apply(&mut self, lexemes: &mut Vec<SpannedLexeme<L>>, text: &str) {
+        let input = std::mem::replace(lexemes, Vec::with_capacity(lexemes.len()));
+        for (i, lexeme) in input.iter().enumerate() {
+            self.filter(*lexeme, &input[i+1..], text, lexemes);
+        }
+        self.finish(text, lexemes);
+    }
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use parce::lexer::SpannedLexeme;
+    use super::TokenFilter;
+
+    #[lexer(FilterLexer)]
+    enum FilterLexemes {
+        A = 'a',
+        B = 'b',
+        #[skip] Space = ' '
+    }
+
+    #[parser(FilterLexer)]
+    enum FilterGrammar {
+        Pairs = "(A B)+"
+    }
+
+    /// Puts a B after every A that isn't followed by one.
+    struct Pair;
+
+    impl TokenFilter<FilterLexemes> for Pair {
+        fn filter(&mut self, lexeme: SpannedLexeme<FilterLexemes>, ahead: &[SpannedLexeme<FilterLexemes>], _text: &str, out: &mut Vec<SpannedLexeme<FilterLexemes>>) {
+            out.push(lexeme);
+            if *lexeme == FilterLexemes::A && ahead.first().is_none_or(|l| **l != FilterLexemes::B) {
+                out.push(SpannedLexeme { data: FilterLexemes::B, start: lexeme.start + lexeme.len, len: 0 });
+            }
+        }
+    }
+
+    /// Drops every other B, and checks that state is reset between inputs.
+    #[derive(Default)]
+    struct DropOdd(bool);
+
+    impl TokenFilter<FilterLexemes> for DropOdd {
+        fn filter(&mut self, lexeme: SpannedLexeme<FilterLexemes>, _ahead: &[SpannedLexeme<FilterLexemes>], _text: &str, out: &mut Vec<SpannedLexeme<FilterLexemes>>) {
+            if *lexeme == FilterLexemes::B {
+                self.0 = !self.0;
+                if self.0 {
+                    return;
+                }
+            }
+            out.push(lexeme);
+        }
+
+        fn finish(&mut self, _text: &str, _out: &mut Vec<SpannedLexeme<FilterLexemes>>) {
+            self.0 = false;
+        }
+    }
+
+    #[test]
+    fn chain() {
+        let mut session = ParseSession::new(FilterLexer::default())
+            .with_filter(DropOdd::default())
+            .with_filter(Pair);
+        assert_eq!(session.parse_all::<FilterGrammar>("a a b"), Ok(FilterGrammar::Pairs));
+        assert_eq!(session.lexemes().len(), 4);
+        assert_eq!(session.parse_all::<FilterGrammar>("a b b a b"), Ok(FilterGrammar::Pairs));
+        assert_eq!(session.lexemes().len(), 4);
+        assert!(session.parse_all::<FilterGrammar>("b").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Contains [logos_lexer](crate::logos_lexer), which wraps a token enum derived with
+//! [logos](https://docs.rs/logos) as a parce [Lexer](super::Lexer), for the `logos` feature.
+//!
+//! Logos compiles all of its patterns into one hand-tuned state machine, so it is usually faster
+//! than a generated parce lexer (compare them on your own input with the `logos` bench, or with
+//! `lex_throughput` from the `bench` feature). A project that already has a logos
+//! tokenizer can keep it and still write its grammar with `#[parser]`.
+//!
+//! The token enum has to meet parce's requirements for lexemes: its variants can't have fields, and
+//! it has to be `Debug + Eq + Copy`. Logos doesn't have modes, so the lexer has only the one
+//! named `Default`, and logos' own `#[logos(skip ...)]` decides what is skipped. Since logos
+//! reads from a `&str`, lexing through a [Cursor] copies the rest of the cursor's input into a
+//! string first; [lex](super::Lexer::lex) doesn't.
+
+use super::{Lexeme, LexemeBuffer, SpannedLexeme};
+use super::cursor::Cursor;
+use crate::error::{ParceError, ParceErrorInfo};
+
+pub use logos::Logos;
+
+/// Wraps a token enum derived with [logos](https://docs.rs/logos) as a parce [Lexer](crate::lexer::Lexer).
+///
+/// Write the lexer's name, `for`, the token enum, and then every variant of the enum in braces.
+/// This declares the lexer as a unit struct, and implements [Lexeme](crate::lexer::Lexeme) for
+/// the token enum, with [kind_ids](crate::lexer::Lexeme::kind_id) in the order the variants are
+/// listed. Lexing fails where logos produces an error.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::logos::Logos;
+///
+/// #[derive(Logos, Debug, Copy, Clone, Eq, PartialEq)]
+/// #[logos(skip " +")]
+/// enum Token {
+///     #[regex("[a-z]+")]
+///     Word,
+///     #[regex("[0-9]+")]
+///     Number,
+///     #[token(",")]
+///     Comma
+/// }
+///
+/// parce::logos_lexer!(TokenLexer for Token { Word, Number, Comma });
+///
+/// #[parser(TokenLexer)]
+/// enum Pair {
+///     Pair(String, u32) = "0=Word Comma 1=Number"
+/// }
+///
+/// assert_eq!(TokenLexer::LEXEMES, &["Word", "Number", "Comma"]);
+/// assert_eq!(Token::KIND_COUNT, 3);
+/// assert_eq!(TokenLexer.lex("abc , 12").unwrap()[1].start, 4);
+/// assert_eq!("abc, 12".parse(), Ok(Pair::Pair("abc".to_string(), 12)));
+/// assert_eq!(TokenLexer.lex("abc ; 12").unwrap_err().start, 4);
+/// ```
+#[macro_export]
+macro_rules! logos_lexer {
+    ($(#[$attr:meta])* $vis:vis $lexer:ident for $token:ty { $($variant:ident),* $(,)? }) => {
+        $(#[$attr])*
+        #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+        $vis struct $lexer;
+
+        impl std::fmt::Display for $lexer {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "Default")
+            }
+        }
+
+        impl $crate::lexer::Lexer for $lexer {
+            type Lexemes = $token;
+
+            const LEXEMES: &'static [&'static str] = &[$(stringify!($variant)),*];
+
+            const FINGERPRINT: u64 = $crate::fingerprint::fnv1a($crate::fingerprint::FNV_OFFSET, concat!($(stringify!($variant), " "),*).as_bytes());
+
+            fn lex(self, s: &str) -> Result<Vec<$crate::lexer::SpannedLexeme<$token>>, $crate::error::ParceError> {
+                let mut result = vec![];
+                $crate::lexer::logos::lex_into(s, 0, &mut result)?;
+                Ok(result)
+            }
+
+            fn lex_cursor_into<C: $crate::lexer::cursor::Cursor, B: $crate::lexer::LexemeBuffer<$token>>(self, cursor: C, result: &mut B) -> Result<(), $crate::error::ParceError> {
+                $crate::lexer::logos::lex_cursor_into(cursor, result)
+            }
+
+            fn lex_all_cursor_into<C: $crate::lexer::cursor::Cursor, B: $crate::lexer::LexemeBuffer<$token>>(self, cursor: C, result: &mut B) -> Result<(), $crate::error::ParceError> {
+                $crate::lexer::logos::lex_cursor_into(cursor, result)
+            }
+        }
+
+        impl $crate::lexer::Lexeme for $token {
+            const KIND_COUNT: u16 = <$lexer as $crate::lexer::Lexer>::LEXEMES.len() as u16;
+
+            fn kind_id(&self) -> u16 {
+                [$(<$token>::$variant),*].iter().position(|variant| variant == self)
+                    .expect("logos_lexer! should list every variant of the token enum") as u16
+            }
+
+            fn from_kind_id(id: u16) -> Option<Self> {
+                [$(<$token>::$variant),*].get(id as usize).copied()
+            }
+        }
+    };
+}
+
+/// Lexes `input` from byte `start` with the logos token enum `T`, pushing the lexemes onto `result`.
+#[doc(hidden)]
+pub fn lex_into<T, B: LexemeBuffer<T>>(input: &str, start: usize, result: &mut B) -> Result<(), ParceError>
+    where T: Lexeme + for<'s> Logos<'s, Source = str>, for<'s> <T as Logos<'s>>::Extras: Default {
+    let mut lexer = T::lexer(&input[start..]);
+    while let Some(token) = lexer.next() {
+        let span = lexer.span();
+        match token {
+            Ok(data) => result.push(SpannedLexeme { data, start: start + span.start, len: span.len() }),
+            Err(_) => return Err(ParceError {
+                input: input.to_string(),
+                start: start + span.start,
+                info: ParceErrorInfo::lex("Default".to_string())
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Reads the rest of `cursor` into a string and lexes it with [lex_into].
+#[doc(hidden)]
+pub fn lex_cursor_into<T, C: Cursor, B: LexemeBuffer<T>>(mut cursor: C, result: &mut B) -> Result<(), ParceError>
+    where T: Lexeme + for<'s> Logos<'s, Source = str>, for<'s> <T as Logos<'s>>::Extras: Default {
+    let start = cursor.mark();
+    while cursor.peek().is_some() {
+        cursor.advance();
+    }
+    lex_into(&cursor.input(), start, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use parce::lexer::cursor::{IterCursor, StrCursor};
+    use super::Logos;
+
+    #[derive(Logos, Debug, Copy, Clone, Eq, PartialEq)]
+    #[logos(skip r"[ \n]+")]
+    enum LogosToken {
+        #[token("let")]
+        Let,
+        #[regex("[a-zé]+")]
+        Ident,
+        #[token("=")]
+        Equals,
+        #[regex("[0-9]+")]
+        Number
+    }
+
+    crate::logos_lexer!(LogosTokenLexer for LogosToken { Let, Ident, Equals, Number });
+
+    #[parser(LogosTokenLexer)]
+    enum Binding {
+        Binding { name: String, value: u32 } = "Let name=Ident Equals value=Number"
+    }
+
+    #[test]
+    fn wrapped() {
+        use LogosToken::*;
+
+        let text = "let é =\n42";
+        let lexemes = LogosTokenLexer.lex(text).unwrap();
+        assert_eq!(lexemes.iter().map(|l| (l.data, l.start, l.len)).collect::<Vec<_>>(), vec![
+            (Let, 0, 3), (Ident, 4, 2), (Equals, 7, 1), (Number, 9, 2)
+        ]);
+        assert_eq!(LogosTokenLexer.lex_cursor(IterCursor::new(text.chars())), Ok(lexemes.clone()));
+
+        let mut cursor = StrCursor::new(text);
+        (0..4).for_each(|_| cursor.advance());
+        assert_eq!(LogosTokenLexer.lex_cursor(cursor).unwrap(), lexemes[1..]);
+
+        assert_eq!([Let, Number].map(|l| l.kind_id()), [0, 3]);
+        assert_eq!(LogosToken::from_kind_id(2), Some(Equals));
+        assert_eq!(LogosToken::from_kind_id(4), None);
+
+        let error = LogosTokenLexer.lex("let x = ?").unwrap_err();
+        assert_eq!((error.start, error.info), (8, parce::error::ParceErrorInfo::lex("Default".to_string())));
+
+        assert_eq!("let x = 3".parse(), Ok(Binding::Binding { name: "x".to_string(), value: 3 }));
+        let mut session = ParseSession::new(LogosTokenLexer).with_trivia();
+        assert!(session.parse_all::<Binding>("let y = 4").is_ok());
+        assert!(session.trivia().unwrap().all().is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+//! Contains [TokenCursor], for reading lexemes by hand with lookahead and backtracking.
+
+use super::{Lexeme, SpannedLexeme};
+
+/// A cursor over a slice of lexemes, for code that inspects or consumes them by hand, like a
+/// [TokenFilter](super::filter::TokenFilter) that needs more than one lexeme of context, or a
+/// hand-written parser for the part of a language the generated one can't handle.
+///
+/// [peek](TokenCursor::peek) looks any number of lexemes ahead without consuming anything, and
+/// [checkpoint](TokenCursor::checkpoint) and [rewind](TokenCursor::rewind) let a speculative read
+/// be undone. None of them can go out of bounds: peeking past the end returns `None`.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::lookahead::TokenCursor;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Ident = "[a-z]+",
+///     Colon = ':',
+///     Equals = '=',
+///     #[skip] Space = ' '
+/// }
+///
+/// let text = "x : y = z";
+/// let lexemes = MyLexer::default().lex(text).unwrap();
+/// let mut cursor = TokenCursor::new(&lexemes);
+///
+/// // Is this a typed binding? Try to read one, and back out if it isn't.
+/// let start = cursor.checkpoint();
+/// let typed = cursor.eat(MyLexemes::Ident).is_some() && cursor.eat(MyLexemes::Equals).is_some();
+/// if !typed {
+///     cursor.rewind(start);
+/// }
+/// assert_eq!(cursor.position(), 0);
+///
+/// assert_eq!(cursor.peek(1).map(|l| l.data), Some(MyLexemes::Colon));
+/// assert_eq!(cursor.next().unwrap().text(text), "x");
+/// assert_eq!(cursor.rest().len(), 4);
+/// assert!(cursor.peek(4).is_none());
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TokenCursor<'a, L: Lexeme> {
+    lexemes: &'a [SpannedLexeme<L>],
+    pos: usize
+}
+
+/// A position in a [TokenCursor], returned by [checkpoint](TokenCursor::checkpoint).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Checkpoint(usize);
+
+impl<'a, L: Lexeme> TokenCursor<'a, L> {
+    /// Creates a cursor before the first of `lexemes`.
+    pub fn new(lexemes: &'a [SpannedLexeme<L>]) -> Self {
+        TokenCursor {
+            lexemes,
+            pos: 0
+        }
+    }
+
+    /// The lexeme `n` places ahead of the cursor, without consuming anything. `peek(0)` is the one
+    /// [next](Iterator::next) would return.
+    pub fn peek(&self, n: usize) -> Option<&'a SpannedLexeme<L>> {
+        self.lexemes.get(self.pos.checked_add(n)?)
+    }
+
+    /// Whether the lexeme `n` places ahead of the cursor is a `kind`.
+    pub fn at(&self, n: usize, kind: L) -> bool {
+        self.peek(n).is_some_and(|l| l.data == kind)
+    }
+
+    /// Consumes and returns the next lexeme if it is a `kind`. Otherwise consumes nothing.
+    pub fn eat(&mut self, kind: L) -> Option<&'a SpannedLexeme<L>> {
+        if self.at(0, kind) {
+            self.next()
+        } else {
+            None
+        }
+    }
+
+    /// Saves the current position, to [rewind](Self::rewind) to later.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.pos)
+    }
+
+    /// Moves back (or forward) to a position saved with [checkpoint](Self::checkpoint). A
+    /// checkpoint from a different cursor is clamped to the end of this one's lexemes.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.pos = checkpoint.0.min(self.lexemes.len());
+    }
+
+    /// How many lexemes have been consumed.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// The lexemes that haven't been consumed yet.
+    pub fn rest(&self) -> &'a [SpannedLexeme<L>] {
+        &self.lexemes[self.pos..]
+    }
+
+    /// Whether every lexeme has been consumed.
+    pub fn is_at_end(&self) -> bool {
+        self.pos == self.lexemes.len()
+    }
+}
+
+impl<'a, L: Lexeme> Iterator for TokenCursor<'a, L> {
+    type Item = &'a SpannedLexeme<L>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let lexeme = self.lexemes.get(self.pos)?;
+        self.pos += 1;
+        Some(lexeme)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(LookaheadLexer)]
+    enum LookaheadLexemes {
+        A = 'a',
+        B = 'b',
+        #[skip] Space = ' '
+    }
+
+    #[test]
+    fn lookahead() {
+        use LookaheadLexemes::*;
+        let lexemes = LookaheadLexer::default().lex("a b b a").unwrap();
+        let mut cursor = TokenCursor::new(&lexemes);
+
+        assert!(cursor.at(0, A) && cursor.at(2, B) && !cursor.at(4, A));
+        assert_eq!(cursor.peek(usize::MAX), None);
+        assert_eq!(cursor.eat(B), None);
+        assert_eq!(cursor.eat(A).map(|l| l.start), Some(0));
+
+        let after_a = cursor.checkpoint();
+        assert_eq!(cursor.by_ref().take_while(|l| l.data == B).count(), 2);
+        assert_eq!(cursor.position(), 4);
+        assert!(cursor.is_at_end() && cursor.next().is_none());
+
+        cursor.rewind(after_a);
+        assert_eq!(cursor.rest().len(), 3);
+        assert!(cursor.checkpoint() > TokenCursor::new(&lexemes).checkpoint());
+
+        cursor.rewind(Checkpoint(100));
+        assert!(cursor.is_at_end());
+    }
+}
@@ -0,0 +1,1064 @@
+//! Contains the [Lexer] trait and the [Lexeme] wrapper struct used by the generated lexers.
+
+pub mod checkpoint;
+pub mod cursor;
+pub mod filter;
+#[cfg(feature = "logos")]
+pub mod logos;
+pub mod lookahead;
+pub mod modes;
+pub mod newline;
+pub mod security;
+pub mod stats;
+pub mod trivia;
+
+use crate::error::ParceError;
+#[cfg(feature = "parser")]
+use crate::report::RuleInfo;
+use std::fmt::Debug;
+use tinyvec::TinyVec;
+use std::ops::Range;
+use std::borrow::Cow;
+use crate::span::Span;
+use shrinkwraprs::Shrinkwrap;
+use cursor::{Cursor, StrCursor};
+use trivia::TriviaIndex;
+use modes::ModalToken;
+
+/// Enables lexing a string into a vector of lexemes. The [parce_macros::lexer] attribute macro
+/// generates impls of this trait.
+///
+/// Generated lexers are fieldless enums whose variants are the lexer's modes, so constructing one
+/// is free. Each also gets a `const fn new()` that returns the default mode, for use in `const`
+/// and `static` items. Character classes are computed at compile time, into a bitmask for ASCII
+/// and a table of ranges for everything else, so a lexer has nothing to build before its first use.
+///
+/// Everything else a lexer needs is immutable static data, so lexers are `Send + Sync` and many
+/// threads can lex at once without sharing anything mutable.
+pub trait Lexer: std::fmt::Display + Debug + Copy + Eq + Send + Sync + 'static {
+    /// The enum type that the [parce_macros::lexer] attribute macro was applied to. These are
+    /// wrapped in the [Lexeme] type in the output.
+    type Lexemes: Lexeme;
+
+    /// Names of the lexemes this lexer can output, in declaration order. Skipped and fragment
+    /// lexemes are not included.
+    const LEXEMES: &'static [&'static str];
+
+    /// A hash of the lexer's declaration (its modes, and every lexeme's name, pattern and
+    /// attributes), without doc comments or visibility. It is the same on every build and every platform until
+    /// the declaration changes. See [GrammarInfo::fingerprint](crate::report::GrammarInfo::fingerprint).
+    ///
+    /// Lexers made with [logos_lexer](crate::logos_lexer) only hash the names of their lexemes,
+    /// since their patterns are out of parce's sight.
+    const FINGERPRINT: u64;
+
+    /// Descriptions of every parser rule that was generated for this lexer, in no particular order.
+    ///
+    /// Rules only register themselves with the `inventory` feature, which needs platform support for
+    /// running code before `main` (so not wasm, for example). Without it, this is always empty, and
+    /// [grammar_report_with](crate::report::grammar_report_with) can be given the rules instead.
+    #[cfg(feature = "parser")]
+    fn registered_rules() -> Vec<&'static RuleInfo> {
+        vec![]
+    }
+
+    /// Lexes an input string into a vector of lexemes. Lexemes declared `#[skip]` are left out; see
+    /// [TriviaIndex] for what that means for spans, and how to keep them.
+    fn lex(self, s: &str) -> Result<Vec<SpannedLexeme<Self::Lexemes>>, ParceError> {
+        self.lex_cursor(StrCursor::new(s))
+    }
+
+    /// Lexes `input` from byte `start` on, in this mode, as if the lexer had just reached it. The
+    /// lexemes' spans, and the error's position, are relative to the whole of `input`. This lets a
+    /// tool lex again only the part of a document around an edit, starting in the mode it knows the
+    /// lexer was in there, like the one from a [LineCheckpoint](checkpoint::LineCheckpoint).
+    ///
+    /// Lexemes declared `#[bom]` or `#[shebang]` are only looked for if `start` is 0.
+    ///
+    /// # Panics
+    ///
+    /// If `start` is past the end of `input`, or not on a character boundary.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    ///
+    /// #[lexer(MyLexer)]
+    /// #[modes(Code, Comment)]
+    /// enum MyLexemes {
+    ///     Word = "[a-z]+",
+    ///     #[set_mode(Comment)] Open = "'/*'",
+    ///     #[skip] Space = ' ',
+    ///
+    ///     #[mode(Comment)]
+    ///     #[set_mode(Code)] Close = "'*/'",
+    ///     Text = "[^*]+"
+    /// }
+    ///
+    /// let text = "a /* b c */ d";
+    /// let lexemes = MyLexer::Comment.lex_at(text, 5).unwrap();
+    /// let found: Vec<_> = lexemes.iter().map(|l| (l.data, l.start)).collect();
+    /// assert_eq!(found, vec![(MyLexemes::Text, 5), (MyLexemes::Close, 9), (MyLexemes::Word, 12)]);
+    /// assert_eq!(MyLexer::Code.lex_at(text, 8).unwrap_err().start, 9);
+    /// ```
+    fn lex_at(self, input: &str, start: usize) -> Result<Vec<SpannedLexeme<Self::Lexemes>>, ParceError> {
+        let mut lexemes = vec![];
+        let lexed = match start {
+            0 => self.lex_all_cursor_into(StrCursor::new(input), &mut lexemes),
+            _ => self.lex_rest_cursor_into(StrCursor::new(&input[start..]), &mut lexemes)
+        };
+        lexed.map_err(|e| ParceError {
+            input: input.to_string(),
+            start: e.start + start,
+            info: e.info
+        })?;
+        lexemes.retain(|l| !l.is_skipped());
+        for lexeme in &mut lexemes {
+            lexeme.start += start;
+        }
+        Ok(lexemes)
+    }
+
+    /// Like [lex](Lexer::lex), but moves the lexemes declared `#[skip]` into a [TriviaIndex]
+    /// instead of dropping them.
+    #[allow(clippy::type_complexity)]
+    fn lex_with_trivia(self, s: &str) -> Result<(Vec<SpannedLexeme<Self::Lexemes>>, TriviaIndex<Self::Lexemes>), ParceError> {
+        let mut lexemes = vec![];
+        self.lex_all_cursor_into(StrCursor::new(s), &mut lexemes)?;
+        let mut trivia = TriviaIndex::default();
+        trivia.take_from(&mut lexemes, |l| l.is_skipped());
+        Ok((lexemes, trivia))
+    }
+
+    /// Like [lex](Lexer::lex), but gives the mode each lexeme was matched in, and a
+    /// [ModeChange](ModalToken::ModeChange) wherever the mode changes. See [ModalToken].
+    fn lex_with_modes(self, s: &str) -> Result<Vec<ModalToken<Self>>, ParceError> {
+        let mut lexemes = vec![];
+        self.lex_all_cursor_into(StrCursor::new(s), &mut lexemes)?;
+        Ok(modes::annotate(self, lexemes))
+    }
+
+    /// The mode the lexer is in after matching `lexeme` in this mode. Lexemes without
+    /// `#[set_mode(...)]` stay in the same mode.
+    fn next_mode(self, lexeme: Self::Lexemes) -> Self {
+        let _ = lexeme;
+        self
+    }
+
+    /// Lexes the input behind a [Cursor] into a vector of lexemes.
+    ///
+    /// Use this for input that isn't a single contiguous `&str`, such as a character iterator
+    /// wrapped in an [IterCursor](cursor::IterCursor).
+    fn lex_cursor<C: Cursor>(self, cursor: C) -> Result<Vec<SpannedLexeme<Self::Lexemes>>, ParceError> {
+        let mut result = vec![];
+        self.lex_cursor_into(cursor, &mut result)?;
+        Ok(result)
+    }
+
+    /// Lexes the input behind a [Cursor], appending the lexemes to `result`. This lets a buffer be
+    /// reused across many inputs, or kept in memory from another allocator (see [LexemeBuffer]).
+    /// If lexing fails, `result` keeps the lexemes found before the error.
+    fn lex_cursor_into<C: Cursor, B: LexemeBuffer<Self::Lexemes>>(self, cursor: C, result: &mut B) -> Result<(), ParceError>;
+
+    /// Like [lex_cursor_into](Lexer::lex_cursor_into), but keeps the lexemes declared `#[skip]`,
+    /// so the caller can decide which ones to drop.
+    fn lex_all_cursor_into<C: Cursor, B: LexemeBuffer<Self::Lexemes>>(self, cursor: C, result: &mut B) -> Result<(), ParceError>;
+
+    /// Like [lex_all_cursor_into](Lexer::lex_all_cursor_into), but for input that goes on from
+    /// earlier input, like the rest of a document after a checkpoint. Lexemes declared `#[bom]` or
+    /// `#[shebang]` can only be at the start of the whole document, so they aren't looked for.
+    fn lex_rest_cursor_into<C: Cursor, B: LexemeBuffer<Self::Lexemes>>(self, cursor: C, result: &mut B) -> Result<(), ParceError> {
+        self.lex_all_cursor_into(cursor, result)
+    }
+}
+
+/// Trait implemented by the lexeme enum generated by the [lexer](parce_macros::lexer) macro.
+pub trait Lexeme: Debug + Eq + Copy + Send + Sync + 'static {
+    /// The number of variants in the enum, including skipped lexemes and fragments.
+    const KIND_COUNT: u16;
+
+    /// The paired delimiters declared with `#[pair(Opener, Closer)]`, as `(opener, closer)`. See
+    /// [Paired Delimiters](crate::prelude::lexer#paired-delimiters).
+    const PAIRS: &'static [(Self, Self)] = &[];
+
+    /// A dense id for the variant, from 0 to `KIND_COUNT - 1` in declaration order. Useful for
+    /// indexing arrays by lexeme kind.
+    fn kind_id(&self) -> u16;
+
+    /// The variant with the given [kind_id](Lexeme::kind_id), if there is one.
+    fn from_kind_id(id: u16) -> Option<Self>;
+
+    /// Whether the lexeme was declared `#[ignore_case]`, so it matches text in any case.
+    fn ignores_case(&self) -> bool {
+        false
+    }
+
+    /// Whether the lexeme was declared `#[skip]`, so the lexer leaves it out of its output.
+    fn is_skipped(&self) -> bool {
+        false
+    }
+
+    /// The categories the lexeme was declared in with `#[category(...)]`, in the order they were
+    /// listed. The lexer also generates an `is_` method for each category, like `is_keyword`.
+    fn categories(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The message of the lexeme's `#[deprecated_syntax(message = "...")]` attribute, if it has
+    /// one. See [ParseSession::warnings](crate::parser::ParseSession::warnings).
+    fn deprecation(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Wrapper for the lexeme enum, containing extra information about the location
+/// and length of the lexeme in the string input.
+#[derive(Shrinkwrap, Debug, Default, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct SpannedLexeme<L: Lexeme> {
+    /// The lexeme matched in the input. The Lexeme struct is [Shrinkwrapped](shrinkwraprs)
+    /// around this field.
+    #[shrinkwrap(main_field)] pub data: L,
+
+    /// The index in the input string where the lexeme starts.
+    pub start: usize,
+
+    /// The number characters the lexeme uses in the input.
+    pub len: usize
+}
+
+impl<L: Lexeme> SpannedLexeme<L> {
+    /// The index in the input string just after the lexeme.
+    pub fn end(&self) -> usize {
+        self.start + self.len
+    }
+
+    /// The range of the input string the lexeme covers.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end()
+    }
+
+    /// The part of the input the lexeme covers.
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.end())
+    }
+
+    /// The text the lexeme matched, given the input it was lexed from.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Word = "[a-z]+",
+    ///     #[skip] Space = ' '
+    /// }
+    ///
+    /// let input = "hello  world";
+    /// let lexemes = MyLexer::default().lex(input).unwrap();
+    /// assert!(lexemes[1].is(MyLexemes::Word));
+    /// assert_eq!(lexemes[1].text(input), "world");
+    /// assert_eq!(lexemes[1].range(), 7..12);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the lexeme isn't inside `input`, like when it came from a different string.
+    pub fn text<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.range()]
+    }
+
+    /// The lexeme's text in lowercase if it [ignores case](Lexeme::ignores_case), so that every
+    /// spelling of the same keyword or identifier compares equal. Other lexemes' text is unchanged.
+    pub fn normalized_text<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let text = self.text(input);
+        if self.data.ignores_case() {
+            Cow::Owned(text.to_lowercase())
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// Whether the lexeme is `kind`. Same as comparing with `==`, but reads better in conditions.
+    pub fn is(&self, kind: L) -> bool {
+        self.data == kind
+    }
+}
+
+/// Somewhere lexers can put the lexemes they find, with
+/// [lex_cursor_into](Lexer::lex_cursor_into) and the methods like it.
+///
+/// Implemented for `Vec`, and for the `Vec` of [allocator_api2], which keeps its items in memory
+/// from any [Allocator](allocator_api2::alloc::Allocator), like a bump or region allocator.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::cursor::StrCursor;
+/// use parce::allocator_api2::{alloc::Global, vec::Vec};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Word = "[a-z]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// let mut buffer = Vec::new_in(Global);
+/// MyLexer::default().lex_cursor_into(StrCursor::new("two words"), &mut buffer).unwrap();
+/// assert_eq!(buffer.len(), 2);
+/// assert_eq!(buffer[1].start, 4);
+/// ```
+pub trait LexemeBuffer<L: Lexeme> {
+    /// Adds `lexeme` to the end of the buffer.
+    fn push(&mut self, lexeme: SpannedLexeme<L>);
+}
+
+impl<L: Lexeme> LexemeBuffer<L> for Vec<SpannedLexeme<L>> {
+    fn push(&mut self, lexeme: SpannedLexeme<L>) {
+        Vec::push(self, lexeme)
+    }
+}
+
+impl<L: Lexeme, A: allocator_api2::alloc::Allocator> LexemeBuffer<L> for allocator_api2::vec::Vec<SpannedLexeme<L>, A> {
+    fn push(&mut self, lexeme: SpannedLexeme<L>) {
+        allocator_api2::vec::Vec::push(self, lexeme)
+    }
+}
+
+/// Can only implement Lexeme<T> == T, because trying to implement T == Lexeme<T> violates
+/// orphan rules. When comparing a spanned lexeme to its internal type, put the spanned version first.
+impl<L: Lexeme> PartialEq<L> for SpannedLexeme<L> {
+    fn eq(&self, other: &L) -> bool {
+        self.data == *other
+    }
+}
+
+/// A set of lexeme kinds, stored as one bit per [kind_id](Lexeme::kind_id). The parser keeps one
+/// for each state that can only use certain lexemes, so it can rule an automaton out with a single
+/// bit test, without running its state.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::KindSet;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     A = 'a',
+///     B = 'b',
+///     C = 'c'
+/// }
+///
+/// let set = KindSet::of(&[MyLexemes::A, MyLexemes::C]);
+/// assert!(set.contains(MyLexemes::C.kind_id()));
+/// assert!(!set.contains(MyLexemes::B.kind_id()));
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct KindSet(Vec<u64>);
+
+impl KindSet {
+    /// The set of the kinds of `lexemes`.
+    pub fn of<L: Lexeme>(lexemes: &[L]) -> KindSet {
+        let mut words = vec![0; (L::KIND_COUNT as usize).div_ceil(64)];
+        for lexeme in lexemes {
+            let id = lexeme.kind_id() as usize;
+            words[id / 64] |= 1 << (id % 64);
+        }
+        KindSet(words)
+    }
+
+    /// Whether the kind with [kind_id](Lexeme::kind_id) `kind` is in the set.
+    pub fn contains(&self, kind: u16) -> bool {
+        self.0.get(kind as usize / 64).is_some_and(|word| word & (1 << (kind % 64)) != 0)
+    }
+}
+
+/// Error returned when converting text to a lexeme with [FromStr](std::str::FromStr) or
+/// [TryFrom](std::convert::TryFrom), if no lexeme's pattern is exactly that literal.
+///
+/// Only lexemes whose whole pattern is a single literal, like `If = "'if'"`, can be converted
+/// from text. Fragments can't be.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::UnknownLiteral;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     If = "'if'",
+///     Plus = '+',
+///     Ident = "[a-z]+"
+/// }
+///
+/// assert_eq!("if".parse(), Ok(MyLexemes::If));
+/// assert_eq!(MyLexemes::try_from("+"), Ok(MyLexemes::Plus));
+/// assert_eq!("abc".parse::<MyLexemes>(), Err(UnknownLiteral("abc".to_string())));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnknownLiteral(pub String);
+
+impl std::fmt::Display for UnknownLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\" is not the literal pattern of any lexeme", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLiteral {}
+
+/// Two lexemes that can match the same input, as listed in the `OVERLAPS` const generated by
+/// `#[overlap_matrix]`.
+///
+/// When both lexemes match the same length of input, the one declared first wins, so `second`
+/// is never produced for `example`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct LexemeOverlap {
+    /// The lexeme declared first.
+    pub first: &'static str,
+
+    /// The lexeme declared second.
+    pub second: &'static str,
+
+    /// The shortest input both lexemes match completely.
+    pub example: &'static str
+}
+
+/// Whether `c` is in one of `ranges`, which must be sorted and not overlap. Used by the generated
+/// lexers and parsers to test characters against a class, whose ranges are computed at compile
+/// time.
+pub fn class_contains(ranges: &[(char, char)], c: char) -> bool {
+    ranges.binary_search_by(|&(first, last)| {
+        if last < c {
+            std::cmp::Ordering::Less
+        } else if first > c {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    }).is_ok()
+}
+
+/// Removes adjacent duplicates from the list of lengths a pattern matched. Used by the generated
+/// lexers, whose patterns produce their possible match lengths in sorted order.
+pub fn dedup_tiny(tiny: &mut TinyVec<[usize; 2]>) {
+    if let Some(mut i) = tiny.len().checked_sub(1) {
+        while i > 0 {
+            if tiny[i] == tiny[i - 1] {
+                tiny.remove(i);
+            }
+            i -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    /// Shorthand to make testing easier
+    macro_rules! lexemes {
+        ($($lexeme:ident $start:literal $len:literal),+) => {
+            Ok(vec![
+                $(
+                    SpannedLexeme {
+                        data: $lexeme,
+                        start: $start,
+                        len: $len
+                    }
+                ),+
+            ])
+        }
+    }
+
+    macro_rules! lexer_error {
+        ($input:literal $start:literal) => {
+            Err(ParceError {
+                input: $input.to_string(),
+                start: $start,
+                info: parce::error::ParceErrorInfo::lex("Default".to_string())
+            })
+        };
+        ($input:literal $start:literal $mode:literal) => {
+            Err(ParceError {
+                input: $input.to_string(),
+                start: $start,
+                info: parce::error::ParceErrorInfo::lex($mode.to_string())
+            })
+        }
+    }
+
+    use crate::prelude::*;
+    use crate as parce;
+    use super::*;
+
+    /////// LITERALS
+
+    #[lexer(LiteralLexer)]
+    enum LiteralLexeme {
+        A = 'a',
+        B = "'b'"
+    }
+
+    #[test]
+    fn basic_pass() {
+        use LiteralLexeme::*;
+
+        assert_eq!(LiteralLexer::default().lex("ab"), lexemes![A 0 1, B 1 1]);
+    }
+
+    #[test]
+    fn basic_fail() {
+        assert_eq!(LiteralLexer::default().lex("a b"), lexer_error!("a b" 1));
+    }
+
+    /////// OPERATORS
+
+    #[lexer(OperatorLexer)]
+    enum OperatorLexeme {
+        A = "'a'",
+        BPlus = "'b'+",
+        CStar = "'c'*",
+        DQuestion = "'d'?",
+        ERange = "'e'{2,4}",
+        FExact = "'f'{2}",
+        GMin = "'g'{2,}"
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(OperatorLexer::default().lex(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn plus() {
+        use OperatorLexeme::*;
+
+        assert_eq!(OperatorLexer::default().lex("bbbabb"), lexemes![BPlus 0 3, A 3 1, BPlus 4 2]);
+    }
+
+    #[test]
+    fn star() {
+        use OperatorLexeme::*;
+
+        assert_eq!(OperatorLexer::default().lex("cccacc"), lexemes![CStar 0 3, A 3 1, CStar 4 2]);
+    }
+
+    #[test]
+    fn question() {
+        use OperatorLexeme::*;
+
+        assert_eq!(OperatorLexer::default().lex("a"), lexemes![A 0 1]);
+        assert_eq!(OperatorLexer::default().lex("d"), lexemes![DQuestion 0 1]);
+        assert_eq!(OperatorLexer::default().lex("dd"), lexemes![DQuestion 0 1, DQuestion 1 1]);
+    }
+
+    #[test]
+    fn range() {
+        use OperatorLexeme::*;
+
+        assert_eq!(OperatorLexer::default().lex("e"), lexer_error!("e" 0));
+        assert_eq!(OperatorLexer::default().lex("ee"), lexemes![ERange 0 2]);
+        assert_eq!(OperatorLexer::default().lex("eee"), lexemes![ERange 0 3]);
+        assert_eq!(OperatorLexer::default().lex("eeee"), lexemes![ERange 0 4]);
+        assert_eq!(OperatorLexer::default().lex("eeeee"), lexer_error!("eeeee" 4));
+
+        assert_eq!(OperatorLexer::default().lex("f"), lexer_error!("f" 0));
+        assert_eq!(OperatorLexer::default().lex("ff"), lexemes![FExact 0 2]);
+        assert_eq!(OperatorLexer::default().lex("fff"), lexer_error!("fff" 2));
+
+        assert_eq!(OperatorLexer::default().lex("g"), lexer_error!("g" 0));
+        assert_eq!(OperatorLexer::default().lex("gg"), lexemes![GMin 0 2]);
+        assert_eq!(OperatorLexer::default().lex("ggg"), lexemes![GMin 0 3]);
+        assert_eq!(OperatorLexer::default().lex("gggg"), lexemes![GMin 0 4]);
+    }
+
+    /////// NESTING & SEQUENCES
+
+    #[lexer(NestingLexer)]
+    enum NestingLexeme {
+        A = "'a'",
+        Nest = "'b' A 'b'",
+        DoubleNest = "'a' Nest* 'a'"
+    }
+
+    #[test]
+    fn nest_and_sequence() {
+        use NestingLexeme::*;
+
+        assert_eq!(NestingLexer::default().lex("bab"), lexemes![Nest 0 3]);
+        assert_eq!(NestingLexer::default().lex("ababbaba"), lexemes![DoubleNest 0 8]);
+        assert_eq!(NestingLexer::default().lex("aa"), lexemes![DoubleNest 0 2]);
+        assert_eq!(NestingLexer::default().lex("a"), lexemes![A 0 1]);
+    }
+
+    /////// PRIORITY & DOTS
+
+    #[lexer(DotLexer)]
+    enum DotLexeme {
+        A = "'a'",
+        Dot = ".",
+        Junk = "'c' .* 'c'"
+    }
+
+    #[test]
+    fn priority_and_dot() {
+        use DotLexeme::*;
+
+        assert_eq!(DotLexer::default().lex("ab"), lexemes![A 0 1, Dot 1 1]);
+        assert_eq!(DotLexer::default().lex("cxcvbxc"), lexemes![Junk 0 7]);
+    }
+
+    /////// CLASSES
+
+    #[lexer(ClassLexer)]
+    enum ClassLexeme {
+        AB = "[ab]",
+        Number = "[0-9]+",
+        String = r#" '"' [^"\n\r]* '"' "#,
+        Cyrillic = r"[\x{400}-\x{4ff}\x{1F600}]+",
+        #[ignore_case] Greek = r"[\x{3b1}-\x{3c9}]"
+    }
+
+    #[test]
+    fn class() {
+        use ClassLexeme::*;
+
+        const LEXER: ClassLexer = ClassLexer::new();
+        assert_eq!(LEXER.lex("ab"), lexemes![AB 0 1, AB 1 1]);
+
+        assert_eq!(ClassLexer::default().lex("a"), lexemes![AB 0 1]);
+        assert_eq!(ClassLexer::default().lex("b"), lexemes![AB 0 1]);
+        assert_eq!(ClassLexer::default().lex("c"), lexer_error!("c" 0));
+
+        assert_eq!(ClassLexer::default().lex("1105"), lexemes![Number 0 4]);
+
+        assert_eq!(ClassLexer::default().lex(r#""Hello World!""#), lexemes![String 0 14]);
+        assert_eq!(ClassLexer::default().lex(r#""Unclosed"#), lexer_error!(r#""Unclosed"# 0));
+        assert_eq!(ClassLexer::default().lex(r#""Extra" "#), lexer_error!(r#""Extra" "# 7));
+
+        assert_eq!(ClassLexer::default().lex("жд😀"), lexemes![Cyrillic 0 8]);
+        assert_eq!(ClassLexer::default().lex("ж😁"), lexer_error!("ж😁" 2));
+        assert_eq!(ClassLexer::default().lex("Ωω"), lexemes![Greek 0 2, Greek 2 2]);
+        assert_eq!(ClassLexer::default().lex("é"), lexer_error!("é" 0));
+    }
+
+    #[test]
+    fn class_ranges() {
+        let ranges = [('a', 'c'), ('x', 'x'), ('\u{400}', '\u{4ff}')];
+        for (c, expected) in [('a', true), ('b', true), ('c', true), ('d', false), ('x', true), ('y', false), ('\u{3ff}', false), ('ж', true), ('😀', false)] {
+            assert_eq!(class_contains(&ranges, c), expected, "{:?}", c);
+        }
+        assert!(!class_contains(&[], 'a'));
+    }
+
+    /////// UNICODE FRAGMENTS
+
+    #[lexer(UnicodeLexer)]
+    enum UnicodeLexeme {
+        Ident = "XID_IDENT",
+        Private = "'_' XID_CONTINUE*",
+        Arrow = "'→'",
+        Newline = "LINE_TERMINATOR",
+        #[skip] Space = "WHITESPACE_UNICODE"
+    }
+
+    #[test]
+    fn unicode_fragments() {
+        use UnicodeLexeme::*;
+
+        assert_eq!(UnicodeLexer::default().lex("café_1 → Δx"), lexemes![Ident 0 7, Arrow 8 3, Ident 12 3]);
+        assert_eq!(UnicodeLexer::default().lex("_x\r\n\u{2028}\u{3000}名前"), lexemes![Private 0 2, Newline 2 2, Newline 4 3, Ident 10 6]);
+        assert_eq!(UnicodeLexer::default().lex("\n\r"), lexemes![Newline 0 1, Newline 1 1]);
+        // Digits and combining marks can continue an identifier, but not start one.
+        assert_eq!(UnicodeLexer::default().lex("1x"), lexer_error!("1x" 0));
+        assert_eq!(UnicodeLexer::default().lex("a\u{301}"), lexemes![Ident 0 3]);
+        assert_eq!(UnicodeLexer::default().lex("\u{301}"), lexer_error!("\u{301}" 0));
+    }
+
+    /////// PROLOGUE
+
+    #[lexer(ScriptLexer)]
+    enum ScriptLexeme {
+        #[bom] Bom,
+        Word = "[a-z]+",
+        Hash = "'#' [^\n]*",
+        #[shebang] Shebang,
+        #[skip] Space = "[ \n]"
+    }
+
+    #[test]
+    fn prologue() {
+        use ScriptLexeme::*;
+
+        assert_eq!(ScriptLexer::default().lex("\u{feff}#!/bin/sh -e\nrun"), lexemes![Word 16 3]);
+        let (lexemes, trivia) = ScriptLexer::default().lex_with_trivia("\u{feff}#!/bin/sh -e\nrun").unwrap();
+        assert_eq!(lexemes, vec![SpannedLexeme { data: Word, start: 16, len: 3 }]);
+        assert_eq!(trivia.leading(0).iter().map(|l| (l.data, l.start, l.len)).collect::<Vec<_>>(), vec![(Bom, 0, 3), (Shebang, 3, 12), (Space, 15, 1)]);
+
+        // Either can be left out, but neither is matched anywhere but the start.
+        assert_eq!(ScriptLexer::default().lex("#!x\na"), lexemes![Word 4 1]);
+        assert_eq!(ScriptLexer::default().lex("a #!x"), lexemes![Word 0 1, Hash 2 3]);
+        assert_eq!(ScriptLexer::default().lex("a\u{feff}"), lexer_error!("a\u{feff}" 1));
+        assert_eq!(ScriptLexer::default().lex(" #!x"), lexemes![Hash 1 3]);
+        assert!(!ScriptLexer::LEXEMES.contains(&"Shebang"));
+        assert!(Shebang.is_skipped());
+
+        let mut rest = vec![];
+        ScriptLexer::default().lex_rest_cursor_into(StrCursor::new("#!x"), &mut rest).unwrap();
+        assert_eq!(rest, vec![SpannedLexeme { data: Hash, start: 0, len: 3 }]);
+    }
+
+    /////// GREEDINESS
+
+    #[lexer(GreedyLexer)]
+    enum GreedyLexeme {
+        A = "'a' .* 'b'",
+        One = "'c' [de]*",
+        Two = "'e' 'd' 'e' 'f'*",
+        Both = "One Two"
+    }
+
+    #[test]
+    fn greedy() {
+        use GreedyLexeme::*;
+
+        assert_eq!(GreedyLexer::default().lex("abbb"), lexemes![A 0 4]);
+        assert_eq!(GreedyLexer::default().lex("cddedefff"), lexemes![Both 0 9]);
+    }
+
+    /////// OR & GROUPS
+
+    #[lexer(OrLexer)]
+    enum OrLexeme {
+        AB = "'a' | 'b'",
+        Group = "'c' ( AB | 'd' )+ 'c'"
+    }
+
+    #[test]
+    fn or() {
+        use OrLexeme::*;
+
+        assert_eq!(OrLexer::default().lex("a"), lexemes![AB 0 1]);
+        assert_eq!(OrLexer::default().lex("b"), lexemes![AB 0 1]);
+        assert_eq!(OrLexer::default().lex("c"), lexer_error!("c" 0));
+
+        assert_eq!(OrLexer::default().lex("cabdbdabdabdc"), lexemes![Group 0 13])
+    }
+
+    /////// SKIPS
+
+    #[lexer(SkipLexer)]
+    enum SkipLexeme {
+        #[skip] WhiteSpace = "[ \n\r\t]",
+        A = "'a'",
+        B = "'b'"
+    }
+
+    #[test]
+    fn skip() {
+        use SkipLexeme::*;
+
+        assert_eq!(SkipLexer::default().lex(" a\nb "), lexemes![A 1 1, B 3 1]);
+
+        let mut all = vec![];
+        let lexed = SkipLexer::default().lex_all_cursor_into(StrCursor::new(" a\nb"), &mut all).map(|()| all);
+        assert_eq!(lexed, lexemes![WhiteSpace 0 1, A 1 1, WhiteSpace 2 1, B 3 1]);
+        assert!(WhiteSpace.is_skipped() && !A.is_skipped());
+    }
+
+    /////// FRAGMENTS
+
+    #[lexer(FragmentLexer)]
+    enum FragmentLexeme {
+        #[frag] A = "'a'",
+        AA = "A A"
+    }
+
+    #[test]
+    fn fragment() {
+        use FragmentLexeme::*;
+
+        assert_eq!(FragmentLexer::default().lex("aa"), lexemes![AA 0 2]);
+        assert_eq!(FragmentLexer::default().lex("a"), lexer_error!["a" 0]);
+    }
+
+    /////// MODES
+
+    #[lexer(ModalLexer)]
+    #[modes(One, Two)]
+    enum ModalLexeme {
+        A = "'a'",
+        #[set_mode(Two)] B = "'b'",
+
+        #[mode(Two)]
+
+        C = "'c'",
+        #[set_mode(One)] D = "'d'",
+
+        #[mode(One, Two)]
+        E = "'e'"
+    }
+
+    #[test]
+    fn change_mode() {
+        use ModalLexeme::*;
+
+        assert_eq!(ModalLexer::default().lex("aeabcecda"), lexemes![
+            A 0 1, E 1 1, A 2 1, B 3 1, C 4 1, E 5 1, C 6 1, D 7 1, A 8 1
+        ]);
+        assert_eq!(ModalLexer::default().lex("d"), lexer_error!["d" 0 "One"]);
+
+        assert_eq!(ModalLexer::Two.lex("d"), lexemes![D 0 1]);
+    }
+
+    #[test]
+    fn mode_annotations() {
+        use ModalLexeme::*;
+        use ModalLexer::{One, Two};
+        use modes::ModalToken;
+
+        let lexeme = |data, start, mode| ModalToken::Lexeme { lexeme: SpannedLexeme { data, start, len: 1 }, mode };
+        assert_eq!(ModalLexer::default().lex_with_modes("abcda"), Ok(vec![
+            lexeme(A, 0, One),
+            lexeme(B, 1, One),
+            ModalToken::ModeChange { offset: 2, from: One, to: Two },
+            lexeme(C, 2, Two),
+            lexeme(D, 3, Two),
+            ModalToken::ModeChange { offset: 4, from: Two, to: One },
+            lexeme(A, 4, One)
+        ]));
+        assert_eq!(Two.lex_with_modes("e"), Ok(vec![lexeme(E, 0, Two)]));
+        assert_eq!(ModalLexer::default().lex_with_modes("c"), lexer_error!["c" 0 "One"]);
+        assert_eq!((One.next_mode(B), Two.next_mode(B), Two.next_mode(E)), (Two, Two, Two));
+    }
+
+    /////// CURSORS
+
+    #[lexer(CursorLexer)]
+    enum CursorLexeme {
+        Word = "[\\p{Alphabetic}]+",
+        Arrow = "[\\x{2192}]",
+        #[skip] WhiteSpace = "[ \n\r\t]"
+    }
+
+    #[test]
+    fn iter_cursor() {
+        use CursorLexeme::*;
+
+        let input = "abc → dé";
+        assert_eq!(CursorLexer::default().lex_cursor(IterCursor::new(input.chars())), lexemes![Word 0 3, Arrow 4 3, Word 8 3]);
+        assert_eq!(CursorLexer::default().lex_cursor(IterCursor::new(input.chars())), CursorLexer::default().lex(input));
+        assert_eq!(CursorLexer::default().lex_cursor(IterCursor::new("ab!".chars())), lexer_error!("ab!" 2));
+    }
+
+    /////// CONFIGURED ITEMS
+
+    mod configured {
+        use crate as parce;
+        use parce::prelude::*;
+
+        #[lexer(ConfiguredLexer, derive(Hash, PartialOrd, Ord), vis = pub(crate))]
+        #[modes(One, Two)]
+        pub(crate) enum ConfiguredLexeme {
+            A = 'a'
+        }
+
+        #[lexer(RepeatedDeriveLexer, derive(Clone, Hash))]
+        pub(crate) enum RepeatedDeriveLexeme {
+            A = 'a'
+        }
+    }
+
+    #[test]
+    fn configured_items() {
+        use configured::ConfiguredLexer;
+        use std::collections::HashSet;
+
+        assert!(ConfiguredLexer::One < ConfiguredLexer::Two);
+        let modes: HashSet<_> = [ConfiguredLexer::One, ConfiguredLexer::Two, ConfiguredLexer::new()].into_iter().collect();
+        assert_eq!(modes.len(), 2);
+
+        let repeated: HashSet<_> = [configured::RepeatedDeriveLexer::new()].into_iter().collect();
+        assert_eq!(repeated.len(), 1);
+    }
+
+    /////// FROM STR
+
+    #[test]
+    fn from_str() {
+        assert_eq!("a".parse(), Ok(LiteralLexeme::A));
+        assert_eq!(LiteralLexeme::try_from("b"), Ok(LiteralLexeme::B));
+        assert_eq!("ab".parse::<LiteralLexeme>(), Err(UnknownLiteral("ab".to_string())));
+
+        // Only whole-literal patterns, and not fragments.
+        assert_eq!("if".parse(), Ok(OverlapLexeme::If));
+        assert!("x".parse::<OverlapLexeme>().is_err());
+        assert_eq!("()".parse(), Ok(OverlapLexeme::Paren));
+    }
+
+    /////// IGNORE CASE
+
+    #[lexer(CaseLexer)]
+    enum CaseLexeme {
+        #[ignore_case] Select = "'select'",
+        Exact = "'X'",
+        #[ignore_case] Ident = "[a-z]+",
+        #[skip] Space = ' '
+    }
+
+    #[lexer(AllCaseLexer)]
+    #[ignore_case]
+    enum AllCaseLexeme {
+        Begin = "'begin'",
+        End = "'end'"
+    }
+
+    #[test]
+    fn ignore_case() {
+        use CaseLexeme::*;
+
+        let input = "SeLeCt Foo X";
+        let lexed = CaseLexer::default().lex(input);
+        assert_eq!(lexed, lexemes!(Select 0 6, Ident 7 3, Exact 11 1));
+        let lexed = lexed.unwrap();
+        assert_eq!(lexed[0].text(input), "SeLeCt");
+        assert_eq!(lexed[1].normalized_text(input), "foo");
+        assert_eq!(lexed[2].normalized_text(input), "X");
+        assert!(CaseLexer::default().lex("x").unwrap()[0].is(Ident));
+
+        assert_eq!("SELECT".parse(), Ok(Select));
+        assert!("x".parse::<CaseLexeme>().is_err());
+        assert!(Select.ignores_case() && !Exact.ignores_case());
+
+        use AllCaseLexeme::*;
+        assert_eq!(AllCaseLexer::default().lex("BEGINend"), lexemes!(Begin 0 5, End 5 3));
+    }
+
+    /////// KIND IDS
+
+    #[test]
+    fn kind_ids() {
+        use ModalLexeme::*;
+
+        assert_eq!(ModalLexeme::KIND_COUNT, 5);
+        assert_eq!([A, B, C, D, E].map(|l| l.kind_id()), [0, 1, 2, 3, 4]);
+        assert_eq!(ModalLexeme::from_kind_id(3), Some(D));
+        assert_eq!(ModalLexeme::from_kind_id(5), None);
+
+        // Fragments and skipped lexemes still get ids, so adding one doesn't shift the others.
+        assert_eq!(OverlapLexeme::KIND_COUNT, 10);
+        assert_eq!(OverlapLexeme::Word.kind_id(), 9);
+    }
+
+    /////// CATEGORIES
+
+    #[lexer(CategoryLexer)]
+    #[modes(Code, Text)]
+    enum CategoryLexeme {
+        #[category(Keyword)] Fn = "'fn'",
+        #[category(Keyword, Reserved)] Let = "'let'",
+        #[category(Literal)] #[frag] Digit = "[0-9]",
+        Ident = "[a-z]+",
+        #[skip] #[category(Trivia)] Space = ' ',
+
+        #[mode(Text)]
+        #[category(Trivia)] Text = "[^{]+"
+    }
+
+    #[test]
+    fn categories() {
+        use CategoryLexeme::*;
+
+        assert!(Fn.is_keyword() && Let.is_keyword() && !Ident.is_keyword());
+        assert!(Let.is_reserved() && !Fn.is_reserved());
+        assert!(Space.is_trivia() && Text.is_trivia() && Digit.is_literal());
+        assert_eq!(Let.categories(), &["Keyword", "Reserved"]);
+        assert_eq!(Ident.categories(), &[] as &[&str]);
+
+        let lexemes = CategoryLexer::default().lex("let x").unwrap();
+        assert_eq!(lexemes.iter().filter(|l| l.is_keyword()).count(), 1);
+        // Lexers without categories still have the method.
+        assert_eq!(LiteralLexeme::A.categories(), &[] as &[&str]);
+    }
+
+    /////// OVERLAPS
+
+    #[lexer(OverlapLexer)]
+    #[overlap_matrix]
+    #[modes(Code, Text)]
+    enum OverlapLexeme {
+        If = "'if'",
+        Ident = "Letter IdentTail*",
+        #[frag] Letter = "[a-z]",
+        #[frag] IdentTail = "[a-z0-9_]",
+        #[frag] Digit = "[0-9]",
+        Number = "Digit+ ('.' Digit*)?",
+        Float = "Digit* '.' Digit+",
+        Nested = "'(' Nested? ')'",
+        Paren = "'()'",
+
+        #[mode(Text)]
+        Word = "Letter+"
+    }
+
+    #[test]
+    fn overlap_matrix() {
+        assert_eq!(OverlapLexer::OVERLAPS, &[
+            LexemeOverlap { first: "If", second: "Ident", example: "if" },
+            LexemeOverlap { first: "Number", second: "Float", example: "0.0" }
+        ]);
+    }
+
+    /////// PATTERN CONSTANTS
+
+    crate::pattern! {
+        HEX_DIGIT = "[0-9a-f]";
+        HEX = p!("0x" HEX_DIGIT+);
+        EQ = "'=='"
+    }
+
+    #[lexer(ConstantLexer)]
+    #[modes(Code, Comment)]
+    enum ConstantLexeme {
+        Number = "HEX | [0-9]+",
+        // A lexeme with the same name as a constant hides it.
+        EQ = '=',
+        Assign = "EQ",
+        #[set_mode(Comment)] Slash = "'//'",
+        #[mode(Comment)] Text = "HEX_DIGIT+"
+    }
+
+    #[test]
+    fn pattern_constants() {
+        use ConstantLexeme::*;
+
+        assert_eq!(ConstantLexer::default().lex("0xff=12"), lexemes![Number 0 4, EQ 4 1, Number 5 2]);
+        assert_eq!(ConstantLexer::default().lex("//a1"), lexemes![Slash 0 2, Text 2 2]);
+    }
+
+    /////// MULTIPLE LEXERS
+
+    #[lexer(StrictLexer, LenientLexer, LoudLexer)]
+    #[modes(Code, Quote)]
+    enum DialectLexeme {
+        Word = "[a-z]+",
+        #[lexers(LenientLexer, LoudLexer)] Upper = "[A-Z]+",
+        #[lexers(LoudLexer)] Bang = '!',
+        #[set_mode(Quote)] Open = '"',
+        #[mode(Quote)] #[set_mode(Code)] Close = '"',
+        #[mode(Quote)] #[lexers(LenientLexer)] Text = "[^\"]+",
+        #[mode(Code, Quote)] #[skip] Space = ' '
+    }
+
+    #[test]
+    fn multiple_lexers() {
+        use DialectLexeme::*;
+
+        assert_eq!(StrictLexer::default().lex("ab"), lexemes![Word 0 2]);
+        assert_eq!(StrictLexer::default().lex("aB"), lexer_error!("aB" 1 "Code"));
+        assert_eq!(LenientLexer::default().lex("aB"), lexemes![Word 0 1, Upper 1 1]);
+        assert_eq!(LenientLexer::default().lex("\"A!\""), lexemes![Open 0 1, Text 1 2, Close 3 1]);
+        assert_eq!(LoudLexer::default().lex("A!"), lexemes![Upper 0 1, Bang 1 1]);
+        assert!(LoudLexer::default().lex("\"A!\"").is_err());
+        assert_eq!(LenientLexer::Quote.to_string(), "Quote");
+
+        assert_eq!(StrictLexer::LEXEMES, &["Word", "Open", "Close"]);
+        assert_eq!(LoudLexer::LEXEMES, &["Word", "Upper", "Bang", "Open", "Close"]);
+        assert_ne!(StrictLexer::FINGERPRINT, LenientLexer::FINGERPRINT);
+        assert_ne!(LenientLexer::FINGERPRINT, LoudLexer::FINGERPRINT);
+    }
+}
@@ -0,0 +1,88 @@
+//! Contains [ModalToken], which [lex_with_modes](super::Lexer::lex_with_modes) uses to say which
+//! mode each lexeme was matched in, and where the mode changed.
+
+use super::{Lexer, SpannedLexeme};
+
+/// One item of the output of [lex_with_modes](super::Lexer::lex_with_modes): a lexeme with the mode
+/// it was matched in, or a change of mode.
+///
+/// An editor that re-highlights part of a file can restart the lexer at any lexeme with the mode
+/// it was matched in, instead of lexing from the start of the file to find out what mode it is in.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::modes::ModalToken;
+///
+/// #[lexer(TemplateLexer)]
+/// #[modes(Text, Code)]
+/// enum TemplateLexemes {
+///     #[set_mode(Code)] Open = "'{{'",
+///     Text = "[^{]+",
+///
+///     #[mode(Code)]
+///     #[set_mode(Text)] Close = "'}}'",
+///     Ident = "[a-z]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// let text = "hi {{ name }}!";
+/// let tokens = TemplateLexer::default().lex_with_modes(text).unwrap();
+/// let modes: Vec<_> = tokens.iter().filter_map(|t| t.lexeme().map(|(l, mode)| (l.text(text), mode))).collect();
+/// assert_eq!(modes, vec![
+///     ("hi ", TemplateLexer::Text),
+///     ("{{", TemplateLexer::Text),
+///     ("name", TemplateLexer::Code),
+///     ("}}", TemplateLexer::Code),
+///     ("!", TemplateLexer::Text)
+/// ]);
+/// assert_eq!(tokens[2], ModalToken::ModeChange { offset: 5, from: TemplateLexer::Text, to: TemplateLexer::Code });
+///
+/// // Lexing from the middle works, given the mode there.
+/// let rest = TemplateLexer::Code.lex(&text[5..]).unwrap();
+/// assert_eq!(rest[0].text(&text[5..]), "name");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ModalToken<L: Lexer> {
+    /// A lexeme, and the mode the lexer was in when it matched it.
+    Lexeme {
+        lexeme: SpannedLexeme<L::Lexemes>,
+        mode: L
+    },
+    /// The lexer changed mode, starting at byte `offset`, after the lexeme before it. Comes after
+    /// that lexeme, or in its place if it was skipped.
+    ModeChange {
+        offset: usize,
+        from: L,
+        to: L
+    }
+}
+
+impl<L: Lexer> ModalToken<L> {
+    /// The lexeme and its mode, if this is a lexeme.
+    pub fn lexeme(&self) -> Option<(&SpannedLexeme<L::Lexemes>, L)> {
+        match self {
+            ModalToken::Lexeme { lexeme, mode } => Some((lexeme, *mode)),
+            ModalToken::ModeChange { .. } => None
+        }
+    }
+}
+
+/// Annotates `lexemes`, lexed from the start of the input in mode `mode`, with modes. Skipped
+/// lexemes are dropped, but the mode changes they cause are kept.
+pub(crate) fn annotate<L: Lexer>(mut mode: L, lexemes: Vec<SpannedLexeme<L::Lexemes>>) -> Vec<ModalToken<L>> {
+    use super::Lexeme;
+
+    let mut tokens = Vec::with_capacity(lexemes.len());
+    for lexeme in lexemes {
+        let next = mode.next_mode(lexeme.data);
+        let offset = lexeme.end();
+        if !lexeme.is_skipped() {
+            tokens.push(ModalToken::Lexeme { lexeme, mode });
+        }
+        if next != mode {
+            tokens.push(ModalToken::ModeChange { offset, from: mode, to: next });
+            mode = next;
+        }
+    }
+    tokens
+}
@@ -0,0 +1,182 @@
+//! Contains [NewlinePolicy], which decides which newlines end statements in languages where
+//! statements don't need a terminator, like Go, Swift, or JavaScript.
+
+use super::{Lexeme, SpannedLexeme};
+use super::filter::TokenFilter;
+
+/// A [TokenFilter] that drops the newline lexemes that shouldn't end a statement, so a grammar can
+/// use the rest as terminators.
+///
+/// The lexer has to output newlines as a normal (not skipped) lexeme. Then a newline is dropped if
+/// it is:
+/// - inside a pair of [brackets](NewlinePolicy::brackets), like the arguments of a call,
+/// - right after a lexeme that [continues](NewlinePolicy::continue_after) onto the next line, like
+///   a binary operator or comma,
+/// - right before a lexeme that [continues](NewlinePolicy::continue_before) the previous line, like a
+///   leading `.` in a method chain,
+/// - at the start of the input, or right after another newline that was kept.
+///
+/// A newline at the end of the input is kept, so the last statement can be terminated like the others.
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// use parce::prelude::*;
+/// use parce::lexer::newline::NewlinePolicy;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Ident = "[a-z]+",
+///     Plus = '+',
+///     LParen = '(',
+///     RParen = ')',
+///     Newline = '\n',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Statement {
+///     Statements(Vec<Line>) = "0*"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Line {
+///     Sum = "Ident (Plus Ident)* Newline",
+///     Call = "Ident LParen Ident* RParen Newline"
+/// }
+///
+/// let policy = NewlinePolicy::new(MyLexemes::Newline)
+///     .brackets(MyLexemes::LParen, MyLexemes::RParen)
+///     .continue_after(MyLexemes::Plus);
+/// let mut session = ParseSession::new(MyLexer::default()).with_filter(policy);
+///
+/// let parsed = session.parse_all::<Statement>("\na +\n b\n\nf(\n x\n)\n").unwrap();
+/// assert_eq!(parsed, Statement::Statements(vec![Line::Sum, Line::Call]));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NewlinePolicy<L: Lexeme> {
+    newline: L,
+    brackets: Vec<(L, L)>,
+    continue_after: Vec<L>,
+    continue_before: Vec<L>,
+    /// How many brackets are open at the current lexeme.
+    depth: usize,
+    /// The newline that will be kept unless the next lexeme continues the line. The rest of its
+    /// run is dropped as it is read.
+    pending: Option<SpannedLexeme<L>>
+}
+
+impl<L: Lexeme> NewlinePolicy<L> {
+    /// Creates a policy where `newline` is the lexeme to filter. Without any more configuration,
+    /// this only collapses blank lines and drops leading newlines.
+    pub fn new(newline: L) -> Self {
+        NewlinePolicy {
+            newline,
+            brackets: vec![],
+            continue_after: vec![],
+            continue_before: vec![],
+            depth: 0,
+            pending: None
+        }
+    }
+
+    /// Newlines between `open` and `close` are dropped. Pairs can nest inside each other.
+    pub fn brackets(mut self, open: L, close: L) -> Self {
+        self.brackets.push((open, close));
+        self
+    }
+
+    /// Newlines right after `lexeme` are dropped.
+    pub fn continue_after(mut self, lexeme: L) -> Self {
+        self.continue_after.push(lexeme);
+        self
+    }
+
+    /// Newlines right before `lexeme` are dropped.
+    pub fn continue_before(mut self, lexeme: L) -> Self {
+        self.continue_before.push(lexeme);
+        self
+    }
+}
+
+impl<L: Lexeme> TokenFilter<L> for NewlinePolicy<L> {
+    fn filter(&mut self, lexeme: SpannedLexeme<L>, _ahead: &[SpannedLexeme<L>], _text: &str, out: &mut Vec<SpannedLexeme<L>>) {
+        if lexeme.data == self.newline {
+            let starts_run = self.pending.is_none()
+                && self.depth == 0
+                && out.last().is_some_and(|p| p.data != self.newline && !self.continue_after.contains(&p.data));
+            if starts_run {
+                self.pending = Some(lexeme);
+            }
+            return;
+        }
+        if let Some(newline) = self.pending.take() {
+            if !self.continue_before.contains(&lexeme.data) {
+                out.push(newline);
+            }
+        }
+        if self.brackets.iter().any(|(open, _)| lexeme.data == *open) {
+            self.depth += 1;
+        } else if self.brackets.iter().any(|(_, close)| lexeme.data == *close) {
+            self.depth = self.depth.saturating_sub(1);
+        }
+        out.push(lexeme);
+    }
+
+    fn finish(&mut self, _text: &str, out: &mut Vec<SpannedLexeme<L>>) {
+        if let Some(newline) = self.pending.take() {
+            out.push(newline);
+        }
+        self.depth = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use parce::lexer::filter::TokenFilter;
+    use super::NewlinePolicy;
+
+    #[lexer(NewlineLexer)]
+    enum NewlineLexemes {
+        A = 'a',
+        Plus = '+',
+        Dot = '.',
+        LParen = '(',
+        RParen = ')',
+        Newline = '\n',
+        #[skip] Space = ' '
+    }
+
+    fn filtered(policy: &mut NewlinePolicy<NewlineLexemes>, input: &str) -> String {
+        let mut lexemes = NewlineLexer::default().lex(input).unwrap();
+        policy.apply(&mut lexemes, input);
+        lexemes.iter().map(|l| l.text(input)).collect::<Vec<_>>().join("")
+    }
+
+    #[test]
+    fn newlines() {
+        let mut plain = NewlinePolicy::new(NewlineLexemes::Newline);
+        assert_eq!(filtered(&mut plain, "\n\na\n\n\na\n"), "a\na\n");
+        assert_eq!(filtered(&mut plain, "a + (\na)\n"), "a+(\na)\n");
+
+        let mut policy = plain
+            .brackets(NewlineLexemes::LParen, NewlineLexemes::RParen)
+            .continue_after(NewlineLexemes::Plus)
+            .continue_before(NewlineLexemes::Dot);
+        assert_eq!(filtered(&mut policy, "a + (\na)\n"), "a+(a)\n");
+        assert_eq!(filtered(&mut policy, "a +\n\na\n"), "a+a\n");
+        assert_eq!(filtered(&mut policy, "a\n\n  .a\na"), "a.a\na");
+        assert_eq!(filtered(&mut policy, "((\n)\n)\na"), "(())\na");
+        assert_eq!(filtered(&mut policy, ")\na"), ")\na");
+    }
+
+    #[test]
+    fn long_newline_runs() {
+        let mut policy = NewlinePolicy::new(NewlineLexemes::Newline)
+            .continue_before(NewlineLexemes::Dot);
+        let run = "\n".repeat(100_000);
+        assert_eq!(filtered(&mut policy, &format!("a{run}a{run}")), "a\na\n");
+        assert_eq!(filtered(&mut policy, &format!("a{run}.a")), "a.a");
+    }
+}
@@ -0,0 +1,166 @@
+//! Contains [security_lints], which looks for text that reads differently than it parses, like
+//! the "Trojan Source" attacks.
+
+use super::{Lexeme, SpannedLexeme};
+
+/// Something in the input that could make it look different to a reviewer than it does to the
+/// parser.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SecurityLint {
+    /// The byte index in the input where the problem starts.
+    pub start: usize,
+    /// The length of the problem in bytes.
+    pub len: usize,
+    /// What the problem is.
+    pub kind: SecurityLintKind
+}
+
+/// The kinds of [SecurityLint].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SecurityLintKind {
+    /// A Unicode bidirectional control character, which can reorder how the text around it is
+    /// displayed. These are found anywhere in the input, including in skipped lexemes like comments.
+    Bidi(char),
+    /// A character with no width, inside an identifier.
+    Invisible(char),
+    /// An identifier that looks the same as a different, earlier identifier, because they use
+    /// look-alike characters from different scripts. Holds the start of the earlier identifier.
+    Confusable {
+        original: usize
+    }
+}
+
+impl std::fmt::Display for SecurityLint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            SecurityLintKind::Bidi(c) => write!(f, "bidirectional control character U+{:04X} at {}", c as u32, self.start),
+            SecurityLintKind::Invisible(c) => write!(f, "invisible character U+{:04X} in identifier at {}", c as u32, self.start),
+            SecurityLintKind::Confusable { original } => write!(f, "identifier at {} looks like the identifier at {}", self.start, original)
+        }
+    }
+}
+
+const BIDI: &[char] = &[
+    '\u{061C}', '\u{200E}', '\u{200F}',
+    '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}',
+    '\u{2066}', '\u{2067}', '\u{2068}', '\u{2069}'
+];
+
+const INVISIBLE: &[char] = &['\u{00AD}', '\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// Greek and Cyrillic letters that are drawn the same as a Latin letter in most fonts. This is a
+/// small subset of the Unicode confusables table, covering the usual suspects.
+const CONFUSABLES: &[(char, char)] = &[
+    ('а', 'a'), ('е', 'e'), ('о', 'o'), ('р', 'p'), ('с', 'c'), ('у', 'y'), ('х', 'x'),
+    ('і', 'i'), ('ј', 'j'), ('ѕ', 's'), ('ԁ', 'd'), ('һ', 'h'), ('ԛ', 'q'), ('ԝ', 'w'),
+    ('А', 'A'), ('В', 'B'), ('Е', 'E'), ('К', 'K'), ('М', 'M'), ('Н', 'H'), ('О', 'O'),
+    ('Р', 'P'), ('С', 'C'), ('Т', 'T'), ('Х', 'X'), ('І', 'I'), ('Ј', 'J'), ('Ѕ', 'S'),
+    ('ο', 'o'), ('ν', 'v'), ('α', 'a'), ('ι', 'i'), ('κ', 'k'),
+    ('Α', 'A'), ('Β', 'B'), ('Ε', 'E'), ('Ζ', 'Z'), ('Η', 'H'), ('Ι', 'I'), ('Κ', 'K'),
+    ('Μ', 'M'), ('Ν', 'N'), ('Ο', 'O'), ('Ρ', 'P'), ('Τ', 'T'), ('Υ', 'Y'), ('Χ', 'X')
+];
+
+/// What an identifier looks like, with look-alike characters replaced by the Latin letter they
+/// resemble and invisible characters removed.
+fn skeleton(identifier: &str) -> String {
+    identifier.chars()
+        .filter(|c| !INVISIBLE.contains(c))
+        .map(|c| CONFUSABLES.iter().find(|(from, _)| *from == c).map(|(_, to)| *to).unwrap_or(c))
+        .collect()
+}
+
+/// Checks lexed input for bidirectional control characters anywhere in `text`, and for invisible
+/// characters and confusable look-alikes in the lexemes whose kind is one of `identifiers`.
+/// Returns the problems in the order they appear.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::security::{security_lints, SecurityLintKind};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Ident = "[^ ]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// let text = "scope sсope"; // the second c is Cyrillic
+/// let lexemes = MyLexer::default().lex(text).unwrap();
+/// let lints = security_lints(&lexemes, text, &[MyLexemes::Ident]);
+/// assert_eq!(lints.len(), 1);
+/// assert_eq!(lints[0].start, 6);
+/// assert_eq!(lints[0].kind, SecurityLintKind::Confusable { original: 0 });
+/// ```
+pub fn security_lints<L: Lexeme>(lexemes: &[SpannedLexeme<L>], text: &str, identifiers: &[L]) -> Vec<SecurityLint> {
+    let mut lints: Vec<SecurityLint> = text.char_indices()
+        .filter(|(_, c)| BIDI.contains(c))
+        .map(|(start, c)| SecurityLint { start, len: c.len_utf8(), kind: SecurityLintKind::Bidi(c) })
+        .collect();
+
+    let mut seen: Vec<(&str, String, usize)> = vec![];
+    for lexeme in lexemes.iter().filter(|l| identifiers.contains(&l.data)) {
+        let identifier = match text.get(lexeme.range()) {
+            Some(i) => i,
+            None => continue
+        };
+        for (i, c) in identifier.char_indices().filter(|(_, c)| INVISIBLE.contains(c)) {
+            lints.push(SecurityLint { start: lexeme.start + i, len: c.len_utf8(), kind: SecurityLintKind::Invisible(c) });
+        }
+        let skeleton = skeleton(identifier);
+        match seen.iter().find(|(_, s, _)| *s == skeleton) {
+            Some((original, _, original_start)) if *original != identifier => {
+                lints.push(SecurityLint {
+                    start: lexeme.start,
+                    len: lexeme.len,
+                    kind: SecurityLintKind::Confusable { original: *original_start }
+                });
+            }
+            Some(_) => {}
+            None => seen.push((identifier, skeleton, lexeme.start))
+        }
+    }
+    lints.sort_by_key(|l| l.start);
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(SecurityLexer)]
+    enum SecurityLexemes {
+        Ident = "[^ \"]+",
+        Str = "'\"' [^\"]* '\"'",
+        #[skip] Space = ' '
+    }
+
+    fn lints(text: &str) -> Vec<SecurityLint> {
+        let lexemes = SecurityLexer::default().lex(text).unwrap();
+        security_lints(&lexemes, text, &[SecurityLexemes::Ident])
+    }
+
+    #[test]
+    fn security() {
+        assert_eq!(lints("access_level \"user\" access_level"), vec![]);
+
+        let text = "is_admin \"\u{202E} \u{2066}// check\u{2069} \u{2066}\"";
+        let found: Vec<_> = lints(text).iter().map(|l| l.kind).collect();
+        assert_eq!(found, vec![
+            SecurityLintKind::Bidi('\u{202E}'),
+            SecurityLintKind::Bidi('\u{2066}'),
+            SecurityLintKind::Bidi('\u{2069}'),
+            SecurityLintKind::Bidi('\u{2066}')
+        ]);
+
+        let text = "value val\u{200B}ue";
+        assert_eq!(lints(text), vec![
+            SecurityLint { start: 6, len: 8, kind: SecurityLintKind::Confusable { original: 0 } },
+            SecurityLint { start: 9, len: 3, kind: SecurityLintKind::Invisible('\u{200B}') }
+        ]);
+
+        // Strings aren't identifiers, so look-alikes in them are fine.
+        assert_eq!(lints("\"сat\" cat"), vec![]);
+        assert_eq!(lints("Οk Ok ok").len(), 1);
+    }
+}
@@ -0,0 +1,220 @@
+//! Contains [TokenStats], which counts the lexemes of some inputs by kind, for checking a grammar
+//! against a corpus and finding the lexemes that are worth making fast.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::error::ParceError;
+use super::{Lexeme, Lexer, SpannedLexeme};
+use super::cursor::StrCursor;
+
+/// How often one kind of lexeme was found, in [TokenStats].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct KindStats<L: Lexeme> {
+    /// The kind of lexeme.
+    pub kind: L,
+    /// How many were found.
+    pub count: usize,
+    /// Their total length in bytes.
+    pub bytes: usize,
+    /// The length of the longest one in bytes.
+    pub longest: usize
+}
+
+impl<L: Lexeme> KindStats<L> {
+    /// The average length in bytes, or 0 if none were found.
+    pub fn average_len(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            count => self.bytes as f64 / count as f64
+        }
+    }
+}
+
+/// Counts of the lexemes in one or more inputs, by kind: how many there are, how much of the input
+/// they cover, and how long they are on average.
+///
+/// Skipped lexemes are counted too, since they are lexed like any other. Its
+/// [Display](fmt::Display) form is a table of the kinds, most common first, with a bar for each.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::lexer::stats::TokenStats;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Word = "[a-z]+",
+///     Number = "[0-9]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// let mut stats = TokenStats::default();
+/// for file in ["let x 1", "let yy 22"] {
+///     stats.add_input(MyLexer::default(), file).unwrap();
+/// }
+/// assert_eq!((stats.inputs(), stats.tokens(), stats.input_bytes()), (2, 10, 16));
+///
+/// let words = stats.get(MyLexemes::Word).unwrap();
+/// assert_eq!((words.count, words.bytes, words.longest), (4, 9, 3));
+/// assert_eq!(words.average_len(), 2.25);
+/// assert_eq!(stats.coverage(MyLexemes::Space), 0.25);
+///
+/// let histogram: Vec<_> = stats.histogram().iter().map(|k| k.kind).collect();
+/// assert_eq!(histogram, vec![MyLexemes::Word, MyLexemes::Space, MyLexemes::Number]);
+/// println!("{}", stats);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TokenStats<L: Lexeme> {
+    kinds: BTreeMap<u16, KindStats<L>>,
+    inputs: usize,
+    input_bytes: usize
+}
+
+impl<L: Lexeme> Default for TokenStats<L> {
+    fn default() -> Self {
+        TokenStats {
+            kinds: BTreeMap::new(),
+            inputs: 0,
+            input_bytes: 0
+        }
+    }
+}
+
+impl<L: Lexeme> TokenStats<L> {
+    /// The counts of `lexemes`, lexed from an input `input_len` bytes long.
+    pub fn of(lexemes: &[SpannedLexeme<L>], input_len: usize) -> Self {
+        let mut stats = TokenStats::default();
+        stats.add_lexemes(lexemes, input_len);
+        stats
+    }
+
+    /// Adds the counts of `lexemes`, lexed from another input `input_len` bytes long.
+    pub fn add_lexemes(&mut self, lexemes: &[SpannedLexeme<L>], input_len: usize) {
+        for lexeme in lexemes {
+            let kind = self.kinds.entry(lexeme.kind_id()).or_insert(KindStats {
+                kind: lexeme.data,
+                count: 0,
+                bytes: 0,
+                longest: 0
+            });
+            kind.count += 1;
+            kind.bytes += lexeme.len;
+            kind.longest = kind.longest.max(lexeme.len);
+        }
+        self.inputs += 1;
+        self.input_bytes += input_len;
+    }
+
+    /// Lexes `input` with `lexer`, keeping the skipped lexemes, and adds their counts. Returns the
+    /// error if the input doesn't lex, without adding anything.
+    pub fn add_input<X: Lexer<Lexemes = L>>(&mut self, lexer: X, input: &str) -> Result<(), ParceError> {
+        let mut lexemes = vec![];
+        lexer.lex_all_cursor_into(StrCursor::new(input), &mut lexemes)?;
+        self.add_lexemes(&lexemes, input.len());
+        Ok(())
+    }
+
+    /// The counts for `kind`, if any were found.
+    pub fn get(&self, kind: L) -> Option<&KindStats<L>> {
+        self.kinds.get(&kind.kind_id())
+    }
+
+    /// The counts for every kind that was found, most common first. Kinds found equally often are
+    /// in the order they were declared.
+    pub fn histogram(&self) -> Vec<&KindStats<L>> {
+        let mut kinds: Vec<_> = self.kinds.values().collect();
+        kinds.sort_by_key(|k| std::cmp::Reverse(k.count));
+        kinds
+    }
+
+    /// How many inputs were added.
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// How many lexemes were found, of every kind.
+    pub fn tokens(&self) -> usize {
+        self.kinds.values().map(|k| k.count).sum()
+    }
+
+    /// The total length of the inputs in bytes.
+    pub fn input_bytes(&self) -> usize {
+        self.input_bytes
+    }
+
+    /// The fraction of the inputs' bytes, from 0 to 1, that are inside lexemes of `kind`.
+    pub fn coverage(&self, kind: L) -> f64 {
+        match (self.get(kind), self.input_bytes) {
+            (Some(kind), total) if total > 0 => kind.bytes as f64 / total as f64,
+            _ => 0.0
+        }
+    }
+
+    /// The average length in bytes of every lexeme, or 0 if there are none.
+    pub fn average_len(&self) -> f64 {
+        match self.tokens() {
+            0 => 0.0,
+            tokens => self.kinds.values().map(|k| k.bytes).sum::<usize>() as f64 / tokens as f64
+        }
+    }
+}
+
+impl<L: Lexeme> fmt::Display for TokenStats<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const BAR: usize = 40;
+        let histogram = self.histogram();
+        let names: Vec<String> = histogram.iter().map(|k| format!("{:?}", k.kind)).collect();
+        let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0).max(4);
+        let most = histogram.first().map_or(0, |k| k.count);
+        writeln!(f, "{:width$}  {:>8}  {:>8}  {:>7}  {:>7}", "kind", "count", "bytes", "cover", "avg len")?;
+        for (kind, name) in histogram.iter().zip(&names) {
+            let bar = (kind.count * BAR).div_ceil(most);
+            writeln!(
+                f, "{:width$}  {:>8}  {:>8}  {:>6.1}%  {:>7.2}  {}",
+                name, kind.count, kind.bytes, self.coverage(kind.kind) * 100.0, kind.average_len(), "#".repeat(bar)
+            )?;
+        }
+        write!(
+            f, "{} tokens in {} bytes of {} inputs, {:.2} bytes per token",
+            self.tokens(), self.input_bytes, self.inputs, self.average_len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(StatsLexer)]
+    enum StatsLexemes {
+        Ident = "[a-z]+",
+        Semicolon = ';',
+        #[skip] Space = ' '
+    }
+
+    #[test]
+    fn counts() {
+        let mut stats = TokenStats::default();
+        assert_eq!((stats.tokens(), stats.average_len(), stats.coverage(StatsLexemes::Ident)), (0, 0.0, 0.0));
+        stats.add_input(StatsLexer::default(), "ab;c ; ").unwrap();
+        assert!(stats.add_input(StatsLexer::default(), "a!").is_err());
+        assert_eq!((stats.inputs(), stats.input_bytes()), (1, 7));
+        assert_eq!(stats.get(StatsLexemes::Semicolon).map(|k| k.count), Some(2));
+        assert_eq!(stats.get(StatsLexemes::Ident).map(|k| (k.bytes, k.longest)), Some((3, 2)));
+
+        // Lexemes from lex don't have the skipped ones.
+        let lexemes = StatsLexer::default().lex("x ;").unwrap();
+        stats.add_lexemes(&lexemes, 3);
+        assert_eq!(TokenStats::of(&lexemes, 3).get(StatsLexemes::Space), None);
+        assert_eq!(stats.get(StatsLexemes::Space).map(|k| k.count), Some(2));
+        assert_eq!(stats.tokens(), 8);
+
+        let table = stats.to_string();
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with("Ident") && lines[1].ends_with(&"#".repeat(40)), "{}", table);
+        assert!(lines[2].starts_with("Semicolon"), "{}", table);
+        assert_eq!(lines[4], "8 tokens in 10 bytes of 2 inputs, 1.12 bytes per token");
+    }
+}
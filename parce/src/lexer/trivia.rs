@@ -0,0 +1,383 @@
+//! Contains [TriviaIndex], which keeps the skipped lexemes of an input where later stages can find
+//! them, and [Layout], which describes the whitespace between two lexemes for formatters.
+
+use std::ops::Range;
+use crate::span::Span;
+use super::{Lexeme, SpannedLexeme};
+
+/// The skipped lexemes (trivia, like whitespace and comments) of one input, indexed by the lexeme
+/// they come before.
+///
+/// Skipped lexemes never reach the parser. They aren't in the output of [lex](super::Lexer::lex),
+/// and no rule can match them, but their text is still inside the span of any rule that has
+/// lexemes on both sides of them: a [RuleSpan](crate::parser::RuleSpan) runs from the start of its
+/// first lexeme to the end of its last. Trivia before a rule's first lexeme or after its last
+/// isn't in its span. Tools that need the trivia back, like formatters or doc extractors, can keep
+/// it in an index, with [lex_with_trivia](super::Lexer::lex_with_trivia) or
+/// [ParseSession::with_trivia](crate::parser::ParseSession::with_trivia).
+///
+/// Lexeme `n` of the kept lexemes has [leading](Self::leading) trivia `n`. The trivia after the
+/// last kept lexeme is [trailing](Self::trailing), which is also `leading(len)`.
+///
+/// ```
+/// use parce::prelude::*;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Word = "[a-z]+",
+///     #[skip] Comment = "'#' [^\n]*",
+///     #[skip] Space = "[ \n]"
+/// }
+///
+/// let text = "# greeting\nhello world # done";
+/// let (lexemes, trivia) = MyLexer::default().lex_with_trivia(text).unwrap();
+/// assert_eq!(lexemes.len(), 2);
+///
+/// let comments = |trivia: &[parce::lexer::SpannedLexeme<MyLexemes>]| trivia.iter()
+///     .filter(|l| l.is(MyLexemes::Comment))
+///     .map(|l| l.text(text))
+///     .collect::<Vec<_>>();
+/// assert_eq!(comments(trivia.leading(0)), vec!["# greeting"]);
+/// assert_eq!(trivia.leading(1).len(), 1);
+/// assert_eq!(comments(trivia.trailing()), vec!["# done"]);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TriviaIndex<L: Lexeme> {
+    trivia: Vec<SpannedLexeme<L>>,
+    /// `ends[n]` is how many trivia lexemes come before kept lexeme `n`. The last entry is the
+    /// total, for the trailing trivia.
+    ends: Vec<usize>,
+    /// Where each kept lexeme starts in the input, for [layout](Self::layout).
+    starts: Vec<usize>
+}
+
+impl<L: Lexeme> Default for TriviaIndex<L> {
+    fn default() -> Self {
+        TriviaIndex {
+            trivia: vec![],
+            ends: vec![0],
+            starts: vec![]
+        }
+    }
+}
+
+impl<L: Lexeme> TriviaIndex<L> {
+    /// Moves the lexemes that `is_trivia` returns true for out of `lexemes` and into this index,
+    /// replacing anything it held before.
+    pub fn take_from(&mut self, lexemes: &mut Vec<SpannedLexeme<L>>, is_trivia: impl Fn(L) -> bool) {
+        self.trivia.clear();
+        self.ends.clear();
+        self.starts.clear();
+        lexemes.retain(|lexeme| {
+            if is_trivia(lexeme.data) {
+                self.trivia.push(*lexeme);
+                false
+            } else {
+                self.ends.push(self.trivia.len());
+                self.starts.push(lexeme.start);
+                true
+            }
+        });
+        self.ends.push(self.trivia.len());
+    }
+
+    /// The trivia between kept lexeme `n - 1` and kept lexeme `n`, or before lexeme 0. `leading(len)`
+    /// is the [trailing](Self::trailing) trivia, and anything after that is empty.
+    pub fn leading(&self, n: usize) -> &[SpannedLexeme<L>] {
+        match self.ends.get(n) {
+            Some(&end) => &self.trivia[if n == 0 { 0 } else { self.ends[n - 1] }..end],
+            None => &[]
+        }
+    }
+
+    /// The trivia after the last kept lexeme, or all of it if no lexemes were kept.
+    pub fn trailing(&self) -> &[SpannedLexeme<L>] {
+        self.leading(self.len())
+    }
+
+    /// The trivia lexemes that start inside `range` of the input. Unlike [leading](Self::leading),
+    /// this doesn't depend on the kept lexemes, so it still works after a
+    /// [TokenFilter](super::filter::TokenFilter) added or removed some.
+    pub fn in_range(&self, range: Range<usize>) -> &[SpannedLexeme<L>] {
+        let first = self.trivia.partition_point(|l| l.start < range.start);
+        let last = self.trivia.partition_point(|l| l.start < range.end);
+        &self.trivia[first..last.max(first)]
+    }
+
+    /// Every trivia lexeme, in input order.
+    pub fn all(&self) -> &[SpannedLexeme<L>] {
+        &self.trivia
+    }
+
+    /// How many lexemes were kept, which is the index of the [trailing](Self::trailing) trivia.
+    pub fn len(&self) -> usize {
+        self.ends.len() - 1
+    }
+
+    /// Whether no lexemes were kept.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The [Layout] of the [leading](Self::leading) trivia of kept lexeme `n`, whose text is in
+    /// `text`. Tabs move to the next multiple of `tab_width` columns. For the
+    /// [trailing](Self::trailing) trivia, the column is the one at the end of `text`.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    /// use parce::lexer::trivia::{LayoutPiece, LineEnding, Whitespace};
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Word = "[a-z]+",
+    ///     #[skip] Comment = "'#' [^\r\n]*",
+    ///     #[skip] Space = "[ \t\r\n]+"
+    /// }
+    ///
+    /// let text = "if\r\n\r\n\t  # why\n\t  then";
+    /// let (_, trivia) = MyLexer::default().lex_with_trivia(text).unwrap();
+    /// let layout = trivia.layout(1, text, 4);
+    /// assert_eq!(layout.pieces, vec![
+    ///     LayoutPiece::Whitespace(Whitespace::Newlines(2, LineEnding::CrLf)),
+    ///     LayoutPiece::Whitespace(Whitespace::Tabs(1)),
+    ///     LayoutPiece::Whitespace(Whitespace::Spaces(2)),
+    ///     LayoutPiece::Other((9..14).into()),
+    ///     LayoutPiece::Whitespace(Whitespace::Newlines(1, LineEnding::Lf)),
+    ///     LayoutPiece::Whitespace(Whitespace::Tabs(1)),
+    ///     LayoutPiece::Whitespace(Whitespace::Spaces(2))
+    /// ]);
+    /// assert_eq!((layout.newlines, layout.blank_lines(), layout.column), (3, 1, 6));
+    /// assert_eq!(layout.indent, vec![Whitespace::Tabs(1), Whitespace::Spaces(2)]);
+    /// assert_eq!(layout.text(text), &text[2..18]);
+    /// ```
+    pub fn layout(&self, n: usize, text: &str, tab_width: usize) -> Layout {
+        let mut pieces = vec![];
+        for lexeme in self.leading(n) {
+            let piece = lexeme.text(text);
+            match piece.chars().all(char::is_whitespace) {
+                true => pieces.extend(whitespace_runs(piece).into_iter().map(LayoutPiece::Whitespace)),
+                false => pieces.push(LayoutPiece::Other(lexeme.span()))
+            }
+        }
+        let newlines = pieces.iter().map(|piece| match piece {
+            LayoutPiece::Whitespace(Whitespace::Newlines(count, _)) => *count,
+            _ => 0
+        }).sum();
+        let last_line = pieces.iter().rposition(|piece| matches!(piece, LayoutPiece::Whitespace(Whitespace::Newlines(..))));
+        let indent = match last_line {
+            Some(line) => pieces[line + 1..].iter().map_while(|piece| match piece {
+                LayoutPiece::Whitespace(whitespace) => Some(*whitespace),
+                LayoutPiece::Other(_) => None
+            }).collect(),
+            None => vec![]
+        };
+        let end = self.starts.get(n).copied().unwrap_or(text.len());
+        Layout {
+            pieces,
+            newlines,
+            indent,
+            column: column(text, end, tab_width)
+        }
+    }
+}
+
+/// A run of one kind of whitespace.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Whitespace {
+    /// This many spaces.
+    Spaces(usize),
+    /// This many tabs.
+    Tabs(usize),
+    /// This many line breaks, all written the same way.
+    Newlines(usize, LineEnding),
+    /// This many of some other whitespace character, like a non-breaking space.
+    Other(char, usize)
+}
+
+impl Whitespace {
+    /// The text of the run.
+    pub fn text(&self) -> String {
+        match *self {
+            Whitespace::Spaces(count) => " ".repeat(count),
+            Whitespace::Tabs(count) => "\t".repeat(count),
+            Whitespace::Newlines(count, ending) => ending.as_str().repeat(count),
+            Whitespace::Other(c, count) => c.to_string().repeat(count)
+        }
+    }
+}
+
+/// How a line break is written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LineEnding {
+    /// `"\n"`
+    Lf,
+    /// `"\r\n"`
+    CrLf,
+    /// `"\r"` on its own.
+    Cr
+}
+
+impl LineEnding {
+    /// The text of the line break.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r"
+        }
+    }
+}
+
+/// One part of the trivia between two lexemes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LayoutPiece {
+    /// A run of whitespace.
+    Whitespace(Whitespace),
+    /// A trivia lexeme that isn't only whitespace, like a comment, which a formatter has to keep.
+    Other(Span)
+}
+
+/// The structure of the trivia before a lexeme, from [TriviaIndex::layout], so a formatter can
+/// reproduce it exactly, or keep only the parts it cares about (like blank lines) and redo the rest.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Layout {
+    /// Every part of the trivia, in order. Whitespace lexemes are split into runs of one kind.
+    pub pieces: Vec<LayoutPiece>,
+    /// How many line breaks there are, outside of [other](LayoutPiece::Other) trivia.
+    pub newlines: usize,
+    /// The whitespace after the last line break, up to the lexeme or to other trivia: the
+    /// lexeme's indentation. Empty if there is no line break.
+    pub indent: Vec<Whitespace>,
+    /// The column the lexeme starts at, counting from 0, with tabs expanded.
+    pub column: usize
+}
+
+impl Layout {
+    /// How many lines between the lexemes have nothing but whitespace on them. Lines with other
+    /// trivia, like comments, aren't blank.
+    pub fn blank_lines(&self) -> usize {
+        let (mut blank, mut line, mut content) = (0, false, false);
+        for piece in &self.pieces {
+            match piece {
+                LayoutPiece::Whitespace(Whitespace::Newlines(count, _)) => {
+                    blank += count - 1 + (line && !content) as usize;
+                    (line, content) = (true, false);
+                }
+                LayoutPiece::Other(_) => content = true,
+                LayoutPiece::Whitespace(_) => {}
+            }
+        }
+        blank
+    }
+
+    /// The trivia's text, put back together from the pieces. `text` is the input, for the text of
+    /// [other](LayoutPiece::Other) trivia.
+    pub fn text(&self, text: &str) -> String {
+        self.pieces.iter().map(|piece| match piece {
+            LayoutPiece::Whitespace(whitespace) => whitespace.text(),
+            LayoutPiece::Other(span) => text[span.start..span.end].to_string()
+        }).collect()
+    }
+}
+
+/// Splits `text` into runs of whitespace. Characters that aren't whitespace are left out.
+pub fn whitespace_runs(text: &str) -> Vec<Whitespace> {
+    let mut runs: Vec<Whitespace> = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let run = match c {
+            ' ' => Whitespace::Spaces(1),
+            '\t' => Whitespace::Tabs(1),
+            '\n' => Whitespace::Newlines(1, LineEnding::Lf),
+            '\r' if chars.next_if_eq(&'\n').is_some() => Whitespace::Newlines(1, LineEnding::CrLf),
+            '\r' => Whitespace::Newlines(1, LineEnding::Cr),
+            c if c.is_whitespace() => Whitespace::Other(c, 1),
+            _ => continue
+        };
+        match (runs.last_mut(), run) {
+            (Some(Whitespace::Spaces(count)), Whitespace::Spaces(_))
+            | (Some(Whitespace::Tabs(count)), Whitespace::Tabs(_)) => *count += 1,
+            (Some(Whitespace::Newlines(count, ending)), Whitespace::Newlines(_, next)) if *ending == next => *count += 1,
+            (Some(Whitespace::Other(c, count)), Whitespace::Other(next, _)) if *c == next => *count += 1,
+            _ => runs.push(run)
+        }
+    }
+    runs
+}
+
+/// The column of byte `offset` in `text`, counting characters from the start of its line (after
+/// the last `\n` or `\r`) from 0, with tabs moving to the next multiple of `tab_width`.
+pub fn column(text: &str, offset: usize, tab_width: usize) -> usize {
+    let line = &text[..offset];
+    let line = &line[line.rfind(['\n', '\r']).map_or(0, |i| i + 1)..];
+    line.chars().fold(0, |column, c| match c {
+        '\t' if tab_width > 0 => (column / tab_width + 1) * tab_width,
+        _ => column + 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(TriviaLexer)]
+    enum TriviaLexemes {
+        A = 'a',
+        B = 'b',
+        #[skip] Space = ' ',
+        #[skip] Dash = '-'
+    }
+
+    #[test]
+    fn index() {
+        let text = " a- -ab -";
+        let (lexemes, trivia) = TriviaLexer::default().lex_with_trivia(text).unwrap();
+        assert_eq!(lexemes, TriviaLexer::default().lex(text).unwrap());
+        assert_eq!((trivia.len(), trivia.all().len()), (3, 6));
+
+        let starts = |lexemes: &[SpannedLexeme<TriviaLexemes>]| lexemes.iter().map(|l| l.start).collect::<Vec<_>>();
+        assert_eq!(starts(trivia.leading(0)), vec![0]);
+        assert_eq!(starts(trivia.leading(1)), vec![2, 3, 4]);
+        assert!(trivia.leading(2).is_empty());
+        assert_eq!(starts(trivia.trailing()), vec![7, 8]);
+        assert_eq!(trivia.leading(3), trivia.trailing());
+        assert!(trivia.leading(4).is_empty());
+        assert_eq!(starts(trivia.in_range(3..8)), vec![3, 4, 7]);
+
+        let (lexemes, trivia) = TriviaLexer::default().lex_with_trivia("- -").unwrap();
+        assert!(lexemes.is_empty() && trivia.is_empty());
+        assert_eq!(trivia.trailing().len(), 3);
+        assert!(TriviaIndex::<TriviaLexemes>::default().trailing().is_empty());
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(whitespace_runs("  \t\t\n\n\r\n\r\u{a0}x "), vec![
+            Whitespace::Spaces(2), Whitespace::Tabs(2), Whitespace::Newlines(2, LineEnding::Lf),
+            Whitespace::Newlines(1, LineEnding::CrLf), Whitespace::Newlines(1, LineEnding::Cr),
+            Whitespace::Other('\u{a0}', 1), Whitespace::Spaces(1)
+        ]);
+        assert_eq!(column("ab\tc", 3, 4), 4);
+        assert_eq!(column("ab\tc", 4, 4), 5);
+        assert_eq!(column("x\n\t\t", 4, 8), 16);
+        assert_eq!(column("é\t", 3, 2), 2);
+
+        let text = "a -  b";
+        let (_, trivia) = TriviaLexer::default().lex_with_trivia(text).unwrap();
+        let layout = trivia.layout(1, text, 4);
+        assert_eq!(layout.pieces, vec![
+            LayoutPiece::Whitespace(Whitespace::Spaces(1)),
+            LayoutPiece::Other((2..3).into()),
+            LayoutPiece::Whitespace(Whitespace::Spaces(1)),
+            LayoutPiece::Whitespace(Whitespace::Spaces(1))
+        ]);
+        assert_eq!((layout.newlines, layout.blank_lines(), layout.column, layout.indent.len()), (0, 0, 5, 0));
+        assert_eq!(layout.text(text), " -  ");
+        assert_eq!(trivia.layout(0, text, 4), Layout { pieces: vec![], newlines: 0, indent: vec![], column: 0 });
+        assert_eq!(trivia.layout(2, text, 4).column, 6);
+        let (_, trivia) = TriviaLexer::default().lex_with_trivia("ab").unwrap();
+        assert_eq!(trivia.layout(1, "ab", 4), Layout { pieces: vec![], newlines: 0, indent: vec![], column: 1 });
+    }
+}
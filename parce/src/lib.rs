@@ -33,7 +33,8 @@
 //!     Bool = " 'true' | 'false' ", // match string literals, use | for multiple possible patterns
 //!     Digit = "[0-9]", // use Regex-like character classes
 //!     And = '&', // can omit double quotes if pattern is a single character
-//!     #[skip] Whitespace = "[ \n\r\t]" // skippable lexemes
+//!     #[skip] Whitespace = "[ \n\r\t]", // skippable lexemes
+//!     Arrow = p!("->" | "=>") // or write the pattern as Rust tokens
 //! }
 //!
 //! // The macro generates an enum "MyLexer" that implements the Lexer trait.
@@ -65,6 +66,51 @@
 //!
 //! ## Parser Features
 //!
+//! # Cargo Features
+//!
+//! - `parser` (on by default): the parser engine and the [parser](macro@parce_macros::parser)
+//!   macro. For jobs that only need a lexer, like syntax highlighting, turn off default features
+//!   to skip the parser's code.
+//! - `inventory` (off by default): rules register themselves with their lexer through the
+//!   `inventory` crate, so [registered_rules](crate::lexer::Lexer::registered_rules) can list them
+//!   and [grammar_report](crate::report::grammar_report) can find rules that nothing uses. This
+//!   runs code before `main`, which some platforms (like wasm) don't support, and costs link time.
+//!   Parsing never needs it: each rule's generated tables point to the tables of the rules it uses.
+//!   Without it, pass the rules to [grammar_report_with](crate::report::grammar_report_with).
+//! - `bench` (off by default): the [bench](crate::bench) module, for measuring the throughput of
+//!   a lexer or grammar on your own inputs.
+//! - `logos` (off by default): the [logos_lexer] macro, which wraps a token enum derived with the
+//!   `logos` crate as a parce lexer, so a grammar can be parsed from an existing logos tokenizer.
+//! - `tracing` (off by default): every lex and parse run by a
+//!   [ParseSession](crate::parser::ParseSession), including the ones behind `FromStr` and
+//!   [Parse](crate::parser::Parse), is a `DEBUG` span from the `tracing` crate, recording the
+//!   input size, lexeme count, errors, memory, and duration.
+//! - `safe` (off by default): builds parce with `#![forbid(unsafe_code)]`, and generates rules
+//!   without `unsafe` blocks, so crates that forbid `unsafe` can still use it. The parser's
+//!   automata are kept in `RefCell`s instead of behind raw pointers, which costs a borrow check on
+//!   every access, and they are allocated with the `typed-arena` crate, which is only a dependency
+//!   with this feature. Reference fields in borrowed rules need the
+//!   [NodeArena](crate::parser::arena::NodeArena), which isn't available, so use `Box` instead.
+//!
+//! # Concurrency
+//!
+//! A generated grammar can drive any number of parses at once, on any threads:
+//!
+//! - Lexers are small `Copy` values holding only their starting mode. Every call to
+//!   [lex](crate::lexer::Lexer::lex) works on its own copy.
+//! - A rule's tables, its [RuleInfo](crate::report::RuleInfo), the compiled character classes, and
+//!   the `inventory` registry are immutable statics. The few that are built on first use are built
+//!   once, behind a lock, no matter how many threads get there at the same time.
+//! - Every parse allocates its own automata, and frees them when it ends.
+//!
+//! The mutable state is in values you own. A [ParseSession](crate::parser::ParseSession) and a
+//! [ParseSnapshot](crate::parser::ParseSnapshot) are `Send` but used through `&mut`, so share
+//! them between threads with a lock, or (better) keep one per thread. Two things are per thread:
+//! the interner that [Symbol](crate::convert::Symbol) fields go into, unless the session has its
+//! own, and the arena set by [parse_all_in](crate::parser::ParseSession::parse_all_in) while it runs.
+//! So symbols from parses on different threads can only be compared if they used one interner,
+//! passed between the threads' sessions.
+//!
 //! # Comparison to ANTLR
 //!
 //! Since Parce and ANTLR serve very similar purposes, here are the pros and cons of using Parce over ANTLR:
@@ -77,8 +123,9 @@
 //!
 //! **Cons:**
 //! - ANTLR's runtime performance is faster.
-//!     - I haven't actually benchmarked it, but Parce is extremely unlikely to be faster, given how
-//!       much smarter the ANTLR devs are than me ;)
+//!     - Parce is extremely unlikely to be faster, given how much smarter the ANTLR devs are than me ;)
+//!       `cargo bench` measures lexing and parsing throughput on JSON, a small C-like language, and
+//!       Brainfuck, so you can check any comparison yourself.
 //! - ANTLR's grammars are language-independent, as long as you don't embed code in your grammars.
 //! - ANTLR has more features.
 //!     - Mixed lexer/parse grammars.
@@ -95,8 +142,24 @@
 //!
 //! If you find a bug or want a new feature, please create an issue or pull request on [GitHub](https://github.com/JoelCourtney/parce)!
 
+#![cfg_attr(feature = "safe", forbid(unsafe_code))]
+
 pub mod internal_prelude;
 pub mod prelude;
 pub mod lexer;
+#[cfg(feature = "parser")]
 pub mod parser;
 pub mod error;
+#[cfg(feature = "parser")]
+pub mod report;
+pub mod scannerless;
+pub mod preprocess;
+pub mod convert;
+pub mod span;
+pub mod version;
+pub mod pattern;
+pub mod fingerprint;
+
+pub use allocator_api2;
+#[cfg(feature = "bench")]
+pub mod bench;
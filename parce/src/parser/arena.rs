@@ -0,0 +1,449 @@
+//! Contains [NodeArena], which lets borrowed rules hold their children by reference instead of in a
+//! [Box].
+//!
+//! The arena hands out memory it manages itself, which takes `unsafe` code, so it isn't available
+//! with the `safe` feature. Rules with reference fields can't be parsed then; give them `Box`
+//! fields instead.
+
+use crate::error::ParceError;
+#[cfg(not(feature = "safe"))]
+use crate::error::{ParceErrorInfo, ParsePhaseFailure};
+#[cfg(not(feature = "safe"))]
+use std::alloc::{handle_alloc_error, Layout};
+#[cfg(not(feature = "safe"))]
+use allocator_api2::alloc::{Allocator, Global};
+#[cfg(not(feature = "safe"))]
+use std::cell::{Cell, RefCell};
+#[cfg(not(feature = "safe"))]
+use std::fmt::{Debug, Formatter};
+#[cfg(not(feature = "safe"))]
+use std::ptr::NonNull;
+
+/// A bump allocator for the nodes of a syntax tree.
+///
+/// Fields of a borrowed rule can hold other rules as `&'a Rule<'a>` (also inside `Vec` and
+/// `Option`) when parsing with [ParseSession::parse_all_in](super::ParseSession::parse_all_in).
+/// The nodes are allocated next to each other in large chunks, and the whole tree is freed at once
+/// when the arena is dropped, instead of one `Box` at a time.
+///
+/// The rules parsed into the arena are dropped along with it, last allocated first, so memory they
+/// own, like the buffer of a `Vec<&'a Rule<'a>>` field, is freed then. Values moved in with
+/// [alloc](Self::alloc) are never dropped.
+///
+/// The chunks come from the global allocator, or from any other [Allocator] given to
+/// [new_in](Self::new_in), like a region that outlives many parses.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::arena::NodeArena;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Digit = "[0-9]",
+///     Plus = '+',
+///     LParen = '(',
+///     RParen = ')',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer, borrowed)]
+/// enum Expr<'a> {
+///     Sum(&'a Atom<'a>, Option<&'a Expr<'a>>) = "0 (Plus 1)?"
+/// }
+///
+/// #[parser(MyLexer, borrowed)]
+/// enum Atom<'a> {
+///     Digit(&'a str) = "0=Digit",
+///     Group(&'a Expr<'a>) = "LParen 0 RParen"
+/// }
+///
+/// let arena = NodeArena::new();
+/// let mut session = ParseSession::new(MyLexer::default());
+/// let tree = session.parse_all_in::<Expr>("1 + (2 + 3)", &arena).unwrap();
+///
+/// let Expr::Sum(Atom::Digit(one), Some(Expr::Sum(Atom::Group(inner), None))) = tree else { panic!() };
+/// assert_eq!(*one, "1");
+/// assert!(matches!(inner, Expr::Sum(Atom::Digit("2"), Some(_))));
+/// assert_eq!(arena.len(), 7);
+/// ```
+#[cfg(not(feature = "safe"))]
+pub struct NodeArena<A: Allocator = Global> {
+    chunks: RefCell<Vec<Chunk>>,
+    len: Cell<usize>,
+    /// The rules that need dropping, in the order they were allocated.
+    drops: RefCell<Vec<Deferred>>,
+    allocator: A
+}
+
+#[cfg(not(feature = "safe"))]
+struct Chunk {
+    start: NonNull<u8>,
+    layout: Layout,
+    used: usize
+}
+
+/// A value in the arena, and the function that drops it.
+#[cfg(not(feature = "safe"))]
+type Deferred = (NonNull<u8>, unsafe fn(*mut u8));
+
+/// Size of the first chunk. Each new chunk is twice as big as the last one.
+#[cfg(not(feature = "safe"))]
+const FIRST_CHUNK: usize = 4096;
+
+#[cfg(not(feature = "safe"))]
+impl NodeArena {
+    /// Creates an empty arena. Nothing is allocated until the first node.
+    pub fn new() -> Self {
+        NodeArena::new_in(Global)
+    }
+}
+
+#[cfg(not(feature = "safe"))]
+impl<A: Allocator> NodeArena<A> {
+    /// Creates an empty arena whose chunks come from `allocator`.
+    ///
+    /// ```
+    /// use parce::parser::arena::NodeArena;
+    /// use parce::allocator_api2::alloc::Global;
+    ///
+    /// let arena = NodeArena::new_in(&Global);
+    /// assert_eq!(*arena.alloc(5), 5);
+    /// ```
+    pub fn new_in(allocator: A) -> Self {
+        NodeArena {
+            chunks: RefCell::new(vec![]),
+            len: Cell::new(0),
+            drops: RefCell::new(vec![]),
+            allocator
+        }
+    }
+
+    /// Moves `value` into the arena. Unlike the rules the parser puts there, it is never dropped, so
+    /// anything it owns, like a `String`, is leaked.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        place(self, value)
+    }
+
+    /// The allocator the chunks come from.
+    pub fn allocator(&self) -> &A {
+        &self.allocator
+    }
+
+    /// The number of values allocated so far.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Whether nothing has been allocated.
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    fn bump(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            return NonNull::new(layout.align() as *mut u8).unwrap();
+        }
+        let mut chunks = self.chunks.borrow_mut();
+        if let Some(chunk) = chunks.last_mut() {
+            let address = chunk.start.as_ptr() as usize + chunk.used;
+            let offset = (address.next_multiple_of(layout.align())) - chunk.start.as_ptr() as usize;
+            if offset + layout.size() <= chunk.layout.size() {
+                chunk.used = offset + layout.size();
+                // SAFETY: offset is inside the chunk.
+                return unsafe { NonNull::new_unchecked(chunk.start.as_ptr().add(offset)) };
+            }
+        }
+
+        let previous = chunks.last().map_or(FIRST_CHUNK / 2, |c| c.layout.size());
+        let size = (previous * 2).max(layout.size());
+        let chunk_layout = Layout::from_size_align(size, layout.align().max(16)).unwrap();
+        let start = match self.allocator.allocate(chunk_layout) {
+            Ok(start) => start.cast(),
+            Err(_) => handle_alloc_error(chunk_layout)
+        };
+        chunks.push(Chunk {
+            start,
+            layout: chunk_layout,
+            used: layout.size()
+        });
+        start
+    }
+}
+
+#[cfg(not(feature = "safe"))]
+impl Default for NodeArena {
+    fn default() -> Self {
+        NodeArena::new()
+    }
+}
+
+#[cfg(not(feature = "safe"))]
+impl<A: Allocator> Debug for NodeArena<A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeArena")
+            .field("len", &self.len.get())
+            .field("chunks", &self.chunks.borrow().len())
+            .finish()
+    }
+}
+
+#[cfg(not(feature = "safe"))]
+impl<A: Allocator> Drop for NodeArena<A> {
+    fn drop(&mut self) {
+        for &(value, drop) in self.drops.get_mut().iter().rev() {
+            // SAFETY: the value was written by place_rule and hasn't been dropped. Parents are
+            // allocated after their children, so they're dropped first, and rules have no Drop
+            // impls that could read the nodes or text they borrow.
+            unsafe { drop(value.as_ptr()) }
+        }
+        for chunk in self.chunks.get_mut() {
+            // SAFETY: the chunk was allocated by this allocator with this layout in bump.
+            unsafe { self.allocator.deallocate(chunk.start, chunk.layout) }
+        }
+    }
+}
+
+/// A [NodeArena] with any allocator, so the current thread's arena can be kept without naming it.
+#[cfg(not(feature = "safe"))]
+pub(crate) trait Place {
+    /// Counts one more value, and returns memory for it that fits `layout`.
+    fn place(&self, layout: Layout) -> NonNull<u8>;
+
+    /// Calls `drop` on `value` when the arena is dropped.
+    fn drop_later(&self, value: NonNull<u8>, drop: unsafe fn(*mut u8));
+}
+
+#[cfg(not(feature = "safe"))]
+impl<A: Allocator> Place for NodeArena<A> {
+    fn place(&self, layout: Layout) -> NonNull<u8> {
+        self.len.set(self.len.get() + 1);
+        self.bump(layout)
+    }
+
+    fn drop_later(&self, value: NonNull<u8>, drop: unsafe fn(*mut u8)) {
+        self.drops.borrow_mut().push((value, drop));
+    }
+}
+
+/// Moves `value` into `arena`.
+#[cfg(not(feature = "safe"))]
+#[allow(clippy::mut_from_ref)]
+fn place<T>(arena: &dyn Place, value: T) -> &mut T {
+    let ptr = arena.place(Layout::new::<T>()).as_ptr() as *mut T;
+    // SAFETY: place returned memory that fits `T`, which is owned by the arena until it's dropped
+    // and never handed out twice.
+    unsafe {
+        ptr.write(value);
+        &mut *ptr
+    }
+}
+
+/// Moves the rule `value` into `arena`, to be dropped with it.
+#[cfg(not(feature = "safe"))]
+fn place_rule<T>(arena: &dyn Place, value: T) -> &mut T {
+    /// Drops the `T` at `value`.
+    unsafe fn drop_value<T>(value: *mut u8) {
+        // SAFETY: the arena only calls this once, on the value it was registered with.
+        std::ptr::drop_in_place(value as *mut T)
+    }
+
+    let value = place(arena, value);
+    if std::mem::needs_drop::<T>() {
+        arena.drop_later(NonNull::from(&mut *value).cast(), drop_value::<T>);
+    }
+    value
+}
+
+#[cfg(not(feature = "safe"))]
+thread_local! {
+    /// The arena of the parse running on this thread, if it was started with `parse_all_in`.
+    static CURRENT: Cell<Option<NonNull<dyn Place>>> = const { Cell::new(None) };
+}
+
+/// Makes `arena` the current thread's arena until the guard is dropped. Parses without an arena
+/// clear it, so a nested parse can't put nodes in an arena that doesn't live long enough.
+#[cfg(not(feature = "safe"))]
+pub(crate) fn enter(arena: Option<&dyn Place>) -> ArenaGuard {
+    // SAFETY: only the lifetime is changed. The arena is only used through CURRENT by the parse
+    // that entered it, which borrows it for longer than the guard lives.
+    let arena = arena.map(|a| unsafe { std::mem::transmute::<NonNull<dyn Place + '_>, NonNull<dyn Place>>(NonNull::from(a)) });
+    ArenaGuard(CURRENT.with(|current| current.replace(arena)))
+}
+
+/// Restores the previous arena when dropped.
+#[cfg(not(feature = "safe"))]
+pub(crate) struct ArenaGuard(Option<NonNull<dyn Place>>);
+
+#[cfg(not(feature = "safe"))]
+impl Drop for ArenaGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.0));
+    }
+}
+
+/// Stands in for the arena with the `safe` feature. Nothing implements it, so no parse has one.
+#[cfg(feature = "safe")]
+pub(crate) trait Place {}
+
+#[cfg(feature = "safe")]
+pub(crate) fn enter(_arena: Option<&dyn Place>) -> ArenaGuard {
+    ArenaGuard
+}
+
+#[cfg(feature = "safe")]
+pub(crate) struct ArenaGuard;
+
+/// Converts an assembled rule into the type of the field it's stored in: the rule itself, a `Box`
+/// of it, or a reference into the current [NodeArena]. With the `safe` feature, references
+/// aren't supported and the conversion is safe.
+///
+/// When there is no arena, because the parse didn't come from
+/// [parse_all_in](super::ParseSession::parse_all_in), references are a
+/// [NoArena](crate::error::ParsePhaseFailure::NoArena) error in `text`.
+#[doc(hidden)]
+pub trait IntoField<F> {
+    /// # Safety
+    ///
+    /// Only the generated assemblers call this. For references, the current arena must live at
+    /// least as long as `F`'s lifetime, which [parse_all_in](super::ParseSession::parse_all_in)
+    /// guarantees by tying the arena to the input text.
+    #[cfg(not(feature = "safe"))]
+    unsafe fn into_field(self, text: &str) -> Result<F, ParceError>;
+    #[cfg(feature = "safe")]
+    fn into_field(self, text: &str) -> Result<F, ParceError>;
+}
+
+impl<T> IntoField<T> for T {
+    #[cfg(not(feature = "safe"))]
+    unsafe fn into_field(self, _text: &str) -> Result<T, ParceError> {
+        Ok(self)
+    }
+    #[cfg(feature = "safe")]
+    fn into_field(self, _text: &str) -> Result<T, ParceError> {
+        Ok(self)
+    }
+}
+
+impl<T> IntoField<Box<T>> for T {
+    #[cfg(not(feature = "safe"))]
+    unsafe fn into_field(self, _text: &str) -> Result<Box<T>, ParceError> {
+        Ok(Box::new(self))
+    }
+    #[cfg(feature = "safe")]
+    fn into_field(self, _text: &str) -> Result<Box<T>, ParceError> {
+        Ok(Box::new(self))
+    }
+}
+
+#[cfg(not(feature = "safe"))]
+impl<'a, T> IntoField<&'a T> for T {
+    unsafe fn into_field(self, text: &str) -> Result<&'a T, ParceError> {
+        match CURRENT.with(Cell::get) {
+            Some(arena) => Ok(place_rule(arena.as_ref(), self)),
+            None => Err(ParceError {
+                input: text.to_string(),
+                start: 0,
+                info: ParceErrorInfo::parse(ParsePhaseFailure::NoArena)
+            })
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "safe")))]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(ArenaLexer)]
+    enum ArenaLexemes {
+        Word = "[a-z]+",
+        LBracket = '[',
+        RBracket = ']',
+        #[skip] Space = ' '
+    }
+
+    #[parser(ArenaLexer, borrowed)]
+    enum List<'a> {
+        List(Vec<&'a Item<'a>>) = "LBracket 0* RBracket"
+    }
+
+    #[parser(ArenaLexer, borrowed)]
+    enum Item<'a> {
+        Word(&'a str) = "0=Word",
+        List(&'a List<'a>) = "0"
+    }
+
+    #[test]
+    fn alloc() {
+        let arena = NodeArena::new();
+        let a = arena.alloc(1u8);
+        let b = arena.alloc(2u64);
+        let big = arena.alloc([7u8; 10000]);
+        let c = arena.alloc(3u16);
+        let unit = arena.alloc(());
+        assert_eq!((*a, *b, big[9999], *c, *unit), (1, 2, 7, 3, ()));
+        assert_eq!(b as *const u64 as usize % std::mem::align_of::<u64>(), 0);
+        assert_eq!(arena.len(), 5);
+        assert_eq!(arena.chunks.borrow().len(), 3);
+
+        *a = 5;
+        assert_eq!(*a, 5);
+    }
+
+    #[test]
+    fn references() {
+        let arena = NodeArena::new();
+        let mut session = ParseSession::new(ArenaLexer::default());
+        let list = session.parse_all_in::<List>("[a [b c] []]", &arena).unwrap();
+        let List::List(items) = list;
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[0], Item::Word("a")));
+        assert!(matches!(items[1], Item::List(List::List(inner)) if inner.len() == 2));
+        assert_eq!(arena.len(), 7);
+
+        let missing = ParseSession::new(ArenaLexer::default()).parse_all::<List>("[a]").unwrap_err();
+        assert_eq!(missing.info, ParceErrorInfo::parse(ParsePhaseFailure::NoArena));
+    }
+
+    static DROPPED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq)]
+    struct Counted;
+
+    impl std::str::FromStr for Counted {
+        type Err = ();
+
+        fn from_str(_: &str) -> Result<Self, ()> {
+            Ok(Counted)
+        }
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    #[parser(ArenaLexer, borrowed)]
+    enum Owning<'a> {
+        List(Vec<&'a Owned>) = "LBracket 0* RBracket"
+    }
+
+    #[parser(ArenaLexer)]
+    enum Owned {
+        Word(Counted) = "0=Word"
+    }
+
+    #[test]
+    fn drops() {
+        let arena = NodeArena::new();
+        let mut session = ParseSession::new(ArenaLexer::default());
+        let Owning::List(words) = session.parse_all_in::<Owning>("[a b c]", &arena).unwrap();
+        assert_eq!(words.len(), 3);
+        assert_eq!(DROPPED.load(std::sync::atomic::Ordering::Relaxed), 0);
+        drop(arena);
+        assert_eq!(DROPPED.load(std::sync::atomic::Ordering::Relaxed), 3);
+    }
+}
@@ -1,16 +1,45 @@
 //! Contains the automata used by the parser algorithm, and their basic operations.
 
-use typed_arena::Arena;
-use core::any::TypeId as Rule;
-use shrinkwraprs::Shrinkwrap;
+use super::{RuleId, RuleTables};
+use crate::lexer::Lexeme;
+use allocator_api2::alloc::Allocator;
+use std::marker::PhantomData;
 use tinyvec::{TinyVec, tiny_vec, ArrayVec};
-use std::ptr::null_mut;
+#[cfg(not(feature = "safe"))]
+use std::alloc::{handle_alloc_error, Layout};
+#[cfg(not(feature = "safe"))]
+use std::cell::{Cell, RefCell};
+#[cfg(not(feature = "safe"))]
+use std::ptr::NonNull;
+#[cfg(feature = "safe")]
+use typed_arena::Arena;
+#[cfg(feature = "safe")]
+use std::cell::{Cell, Ref, RefCell, RefMut};
+#[cfg(feature = "safe")]
+use std::fmt::{Debug, Formatter};
+
+/// How many children an automaton stores inline before they spill to the heap. Each rule, group,
+/// or repetition matched inside a production is one child, so productions with many of them
+/// allocate. Doubled by the `wide-automata` feature.
+#[cfg(not(feature = "wide-automata"))]
+pub const INLINE_CHILDREN: usize = 2;
+#[cfg(feature = "wide-automata")]
+pub const INLINE_CHILDREN: usize = 4;
+
+/// How many automata one command can spawn or wake up before the list spills to the heap.
+/// Referencing a rule spawns one automaton per production, and an or group one per alternative,
+/// so [GrammarInfo::widest_choice](crate::report::GrammarInfo::widest_choice) tells whether a
+/// grammar fits. Doubled by the `wide-automata` feature.
+#[cfg(not(feature = "wide-automata"))]
+pub const INLINE_SPAWNS: usize = 4;
+#[cfg(feature = "wide-automata")]
+pub const INLINE_SPAWNS: usize = 8;
 
 /// Represents the full state of a DFA used in the parser.
 #[derive(Clone, Debug)]
 pub struct Automaton<'a> {
     /// The rule this automaton is parsing
-    pub rule: Rule,
+    pub rule: RuleId,
     /// The route it is parsing. See [Parseable](crate::parser::Parseable) for details on routes.
     pub route: u32,
     /// The current state of the automaton
@@ -22,40 +51,165 @@ pub struct Automaton<'a> {
     /// If this automaton is a child, this is a pointer to its parent.
     pub parent: Option<(Rawtomaton<'a>, Continuation)>,
     /// After being successfully reawakened by a child, it is added to this vec
-    pub children: TinyVec<[Rawtomaton<'a>; 2]>
+    pub children: TinyVec<[Rawtomaton<'a>; INLINE_CHILDREN]>,
+    /// If this automaton was spawned in place of a production of an `#[inline_rule]` (see
+    /// [RuleTable::inline](super::RuleTable::inline)), the rule and route of that production. Its
+    /// automaton is only allocated once this one declares victory.
+    pub inlined: Option<(RuleId, u32)>,
+    /// How many pieces of broken input were skipped for [Recovered](super::recovery::Recovered)
+    /// fields in the matches under this automaton: its children, and theirs.
+    pub errors: u32,
+    /// Whether this automaton is inside a `#[no_skip]` production, its own or one it is nested in,
+    /// so it sees the skipped lexemes kept in the input for those productions.
+    pub no_skip: bool
 }
 
-/// A newtype wrapper for a raw pointer to an [Automaton].
-#[derive(Shrinkwrap, Debug, Copy, Clone, Eq, PartialEq)]
+/// A handle to an [Automaton] in an [Army].
+///
+/// By default this is a raw pointer. With the `safe` feature it is a reference to a [RefCell], so
+/// the parser has no `unsafe` code at the cost of a borrow flag per automaton and a check on every
+/// access. Either way, automata are only written by the parser, and a default handle points to
+/// nothing.
+#[cfg(not(feature = "safe"))]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct Rawtomaton<'a>(*mut Automaton<'a>);
 
-impl Default for Rawtomaton<'_> {
-    fn default() -> Self {
-        Rawtomaton(null_mut())
+/// A handle to an [Automaton] in an [Army].
+///
+/// By default this is a raw pointer. With the `safe` feature it is a reference to a [RefCell], so
+/// the parser has no `unsafe` code at the cost of a borrow flag per automaton and a check on every
+/// access. Either way, automata are only written by the parser, and a default handle points to
+/// nothing.
+#[cfg(feature = "safe")]
+#[derive(Copy, Clone)]
+pub struct Rawtomaton<'a>(Option<&'a RefCell<Automaton<'a>>>);
+
+/// What the arena of an [Army] holds for each automaton.
+#[cfg(not(feature = "safe"))]
+type Slot<'a> = Automaton<'a>;
+#[cfg(feature = "safe")]
+type Slot<'a> = RefCell<Automaton<'a>>;
+
+/// The bytes the arena of an [Army] takes for each automaton.
+pub(super) const AUTOMATON_BYTES: usize = std::mem::size_of::<Slot>();
+
+#[cfg(not(feature = "safe"))]
+impl<'a> Rawtomaton<'a> {
+    /// The automaton this points to, for generated [assemble](crate::parser::Parseable::assemble)
+    /// functions. Automata are only written while parsing, before any are assembled.
+    ///
+    /// # Panics
+    ///
+    /// If this is a default handle.
+    #[doc(hidden)]
+    pub fn automaton(&self) -> &Automaton<'a> {
+        self.get()
+    }
+
+    /// The automaton this points to.
+    ///
+    /// # Panics
+    ///
+    /// If this is a default handle.
+    pub(crate) fn get(&self) -> &Automaton<'a> {
+        // SAFETY: handles are only made by an Army from automata it allocated, and the Army is
+        // borrowed for as long as the handles live. The parser never holds on to the reference
+        // while writing through another handle to the same automaton, and code outside the crate
+        // can only read through automaton.
+        unsafe { self.0.as_ref() }.expect("default Rawtomaton doesn't point to an automaton")
+    }
+
+    /// The automaton this points to, for writing.
+    ///
+    /// # Panics
+    ///
+    /// If this is a default handle.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) fn get_mut(&self) -> &mut Automaton<'a> {
+        // SAFETY: see get.
+        unsafe { self.0.as_mut() }.expect("default Rawtomaton doesn't point to an automaton")
+    }
+
+    /// An identity for the automaton, for keying maps by it.
+    pub(super) fn id(&self) -> usize {
+        self.0 as usize
     }
 }
 
-impl<'a> From<*mut Automaton<'a>> for Rawtomaton<'a> {
-    fn from(a: *mut Automaton<'a>) -> Self {
-        Rawtomaton(a)
+#[cfg(feature = "safe")]
+impl<'a> Rawtomaton<'a> {
+    /// The automaton this points to, for generated [assemble](crate::parser::Parseable::assemble)
+    /// functions. Automata are only written while parsing, before any are assembled.
+    ///
+    /// # Panics
+    ///
+    /// If this is a default handle, or the automaton is being written.
+    #[doc(hidden)]
+    pub fn automaton(&self) -> Ref<'_, Automaton<'a>> {
+        self.get()
+    }
+
+    /// The automaton this points to.
+    ///
+    /// # Panics
+    ///
+    /// If this is a default handle, or the automaton is being written.
+    pub(crate) fn get(&self) -> Ref<'a, Automaton<'a>> {
+        self.0.expect("default Rawtomaton doesn't point to an automaton").borrow()
+    }
+
+    /// The automaton this points to, for writing.
+    ///
+    /// # Panics
+    ///
+    /// If this is a default handle, or the automaton is being read or written.
+    pub(crate) fn get_mut(&self) -> RefMut<'a, Automaton<'a>> {
+        self.0.expect("default Rawtomaton doesn't point to an automaton").borrow_mut()
+    }
+
+    /// An identity for the automaton, for keying maps by it.
+    pub(super) fn id(&self) -> usize {
+        self.0.map_or(0, |cell| cell as *const RefCell<Automaton> as usize)
     }
 }
 
-impl<'a> From<&mut Automaton<'a>> for Rawtomaton<'a> {
-    fn from(a: &mut Automaton<'a>) -> Self {
-        Rawtomaton(a)
+#[cfg(feature = "safe")]
+impl PartialEq for Rawtomaton<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+#[cfg(feature = "safe")]
+impl Eq for Rawtomaton<'_> {}
+
+#[cfg(feature = "safe")]
+impl Debug for Rawtomaton<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Rawtomaton({:#x})", self.id())
+    }
+}
+
+impl Default for Rawtomaton<'_> {
+    fn default() -> Self {
+        #[cfg(not(feature = "safe"))]
+        return Rawtomaton(std::ptr::null_mut());
+        #[cfg(feature = "safe")]
+        return Rawtomaton(None);
     }
 }
 
 impl Automaton<'_> {
-    fn new<'a>(rule: Rule, route: u32, lexeme_start: usize) -> Automaton<'a> {
+    fn new<'a>(rule: RuleId, route: u32, lexeme_start: usize) -> Automaton<'a> {
         Automaton {
             rule,
             route,
             state: 0,
             lexeme_start,
             parent: None,
-            children: tiny_vec![]
+            children: tiny_vec![],
+            inlined: None,
+            errors: 0,
+            no_skip: false
         }
     }
 }
@@ -71,14 +225,16 @@ impl Eq for Automaton<'_> {}
 
 /// Commands that Automata can execute at each step.
 ///
-/// They can do as many of these as they need at each step, but in practice they never need
-/// more than three, so they are passed around in array_vecs of length 3.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// They can do as many of these as they need at each step, but the generated code never gives
+/// more than three, so they are passed around in array_vecs of length 3. Unlike the other small
+/// vectors, these can't spill: the limit is fixed by the code generator, not the grammar.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
 pub enum AutomatonCommand {
     /// Spawns a new child (or children), whose state 0 will be evaluated on this same lexeme.
     Spawn {
-        /// Which grammar rule to use
-        rule: Rule,
+        /// Which grammar rule to use: 0 for the rule giving the command, or `n` for the `n`th rule
+        /// (counting from 1) in its [RuleTable::uses](super::RuleTable::uses).
+        rule: u16,
         /// Lowest route to use
         route: u32,
         /// How many children to spawn. If greater than one, they are spawned on consecutive routes.
@@ -95,6 +251,7 @@ pub enum AutomatonCommand {
     Victory,
 
     /// Deactivate the automaton
+    #[default]
     Die,
 
     /// Makes the parser re-evaluate this automaton immediately after this step. This is used for
@@ -104,12 +261,6 @@ pub enum AutomatonCommand {
     Fallthrough
 }
 
-impl Default for AutomatonCommand {
-    fn default() -> AutomatonCommand {
-        AutomatonCommand::Die
-    }
-}
-
 /// What to do when an automaton's child declares victory.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Continuation {
@@ -121,29 +272,184 @@ pub enum Continuation {
     Advance,
 }
 
-/// A wrapper around a typed arena of Automata. Handles the AutomatonCommands because they often
+/// A wrapper around an arena of Automata. Handles the AutomatonCommands because they often
 /// require memory allocations.
-#[derive(Shrinkwrap)]
-pub(super) struct Army<'a>(Arena<Automaton<'a>>);
+///
+/// The automata are kept in chunks from an [Allocator] that lives for `'b`, each twice as big as
+/// the last, and are dropped with the army. With the `safe` feature they are in a typed arena from
+/// the global allocator instead.
+pub(super) struct Army<'a, 'b> {
+    #[cfg(not(feature = "safe"))]
+    chunks: RefCell<Vec<Chunk<'b>>>,
+    #[cfg(not(feature = "safe"))]
+    allocator: &'b dyn Allocator,
+    #[cfg(not(feature = "safe"))]
+    len: Cell<usize>,
+    /// The first chunk's capacity.
+    #[cfg(not(feature = "safe"))]
+    first: usize,
+    #[cfg(feature = "safe")]
+    arena: Arena<Slot<'a>>,
+    _automata: PhantomData<(Cell<Slot<'a>>, &'b dyn Allocator)>
+}
+
+/// A chunk of automata in an [Army].
+///
+/// It doesn't name the automata's lifetime, so an army can be dropped while it's still borrowed
+/// for that lifetime, as it always is by its own automata. Dropping automata doesn't follow their
+/// handles to other automata, so those don't need to be valid.
+#[cfg(not(feature = "safe"))]
+struct Chunk<'b> {
+    start: NonNull<Automaton<'static>>,
+    capacity: usize,
+    len: usize,
+    allocator: &'b dyn Allocator
+}
 
-impl<'a> Army<'a> {
-    pub fn new() -> Army<'a> {
-        Army(Arena::with_capacity(10))
+#[cfg(not(feature = "safe"))]
+impl<'b> Chunk<'b> {
+    fn new(capacity: usize, allocator: &'b dyn Allocator) -> Self {
+        let layout = Layout::array::<Automaton>(capacity).unwrap();
+        let start = match allocator.allocate(layout) {
+            Ok(start) => start.cast(),
+            Err(_) => handle_alloc_error(layout)
+        };
+        Chunk {
+            start,
+            capacity,
+            len: 0,
+            allocator
+        }
     }
+}
 
-    pub fn spawn(&'a self, rule: Rule, route: u32, lexeme_start: usize) -> Rawtomaton {
-        self.alloc(Automaton::new(rule, route, lexeme_start)).into()
+#[cfg(not(feature = "safe"))]
+impl Drop for Chunk<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the first `len` automata were written by Army::alloc, and the memory was
+        // allocated by `allocator` with this layout in Chunk::new.
+        unsafe {
+            std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(self.start.as_ptr(), self.len));
+            self.allocator.deallocate(self.start.cast(), Layout::array::<Automaton>(self.capacity).unwrap());
+        }
+    }
+}
+
+impl<'a, 'b> Army<'a, 'b> {
+    /// Creates an army whose first chunk can hold `n` automata before the arena has to grow.
+    pub fn with_capacity(n: usize) -> Army<'a, 'b> {
+        Army::with_capacity_in(n, &allocator_api2::alloc::Global)
+    }
+
+    /// Like [with_capacity](Self::with_capacity), taking the automata's memory from `allocator`.
+    /// Children lists that spill out of their automata still use the global allocator. With the
+    /// `safe` feature, the allocator isn't used.
+    #[cfg_attr(feature = "safe", allow(unused_variables))]
+    pub fn with_capacity_in(n: usize, allocator: &'b dyn Allocator) -> Army<'a, 'b> {
+        Army {
+            #[cfg(not(feature = "safe"))]
+            chunks: RefCell::new(vec![]),
+            #[cfg(not(feature = "safe"))]
+            allocator,
+            #[cfg(not(feature = "safe"))]
+            len: Cell::new(0),
+            #[cfg(not(feature = "safe"))]
+            first: n.max(1),
+            #[cfg(feature = "safe")]
+            arena: Arena::with_capacity(n),
+            _automata: PhantomData
+        }
+    }
+
+    /// How many automata have been allocated.
+    pub fn len(&self) -> usize {
+        #[cfg(not(feature = "safe"))]
+        return self.len.get();
+        #[cfg(feature = "safe")]
+        return self.arena.len();
+    }
+
+    /// Moves `slot` into the next free place of the last chunk, starting a new chunk if it's full.
+    #[cfg(not(feature = "safe"))]
+    #[allow(clippy::mut_from_ref)]
+    fn alloc(&self, slot: Slot<'a>) -> &mut Slot<'a> {
+        let mut chunks = self.chunks.borrow_mut();
+        if chunks.last().is_none_or(|chunk| chunk.len == chunk.capacity) {
+            let capacity = chunks.last().map_or(self.first, |chunk| chunk.capacity * 2);
+            chunks.push(Chunk::new(capacity, self.allocator));
+        }
+        let chunk = chunks.last_mut().unwrap();
+        self.len.set(self.len.get() + 1);
+        // SAFETY: the place is inside the chunk and hasn't been written. Chunks never move or
+        // reuse their memory, so the reference stays valid, and unique, until the army is dropped.
+        unsafe {
+            let place = (chunk.start.as_ptr() as *mut Slot<'a>).add(chunk.len);
+            place.write(slot);
+            chunk.len += 1;
+            &mut *place
+        }
     }
 
-    pub(crate) unsafe fn command(&'a self, auto: Rawtomaton<'a>, actions: ArrayVec<[AutomatonCommand; 3]>, lexeme_index: usize) -> CommandResult<'a> {
+    #[cfg(feature = "safe")]
+    fn alloc(&self, slot: Slot<'a>) -> &mut Slot<'a> {
+        self.arena.alloc(slot)
+    }
+
+    /// The bytes taken by the automata allocated so far. Children lists that spill out of their
+    /// automata aren't counted.
+    pub fn bytes(&self) -> usize {
+        self.len() * AUTOMATON_BYTES
+    }
+
+    pub fn spawn(&'a self, rule: RuleId, route: u32, lexeme_start: usize) -> Rawtomaton<'a> {
+        self.recruit(Automaton::new(rule, route, lexeme_start))
+    }
+
+    /// Moves `auto` into the arena.
+    fn recruit(&'a self, auto: Automaton<'a>) -> Rawtomaton<'a> {
+        #[cfg(not(feature = "safe"))]
+        return Rawtomaton(self.alloc(auto));
+        #[cfg(feature = "safe")]
+        return Rawtomaton(Some(self.alloc(RefCell::new(auto))));
+    }
+
+    /// If `auto` is on the route of a [SharedPrefix](super::SharedPrefix) and has matched all of it,
+    /// moves it onto the first production that shares the prefix, and returns it with a copy for
+    /// each of the others, in order. Like with spawning, productions that can't use the lexeme of
+    /// kind `kind` are left out.
+    pub(crate) fn fork<L: Lexeme>(&'a self, auto: Rawtomaton<'a>, kind: Option<u16>, grammar: &RuleTables<L>) -> Option<TinyVec<[Rawtomaton<'a>; INLINE_SPAWNS]>> {
+        let prefix = grammar.tables[auto.get().rule.0 as usize].shared_prefix(auto.get().route)?;
+        if auto.get().state < prefix.len {
+            return None;
+        }
+        let routes = prefix.productions.clone()
+            .filter(|&route| kind.is_none_or(|kind| grammar.accepts(auto.get().rule, route, prefix.len, kind)));
+        let mut forks: TinyVec<[Rawtomaton; INLINE_SPAWNS]> = TinyVec::new();
+        for route in routes {
+            let fork: Rawtomaton = match forks.is_empty() {
+                true => auto,
+                false => self.recruit(auto.get().clone())
+            };
+            fork.get_mut().route = route;
+            forks.push(fork);
+        }
+        Some(forks)
+    }
+
+    /// Runs `actions` on `auto`, which is parsing a rule in `grammar`. `kind` is the
+    /// [kind_id](Lexeme::kind_id) of the lexeme that spawned automata will see first, if there is
+    /// one, so the ones that would die on it aren't spawned.
+    pub(crate) fn command<L: Lexeme>(&'a self, auto: Rawtomaton<'a>, actions: ArrayVec<[AutomatonCommand; 3]>, lexeme_index: usize, kind: Option<u16>, grammar: &RuleTables<L>) -> CommandResult<'a> {
         use AutomatonCommand::*;
 
+        let links = &grammar.links[auto.get().rule.0 as usize];
+
         let mut clone: Option<Rawtomaton> = None;
-        let mut get_clone = move || unsafe {
+        let mut get_clone = move || {
             if let Some(c) = clone {
                 c
             } else {
-                let c = self.alloc((**auto).clone()).into();
+                let c = self.recruit(auto.get().clone());
                 clone = Some(c);
                 c
             }
@@ -154,7 +460,7 @@ impl<'a> Army<'a> {
         for action in &actions {
             match action {
                 Advance => {
-                    (**auto).state += 1;
+                    auto.get_mut().state += 1;
                 }
                 Die => {
                     result.remove = true;
@@ -166,31 +472,78 @@ impl<'a> Army<'a> {
                     on_victory
                 } => {
                     let mut die = actions.contains(&AutomatonCommand::Die);
+                    let target = links[*rule as usize];
+                    // Rule 0 is this rule's own extra routes, which are never productions.
+                    let inline = if *rule == 0 { &[][..] } else { grammar.tables[target.0 as usize].inline };
                     for i in 0..*how_many {
-                        let new = self.spawn(*rule, route + i, lexeme_index);
-                        if die {
-                            (**new).parent = Some((auto, *on_victory));
-                            die = false;
-                        } else {
-                            (**new).parent = Some((get_clone(), *on_victory));
+                        // A production of an inlined rule is replaced by the productions of the rule
+                        // it matches, unless that rule is inlined too.
+                        let (spawn_rule, spawn_routes, inlined) = match inline.get((route + i) as usize) {
+                            Some(&(used, productions)) => {
+                                let delegate = grammar.links[target.0 as usize][used as usize];
+                                if grammar.tables[delegate.0 as usize].inline.is_empty() {
+                                    (delegate, 0..productions, Some((target, route + i)))
+                                } else {
+                                    (target, route + i..route + i + 1, None)
+                                }
+                            }
+                            None => (target, route + i..route + i + 1, None)
+                        };
+                        let table = grammar.tables[spawn_rule.0 as usize];
+                        let routes = spawn_routes
+                            .filter_map(|route| table.entry_route(route))
+                            .filter(|&route| kind.is_none_or(|kind| grammar.accepts(spawn_rule, route, 0, kind)));
+                        for spawn_route in routes {
+                            let no_skip = auto.get().no_skip || grammar.no_skip(spawn_rule, spawn_route);
+                            let new = self.spawn(spawn_rule, spawn_route, lexeme_index);
+                            new.get_mut().inlined = inlined;
+                            new.get_mut().no_skip = no_skip;
+                            if die {
+                                new.get_mut().parent = Some((auto, *on_victory));
+                                die = false;
+                            } else {
+                                new.get_mut().parent = Some((get_clone(), *on_victory));
+                            }
+                            result.new_spawns.push(new);
                         }
-                        result.new_spawns.push(new);
                     }
                 }
                 Victory => {
                     let mut die = actions.contains(&AutomatonCommand::Die);
                     let mut auto = auto;
-                    (**auto).state += 1;
+                    if die && actions.iter().any(|a| matches!(a, Spawn {..})) {
+                        // The first child spawned by this state has this automaton as its parent,
+                        // so it has to stay in this state for when that child finishes.
+                        auto = self.recruit(auto.get().clone());
+                    }
+                    auto.get_mut().state += 1;
                     loop {
-                        match (**auto).parent {
-                            Some((mut parent, cont)) => {
+                        let inlined = auto.get().inlined;
+                        if let Some((rule, route)) = inlined {
+                            // The production of the inlined rule that this automaton stood in for
+                            // gets its automaton now, already finished, with this one as its child.
+                            let child = if die { auto } else { self.recruit(auto.get().clone()) };
+                            let mut production = Automaton::new(rule, route, auto.get().lexeme_start);
+                            production.state = 1;
+                            production.parent = auto.get().parent;
+                            production.errors = child.get().errors;
+                            production.no_skip = production.parent.is_some_and(|(parent, _)| parent.get().no_skip);
+                            production.children.push(child);
+                            auto = self.recruit(production);
+                            die = true;
+                        }
+                        let parent = auto.get().parent;
+                        match parent {
+                            Some((parent, cont)) => {
+                                // The parent can be woken up more than once when the input is
+                                // ambiguous, so each victory gets its own copy.
+                                let parent: Rawtomaton = self.recruit(parent.get().clone());
+                                parent.get_mut().state += 1;
+                                parent.get_mut().errors += auto.get().errors + grammar.is_recovery(auto.get().rule, auto.get().route) as u32;
                                 if die {
-                                    (**parent).state += 1;
-                                    (**parent).children.push(auto);
+                                    parent.get_mut().children.push(auto);
                                 } else {
-                                    parent = self.alloc((**parent).clone()).into();
-                                    (**parent).state += 1;
-                                    (**parent).children.push(self.alloc((**auto).clone()).into());
+                                    parent.get_mut().children.push(self.recruit(auto.get().clone()));
                                 }
                                 match cont {
                                     Continuation::PassDie => {
@@ -220,15 +573,223 @@ impl<'a> Army<'a> {
                 }
             }
         }
+        // A state that spawns children and declares victory at once is a `?` or `*` that was
+        // skipped, so the victory came before the current lexeme.
+        result.early_victory = actions.iter().any(|a| matches!(a, Spawn {..})) && actions.contains(&Victory);
         result
     }
 }
 
 #[derive(Default)]
 pub(super) struct CommandResult<'a> {
-    pub new_spawns: TinyVec<[Rawtomaton<'a>; 4]>,
-    pub reactivated: TinyVec<[Rawtomaton<'a>; 4]>,
+    pub new_spawns: TinyVec<[Rawtomaton<'a>; INLINE_SPAWNS]>,
+    pub reactivated: TinyVec<[Rawtomaton<'a>; INLINE_SPAWNS]>,
     pub victorious: Option<Rawtomaton<'a>>,
     pub remove: bool,
-    pub fallthrough: bool
-}
\ No newline at end of file
+    pub fallthrough: bool,
+    /// The victory didn't use the current lexeme, so reactivated parents still need to see it.
+    pub early_victory: bool
+}
+/// These drive [Army::command] directly, so they are small enough to run under Miri, which checks
+/// that the automata graph is only read and written through live handles:
+///
+/// ```text
+/// cargo +nightly miri test -p parce --lib parser::automata
+/// ```
+///
+/// or under AddressSanitizer:
+///
+/// ```text
+/// RUSTFLAGS=-Zsanitizer=address cargo +nightly test -Zbuild-std --target x86_64-unknown-linux-gnu -p parce --lib parser::automata
+/// ```
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use parce::parser::{Parseable, ParseSnapshot, RuleId};
+    use super::*;
+    use AutomatonCommand::*;
+
+    #[lexer(EngineLexer)]
+    enum EngineLexemes {
+        A = 'a',
+        B = 'b',
+        LParen = '(',
+        RParen = ')',
+        #[skip] Space = ' '
+    }
+
+    #[parser(EngineLexer)]
+    enum Pair {
+        Pair(Letter, Letter) = "0 1"
+    }
+
+    #[parser(EngineLexer)]
+    enum Letter {
+        A = "A",
+        B = "B"
+    }
+
+    #[parser(EngineLexer)]
+    enum Group {
+        Group(Vec<Letter>, Option<Pair>) = "LParen 0* RParen 1?"
+    }
+
+    fn commands(list: &[AutomatonCommand]) -> ArrayVec<[AutomatonCommand; 3]> {
+        list.iter().copied().collect()
+    }
+
+    fn spawn_letters(on_victory: Continuation) -> AutomatonCommand {
+        Spawn { rule: 1, route: 0, how_many: 2, on_victory }
+    }
+
+    /// Counts the chunks it hands out, and the ones given back.
+    #[cfg(not(feature = "safe"))]
+    #[derive(Default)]
+    struct Counting {
+        allocated: Cell<usize>,
+        freed: Cell<usize>
+    }
+
+    #[cfg(not(feature = "safe"))]
+    unsafe impl Allocator for Counting {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+            self.allocated.set(self.allocated.get() + 1);
+            allocator_api2::alloc::Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.freed.set(self.freed.get() + 1);
+            allocator_api2::alloc::Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "safe"))]
+    fn allocator() {
+        let counting = Counting::default();
+        {
+            let army = Army::with_capacity_in(2, &counting);
+            let first = army.spawn(RuleId(0), 0, 0);
+            for route in 1..7 {
+                army.spawn(RuleId(0), route, 0);
+            }
+            // Chunks of 2, 4, and 8 automata, which never move.
+            assert_eq!((army.len(), counting.allocated.get()), (7, 3));
+            assert_eq!(first.get().route, 0);
+            first.get_mut().children.extend((0..10).map(|_| first));
+        }
+        assert_eq!(counting.freed.get(), 3);
+    }
+
+    #[test]
+    fn recruit_with_die() {
+        let grammar = (Pair::table().reachable)();
+        let army = Army::with_capacity(1);
+        let root = army.spawn(RuleId(0), 0, 0);
+
+        // The first child takes over the dying automaton as its parent, and the rest share a copy.
+        let result = army.command(root, commands(&[spawn_letters(Continuation::Advance), Die]), 0, None, grammar);
+        assert!(result.remove);
+        let parents: Vec<_> = result.new_spawns.iter().map(|child| child.get().parent.unwrap().0).collect();
+        assert_eq!(parents[0], root);
+        assert_ne!(parents[1], root);
+        assert_eq!(*parents[1].get(), *root.get());
+        assert!(result.new_spawns.iter().all(|child| child.get().rule == grammar.links[0][1]));
+
+        let result = army.command(root, commands(&[spawn_letters(Continuation::Advance)]), 0, None, grammar);
+        assert!(!result.remove);
+        let parents: Vec<_> = result.new_spawns.iter().map(|child| child.get().parent.unwrap().0).collect();
+        assert_ne!(parents[0], root);
+        assert_eq!(parents[0], parents[1]);
+
+        // Spawned automata that would die on the next lexeme aren't spawned.
+        let a = EngineLexemes::A.kind_id();
+        let result = army.command(root, commands(&[spawn_letters(Continuation::Advance), Die]), 0, Some(a), grammar);
+        assert_eq!(result.new_spawns.len(), 1);
+        assert_eq!(result.new_spawns[0].get().parent.unwrap().0, root);
+        assert_eq!(army.len(), 8);
+    }
+
+    #[test]
+    fn clone_on_victory() {
+        let grammar = (Pair::table().reachable)();
+        let army = Army::with_capacity(1);
+        let root = army.spawn(RuleId(0), 0, 0);
+        let children = army.command(root, commands(&[spawn_letters(Continuation::Advance), Die]), 0, None, grammar).new_spawns;
+
+        // Each victory wakes up its own copy of the parent, and the parent itself stays as it was.
+        let first = army.command(children[0], commands(&[Victory, Die]), 0, None, grammar);
+        let second = army.command(children[1], commands(&[Victory]), 0, None, grammar);
+        let (first, second) = (first.reactivated[0], second.reactivated[0]);
+        assert_ne!(first, second);
+        assert!(first != root && second != root);
+        assert_eq!((root.get().state, root.get().children.len()), (0, 0));
+        assert_eq!((first.get().state, second.get().state), (1, 1));
+
+        // A child that dies is moved into its parent, and one that stays alive is copied.
+        assert_eq!(first.get().children.as_slice(), &[children[0]]);
+        assert_ne!(second.get().children[0], children[1]);
+        assert_eq!(children[1].get().state, 1);
+
+        second.get_mut().state += 1;
+        assert_eq!((first.get().state, root.get().state), (1, 0));
+        assert_eq!(first.get().parent, None);
+    }
+
+    #[test]
+    fn pass_victory() {
+        let grammar = (Pair::table().reachable)();
+        let army = Army::with_capacity(1);
+        let root = army.spawn(RuleId(0), 0, 0);
+        root.get_mut().state = 1;
+        let children = army.command(root, commands(&[spawn_letters(Continuation::PassDie), Die]), 1, None, grammar).new_spawns;
+
+        // The last field's victory is the parent's victory too.
+        let result = army.command(children[1], commands(&[Victory, Die]), 1, None, grammar);
+        assert!(result.reactivated.is_empty());
+        let victorious = result.victorious.unwrap();
+        assert_ne!(victorious, root);
+        assert_eq!((victorious.get().state, root.get().state), (2, 1));
+        assert_eq!(victorious.get().children.as_slice(), &[children[1]]);
+
+        let result = army.command(children[0], commands(&[Victory, Die]), 1, None, grammar);
+        assert_ne!(result.victorious, Some(victorious));
+        assert_eq!(victorious.get().children.len(), 1);
+    }
+
+    #[test]
+    fn early_victory() {
+        let grammar = (Pair::table().reachable)();
+        let army = Army::with_capacity(1);
+        let root = army.spawn(RuleId(0), 0, 0);
+
+        // A skipped `?` or `*` spawns its children and declares victory at once. The children keep
+        // the automaton in the state that spawned them.
+        let result = army.command(root, commands(&[spawn_letters(Continuation::Advance), Victory, Die]), 0, None, grammar);
+        assert!(result.early_victory && result.remove);
+        let victorious = result.victorious.unwrap();
+        assert_ne!(victorious, root);
+        assert_eq!((victorious.get().state, root.get().state), (1, 0));
+        assert_eq!(result.new_spawns[0].get().parent.unwrap().0, root);
+
+        let result = army.command(root, commands(&[Advance, Fallthrough]), 0, None, grammar);
+        assert!(result.fallthrough && !result.remove && !result.early_victory);
+        assert_eq!(root.get().state, 1);
+    }
+
+    #[test]
+    fn engine() {
+        assert_eq!("a b".parse(), Ok(Pair::Pair(Letter::A, Letter::B)));
+        assert_eq!("(a b) a b".parse(), Ok(Group::Group(vec![Letter::A, Letter::B], Some(Pair::Pair(Letter::A, Letter::B)))));
+        assert_eq!("(b)".parse(), Ok(Group::Group(vec![Letter::B], None)));
+        assert!("(a b a".parse::<Group>().is_err());
+
+        let mut snapshot = ParseSnapshot::<Group>::new();
+        let mut session = ParseSession::new(EngineLexer::default());
+        session.feed(&mut snapshot, "( a b").unwrap();
+        session.feed(&mut snapshot, " ) b a").unwrap();
+        let (group, _) = session.finish(&snapshot).unwrap();
+        assert_eq!(group, Group::Group(vec![Letter::A, Letter::B], Some(Pair::Pair(Letter::B, Letter::A))));
+    }
+}
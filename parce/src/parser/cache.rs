@@ -0,0 +1,248 @@
+//! Contains [ParseCache], which remembers the values parsed from inputs that have been seen
+//! before, for tools like build systems that parse the same unchanged files over and over.
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::error::ParceError;
+use crate::fingerprint::{FNV_OFFSET, combine_fingerprints, fnv1a};
+use super::{Parseable, ParseSession};
+
+/// What a cached value is stored under: the grammar it was parsed with, and a hash of the input.
+///
+/// Both halves are the same on every build and every platform, so keys can name files in a cache
+/// directory that outlives the process. The input hash isn't collision resistant, and inputs that
+/// collide are easy to make, so a [ParseCache] keeps each value's input too, and only uses the
+/// value when its input is the one being parsed. Its [Display](fmt::Display) form, like
+/// `"9f0e0c6a2b3d4e5f-0123456789abcdef"`, only uses characters that are safe in file names.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct CacheKey {
+    /// The [fingerprint](crate::report::GrammarInfo::fingerprint) of the grammar, combined with
+    /// the session's lexer mode and [version](crate::version::GrammarVersion::fingerprint).
+    pub grammar: u64,
+    /// An FNV-1a hash of the input's length and text.
+    pub input: u64
+}
+
+impl CacheKey {
+    /// The key for parsing `text` as an `O` with `session`.
+    pub fn new<O: Parseable>(session: &ParseSession<O::Lexer>, text: &str) -> CacheKey {
+        let mut grammar = O::GRAMMAR.fingerprint();
+        grammar = fnv1a(grammar, session.lexer().to_string().as_bytes());
+        if let Some(version) = session.version() {
+            grammar = combine_fingerprints(grammar, version.fingerprint());
+        }
+        CacheKey {
+            grammar,
+            input: fnv1a(combine_fingerprints(FNV_OFFSET, text.len() as u64), text.as_bytes())
+        }
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}-{:016x}", self.grammar, self.input)
+    }
+}
+
+/// Where a [ParseCache] keeps its values, along with the inputs they were parsed from. A `HashMap`
+/// keeps them in memory; anything that can write them somewhere, like files named by their keys,
+/// can keep them between runs.
+pub trait CacheStorage<T> {
+    /// The input and value stored under `key`, if there are some.
+    fn load(&mut self, key: CacheKey) -> Option<(String, T)>;
+
+    /// Stores `value`, parsed from `input`, under `key`, replacing anything already there.
+    fn store(&mut self, key: CacheKey, input: &str, value: &T);
+}
+
+impl<T: Clone> CacheStorage<T> for HashMap<CacheKey, (String, T)> {
+    fn load(&mut self, key: CacheKey) -> Option<(String, T)> {
+        self.get(&key).cloned()
+    }
+
+    fn store(&mut self, key: CacheKey, input: &str, value: &T) {
+        self.insert(key, (input.to_string(), value.clone()));
+    }
+}
+
+/// Remembers the values parsed from inputs, by [CacheKey], so an input is only parsed again when it
+/// or the grammar changes. A stored value whose input isn't the one being parsed, because their
+/// hashes collide, is parsed again and replaced.
+///
+/// Only values that parse are stored; errors are found again every time, and are recorded in the
+/// session's [diagnostics](ParseSession::diagnostics) as usual. Keys cover the grammar, the
+/// session's lexer mode and [version](ParseSession::with_version), and the input, but not the
+/// session's [filters](ParseSession::with_filter) or [skip](ParseSession::with_skip) function, so
+/// sessions set up differently shouldn't share a storage.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::cache::{CacheKey, CacheStorage, ParseCache};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Number = "[0-9]+",
+///     Comma = ',',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// #[derive(Clone)]
+/// enum List {
+///     List(Vec<u32>) = "(0=Number Comma?)*"
+/// }
+///
+/// /// Keeps inputs and values as text, the way a cache directory would keep one file per key.
+/// #[derive(Default)]
+/// struct TextStorage(std::collections::BTreeMap<String, (String, String)>);
+///
+/// impl CacheStorage<List> for TextStorage {
+///     fn load(&mut self, key: CacheKey) -> Option<(String, List)> {
+///         let (input, text) = self.0.get(&key.to_string())?;
+///         Some((input.clone(), List::List(text.split(' ').filter_map(|n| n.parse().ok()).collect())))
+///     }
+///
+///     fn store(&mut self, key: CacheKey, input: &str, List::List(numbers): &List) {
+///         let text = numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(" ");
+///         self.0.insert(key.to_string(), (input.to_string(), text));
+///     }
+/// }
+///
+/// let mut session = ParseSession::new(MyLexer::default());
+/// let mut cache = ParseCache::new(TextStorage::default());
+/// for text in ["1, 2, 3", "4", "1, 2, 3"] {
+///     cache.parse_all::<List>(&mut session, text).unwrap();
+/// }
+/// assert_eq!((cache.hits(), cache.misses()), (1, 2));
+/// assert_eq!(cache.storage().0.len(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct ParseCache<S> {
+    storage: S,
+    hits: usize,
+    misses: usize
+}
+
+impl<S> ParseCache<S> {
+    /// Creates a cache that keeps its values in `storage`.
+    pub fn new(storage: S) -> Self {
+        ParseCache {
+            storage,
+            hits: 0,
+            misses: 0
+        }
+    }
+
+    /// Like [ParseSession::parse_all], but gives back the stored value if `text` was parsed as an
+    /// `O` before, and stores the value otherwise.
+    pub fn parse_all<O>(&mut self, session: &mut ParseSession<O::Lexer>, text: &str) -> Result<O, ParceError>
+    where O: for<'t> Parseable<Output<'t> = O>, S: CacheStorage<O> {
+        let key = CacheKey::new::<O>(session, text);
+        if let Some((input, value)) = self.storage.load(key) {
+            if input == text {
+                self.hits += 1;
+                return Ok(value);
+            }
+        }
+        self.misses += 1;
+        let value = session.parse_all::<O>(text)?;
+        self.storage.store(key, text, &value);
+        Ok(value)
+    }
+
+    /// How many parses were answered from the storage.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// How many parses weren't in the storage, and were parsed.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// The storage the values are kept in.
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// The storage the values are kept in, to remove or add values.
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    /// Takes the storage back, to save it or hand it to another cache.
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use parce::version::GrammarVersion;
+    use super::*;
+
+    #[lexer(CacheLexer)]
+    #[modes(Numeric, Alpha)]
+    enum CacheLexemes {
+        Number = "[0-9]+",
+        #[skip] Space = ' ',
+
+        #[mode(Alpha)]
+        Word = "[a-z]+"
+    }
+
+    #[parser(CacheLexer)]
+    #[derive(Clone)]
+    enum Item {
+        Number(u32) = "0=Number",
+        #[since(2)] Pair(u32, u32) = "0=Number 1=Number",
+        Word(String) = "0=Word"
+    }
+
+    #[test]
+    fn keys() {
+        let session = ParseSession::new(CacheLexer::Numeric);
+        let key = CacheKey::new::<Item>(&session, "1");
+        assert_eq!(key, CacheKey::new::<Item>(&ParseSession::new(CacheLexer::Numeric), "1"));
+        assert_ne!(key.input, CacheKey::new::<Item>(&session, "2").input);
+        assert_ne!(key.grammar, CacheKey::new::<Item>(&ParseSession::new(CacheLexer::Alpha), "1").grammar);
+        let versioned = ParseSession::new(CacheLexer::Numeric).with_version(GrammarVersion::at(&[1]));
+        assert_ne!(key.grammar, CacheKey::new::<Item>(&versioned, "1").grammar);
+        assert_eq!(key.to_string().len(), 33);
+    }
+
+    #[test]
+    fn parses_once() {
+        let mut cache = ParseCache::new(HashMap::new());
+        let mut session = ParseSession::new(CacheLexer::Numeric).with_diagnostics(100);
+        assert_eq!(cache.parse_all::<Item>(&mut session, "1 2").unwrap(), Item::Pair(1, 2));
+        assert_eq!(cache.parse_all::<Item>(&mut session, "1 2").unwrap(), Item::Pair(1, 2));
+        assert!(cache.parse_all::<Item>(&mut session, "x").is_err());
+        assert!(cache.parse_all::<Item>(&mut session, "x").is_err());
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+        assert_eq!(session.diagnostics().len(), 2);
+
+        // A different version is a different grammar, so it doesn't get the old value.
+        let mut old = ParseSession::new(CacheLexer::Numeric).with_version(GrammarVersion::at(&[1]));
+        assert!(cache.parse_all::<Item>(&mut old, "1 2").is_err());
+        let mut words = ParseSession::new(CacheLexer::Alpha);
+        assert_eq!(cache.parse_all::<Item>(&mut words, "ab").unwrap(), Item::Word("ab".to_string()));
+        assert_eq!(cache.into_storage().len(), 2);
+    }
+
+    #[test]
+    fn collisions() {
+        let mut cache = ParseCache::new(HashMap::new());
+        let mut session = ParseSession::new(CacheLexer::Numeric);
+        // Stands in for another input whose hash is the same as "1".
+        let key = CacheKey::new::<Item>(&session, "1");
+        cache.storage_mut().insert(key, ("2".to_string(), Item::Number(2)));
+
+        assert_eq!(cache.parse_all::<Item>(&mut session, "1").unwrap(), Item::Number(1));
+        assert_eq!(cache.parse_all::<Item>(&mut session, "1").unwrap(), Item::Number(1));
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+        assert_eq!(cache.storage()[&key], ("1".to_string(), Item::Number(1)));
+    }
+}
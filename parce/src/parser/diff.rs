@@ -0,0 +1,189 @@
+//! Contains [diff_trees], which lines up the nodes of two parses of different versions of an input.
+
+use super::index::{NodeId, TreeIndex};
+
+/// One entry in the result of [diff_trees].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TreeEdit {
+    /// A node in the old tree corresponds to a node in the new tree: they are the same rule and
+    /// production, in the same place.
+    Matched {
+        old: NodeId,
+        new: NodeId,
+        /// Whether the two nodes matched exactly the same text. The children of unchanged nodes
+        /// are all matched and unchanged too.
+        unchanged: bool
+    },
+    /// A node of the old tree, and everything under it, has no counterpart in the new tree.
+    Deleted(NodeId),
+    /// A node of the new tree, and everything under it, has no counterpart in the old tree.
+    Inserted(NodeId)
+}
+
+/// Compares two trees, like the trees of the old and new versions of a file being edited.
+///
+/// Starting from the roots, the children of every matched pair are lined up so the most children
+/// match, preferring children with the same text. Matched children are compared the same way, and
+/// unmatched ones are reported as deleted or inserted along with their whole subtree. Edits come
+/// out in pre-order.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::index::TreeIndex;
+/// use parce::parser::diff::{diff_trees, TreeEdit};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Word = "[a-z]+",
+///     Number = "[0-9]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Items {
+///     Items(Vec<Item>) = "0*"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Item {
+///     Word = "Word",
+///     Number = "Number"
+/// }
+///
+/// let (old_text, new_text) = ("a b 1", "a 2 1 c");
+/// let (_, old): (Items, _) = old_text.parse_tree().unwrap();
+/// let (_, new): (Items, _) = new_text.parse_tree().unwrap();
+/// let (old, new) = (TreeIndex::new(&old), TreeIndex::new(&new));
+///
+/// let edits = diff_trees(&old, old_text, &new, new_text);
+/// let summary: Vec<_> = edits.iter().map(|e| match e {
+///     TreeEdit::Matched { new: n, unchanged: true, .. } => format!("={}", new.get(*n).production),
+///     TreeEdit::Matched { new: n, unchanged: false, .. } => format!("~{}", new.get(*n).production),
+///     TreeEdit::Deleted(o) => format!("-{}", old.get(*o).production),
+///     TreeEdit::Inserted(n) => format!("+{}", new.get(*n).production)
+/// }).collect();
+/// assert_eq!(summary, vec!["~Items", "=Word", "-Word", "+Number", "=Number", "+Word"]);
+/// ```
+pub fn diff_trees(old: &TreeIndex, old_text: &str, new: &TreeIndex, new_text: &str) -> Vec<TreeEdit> {
+    let mut edits = vec![];
+    let differ = Differ { old, old_text, new, new_text };
+    if differ.similar(old.root(), new.root()) {
+        differ.matched(old.root(), new.root(), &mut edits);
+    } else {
+        edits.push(TreeEdit::Deleted(old.root()));
+        edits.push(TreeEdit::Inserted(new.root()));
+    }
+    edits
+}
+
+struct Differ<'a, 'b> {
+    old: &'a TreeIndex<'b>,
+    old_text: &'a str,
+    new: &'a TreeIndex<'b>,
+    new_text: &'a str
+}
+
+impl Differ<'_, '_> {
+    /// Whether two nodes can be matched at all.
+    fn similar(&self, old: NodeId, new: NodeId) -> bool {
+        let (o, n) = (self.old.get(old), self.new.get(new));
+        o.rule == n.rule && o.production == n.production
+    }
+
+    fn same_text(&self, old: NodeId, new: NodeId) -> bool {
+        let (o, n) = (self.old.get(old), self.new.get(new));
+        self.old_text.get(o.start..o.end) == self.new_text.get(n.start..n.end)
+    }
+
+    fn matched(&self, old: NodeId, new: NodeId, edits: &mut Vec<TreeEdit>) {
+        let unchanged = self.same_text(old, new);
+        edits.push(TreeEdit::Matched { old, new, unchanged });
+
+        let old_children: Vec<_> = self.old.children(old).collect();
+        let new_children: Vec<_> = self.new.children(new).collect();
+        let (m, n) = (old_children.len(), new_children.len());
+
+        // Weighted longest common subsequence: an exact match is worth more than a similar node.
+        let score = |i: usize, j: usize| {
+            let (o, n) = (old_children[i], new_children[j]);
+            match (self.similar(o, n), self.same_text(o, n)) {
+                (false, _) => 0,
+                (true, false) => 1,
+                (true, true) => 2
+            }
+        };
+        let mut table = vec![vec![0u32; n + 1]; m + 1];
+        for i in (0..m).rev() {
+            for j in (0..n).rev() {
+                let here = score(i, j);
+                let take = if here > 0 { here + table[i + 1][j + 1] } else { 0 };
+                table[i][j] = take.max(table[i + 1][j]).max(table[i][j + 1]);
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < m || j < n {
+            if i < m && j < n {
+                let here = score(i, j);
+                if here > 0 && table[i][j] == here + table[i + 1][j + 1] {
+                    self.matched(old_children[i], new_children[j], edits);
+                    i += 1;
+                    j += 1;
+                    continue;
+                }
+            }
+            if j == n || (i < m && table[i][j] == table[i + 1][j]) {
+                edits.push(TreeEdit::Deleted(old_children[i]));
+                i += 1;
+            } else {
+                edits.push(TreeEdit::Inserted(new_children[j]));
+                j += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::RuleSpan;
+    use crate::report::Resolve;
+    use super::*;
+
+    fn node(production: &'static str, start: usize, end: usize, children: Vec<RuleSpan>) -> RuleSpan {
+        RuleSpan { rule: "R", production, resolve: Resolve::Unspecified, start, end, children, deprecated: None }
+    }
+
+    #[test]
+    fn diff() {
+        let old_text = "(x y) z";
+        let old = node("Root", 0, 7, vec![
+            node("Group", 0, 5, vec![node("Leaf", 1, 2, vec![]), node("Leaf", 3, 4, vec![])]),
+            node("Leaf", 6, 7, vec![])
+        ]);
+        let new_text = "(x w) z";
+        let new = node("Root", 0, 7, vec![
+            node("Group", 0, 5, vec![node("Leaf", 1, 2, vec![]), node("Leaf", 3, 4, vec![])]),
+            node("Leaf", 6, 7, vec![])
+        ]);
+        let (old, new) = (TreeIndex::new(&old), TreeIndex::new(&new));
+        let id: Vec<_> = std::iter::once(old.root()).chain(old.descendants(old.root())).collect();
+
+        assert_eq!(diff_trees(&old, old_text, &new, new_text), vec![
+            TreeEdit::Matched { old: id[0], new: id[0], unchanged: false },
+            TreeEdit::Matched { old: id[1], new: id[1], unchanged: false },
+            TreeEdit::Matched { old: id[2], new: id[2], unchanged: true },
+            TreeEdit::Matched { old: id[3], new: id[3], unchanged: false },
+            TreeEdit::Matched { old: id[4], new: id[4], unchanged: true },
+        ]);
+
+        assert_eq!(diff_trees(&old, old_text, &old, old_text).iter().filter(|e| matches!(e, TreeEdit::Matched { unchanged: true, .. })).count(), 5);
+
+        let other_text = "z";
+        let other = node("Leaf", 0, 1, vec![]);
+        let other = TreeIndex::new(&other);
+        assert_eq!(diff_trees(&old, old_text, &other, other_text), vec![
+            TreeEdit::Deleted(old.root()),
+            TreeEdit::Inserted(other.root())
+        ]);
+    }
+}
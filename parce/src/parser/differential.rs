@@ -0,0 +1,145 @@
+//! Contains [compare_with], which runs a grammar and a reference implementation over the same
+//! inputs and reports where they disagree about which inputs are valid.
+//!
+//! This is for validating a grammar migrated from another parser, or written for a format that
+//! already has a trusted parser, like `serde_json` for JSON: feed both a corpus of real and
+//! made-up inputs, and every input only one of them accepts is a bug in one of them.
+
+use std::fmt;
+use crate::error::ParceError;
+use super::{ParseSession, Parseable};
+
+/// An input that parce and the reference disagreed on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// The position of the input in the corpus.
+    pub index: usize,
+    pub input: String,
+    /// The error parce gave, if it rejected the input and the reference accepted it. `None` means
+    /// parce accepted it and the reference didn't.
+    pub parce_error: Option<ParceError>
+}
+
+/// The result of [compare_with].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DifferentialReport {
+    /// How many inputs both accepted.
+    pub accepted: usize,
+    /// How many inputs both rejected.
+    pub rejected: usize,
+    /// The inputs they disagreed on, in corpus order.
+    pub divergences: Vec<Divergence>
+}
+
+impl DifferentialReport {
+    /// Whether parce and the reference agreed on every input.
+    pub fn agrees(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl fmt::Display for DifferentialReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} accepted by both, {} rejected by both, {} divergent", self.accepted, self.rejected, self.divergences.len())?;
+        for divergence in &self.divergences {
+            match &divergence.parce_error {
+                Some(error) => writeln!(f, "input {} {:?}: rejected by parce at byte {}, accepted by the reference", divergence.index, divergence.input, error.start)?,
+                None => writeln!(f, "input {} {:?}: accepted by parce, rejected by the reference", divergence.index, divergence.input)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses each input of `corpus` all the way as an `O`, asks `reference` whether it accepts the
+/// input, and compares the answers.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::differential::compare_with;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Minus = '-',
+///     Digits = "[0-9]+"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Integer {
+///     Integer = "Minus? Digits"
+/// }
+///
+/// let corpus = ["12", "-3", "--3", "+5", "99999999999999999999", ""];
+/// let report = compare_with::<Integer, _>(corpus, |s| s.parse::<i64>().is_ok());
+/// assert_eq!((report.accepted, report.rejected), (2, 2));
+///
+/// // Rust's integers also take a leading '+', and can overflow.
+/// let inputs: Vec<_> = report.divergences.iter().map(|d| (d.index, d.parce_error.is_some())).collect();
+/// assert_eq!(inputs, vec![(3, true), (4, false)]);
+/// println!("{}", report);
+/// ```
+pub fn compare_with<O: Parseable, S: AsRef<str>>(corpus: impl IntoIterator<Item = S>, mut reference: impl FnMut(&str) -> bool) -> DifferentialReport {
+    let mut session = ParseSession::new(O::default_lexer());
+    let mut report = DifferentialReport::default();
+    for (index, input) in corpus.into_iter().enumerate() {
+        let input = input.as_ref();
+        let parsed = session.parse_all::<O>(input).map(|_| ());
+        match (parsed, reference(input)) {
+            (Ok(()), true) => report.accepted += 1,
+            (Err(_), false) => report.rejected += 1,
+            (parsed, _) => report.divergences.push(Divergence {
+                index,
+                input: input.to_string(),
+                parce_error: parsed.err()
+            })
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(BoolLexer)]
+    enum BoolLexemes {
+        True = "'true'",
+        False = "'false'",
+        And = "'&&'",
+        #[skip] Space = ' '
+    }
+
+    #[parser(BoolLexer)]
+    enum Conjunction {
+        Conjunction = "#Term (And #Term)*"
+    }
+
+    #[parser(BoolLexer)]
+    enum Term {
+        True = "True",
+        False = "False"
+    }
+
+    /// Splits on `&&` and checks each side, ignoring case, so it takes some inputs the grammar doesn't.
+    fn reference(input: &str) -> bool {
+        input.split("&&").all(|term| term.trim().eq_ignore_ascii_case("true") || term.trim().eq_ignore_ascii_case("false"))
+    }
+
+    #[test]
+    fn divergences() {
+        let corpus = vec!["true", "true && false", "false&&true", "", "true &&", "TRUE && false", "true && maybe"];
+        let report = compare_with::<Conjunction, _>(&corpus, reference);
+        assert_eq!((report.accepted, report.rejected), (3, 3));
+        assert_eq!(report.divergences.len(), 1);
+        assert_eq!((report.divergences[0].index, report.divergences[0].input.as_str()), (5, "TRUE && false"));
+        assert_eq!(report.divergences[0].parce_error.as_ref().map(|e| e.start), Some(0));
+        assert!(!report.agrees());
+
+        let report = compare_with::<Conjunction, _>(&corpus, |input| input.len() > 4 && reference(input));
+        assert_eq!(report.divergences.iter().map(|d| (d.index, d.parce_error.is_some())).collect::<Vec<_>>(), vec![(0, false), (5, true)]);
+        assert_eq!(report.to_string().lines().count(), 3);
+        assert!(compare_with::<Conjunction, &str>([], reference).agrees());
+    }
+}
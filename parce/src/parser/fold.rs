@@ -0,0 +1,256 @@
+//! Contains the [Fold] trait, for rewriting parsed trees, and the [Foldable] trait the parser macro
+//! implements for every rule. Also contains [splice], which the [node!](crate::node) macro uses to
+//! fill holes in trees built from snippets.
+
+use std::any::Any;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A pass that rebuilds a tree, like a desugaring or an optimization.
+///
+/// [fold](Fold::fold) is called on every node of every rule type. The default just folds the
+/// node's children, so implementations override it, fold the children with
+/// [fold_children](Foldable::fold_children) (or not, to stop at this node), and use [rewrite] to
+/// change the nodes of the types they care about.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::fold::{Fold, Foldable, rewrite};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Digit = "[0-9]+",
+///     Minus = '-',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Expr {
+///     Neg(Box<Expr>) = "Minus 0",
+///     Num(u32) = "0=Digit"
+/// }
+///
+/// /// Removes double negations.
+/// struct Simplify;
+///
+/// impl Fold for Simplify {
+///     fn fold<N: Foldable>(&mut self, node: N) -> N {
+///         let node = node.fold_children(self);
+///         rewrite(node, |expr: Expr| match expr {
+///             Expr::Neg(inner) => match *inner {
+///                 Expr::Neg(double) => *double,
+///                 other => Expr::Neg(Box::new(other))
+///             },
+///             other => other
+///         })
+///     }
+/// }
+///
+/// let parsed: Expr = "- - - 1".parse().unwrap();
+/// assert_eq!(Simplify.fold(parsed), Expr::Neg(Box::new(Expr::Num(1))));
+/// ```
+pub trait Fold {
+    /// Rebuilds `node`. The default folds its children and leaves the node itself alone.
+    fn fold<N: Foldable>(&mut self, node: N) -> N {
+        node.fold_children(self)
+    }
+}
+
+/// A rule that can be rebuilt by a [Fold]. The parser macro implements this for every rule that
+/// owns its data. Borrowed rules (`#[parser(Lexer, borrowed)]`) don't get it, because [rewrite]
+/// needs to know the node's type at runtime.
+pub trait Foldable: Sized + 'static {
+    /// Passes every field that holds another rule through `folder`, including ones inside a
+    /// `Box`, `Vec`, or `Option`, and rebuilds the node from the results.
+    fn fold_children<F: Fold + ?Sized>(self, folder: &mut F) -> Self;
+}
+
+/// If `node` is a `T`, replaces it with `f(node)`. Otherwise returns it unchanged.
+pub fn rewrite<N: Foldable, T: Foldable>(node: N, f: impl FnOnce(T) -> T) -> N {
+    let mut slot = Some(node);
+    if let Some(slot) = (&mut slot as &mut dyn Any).downcast_mut::<Option<T>>() {
+        let node = slot.take().unwrap();
+        *slot = Some(f(node));
+    }
+    slot.unwrap()
+}
+
+/// Builds a tree by parsing a snippet, and splices existing nodes into it.
+///
+/// The first argument is the rule to parse as, and the second is the snippet. Each hole is written
+/// in the snippet as a placeholder, which can be any text that parses as the type of the node
+/// filling it, and is listed after the snippet as `"placeholder" => node`. The snippet is parsed
+/// at runtime, every time the macro runs.
+///
+/// ```
+/// use parce::prelude::*;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     If = "'if'",
+///     Digit = "[0-9]+",
+///     Ident = "[a-z]+",
+///     Minus = '-',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Expr {
+///     If { condition: Box<Expr>, then: Box<Expr> } = "If condition then",
+///     Neg(Box<Expr>) = "Minus 0",
+///     Num(u32) = "0=Digit",
+///     Var(String) = "0=Ident"
+/// }
+///
+/// let condition: Expr = "- 1".parse().unwrap();
+/// let built = node!(Expr, "if cond - 2", "cond" => condition);
+/// assert_eq!(built, "if - 1 - 2".parse().unwrap());
+/// ```
+///
+/// # Panics
+///
+/// If the snippet or a placeholder doesn't parse, or a placeholder doesn't appear in the tree exactly
+/// once. Placeholders are found by comparing nodes, so one that also appears as a real part of the
+/// snippet counts as appearing twice.
+#[macro_export]
+macro_rules! node {
+    ($rule:ty, $text:expr $(, $placeholder:expr => $value:expr)* $(,)?) => {{
+        let node: $rule = match <$rule as std::str::FromStr>::from_str($text) {
+            Ok(node) => node,
+            Err(e) => panic!("snippet failed to parse: {}", e)
+        };
+        $(let node = $crate::parser::fold::splice(node, $placeholder, $value);)*
+        node
+    }};
+}
+
+/// Finds the node in `node` that is equal to `placeholder` parsed as a `T`, and replaces it with `value`.
+///
+/// # Panics
+///
+/// If `placeholder` doesn't parse, or its node doesn't appear exactly once.
+pub fn splice<N: Foldable, T: Foldable + FromStr + PartialEq>(node: N, placeholder: &str, value: T) -> N
+    where T::Err: Display {
+    let hole = match placeholder.parse() {
+        Ok(hole) => hole,
+        Err(e) => panic!("placeholder {:?} failed to parse: {}", placeholder, e)
+    };
+    let mut splice = Splice { hole, value: Some(value), found: 0 };
+    let node = splice.fold(node);
+    if splice.found != 1 {
+        panic!("placeholder {:?} should appear once, but appeared {} times", placeholder, splice.found);
+    }
+    node
+}
+
+struct Splice<T> {
+    hole: T,
+    value: Option<T>,
+    found: usize
+}
+
+impl<T: Foldable + PartialEq> Fold for Splice<T> {
+    fn fold<N: Foldable>(&mut self, node: N) -> N {
+        let before = self.found;
+        let node = rewrite(node, |node: T| if node == self.hole {
+            self.found += 1;
+            self.value.take().unwrap_or(node)
+        } else {
+            node
+        });
+        // Don't look for holes inside the value that was just spliced in.
+        if self.found == before {
+            node.fold_children(self)
+        } else {
+            node
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(FoldLexer)]
+    enum FoldLexemes {
+        Word = "[a-z]+",
+        Comma = ',',
+        LParen = '(',
+        RParen = ')',
+        #[skip] Space = ' '
+    }
+
+    #[parser(FoldLexer)]
+    enum Call {
+        Call { name: String, args: Vec<Arg> } = "name=Word LParen args* RParen",
+        Bare(Option<Box<Call>>, String) = "LParen 0? RParen 1=Word"
+    }
+
+    #[parser(FoldLexer)]
+    enum Arg {
+        Nested(Box<Call>) = "0 Comma?",
+        Word(String) = "0=Word Comma?"
+    }
+
+    /// Renames every word argument, and counts the calls it saw.
+    struct Rename(usize);
+
+    impl Fold for Rename {
+        fn fold<N: Foldable>(&mut self, node: N) -> N {
+            let node = rewrite(node, |call: Call| {
+                self.0 += 1;
+                call
+            });
+            rewrite(node.fold_children(self), |arg: Arg| match arg {
+                Arg::Word(w) => Arg::Word(w.to_uppercase()),
+                other => other
+            })
+        }
+    }
+
+    #[test]
+    fn fold() {
+        let call: Call = "f(a, g(b), c)".parse().unwrap();
+        let mut rename = Rename(0);
+        assert_eq!(rename.fold(call), Call::Call {
+            name: "f".to_string(),
+            args: vec![
+                Arg::Word("A".to_string()),
+                Arg::Nested(Box::new(Call::Call { name: "g".to_string(), args: vec![Arg::Word("B".to_string())] })),
+                Arg::Word("C".to_string())
+            ]
+        });
+        assert_eq!(rename.0, 2);
+
+        // The text of assigned fields isn't folded, only other rules.
+        let bare: Call = "(() x) y".parse().unwrap();
+        let mut rename = Rename(0);
+        assert_eq!(rename.fold(bare), "(() x) y".parse().unwrap());
+        assert_eq!(rename.0, 2);
+    }
+
+    #[test]
+    fn splice() {
+        let inner: Call = "g(b)".parse().unwrap();
+        let built = crate::node!(Call, "f(a, x(), c)", "x()" => inner, "c" => Arg::Word("d".to_string()));
+        assert_eq!(built, "f(a, g(b), d)".parse().unwrap());
+
+        // Holes of different types don't match each other, even with the same text.
+        let built = crate::node!(Call, "(f(a)) a", "a" => Arg::Word("b".to_string()));
+        assert_eq!(built, "(f(b)) a".parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "appeared 2 times")]
+    fn splice_twice() {
+        crate::node!(Call, "f(x, x)", "x" => Arg::Word("y".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "appeared 0 times")]
+    fn splice_missing() {
+        crate::node!(Call, "f(a)", "x" => Arg::Word("y".to_string()));
+    }
+}
@@ -0,0 +1,270 @@
+//! Contains [TreeIndex], which gives every node of a [RuleSpan] tree an id and answers structural
+//! queries about it, like the ones an editor asks about the node under the cursor.
+
+use std::ops::Range;
+use super::RuleSpan;
+
+/// Identifies a node in a [TreeIndex]. Ids count up from 0 at the root in pre-order (parents before
+/// their children, children in input order), so they are the same every time the same tree is
+/// indexed, and a node's id is smaller than all of its descendants'.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    /// The node's position in pre-order.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// An index over a tree returned by [parse_tree](super::Parse::parse_tree), with parent links.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::index::TreeIndex;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Word = "[a-z]+",
+///     LParen = '(',
+///     RParen = ')',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum List {
+///     List(Vec<Item>) = "LParen 0* RParen"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Item {
+///     Word = "Word",
+///     List(Box<List>) = "0"
+/// }
+///
+/// let (_, tree): (List, _) = "(a (bc d))".parse_tree().unwrap();
+/// let index = TreeIndex::new(&tree);
+///
+/// // The innermost node under offset 5, which is in "bc".
+/// let node = index.node_at(5).unwrap();
+/// assert_eq!(index.get(node).rule, "Item");
+/// assert_eq!((index.get(node).start, index.get(node).end), (4, 6));
+///
+/// let path: Vec<_> = index.ancestors(node).map(|a| index.get(a).rule).collect();
+/// assert_eq!(path, vec!["List", "Item", "List"]);
+/// assert!(index.is_ancestor(index.root(), node));
+/// assert_eq!(index.descendants(index.root()).count(), 5);
+///
+/// // "bc d" is covered by the inner list. Growing a selection of the whole inner list skips the
+/// // item holding it, which has the same span, and selects the outer list.
+/// assert_eq!(index.get(index.covering(4..8).unwrap()).rule, "List");
+/// assert_eq!(index.get(index.expand(3..9).unwrap()).rule, "List");
+/// assert_eq!(index.intersecting(5..8).len(), 5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TreeIndex<'a> {
+    nodes: Vec<Entry<'a>>
+}
+
+#[derive(Debug, Clone)]
+struct Entry<'a> {
+    span: &'a RuleSpan,
+    parent: Option<NodeId>,
+    /// The id after this node's last descendant.
+    end: u32
+}
+
+impl<'a> TreeIndex<'a> {
+    /// Indexes the tree under `root`.
+    pub fn new(root: &'a RuleSpan) -> Self {
+        let mut index = TreeIndex { nodes: vec![] };
+        index.add(root, None);
+        index
+    }
+
+    fn add(&mut self, span: &'a RuleSpan, parent: Option<NodeId>) {
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(Entry { span, parent, end: 0 });
+        for child in &span.children {
+            self.add(child, Some(id));
+        }
+        self.nodes[id.index()].end = self.nodes.len() as u32;
+    }
+
+    /// The id of the root.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// The number of nodes in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Always false, since a tree has at least its root.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The span of a node.
+    ///
+    /// # Panics
+    ///
+    /// If `id` came from a different index with more nodes.
+    pub fn get(&self, id: NodeId) -> &'a RuleSpan {
+        self.nodes[id.index()].span
+    }
+
+    /// The node's parent, or `None` for the root.
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.index()].parent
+    }
+
+    /// The node's parent, its parent, and so on up to the root.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), move |a| self.parent(*a))
+    }
+
+    /// The node's children, in input order.
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let end = self.nodes[id.index()].end;
+        std::iter::successors(Some(id.0 + 1), move |child| self.nodes.get(*child as usize).map(|e| e.end))
+            .take_while(move |child| *child < end)
+            .map(NodeId)
+    }
+
+    /// Every node under this one, in pre-order.
+    pub fn descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> {
+        (id.0 + 1..self.nodes[id.index()].end).map(NodeId)
+    }
+
+    /// Whether `ancestor` is a strict ancestor of `node`.
+    pub fn is_ancestor(&self, ancestor: NodeId, node: NodeId) -> bool {
+        ancestor < node && node.0 < self.nodes[ancestor.index()].end
+    }
+
+    /// The innermost node whose span contains `offset`, an index into the input. `None` if the
+    /// offset is outside the root's span.
+    pub fn node_at(&self, offset: usize) -> Option<NodeId> {
+        let contains = |id: NodeId| {
+            let span = self.get(id);
+            span.start <= offset && offset < span.end
+        };
+        let mut current = Some(self.root()).filter(|r| contains(*r))?;
+        while let Some(child) = self.children(current).find(|c| contains(*c)) {
+            current = child;
+        }
+        Some(current)
+    }
+
+    /// The smallest node whose span contains all of `range`. An empty range is treated like
+    /// [node_at](Self::node_at) its start.
+    pub fn covering(&self, range: Range<usize>) -> Option<NodeId> {
+        if range.is_empty() {
+            return self.node_at(range.start);
+        }
+        let covers = |id: NodeId| {
+            let span = self.get(id);
+            span.start <= range.start && range.end <= span.end
+        };
+        let mut current = Some(self.root()).filter(|r| covers(*r))?;
+        while let Some(child) = self.children(current).find(|c| covers(*c)) {
+            current = child;
+        }
+        Some(current)
+    }
+
+    /// The smallest node whose span contains `range` and is bigger than it, for growing a
+    /// selection one step at a time. `None` once the selection is the whole root.
+    pub fn expand(&self, range: Range<usize>) -> Option<NodeId> {
+        let bigger = |id: &NodeId| {
+            let span = self.get(*id);
+            span.end - span.start > range.len()
+        };
+        let node = self.covering(range.clone())?;
+        std::iter::once(node).chain(self.ancestors(node)).find(bigger)
+    }
+
+    /// Every node whose span overlaps `range`, in pre-order. An empty range finds the nodes
+    /// containing its start.
+    pub fn intersecting(&self, range: Range<usize>) -> Vec<NodeId> {
+        let overlaps = |id: NodeId| {
+            let span = self.get(id);
+            if range.is_empty() {
+                span.start <= range.start && range.start < span.end
+            } else {
+                span.start < range.end && range.start < span.end
+            }
+        };
+        let mut result = vec![];
+        let mut stack = vec![self.root()];
+        while let Some(id) = stack.pop() {
+            if overlaps(id) {
+                result.push(id);
+                let first = stack.len();
+                stack.extend(self.children(id));
+                stack[first..].reverse();
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::Resolve;
+    use super::*;
+
+    fn span(start: usize, end: usize, children: Vec<RuleSpan>) -> RuleSpan {
+        RuleSpan { rule: "R", production: "P", resolve: Resolve::Unspecified, start, end, children, deprecated: None }
+    }
+
+    #[test]
+    fn index() {
+        // 0: 0..10
+        //   1: 0..4
+        //     2: 1..2
+        //     3: 2..4
+        //   4: 6..10
+        let tree = span(0, 10, vec![
+            span(0, 4, vec![span(1, 2, vec![]), span(2, 4, vec![])]),
+            span(6, 10, vec![])
+        ]);
+        let index = TreeIndex::new(&tree);
+        let ids = |v: Vec<u32>| v.into_iter().map(NodeId).collect::<Vec<_>>();
+
+        assert_eq!(index.len(), 5);
+        assert_eq!(index.children(NodeId(0)).collect::<Vec<_>>(), ids(vec![1, 4]));
+        assert_eq!(index.children(NodeId(1)).collect::<Vec<_>>(), ids(vec![2, 3]));
+        assert_eq!(index.children(NodeId(3)).count(), 0);
+        assert_eq!(index.parent(NodeId(3)), Some(NodeId(1)));
+        assert_eq!(index.parent(NodeId(0)), None);
+        assert_eq!(index.ancestors(NodeId(2)).collect::<Vec<_>>(), ids(vec![1, 0]));
+        assert_eq!(index.descendants(NodeId(1)).collect::<Vec<_>>(), ids(vec![2, 3]));
+        assert!(index.is_ancestor(NodeId(1), NodeId(3)));
+        assert!(!index.is_ancestor(NodeId(1), NodeId(4)));
+        assert!(!index.is_ancestor(NodeId(1), NodeId(1)));
+
+        assert_eq!(index.node_at(0), Some(NodeId(1)));
+        assert_eq!(index.node_at(3), Some(NodeId(3)));
+        assert_eq!(index.node_at(5), Some(NodeId(0)));
+        assert_eq!(index.node_at(9), Some(NodeId(4)));
+        assert_eq!(index.node_at(10), None);
+
+        assert_eq!(index.covering(1..4), Some(NodeId(1)));
+        assert_eq!(index.covering(2..3), Some(NodeId(3)));
+        assert_eq!(index.covering(3..7), Some(NodeId(0)));
+        assert_eq!(index.covering(2..2), Some(NodeId(3)));
+        assert_eq!(index.covering(8..11), None);
+
+        assert_eq!(index.expand(2..3), Some(NodeId(3)));
+        assert_eq!(index.expand(2..4), Some(NodeId(1)));
+        assert_eq!(index.expand(0..4), Some(NodeId(0)));
+        assert_eq!(index.expand(0..10), None);
+
+        assert_eq!(index.intersecting(3..7), ids(vec![0, 1, 3, 4]));
+        assert_eq!(index.intersecting(4..6), ids(vec![0]));
+        assert_eq!(index.intersecting(1..1), ids(vec![0, 1, 2]));
+        assert_eq!(index.intersecting(10..12), ids(vec![]));
+    }
+}
@@ -1,16 +1,38 @@
 //! Contains traits for parsing, and the skeleton of the packrat parser algorithm used by the
 //! parsers.
 
+pub mod arena;
 pub mod automata;
-
-use crate::lexer::{SpannedLexeme, Lexer};
+pub mod cache;
+pub mod diff;
+pub mod differential;
+pub mod fold;
+pub mod index;
+pub mod observer;
+pub mod recovery;
+pub mod round_trip;
+pub mod validate;
+mod session;
+mod snapshot;
+mod trace;
+
+pub use session::{ParseSession, ParseStream};
+pub use snapshot::{ParseSnapshot, SavedAutomaton};
+
+use crate::lexer::{SpannedLexeme, Lexer, Lexeme, KindSet};
 use core::any::TypeId as Rule;
 use automata::*;
 use tinyvec::ArrayVec;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use crate::error::{ParceError, ParsePhaseFailure, ParceErrorInfo};
 use std::fmt::Debug;
 use crate::error::ParsePhaseFailure::NothingToParse;
+use crate::report::{RuleInfo, GrammarInfo, Expected, Resolve};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::time::Instant;
+use crate::span::Span;
 
 /// Trait for parsing types that implement [ToString] into types that
 /// implement [Parseable].
@@ -55,6 +77,63 @@ pub trait Parse<O: Parseable>: ToString  {
     /// Parses a rule and requires that it uses all of the input lexemes. Returns an
     /// error if no productions use all of the input.
     fn parse_all(&self) -> Result<O, ParceError>;
+
+    /// Like [Parse::parse_all], but also returns the span of input matched by every rule in the
+    /// result, which can be used to find the rules enclosing an offset.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     A = 'a',
+    ///     B = 'b'
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Outer {
+    ///     Thing = "A #Inner A"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Inner {
+    ///     Thing = "B B"
+    /// }
+    ///
+    /// let (_, tree): (Outer, _) = "abba".parse_tree().unwrap();
+    /// assert_eq!(tree.path_at(2), vec!["Outer", "Inner"]);
+    /// assert_eq!(tree.path_at(0), vec!["Outer"]);
+    /// ```
+    fn parse_tree(&self) -> Result<(O, RuleSpan), ParceError>;
+
+    /// Like [Parse::parse_max], but sorts the result into a [ParseOutcome], so each case can be
+    /// handled in one match.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     A = 'a',
+    ///     B = 'b'
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum MyGrammar {
+    ///     Rule = "A B"
+    /// }
+    ///
+    /// for input in ["ab", "abab", "b"] {
+    ///     match input.parse_outcome() {
+    ///         ParseOutcome::Complete(MyGrammar::Rule) => assert_eq!(input, "ab"),
+    ///         ParseOutcome::Partial { consumed, .. } => assert_eq!(&input[consumed..], "ab"),
+    ///         ParseOutcome::Failed(errors) => assert_eq!(errors[0].start, 0)
+    ///     }
+    /// }
+    /// ```
+    fn parse_outcome(&self) -> ParseOutcome<O> {
+        self.parse_max().into()
+    }
 }
 
 /// Indicates how much of the input was used by a call to [Parse::parse_max].
@@ -63,27 +142,202 @@ pub enum ParseCompletion {
     /// The result used all of the input.
     Complete,
 
-    /// The result used some of the input. The number of lexemes used is stored as the data here.
+    /// The result used some of the input. Holds the byte offset just after the last lexeme used.
     Incomplete(usize)
 }
 
+impl ParseCompletion {
+    /// Whether the result used all of the input.
+    pub fn is_complete(&self) -> bool {
+        matches!(self, ParseCompletion::Complete)
+    }
+}
+
+/// Everything a call to [Parse::parse_max] can end with, in one enum. Returned by
+/// [Parse::parse_outcome] and [ParseSession::parse_outcome].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseOutcome<T> {
+    /// The value used all of the input.
+    Complete(T),
+
+    /// The value only used the start of the input.
+    Partial {
+        /// The value parsed from the start of the input.
+        value: T,
+        /// The byte offset just after the last lexeme the value used.
+        consumed: usize
+    },
+
+    /// Nothing could be parsed. Holds every error found, in the order they were found.
+    Failed(Vec<ParceError>)
+}
+
+impl<T> ParseOutcome<T> {
+    /// Whether the value used all of the input.
+    pub fn is_complete(&self) -> bool {
+        matches!(self, ParseOutcome::Complete(_))
+    }
+
+    /// Whether nothing could be parsed.
+    pub fn is_failed(&self) -> bool {
+        matches!(self, ParseOutcome::Failed(_))
+    }
+
+    /// The parsed value, whether or not it used all of the input.
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            ParseOutcome::Complete(value) | ParseOutcome::Partial { value, .. } => Some(value),
+            ParseOutcome::Failed(_) => None
+        }
+    }
+
+    /// Takes the parsed value, whether or not it used all of the input.
+    pub fn into_value(self) -> Option<T> {
+        match self {
+            ParseOutcome::Complete(value) | ParseOutcome::Partial { value, .. } => Some(value),
+            ParseOutcome::Failed(_) => None
+        }
+    }
+
+    /// Where a partial value ended. `None` for the other outcomes.
+    pub fn consumed(&self) -> Option<usize> {
+        match self {
+            ParseOutcome::Partial { consumed, .. } => Some(*consumed),
+            _ => None
+        }
+    }
+
+    /// The errors of a failed parse. Empty for the other outcomes.
+    pub fn errors(&self) -> &[ParceError] {
+        match self {
+            ParseOutcome::Failed(errors) => errors,
+            _ => &[]
+        }
+    }
+}
+
+impl<T> From<Result<(T, ParseCompletion), ParceError>> for ParseOutcome<T> {
+    fn from(result: Result<(T, ParseCompletion), ParceError>) -> Self {
+        match result {
+            Ok((value, ParseCompletion::Complete)) => ParseOutcome::Complete(value),
+            Ok((value, ParseCompletion::Incomplete(consumed))) => ParseOutcome::Partial { value, consumed },
+            Err(e) => ParseOutcome::Failed(vec![e])
+        }
+    }
+}
+
+/// The span of input matched by one rule in a finished parse, along with the rules matched inside
+/// it. Returned by [Parse::parse_tree].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RuleSpan {
+    /// The name of the rule.
+    pub rule: &'static str,
+    /// The name of the production (variant) that matched.
+    pub production: &'static str,
+    /// How the production breaks ties with other parses of the same input.
+    pub resolve: Resolve,
+    /// The index in the input where the rule starts.
+    pub start: usize,
+    /// The index in the input just after the end of the rule.
+    pub end: usize,
+    /// The rules matched inside this one, in order.
+    pub children: Vec<RuleSpan>,
+    /// The message of the production's `#[deprecated_syntax(...)]` attribute, if it has one.
+    pub deprecated: Option<&'static str>
+}
+
+impl RuleSpan {
+    /// Builds the span of a rule that matched on `route`, covering `consumed` lexemes from
+    /// `lexemes[first]`. A rule that consumed nothing gets an empty span where its next lexeme
+    /// would have started. Used by the generated [Parseable::assemble].
+    pub fn assembled<L: Lexeme>(info: &RuleInfo, route: u32, lexemes: &[SpannedLexeme<L>], first: usize, consumed: usize, text: &str, children: Vec<RuleSpan>) -> RuleSpan {
+        let (start, end) = if consumed == 0 {
+            let at = lexemes.get(first).map(|l| l.start).unwrap_or(text.len());
+            (at, at)
+        } else {
+            (lexemes[first].start, lexemes[first + consumed - 1].end())
+        };
+        let production = &info.productions[route as usize];
+        RuleSpan {
+            rule: info.name,
+            production: production.name,
+            resolve: production.resolve,
+            start,
+            end,
+            children,
+            deprecated: production.deprecated
+        }
+    }
+
+    /// The part of the input the rule covers.
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.end)
+    }
+
+    /// Every rule whose span contains `offset`, from this one (outermost) to the innermost. Empty
+    /// if `offset` is not inside this span.
+    pub fn enclosing(&self, offset: usize) -> Vec<&RuleSpan> {
+        let mut result = vec![];
+        let mut current = Some(self);
+        while let Some(span) = current.filter(|s| s.start <= offset && offset < s.end) {
+            result.push(span);
+            current = span.children.iter().find(|c| c.start <= offset && offset < c.end);
+        }
+        result
+    }
+
+    /// The names of the rules enclosing `offset`, outermost first.
+    pub fn path_at(&self, offset: usize) -> Vec<&'static str> {
+        self.enclosing(offset).into_iter().map(|s| s.rule).collect()
+    }
+}
+
 /// Trait implemented by the [parce_macros::parser] attribute macro.
 ///
 /// Contains the logic needed to drive the automata used in the packrat parser.
+///
+/// Everything a rule needs to parse (its command tables and [RuleInfo]) is immutable static data,
+/// and every parse allocates its own automata, so one grammar can be used from many threads at once.
 pub trait Parseable: 'static + Sized {
     type Lexer: Lexer;
 
+    /// The type produced by [Parseable::assemble], when the input text lives for `'t`. This is just
+    /// `Self` for rules that own their data. Borrowed rules (`#[parser(Lexer, borrowed)]`) implement
+    /// this trait on their `'static` version, and produce the version borrowing from the input.
+    type Output<'t>;
+
     /// The number of variants in this rule.
     const PRODUCTIONS: u32;
 
+    /// Static description of the grammar rooted at this rule, for tools that need the grammar as data.
+    const GRAMMAR: GrammarInfo;
+
+    /// A hash of this rule's declaration and its lexer's [fingerprint](Lexer::FINGERPRINT). The
+    /// rules it uses aren't included; [GrammarInfo::fingerprint] hashes the whole grammar.
+    const FINGERPRINT: u64;
+
     /// Returns the default lexer that will be used to generate the lexemes.
-    fn default_lexer() -> Box<Self::Lexer>;
+    fn default_lexer() -> Self::Lexer;
+
+    /// Returns a static description of this rule, used by [grammar_report](crate::report::grammar_report).
+    fn rule_info() -> &'static RuleInfo;
+
+    /// Whether `lexeme` is one of this rule's own `#[sync(...)]` anchors, where skipping a broken
+    /// [Recovered](recovery::Recovered) field of this rule stops. See [RuleInfo::sync].
+    fn is_sync(lexeme: <Self::Lexer as Lexer>::Lexemes) -> bool {
+        let _ = lexeme;
+        false
+    }
+
+    /// The generated tables of this rule. [Parseable::commands], [Parseable::last_commands], and
+    /// [Parseable::rule_info_of] are all lookups in the table of whichever rule they are asked about.
+    fn table() -> &'static RuleTable<<Self::Lexer as Lexer>::Lexemes>;
 
     /// The state machine used by the [Parse] trait to drive the automata during parsing.
     ///
     /// - `rule`: all commands will come from the [Parseable::commands] function *on the type being parsed*,
-    ///   even if it uses other rules internally. So each implementation checks if rule is it's own type,
-    ///   and delegates to other types if not.
+    ///   even if it uses other rules internally. So `rule` is a [RuleId] in this rule's grammar, and
+    ///   indexes the [RuleTables] reachable from its own table.
     /// - `route`: routes are a generalization of productions. The automata in this algorithm don't have
     ///   branching decision trees, they are a straight line of requirements. Each top-level production
     ///   in a rule are the main routes, indexed 0 through `PRODUCTIONS - 1`. In grammars that could match
@@ -105,6 +359,8 @@ pub trait Parseable: 'static + Sized {
     ///   }
     ///   ```
     ///   The full rule is route 0, just the "B" is route 1, and just the "C" is route 2.
+    ///   Consecutive productions that start with the same lexemes also get one extra route for those
+    ///   lexemes, after all the others. See [SharedPrefix].
     /// - `state`: Each time a requirement for a route is fulfilled, the state of the automaton is incremented.
     ///   For example, in the grammar above:
     ///   - route 0 state 0 looks for A. If found, increments state
@@ -116,12 +372,17 @@ pub trait Parseable: 'static + Sized {
     ///     In cases where multiple child routes can succeed, it is perfectly normal to reactivate multiple
     ///     clones of the original parent.
     ///   - route 0 state 2 looks for A. If found, increments state, declares victory, and the parse is successful.
+    /// - `lexeme`: the lexeme being matched.
+    /// - `text`: the whole input, for soft keywords that need the text of `lexeme` and not just its kind.
     fn commands(
-        rule: Rule,
+        rule: RuleId,
         route: u32,
         state: u32,
-        lexeme: SpannedLexeme<<Self::Lexer as Lexer>::Lexemes>
-    ) -> ArrayVec<[AutomatonCommand; 3]>;
+        lexeme: SpannedLexeme<<Self::Lexer as Lexer>::Lexemes>,
+        text: &str
+    ) -> ArrayVec<[AutomatonCommand; 3]> {
+        (table_of::<Self>(rule).commands)(route, state, lexeme, text)
+    }
 
     /// This is a special case of the [Parseable::commands] function, run at the end of the lexemes if
     /// no rules were matched.
@@ -147,86 +408,717 @@ pub trait Parseable: 'static + Sized {
     /// The input `[A]` should be successfully parsed, but that would usually require the automaton to be
     /// in state 1, where it would be unconditionally successful, and spawn a child automaton to look for B's.
     /// But since there are no more lexemes, the star would not have the chance to be successful without this function.
-    fn last_commands(rule: Rule, route: u32, state: u32) -> bool;
+    fn last_commands(rule: RuleId, route: u32, state: u32) -> bool {
+        (table_of::<Self>(rule).last_commands)(route, state)
+    }
+
+    /// Looks up what an automaton on `rule`, `route`, and `state` can match next, from tables generated
+    /// with the rule. Like [Parseable::commands], this delegates to the other rules this one uses, so it
+    /// works for any automaton paused during a parse. Returns `None` if the route doesn't exist, or
+    /// `rule` isn't an id in this rule's grammar.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// use parce::parser::{Parseable, RuleId};
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexeme {
+    ///     A = 'a',
+    ///     B = 'b',
+    ///     C = 'c'
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum MyGrammar {
+    ///     // State:  0 1----- 2
+    ///     Rule = "A (B | C)? A"
+    /// }
+    ///
+    /// let expected = MyGrammar::expected(RuleId(0), 0, 1).unwrap();
+    /// assert_eq!(expected.lexemes(), vec!["A", "B", "C"]);
+    /// ```
+    fn expected(rule: RuleId, route: u32, state: u32) -> Option<&'static Expected> {
+        Self::rule_info_of(rule)?.expected(route, state)
+    }
+
+    /// Finds the static description of this rule, or any rule it uses, directly or not.
+    fn rule_info_of(rule: RuleId) -> Option<&'static RuleInfo> {
+        find_table::<Self>(rule).map(|table| (table.rule_info)())
+    }
+
+    /// The id of the rule with the [TypeId](core::any::TypeId) `rule` in the grammar rooted at this
+    /// rule, or `None` if it can't be reached from this one. The root is always `RuleId(0)`.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// use parce::parser::{Parseable, RuleId};
+    /// use core::any::TypeId;
+    ///
+    /// # #[lexer(MyLexer)]
+    /// # enum MyLexeme { A = 'a', B = 'b' }
+    /// #[parser(MyLexer)]
+    /// enum Root {
+    ///     Thing = "A #Child"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Child {
+    ///     Thing = "B"
+    /// }
+    ///
+    /// assert_eq!(Root::rule_id(TypeId::of::<Root>()), Some(RuleId(0)));
+    /// assert_eq!(Root::rule_id(TypeId::of::<Child>()), Some(RuleId(1)));
+    /// assert_eq!(Child::rule_id(TypeId::of::<Child>()), Some(RuleId(0)));
+    /// assert_eq!(Child::rule_id(TypeId::of::<Root>()), None);
+    /// ```
+    fn rule_id(rule: Rule) -> Option<RuleId> {
+        (Self::table().reachable)().tables.iter()
+            .position(|table| (table.rule)() == rule)
+            .map(|i| RuleId(i as u16))
+    }
+
+    /// Names the grammar construct an automaton on `rule` and `route` is matching, for traces and
+    /// diagnostics. See [RuleInfo::route_name]. Like [Parseable::rule_info_of], `rule` can be this
+    /// rule or any rule it uses.
+    fn route_name(rule: RuleId, route: u32) -> Option<String> {
+        Self::rule_info_of(rule)?.route_name(route)
+    }
 
     /// The last step of the parsing process. After the parse is successful, [Parseable::assemble] builds the resulting
     /// grammar rule. `auto` is the automaton that was on the main route that was successful, and its
-    /// pointers to its children are used to build the output.
-    fn assemble(auto: Rawtomaton, lexemes: &[SpannedLexeme<<Self::Lexer as Lexer>::Lexemes>], text: &str) -> Result<(usize, Self), ParceError>;
+    /// pointers to its children are used to build the output. The span of the rule is pushed onto `spans`.
+    fn assemble<'t>(auto: Rawtomaton, lexemes: &[SpannedLexeme<<Self::Lexer as Lexer>::Lexemes>], text: &'t str, spans: &mut Vec<RuleSpan>) -> Result<(usize, Self::Output<'t>), ParceError>;
 }
 
-impl<I: ToString, O: Parseable> Parse<O> for I {
-    fn parse_max(&self) -> Result<(O, ParseCompletion), ParceError> {
-        let text = self.to_string();
-        let lexemes = O::default_lexer().lex(&text)?;
-        if lexemes.len() == 0 {
+/// Identifies a rule within one grammar. Rules are numbered densely, in the order they are found
+/// from the grammar's root, which is always `RuleId(0)`. So the same rule can have different ids in
+/// different grammars. [Parseable::rule_id] finds the id of a rule type.
+///
+/// Ids index straight into the grammar's [RuleTables], and unlike a [TypeId](core::any::TypeId),
+/// they are plain numbers that stay the same between runs of the same program.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct RuleId(pub u16);
+
+/// The command tables and description generated for one rule. The generated code for a rule is
+/// just these tables; walking them is left to generic functions like [Parseable::commands].
+///
+/// Each table lists the tables of the rules it spawns directly, so the parser can find the table of
+/// every rule it might need from the root, without any global registration.
+pub struct RuleTable<L: Lexeme> {
+    /// The rule the table belongs to.
+    pub rule: fn() -> Rule,
+    /// The tables of the other rules this one spawns directly.
+    pub uses: &'static [fn() -> &'static RuleTable<L>],
+    /// The tables of every rule reachable from this one, starting with itself. Generated rules
+    /// compute this once, with [RuleTable::collect_reachable].
+    pub reachable: fn() -> &'static RuleTables<L>,
+    /// The state machine of [Parseable::commands], for this rule's routes.
+    #[allow(clippy::type_complexity)]
+    pub commands: fn(u32, u32, SpannedLexeme<L>, &str) -> ArrayVec<[AutomatonCommand; 3]>,
+    /// The state machine of [Parseable::last_commands], for this rule's routes.
+    pub last_commands: fn(u32, u32) -> bool,
+    /// For an `#[inline_rule]`, the rule each production matches (numbered like the rule of
+    /// [AutomatonCommand::Spawn]) and how many productions that rule has. Empty for other rules.
+    pub inline: &'static [(u16, u32)],
+    /// The groups of productions that start with the same lexemes. Each group shares an extra
+    /// route until the lexemes are matched.
+    pub prefixes: &'static [SharedPrefix],
+    /// The extra routes that skip broken input for a [Recovered](recovery::Recovered) field.
+    pub recovery: &'static [u32],
+    /// The productions marked `#[since(...)]` or `#[cfg_grammar(...)]`, and what they need. The
+    /// parser drops their automata when the current [GrammarVersion](crate::version::GrammarVersion)
+    /// doesn't allow them.
+    pub gates: &'static [(u32, crate::version::Gate)],
+    /// The lexemes each route can use in each state, indexed by route. Generated rules build
+    /// these once, on first use.
+    pub guards: fn() -> &'static [RouteGuard],
+    /// Same as [Parseable::rule_info].
+    pub rule_info: fn() -> &'static RuleInfo
+}
+
+/// Consecutive productions of a rule that start with the same lexemes, like `If Expr Block` and
+/// `If Expr Block Else Block`. Instead of an automaton for each production, the parser spawns one on
+/// [route](Self::route), which only matches the shared lexemes. Once they are matched, it is replaced
+/// by an automaton for each production, in the state after them.
+///
+/// The [parser](parce_macros::parser) macro finds these groups itself. Productions with a cut or
+/// `#[no_skip]` are left out.
+#[derive(Debug)]
+pub struct SharedPrefix {
+    /// The extra route that matches the shared lexemes.
+    pub route: u32,
+    /// The productions that start with them.
+    pub productions: Range<u32>,
+    /// How many lexemes are shared, which is also the state the productions start in.
+    pub len: u32
+}
+
+/// The lexemes that each state of one route can use. A state that only checks for certain lexemes
+/// (like `A` in `"A B"`) has a [KindSet] of them, and dies on any other lexeme, so the parser can
+/// test the set instead of running the state. This also lets it skip spawning automata that
+/// would die on the lexeme they are spawned on, which saves the most in rules with many
+/// alternatives.
+///
+/// States that match a character or a class, spawn other automata, or can be skipped have no
+/// set, and accept every lexeme.
+#[derive(Debug, Clone, Default)]
+pub struct RouteGuard {
+    /// Whether the route repeats, so `states` only lists one cycle of states.
+    pub repeats: bool,
+    /// The lexemes each state can use, or `None` if it could do something with any of them.
+    pub states: Vec<Option<KindSet>>
+}
+
+impl RouteGuard {
+    /// Whether a route in `state` could do anything with a lexeme of kind `kind` besides die.
+    pub fn accepts(&self, state: u32, kind: u16) -> bool {
+        let state = match self.repeats {
+            true => state as usize % self.states.len().max(1),
+            false => state as usize
+        };
+        match self.states.get(state) {
+            Some(Some(kinds)) => kinds.contains(kind),
+            _ => true
+        }
+    }
+}
+
+impl<L: Lexeme> RuleTable<L> {
+    /// The route to spawn for `route` when spawning every production of this rule. That is the
+    /// shared route for the first production of a [SharedPrefix], and nothing for the others.
+    pub fn entry_route(&self, route: u32) -> Option<u32> {
+        match self.prefixes.iter().find(|prefix| prefix.productions.contains(&route)) {
+            Some(prefix) if prefix.productions.start == route => Some(prefix.route),
+            Some(_) => None,
+            None => Some(route)
+        }
+    }
+
+    /// The [SharedPrefix] matched by `route`, if it is a shared route.
+    pub fn shared_prefix(&self, route: u32) -> Option<&SharedPrefix> {
+        self.prefixes.iter().find(|prefix| prefix.route == route)
+    }
+
+    /// Walks [RuleTable::uses] from `root`, and numbers every table found, starting with `root`.
+    pub fn collect_reachable(root: &'static RuleTable<L>) -> RuleTables<L> {
+        let mut tables = vec![root];
+        let mut i = 0;
+        while i < tables.len() {
+            for used in tables[i].uses {
+                let used = used();
+                if !tables.iter().any(|t| std::ptr::eq(*t, used)) {
+                    tables.push(used);
+                }
+            }
+            i += 1;
+        }
+        assert!(tables.len() <= u16::MAX as usize + 1, "a grammar can't have more than {} rules", u16::MAX as usize + 1);
+
+        let id = |table: &'static RuleTable<L>| RuleId(tables.iter().position(|t| std::ptr::eq(*t, table)).unwrap() as u16);
+        let links = tables.iter()
+            .map(|table| std::iter::once(id(table)).chain(table.uses.iter().map(|used| id(used()))).collect())
+            .collect();
+        let guards = tables.iter().map(|table| (table.guards)()).collect();
+        RuleTables { tables, links, guards }
+    }
+}
+
+/// The tables of every rule in a grammar, indexed by [RuleId].
+pub struct RuleTables<L: Lexeme> {
+    /// The table of each rule, starting with the root.
+    pub tables: Vec<&'static RuleTable<L>>,
+    /// For each table, its own id, followed by the ids of the rules in its [RuleTable::uses]. This
+    /// turns the rule numbers in [AutomatonCommand::Spawn], which only the generated table knows
+    /// the meaning of, into ids in this grammar.
+    pub links: Vec<Vec<RuleId>>,
+    /// The [guards](RuleTable::guards) of each table.
+    pub guards: Vec<&'static [RouteGuard]>
+}
+
+impl<L: Lexeme> RuleTables<L> {
+    /// Whether an automaton of `rule` on `route` in `state` could use a lexeme of kind `kind`.
+    pub fn accepts(&self, rule: RuleId, route: u32, state: u32, kind: u16) -> bool {
+        self.guards[rule.0 as usize].get(route as usize).is_none_or(|guard| guard.accepts(state, kind))
+    }
+
+    /// Whether `route` of `rule` skips broken input for a [Recovered](recovery::Recovered) field.
+    /// See [RuleTable::recovery].
+    pub fn is_recovery(&self, rule: RuleId, route: u32) -> bool {
+        self.tables[rule.0 as usize].recovery.contains(&route)
+    }
+
+    /// Whether `route` of `rule` is in the current [GrammarVersion](crate::version::GrammarVersion).
+    /// See [RuleTable::gates].
+    pub fn enabled(&self, rule: RuleId, route: u32) -> bool {
+        self.tables[rule.0 as usize].gates.iter().find(|(r, _)| *r == route).is_none_or(|(_, gate)| crate::version::enabled(gate))
+    }
+
+    /// Whether `route` of `rule` is a `#[no_skip]` production.
+    pub fn no_skip(&self, rule: RuleId, route: u32) -> bool {
+        (self.tables[rule.0 as usize].rule_info)().productions.get(route as usize).is_some_and(|p| p.no_skip)
+    }
+
+    /// Whether any rule has [gates](RuleTable::gates).
+    pub fn has_gates(&self) -> bool {
+        self.tables.iter().any(|table| !table.gates.is_empty())
+    }
+}
+
+/// Finds the table of `rule`, if it is in the grammar rooted at `O`.
+fn find_table<O: Parseable>(rule: RuleId) -> Option<&'static RuleTable<<O::Lexer as Lexer>::Lexemes>> {
+    (O::table().reachable)().tables.get(rule.0 as usize).copied()
+}
+
+fn table_of<O: Parseable>(rule: RuleId) -> &'static RuleTable<<O::Lexer as Lexer>::Lexemes> {
+    find_table::<O>(rule).unwrap_or_else(|| panic!("rule number {:?} not found", rule))
+}
+
+/// Names of the rules an automaton is nested in, outermost first. Automata on routes that aren't
+/// productions or [shared prefixes](SharedPrefix) (so groups, repetitions, etc) don't add a name.
+fn rule_stack<O: Parseable>(auto: Rawtomaton) -> Vec<&'static str> {
+    let mut stack = vec![];
+    let mut current = Some(auto);
+    while let Some(auto) = current {
+        if let Some(info) = O::rule_info_of(auto.get().rule) {
+            let shared = find_table::<O>(auto.get().rule).is_some_and(|table| table.shared_prefix(auto.get().route).is_some());
+            if (auto.get().route as usize) < info.productions.len() || shared {
+                stack.push(info.name);
+            }
+        }
+        if let Some(info) = auto.get().inlined.and_then(|(rule, _)| O::rule_info_of(rule)) {
+            stack.push(info.name);
+        }
+        current = auto.get().parent.map(|(parent, _)| parent);
+    }
+    stack.reverse();
+    stack
+}
+
+/// Whether `auto` is inside a `#[no_skip]` production that started before lexeme `i`, so lexeme `i`
+/// can't come after skipped text.
+fn inside_no_skip<O: Parseable>(auto: Rawtomaton, i: usize) -> bool {
+    let mut current = Some(auto);
+    while let Some(auto) = current {
+        if auto.get().lexeme_start < i {
+            let production = O::rule_info_of(auto.get().rule).and_then(|info| info.productions.get(auto.get().route as usize));
+            if production.is_some_and(|p| p.no_skip) {
+                return true;
+            }
+        }
+        current = auto.get().parent.map(|(parent, _)| parent);
+    }
+    false
+}
+
+thread_local! {
+    /// The kinds of the skipped lexemes that the [ParseSession] running on this thread kept in the
+    /// input, because the grammar has `#[no_skip]` productions. Only automata inside those
+    /// productions see them; every other automaton is passed over.
+    static KEPT: RefCell<KindSet> = RefCell::new(KindSet::default());
+}
+
+/// Makes `kept` the kinds of skipped lexemes kept in the current thread's input until the guard is
+/// dropped.
+fn keep(kept: KindSet) -> KeptGuard {
+    KeptGuard(KEPT.with(|current| current.replace(kept)))
+}
+
+/// Restores the previous kinds of kept lexemes when dropped.
+struct KeptGuard(KindSet);
+
+impl Drop for KeptGuard {
+    fn drop(&mut self) {
+        KEPT.with(|current| current.replace(std::mem::take(&mut self.0)));
+    }
+}
+
+/// The index of the first lexeme from `position` on that `auto` was fed. That is `position`,
+/// unless `auto` isn't inside a `#[no_skip]` production and the lexemes there were only kept for
+/// one. Used by the generated [Parseable::assemble].
+#[doc(hidden)]
+pub fn next_seen<L: Lexeme>(auto: Rawtomaton, lexemes: &[SpannedLexeme<L>], mut position: usize) -> usize {
+    if !auto.automaton().no_skip {
+        KEPT.with(|kept| {
+            let kept = kept.borrow();
+            while lexemes.get(position).is_some_and(|l| kept.contains(l.data.kind_id())) {
+                position += 1;
+            }
+        });
+    }
+    position
+}
+
+/// Compares two parses of the same input by the `#[resolve]` preferences of their productions.
+/// Preferred productions are compared innermost (latest starting) first, and the first pair that
+/// start together but end apart decides. `Greater` means `a` is preferred.
+fn preference(a: &RuleSpan, b: &RuleSpan) -> Ordering {
+    fn preferred(span: &RuleSpan, out: &mut Vec<(usize, usize, Resolve)>) {
+        if span.resolve != Resolve::Unspecified {
+            out.push((span.start, span.end, span.resolve));
+        }
+        for child in &span.children {
+            preferred(child, out);
+        }
+    }
+
+    let (mut a_nodes, mut b_nodes) = (vec![], vec![]);
+    preferred(a, &mut a_nodes);
+    preferred(b, &mut b_nodes);
+    a_nodes.sort_by_key(|n| std::cmp::Reverse(n.0));
+    b_nodes.sort_by_key(|n| std::cmp::Reverse(n.0));
+    for (&(a_start, a_end, resolve), &(b_start, b_end, _)) in a_nodes.iter().zip(&b_nodes) {
+        if a_start != b_start {
+            break;
+        }
+        match resolve {
+            Resolve::Longest if a_end != b_end => return a_end.cmp(&b_end),
+            Resolve::Shortest if a_end != b_end => return b_end.cmp(&a_end),
+            _ => {}
+        }
+    }
+    Ordering::Equal
+}
+
+/// The production that won each cut so far, keyed by its rule and the lexeme it started at.
+type Cuts = HashMap<(RuleId, usize), u32>;
+
+/// If `auto` is a production that has passed its cut (`!`), records it as the winner among the
+/// productions of its rule that started where it did, unless another one got there first.
+fn commit<O: Parseable>(auto: Rawtomaton, cuts: &mut Cuts) {
+    let production = O::rule_info_of(auto.get().rule).and_then(|info| info.productions.get(auto.get().route as usize));
+    if production.and_then(|p| p.cut).is_some_and(|cut| auto.get().state >= cut) {
+        cuts.entry((auto.get().rule, auto.get().lexeme_start)).or_insert(auto.get().route);
+    }
+}
+
+/// Whether `auto` is a production that lost a cut to another production of its rule.
+fn lost<O: Parseable>(auto: Rawtomaton, cuts: &Cuts) -> bool {
+    cuts.get(&(auto.get().rule, auto.get().lexeme_start)).is_some_and(|&winner| {
+        winner != auto.get().route
+            && O::rule_info_of(auto.get().rule).is_some_and(|info| (auto.get().route as usize) < info.productions.len())
+    })
+}
+
+/// Whether `auto`, or a rule it is nested in, lost a cut or already matched a rule that did.
+fn cut_off<O: Parseable>(auto: Rawtomaton, cuts: &Cuts) -> bool {
+    let mut current = Some(auto);
+    while let Some(auto) = current {
+        if lost::<O>(auto, cuts) || auto.get().children.iter().any(|child| lost::<O>(*child, cuts)) {
+            return true;
+        }
+        current = auto.get().parent.map(|(parent, _)| parent);
+    }
+    false
+}
+
+/// Whether anything in the tree of matches under `auto` lost a cut.
+fn uses_cut_off<O: Parseable>(auto: Rawtomaton, cuts: &Cuts) -> bool {
+    lost::<O>(auto, cuts) || auto.get().children.iter().any(|child| uses_cut_off::<O>(*child, cuts))
+}
+
+/// A finished parse, along with how much of the input it used and its tree of spans.
+type Spanned<'t, O> = Result<(<O as Parseable>::Output<'t>, ParseCompletion, RuleSpan), ParceError>;
+
+/// Where a parse gives up, set by a [ParseSession].
+#[derive(Debug, Default, Copy, Clone)]
+struct Limits {
+    /// The most bytes the automata can take.
+    memory: Option<usize>,
+    /// When the parse has to be done by.
+    deadline: Option<Instant>,
+    /// How deep productions can be nested in the tree.
+    depth: Option<usize>,
+    /// How many productions can be in the tree.
+    nodes: Option<usize>
+}
+
+/// How many automata are run between checks of the clock, when there is a deadline.
+const DEADLINE_CHECK_INTERVAL: usize = 64;
+
+/// Runs the automata over already-lexed input, calling `progress` with the number of lexemes used
+/// after each one, and stopping if they go past `limits`. The automata are allocated by
+/// `allocator`. Returns the result along with the number of automata allocated, so a
+/// [ParseSession] can size the next arena.
+fn run<'t, O: Parseable>(lexemes: &[SpannedLexeme<<O::Lexer as Lexer>::Lexemes>], text: &'t str, capacity: usize, allocator: &dyn allocator_api2::alloc::Allocator, limits: Limits, progress: &mut dyn FnMut(usize)) -> (Spanned<'t, O>, usize) {
+    if lexemes.is_empty() {
+        return (Err(ParceError {
+            input: text.to_string(),
+            start: 0,
+            info: ParceErrorInfo::parse(NothingToParse)
+        }), 0)
+    }
+
+    let army = Army::with_capacity_in(capacity, allocator);
+    let result = drive::<O>(lexemes, text, &army, limits, progress);
+    (result, army.len())
+}
+
+fn drive<'a, 't, O: Parseable>(lexemes: &[SpannedLexeme<<O::Lexer as Lexer>::Lexemes>], text: &'t str, army: &'a Army<'a, '_>, limits: Limits, progress: &mut dyn FnMut(usize)) -> Spanned<'t, O> {
+    let mut engine = Engine::start::<O>(army);
+    engine.advance::<O>(lexemes, text, army, limits, progress)?;
+    engine.finish::<O>(lexemes, text, army, limits)
+}
+
+/// Checks the tree of matches under the victorious `auto` against the depth and node `limits`,
+/// before it is assembled. Only the automata on a production's main route are counted, not the
+/// ones for the groups inside it. The tree is walked without recursion, so a tree too deep to
+/// assemble is turned away safely.
+fn check_size<O: Parseable>(auto: Rawtomaton, lexemes: &[SpannedLexeme<<O::Lexer as Lexer>::Lexemes>], text: &str, limits: Limits) -> Result<(), ParceError> {
+    if limits.depth.is_none() && limits.nodes.is_none() {
+        return Ok(());
+    }
+    let grammar = (O::table().reachable)();
+    let mut pending = vec![(auto, 0)];
+    let mut nodes = 0;
+    while let Some((auto, depth)) = pending.pop() {
+        let a = auto.get();
+        let production = (a.route as usize) < ((grammar.tables[a.rule.0 as usize].rule_info)()).productions.len();
+        let depth = depth + production as usize;
+        nodes += production as usize;
+        let failure = if limits.depth.is_some_and(|limit| depth > limit) {
+            Some(ParsePhaseFailure::DepthLimit)
+        } else if limits.nodes.is_some_and(|limit| nodes > limit) {
+            Some(ParsePhaseFailure::NodeLimit)
+        } else {
+            None
+        };
+        if let Some(failure) = failure {
             return Err(ParceError {
-                input: text,
-                start: 0,
-                info: ParceErrorInfo::Parse { failure: NothingToParse }
-            })
+                input: text.to_string(),
+                start: lexemes.get(a.lexeme_start).map_or(text.len(), |l| l.start),
+                info: ParceErrorInfo::parse(failure)
+            });
         }
+        pending.extend(a.children.iter().rev().map(|child| (*child, depth)));
+    }
+    Ok(())
+}
 
-        let army: Army = Army::new();
-        let mut alive: VecDeque<Rawtomaton> = VecDeque::new();
+/// The automata of a parse that has used the first `position` lexemes of its input. A parse can
+/// stop here and be picked up again with more lexemes, see [ParseSnapshot].
+struct Engine<'a> {
+    /// The automata waiting for the next lexeme.
+    alive: VecDeque<Rawtomaton<'a>>,
+    /// The automata that were alive before the last lexeme, for errors when none are left.
+    frontier: Vec<Rawtomaton<'a>>,
+    /// Victories, and the step each happened on. Only the ones from the latest step count, and
+    /// more than one means the input is ambiguous. A cut can rule out a victory after the fact, so
+    /// with cuts in the grammar, the earlier ones are kept in case the later ones are.
+    victories: Vec<(usize, Rawtomaton<'a>)>,
+    cuts: Cuts,
+    position: usize
+}
 
-        for i in 0..O::PRODUCTIONS {
-            alive.push_back(army.spawn(Rule::of::<O>(), i, 0).into());
+impl<'a> Engine<'a> {
+    /// Spawns an automaton for each production of the root rule.
+    fn start<O: Parseable>(army: &'a Army<'a, '_>) -> Engine<'a> {
+        Engine {
+            alive: (0..O::PRODUCTIONS)
+                .filter_map(|i| O::table().entry_route(i))
+                .filter(|&i| (O::table().reachable)().enabled(RuleId(0), i))
+                .map(|i| {
+                    let auto = army.spawn(RuleId(0), i, 0);
+                    auto.get_mut().no_skip = (O::table().reachable)().no_skip(RuleId(0), i);
+                    auto
+                })
+                .collect(),
+            frontier: vec![],
+            victories: vec![],
+            cuts: Cuts::new(),
+            position: 0
         }
+    }
 
-        let mut last = None;
+    fn victory(&mut self, has_cuts: bool, step: usize, vic: Rawtomaton<'a>) {
+        if !has_cuts && self.victories.last().is_some_and(|(last, _)| *last != step) {
+            self.victories.clear();
+        }
+        self.victories.push((step, vic));
+    }
 
-        let mut i = 0;
-        while !alive.is_empty() && i < lexemes.len() {
+    /// Feeds the automata every lexeme from `position` on, or until they all die. Fails if the
+    /// automata in `army` take more memory than `limits` allow, or its deadline passes.
+    fn advance<O: Parseable>(&mut self, lexemes: &[SpannedLexeme<<O::Lexer as Lexer>::Lexemes>], text: &str, army: &'a Army<'a, '_>, limits: Limits, progress: &mut dyn FnMut(usize)) -> Result<(), ParceError> {
+        let grammar = (O::table().reachable)();
+        let mut steps = 0;
+        let no_skip = has_no_skip::<O>();
+        let kept = KEPT.with(|kept| kept.borrow().clone());
+        let has_cuts = has_cuts::<O>();
+        let has_recovery = recovery::has_recovery::<O>();
+        let has_gates = grammar.has_gates();
+
+        while !self.alive.is_empty() && self.position < lexemes.len() {
+            let i = self.position;
+            if has_recovery {
+                recovery::prune(&mut self.alive, grammar, has_cuts);
+            }
+            self.frontier.clear();
+            self.frontier.extend(self.alive.iter().copied());
+            let gap = no_skip && i > 0 && lexemes[i-1].end() != lexemes[i].start;
+            let kind = lexemes[i].data.kind_id();
             let mut j = 0;
-            while j < alive.len() {
-                let auto = alive[j];
-                unsafe {
-                    let commands = O::commands((**auto).rule, (**auto).route, (**auto).state, lexemes[i]);
-                    let result = army.command(auto, commands, i);
-                    alive.extend(result.new_spawns);
-                    j += result.reactivated.len();
-                    for old in result.reactivated {
-                        alive.push_front(old);
+            while j < self.alive.len() {
+                let auto = self.alive[j];
+                // Skipped lexemes kept for `#[no_skip]` productions pass everything else by. Automata
+                // skipping broken input take them too, so what they skip is in one piece.
+                if kept.contains(kind) && !auto.get().no_skip && !grammar.is_recovery(auto.get().rule, auto.get().route) {
+                    j += 1;
+                    continue;
+                }
+                if let Some(forks) = army.fork(auto, Some(kind), grammar) {
+                    self.alive.remove(j);
+                    for (k, fork) in forks.into_iter().enumerate() {
+                        self.alive.insert(j + k, fork);
                     }
-                    if let Some(vic) = result.victorious {
-                        last = Some(vic);
+                    continue;
+                }
+                if has_cuts {
+                    commit::<O>(auto, &mut self.cuts);
+                    if cut_off::<O>(auto, &self.cuts) {
+                        self.alive.remove(j);
+                        continue;
                     }
-                    if result.remove {
-                        alive.remove(j);
-                    } else if !result.fallthrough {
-                        j += 1;
+                }
+                if !grammar.accepts(auto.get().rule, auto.get().route, auto.get().state, kind)
+                    || (has_gates && !grammar.enabled(auto.get().rule, auto.get().route)) {
+                    self.alive.remove(j);
+                    continue;
+                }
+                let mut commands = O::commands(auto.get().rule, auto.get().route, auto.get().state, lexemes[i], text);
+                // Anything spawned here is checked when it gets its own commands, so only automata
+                // using the lexeme directly need to be stopped.
+                if gap && !commands.iter().any(|c| matches!(c, AutomatonCommand::Spawn {..})) && inside_no_skip::<O>(auto, i) {
+                    commands = tinyvec::array_vec!([AutomatonCommand; 3] => AutomatonCommand::Die);
+                }
+                let result = army.command(auto, commands, i, Some(kind), grammar);
+                self.alive.extend(result.new_spawns);
+                if result.early_victory {
+                    // The victory skipped past a `?` or `*` without using this lexeme, so the
+                    // parents it woke up get to see this lexeme too.
+                    self.alive.extend(result.reactivated);
+                } else {
+                    j += result.reactivated.len();
+                    for old in result.reactivated {
+                        self.alive.push_front(old);
                     }
                 }
+                if let Some(vic) = result.victorious {
+                    self.victory(has_cuts, i, vic);
+                }
+                if result.remove {
+                    self.alive.remove(j);
+                } else if !result.fallthrough {
+                    j += 1;
+                }
+                let failure = if limits.memory.is_some_and(|limit| army.bytes() > limit) {
+                    Some(ParsePhaseFailure::ResourceExhausted)
+                } else if limits.deadline.is_some_and(|deadline| steps % DEADLINE_CHECK_INTERVAL == 0 && Instant::now() >= deadline) {
+                    Some(ParsePhaseFailure::Timeout)
+                } else {
+                    None
+                };
+                if let Some(failure) = failure {
+                    return Err(ParceError {
+                        input: text.to_string(),
+                        start: lexemes[i].start,
+                        info: ParceErrorInfo::parse(failure)
+                    });
+                }
+                steps += 1;
             }
-            i += 1;
+            self.position += 1;
+            progress(self.position);
         }
+        Ok(())
+    }
+
+    /// Ends the input after `position` lexemes, and assembles the result.
+    fn finish<'t, O: Parseable>(mut self, lexemes: &[SpannedLexeme<<O::Lexer as Lexer>::Lexemes>], text: &'t str, army: &'a Army<'a, '_>, limits: Limits) -> Spanned<'t, O> {
+        let has_cuts = has_cuts::<O>();
+        let has_gates = (O::table().reachable)().has_gates();
+        let i = self.position;
 
         if i == lexemes.len() {
-            for auto in &alive {
-                unsafe {
-                    if O::last_commands((***auto).rule, (***auto).route, (***auto).state) {
-                        let result = army.command(*auto, tinyvec::array_vec!([AutomatonCommand; 3] => automata::AutomatonCommand::Victory), 0);
-                        if let Some(vic) = result.victorious {
-                            dbg!("hellO");
-                            last = Some(vic);
-                        }
+            // Parents woken up by a victory here might be able to finish without more input too.
+            let mut pending: VecDeque<Rawtomaton> = self.alive.iter().copied().collect();
+            while let Some(auto) = pending.pop_front() {
+                if let Some(forks) = army.fork(auto, None, (O::table().reachable)()) {
+                    for fork in forks.into_iter().rev() {
+                        pending.push_front(fork);
+                    }
+                    continue;
+                }
+                if has_gates && !(O::table().reachable)().enabled(auto.get().rule, auto.get().route) {
+                    continue;
+                }
+                if has_cuts {
+                    commit::<O>(auto, &mut self.cuts);
+                    if cut_off::<O>(auto, &self.cuts) {
+                        continue;
+                    }
+                }
+                if O::last_commands(auto.get().rule, auto.get().route, auto.get().state) {
+                    let result = army.command(auto, tinyvec::array_vec!([AutomatonCommand; 3] => automata::AutomatonCommand::Victory), 0, None, (O::table().reachable)());
+                    pending.extend(result.reactivated);
+                    if let Some(vic) = result.victorious {
+                        self.victory(has_cuts, lexemes.len() - 1, vic);
                     }
                 }
             }
         }
 
-        if let Some(l) = last {
-            let (consumed, result) = O::assemble(l, lexemes.as_slice(), &text)?;
-            let completion = if consumed == lexemes.len() {
+        let Engine { alive, frontier, mut victories, cuts, .. } = self;
+        if has_cuts {
+            victories.retain(|(_, vic)| !uses_cut_off::<O>(*vic, &cuts));
+        }
+        let last_step = victories.last().map(|(step, _)| *step);
+        let tied: Vec<Rawtomaton> = victories.iter().filter(|(step, _)| Some(*step) == last_step).map(|(_, vic)| *vic).collect();
+
+        if let Some(&last) = tied.last() {
+            check_size::<O>(last, lexemes, text, limits)?;
+            let mut spans = vec![];
+            let (mut consumed, mut result) = O::assemble(last, lexemes, text, &mut spans)?;
+            let mut span = spans.pop().expect("assembling a rule always pushes its span");
+            // The parse with the fewest recovered errors wins. Between those, the last victory wins,
+            // unless productions marked with #[resolve] override that.
+            let mut errors = recovery::errors_in(&span);
+            for other in tied.iter().rev().skip(1).filter(|other| check_size::<O>(**other, lexemes, text, limits).is_ok()) {
+                if let Ok((other_consumed, other_result)) = O::assemble(*other, lexemes, text, &mut spans) {
+                    let other_span = spans.pop().expect("assembling a rule always pushes its span");
+                    let other_errors = recovery::errors_in(&other_span);
+                    let better = match other_errors.cmp(&errors) {
+                        Ordering::Equal => preference(&other_span, &span) == Ordering::Greater,
+                        fewer => fewer == Ordering::Less
+                    };
+                    if other_consumed == consumed && better {
+                        errors = other_errors;
+                        consumed = other_consumed;
+                        result = other_result;
+                        span = other_span;
+                    }
+                }
+            }
+            let kept = KEPT.with(|kept| kept.borrow().clone());
+            let completion = if lexemes[consumed..].iter().all(|l| kept.contains(l.data.kind_id())) {
                 ParseCompletion::Complete
+            } else if consumed == 0 {
+                ParseCompletion::Incomplete(lexemes[0].start)
             } else {
-                ParseCompletion::Incomplete(lexemes[consumed-1].start + lexemes[consumed-1].len)
+                ParseCompletion::Incomplete(lexemes[consumed-1].end())
             };
-            Ok((result, completion))
+            Ok((result, completion, span))
         } else {
+            // The deepest automaton still alive when parsing stopped is the best guess at where it went wrong.
+            let mut stuck: Vec<Rawtomaton> = if alive.is_empty() { frontier } else { alive.iter().copied().collect() };
+            // Alternatives that lost a cut aren't what the input was meant to be.
+            if stuck.iter().any(|auto| !cut_off::<O>(*auto, &cuts)) {
+                stuck.retain(|auto| !cut_off::<O>(*auto, &cuts));
+            }
+            let stack = stuck.iter()
+                .map(|auto| rule_stack::<O>(*auto))
+                .fold(vec![], |deepest, stack| if stack.len() > deepest.len() { stack } else { deepest });
             Err(ParceError {
-                start: if alive.len() == 0 {
+                start: if alive.is_empty() {
                     if i > 1 {
                         lexemes[i-1].start
                     } else {
@@ -237,32 +1129,43 @@ impl<I: ToString, O: Parseable> Parse<O> for I {
                 } else {
                     0
                 },
-                input: text,
-                info: ParceErrorInfo::parse(
-                    if alive.len() == 0 {
+                input: text.to_string(),
+                info: ParceErrorInfo::Parse {
+                    failure: if alive.is_empty() {
                         ParsePhaseFailure::NoMatches
                     } else {
                         ParsePhaseFailure::InputEndedTooSoon
-                    }
-                )
+                    },
+                    stack
+                }
             })
         }
     }
+}
 
-    fn parse_all(&self) -> Result<O, ParceError> {
-        let (result, completion) = self.parse_max()?;
-        match completion {
-            ParseCompletion::Complete => Ok(result),
-            ParseCompletion::Incomplete(n) => Err(ParceError {
-                input: self.to_string(),
-                start: n+1,
-                info: ParceErrorInfo::parse(ParsePhaseFailure::LeftoverLexemes)
-            })
-        }
-    }
+fn has_cuts<O: Parseable>() -> bool {
+    O::GRAMMAR.rules().iter().any(|rule| rule.productions.iter().any(|p| p.cut.is_some()))
 }
 
-#[cfg(test)]
+fn has_no_skip<O: Parseable>() -> bool {
+    O::GRAMMAR.rules().iter().any(|rule| rule.productions.iter().any(|p| p.no_skip))
+}
+
+impl<I: ToString, O> Parse<O> for I where O: for<'t> Parseable<Output<'t> = O> {
+    fn parse_max(&self) -> Result<(O, ParseCompletion), ParceError> {
+        ParseSession::new(O::default_lexer()).parse_max::<O>(&self.to_string())
+    }
+
+    fn parse_all(&self) -> Result<O, ParceError> {
+        ParseSession::new(O::default_lexer()).parse_all::<O>(&self.to_string())
+    }
+
+    fn parse_tree(&self) -> Result<(O, RuleSpan), ParceError> {
+        ParseSession::new(O::default_lexer()).parse_tree::<O>(&self.to_string())
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use crate as parce;
     use parce::prelude::*;
@@ -283,9 +1186,16 @@ mod tests {
         }
     }
 
+    /// Checks the failure and where it happened. Rule stacks are checked separately, in the `stack` test.
     macro_rules! fail {
         ($str:literal $grammar:ident $where:literal $error:ident) => {
-            assert_eq!($str.parse() as Result<$grammar,_>, parser_error!($str $where $error))
+            let result = ($str.parse() as Result<$grammar, parce::error::ParceError>).map_err(|mut e| {
+                if let parce::error::ParceErrorInfo::Parse { stack, .. } = &mut e.info {
+                    stack.clear();
+                }
+                e
+            });
+            assert_eq!(result, parser_error!($str $where $error))
         }
     }
 
@@ -332,6 +1242,20 @@ mod tests {
         fail!("a bbc a" OrGrammar 3 NoMatches);
     }
 
+    ////// CLASSES
+
+    #[parser(MyLexer)]
+    enum ClassGrammar {
+        Class = "[0-4] Period [^0-4]"
+    }
+
+    #[test]
+    fn class() {
+        pass!("3.7" ClassGrammar::Class);
+        fail!("3.2" ClassGrammar 2 NoMatches);
+        fail!("5.7" ClassGrammar 0 NoMatches);
+    }
+
     ////// STAR, PARSE COMPLETION
 
     #[parser(MyLexer)]
@@ -343,6 +1267,12 @@ mod tests {
     fn star() {
         pass!("abc abc" StarGrammar::Star);
         assert_eq!("abc a".parse_max(), Ok((StarGrammar::Star, ParseCompletion::Incomplete(3))));
+        assert_eq!("abc a".parse_outcome(), ParseOutcome::Partial { value: StarGrammar::Star, consumed: 3 });
+        assert_eq!("abc".parse_outcome().into_value(), Some(StarGrammar::Star));
+        assert_eq!("abd".parse_outcome(), ParseOutcome::Partial { value: StarGrammar::Star, consumed: 0 });
+        let outcome: ParseOutcome<BasicGrammar> = "abd".parse_outcome();
+        assert!(outcome.is_failed());
+        assert_eq!((outcome.value(), outcome.errors()[0].start), (None, 2));
         fail!("abc a" StarGrammar 4 LeftoverLexemes);
     }
 
@@ -403,6 +1333,38 @@ mod tests {
         pass!("a abca a" DelegateGrammar::Start);
     }
 
+    #[test]
+    fn rule_tables() {
+        use parce::internal_prelude::{Parseable, Rule, RuleId};
+
+        let reachable = (DelegateGrammar::table().reachable)();
+        assert_eq!(reachable.tables.len(), 2);
+        assert!(std::ptr::eq(reachable.tables[0], DelegateGrammar::table()));
+        assert!(std::ptr::eq(reachable.tables[1], OrGrammar::table()));
+        assert_eq!(reachable.links, vec![vec![RuleId(0), RuleId(1)], vec![RuleId(1)]]);
+
+        let or = DelegateGrammar::rule_id(Rule::of::<OrGrammar>()).unwrap();
+        assert_eq!(or, RuleId(1));
+        assert_eq!(DelegateGrammar::rule_info_of(or).unwrap().name, "OrGrammar");
+        assert!(OrGrammar::rule_info_of(RuleId(1)).is_none());
+        assert!(OrGrammar::rule_id(Rule::of::<DelegateGrammar>()).is_none());
+
+        let names = |info: &parce::report::RuleInfo| (0..info.routes.len() as u32).map(|r| info.route_name(r).unwrap()).collect::<Vec<_>>();
+        assert_eq!(DelegateGrammar::route_name(or, 2).unwrap(), "\"B C\" in OrGrammar::Or");
+        assert_eq!(names(OperatorGrammar::rule_info())[3..], [
+            "OperatorGrammar::InfiniteRange",
+            "OperatorGrammar::LimitedRange",
+            "\"B C\" in OperatorGrammar::Plus",
+            "\"A C\" in OperatorGrammar::Question",
+            "\"B A\" in OperatorGrammar::FixedRange",
+            "\"(A B){2,}\" in OperatorGrammar::InfiniteRange",
+            "\"(A B){2,}\" in OperatorGrammar::InfiniteRange",
+            "\"(A B){2,4}\" in OperatorGrammar::LimitedRange",
+            "\"(A B){2,4}\" in OperatorGrammar::LimitedRange"
+        ]);
+        assert_eq!(names(AssignGrammar::rule_info())[7..], ["\"Digit\" in AssignGrammar::Number", "\"maybe=Bool\" in AssignGrammar::Bool"]);
+    }
+
     ////// DOT & GREEDINESS
 
     #[parser(MyLexer)]
@@ -488,6 +1450,106 @@ mod tests {
         pass!("c abc ab abcccc" NestingGrammar::Star);
     }
 
+    ////// AMBIGUOUS CHILDREN
+
+    #[parser(MyLexer)]
+    enum PrefixGrammar {
+        Other = "D",
+        Short = "A",
+        Long = "A B"
+    }
+
+    #[parser(MyLexer)]
+    enum PrefixParentGrammar {
+        Thing(PrefixGrammar) = "0 C"
+    }
+
+    #[test]
+    fn prefix_child() {
+        pass!("a c" PrefixParentGrammar::Thing(PrefixGrammar::Short));
+        pass!("a b c" PrefixParentGrammar::Thing(PrefixGrammar::Long));
+    }
+
+    #[test]
+    fn past_last_state() {
+        use parce::parser::{Parseable, RuleId, automata::AutomatonCommand};
+        use parce::lexer::SpannedLexeme;
+
+        // "A B C" only has states 0 through 2.
+        let rule = RuleId(0);
+        let lexeme = SpannedLexeme { data: MyLexeme::A, start: 0, len: 1 };
+        assert_eq!(BasicGrammar::commands(rule, 0, 3, lexeme, "a").as_slice(), &[AutomatonCommand::Die]);
+        assert!(!BasicGrammar::last_commands(rule, 0, 3));
+    }
+
+    #[parser(MyLexer)]
+    enum OptionalTailGrammar {
+        Thing = "A B?"
+    }
+
+    #[parser(MyLexer)]
+    enum RepeatedTail {
+        B = "B"
+    }
+
+    #[parser(MyLexer)]
+    enum RepeatedTailGrammar {
+        Thing(Vec<RepeatedTail>) = "A 0*"
+    }
+
+    #[parser(MyLexer)]
+    enum OptionalTailParentGrammar {
+        Optional(OptionalTailGrammar) = "0 C",
+        Repeated(RepeatedTailGrammar) = "0 E"
+    }
+
+    #[test]
+    fn optional_tail_child() {
+        pass!("a c" OptionalTailParentGrammar::Optional(OptionalTailGrammar::Thing));
+        pass!("a b c" OptionalTailParentGrammar::Optional(OptionalTailGrammar::Thing));
+        pass!("a e" OptionalTailParentGrammar::Repeated(RepeatedTailGrammar::Thing(vec![])));
+        pass!("a b b e" OptionalTailParentGrammar::Repeated(RepeatedTailGrammar::Thing(vec![RepeatedTail::B, RepeatedTail::B])));
+    }
+
+    #[parser(MyLexer)]
+    enum RepeatedPair {
+        D = "D"
+    }
+
+    #[parser(MyLexer)]
+    enum RepeatedPairGrammar {
+        Thing(Vec<RepeatedPair>) = "B (C 0)*"
+    }
+
+    #[parser(MyLexer)]
+    enum OffsetParentGrammar {
+        Thing(RepeatedPairGrammar) = "A 0"
+    }
+
+    #[test]
+    fn offset_child() {
+        pass!("a b" OffsetParentGrammar::Thing(RepeatedPairGrammar::Thing(vec![])));
+        pass!("a b c d c d" OffsetParentGrammar::Thing(RepeatedPairGrammar::Thing(vec![RepeatedPair::D, RepeatedPair::D])));
+    }
+
+    #[parser(MyLexer)]
+    enum TrailingGrammar {
+        Thing = "B C?"
+    }
+
+    #[parser(MyLexer)]
+    enum TrailingParentGrammar {
+        Thing(TrailingGrammar) = "A 0 D?"
+    }
+
+    #[test]
+    fn trailing_child() {
+        pass!("a b" TrailingParentGrammar::Thing(TrailingGrammar::Thing));
+        pass!("a b c" TrailingParentGrammar::Thing(TrailingGrammar::Thing));
+        pass!("a b d" TrailingParentGrammar::Thing(TrailingGrammar::Thing));
+        pass!("a b c d" TrailingParentGrammar::Thing(TrailingGrammar::Thing));
+    }
+
     ////// BARE UNNAMED FIELDS
 
     #[parser(MyLexer)]
@@ -570,7 +1632,8 @@ mod tests {
         String(String) = "A 0=(B C D)",
         Pass(String, BasicGrammar) = "B 0=1",
         Number(f32, u64) = "C 0=(Digit+ Period Digit+) A 1=Digit+",
-        Bool {maybe: Vec<bool>} = "D (maybe=Bool)+"
+        Bool {maybe: Vec<bool>} = "D (maybe=Bool)+",
+        Named {text: String} = "E text=(F G)"
     }
 
     #[test]
@@ -583,5 +1646,854 @@ mod tests {
 
         pass!("d false" AssignGrammar::Bool {maybe: vec![false]});
         pass!("d true false false" AssignGrammar::Bool {maybe: vec![true, false, false]});
+
+        pass!("e f g" AssignGrammar::Named {text: "f g".to_string()});
+    }
+
+    ////// BORROWED FIELDS
+
+    #[parser(MyLexer, borrowed)]
+    enum BorrowedGrammar<'a> {
+        Word(&'a str) = "A 0=(B C D)",
+        Many {words: Vec<&'a str>, inner: Option<Box<BorrowedGrammar<'a>>>} = "B (E words=Bool)+ inner?",
+        Number(u64, &'a str) = "C 0=Digit+ 1=(Period Digit)"
+    }
+
+    #[test]
+    fn borrowed_grammar() {
+        let text = "a b cd".to_string();
+        let mut session = ParseSession::new(MyLexer::default());
+        assert_eq!(session.parse_all::<BorrowedGrammar>(&text), Ok(BorrowedGrammar::Word("b cd")));
+        assert_eq!(
+            session.parse_all::<BorrowedGrammar>("b etrue efalse b etrue"),
+            Ok(BorrowedGrammar::Many {
+                words: vec!["true", "false"],
+                inner: Some(Box::new(BorrowedGrammar::Many { words: vec!["true"], inner: None }))
+            })
+        );
+        assert_eq!(
+            session.parse_all::<BorrowedGrammar>("b etrue b efalse"),
+            Ok(BorrowedGrammar::Many {
+                words: vec!["true"],
+                inner: Some(Box::new(BorrowedGrammar::Many { words: vec!["false"], inner: None }))
+            })
+        );
+        assert_eq!(session.parse_all::<BorrowedGrammar>("c 12 .3"), Ok(BorrowedGrammar::Number(12, ".3")));
+        assert!(session.parse_all::<BorrowedGrammar>("a b").is_err());
+    }
+
+    ////// SOFT KEYWORDS
+
+    #[parser(MyLexer)]
+    enum SoftKeywordGrammar {
+        Yes = "Bool('true') A",
+        No = "Bool(\"false\") A",
+        Other = "Bool B",
+        Only = "C Bool('true')"
+    }
+
+    #[test]
+    fn soft_keywords() {
+        pass!("true a" SoftKeywordGrammar::Yes);
+        pass!("false a" SoftKeywordGrammar::No);
+        pass!("true b" SoftKeywordGrammar::Other);
+        pass!("c true" SoftKeywordGrammar::Only);
+        fail!("c false" SoftKeywordGrammar 2 NoMatches);
+    }
+
+    #[lexer(WordLexer)]
+    enum WordLexeme {
+        Ident = "[^ ]+",
+        #[skip] Space = ' '
+    }
+
+    #[parser(WordLexer)]
+    enum NonAsciiKeyword {
+        Order = "Ident('café') Ident",
+        Single = "Ident"
+    }
+
+    #[test]
+    fn non_ascii_soft_keywords() {
+        let mut session = ParseSession::new(WordLexer::default());
+        assert_eq!(session.parse_all::<NonAsciiKeyword>("café noir"), Ok(NonAsciiKeyword::Order));
+        assert_eq!(session.parse_all::<NonAsciiKeyword>("café"), Ok(NonAsciiKeyword::Single));
+        assert!(session.parse_all::<NonAsciiKeyword>("thé noir").is_err());
+    }
+
+    ////// NO SKIP
+
+    #[parser(MyLexer)]
+    enum NoSkipGrammar {
+        #[no_skip]
+        Tight = "A B C",
+        Loose = "A B D"
+    }
+
+    #[parser(MyLexer)]
+    #[no_skip]
+    enum NoSkipNested {
+        Nested(BasicGrammar) = "D 0"
+    }
+
+    #[parser(MyLexer)]
+    #[no_skip]
+    enum NoSkipOptional {
+        Thing = "E F?"
+    }
+
+    #[parser(MyLexer)]
+    enum NoSkipOuter {
+        Pair(NoSkipOptional) = "0 G"
+    }
+
+    #[test]
+    fn no_skip() {
+        pass!("abc" NoSkipGrammar::Tight);
+        pass!("a b d" NoSkipGrammar::Loose);
+        fail!("a bc" NoSkipGrammar 3 NoMatches);
+
+        pass!("dabc" NoSkipNested::Nested(BasicGrammar::Thing));
+        fail!("d abc" NoSkipNested 1 NoMatches);
+        fail!("dab c" NoSkipNested 3 NoMatches);
+
+        pass!("ef g" NoSkipOuter::Pair(NoSkipOptional::Thing));
+        pass!("e g" NoSkipOuter::Pair(NoSkipOptional::Thing));
+        fail!("e f g" NoSkipOuter 2 NoMatches);
+    }
+
+    #[lexer(LineLexer)]
+    enum LineLexeme {
+        Ident = "[a-z]+",
+        Number = "[0-9]+",
+        Eq = '=',
+        #[skip] Space = ' ',
+        #[skip] Newline = '\n'
+    }
+
+    #[parser(LineLexer)]
+    enum Program {
+        Program(Vec<Statement>) = "0*"
+    }
+
+    #[parser(LineLexer)]
+    #[no_skip]
+    enum Statement {
+        Assign { name: String, value: u32 } = "name=Ident Space* Eq Space* value=Number Space* Newline"
+    }
+
+    #[test]
+    fn significant_newlines() {
+        let assign = |name: &str, value| Statement::Assign { name: name.to_string(), value };
+        let program = || Program::Program(vec![assign("x", 1), assign("yz", 23)]);
+        assert_eq!("x = 1\nyz=23  \n".parse(), Ok(program()));
+        assert_eq!("\n  x = 1\n\n yz=23\n\n".parse(), Ok(program()));
+        assert!(("x = 1".parse() as Result<Program, _>).is_err());
+        assert!(("x = 1 yz = 23\n".parse() as Result<Program, _>).is_err());
+        assert!(("x\n= 1\n".parse() as Result<Program, _>).is_err());
+
+        let (_, tree): (Program, _) = "  x = 1\n".parse_tree().unwrap();
+        assert_eq!((tree.start, tree.children[0].start, tree.children[0].end), (2, 2, 8));
+
+        let mut session = ParseSession::new(LineLexer::default());
+        let mut snapshot = super::ParseSnapshot::<Program>::new();
+        session.feed(&mut snapshot, "x").unwrap();
+        session.feed(&mut snapshot, " = 1\nyz=23\n").unwrap();
+        assert_eq!(session.finish(&snapshot), Ok((program(), ParseCompletion::Complete)));
+    }
+
+    ////// AMBIGUITY
+
+    #[parser(MyLexer)]
+    enum Shift {
+        #[prefer_shift]
+        If {then: Box<Shift>, otherwise: Option<Box<Shift>>} = "A then (B otherwise)?",
+        Leaf = "C"
+    }
+
+    #[parser(MyLexer)]
+    enum Reduce {
+        #[resolve(shortest)]
+        If {then: Box<Reduce>, otherwise: Option<Box<Reduce>>} = "A then (B otherwise)?",
+        Leaf = "C"
+    }
+
+    #[test]
+    fn dangling_else() {
+        pass!("a c" Shift::If { then: Box::new(Shift::Leaf), otherwise: None });
+        pass!("a c b c" Shift::If { then: Box::new(Shift::Leaf), otherwise: Some(Box::new(Shift::Leaf)) });
+        pass!("a a c" Shift::If { then: Box::new(Shift::If { then: Box::new(Shift::Leaf), otherwise: None }), otherwise: None });
+        pass!("a a c b c" Shift::If {
+            then: Box::new(Shift::If { then: Box::new(Shift::Leaf), otherwise: Some(Box::new(Shift::Leaf)) }),
+            otherwise: None
+        });
+        pass!("a a c b c" Reduce::If {
+            then: Box::new(Reduce::If { then: Box::new(Reduce::Leaf), otherwise: None }),
+            otherwise: Some(Box::new(Reduce::Leaf))
+        });
+        pass!("a c b a c b c" Shift::If {
+            then: Box::new(Shift::Leaf),
+            otherwise: Some(Box::new(Shift::If { then: Box::new(Shift::Leaf), otherwise: Some(Box::new(Shift::Leaf)) }))
+        });
+    }
+
+    #[parser(MyLexer)]
+    #[resolve(longest)]
+    enum Greedy {
+        Long = "A B",
+        Short = "A"
+    }
+
+    #[parser(MyLexer)]
+    #[resolve(shortest)]
+    enum Lazy {
+        Long = "A B",
+        Short = "A"
+    }
+
+    #[parser(MyLexer)]
+    enum TrailingB {
+        B = "B"
+    }
+
+    #[parser(MyLexer)]
+    enum Trailing {
+        Greedy(Vec<Greedy>, Option<TrailingB>) = "0* 1?",
+        Lazy(Vec<Lazy>, Option<TrailingB>) = "D 0* 1?"
+    }
+
+    #[test]
+    fn rule_level_resolve() {
+        // The last A could end on its own, leaving its B for the optional one after the list.
+        pass!("a b a b" Trailing::Greedy(vec![Greedy::Long, Greedy::Long], None));
+        pass!("d a b a b" Trailing::Lazy(vec![Lazy::Long, Lazy::Short], Some(TrailingB::B)));
+    }
+
+    ////// CUTS
+
+    #[parser(MyLexer)]
+    enum Cut {
+        Committed = "A B ! C",
+        Fallback = "A B+"
+    }
+
+    #[parser(MyLexer)]
+    enum NoCut {
+        Committed = "A B C",
+        Fallback = "A B+"
+    }
+
+    #[parser(MyLexer)]
+    enum CutList {
+        List(Vec<Cut>) = "0* D"
+    }
+
+    #[test]
+    fn cut() {
+        pass!("a b c" Cut::Committed);
+        pass!("a b c" NoCut::Committed);
+
+        // Once past the B, only Committed is tried, so the input has to go on to a C.
+        pass!("a b" NoCut::Fallback);
+        fail!("a b" Cut 3 InputEndedTooSoon);
+        pass!("a b b" NoCut::Fallback);
+        fail!("a b b" Cut 4 NoMatches);
+
+        // The cut only applies where the rule started.
+        pass!("a b c a b c d" CutList::List(vec![Cut::Committed, Cut::Committed]));
+        fail!("a b c a b d" CutList 10 NoMatches);
+    }
+
+    ////// RULE STACKS AND SPANS
+
+    #[test]
+    fn stack() {
+        use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+
+        let stack = |input: &str| match (input.parse() as Result<BareUnnamedGrammar, ParceError>).unwrap_err().info {
+            ParceErrorInfo::Parse { stack, .. } => stack,
+            other => panic!("expected a parse error, found {:?}", other)
+        };
+        assert_eq!(stack("a ab a"), vec!["BareUnnamedGrammar", "BasicGrammar"]);
+        assert_eq!(stack("d a ab d"), vec!["BareUnnamedGrammar", "BasicGrammar"]);
+        assert_eq!(stack("a a"), vec!["BareUnnamedGrammar", "BasicGrammar"]);
+
+        let error = ParceError {
+            input: "a ab a".to_string(),
+            start: 5,
+            info: ParceErrorInfo::Parse {
+                failure: ParsePhaseFailure::NoMatches,
+                stack: vec!["BareUnnamedGrammar", "BasicGrammar"]
+            }
+        };
+        assert_eq!(
+            error.display_plain().to_string(),
+            "Parser Error: no productions matched this input\nWhile parsing: BareUnnamedGrammar → BasicGrammar\nInput: a ab a\n            ^"
+        );
+    }
+
+    #[test]
+    fn tree() {
+        let (_, tree): (BareUnnamedGrammar, _) = "g abc a abca".parse_tree().unwrap();
+        assert_eq!((tree.rule, tree.production, tree.start, tree.end), ("BareUnnamedGrammar", "Multiple", 0, 12));
+        assert_eq!(tree.children.iter().map(|c| (c.rule, c.start, c.end)).collect::<Vec<_>>(), vec![
+            ("BasicGrammar", 2, 5),
+            ("OrGrammar", 8, 12)
+        ]);
+        assert_eq!(tree.path_at(3), vec!["BareUnnamedGrammar", "BasicGrammar"]);
+        assert_eq!(tree.path_at(6), vec!["BareUnnamedGrammar"]);
+        assert!(tree.enclosing(12).is_empty());
+    }
+
+    ////// THREADS
+
+    #[test]
+    fn shared_between_threads() {
+        fn assert_shareable<T: Send + Sync>() {}
+        assert_shareable::<MyLexer>();
+        assert_shareable::<crate::report::RuleInfo>();
+        assert_shareable::<crate::report::GrammarInfo>();
+        assert_shareable::<crate::report::Expected>();
+
+        let grammar = &<BareUnnamedGrammar as super::Parseable>::GRAMMAR;
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4).map(|_| scope.spawn(|| {
+                for _ in 0..20 {
+                    pass!("g abc a abca" BareUnnamedGrammar::Multiple(Some(BasicGrammar::Thing), vec![OrGrammar::Or]));
+                    fail!("a b" BasicGrammar 3 InputEndedTooSoon);
+                }
+                grammar.rules().len()
+            })).collect();
+            for handle in handles {
+                assert_eq!(handle.join().unwrap(), 3);
+            }
+        });
+    }
+
+    #[lexer(RaceLexer)]
+    enum RaceLexemes {
+        Word = "[a-z]+",
+        Number = "[0-9]+",
+        #[skip] Space = ' '
     }
-}
\ No newline at end of file
+
+    #[parser(RaceLexer)]
+    enum RaceList {
+        List(Vec<RaceItem>) = "0+"
+    }
+
+    #[parser(RaceLexer)]
+    enum RaceItem {
+        Word(crate::convert::Symbol) = "0=Word",
+        Number(u32) = "0=Number"
+    }
+
+    /// Nothing else uses these rules, so the threads race to build their statics on first use.
+    #[test]
+    fn first_use_from_many_threads() {
+        use std::sync::Barrier;
+        use parce::convert::Interner;
+        use parce::parser::ParseSnapshot;
+
+        fn assert_send<T: Send>() {}
+        fn assert_shareable<T: Send + Sync>() {}
+        assert_send::<ParseSession<RaceLexer>>();
+        assert_send::<Interner>();
+        assert_shareable::<ParseSnapshot<RaceList>>();
+        assert_shareable::<RaceList>();
+
+        let threads = 8;
+        let start = Barrier::new(threads);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads).map(|t| {
+                let start = &start;
+                scope.spawn(move || {
+                    let mut session = ParseSession::new(RaceLexer::default()).with_interner(Interner::default());
+                    let mut snapshot = ParseSnapshot::<RaceList>::new();
+                    start.wait();
+                    #[cfg(feature = "inventory")]
+                    assert_eq!(RaceLexer::registered_rules().len(), 2);
+                    let words = format!("w{} {}", "x".repeat(t), t);
+                    let list = session.parse_all::<RaceList>(&words).unwrap();
+                    session.feed(&mut snapshot, &format!("{} ", t)).unwrap();
+                    session.feed(&mut snapshot, "seven").unwrap();
+                    let (resumed, _) = session.finish(&snapshot).unwrap();
+                    (list, resumed, session.take_interner().unwrap())
+                })
+            }).collect();
+            for (t, handle) in handles.into_iter().enumerate() {
+                let (RaceList::List(list), RaceList::List(resumed), interner) = handle.join().unwrap();
+                let RaceItem::Word(word) = list[0] else { panic!() };
+                assert_eq!(interner.resolve(word).unwrap(), format!("w{}", "x".repeat(t)));
+                assert_eq!(list.len(), 2);
+                assert_eq!(resumed[0], RaceItem::Number(t as u32));
+                // Each thread interned into its own table, so the ids only mean anything with it.
+                assert_eq!(resumed[1], RaceItem::Word(interner.get("seven").unwrap()));
+                assert_eq!(interner.len(), 2);
+            }
+        });
+    }
+
+    // Its own lexer, so the race test above still sees only its two rules registered.
+    #[lexer(InternLexer)]
+    enum InternLexemes {
+        Word = "[a-z]+",
+        Number = "[0-9]+",
+        #[skip] Space = ' '
+    }
+
+    #[parser(InternLexer)]
+    enum PanickingSymbols {
+        Pair(crate::convert::Symbol, Panicking) = "0=Word 1=Number",
+        Single(crate::convert::Symbol) = "0=Word"
+    }
+
+    #[test]
+    fn interner_after_panic() {
+        use parce::convert::{intern, Interner};
+
+        let mut session = ParseSession::new(InternLexer::default()).with_interner(Interner::default());
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            session.parse_all::<PanickingSymbols>("before 1")
+        }));
+        assert!(panicked.is_err());
+        // The session got its own interner back, with what was interned before the panic.
+        assert!(session.interner().unwrap().get("before").is_some());
+        assert_eq!(intern::with_current(|i| i.get("before")), None);
+
+        session.parse_all::<PanickingSymbols>("after").unwrap();
+        assert!(session.interner().unwrap().get("after").is_some());
+        assert_eq!(intern::with_current(|i| i.get("after")), None);
+    }
+
+    ////// SESSION
+
+    #[test]
+    fn session() {
+        let mut session = ParseSession::new(MyLexer::default()).with_diagnostics(100);
+        assert_eq!(session.parse_all::<BasicGrammar>("abc"), Ok(BasicGrammar::Thing));
+        assert_eq!(session.parse_all::<OrGrammar>("abca"), Ok(OrGrammar::Or));
+        assert_eq!(session.lexemes().len(), 4);
+        assert_eq!(session.parse_max::<BasicGrammar>("abc abc"), Ok((BasicGrammar::Thing, ParseCompletion::Incomplete(3))));
+        assert!(session.diagnostics().is_empty());
+
+        assert!(session.parse_all::<BasicGrammar>("ab").is_err());
+        assert!(session.parse_all::<BasicGrammar>("abc!").is_err());
+        assert!(session.parse_all::<BasicGrammar>("").is_err());
+        assert_eq!(
+            session.diagnostics().iter().map(|e| e.input.as_str()).collect::<Vec<_>>(),
+            vec!["ab", "abc!", ""]
+        );
+        assert_eq!(session.take_diagnostics().len(), 3);
+        assert!(session.diagnostics().is_empty());
+
+        let (_, tree) = session.parse_tree::<BareUnnamedGrammar>("g abc a abca").unwrap();
+        assert_eq!(tree.children.len(), 2);
+    }
+
+    #[test]
+    fn stream() {
+        let mut session = ParseSession::new(MyLexer::default()).with_diagnostics(100);
+        let parsed: Vec<_> = session.parse_stream_of::<BasicGrammar>("abc abcabc").collect();
+        assert_eq!(parsed, vec![Ok(BasicGrammar::Thing), Ok(BasicGrammar::Thing), Ok(BasicGrammar::Thing)]);
+
+        let parsed: Vec<_> = session.parse_stream_of::<BasicGrammar>("abc bc abc ab").collect();
+        assert_eq!(parsed.iter().map(|r| r.as_ref().map_err(|e| e.start)).collect::<Vec<_>>(), vec![
+            Ok(&BasicGrammar::Thing),
+            Err(4),
+            Err(5),
+            Ok(&BasicGrammar::Thing),
+            Err(13)
+        ]);
+        assert_eq!(session.take_diagnostics().len(), 3);
+
+        let parsed: Vec<_> = session.parse_stream_of::<BasicGrammar>("  bc abc").take(5).collect();
+        assert_eq!(parsed.iter().map(|r| r.as_ref().map_err(|e| e.start)).collect::<Vec<_>>(), vec![
+            Err(2),
+            Err(3),
+            Ok(&BasicGrammar::Thing)
+        ]);
+
+        assert_eq!(session.parse_stream_of::<BasicGrammar>("").count(), 0);
+        let parsed: Vec<_> = session.parse_stream_of::<BasicGrammar>("abc!abc").collect();
+        assert_eq!(parsed.len(), 1);
+        assert!(parsed[0].is_err());
+    }
+
+    #[test]
+    fn progress() {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let log = seen.clone();
+        let mut session = ParseSession::new(MyLexer::default())
+            .with_progress(3, move |consumed| log.lock().unwrap().push(consumed));
+
+        session.parse_all::<BareUnnamedGrammar>("g abc a abca").unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![3, 6, 9]);
+
+        seen.lock().unwrap().clear();
+        assert!(session.parse_all::<BasicGrammar>("abd abc").is_err());
+        assert_eq!(*seen.lock().unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn memory_limit() {
+        use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+        use parce::parser::ParseSnapshot;
+
+        let input = "g abc a abca";
+        let mut session = ParseSession::new(MyLexer::default());
+        session.parse_all::<BareUnnamedGrammar>(input).unwrap();
+        let needed = session.memory_used();
+        assert!(needed > 0);
+        session.parse_all::<BareUnnamedGrammar>(input).unwrap();
+        assert_eq!(session.memory_used(), needed);
+
+        let mut session = ParseSession::new(MyLexer::default()).with_memory_limit(needed);
+        assert!(session.parse_all::<BareUnnamedGrammar>(input).is_ok());
+
+        let mut session = ParseSession::new(MyLexer::default()).with_memory_limit(needed - 1);
+        let exhausted = |e: &ParceError| e.info == ParceErrorInfo::parse(ParsePhaseFailure::ResourceExhausted);
+        let first = session.parse_all::<BareUnnamedGrammar>(input).unwrap_err();
+        assert!(exhausted(&first));
+        assert_eq!(session.parse_all::<BareUnnamedGrammar>(input), Err(first));
+        assert!(session.parse_all::<BasicGrammar>("abc").is_ok());
+
+        // A chunk that goes over the limit isn't added to the snapshot.
+        let mut snapshot = ParseSnapshot::<BareUnnamedGrammar>::new();
+        session.feed(&mut snapshot, "g ").unwrap();
+        assert!(exhausted(&session.feed(&mut snapshot, "abc a abca").unwrap_err()));
+        assert_eq!(snapshot.text, "g ");
+        assert_eq!(snapshot.position, 1);
+    }
+
+    #[parser(MyLexer)]
+    enum NestedGrammar {
+        Leaf = "A",
+        Nest(Box<NestedGrammar>) = "B 0 C",
+        Group(Vec<NestedGrammar>) = "D (0 E)*"
+    }
+
+    #[test]
+    fn size_limits() {
+        use parce::error::{ParceErrorInfo, ParsePhaseFailure::*};
+        use parce::parser::ParseSnapshot;
+
+        let failure = |result: Result<NestedGrammar, ParceError>| result.map_err(|e| (e.start, e.info));
+        let mut session = ParseSession::new(MyLexer::default()).with_depth_limit(2);
+        assert!(session.parse_all::<NestedGrammar>("bac").is_ok());
+        assert_eq!(failure(session.parse_all::<NestedGrammar>("bbacc")), Err((2, ParceErrorInfo::parse(DepthLimit))));
+        // Groups inside a production don't count.
+        assert!(session.parse_all::<NestedGrammar>("dae ae ae").is_ok());
+        assert_eq!(failure(session.parse_all::<NestedGrammar>("dae bace")), Err((5, ParceErrorInfo::parse(DepthLimit))));
+
+        let mut session = ParseSession::new(MyLexer::default()).with_node_limit(4).with_diagnostics(100);
+        assert!(session.parse_all::<NestedGrammar>("dae ae ae").is_ok());
+        assert_eq!(failure(session.parse_all::<NestedGrammar>("dae ae ae ae")), Err((10, ParceErrorInfo::parse(NodeLimit))));
+        assert_eq!(session.diagnostics().len(), 1);
+
+        // Snapshots are checked when they finish.
+        let mut snapshot = ParseSnapshot::<NestedGrammar>::new();
+        session.feed(&mut snapshot, "bbbb").unwrap();
+        session.feed(&mut snapshot, "acccc").unwrap();
+        assert!(session.finish(&snapshot).is_err());
+    }
+
+    #[test]
+    fn deadline() {
+        use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+        use std::time::Duration;
+
+        let input = "g abc a abca";
+        let mut session = ParseSession::new(MyLexer::default()).with_diagnostics(100);
+        let timed_out = session.parse_with_deadline::<BareUnnamedGrammar>(input, Duration::ZERO).unwrap_err();
+        assert_eq!(timed_out.info, ParceErrorInfo::parse(ParsePhaseFailure::Timeout));
+        assert_eq!(timed_out.start, 0);
+        assert_eq!(session.diagnostics().last(), Some(&timed_out));
+
+        // The deadline only applies to the one parse.
+        assert!(session.parse_all::<BareUnnamedGrammar>(input).is_ok());
+        assert!(session.parse_with_deadline::<BareUnnamedGrammar>(input, Duration::from_secs(600)).is_ok());
+        assert!(session.parse_with_deadline::<BasicGrammar>("abd", Duration::from_secs(600)).is_err());
+        assert!(session.parse_with_deadline::<BareUnnamedGrammar>(input, Duration::MAX).is_ok());
+
+        // Nor does it outlive a parse that panics.
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            session.parse_with_deadline::<PanickingGrammar>("a", Duration::from_millis(200))
+        }));
+        assert!(panicked.is_err());
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(session.parse_all::<BareUnnamedGrammar>(input).is_ok());
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Panicking;
+
+    impl std::str::FromStr for Panicking {
+        type Err = ();
+
+        fn from_str(_: &str) -> Result<Self, ()> {
+            panic!("assembling this always panics")
+        }
+    }
+
+    #[parser(MyLexer)]
+    enum PanickingGrammar {
+        Boom(Panicking) = "0=A"
+    }
+
+    ////// INLINE RULES
+
+    #[parser(MyLexer)]
+    #[inline_rule]
+    enum InlinedWrapper {
+        Basic(BasicGrammar) = "0",
+        Or(OrGrammar) = "0",
+        Bare {inner: BareUnnamedGrammar} = "inner"
+    }
+
+    #[parser(MyLexer)]
+    enum PlainWrapper {
+        Basic(BasicGrammar) = "0",
+        Or(OrGrammar) = "0",
+        Bare {inner: BareUnnamedGrammar} = "inner"
+    }
+
+    #[parser(MyLexer)]
+    #[inline_rule]
+    enum InlinedTwice {
+        Wrapper(InlinedWrapper) = "0"
+    }
+
+    #[parser(MyLexer)]
+    enum WrapperList {
+        Inlined(Vec<InlinedWrapper>) = "Period 0*",
+        Plain(Vec<PlainWrapper>) = "Digit 0*",
+        Twice(Vec<InlinedTwice>) = "Bool 0*",
+        InlinedOne(InlinedWrapper) = "F 0",
+        PlainOne(PlainWrapper) = "G 0"
+    }
+
+    #[test]
+    fn inline_rule() {
+        use parce::error::ParceErrorInfo;
+        use parce::parser::ParseSnapshot;
+        use BareUnnamedGrammar::RangeVec;
+        use BasicGrammar::Thing;
+
+        pass!(". abca abc e abc abc" WrapperList::Inlined(vec![
+            InlinedWrapper::Or(OrGrammar::Or),
+            InlinedWrapper::Basic(Thing),
+            InlinedWrapper::Bare { inner: RangeVec(vec![Thing, Thing]) }
+        ]));
+        pass!("1 abca abc e abc abc" WrapperList::Plain(vec![
+            PlainWrapper::Or(OrGrammar::Or),
+            PlainWrapper::Basic(Thing),
+            PlainWrapper::Bare { inner: RangeVec(vec![Thing, Thing]) }
+        ]));
+        pass!("true a abc a abc" WrapperList::Twice(vec![
+            InlinedTwice::Wrapper(InlinedWrapper::Bare { inner: BareUnnamedGrammar::Basic(Thing) }),
+            InlinedTwice::Wrapper(InlinedWrapper::Basic(Thing))
+        ]));
+
+        // Inlining skips the wrapper's automata while its productions are still racing.
+        let mut session = ParseSession::new(MyLexer::default());
+        session.parse_all::<WrapperList>(". abca abc e abc abc abca abc").unwrap();
+        let inlined = session.memory_used();
+        session.parse_all::<WrapperList>("1 abca abc e abc abc abca abc").unwrap();
+        assert!(inlined < session.memory_used());
+
+        // The wrapper still shows up in spans and error stacks.
+        let (_, tree): (WrapperList, _) = ". abca abc".parse_tree().unwrap();
+        assert_eq!(tree.children.iter().map(|c| (c.rule, c.production, c.start, c.end, c.children[0].rule)).collect::<Vec<_>>(), vec![
+            ("InlinedWrapper", "Or", 2, 6, "OrGrammar"),
+            ("InlinedWrapper", "Basic", 7, 10, "BasicGrammar")
+        ]);
+        let stack = |input: &str| match (input.parse() as Result<WrapperList, ParceError>).unwrap_err().info {
+            ParceErrorInfo::Parse { stack, .. } => stack,
+            other => panic!("expected a parse error, found {:?}", other)
+        };
+        assert_eq!(stack("f a abc"), vec!["WrapperList", "InlinedWrapper", "BareUnnamedGrammar"]);
+        assert_eq!(stack("g a abc"), vec!["WrapperList", "PlainWrapper", "BareUnnamedGrammar"]);
+
+        let mut snapshot = ParseSnapshot::<WrapperList>::new();
+        session.feed(&mut snapshot, ". abca a").unwrap();
+        session.feed(&mut snapshot, "bc e abc abc").unwrap();
+        assert_eq!(session.finish(&snapshot).unwrap().0, WrapperList::Inlined(vec![
+            InlinedWrapper::Or(OrGrammar::Or),
+            InlinedWrapper::Basic(Thing),
+            InlinedWrapper::Bare { inner: RangeVec(vec![Thing, Thing]) }
+        ]));
+    }
+
+    ////// SHARED PREFIXES
+
+    #[parser(MyLexer)]
+    enum SharedPrefixGrammar {
+        Short = "A B C",
+        Long = "A B C D E",
+        Maybe = "A B D?",
+        Other = "F",
+        Another = "G"
+    }
+
+    #[parser(MyLexer)]
+    enum UnsharedGrammar {
+        Short = "A B C",
+        Other = "F",
+        Long = "A B C D E",
+        Another = "G",
+        Maybe = "A B D?"
+    }
+
+    #[parser(MyLexer)]
+    enum SharedPrefixList {
+        Shared(Vec<SharedPrefixGrammar>) = "Period 0*",
+        Unshared(Vec<UnsharedGrammar>) = "Digit 0*"
+    }
+
+    #[test]
+    fn shared_prefix() {
+        use parce::error::ParceErrorInfo;
+        use parce::parser::{ParseSnapshot, Parseable};
+        use SharedPrefixGrammar::*;
+
+        let prefixes = SharedPrefixGrammar::table().prefixes;
+        assert_eq!(prefixes.iter().map(|p| (p.route, p.productions.clone(), p.len)).collect::<Vec<_>>(), vec![(6, 0..3, 2)]);
+        assert!(UnsharedGrammar::table().prefixes.is_empty());
+        assert_eq!(SharedPrefixGrammar::rule_info().route_name(6).unwrap(), "\"A B\" in SharedPrefixGrammar::Short");
+
+        pass!("abcde" Long);
+        pass!("abd" Maybe);
+        pass!("ab" Maybe);
+        pass!("f" Other);
+        fail!("aa" SharedPrefixGrammar 1 NoMatches);
+        fail!("ac" SharedPrefixGrammar 1 NoMatches);
+        match ("a".parse() as Result<SharedPrefixGrammar, ParceError>).unwrap_err().info {
+            ParceErrorInfo::Parse { stack, .. } => assert_eq!(stack, vec!["SharedPrefixGrammar"]),
+            other => panic!("expected a parse error, found {:?}", other)
+        }
+
+        pass!(". abc f abcde ab g" SharedPrefixList::Shared(vec![Short, Other, Long, Maybe, Another]));
+        pass!("1 abc f abcde ab g" SharedPrefixList::Unshared(vec![
+            UnsharedGrammar::Short, UnsharedGrammar::Other, UnsharedGrammar::Long, UnsharedGrammar::Maybe, UnsharedGrammar::Another
+        ]));
+
+        // Items that only match one of the productions after the prefix only pay for one automaton.
+        let mut session = ParseSession::new(MyLexer::default());
+        session.parse_all::<SharedPrefixList>(". abd abd abd abd").unwrap();
+        let shared = session.memory_used();
+        session.parse_all::<SharedPrefixList>("1 abd abd abd abd").unwrap();
+        assert!(shared < session.memory_used());
+
+        let mut snapshot = ParseSnapshot::<SharedPrefixList>::new();
+        session.feed(&mut snapshot, ". a").unwrap();
+        session.feed(&mut snapshot, "bcd").unwrap();
+        session.feed(&mut snapshot, "e a").unwrap();
+        session.feed(&mut snapshot, "b").unwrap();
+        assert_eq!(session.finish(&snapshot).unwrap().0, SharedPrefixList::Shared(vec![Long, Maybe]));
+    }
+
+    ////// GUARDS
+
+    #[parser(MyLexer)]
+    enum WideGrammar {
+        Wide = "(A | B | C | D | E | F | G) Period"
+    }
+
+    #[parser(MyLexer)]
+    enum NarrowGrammar {
+        Narrow = "(F | G) Period"
+    }
+
+    #[test]
+    fn guards() {
+        use parce::lexer::KindSet;
+        use parce::parser::{Parseable, RouteGuard};
+
+        let kinds = |route: &RouteGuard| route.states.iter()
+            .map(|state| state.as_ref().map(|set| (0..MyLexeme::KIND_COUNT).filter(|k| set.contains(*k)).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        let guards = (OrGrammar::table().guards)();
+        assert_eq!(guards.iter().map(kinds).collect::<Vec<_>>(), vec![
+            vec![Some(vec![0]), None, Some(vec![0])],
+            vec![Some(vec![1])],
+            vec![Some(vec![1]), Some(vec![2])]
+        ]);
+        assert!(guards[2].accepts(1, MyLexeme::C.kind_id()));
+        assert!(!guards[2].accepts(1, MyLexeme::B.kind_id()));
+        assert!(guards[2].accepts(2, MyLexeme::B.kind_id()));
+        assert!((ClassGrammar::table().guards)()[0].states.iter().any(|state| state.is_none()));
+        assert!(!KindSet::of::<MyLexeme>(&[]).contains(0));
+
+        // Alternatives that can't use the lexeme they would start on are never spawned.
+        pass!("g." WideGrammar::Wide);
+        pass!("g." NarrowGrammar::Narrow);
+        let mut session = ParseSession::new(MyLexer::default());
+        session.parse_all::<WideGrammar>("g.").unwrap();
+        let wide = session.memory_used();
+        session.parse_all::<NarrowGrammar>("g.").unwrap();
+        assert_eq!(wide, session.memory_used());
+    }
+
+    ////// VERSIONS
+
+    #[parser(MyLexer)]
+    enum VersionedGrammar {
+        Short = "A B C",
+        #[since(2)] Long = "A B C D",
+        #[cfg_grammar(e)] Extended = "A B E",
+        #[since(2)] Empty = "F G?"
+    }
+
+    #[test]
+    fn versions() {
+        use parce::parser::Parseable;
+        use parce::version::GrammarVersion;
+
+        // Gated productions don't share prefixes, so they can be dropped by route.
+        assert!(VersionedGrammar::table().prefixes.is_empty());
+        assert_eq!(VersionedGrammar::table().gates.iter().map(|(route, _)| *route).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let parse = |version: GrammarVersion, text: &str| ParseSession::new(MyLexer::default()).with_version(version).parse_all::<VersionedGrammar>(text);
+        assert_eq!(parse(GrammarVersion::all(), "abcd"), Ok(VersionedGrammar::Long));
+        assert_eq!(parse(GrammarVersion::all(), "abe"), Ok(VersionedGrammar::Extended));
+        assert!(parse(GrammarVersion::at(&[1]), "abcd").is_err());
+        assert!(parse(GrammarVersion::at(&[1]), "f").is_err());
+        assert_eq!(parse(GrammarVersion::at(&[1]), "abc"), Ok(VersionedGrammar::Short));
+        assert_eq!(parse(GrammarVersion::at(&[2]), "f"), Ok(VersionedGrammar::Empty));
+        assert!(parse(GrammarVersion::at(&[2]), "abe").is_err());
+        assert_eq!(parse(GrammarVersion::at(&[1]).with_feature("e"), "abe"), Ok(VersionedGrammar::Extended));
+
+        // Without a version of its own, a session uses the thread's.
+        assert!(GrammarVersion::at(&[1]).run(|| ("abcd".parse() as Result<VersionedGrammar, _>).is_err()));
+    }
+
+    ////// DEPRECATED SYNTAX
+
+    #[parser(MyLexer)]
+    enum DeprecatedGrammar {
+        New = "A B",
+        #[deprecated_syntax(message = "write ab")] Old = "A C",
+        Nested(Box<DeprecatedGrammar>) = "D 0"
+    }
+
+    #[test]
+    fn deprecated_syntax() {
+        use parce::span::Span;
+
+        let mut session = ParseSession::new(MyLexer::default());
+        assert_eq!(session.parse_all::<DeprecatedGrammar>("dac"), Ok(DeprecatedGrammar::Nested(Box::new(DeprecatedGrammar::Old))));
+        assert!(session.parse_all::<DeprecatedGrammar>("acd").is_err());
+        let warnings = session.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].name.as_str(), warnings[0].message, warnings[0].span), ("DeprecatedGrammar::Old", "write ab", Span::new(1, 3)));
+
+        // Each value of a stream only warns about its own part of the input.
+        let parsed: Vec<_> = session.parse_stream_of::<DeprecatedGrammar>("ac ab ac").collect();
+        assert_eq!(parsed.len(), 3);
+        let spans: Vec<Span> = session.warnings().iter().map(|w| w.span).collect();
+        assert_eq!(spans, vec![Span::new(0, 2), Span::new(6, 8)]);
+    }
+}
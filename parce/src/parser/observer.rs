@@ -0,0 +1,111 @@
+//! Contains [ParseObserver], which is told about every rule in a successful parse, for building
+//! symbol tables and indexes in the same pass as the parse.
+
+use super::RuleSpan;
+
+/// Callbacks for each rule matched by a parse. See [ParseSession::parse_observed](super::ParseSession::parse_observed).
+///
+/// The parser tries many parses of the input at once, and most of them are thrown away, so the
+/// observer is only called once the parse is finished, for the rules of the parse that was kept.
+/// It isn't called at all if the parse fails. Rules are visited in input order:
+/// [rule_start](Self::rule_start) is called for a rule before any of the rules inside it, and
+/// [rule_end](Self::rule_end) after all of them, so an observer can keep a stack of scopes.
+///
+/// Both callbacks get the rule's span, with the spans of the rules captured inside it in
+/// [children](RuleSpan::children), and the text it matched. Both do nothing by default.
+pub trait ParseObserver {
+    /// Called when `span`, which matched `text`, starts.
+    fn rule_start(&mut self, span: &RuleSpan, text: &str) {
+        let _ = (span, text);
+    }
+
+    /// Called when `span`, which matched `text`, ends, after all the rules inside it.
+    fn rule_end(&mut self, span: &RuleSpan, text: &str) {
+        let _ = (span, text);
+    }
+}
+
+/// Calls `observer` for every rule in `tree`, which was parsed from `text`, in the same order as
+/// [ParseSession::parse_observed](super::ParseSession::parse_observed). For replaying a tree from
+/// [parse_tree](super::Parse::parse_tree) into an observer.
+pub fn observe(tree: &RuleSpan, text: &str, observer: &mut dyn ParseObserver) {
+    let matched = &text[tree.start..tree.end];
+    observer.rule_start(tree, matched);
+    for child in &tree.children {
+        observe(child, text, observer);
+    }
+    observer.rule_end(tree, matched);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(ObserverLexer)]
+    enum ObserverLexemes {
+        Word = "[a-z]+",
+        LParen = '(',
+        RParen = ')',
+        #[skip] Space = ' '
+    }
+
+    #[parser(ObserverLexer)]
+    enum List {
+        List(Vec<Item>) = "LParen 0* RParen"
+    }
+
+    #[parser(ObserverLexer)]
+    enum Item {
+        Word = "Word",
+        List(Box<List>) = "0"
+    }
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl ParseObserver for Events {
+        fn rule_start(&mut self, span: &RuleSpan, text: &str) {
+            self.0.push(format!("+{} {:?}", span.rule, text));
+        }
+
+        fn rule_end(&mut self, span: &RuleSpan, _text: &str) {
+            self.0.push(format!("-{} {}", span.rule, span.children.len()));
+        }
+    }
+
+    #[test]
+    fn committed_order() {
+        let mut session = ParseSession::new(ObserverLexer::default());
+        let mut events = Events::default();
+        session.parse_observed::<List>("(a (b))", &mut events).unwrap();
+        assert_eq!(events.0, vec![
+            "+List \"(a (b))\"",
+            "+Item \"a\"",
+            "-Item 0",
+            "+Item \"(b)\"",
+            "+List \"(b)\"",
+            "+Item \"b\"",
+            "-Item 0",
+            "-List 1",
+            "-Item 1",
+            "-List 2"
+        ]);
+
+        // Replaying the tree gives the same calls.
+        let (_, tree): (List, _) = "(a (b))".parse_tree().unwrap();
+        let mut replayed = Events::default();
+        observe(&tree, "(a (b))", &mut replayed);
+        assert_eq!(replayed.0, events.0);
+    }
+
+    #[test]
+    fn not_called_on_failure() {
+        let mut session = ParseSession::new(ObserverLexer::default());
+        let mut events = Events::default();
+        assert!(session.parse_observed::<List>("(a (b)", &mut events).is_err());
+        assert!(session.parse_observed::<List>("(a) b", &mut events).is_err());
+        assert!(events.0.is_empty());
+    }
+}
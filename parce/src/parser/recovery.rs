@@ -0,0 +1,496 @@
+//! The error recovery contract: what parce gives back for broken input.
+//!
+//! Tools built on recovery, like editors and linters, depend on the exact errors and trees that
+//! come out of bad input, so these are part of the API. A change to any of them is a breaking
+//! change, and the tests in this module pin them for a set of canonical broken inputs.
+//!
+//! # One value
+//!
+//! [parse_all](super::Parse::parse_all) and [ParseSession::parse_all](super::ParseSession::parse_all)
+//! give one [ParceError] for broken input, never a partial tree:
+//!
+//! - A character no lexeme matches is a [Lex](crate::error::ParceErrorInfo::Lex) error at that
+//!   character. Nothing is parsed.
+//! - A stray lexeme, where no production can go on, is a
+//!   [NoMatches](crate::error::ParsePhaseFailure::NoMatches) error at the stray lexeme, with the
+//!   rules that were being parsed there as the error's stack.
+//! - A missing closer, or any input cut off in the middle of a rule, is an
+//!   [InputEndedTooSoon](crate::error::ParsePhaseFailure::InputEndedTooSoon) error at the end of
+//!   the input. If the lexer declares the closer with `#[pair(...)]`, and the input parses once
+//!   every unclosed pair is closed at the end, it is an
+//!   [Unclosed](crate::error::ParsePhaseFailure::Unclosed) error at the innermost unclosed opener
+//!   instead.
+//! - Input left over after a complete value is a
+//!   [LeftoverLexemes](crate::error::ParsePhaseFailure::LeftoverLexemes) error one byte past the end of
+//!   the value. [parse_outcome](super::Parse::parse_outcome) gives the value and where it stopped instead.
+//! - Empty input, or input that is only skipped lexemes, is a
+//!   [NothingToParse](crate::error::ParsePhaseFailure::NothingToParse) error at 0.
+//!
+//! # Many values
+//!
+//! [ParseSession::parse_stream_of](super::ParseSession::parse_stream_of) recovers from errors:
+//! every value that parses is yielded in order, and each broken one is replaced by its error. After
+//! an error, parsing starts again at the lexeme after the one the error is at, so a stray lexeme is
+//! dropped alone, and the values after it are unchanged. A value with a broken piece inside it, like
+//! a block with one bad statement, is lost as a whole, and the rest of it is parsed as new values.
+//! A lexing error ends the stream.
+//!
+//! # Diagnostics
+//!
+//! A session made [with_diagnostics](super::ParseSession::with_diagnostics) records every error it
+//! gives back in its [diagnostics](super::ParseSession::diagnostics), in the order they were found,
+//! including the errors yielded by a stream.
+//!
+//! # Recovered fields
+//!
+//! A rule field of type [`Recovered<T>`](Recovered) makes the parser recover from a broken `T`
+//! inside the value instead of failing. Where the field's `T` can't be parsed, the parser skips
+//! lexemes up to and including the next of `T`'s `#[sync(...)]` anchors, or to the end of the
+//! input, and fills the field with an [ErrorNode] for what it skipped. When a value can be parsed
+//! with fewer errors, it always is, so input without errors never has any error nodes.
+//!
+//! The error's span is in the value's [RuleSpan] tree too, as a span of the rule `T` with the
+//! production [`ErrorNode::PRODUCTION`].
+//!
+//! # Closed delimiters
+//!
+//! [ParseSession::parse_all_closed](super::ParseSession::parse_all_closed) goes further for input
+//! that only fails because `#[pair(...)]` delimiters are left open: it closes them with
+//! zero-length closers at the end of the input, and gives back the value along with an
+//! [Unclosed](crate::error::ParsePhaseFailure::Unclosed) error for each opener, outermost first.
+//!
+//! [ParceError]: crate::error::ParceError
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use crate::error::{Message, MessageCatalog};
+use crate::lexer::{Lexeme, Lexer, SpannedLexeme};
+use crate::report::Resolve;
+use crate::span::Span;
+use super::automata::{Army, Rawtomaton};
+use super::{Engine, Limits, Parseable, RuleId, RuleSpan, RuleTables};
+
+/// A piece of broken input that the parser skipped in place of a [Recovered] field.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ErrorNode {
+    /// The input that was skipped.
+    pub span: Span,
+    /// The lexemes the field's rule could have gone on with where it broke, sorted by name. `"."`
+    /// means any lexeme.
+    pub expected: Vec<&'static str>,
+    /// How many lexemes were skipped, not counting skipped lexemes like whitespace.
+    pub consumed_tokens: usize
+}
+
+impl ErrorNode {
+    /// The production name of an error's [RuleSpan].
+    pub const PRODUCTION: &'static str = "<error>";
+
+    /// Describes the error as "expected X, found Y" in the language of `catalog`, where `text` is
+    /// the input that was parsed. An error that skipped nothing found the end of the input.
+    pub fn message(&self, text: &str, catalog: &dyn MessageCatalog) -> String {
+        let found = match self.span.is_empty() {
+            true => catalog.format(Message::EndOfInput, &[]),
+            false => format!("`{}`", &text[self.span.start..self.span.end])
+        };
+        catalog.format(Message::ExpectedFound, &[("expected", &self.expected.join(", ")), ("found", &found)])
+    }
+
+    /// Builds the error for `count` lexemes from `lexemes[first]`, which `O` couldn't parse, and
+    /// pushes its span onto `spans`. Used by the generated [Parseable::assemble].
+    #[doc(hidden)]
+    pub fn skipped<O: Parseable>(lexemes: &[SpannedLexeme<<O::Lexer as Lexer>::Lexemes>], first: usize, count: usize, text: &str, spans: &mut Vec<RuleSpan>) -> ErrorNode {
+        let skipped = &lexemes[first..first + count];
+        let span = match (skipped.first(), skipped.last()) {
+            (Some(start), Some(end)) => Span::new(start.start, end.end()),
+            _ => {
+                let at = lexemes.get(first).map(|l| l.start).unwrap_or(text.len());
+                Span::new(at, at)
+            }
+        };
+        spans.push(RuleSpan {
+            rule: O::rule_info().name,
+            production: Self::PRODUCTION,
+            resolve: Resolve::Unspecified,
+            start: span.start,
+            end: span.end,
+            children: vec![],
+            deprecated: None
+        });
+        ErrorNode {
+            span,
+            expected: expected::<O>(skipped, text),
+            consumed_tokens: count
+        }
+    }
+}
+
+/// A field that holds either a parsed `T`, or the [ErrorNode] the parser put in its place. See
+/// [the module docs](self).
+///
+/// ```
+/// use parce::prelude::*;
+///
+/// #[lexer(ListLexer)]
+/// enum ListLexemes {
+///     Number = "[0-9]+",
+///     Comma = ',',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(ListLexer)]
+/// #[sync(Comma)]
+/// enum Item {
+///     Item(u32) = "0=Number Comma"
+/// }
+///
+/// #[parser(ListLexer)]
+/// enum List {
+///     List(Vec<Recovered<Item>>) = "0*"
+/// }
+///
+/// let text = "1, 2 3, 4,";
+/// let List::List(items) = text.parse().unwrap();
+/// assert_eq!(items[0], Recovered::Parsed(Item::Item(1)));
+/// let error = items[1].error().unwrap();
+/// assert_eq!((error.span.start, error.span.end, error.consumed_tokens), (3, 7, 3));
+/// assert_eq!(error.expected, vec!["Comma"]);
+/// assert_eq!(error.message(text, &parce::error::English), "expected Comma, found `2 3,`");
+/// assert_eq!(items[2], Recovered::Parsed(Item::Item(4)));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Recovered<T> {
+    /// The field was parsed.
+    Parsed(T),
+    /// The field was broken, and skipped.
+    Error(ErrorNode)
+}
+
+impl<T> Recovered<T> {
+    /// Whether the field was broken.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Recovered::Error(_))
+    }
+
+    /// The parsed value, if there is one.
+    pub fn parsed(self) -> Option<T> {
+        match self {
+            Recovered::Parsed(value) => Some(value),
+            Recovered::Error(_) => None
+        }
+    }
+
+    /// The error, if the field was broken.
+    pub fn error(&self) -> Option<&ErrorNode> {
+        match self {
+            Recovered::Parsed(_) => None,
+            Recovered::Error(error) => Some(error)
+        }
+    }
+
+    /// Borrows the parsed value.
+    pub fn as_ref(&self) -> Recovered<&T> {
+        match self {
+            Recovered::Parsed(value) => Recovered::Parsed(value),
+            Recovered::Error(error) => Recovered::Error(error.clone())
+        }
+    }
+
+    /// Changes the parsed value with `f`, and keeps the error as it is.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Recovered<U> {
+        match self {
+            Recovered::Parsed(value) => Recovered::Parsed(f(value)),
+            Recovered::Error(error) => Recovered::Error(error)
+        }
+    }
+
+    /// The parsed value, or the error as a [Result].
+    pub fn into_result(self) -> Result<T, ErrorNode> {
+        match self {
+            Recovered::Parsed(value) => Ok(value),
+            Recovered::Error(error) => Err(error)
+        }
+    }
+}
+
+impl<T> From<T> for Recovered<T> {
+    fn from(value: T) -> Self {
+        Recovered::Parsed(value)
+    }
+}
+
+/// The lexemes `O` could have gone on with where it broke in `lexemes`.
+fn expected<O: Parseable>(lexemes: &[SpannedLexeme<<O::Lexer as Lexer>::Lexemes>], text: &str) -> Vec<&'static str> {
+    let army = Army::with_capacity(lexemes.len() + 1);
+    let mut engine = Engine::start::<O>(&army);
+    let _ = engine.advance::<O>(lexemes, text, &army, Limits::default(), &mut |_| {});
+    // Automata skipping their own broken pieces would take anything, which says nothing.
+    let useful = |auto: &&Rawtomaton| !is_recovery::<O>(**auto);
+    let stuck: Vec<Rawtomaton> = match engine.alive.iter().filter(useful).count() {
+        0 => engine.frontier.iter().filter(useful).copied().collect(),
+        _ => engine.alive.iter().filter(useful).copied().collect()
+    };
+    let mut lexemes = BTreeSet::new();
+    for auto in stuck {
+        if let Some(expected) = O::expected(auto.get().rule, auto.get().route, auto.get().state) {
+            lexemes.extend(expected.lexemes());
+        }
+    }
+    lexemes.into_iter().collect()
+}
+
+/// The `#[pair(...)]` openers in `lexemes` that are never closed, outermost first, with the closer
+/// each one needs. A closer closes the innermost opener it pairs with, and the openers inside that
+/// one that it skips over aren't counted, since they weren't left open at the end of the input.
+pub(super) fn unclosed<L: Lexeme>(lexemes: &[SpannedLexeme<L>]) -> Vec<(SpannedLexeme<L>, L)> {
+    let mut open: Vec<(SpannedLexeme<L>, L)> = vec![];
+    for lexeme in lexemes {
+        if let Some(i) = open.iter().rposition(|(_, closer)| lexeme.data == *closer) {
+            open.truncate(i);
+        } else if let Some(&(_, closer)) = L::PAIRS.iter().find(|(opener, _)| lexeme.data == *opener) {
+            open.push((*lexeme, closer));
+        }
+    }
+    open
+}
+
+/// Whether `auto` is skipping broken input for a [Recovered] field.
+fn is_recovery<O: Parseable>(auto: Rawtomaton) -> bool {
+    (O::table().reachable)().is_recovery(auto.get().rule, auto.get().route)
+}
+
+/// How many errors are in a finished tree of spans.
+pub(super) fn errors_in(span: &RuleSpan) -> usize {
+    (span.production == ErrorNode::PRODUCTION) as usize + span.children.iter().map(errors_in).sum::<usize>()
+}
+
+/// Whether any rule in `O`'s grammar has a [Recovered] field.
+pub(super) fn has_recovery<O: Parseable>() -> bool {
+    (O::table().reachable)().tables.iter().any(|table| !table.recovery.is_empty())
+}
+
+/// Drops the automata that will do the same as another one with as few errors or fewer behind
+/// it: the ones where they and every automaton they are nested in are on the same routes, in the
+/// same states, up to the cycles of repeating routes. Each recovered field offers an error in place
+/// of every piece of input, and without this, every way of combining those errors would stay alive.
+///
+/// Where each automaton started only matters to the future of a parse if there are cuts, which are
+/// won by the production that started at a lexeme, so with `cuts` automata that started in
+/// different places are never the same.
+pub(super) fn prune<L: Lexeme>(alive: &mut VecDeque<Rawtomaton>, grammar: &RuleTables<L>, cuts: bool) {
+    type Place = (RuleId, u32, u32, Option<usize>, Option<(RuleId, u32)>);
+    let mut paths: Vec<(Vec<Place>, u32)> = Vec::with_capacity(alive.len());
+    for auto in alive.iter() {
+        let (mut path, mut errors) = (vec![], 0);
+        let mut current = Some(*auto);
+        while let Some(auto) = current {
+            let a = auto.get();
+            let state = match grammar.guards[a.rule.0 as usize].get(a.route as usize) {
+                Some(guard) if guard.repeats => a.state % guard.states.len().max(1) as u32,
+                _ => a.state
+            };
+            path.push((a.rule, a.route, state, cuts.then_some(a.lexeme_start), a.inlined));
+            errors += a.errors;
+            current = a.parent.map(|(parent, _)| parent);
+        }
+        paths.push((path, errors));
+    }
+    let mut fewest: HashMap<&[Place], (u32, bool)> = HashMap::new();
+    for (path, errors) in &paths {
+        fewest.entry(&path[..]).and_modify(|(fewest, _)| *fewest = (*errors).min(*fewest)).or_insert((*errors, false));
+    }
+    // Parses without errors that would go on the same way are kept, since they might be an
+    // ambiguity for #[resolve] to decide. Between the ones with errors, the first one is as good as any.
+    let mut keep = paths.iter().map(|(path, errors)| {
+        let (fewest, taken) = fewest.get_mut(&path[..]).expect("every path was counted");
+        let kept = *errors == *fewest && (*errors == 0 || !*taken);
+        *taken |= kept;
+        kept
+    }).collect::<Vec<_>>().into_iter();
+    alive.retain(|_| keep.next().unwrap_or(true));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use parce::error::{ParceError, ParceErrorInfo, ParsePhaseFailure};
+    use parce::parser::ParseOutcome;
+    use parce::span::Span;
+
+    #[lexer(BlockLexer)]
+    enum BlockLexemes {
+        Let = "'let'",
+        Ident = "[a-z]+",
+        Number = "[0-9]+",
+        Equals = '=',
+        Semicolon = ';',
+        LBrace = '{',
+        RBrace = '}',
+        #[skip] Space = "[ \n]"
+    }
+
+    #[parser(BlockLexer)]
+    #[sync(Semicolon)]
+    enum Statement {
+        Let(String, u32) = "Let 0=Ident Equals 1=Number Semicolon",
+        Block(Vec<Statement>) = "LBrace 0* RBrace"
+    }
+
+    #[parser(BlockLexer)]
+    #[sync(Semicolon)]
+    enum Recovering {
+        Let(String, u32) = "Let 0=Ident Equals 1=Number Semicolon",
+        Block(Vec<Recovered<Recovering>>) = "LBrace 0* RBrace"
+    }
+
+    fn failure(error: &ParceError) -> (usize, Option<&ParsePhaseFailure>, Vec<&'static str>) {
+        match &error.info {
+            ParceErrorInfo::Parse { failure, stack } => (error.start, Some(failure), stack.clone()),
+            _ => (error.start, None, vec![])
+        }
+    }
+
+    fn parse(text: &str) -> (usize, Option<ParsePhaseFailure>, Vec<&'static str>) {
+        let error = text.parse::<Statement>().unwrap_err();
+        let (start, failure, stack) = failure(&error);
+        (start, failure.cloned(), stack)
+    }
+
+    fn assign(name: &str, value: u32) -> Statement {
+        Statement::Let(name.to_string(), value)
+    }
+
+    #[test]
+    fn one_value() {
+        use ParsePhaseFailure::*;
+        // Missing closing braces, at any depth.
+        assert_eq!(parse("{ let a = 1; "), (13, Some(InputEndedTooSoon), vec!["Statement"]));
+        assert_eq!(parse("{ let a = 1; { let b = 2; }"), (27, Some(InputEndedTooSoon), vec!["Statement"]));
+        // Stray, missing, and misplaced tokens inside a block.
+        assert_eq!(parse("{ let a = = 1; }"), (10, Some(NoMatches), vec!["Statement", "Statement"]));
+        assert_eq!(parse("{ let a 1; }"), (8, Some(NoMatches), vec!["Statement", "Statement"]));
+        assert_eq!(parse("{ let a = 1 }"), (12, Some(NoMatches), vec!["Statement", "Statement"]));
+        // Truncated files.
+        assert_eq!(parse("let a = 1"), (9, Some(InputEndedTooSoon), vec!["Statement"]));
+        assert_eq!(parse("let"), (3, Some(InputEndedTooSoon), vec!["Statement"]));
+        assert_eq!(parse(""), (0, Some(NothingToParse), vec![]));
+        assert_eq!(parse("  \n"), (0, Some(NothingToParse), vec![]));
+        // An extra closing brace.
+        assert_eq!(parse("{ let a = 1; } }"), (15, Some(LeftoverLexemes), vec![]));
+        assert_eq!("{ let a = 1; } }".parse_outcome(), ParseOutcome::Partial { value: Statement::Block(vec![assign("a", 1)]), consumed: 14 });
+        // A character that isn't any lexeme.
+        let error = "{ let a = 1; # }".parse::<Statement>().unwrap_err();
+        assert_eq!((error.start, error.info), (13, ParceErrorInfo::lex("Default".to_string())));
+    }
+
+    #[test]
+    fn many_values() {
+        let mut session = ParseSession::new(BlockLexer::default()).with_diagnostics(100);
+        let text = "let a = 1; let b = = 2; let c = 3; } { let d = 4; let e 5; } let f = 6;";
+        let values: Vec<_> = session.parse_stream_of::<Statement>(text).map(|r| r.map_err(|e| failure(&e).0)).collect();
+        assert_eq!(values, vec![
+            Ok(assign("a", 1)),
+            // The broken statement fails at the second `=`, which is skipped alone, so the rest of
+            // it is broken too.
+            Err(19),
+            Err(21),
+            Err(22),
+            Ok(assign("c", 3)),
+            Err(35),
+            // A broken statement in a block loses the whole block, and the block's closer is
+            // left over.
+            Err(56),
+            Err(57),
+            Err(59),
+            Ok(assign("f", 6))
+        ]);
+        assert_eq!(session.diagnostics().iter().map(|e| e.start).collect::<Vec<_>>(), vec![19, 21, 22, 35, 56, 57, 59]);
+
+        // Cut off in the middle of a statement.
+        let values: Vec<_> = session.parse_stream_of::<Statement>("let a = 1; { let b").map(|r| r.map_err(|e| failure(&e).0)).collect();
+        assert_eq!(values, vec![Ok(assign("a", 1)), Err(18)]);
+
+        // A lexing error ends the stream before anything is parsed.
+        let values: Vec<_> = session.parse_stream_of::<Statement>("let a = 1; # let b = 2;").collect();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].as_ref().unwrap_err().info, ParceErrorInfo::lex("Default".to_string()));
+        assert_eq!(session.diagnostics().len(), 9);
+    }
+
+    #[test]
+    fn recovered_fields() {
+        use super::{ErrorNode, Recovered::*};
+        let let_ = |name: &str, value: u32| Parsed(Recovering::Let(name.to_string(), value));
+
+        let text = "{ let a = 1; let b = = 2; let c = 3; }";
+        let (value, tree): (Recovering, _) = text.parse_tree().unwrap();
+        assert_eq!(value, Recovering::Block(vec![
+            let_("a", 1),
+            Error(ErrorNode { span: Span::new(13, 25), expected: vec!["Number"], consumed_tokens: 6 }),
+            let_("c", 3)
+        ]));
+        let productions: Vec<_> = tree.children.iter().map(|c| (c.rule, c.production, c.start, c.end)).collect();
+        assert_eq!(productions[1], ("Recovering", ErrorNode::PRODUCTION, 13, 25));
+
+        // Errors nest, and input that parses has none.
+        let Recovering::Block(outer) = "{ { let = 1; } let d = 4; }".parse().unwrap() else { panic!() };
+        let Parsed(Recovering::Block(inner)) = &outer[0] else { panic!() };
+        assert_eq!(inner[0].error().map(|e| e.expected.clone()), Some(vec!["Ident"]));
+        assert_eq!(outer[1], let_("d", 4));
+        let many = format!("{{ {} }}", "let a = 1; { let b = 2; } ".repeat(50));
+        let Recovering::Block(statements) = many.parse().unwrap() else { panic!() };
+        assert!(statements.len() == 100 && statements.iter().all(|s| !s.is_error()));
+
+        // Without an anchor, an error skips to the end of the input, which the block still needs.
+        assert!("{ let a = = 1 }".parse::<Recovering>().is_err());
+    }
+
+    #[lexer(PairedLexer)]
+    #[pair(LBrace, RBrace)]
+    enum PairedLexemes {
+        Let = "'let'",
+        Ident = "[a-z]+",
+        Number = "[0-9]+",
+        Equals = '=',
+        Semicolon = ';',
+        LBrace = '{',
+        RBrace = '}',
+        #[skip] Space = "[ \n]"
+    }
+
+    #[parser(PairedLexer)]
+    enum Paired {
+        Let(String, u32) = "Let 0=Ident Equals 1=Number Semicolon",
+        Block(Vec<Paired>) = "LBrace 0* RBrace"
+    }
+
+    #[test]
+    fn unclosed_pairs() {
+        use ParsePhaseFailure::*;
+        let unclosed = Unclosed { opener: "LBrace".into(), closer: "RBrace".into() };
+        let parse = |text: &str| {
+            let error = text.parse::<Paired>().unwrap_err();
+            let (start, failure, _) = failure(&error);
+            (start, failure.cloned())
+        };
+        assert_eq!(parse("{ let a = 1; "), (0, Some(unclosed.clone())));
+        assert_eq!(parse("{ let a = 1; { let b = 2; }"), (0, Some(unclosed.clone())));
+        // The innermost opener is the one reported.
+        assert_eq!(parse("{ { let a = 1; "), (2, Some(unclosed.clone())));
+        // Closing doesn't fix a missing semicolon, so the error is where it was.
+        assert_eq!(parse("{ let a = 1"), (11, Some(InputEndedTooSoon)));
+        assert_eq!(parse("{ let a = 1; } }"), (15, Some(LeftoverLexemes)));
+
+        let mut session = ParseSession::new(PairedLexer::default()).with_diagnostics(100);
+        let text = "{ { let a = 1; ";
+        let (value, errors) = session.parse_all_closed::<Paired>(text).unwrap();
+        assert_eq!(value, Paired::Block(vec![Paired::Block(vec![Paired::Let("a".to_string(), 1)])]));
+        assert_eq!(errors.iter().map(|e| e.start).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(session.diagnostics(), &errors[..]);
+
+        // Input that is broken some other way isn't closed.
+        let error = session.parse_all_closed::<Paired>("{ let a = 1").unwrap_err();
+        assert_eq!(failure(&error).1, Some(&InputEndedTooSoon));
+        assert_eq!(session.diagnostics().len(), 3);
+    }
+}
@@ -0,0 +1,178 @@
+//! Contains [check_tree] and [check_text], which test that a grammar and a printer for its trees
+//! agree with each other.
+//!
+//! Parce doesn't generate a printer (an unparser) for rules, so both take one as a closure. Any
+//! tree the printer can produce should parse back to itself, and printing what a corpus input
+//! parses to should give text that prints the same way after another round. When one of these
+//! fails, the grammar and the tree type have drifted apart: a production doesn't match what its
+//! variant holds, or a field the printer relies on is dropped.
+//!
+//! Trees can come from anywhere, like a property testing crate's strategies or a loop over every
+//! small tree, so this module only checks one at a time.
+
+use std::fmt::Debug;
+use crate::error::ParceError;
+use super::{Parse, Parseable};
+
+/// Why a round trip failed, with the text that was parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoundTripError<O> {
+    /// The text didn't parse.
+    Parse {
+        text: String,
+        error: ParceError
+    },
+    /// The printed text of a tree parsed to a different tree.
+    Mismatch {
+        text: String,
+        reparsed: O
+    },
+    /// Printing the tree a text parsed to, then parsing and printing it again, gave different text.
+    Unstable {
+        first: String,
+        second: String
+    }
+}
+
+fn parse<O: for<'t> Parseable<Output<'t> = O>>(text: String) -> Result<(O, String), RoundTripError<O>> {
+    match text.parse_all() {
+        Ok(tree) => Ok((tree, text)),
+        Err(error) => Err(RoundTripError::Parse { text, error })
+    }
+}
+
+/// Checks that `tree`, printed with `unparse`, parses back to itself.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::round_trip::{check_tree, RoundTripError};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Digit = "[0-9]",
+///     Plus = '+',
+///     LParen = '(',
+///     RParen = ')',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// #[derive(Clone)]
+/// enum Expr {
+///     Sum(Atom, Box<Expr>) = "0 Plus 1",
+///     Atom(Atom) = "0"
+/// }
+///
+/// #[parser(MyLexer)]
+/// #[derive(Clone)]
+/// enum Atom {
+///     Digit(u8) = "0=Digit",
+///     Group(Box<Expr>) = "LParen 0 RParen"
+/// }
+///
+/// fn unparse(expr: &Expr) -> String {
+///     match expr {
+///         Expr::Sum(atom, rest) => format!("{} + {}", unparse_atom(atom), unparse(rest)),
+///         Expr::Atom(atom) => unparse_atom(atom)
+///     }
+/// }
+///
+/// fn unparse_atom(atom: &Atom) -> String {
+///     match atom {
+///         Atom::Digit(d) => d.to_string(),
+///         Atom::Group(expr) => format!("({})", unparse(expr))
+///     }
+/// }
+///
+/// // Every tree of up to two levels of grouping.
+/// let mut trees = vec![Expr::Atom(Atom::Digit(1))];
+/// for _ in 0..2 {
+///     let smaller = trees.clone();
+///     for tree in smaller {
+///         trees.push(Expr::Sum(Atom::Digit(2), Box::new(tree.clone())));
+///         trees.push(Expr::Atom(Atom::Group(Box::new(tree))));
+///     }
+/// }
+/// for tree in &trees {
+///     check_tree(tree, unparse).unwrap();
+/// }
+///
+/// // A printer that leaves out parentheses makes sums that group to the right either way.
+/// let flat = |expr: &Expr| unparse(expr).replace(['(', ')'], "");
+/// let tree = Expr::Sum(Atom::Group(Box::new(Expr::Sum(Atom::Digit(1), Box::new(Expr::Atom(Atom::Digit(2)))))), Box::new(Expr::Atom(Atom::Digit(3))));
+/// assert!(matches!(check_tree(&tree, flat), Err(RoundTripError::Mismatch { text, .. }) if text == "1 + 2 + 3"));
+/// ```
+pub fn check_tree<O: for<'t> Parseable<Output<'t> = O> + PartialEq + Debug>(tree: &O, unparse: impl Fn(&O) -> String) -> Result<(), RoundTripError<O>> {
+    let (reparsed, text) = parse::<O>(unparse(tree))?;
+    if &reparsed == tree {
+        Ok(())
+    } else {
+        Err(RoundTripError::Mismatch { text, reparsed })
+    }
+}
+
+/// Parses `text`, prints the result with `unparse`, and checks that doing that again gives the
+/// same text. Returns the printed text, which is `text` normalized by the printer.
+pub fn check_text<O: for<'t> Parseable<Output<'t> = O> + PartialEq + Debug>(text: &str, unparse: impl Fn(&O) -> String) -> Result<String, RoundTripError<O>> {
+    let (tree, _) = parse::<O>(text.to_string())?;
+    let first = unparse(&tree);
+    let (reparsed, first) = parse::<O>(first)?;
+    let second = unparse(&reparsed);
+    if first == second {
+        Ok(first)
+    } else {
+        Err(RoundTripError::Unstable { first, second })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(ListLexer)]
+    enum ListLexemes {
+        Word = "[a-z]+",
+        Comma = ',',
+        #[skip] Space = ' '
+    }
+
+    #[parser(ListLexer)]
+    enum List {
+        List(Vec<Item>) = "0*"
+    }
+
+    #[parser(ListLexer)]
+    enum Item {
+        Item(String) = "0=Word Comma"
+    }
+
+    fn unparse(list: &List) -> String {
+        let List::List(items) = list;
+        items.iter().map(|Item::Item(word)| format!("{},", word)).collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn round_trips() {
+        let list = |words: &[&str]| List::List(words.iter().map(|w| Item::Item(w.to_string())).collect());
+        for n in 1..4 {
+            check_tree(&list(&["a", "bc", "def"][..n]), unparse).unwrap();
+        }
+        // An empty list prints as empty input, which is never parsed.
+        assert!(matches!(check_tree(&list(&[]), unparse), Err(RoundTripError::Parse { text, .. }) if text.is_empty()));
+        assert_eq!(check_text("a,b ,  c,", unparse), Ok("a, b, c,".to_string()));
+
+        let bare = |list: &List| unparse(list).replace(',', "");
+        assert!(matches!(check_tree(&list(&["a", "b"]), bare), Err(RoundTripError::Parse { text, .. }) if text == "a b"));
+        assert!(matches!(check_text("a, b,", bare), Err(RoundTripError::Parse { .. })));
+        assert!(matches!(check_text::<List>("a", unparse), Err(RoundTripError::Parse { text, .. }) if text == "a"));
+
+        let reversed = |list: &List| unparse(list).rsplit(' ').collect::<Vec<_>>().join(" ");
+        assert_eq!(check_tree(&list(&["a", "b"]), reversed), Err(RoundTripError::Mismatch { text: "b, a,".into(), reparsed: list(&["b", "a"]) }));
+        assert_eq!(check_text("a, b,", reversed), Err(RoundTripError::Unstable { first: "b, a,".into(), second: "a, b,".into() }));
+
+        let growing = |list: &List| unparse(list) + " z,";
+        assert_eq!(check_text("a,", growing), Err(RoundTripError::Unstable { first: "a, z,".into(), second: "a, z, z,".into() }));
+    }
+}
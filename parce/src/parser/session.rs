@@ -0,0 +1,1224 @@
+//! Contains [ParseSession], which keeps the buffers used by the parser alive between parses.
+
+use crate::lexer::{KindSet, Lexeme, Lexer, SpannedLexeme};
+use crate::lexer::filter::TokenFilter;
+use crate::lexer::trivia::TriviaIndex;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+use crate::lexer::cursor::StrCursor;
+use crate::convert::intern::{self, Interner};
+use crate::version::GrammarVersion;
+use super::arena::{self, Place};
+#[cfg(not(feature = "safe"))]
+use super::arena::NodeArena;
+use allocator_api2::alloc::{Allocator, Global};
+use crate::error::{ParceError, ParceErrorInfo, ParceWarning, ParsePhaseFailure};
+use super::{Limits, Parseable, ParseCompletion, ParseOutcome, ParseSnapshot, RuleSpan, Spanned, has_no_skip, keep, run};
+use super::automata::{Army, AUTOMATON_BYTES};
+use super::observer::{self, ParseObserver};
+use super::recovery;
+use super::trace::{Outcome, Phase};
+
+/// Reusable state for parsing many inputs with the same lexer.
+///
+/// The [Parse] trait creates a fresh session for every call. When parsing lots of inputs, keeping
+/// one session around avoids reallocating the lexeme buffer each time, and starts each parse with
+/// an arena big enough for the largest parse so far. With
+/// [with_diagnostics](ParseSession::with_diagnostics), errors are also recorded in the session's
+/// diagnostics, so a batch can be checked once at the end.
+///
+/// The grammar itself is static, so a session is only needed per thread, not per grammar: any rule
+/// using the session's lexer can be parsed with it, and so can rules using another lexer of the
+/// same lexemes (see [Multiple Lexers](crate::prelude::lexer#multiple-lexers)).
+///
+/// ```
+/// use parce::prelude::*;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     A = 'a',
+///     B = 'b'
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum MyGrammar {
+///     Rule = "A B*"
+/// }
+///
+/// let mut session = ParseSession::new(MyLexer::default()).with_diagnostics(100);
+/// for input in ["ab", "abbb", "b", "a"] {
+///     let _ = session.parse_all::<MyGrammar>(input);
+/// }
+/// assert_eq!(session.diagnostics().len(), 1);
+/// assert_eq!(session.diagnostics()[0].input, "b");
+/// ```
+///
+/// [Parse]: super::Parse
+pub struct ParseSession<L: Lexer> {
+    lexer: L,
+    lexemes: Vec<SpannedLexeme<L::Lexemes>>,
+    arena_capacity: usize,
+    memory_limit: Option<usize>,
+    depth_limit: Option<usize>,
+    node_limit: Option<usize>,
+    /// The deadline of the parse running in [parse_with_deadline](Self::parse_with_deadline).
+    deadline: Option<Instant>,
+    memory_used: usize,
+    diagnostics: Vec<ParceError>,
+    /// How many errors [diagnostics](Self::diagnostics) can hold. Nothing is recorded at 0.
+    diagnostics_limit: usize,
+    /// The [Unclosed](ParsePhaseFailure::Unclosed) errors of the last parse that closed the
+    /// `#[pair(...)]` delimiters left open in its input.
+    unclosed: Vec<ParceError>,
+    warnings: Vec<ParceWarning>,
+    progress: Option<Progress>,
+    filters: Vec<Box<dyn TokenFilter<L::Lexemes>>>,
+    skip: Option<Box<dyn Fn(L::Lexemes) -> bool + Send>>,
+    trivia: Option<TriviaIndex<L::Lexemes>>,
+    interner: Option<Interner>,
+    version: Option<GrammarVersion>,
+    allocator: Option<Box<dyn Allocator + Send>>
+}
+
+/// A callback run every `every` lexemes.
+struct Progress {
+    every: usize,
+    callback: Box<dyn FnMut(usize) + Send>
+}
+
+impl<L: Lexer> Debug for ParseSession<L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseSession")
+            .field("lexer", &self.lexer)
+            .field("lexemes", &self.lexemes)
+            .field("arena_capacity", &self.arena_capacity)
+            .field("memory_limit", &self.memory_limit)
+            .field("depth_limit", &self.depth_limit)
+            .field("node_limit", &self.node_limit)
+            .field("deadline", &self.deadline)
+            .field("memory_used", &self.memory_used)
+            .field("diagnostics", &self.diagnostics)
+            .field("diagnostics_limit", &self.diagnostics_limit)
+            .field("unclosed", &self.unclosed)
+            .field("warnings", &self.warnings)
+            .field("progress", &self.progress.as_ref().map(|p| p.every))
+            .field("filters", &self.filters.len())
+            .field("skip", &self.skip.is_some())
+            .field("trivia", &self.trivia)
+            .field("interner", &self.interner)
+            .field("version", &self.version)
+            .field("allocator", &self.allocator.is_some())
+            .finish()
+    }
+}
+
+impl<L: Lexer + Default> Default for ParseSession<L> {
+    fn default() -> Self {
+        ParseSession::new(L::default())
+    }
+}
+
+impl<L: Lexer> ParseSession<L> {
+    /// Creates a session that lexes every input starting from `lexer`'s mode.
+    pub fn new(lexer: L) -> Self {
+        ParseSession {
+            lexer,
+            lexemes: vec![],
+            arena_capacity: 10,
+            memory_limit: None,
+            depth_limit: None,
+            node_limit: None,
+            deadline: None,
+            memory_used: 0,
+            diagnostics: vec![],
+            diagnostics_limit: 0,
+            unclosed: vec![],
+            warnings: vec![],
+            progress: None,
+            filters: vec![],
+            skip: None,
+            trivia: None,
+            interner: None,
+            version: None,
+            allocator: None
+        }
+    }
+
+    /// Calls `callback` with the number of lexemes parsed so far, every `every` lexemes and once
+    /// more when the parser stops. Useful for progress bars, or for deciding to give up on a huge
+    /// input. Lexing happens all at once before parsing starts, and isn't reported.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// # #[lexer(MyLexer)]
+    /// # enum MyLexemes { A = 'a' }
+    /// # #[parser(MyLexer)]
+    /// # enum MyGrammar { Rule = "A+" }
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let seen = Arc::new(Mutex::new(vec![]));
+    /// let log = seen.clone();
+    /// let mut session = ParseSession::new(MyLexer::default())
+    ///     .with_progress(2, move |consumed| log.lock().unwrap().push(consumed));
+    /// session.parse_all::<MyGrammar>("aaaaa").unwrap();
+    /// assert_eq!(*seen.lock().unwrap(), vec![2, 4, 5]);
+    /// ```
+    pub fn with_progress(mut self, every: usize, callback: impl FnMut(usize) + Send + 'static) -> Self {
+        self.progress = Some(Progress {
+            every: every.max(1),
+            callback: Box::new(callback)
+        });
+        self
+    }
+
+    /// Runs `filter` over every input's lexemes before parsing, after any filters added before it.
+    /// See [TokenFilter]. The lexemes include the skipped ones when the grammar has `#[no_skip]`
+    /// productions, which need them kept in the input.
+    pub fn with_filter(mut self, filter: impl TokenFilter<L::Lexemes> + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Replaces the lexer's `#[skip]` lexemes for this session: lexemes that `skip` returns true for
+    /// are dropped before the parser sees them, and all others are kept, even ones declared
+    /// `#[skip]`. This lets a tool that needs comments, like a formatter or a doc extractor, parse
+    /// them with the same lexer that normally skips them. It runs before any [TokenFilter].
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Word = "[a-z]+",
+    ///     #[skip] Comment = "'#' [^ ]*",
+    ///     #[skip] Space = ' '
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Documented {
+    ///     Documented { docs: Vec<String>, name: String } = "(docs=Comment)* name=Word"
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default());
+    /// assert_eq!(
+    ///     session.parse_all::<Documented>("#hi a"),
+    ///     Ok(Documented::Documented { docs: vec![], name: "a".to_string() })
+    /// );
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default()).with_skip(|l| l == MyLexemes::Space);
+    /// assert_eq!(
+    ///     session.parse_all::<Documented>("#hi a"),
+    ///     Ok(Documented::Documented { docs: vec!["#hi".to_string()], name: "a".to_string() })
+    /// );
+    /// ```
+    pub fn with_skip(mut self, skip: impl Fn(L::Lexemes) -> bool + Send + 'static) -> Self {
+        self.skip = Some(Box::new(skip));
+        self
+    }
+
+    /// Stops any parse whose automata take more than `bytes` bytes, with a
+    /// [ResourceExhausted](ParsePhaseFailure::ResourceExhausted) error. The parser keeps every
+    /// alternative it is still considering, so ambiguous or deeply nested input can take a lot of
+    /// memory; services parsing untrusted input can use this to bound it. The count only depends
+    /// on the grammar and the input, so the same input always fails in the same place.
+    ///
+    /// Only the automata are counted, not the lexemes or the parsed result. See
+    /// [memory_used](Self::memory_used) for how much a parse took.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+    ///
+    /// # #[lexer(MyLexer)]
+    /// # enum MyLexemes { A = 'a' }
+    /// #[parser(MyLexer)]
+    /// enum Ambiguous {
+    ///     Rule = "A* A*"
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default());
+    /// session.parse_all::<Ambiguous>("aaaa").unwrap();
+    /// let needed = session.memory_used();
+    ///
+    /// let mut session = session.with_memory_limit(needed / 2);
+    /// let error = session.parse_all::<Ambiguous>("aaaa").unwrap_err();
+    /// assert_eq!(error.info, ParceErrorInfo::parse(ParsePhaseFailure::ResourceExhausted));
+    /// assert!(session.memory_used() > needed / 2);
+    /// ```
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Fails any parse whose tree nests productions more than `depth` deep, counting the rule being
+    /// parsed as 1, with a [DepthLimit](ParsePhaseFailure::DepthLimit) error at the production
+    /// that went too deep.
+    ///
+    /// The parser itself doesn't recurse, but building the value does, and so does dropping it, so
+    /// input like a hundred thousand nested arrays can run out of stack after it has parsed. The
+    /// tree is checked before it is built, so a service can limit the depth to what its stack can
+    /// take. Groups inside a production, like `(Comma 0)*`, don't count.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Number = "[0-9]+",
+    ///     LBracket = '[',
+    ///     RBracket = ']'
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Value {
+    ///     Number = "Number",
+    ///     Array(Vec<Value>) = "LBracket 0* RBracket"
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default()).with_depth_limit(3);
+    /// assert!(session.parse_all::<Value>("[[1]]").is_ok());
+    /// let error = session.parse_all::<Value>("[[[1]]]").unwrap_err();
+    /// assert_eq!((error.start, error.info), (3, ParceErrorInfo::parse(ParsePhaseFailure::DepthLimit)));
+    ///
+    /// let deep = format!("{}1{}", "[".repeat(100_000), "]".repeat(100_000));
+    /// assert!(session.parse_all::<Value>(&deep).is_err());
+    /// ```
+    pub fn with_depth_limit(mut self, depth: usize) -> Self {
+        self.depth_limit = Some(depth);
+        self
+    }
+
+    /// Fails any parse whose tree has more than `nodes` productions in it, with a
+    /// [NodeLimit](ParsePhaseFailure::NodeLimit) error at the first production past the limit.
+    /// Like [with_depth_limit](Self::with_depth_limit), the tree is checked before it is built, so
+    /// the limit also bounds the memory the value takes.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+    ///
+    /// # #[lexer(MyLexer)]
+    /// # enum MyLexemes { Number = "[0-9]+", Comma = ',', #[skip] Space = ' ' }
+    /// #[parser(MyLexer)]
+    /// enum List {
+    ///     List(Vec<Item>) = "(0 Comma?)*"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Item {
+    ///     Number = "Number"
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default()).with_node_limit(3);
+    /// assert!(session.parse_all::<List>("1, 2").is_ok());
+    /// let error = session.parse_all::<List>("1, 2, 3").unwrap_err();
+    /// assert_eq!((error.start, error.info), (6, ParceErrorInfo::parse(ParsePhaseFailure::NodeLimit)));
+    /// ```
+    pub fn with_node_limit(mut self, nodes: usize) -> Self {
+        self.node_limit = Some(nodes);
+        self
+    }
+
+    /// Allocates the parser's automata with `allocator` instead of the global allocator, like a
+    /// bump or region allocator that is reset between requests. Lists of children that spill out of
+    /// their automata still use the global allocator, and so does the lexeme buffer; lexers can
+    /// also fill a buffer from another allocator on their own, see [LexemeBuffer](crate::lexer::LexemeBuffer).
+    /// The rules in reference fields go in the [NodeArena] given to
+    /// [parse_all_in](Self::parse_all_in), which can have its own allocator. Not available with
+    /// the `safe` feature.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    /// use parce::allocator_api2::alloc::{AllocError, Allocator, Global};
+    /// use std::alloc::Layout;
+    /// use std::ptr::NonNull;
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes { A = 'a' }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum MyGrammar { Rule = "A+" }
+    ///
+    /// /// Counts the bytes it hands out.
+    /// #[derive(Clone, Default)]
+    /// struct Counting(Arc<AtomicUsize>);
+    ///
+    /// unsafe impl Allocator for Counting {
+    ///     fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+    ///         self.0.fetch_add(layout.size(), Ordering::Relaxed);
+    ///         Global.allocate(layout)
+    ///     }
+    ///
+    ///     unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+    ///         Global.deallocate(ptr, layout)
+    ///     }
+    /// }
+    ///
+    /// let counting = Counting::default();
+    /// let mut session = ParseSession::new(MyLexer::default()).with_allocator(counting.clone());
+    /// session.parse_all::<MyGrammar>("aaa").unwrap();
+    /// assert!(counting.0.load(Ordering::Relaxed) >= session.memory_used());
+    /// ```
+    #[cfg(not(feature = "safe"))]
+    pub fn with_allocator(mut self, allocator: impl Allocator + Send + 'static) -> Self {
+        self.allocator = Some(Box::new(allocator));
+        self
+    }
+
+    /// The bytes taken by the automata of the most recent parse, including
+    /// [feed](Self::feed) and [finish](Self::finish). Like
+    /// [with_memory_limit](Self::with_memory_limit), only the automata are counted.
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    /// Keeps the lexemes that are skipped (by their `#[skip]` declaration, or by
+    /// [with_skip](Self::with_skip)) in a [TriviaIndex] instead of dropping them, so they can be
+    /// found with [trivia](Self::trivia) after each parse.
+    ///
+    /// The index is built before any [TokenFilter] runs, so [TriviaIndex::leading] lines up with
+    /// [lexemes](Self::lexemes) unless a filter added or removed some; [TriviaIndex::in_range]
+    /// works either way.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Word = "[a-z]+",
+    ///     #[skip] Comment = "'#' [^ ]*",
+    ///     #[skip] Space = ' '
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Words {
+    ///     Words(Vec<String>) = "(0=Word)*"
+    /// }
+    ///
+    /// let text = "#first a #second b";
+    /// let mut session = ParseSession::new(MyLexer::default()).with_trivia();
+    /// let (_, tree) = session.parse_tree::<Words>(text).unwrap();
+    ///
+    /// let trivia = session.trivia().unwrap();
+    /// assert_eq!(trivia.leading(1)[1].text(text), "#second");
+    /// // The span of the rule covers the trivia between its lexemes, but not before its first.
+    /// assert_eq!(trivia.in_range(tree.start..tree.end).len(), 3);
+    /// assert_eq!(trivia.leading(0).len(), 2);
+    /// ```
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = Some(TriviaIndex::default());
+        self
+    }
+
+    /// The trivia of the most recent input, if the session was made [with_trivia](Self::with_trivia).
+    /// Empty if lexing failed.
+    pub fn trivia(&self) -> Option<&TriviaIndex<L::Lexemes>> {
+        self.trivia.as_ref()
+    }
+
+    /// Interns every [Symbol](crate::convert::Symbol) field from this session's parses into
+    /// `interner`, instead of the thread's.
+    pub fn with_interner(mut self, interner: Interner) -> Self {
+        self.interner = Some(interner);
+        self
+    }
+
+    /// The session's interner, if it has one.
+    pub fn interner(&self) -> Option<&Interner> {
+        self.interner.as_ref()
+    }
+
+    /// Removes and returns the session's interner. Later parses intern into the thread's interner.
+    pub fn take_interner(&mut self) -> Option<Interner> {
+        self.interner.take()
+    }
+
+    /// Lexes and parses every input as `version` of the grammar, leaving out the lexemes and
+    /// productions it doesn't allow. Without this, the session uses the thread's current version,
+    /// which is [all](GrammarVersion::all) of them unless it is inside [GrammarVersion::run].
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// use parce::version::GrammarVersion;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Ident = "[a-z]+",
+    ///     Question = '?',
+    ///     #[since(2)] Arrow = "'=>'",
+    ///     #[skip] Space = ' '
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Expr {
+    ///     Var(String) = "0=Ident",
+    ///     #[since(2)] Lambda(String, Box<Expr>) = "0=Ident Arrow 1",
+    ///     #[cfg_grammar(try)] Try(String) = "0=Ident Question"
+    /// }
+    ///
+    /// let mut v1 = ParseSession::new(MyLexer::default()).with_version(GrammarVersion::at(&[1]));
+    /// assert!(v1.parse_all::<Expr>("x => x").is_err());
+    ///
+    /// let mut v2 = ParseSession::new(MyLexer::default()).with_version(GrammarVersion::at(&[2]));
+    /// assert_eq!(v2.parse_all::<Expr>("x => x"), Ok(Expr::Lambda("x".to_string(), Box::new(Expr::Var("x".to_string())))));
+    /// assert!(v2.parse_all::<Expr>("x?").is_err());
+    ///
+    /// let mut v2_try = ParseSession::new(MyLexer::default()).with_version(GrammarVersion::at(&[2]).with_feature("try"));
+    /// assert!(v2_try.parse_all::<Expr>("x?").is_ok());
+    ///
+    /// // The default allows everything.
+    /// assert!("x => y?".parse::<Expr>().is_ok());
+    /// ```
+    pub fn with_version(mut self, version: GrammarVersion) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Sets `flag` in the session's [version](Self::with_version), so the lexemes and productions
+    /// marked `#[flag(...)]` with it match. Without a version, it is set on [all](GrammarVersion::all)
+    /// of them.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Number = "[0-9]+",
+    ///     #[flag(hex)] Hex = "'0x' [0-9a-f]+",
+    ///     Ident = "[a-z]+"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Literal {
+    ///     Number = "Number",
+    ///     #[flag(hex)] Hex = "Hex"
+    /// }
+    ///
+    /// assert!(ParseSession::new(MyLexer::default()).parse_all::<Literal>("0xff").is_err());
+    /// let mut hex = ParseSession::new(MyLexer::default()).with_flag("hex");
+    /// assert_eq!(hex.parse_all::<Literal>("0xff"), Ok(Literal::Hex));
+    /// ```
+    pub fn with_flag(mut self, flag: &str) -> Self {
+        self.version = Some(self.version.take().unwrap_or_default().with_flag(flag));
+        self
+    }
+
+    /// The version given to [with_version](Self::with_version), if any.
+    pub fn version(&self) -> Option<&GrammarVersion> {
+        self.version.as_ref()
+    }
+
+    /// The lexer (and its starting mode) used for each input.
+    pub fn lexer(&self) -> L {
+        self.lexer
+    }
+
+    /// The lexemes of the most recent input after every [TokenFilter] ran, or the ones found before
+    /// the error if lexing failed.
+    pub fn lexemes(&self) -> &[SpannedLexeme<L::Lexemes>] {
+        &self.lexemes
+    }
+
+    /// Records the errors this session gives back in its [diagnostics](Self::diagnostics), until
+    /// there are `limit` of them. Later errors are still given back, just not recorded. Each error
+    /// holds a copy of its input, so without a limit a long-lived session would keep every input
+    /// that failed. By default nothing is recorded.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// # #[lexer(MyLexer)]
+    /// # enum MyLexemes { A = 'a' }
+    /// # #[parser(MyLexer)]
+    /// # enum MyGrammar { Rule = "A" }
+    /// let mut session = ParseSession::new(MyLexer::default());
+    /// assert!(session.parse_all::<MyGrammar>("aa").is_err());
+    /// assert!(session.diagnostics().is_empty());
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default()).with_diagnostics(2);
+    /// for input in ["aa", "", "aaa"] {
+    ///     assert!(session.parse_all::<MyGrammar>(input).is_err());
+    /// }
+    /// assert_eq!(session.diagnostics().iter().map(|e| e.input.as_str()).collect::<Vec<_>>(), vec!["aa", ""]);
+    ///
+    /// // Taking the errors makes room for more.
+    /// session.take_diagnostics();
+    /// assert!(session.parse_all::<MyGrammar>("aaa").is_err());
+    /// assert_eq!(session.diagnostics().len(), 1);
+    /// ```
+    pub fn with_diagnostics(mut self, limit: usize) -> Self {
+        self.diagnostics_limit = limit;
+        self
+    }
+
+    /// The errors recorded by this session so far, oldest first. See
+    /// [with_diagnostics](Self::with_diagnostics).
+    pub fn diagnostics(&self) -> &[ParceError] {
+        &self.diagnostics
+    }
+
+    /// Removes and returns the recorded errors.
+    pub fn take_diagnostics(&mut self) -> Vec<ParceError> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// A warning for every lexeme and production marked `#[deprecated_syntax(message = "...")]` in
+    /// the inputs this session parsed, oldest first. The deprecated syntax still parses; the warning
+    /// only points at where it was used, for telling users how to migrate.
+    ///
+    /// Warnings are only added when a parse succeeds, and only for the part of the input it
+    /// covered. Skipped lexemes aren't checked, since the lexer leaves them out.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     #[deprecated_syntax(message = "use != instead")] Diamond = "'<>'",
+    ///     NotEqual = "'!='",
+    ///     Ident = "[a-z]+",
+    ///     Colon = ':',
+    ///     Equal = '=',
+    ///     #[skip] Space = ' '
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Statement {
+    ///     Compare(String, String) = "0=Ident (Diamond | NotEqual) 1=Ident",
+    ///     Assign(String, String) = "0=Ident Colon Equal 1=Ident",
+    ///     #[deprecated_syntax(message = "use := instead")]
+    ///     OldAssign(String, String) = "0=Ident Equal 1=Ident"
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default());
+    /// for input in ["a != b", "a <> b", "a := b", "a = b"] {
+    ///     session.parse_all::<Statement>(input).unwrap();
+    /// }
+    /// let warnings: Vec<String> = session.warnings().iter().map(|w| w.to_string()).collect();
+    /// assert_eq!(warnings, vec![
+    ///     "Diamond at 2..4 is deprecated: use != instead",
+    ///     "Statement::OldAssign at 0..5 is deprecated: use := instead"
+    /// ]);
+    /// ```
+    pub fn warnings(&self) -> &[ParceWarning] {
+        &self.warnings
+    }
+
+    /// Removes and returns the recorded warnings.
+    pub fn take_warnings(&mut self) -> Vec<ParceWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Like [Parse::parse_max](super::Parse::parse_max), reusing this session's buffers.
+    pub fn parse_max<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str) -> Result<(O::Output<'t>, ParseCompletion), ParceError> {
+        self.parse_spanned::<O>(text, None, false).map(|(result, completion, _)| (result, completion))
+    }
+
+    /// Like [Parse::parse_outcome](super::Parse::parse_outcome), reusing this session's buffers.
+    pub fn parse_outcome<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str) -> ParseOutcome<O::Output<'t>> {
+        self.parse_max::<O>(text).into()
+    }
+
+    /// Like [Parse::parse_all](super::Parse::parse_all), reusing this session's buffers.
+    pub fn parse_all<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str) -> Result<O::Output<'t>, ParceError> {
+        self.parse_tree::<O>(text).map(|(result, _)| result)
+    }
+
+    /// Like [Parse::parse_tree](super::Parse::parse_tree), reusing this session's buffers.
+    pub fn parse_tree<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str) -> Result<(O::Output<'t>, RuleSpan), ParceError> {
+        self.parse_tree_in::<O>(text, None, false)
+    }
+
+    /// Like [parse_all](Self::parse_all), calling `observer` for every rule of the parse, for
+    /// building symbol tables or indexes without walking the tree afterward. See [ParseObserver].
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    /// use parce::parser::RuleSpan;
+    /// use parce::parser::observer::ParseObserver;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Let = "'let'",
+    ///     Ident = "[a-z]+",
+    ///     LBrace = '{',
+    ///     RBrace = '}',
+    ///     Semicolon = ';',
+    ///     #[skip] Space = ' '
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Block {
+    ///     Block(Vec<Statement>) = "LBrace 0* RBrace"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Statement {
+    ///     Let(Binding) = "Let 0 Semicolon",
+    ///     Block(Block) = "0"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Binding {
+    ///     Name = "Ident"
+    /// }
+    ///
+    /// /// Each name with how deeply nested the block defining it is.
+    /// #[derive(Default)]
+    /// struct Symbols {
+    ///     depth: usize,
+    ///     names: Vec<(String, usize)>
+    /// }
+    ///
+    /// impl ParseObserver for Symbols {
+    ///     fn rule_start(&mut self, span: &RuleSpan, text: &str) {
+    ///         match span.rule {
+    ///             "Block" => self.depth += 1,
+    ///             "Binding" => self.names.push((text.to_string(), self.depth)),
+    ///             _ => {}
+    ///         }
+    ///     }
+    ///
+    ///     fn rule_end(&mut self, span: &RuleSpan, _text: &str) {
+    ///         if span.rule == "Block" {
+    ///             self.depth -= 1;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default());
+    /// let mut symbols = Symbols::default();
+    /// session.parse_observed::<Block>("{ let a; { let b; } let c; }", &mut symbols).unwrap();
+    /// assert_eq!(symbols.names, vec![("a".to_string(), 1), ("b".to_string(), 2), ("c".to_string(), 1)]);
+    /// ```
+    pub fn parse_observed<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str, observer: &mut dyn ParseObserver) -> Result<O::Output<'t>, ParceError> {
+        let (result, tree) = self.parse_tree::<O>(text)?;
+        observer::observe(&tree, text, observer);
+        Ok(result)
+    }
+
+    /// Like [parse_all](Self::parse_all), but gives up with a [Timeout](ParsePhaseFailure::Timeout)
+    /// error if the parse isn't done within `timeout`. Lexing counts toward the time, but the
+    /// clock is only checked while the automata run, every few dozen of them, so a parse can go
+    /// a little over. Unlike [with_memory_limit](Self::with_memory_limit), where a parse times
+    /// out depends on the machine and its load. A `timeout` too long to add to the current time,
+    /// like [Duration::MAX], never runs out.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+    /// use std::time::Duration;
+    ///
+    /// # #[lexer(MyLexer)]
+    /// # enum MyLexemes { A = 'a' }
+    /// #[parser(MyLexer)]
+    /// enum Ambiguous {
+    ///     Rule = "A* A* A*"
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default());
+    /// let input = "a".repeat(400);
+    /// let error = session.parse_with_deadline::<Ambiguous>(&input, Duration::ZERO).unwrap_err();
+    /// assert_eq!(error.info, ParceErrorInfo::parse(ParsePhaseFailure::Timeout));
+    /// assert!(session.parse_with_deadline::<Ambiguous>("aaa", Duration::from_secs(60)).is_ok());
+    /// ```
+    pub fn parse_with_deadline<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str, timeout: Duration) -> Result<O::Output<'t>, ParceError> {
+        self.deadline = Instant::now().checked_add(timeout);
+        let session = ClearDeadline(self);
+        session.0.parse_all::<O>(text)
+    }
+
+    /// Like [parse_all](Self::parse_all), allocating the rules stored in reference fields in
+    /// `arena`. See [NodeArena]. Not available with the `safe` feature.
+    #[cfg(not(feature = "safe"))]
+    pub fn parse_all_in<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str, arena: &'t NodeArena<impl Allocator>) -> Result<O::Output<'t>, ParceError> {
+        self.parse_tree_in::<O>(text, Some(arena), false).map(|(result, _)| result)
+    }
+
+    /// Like [parse_all](Self::parse_all), but when the input only fails because delimiters declared
+    /// with `#[pair(...)]` are left open, closes them at the end of the input and gives back the
+    /// value, along with an [Unclosed](ParsePhaseFailure::Unclosed) error for each opener,
+    /// outermost first. The errors are recorded in the [diagnostics](Self::diagnostics) too. The
+    /// closers are zero-length lexemes at the end of the input, so the rules that end with them
+    /// end there.
+    ///
+    /// ```
+    /// # use parce::prelude::*;
+    /// use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+    ///
+    /// #[lexer(MyLexer)]
+    /// #[pair(LBrace, RBrace)]
+    /// enum MyLexemes {
+    ///     Word = "[a-z]+",
+    ///     LBrace = '{',
+    ///     RBrace = '}',
+    ///     #[skip] Space = ' '
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Item {
+    ///     Word = "Word",
+    ///     Block(Vec<Item>) = "LBrace 0* RBrace"
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default());
+    /// let (item, errors) = session.parse_all_closed::<Item>("{ a { b } { c").unwrap();
+    /// assert_eq!(item, Item::Block(vec![Item::Word, Item::Block(vec![Item::Word]), Item::Block(vec![Item::Word])]));
+    /// assert_eq!(errors.iter().map(|e| e.start).collect::<Vec<_>>(), vec![0, 10]);
+    /// assert_eq!(errors[1].info, ParceErrorInfo::Parse {
+    ///     failure: ParsePhaseFailure::Unclosed { opener: "LBrace".into(), closer: "RBrace".into() },
+    ///     stack: vec!["Item", "Item"]
+    /// });
+    ///
+    /// // Input that is broken some other way still fails.
+    /// assert!(session.parse_all_closed::<Item>("{ a } }").is_err());
+    /// assert_eq!(session.parse_all_closed::<Item>("{ a }").unwrap().1, vec![]);
+    /// ```
+    pub fn parse_all_closed<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str) -> Result<(O::Output<'t>, Vec<ParceError>), ParceError> {
+        self.unclosed.clear();
+        let (result, _) = self.parse_tree_in::<O>(text, None, true)?;
+        Ok((result, std::mem::take(&mut self.unclosed)))
+    }
+
+    fn parse_tree_in<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str, arena: Option<&'t dyn Place>, close: bool) -> Result<(O::Output<'t>, RuleSpan), ParceError> {
+        let warnings = self.warnings.len();
+        let (result, completion, tree) = self.parse_spanned::<O>(text, arena, close)?;
+        match completion {
+            ParseCompletion::Complete => Ok((result, tree)),
+            ParseCompletion::Incomplete(n) => {
+                // The parse failed after all, so what it matched isn't warned about.
+                self.warnings.truncate(warnings);
+                self.record(Err(ParceError {
+                input: text.to_string(),
+                start: n+1,
+                info: ParceErrorInfo::parse(ParsePhaseFailure::LeftoverLexemes)
+                }))
+            }
+        }
+    }
+
+    /// Parses one value after another from `text`, until the input runs out. Skipped lexemes
+    /// between the values are ignored, like the newlines between the documents in a stream of
+    /// JSON values, or the whitespace between commands in a REPL history.
+    ///
+    /// The input is lexed once, when this is called. Each value is the longest one that can be
+    /// parsed where the previous one stopped, like [parse_max](Self::parse_max). When a value
+    /// fails to parse, the error is yielded and parsing starts again after the lexeme where it
+    /// failed, so one bad value doesn't stop the rest from being read. A lexing error is yielded
+    /// alone, since there are no lexemes to parse.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     Number = "[0-9]+",
+    ///     LBracket = '[',
+    ///     RBracket = ']',
+    ///     #[skip] Space = "[ \n]"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Value {
+    ///     List(Vec<Value>) = "LBracket 0* RBracket",
+    ///     Number(u32) = "0=Number"
+    /// }
+    ///
+    /// let mut session = ParseSession::new(MyLexer::default());
+    /// let values: Vec<_> = session.parse_stream_of::<Value>("[1 2]\n3\n] [4]").collect();
+    /// assert_eq!(values[0], Ok(Value::List(vec![Value::Number(1), Value::Number(2)])));
+    /// assert_eq!(values[1], Ok(Value::Number(3)));
+    /// assert_eq!(values[2].as_ref().unwrap_err().start, 8);
+    /// assert_eq!(values[3], Ok(Value::List(vec![Value::Number(4)])));
+    /// assert_eq!(values.len(), 4);
+    /// ```
+    pub fn parse_stream_of<'s, 't, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&'s mut self, text: &'t str) -> ParseStream<'s, 't, L, O> {
+        let error = self.lex(text, has_no_skip::<O>()).err();
+        ParseStream {
+            session: self,
+            text,
+            next: 0,
+            error,
+            _rule: PhantomData
+        }
+    }
+
+    /// Lexes `text` and runs the automata of `snapshot` over its lexemes, as if it were the rest of
+    /// the input so far. `text` is added to the end of the snapshot's text. Use
+    /// [finish](Self::finish) to end the input, or keep feeding more.
+    ///
+    /// A lexing error is returned as it is, relative to `text`, and leaves the snapshot unchanged.
+    /// Input that can't parse however it continues isn't an error here; it is reported by
+    /// [finish](Self::finish), and shown by [ParseSnapshot::is_stuck].
+    pub fn feed<O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, snapshot: &mut ParseSnapshot<O>, text: &str) -> Result<(), ParceError> {
+        self.lex(text, has_no_skip::<O>())?;
+        let (old_text, old_lexemes) = (snapshot.text.len(), snapshot.lexemes.len());
+        snapshot.text.push_str(text);
+        snapshot.lexemes.extend(self.lexemes.iter().map(|l| SpannedLexeme { start: l.start + old_text, ..*l }));
+
+        let phase = Phase::parse(O::rule_info().name, self.lexemes.len());
+        let advanced = {
+            let army = Army::with_capacity_in(self.capacity(), allocator(&self.allocator));
+            let mut engine = snapshot.restore(&army);
+            let _kept = keep(self.kept::<O>());
+            let advanced = versioned(&self.version, || engine.advance::<O>(&snapshot.lexemes, &snapshot.text, &army, self.limits(), &mut |_| {}));
+            self.memory_used = army.bytes();
+            self.arena_capacity = self.arena_capacity.max(army.len());
+            if advanced.is_ok() {
+                snapshot.save(engine);
+            }
+            advanced
+        };
+        let result = match advanced {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                snapshot.text.truncate(old_text);
+                snapshot.lexemes.truncate(old_lexemes);
+                self.record(Err(e))
+            }
+        };
+        phase.end(&result, self.outcome(self.lexemes.len()));
+        result
+    }
+
+    /// Ends the input of `snapshot`, and parses everything fed to it, like
+    /// [parse_max](Self::parse_max). The snapshot isn't changed, so if the input was only
+    /// unfinished, it can still be fed more.
+    pub fn finish<'s, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, snapshot: &'s ParseSnapshot<O>) -> Result<(O::Output<'s>, ParseCompletion), ParceError> {
+        if snapshot.lexemes.is_empty() {
+            return self.record(Err(ParceError {
+                input: snapshot.text.clone(),
+                start: 0,
+                info: ParceErrorInfo::parse(ParsePhaseFailure::NothingToParse)
+            }));
+        }
+        let phase = Phase::parse(O::rule_info().name, snapshot.lexemes.len());
+        let result = {
+            let army = Army::with_capacity_in(self.capacity(), allocator(&self.allocator));
+            let limits = self.limits();
+            let kept = self.kept::<O>();
+            let result = versioned(&self.version, || assembling(&mut self.interner, None, kept, || {
+                snapshot.restore(&army).finish::<O>(&snapshot.lexemes, &snapshot.text, &army, limits)
+            }));
+            self.memory_used = army.bytes();
+            result
+        };
+        let result = self.record(result.map(|(result, completion, _)| (result, completion)));
+        phase.end(&result, self.outcome(snapshot.lexemes.len()));
+        result
+    }
+
+    /// How many automata to make room for at the start of a parse: as many as the largest parse so
+    /// far needed, but no more than the memory limit allows.
+    fn capacity(&self) -> usize {
+        match self.memory_limit {
+            Some(limit) => self.arena_capacity.min(limit / AUTOMATON_BYTES).max(1),
+            None => self.arena_capacity
+        }
+    }
+
+    fn limits(&self) -> Limits {
+        Limits {
+            memory: self.memory_limit,
+            deadline: self.deadline,
+            depth: self.depth_limit,
+            nodes: self.node_limit
+        }
+    }
+
+    /// The kinds of lexemes that parses of `O` keep in the input although they are skipped, for
+    /// its `#[no_skip]` productions. Nothing is kept for grammars without any.
+    fn kept<O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&self) -> KindSet {
+        if !has_no_skip::<O>() {
+            return KindSet::default();
+        }
+        let kinds: Vec<L::Lexemes> = (0..L::Lexemes::KIND_COUNT)
+            .filter_map(L::Lexemes::from_kind_id)
+            .filter(|&l| self.skipped(l))
+            .collect();
+        KindSet::of(&kinds)
+    }
+
+    /// Whether `lexeme` is left out of the input: by [with_skip](Self::with_skip) if it was
+    /// given, or else by `#[skip]`.
+    fn skipped(&self, lexeme: L::Lexemes) -> bool {
+        match &self.skip {
+            Some(skip) => skip(lexeme),
+            None => lexeme.is_skipped()
+        }
+    }
+
+    /// Lexes `text` into the buffer. With `keep`, the skipped lexemes stay in it, for a grammar
+    /// with `#[no_skip]` productions.
+    fn lex(&mut self, text: &str, keep: bool) -> Result<(), ParceError> {
+        let phase = Phase::lex(self.lexer, text.len());
+        let version = self.version.clone();
+        let result = versioned(&version, || self.lex_buffer(text, keep));
+        phase.end(&result, Outcome { lexemes: self.lexemes.len(), ..Outcome::default() });
+        result
+    }
+
+    /// Lexes `text` into the buffer, and runs the filters over it. Skipped lexemes that are kept are
+    /// still put in the [TriviaIndex], if there is one.
+    fn lex_buffer(&mut self, text: &str, keep: bool) -> Result<(), ParceError> {
+        self.lexemes.clear();
+        let lexed = match (&self.skip, &mut self.trivia) {
+            (None, None) if !keep => self.lexer.lex_cursor_into(StrCursor::new(text), &mut self.lexemes),
+            (skip, trivia) => self.lexer.lex_all_cursor_into(StrCursor::new(text), &mut self.lexemes).map(|()| {
+                let skipped = |l: L::Lexemes| match skip {
+                    Some(skip) => skip(l),
+                    None => l.is_skipped()
+                };
+                match trivia {
+                    Some(trivia) if keep => trivia.take_from(&mut self.lexemes.clone(), skipped),
+                    Some(trivia) => trivia.take_from(&mut self.lexemes, skipped),
+                    None if keep => {}
+                    None => self.lexemes.retain(|l| !skipped(l.data))
+                }
+            })
+        };
+        if let Err(e) = lexed {
+            if let Some(trivia) = &mut self.trivia {
+                *trivia = TriviaIndex::default();
+            }
+            return self.record(Err(e));
+        }
+        for filter in &mut self.filters {
+            filter.apply(&mut self.lexemes, text);
+        }
+        Ok(())
+    }
+
+    fn parse_spanned<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str, arena: Option<&'t dyn Place>, close: bool) -> Spanned<'t, O> {
+        self.lex(text, has_no_skip::<O>())?;
+        self.parse_lexed::<O>(text, 0, arena, close)
+    }
+
+    /// Parses the lexemes in the buffer starting from `first`. With `close`, input that only fails
+    /// because `#[pair(...)]` delimiters are left open is closed; see [parse_all_closed](Self::parse_all_closed).
+    fn parse_lexed<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str, first: usize, arena: Option<&'t dyn Place>, close: bool) -> Spanned<'t, O> {
+        let count = self.lexemes.len() - first;
+        let phase = Phase::parse(O::rule_info().name, count);
+        let result = match self.run_lexed::<O>(text, first, arena, true) {
+            Err(error) => self.close_pairs::<O>(text, first, arena, close, error),
+            result => result
+        };
+        let result = self.record(result);
+        if let Ok((_, _, tree)) = &result {
+            deprecations(&self.lexemes[first..], tree, &mut self.warnings);
+        }
+        phase.end(&result, self.outcome(count));
+        result
+    }
+
+    /// When `error` is the input ending inside `#[pair(...)]` delimiters, parses the lexemes from
+    /// `first` again with the missing closers added at the end. If all of them parse, gives back
+    /// the value with `close`, after keeping an [Unclosed](ParsePhaseFailure::Unclosed) error for
+    /// each opener in `unclosed` and recording them, and the error for the innermost opener without
+    /// it.
+    /// Otherwise gives back `error`.
+    fn close_pairs<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str, first: usize, arena: Option<&'t dyn Place>, close: bool, error: ParceError) -> Spanned<'t, O> {
+        let stack = match &error.info {
+            ParceErrorInfo::Parse { failure: ParsePhaseFailure::InputEndedTooSoon, stack } => stack.clone(),
+            _ => return Err(error)
+        };
+        let unclosed = recovery::unclosed(&self.lexemes[first..]);
+        if unclosed.is_empty() {
+            return Err(error);
+        }
+        let len = self.lexemes.len();
+        self.lexemes.extend(unclosed.iter().rev().map(|&(_, closer)| SpannedLexeme { data: closer, start: text.len(), len: 0 }));
+        let closed = self.run_lexed::<O>(text, first, arena, false);
+        self.lexemes.truncate(len);
+        let mut errors = unclosed.iter().map(|(opener, closer)| ParceError {
+            input: text.to_string(),
+            start: opener.start,
+            info: ParceErrorInfo::Parse {
+                failure: ParsePhaseFailure::Unclosed { opener: format!("{:?}", opener.data).into(), closer: format!("{:?}", closer).into() },
+                stack: stack.clone()
+            }
+        }).collect::<Vec<_>>();
+        match closed {
+            Ok((value, ParseCompletion::Complete, tree)) if close => {
+                for e in &errors {
+                    self.diagnose(e);
+                }
+                self.unclosed = errors;
+                Ok((value, ParseCompletion::Complete, tree))
+            }
+            Ok((_, ParseCompletion::Complete, _)) => Err(errors.pop().expect("there is an unclosed opener")),
+            _ => Err(error)
+        }
+    }
+
+    /// Runs the parser over the lexemes in the buffer starting from `first`, reporting `progress`.
+    fn run_lexed<'t, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>>(&mut self, text: &'t str, first: usize, arena: Option<&'t dyn Place>, progress: bool) -> Spanned<'t, O> {
+        let (capacity, limits, allocator, kept) = (self.capacity(), self.limits(), allocator(&self.allocator), self.kept::<O>());
+        let lexemes = &self.lexemes[first..];
+        let progress = self.progress.as_mut().filter(|_| progress);
+        let (result, used) = versioned(&self.version, || assembling(&mut self.interner, arena, kept, || match progress {
+            Some(Progress { every, callback }) => {
+                let (mut reached, mut reported) = (0, 0);
+                let result = run::<O>(lexemes, text, capacity, allocator, limits, &mut |consumed| {
+                    reached = consumed;
+                    if consumed % *every == 0 {
+                        reported = consumed;
+                        callback(consumed);
+                    }
+                });
+                if reached != reported {
+                    callback(reached);
+                }
+                result
+            }
+            None => run::<O>(lexemes, text, capacity, allocator, limits, &mut |_| {})
+        }));
+        self.arena_capacity = self.arena_capacity.max(used);
+        self.memory_used = used * AUTOMATON_BYTES;
+        result.map_err(|mut e| {
+            // Errors on the first lexeme are reported at the start of the input.
+            if let Some(lexeme) = lexemes.first().filter(|l| e.start < l.start && first > 0) {
+                e.start = lexeme.start;
+            }
+            e
+        })
+    }
+
+    fn outcome(&self, lexemes: usize) -> Outcome {
+        Outcome {
+            lexemes,
+            memory: self.memory_used,
+            diagnostics: self.diagnostics.len()
+        }
+    }
+
+    fn record<T>(&mut self, result: Result<T, ParceError>) -> Result<T, ParceError> {
+        if let Err(e) = &result {
+            self.diagnose(e);
+        }
+        result
+    }
+
+    /// Adds `error` to the diagnostics, if there is room.
+    fn diagnose(&mut self, error: &ParceError) {
+        if self.diagnostics.len() < self.diagnostics_limit {
+            self.diagnostics.push(error.clone());
+        }
+    }
+}
+
+/// Clears the deadline of a session when dropped, even if the parse panics.
+struct ClearDeadline<'s, L: Lexer>(&'s mut ParseSession<L>);
+
+impl<L: Lexer> Drop for ClearDeadline<'_, L> {
+    fn drop(&mut self) {
+        self.0.deadline = None;
+    }
+}
+
+/// Runs `parse` with `interner` as the current interner, `arena` as the arena for the rules it
+/// assembles, and `kept` as the kinds of skipped lexemes kept in its input.
+fn assembling<T>(interner: &mut Option<Interner>, arena: Option<&dyn Place>, kept: KindSet, parse: impl FnOnce() -> T) -> T {
+    let _interner = interner.as_mut().map(intern::enter);
+    let _arena = arena::enter(arena);
+    let _kept = keep(kept);
+    parse()
+}
+
+/// The allocator a session's automata come from: the one it was given, or the global allocator.
+fn allocator(allocator: &Option<Box<dyn Allocator + Send>>) -> &dyn Allocator {
+    match allocator {
+        Some(allocator) => &**allocator,
+        None => &Global
+    }
+}
+
+/// Adds a warning to `warnings` for every lexeme inside `tree` and every rule in it marked
+/// `#[deprecated_syntax(...)]`, in input order.
+fn deprecations<L: Lexeme>(lexemes: &[SpannedLexeme<L>], tree: &RuleSpan, warnings: &mut Vec<ParceWarning>) {
+    fn rules(span: &RuleSpan, found: &mut Vec<ParceWarning>) {
+        if let Some(message) = span.deprecated {
+            found.push(ParceWarning {
+                name: format!("{}::{}", span.rule, span.production),
+                message,
+                span: span.span()
+            });
+        }
+        for child in &span.children {
+            rules(child, found);
+        }
+    }
+
+    let mut found = vec![];
+    for lexeme in lexemes.iter().filter(|l| l.start >= tree.start && l.end() <= tree.end) {
+        if let Some(message) = lexeme.deprecation() {
+            found.push(ParceWarning {
+                name: format!("{:?}", lexeme.data),
+                message,
+                span: lexeme.span()
+            });
+        }
+    }
+    rules(tree, &mut found);
+    found.sort_by_key(|warning| warning.span);
+    warnings.extend(found);
+}
+
+/// Runs `f` in `version`, if there is one, and in the current version otherwise.
+fn versioned<T>(version: &Option<GrammarVersion>, f: impl FnOnce() -> T) -> T {
+    match version {
+        Some(version) => version.run(f),
+        None => f()
+    }
+}
+
+/// An iterator over the values parsed from one input. Returned by [ParseSession::parse_stream_of].
+pub struct ParseStream<'s, 't, L: Lexer, O> {
+    session: &'s mut ParseSession<L>,
+    text: &'t str,
+    /// The index of the lexeme to start the next value at.
+    next: usize,
+    error: Option<ParceError>,
+    _rule: PhantomData<O>
+}
+
+impl<'t, L: Lexer, O: Parseable<Lexer: Lexer<Lexemes = L::Lexemes>>> Iterator for ParseStream<'_, 't, L, O> {
+    type Item = Result<O::Output<'t>, ParceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            self.next = self.session.lexemes.len();
+            return Some(Err(error));
+        }
+        let lexemes = &self.session.lexemes;
+        if self.next >= lexemes.len() {
+            return None;
+        }
+        let first = self.next;
+        let mut result = self.session.parse_lexed::<O>(self.text, first, None, false);
+        let lexemes = &self.session.lexemes;
+        self.next = match &mut result {
+            Ok((_, ParseCompletion::Complete, _)) => lexemes.len(),
+            Ok((_, ParseCompletion::Incomplete(end), _)) => {
+                let next = first + lexemes[first..].partition_point(|l| l.start < *end);
+                // A value that matched no lexemes would be parsed again forever, so stop there.
+                if next == first {
+                    self.error = Some(ParceError {
+                        input: self.text.to_string(),
+                        start: *end,
+                        info: ParceErrorInfo::parse(ParsePhaseFailure::LeftoverLexemes)
+                    });
+                }
+                next
+            }
+            Err(e) => {
+                // An error before the value's first lexeme, like one at the start of the input when
+                // it begins with skipped text, is reported at that lexeme.
+                e.start = e.start.max(lexemes[first].start);
+                // Always move past at least one lexeme, so a value that keeps failing at the same
+                // place can't be parsed again forever.
+                (first + lexemes[first..].partition_point(|l| l.start <= e.start)).max(first + 1)
+            }
+        };
+        Some(result.map(|(value, _, _)| value))
+    }
+}
@@ -0,0 +1,372 @@
+//! Contains [ParseSnapshot], the state of a parse that stopped partway through its input.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use crate::lexer::{Lexer, SpannedLexeme};
+use super::automata::{Army, Continuation, Rawtomaton};
+use super::{Engine, Parseable, RuleId};
+
+/// A parse that has used all the input it was given so far, and can be picked up again when more
+/// arrives. A REPL can keep one across lines until a statement is complete, and a job reading a
+/// huge input can checkpoint one between chunks.
+///
+/// Start with [ParseSnapshot::new], hand it chunks of input with [ParseSession::feed], and end the
+/// input with [ParseSession::finish], which leaves the snapshot as it was, so it can be fed more
+/// if the input turns out to be unfinished. Each chunk is lexed separately, so chunks have to be
+/// split between lexemes, like at the ends of lines.
+///
+/// Nothing in a snapshot points into memory: the live automata are stored as [SavedAutomaton]s
+/// that refer to each other by index, and every field is public, so a snapshot can be written out
+/// in any format and rebuilt later, by filling in the fields of a [new](ParseSnapshot::new) one
+/// (lexemes can be stored by their [kind_id](crate::lexer::Lexeme::kind_id)). A rebuilt snapshot
+/// must be resumed with the same grammar, built from the same source.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::ParseSnapshot;
+/// use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Number = "[0-9]+",
+///     LParen = '(',
+///     RParen = ')',
+///     #[skip] Space = "[ \n]"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Value {
+///     List(Vec<Value>) = "LParen 0* RParen",
+///     Number(u32) = "0=Number"
+/// }
+///
+/// let mut session = ParseSession::new(MyLexer::default());
+/// let mut snapshot = ParseSnapshot::<Value>::new();
+///
+/// session.feed(&mut snapshot, "(1 (2\n").unwrap();
+/// let unfinished = session.finish(&snapshot).unwrap_err();
+/// assert!(matches!(unfinished.info, ParceErrorInfo::Parse { failure: ParsePhaseFailure::InputEndedTooSoon, .. }));
+///
+/// session.feed(&mut snapshot, "3))\n").unwrap();
+/// let (value, _) = session.finish(&snapshot).unwrap();
+/// assert_eq!(value, Value::List(vec![
+///     Value::Number(1),
+///     Value::List(vec![Value::Number(2), Value::Number(3)])
+/// ]));
+/// assert_eq!(snapshot.text, "(1 (2\n3))\n");
+/// assert_eq!(snapshot.position, 7);
+/// ```
+///
+/// [ParseSession::feed]: super::ParseSession::feed
+/// [ParseSession::finish]: super::ParseSession::finish
+pub struct ParseSnapshot<O: Parseable> {
+    /// All of the input fed so far.
+    pub text: String,
+    /// The lexemes of [text](Self::text).
+    pub lexemes: Vec<SpannedLexeme<<O::Lexer as Lexer>::Lexemes>>,
+    /// How many of the lexemes the automata have seen. This is all of them, unless every automaton
+    /// died before the end, in which case the input can't parse however it continues.
+    pub position: usize,
+    /// Every automaton still needed: the live ones, the ones they are nested in, and the matches
+    /// they have recorded.
+    pub automata: Vec<SavedAutomaton>,
+    /// The automata waiting for the next lexeme, in the order they will see it.
+    pub alive: Vec<usize>,
+    /// The automata that were alive before the last lexeme, for reporting where a failed parse went
+    /// wrong.
+    pub frontier: Vec<usize>,
+    /// The automata that matched the whole root rule, and the index of the lexeme each finished on.
+    pub victories: Vec<(usize, usize)>,
+    /// The production that won each cut (`!`), as the rule, the lexeme its productions started at,
+    /// and the winning route.
+    pub cuts: Vec<(RuleId, usize, u32)>,
+    _rule: PhantomData<O>
+}
+
+/// One automaton in a [ParseSnapshot]. Other automata are referred to by their index in
+/// [ParseSnapshot::automata].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SavedAutomaton {
+    /// The rule the automaton is parsing.
+    pub rule: RuleId,
+    /// The route it is on. See [Parseable::commands].
+    pub route: u32,
+    /// Its state on that route.
+    pub state: u32,
+    /// The index of the lexeme it started at.
+    pub lexeme_start: usize,
+    /// The automaton waiting for this one to finish, and what it does when this one does.
+    pub parent: Option<(usize, Continuation)>,
+    /// The automata that finished inside this one, in order.
+    pub children: Vec<usize>,
+    /// The production of an inlined rule this automaton stands in for, if any. See
+    /// [Automaton::inlined](super::automata::Automaton::inlined).
+    pub inlined: Option<(RuleId, u32)>,
+    /// How many errors were recovered from under it. See
+    /// [Automaton::errors](super::automata::Automaton::errors).
+    pub errors: u32
+}
+
+impl<O: Parseable> ParseSnapshot<O> {
+    /// A parse that hasn't been given any input yet.
+    pub fn new() -> Self {
+        ParseSnapshot {
+            text: String::new(),
+            lexemes: vec![],
+            position: 0,
+            automata: vec![],
+            alive: vec![],
+            frontier: vec![],
+            victories: vec![],
+            cuts: vec![],
+            _rule: PhantomData
+        }
+    }
+
+    /// Whether every automaton has died, so no more input can make this parse succeed.
+    pub fn is_stuck(&self) -> bool {
+        self.position > 0 && self.alive.is_empty()
+    }
+
+    /// Rebuilds the automata in `army`, or spawns the first ones if nothing has been fed yet.
+    pub(super) fn restore<'a>(&self, army: &'a Army<'a, '_>) -> Engine<'a> {
+        if self.position == 0 {
+            return Engine::start::<O>(army);
+        }
+        let autos: Vec<Rawtomaton> = self.automata.iter()
+            .map(|saved| army.spawn(saved.rule, saved.route, saved.lexeme_start))
+            .collect();
+        for (saved, &auto) in self.automata.iter().zip(&autos) {
+            auto.get_mut().state = saved.state;
+            auto.get_mut().parent = saved.parent.map(|(parent, cont)| (autos[parent], cont));
+            auto.get_mut().children = saved.children.iter().map(|&child| autos[child]).collect();
+            auto.get_mut().inlined = saved.inlined;
+            auto.get_mut().errors = saved.errors;
+        }
+        // Whether an automaton is inside a `#[no_skip]` production follows from the ones it is
+        // nested in, so it isn't saved.
+        let grammar = (O::table().reachable)();
+        for (saved, &auto) in self.automata.iter().zip(&autos) {
+            let mut current = Some(saved);
+            while let Some(saved) = current {
+                if grammar.no_skip(saved.rule, saved.route) {
+                    auto.get_mut().no_skip = true;
+                    break;
+                }
+                current = saved.parent.map(|(parent, _)| &self.automata[parent]);
+            }
+        }
+        Engine {
+            alive: self.alive.iter().map(|&i| autos[i]).collect(),
+            frontier: self.frontier.iter().map(|&i| autos[i]).collect(),
+            victories: self.victories.iter().map(|&(step, i)| (step, autos[i])).collect(),
+            cuts: self.cuts.iter().map(|&(rule, start, route)| ((rule, start), route)).collect(),
+            position: self.position
+        }
+    }
+
+    /// Replaces the automata with the ones in `engine`, and everything they point to.
+    pub(super) fn save<'a>(&mut self, engine: Engine<'a>) {
+        let mut indices: HashMap<usize, usize> = HashMap::new();
+        let mut automata = vec![];
+        let mut pending: VecDeque<Rawtomaton> = engine.alive.iter()
+            .chain(&engine.frontier)
+            .chain(engine.victories.iter().map(|(_, vic)| vic))
+            .copied()
+            .collect();
+        // Numbers every automaton reachable from the roots first, then fills in their links.
+        let mut order = vec![];
+        while let Some(auto) = pending.pop_front() {
+            if indices.contains_key(&auto.id()) {
+                continue;
+            }
+            indices.insert(auto.id(), order.len());
+            order.push(auto);
+            pending.extend(auto.get().parent.map(|(parent, _)| parent));
+            pending.extend(auto.get().children.iter().copied());
+        }
+        for auto in order {
+            let auto = auto.get();
+            automata.push(SavedAutomaton {
+                rule: auto.rule,
+                route: auto.route,
+                state: auto.state,
+                lexeme_start: auto.lexeme_start,
+                parent: auto.parent.map(|(parent, cont)| (indices[&parent.id()], cont)),
+                children: auto.children.iter().map(|child| indices[&child.id()]).collect(),
+                inlined: auto.inlined,
+                errors: auto.errors
+            });
+        }
+        let index = |auto: &Rawtomaton<'a>| indices[&auto.id()];
+        self.alive = engine.alive.iter().map(index).collect();
+        self.frontier = engine.frontier.iter().map(index).collect();
+        self.victories = engine.victories.iter().map(|(step, vic)| (*step, index(vic))).collect();
+        self.cuts = engine.cuts.iter().map(|(&(rule, start), &route)| (rule, start, route)).collect();
+        self.cuts.sort();
+        self.position = engine.position;
+        self.automata = automata;
+    }
+}
+
+impl<O: Parseable> Default for ParseSnapshot<O> {
+    fn default() -> Self {
+        ParseSnapshot::new()
+    }
+}
+
+impl<O: Parseable> Clone for ParseSnapshot<O> {
+    fn clone(&self) -> Self {
+        ParseSnapshot {
+            text: self.text.clone(),
+            lexemes: self.lexemes.clone(),
+            position: self.position,
+            automata: self.automata.clone(),
+            alive: self.alive.clone(),
+            frontier: self.frontier.clone(),
+            victories: self.victories.clone(),
+            cuts: self.cuts.clone(),
+            _rule: PhantomData
+        }
+    }
+}
+
+impl<O: Parseable> Debug for ParseSnapshot<O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseSnapshot")
+            .field("text", &self.text)
+            .field("lexemes", &self.lexemes)
+            .field("position", &self.position)
+            .field("automata", &self.automata)
+            .field("alive", &self.alive)
+            .field("frontier", &self.frontier)
+            .field("victories", &self.victories)
+            .field("cuts", &self.cuts)
+            .finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use parce::error::{ParceErrorInfo, ParsePhaseFailure};
+    use super::*;
+
+    #[lexer(SnapshotLexer)]
+    enum SnapshotLexemes {
+        Number = "[0-9]+",
+        Ident = "[a-z]+",
+        LParen = '(',
+        RParen = ')',
+        #[skip] Space = "[ \n]"
+    }
+
+    #[parser(SnapshotLexer)]
+    enum Call {
+        Call(String, Vec<Arg>) = "0=Ident LParen 1* RParen",
+        Name(String) = "0=Ident"
+    }
+
+    #[parser(SnapshotLexer)]
+    enum Arg {
+        Number(u32) = "0=Number",
+        Call(Call) = "0"
+    }
+
+    /// `let` commits to a binding once it's seen, even though it could be a call's name.
+    #[parser(SnapshotLexer)]
+    enum Statement {
+        Let(String, Call) = "Ident('let') ! 0=Ident 1",
+        Call(Call) = "0"
+    }
+
+    /// Feeds `text` split at every pair of lexeme boundaries, and checks that the result matches
+    /// parsing it all at once.
+    fn check_splits<O>(text: &str) where O: for<'t> Parseable<Lexer = SnapshotLexer, Output<'t> = O> + PartialEq + std::fmt::Debug {
+        let mut session = ParseSession::new(SnapshotLexer::default());
+        let whole = session.parse_max::<O>(text);
+        let cuts: Vec<usize> = session.lexemes().iter().map(|l| l.start).chain([text.len()]).collect();
+        for &a in &cuts {
+            for &b in cuts.iter().filter(|&&b| b >= a) {
+                let mut snapshot = ParseSnapshot::<O>::new();
+                for chunk in [&text[..a], &text[a..b], &text[b..]] {
+                    session.feed(&mut snapshot, chunk).unwrap();
+                }
+                assert_eq!(session.finish(&snapshot), whole, "split at {} and {}", a, b);
+                assert_eq!(snapshot.text, text);
+            }
+        }
+    }
+
+    #[test]
+    fn chunks() {
+        check_splits::<Call>("f(1 g(2 h) 3)");
+        check_splits::<Call>("f");
+        check_splits::<Call>("f(1 g(2 h) 3");
+        check_splits::<Call>("f(1 (2))");
+        check_splits::<Call>("f(1) g");
+        check_splits::<Statement>("let x f(1)");
+        check_splits::<Statement>("let x");
+        check_splits::<Statement>("let(1)");
+    }
+
+    #[test]
+    fn unfinished() {
+        let mut session = ParseSession::new(SnapshotLexer::default());
+        let mut snapshot = ParseSnapshot::<Call>::default();
+        let nothing = session.finish(&snapshot).unwrap_err();
+        assert_eq!(nothing.info, ParceErrorInfo::parse(ParsePhaseFailure::NothingToParse));
+
+        session.feed(&mut snapshot, "f(1\n").unwrap();
+        let before = snapshot.clone();
+        // Like parse_max, finishing takes the longest parse so far, even if more input could make a
+        // longer one.
+        assert_eq!(session.finish(&snapshot).unwrap(), (Call::Name("f".to_string()), ParseCompletion::Incomplete(1)));
+        assert_eq!(snapshot.automata, before.automata);
+        assert!(!snapshot.is_stuck());
+
+        // A lexing error leaves the snapshot as it was.
+        assert_eq!(session.feed(&mut snapshot, "2 ?").unwrap_err().start, 2);
+        assert_eq!(snapshot.text, "f(1\n");
+
+        session.feed(&mut snapshot, "2)").unwrap();
+        assert_eq!(session.finish(&snapshot).unwrap().0, Call::Call("f".to_string(), vec![Arg::Number(1), Arg::Number(2)]));
+
+        session.feed(&mut snapshot, ")").unwrap();
+        assert!(snapshot.is_stuck());
+        assert_eq!(session.finish(&snapshot).unwrap().1, ParseCompletion::Incomplete(6));
+    }
+
+    #[test]
+    fn rebuilt() {
+        use parce::lexer::Lexeme;
+
+        let mut session = ParseSession::new(SnapshotLexer::default());
+        let mut original = ParseSnapshot::<Call>::new();
+        session.feed(&mut original, "f(g(1) ").unwrap();
+
+        // Everything in a snapshot can be stored as plain numbers and strings.
+        let lexemes: Vec<(u16, usize, usize)> = original.lexemes.iter().map(|l| (l.kind_id(), l.start, l.len)).collect();
+        let mut rebuilt = ParseSnapshot::<Call>::new();
+        rebuilt.text = original.text.clone();
+        rebuilt.lexemes = lexemes.into_iter()
+            .map(|(kind, start, len)| SpannedLexeme { data: SnapshotLexemes::from_kind_id(kind).unwrap(), start, len })
+            .collect();
+        rebuilt.position = original.position;
+        rebuilt.automata = original.automata.clone();
+        rebuilt.alive = original.alive.clone();
+        rebuilt.frontier = original.frontier.clone();
+        rebuilt.victories = original.victories.clone();
+        rebuilt.cuts = original.cuts.clone();
+
+        for snapshot in [&mut original, &mut rebuilt] {
+            session.feed(snapshot, "h)").unwrap();
+        }
+        assert_eq!(original.automata, rebuilt.automata);
+        assert_eq!(session.finish(&original), session.finish(&rebuilt));
+        assert!(session.finish(&rebuilt).unwrap().1.is_complete());
+    }
+}
@@ -0,0 +1,173 @@
+//! Contains [Phase], which wraps the lexing and parsing done by a
+//! [ParseSession](super::ParseSession) in `tracing` spans, for the `tracing` feature.
+//!
+//! Every phase is a `DEBUG` span named `parce::lex` or `parce::parse`. When a phase ends, its
+//! span gets the number of lexemes, the microseconds it took, and the error if there was one. A
+//! `DEBUG` event with the same fields is emitted inside it. Parse spans also have the rule being
+//! parsed, the bytes the automata took, and how many errors the session has recorded so far.
+//! Without the feature, a [Phase] is empty and does nothing.
+
+use crate::error::ParceError;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+#[cfg(feature = "tracing")]
+use tracing::field::Empty;
+
+/// One phase of a parse, recorded as a span until it is [ended](Self::end).
+pub(crate) struct Phase {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "tracing")]
+    start: Instant
+}
+
+/// What a phase did, for [Phase::end].
+#[derive(Default)]
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub(crate) struct Outcome {
+    pub lexemes: usize,
+    pub memory: usize,
+    pub diagnostics: usize
+}
+
+impl Phase {
+    /// Starts lexing `bytes` bytes with `lexer`.
+    #[allow(unused_variables)]
+    pub fn lex(lexer: impl std::fmt::Display, bytes: usize) -> Phase {
+        Phase {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("parce::lex", lexer = %lexer, bytes, lexemes = Empty, elapsed_us = Empty, error = Empty).entered(),
+            #[cfg(feature = "tracing")]
+            start: Instant::now()
+        }
+    }
+
+    /// Starts parsing `lexemes` lexemes as `rule`.
+    #[allow(unused_variables)]
+    pub fn parse(rule: &'static str, lexemes: usize) -> Phase {
+        Phase {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("parce::parse", rule, lexemes, memory = Empty, diagnostics = Empty, elapsed_us = Empty, error = Empty).entered(),
+            #[cfg(feature = "tracing")]
+            start: Instant::now()
+        }
+    }
+
+    /// Records how the phase went, and closes its span.
+    #[allow(unused_variables)]
+    pub fn end<T>(self, result: &Result<T, ParceError>, outcome: Outcome) {
+        #[cfg(feature = "tracing")]
+        {
+            let elapsed_us = self.start.elapsed().as_micros() as u64;
+            let error = result.as_ref().err().map(|e| format!("{:?} at byte {}", e.info, e.start));
+            // Fields a span doesn't have, like memory for lexing, aren't recorded.
+            let span = &self.span;
+            span.record("lexemes", outcome.lexemes);
+            span.record("memory", outcome.memory);
+            span.record("diagnostics", outcome.diagnostics);
+            span.record("elapsed_us", elapsed_us);
+            if let Some(error) = &error {
+                span.record("error", error.as_str());
+            }
+            tracing::debug!(lexemes = outcome.lexemes, elapsed_us, error = error.as_deref(), "done");
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    #[lexer(TraceLexer)]
+    enum TraceLexemes {
+        A = 'a',
+        B = 'b',
+        #[skip] Space = ' '
+    }
+
+    #[parser(TraceLexer)]
+    enum Traced {
+        Traced = "A B*"
+    }
+
+    type Span = (&'static str, Vec<(&'static str, String)>);
+
+    /// The name and fields of every span, in the order they were made.
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<Span>>>);
+
+    struct Fields<'a>(&'a mut Vec<(&'static str, String)>);
+
+    impl Visit for Fields<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push((field.name(), value.to_string()));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push((field.name(), format!("{:?}", value)));
+        }
+    }
+
+    impl Subscriber for Spans {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = vec![];
+            attributes.record(&mut Fields(&mut fields));
+            spans.push((attributes.metadata().name(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0.lock().unwrap();
+            values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1].1));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    impl Spans {
+        fn field(&self, span: usize, name: &str) -> Option<String> {
+            let spans = self.0.lock().unwrap();
+            spans[span].1.iter().rev().find(|(field, _)| *field == name).map(|(_, value)| value.clone())
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let spans = Spans::default();
+        tracing::subscriber::with_default(spans.clone(), || {
+            let mut session = ParseSession::new(TraceLexer::default()).with_diagnostics(100);
+            session.parse_all::<Traced>("a b b").unwrap();
+            session.parse_all::<Traced>("b a").unwrap_err();
+            session.parse_all::<Traced>("a c").unwrap_err();
+        });
+
+        let names: Vec<_> = spans.0.lock().unwrap().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["parce::lex", "parce::parse", "parce::lex", "parce::parse", "parce::lex"]);
+        assert_eq!(spans.field(0, "bytes").as_deref(), Some("5"));
+        assert_eq!(spans.field(0, "lexemes").as_deref(), Some("3"));
+        assert_eq!(spans.field(0, "lexer").as_deref(), Some("Default"));
+        assert_eq!(spans.field(1, "rule").as_deref(), Some("Traced"));
+        assert_eq!(spans.field(1, "diagnostics").as_deref(), Some("0"));
+        assert!(spans.field(1, "memory").is_some_and(|m| m != "0"));
+        assert!(spans.field(1, "elapsed_us").is_some());
+        assert_eq!(spans.field(1, "error"), None);
+
+        assert_eq!(spans.field(3, "diagnostics").as_deref(), Some("1"));
+        assert!(spans.field(3, "error").is_some_and(|e| e.ends_with("at byte 0")));
+        assert!(spans.field(4, "error").is_some_and(|e| e.ends_with("at byte 2")));
+    }
+}
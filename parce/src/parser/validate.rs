@@ -0,0 +1,218 @@
+//! Contains the [Validate] trait, which the parser macro implements for every rule, for checking a
+//! tree against its grammar without parsing any text.
+//!
+//! Trees that were built by hand, edited, or deserialized can hold things the grammar could never
+//! have parsed, like an empty list where the pattern has a `+`. Validating finds these, along with
+//! the [errors](super::recovery::Recovered) the parser recovered from, before they get to a pass
+//! that assumes the tree came from the parser.
+
+use std::fmt;
+use crate::lexer::{Lexeme, Lexer};
+use super::recovery::ErrorNode;
+
+/// A node of a tree that its grammar couldn't have parsed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Violation {
+    /// Where the node is, as the production and field taken at each step down from the root, like
+    /// `["Block.0[2]", "Let.value"]`. Empty for the root itself.
+    pub path: Vec<String>,
+    /// The name of the node's rule.
+    pub rule: &'static str,
+    /// The name of the node's production (variant).
+    pub production: &'static str,
+    /// The field that is wrong, by name, or by index for unnamed fields.
+    pub field: String,
+    /// What is wrong with it.
+    pub kind: ViolationKind
+}
+
+/// What a [Violation] breaks.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ViolationKind {
+    /// A list or optional field has a number of items the production's pattern can't match. An
+    /// empty option has 0 items, and a full one has 1. `max` is `None` if there is no limit.
+    Count {
+        len: usize,
+        min: usize,
+        max: Option<usize>
+    },
+    /// A text field is not the text of the lexeme its pattern matches, according to the rule's
+    /// default lexer.
+    Text {
+        value: String,
+        lexeme: &'static str
+    },
+    /// A [Recovered](super::recovery::Recovered) field holds an error.
+    Error(ErrorNode)
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{} field {}", self.rule, self.production, self.field)?;
+        if !self.path.is_empty() {
+            write!(f, " (at {})", self.path.join("."))?;
+        }
+        match &self.kind {
+            ViolationKind::Count { len, min, max: Some(max) } => write!(f, " has {} items, but must have {} to {}", len, min, max),
+            ViolationKind::Count { len, min, max: None } => write!(f, " has {} items, but must have at least {}", len, min),
+            ViolationKind::Text { value, lexeme } => write!(f, " is {:?}, which doesn't match {}", value, lexeme),
+            ViolationKind::Error(error) => write!(f, " is an error, skipped from {} to {}", error.span.start, error.span.end)
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// A rule whose trees can be checked against its grammar. The parser macro implements this for
+/// every rule.
+///
+/// A node is checked for the things its type can't rule out by itself:
+///
+/// - The number of items in each list field, and whether each optional field is filled, has to be
+///   something the production's pattern can match. `0+` can't be empty, `0{2,3}` has 2 or 3 items,
+///   and `(0 Comma){2,}` has at least two.
+/// - A text field filled by a single lexeme, like `0=Ident`, has to lex as exactly that lexeme. The
+///   rule's default lexer is used, so lexemes of other modes can't be checked this way, and are
+///   reported. Fields of other types, like numbers, aren't checked.
+/// - [Recovered](super::recovery::Recovered) fields can't hold an error.
+///
+/// Every node of every rule under it is checked too.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::validate::{Validate, ViolationKind};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Ident = "[a-z]+",
+///     Comma = ',',
+///     LParen = '(',
+///     RParen = ')',
+///     #[skip] Space = ' '
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Call {
+///     Call { name: String, args: Vec<Call> } = "name=Ident LParen (args Comma)* RParen",
+///     Var(String) = "0=Ident"
+/// }
+///
+/// let mut call: Call = "f(x, g(y,),)".parse().unwrap();
+/// assert_eq!(call.validate(), Ok(()));
+///
+/// // Edits can make trees the grammar can't parse.
+/// if let Call::Call { args, .. } = &mut call {
+///     args[1] = Call::Var("Y".to_string());
+/// }
+/// let violations = call.validate().unwrap_err();
+/// assert_eq!(violations[0].path, vec!["Call.args[1]"]);
+/// assert_eq!(violations[0].kind, ViolationKind::Text { value: "Y".to_string(), lexeme: "Ident" });
+/// assert_eq!(violations[0].to_string(), "Call::Var field 0 (at Call.args[1]) is \"Y\", which doesn't match Ident");
+/// ```
+pub trait Validate {
+    /// Checks this node and every node under it, and gives back everything wrong with them, in the
+    /// order of the fields.
+    fn validate(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = vec![];
+        self.validate_into(&mut vec![], &mut violations);
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations)
+        }
+    }
+
+    /// Checks this node, which is at `path` from the root, and every node under it, adding what's
+    /// wrong to `violations`.
+    fn validate_into(&self, path: &mut Vec<String>, violations: &mut Vec<Violation>);
+}
+
+/// Whether `text` lexes with `lexer` as exactly one `lexeme`. Used by the generated
+/// [Validate::validate_into].
+#[doc(hidden)]
+pub fn lexes_as<L: Lexer>(lexer: L, text: &str, lexeme: L::Lexemes) -> bool {
+    match lexer.lex(text).as_deref() {
+        Ok([only]) => only.data == lexeme && only.start == 0 && only.len == text.len() && !only.data.is_skipped(),
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use crate::parser::recovery::Recovered;
+    use super::*;
+
+    #[lexer(ValidateLexer)]
+    enum ValidateLexemes {
+        Ident = "[a-z]+",
+        Number = "[0-9]+",
+        Eq = '=',
+        Semicolon = ';',
+        LBrace = '{',
+        RBrace = '}',
+        Comma = ',',
+        #[skip] Space = ' '
+    }
+
+    #[parser(ValidateLexer)]
+    #[sync(Semicolon)]
+    enum Statement {
+        Let { name: String, value: Option<Box<Statement>> } = "Ident('let') name=Ident (Eq value)? Semicolon",
+        Number(u32) = "0=Number Semicolon",
+        Tuple(Vec<Statement>) = "LBrace (0 Comma){2,3} RBrace",
+        Block(Vec<Recovered<Statement>>) = "LBrace 0+ RBrace"
+    }
+
+    fn violations(statement: &Statement) -> Vec<String> {
+        statement.validate().err().unwrap_or_default().iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn parsed_trees_are_valid() {
+        for text in ["let a;", "let a = 1; ;", "{ let a = let b; ; }", "{ 1; , let c; , }", "{ 1; , 2; , 3; , }"] {
+            let statement: Statement = text.parse().unwrap();
+            assert_eq!(statement.validate(), Ok(()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let one = || Statement::Number(1);
+        assert_eq!(violations(&Statement::Tuple(vec![one(), one()])), Vec::<String>::new());
+        assert_eq!(
+            violations(&Statement::Tuple(vec![one()])),
+            vec!["Statement::Tuple field 0 has 1 items, but must have 2 to 3"]
+        );
+        assert_eq!(
+            violations(&Statement::Block(vec![])),
+            vec!["Statement::Block field 0 has 0 items, but must have at least 1"]
+        );
+    }
+
+    #[test]
+    fn text_and_paths() {
+        let bad = Statement::Block(vec![
+            Recovered::Parsed(Statement::Let { name: "a".to_string(), value: None }),
+            Recovered::Parsed(Statement::Let {
+                name: "1".to_string(),
+                value: Some(Box::new(Statement::Let { name: "b c".to_string(), value: None }))
+            })
+        ]);
+        let all = bad.validate().unwrap_err();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].path, vec!["Block.0[1]"]);
+        assert_eq!(all[0].kind, ViolationKind::Text { value: "1".to_string(), lexeme: "Ident" });
+        assert_eq!(all[1].path, vec!["Block.0[1]", "Let.value"]);
+        assert_eq!((all[1].rule, all[1].production, all[1].field.as_str()), ("Statement", "Let", "name"));
+    }
+
+    #[test]
+    fn errors() {
+        let (statement, _): (Statement, _) = "{ let a; let = ; }".parse_tree().unwrap();
+        let all = statement.validate().unwrap_err();
+        assert_eq!(all.len(), 1);
+        assert_eq!((all[0].field.as_str(), all[0].path.len()), ("0[1]", 0));
+        assert!(matches!(&all[0].kind, ViolationKind::Error(error) if error.span == (9..16).into()));
+    }
+}
@@ -0,0 +1,110 @@
+//! Contains the [p](crate::p) macro, for writing lexer and parser patterns as Rust tokens instead
+//! of strings, and the [pattern](crate::pattern) macro, for sharing parts of lexer patterns
+//! between lexers.
+
+/// Writes a lexeme's or production's pattern as Rust tokens, instead of as a string.
+///
+/// `p!(...)` can be used anywhere a pattern string can, and means the same pattern. Lexemes, rules,
+/// and fields are identifiers, string and character literals are quoted literals, `[...]` is a
+/// character class, and `{...}` is a count. Since the pattern is made of tokens, editors highlight
+/// it like code, and a literal that isn't closed is a syntax error before the macro runs.
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// use parce::prelude::*;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Number = p!([0-9]+),
+///     Ident = p!(["a-z_"] ["a-z_0-9"]*),
+///     Arrow = p!("->"),
+///     Quote = p!('"' | "'"),
+///     #[skip] Space = p!(' ' | "\t")
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Function {
+///     Function { name: String, args: Vec<u32> } = p!(name = Ident args = Number* Arrow),
+///     Quoted(String) = p!(Quote 0 = Ident Quote)
+/// }
+///
+/// assert_eq!(
+///     "f 1\t2 ->".parse(),
+///     Ok(Function::Function { name: "f".to_string(), args: vec![1, 2] })
+/// );
+/// assert_eq!("'x'".parse(), Ok(Function::Quoted("x".to_string())));
+/// ```
+///
+/// A lexeme can also be named by a path to its variant, which the compiler checks; see
+/// [Lexeme Paths](crate::prelude::parser#lexeme-paths).
+///
+/// A class can be written with tokens, like `[a-z]`, or as a string, like `[" \n"]`, for the
+/// characters that aren't tokens. It can only be used in the patterns of the [lexer](crate::prelude::lexer)
+/// and [parser](crate::prelude::parser) macros; anywhere else it is an error.
+#[macro_export]
+macro_rules! p {
+    ($($pattern:tt)*) => {
+        compile_error!("p!(...) can only be used as the pattern of a lexeme or a production")
+    };
+}
+
+/// Defines named patterns, which the patterns of any [lexer](crate::prelude::lexer) in scope can
+/// use like a lexeme.
+///
+/// A constant is named in upper snake case, and its pattern is a string or a [p!](crate::p), like
+/// a lexeme's. It works like a [fragment](crate::prelude::lexer#lexeme-fragments) that every lexer
+/// can see, so common parts of patterns, like the digits of a number, don't have to be copied into
+/// each lexer. Constants can use other constants, but not themselves, and the constants they
+/// use have to be in scope where the lexer is too.
+///
+/// ```
+/// use parce::prelude::*;
+///
+/// mod patterns {
+///     parce::pattern! {
+///         pub(crate) DIGITS = "[0-9]+";
+///         pub(crate) NUMBER = p!(DIGITS ("." DIGITS)?)
+///     }
+/// }
+///
+/// use patterns::*;
+///
+/// #[lexer(JsonLexer)]
+/// enum JsonLexemes {
+///     Number = "'-'? NUMBER",
+///     Comma = ','
+/// }
+///
+/// #[lexer(CalcLexer)]
+/// enum CalcLexemes {
+///     Number = "NUMBER",
+///     Minus = '-'
+/// }
+///
+/// assert_eq!(JsonLexer::default().lex("-1.5,2").unwrap().len(), 3);
+/// assert_eq!(CalcLexer::default().lex("-1.5").unwrap().len(), 2);
+/// ```
+///
+/// Each constant is a macro, so it's in scope like one: after the `pattern!` in the same module
+/// and its children, and anywhere it's imported with `use`. It can be public to the crate, but
+/// not outside of it. A name that isn't a lexeme of the lexer is looked up as a constant, and the
+/// compiler reports it if there's no such constant.
+#[macro_export]
+macro_rules! pattern {
+    (@define ($d:tt) $vis:vis $name:ident $pattern:expr) => {
+        #[allow(unused_macros)]
+        #[doc(hidden)]
+        macro_rules! $name {
+            ([$d($d lexer:tt)*] $d($d item:tt)*) => {
+                $d($d lexer)*
+                #[pattern_constant($name = $pattern)]
+                $d($d item)*
+            };
+        }
+        #[allow(unused_imports)]
+        $vis use $name;
+    };
+    ($($vis:vis $name:ident = $pattern:expr);* $(;)?) => {
+        $($crate::pattern!(@define ($) $vis $name $pattern);)*
+    };
+}
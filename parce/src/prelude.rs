@@ -10,9 +10,9 @@
 /// passed as argument. You will rarely (if ever) need to use the lexer directly,
 /// and you can find those docs on the Lexer trait in the main crate.
 ///
-/// This macro also generates a ParserSubmission struct, which has to be publicly accessible to the user,
-/// but they shouldn't use it because it is only for internal use (it registers parsers through the
-/// inventory crate).
+/// With the `inventory` feature, this macro also generates a ParserSubmission struct, which has to be
+/// publicly accessible to the user, but they shouldn't use it because it is only for internal use (it
+/// registers parsers through the inventory crate, for [registered_rules](crate::lexer::Lexer::registered_rules)).
 ///
 /// # Examples
 ///
@@ -61,8 +61,10 @@
 /// ## Character Classes
 ///
 /// In cases where you want to match on many possible characters, using | can be extremely tedious. Instead,
-/// you can use character classes like in regex. In fact, any character classes you use are forwarded
-/// directly to the [regex] crate, so you can use all of the features available there. See [their docs](https://docs.rs/regex/1.5.4/regex/index.html#character-classes).
+/// you can use character classes like in regex. In fact, any character classes you use are parsed with the
+/// regex crate's syntax, so you can use all of the features available there. See [their docs](https://docs.rs/regex/1.5.4/regex/index.html#character-classes).
+/// The class is turned into a table of character ranges at compile time, so the generated lexer doesn't
+/// depend on the regex crate.
 ///
 /// ```
 /// # use parce::prelude::*;
@@ -112,6 +114,40 @@
 /// string. *However* if, during parsing, you capture a portion of the input string in the output,
 /// any characters associated with skipped lexemes inside that portion will still be present.
 ///
+/// ## Byte Order Marks and Shebangs
+///
+/// A lexeme marked `#[bom]` matches a UTF-8 byte order mark, and one marked `#[shebang]` matches a
+/// `#!` line, like `#!/usr/bin/env python`, up to but not including the line break. Both only
+/// match at the very start of the input, the byte order mark first, and are skipped like `#[skip]`
+/// lexemes, so they end up in the [TriviaIndex](crate::lexer::TriviaIndex) instead of the output.
+/// Their patterns are built in, so they don't have one of their own. Either can be left out.
+///
+/// ```
+/// # use parce::prelude::*;
+/// #[lexer(ScriptLexer)]
+/// enum ScriptLexemes {
+///     #[bom] Bom,
+///     #[shebang] Shebang,
+///     Word = "[a-z]+",
+///     Comment = "'#' [^\n]*",
+///     #[skip] Space = "[ \n]"
+/// }
+///
+/// let text = "\u{feff}#!/bin/script\necho # comment";
+/// let (lexemes, trivia) = ScriptLexer::default().lex_with_trivia(text).unwrap();
+/// assert_eq!(lexemes.iter().map(|l| l.data).collect::<Vec<_>>(), vec![ScriptLexemes::Word, ScriptLexemes::Comment]);
+/// assert_eq!(trivia.leading(0)[1].text(text), "#!/bin/script");
+/// ```
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// #[lexer(BadScriptLexer)]
+/// enum BadScriptLexemes {
+///     #[shebang] Shebang = "'#!' .*", // the pattern is built in
+///     Word = "[a-z]+"
+/// }
+/// ```
+///
 /// ## Nested Lexemes
 ///
 /// Lexemes can require other lexemes. This can be helpful to avoid code reuse.
@@ -164,6 +200,89 @@
 /// Adding `#[skip]` to a lexeme that is already `#[frag]` will do nothing, and it will behave like a
 /// normal fragment.
 ///
+/// ## Token Patterns
+///
+/// Patterns can also be written as Rust tokens with [p!](crate::p), which means the same as the
+/// string. String and character literals are the quoted literals:
+///
+/// ```
+/// # use parce::prelude::*;
+/// #[lexer(TokenLexer)]
+/// enum TokenLexemes {
+///     Keyword = p!("let" | "fn"),
+///     Number = p!([0-9]{1,} ('.' [0-9]+)?),
+///     #[skip] Space = p!([" \t\n"])
+/// }
+///
+/// assert_eq!(TokenLexer::default().lex("let 1.5").unwrap().len(), 2);
+/// ```
+///
+/// Other kinds of literals aren't patterns:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// #[lexer(TokenLexer)]
+/// enum TokenLexemes {
+///     Number = p!(1.5)
+/// }
+/// ```
+///
+/// ## Pattern Constants
+///
+/// Parts of patterns can be shared between lexers with [pattern!](crate::pattern). A name in upper
+/// snake case that isn't a lexeme is a constant, and one that was never defined is an error:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// #[lexer(ConstantLexer)]
+/// enum ConstantLexemes {
+///     Number = "DIGITS"
+/// }
+/// ```
+///
+/// A constant can't use itself:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// parce::pattern! { DIGITS = "[0-9] DIGITS?" }
+///
+/// #[lexer(ConstantLexer)]
+/// enum ConstantLexemes {
+///     Number = "DIGITS"
+/// }
+/// ```
+///
+/// ## Unicode Fragments
+///
+/// A few fragments are built in, for the parts of a language that are defined by Unicode rather than
+/// by a list of ASCII characters:
+///
+/// - `XID_START` and `XID_CONTINUE`: a character that can start an identifier, and one that can
+///   continue it, as in [UAX #31](https://www.unicode.org/reports/tr31/).
+/// - `XID_IDENT`: the default identifier of UAX #31, `XID_START XID_CONTINUE*`. Underscores can
+///   continue an identifier but not start it; use `('_' | XID_START) XID_CONTINUE*` for identifiers
+///   like Rust's.
+/// - `WHITESPACE_UNICODE`: any character with the `White_Space` property.
+/// - `PATTERN_WHITESPACE`: the smaller set of whitespace UAX #31 recommends for programming languages.
+/// - `LINE_TERMINATOR`: a line break, as in [UTS #18](https://www.unicode.org/reports/tr18/#Line_Boundaries).
+///   `"\r\n"` is one break.
+///
+/// They are used like lexemes, and their names can't be used for your own lexemes.
+///
+/// ```
+/// # use parce::prelude::*;
+/// #[lexer(UnicodeLexer)]
+/// enum UnicodeLexemes {
+///     Ident = "('_' | XID_START) XID_CONTINUE*",
+///     Newline = "LINE_TERMINATOR",
+///     #[skip] Space = "PATTERN_WHITESPACE"
+/// }
+///
+/// let lexemes = UnicodeLexer::default().lex("größe _π\r\nnäme").unwrap();
+/// let kinds: Vec<_> = lexemes.iter().map(|l| l.data).collect();
+/// assert_eq!(kinds, vec![UnicodeLexemes::Ident, UnicodeLexemes::Ident, UnicodeLexemes::Newline, UnicodeLexemes::Ident]);
+/// ```
+///
 /// ## Modal Lexers
 ///
 /// Lexers can have multiple modes. So far, all of the examples have been single-mode lexers, but you
@@ -205,10 +324,313 @@
 /// Applying `#[mode]` or `#[set_mode]` to a fragment lexeme will do nothing. Fragments do not have modes,
 /// they can be used in any mode that has a lexeme that requires them. They also cannot set a new mode
 /// because they are never matched directly.
+///
+/// [lex_with_modes](crate::lexer::Lexer::lex_with_modes) gives the mode each lexeme was matched in,
+/// and where the mode changed, so lexing can restart in the middle of an input.
+///
+/// ## Derives and Visibility
+///
+/// The lexer enum (the one holding the modes) and the other generated items use the visibility of the
+/// lexeme enum, and derive `Debug`, `Eq`, `PartialEq`, `Copy`, and `Clone`. Both can be changed with
+/// extra arguments after the lexer name. Derives the lexer already has are skipped:
+///
+/// ```
+/// # use parce::prelude::*;
+/// #[lexer(ConfiguredLexer, derive(Hash, PartialOrd, Ord), vis = pub(crate))]
+/// pub enum ConfiguredLexemes {
+///     A = 'a'
+/// }
+///
+/// let mut modes = std::collections::BTreeSet::new();
+/// modes.insert(ConfiguredLexer::default());
+/// ```
+///
+/// ## Multiple Lexers
+///
+/// More lexer names after the first generate more lexers for the same lexemes, like a strict and a
+/// lenient lexer, or one per dialect. A lexeme marked `#[lexers(...)]` is only lexed by the lexers
+/// it names, and the rest are lexed by all of them. Since they produce the same lexemes, a rule
+/// written for one lexer can be parsed with a [ParseSession](crate::parser::ParseSession) of any
+/// of the others:
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// # use parce::prelude::*;
+/// #[lexer(JsonLexer, Json5Lexer)]
+/// enum JsonLexemes {
+///     Number = "[0-9]+",
+///     LBracket = '[',
+///     RBracket = ']',
+///     Comma = ',',
+///     #[skip] Space = ' ',
+///     #[lexers(Json5Lexer)] #[skip] Comment = "'//' [^\n]*"
+/// }
+///
+/// #[parser(JsonLexer)]
+/// enum Array {
+///     Array(Vec<u32>) = "LBracket (0=Number Comma?)* RBracket"
+/// }
+///
+/// assert!(JsonLexer::default().lex("[1] // one").is_err());
+/// assert_eq!(Json5Lexer::default().lex("[1] // one").unwrap().len(), 3);
+///
+/// let mut session = ParseSession::new(Json5Lexer::default());
+/// assert_eq!(session.parse_all::<Array>("[1, 2,] // two"), Ok(Array::Array(vec![1, 2])));
+/// ```
+///
+/// The lexers named by `#[lexers(...)]` have to be declared:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// #[lexer(JsonLexer, Json5Lexer)]
+/// enum JsonLexemes {
+///     #[lexers(YamlLexer)] Hash = '#'
+/// }
+/// ```
+///
+/// ## Overlap Matrix
+///
+/// When two lexemes match the same input, the longest match wins, and ties go to whichever lexeme was
+/// declared first. That can be surprising, so adding `#[overlap_matrix]` to the enum generates an `OVERLAPS`
+/// const on the lexer, listing every pair of lexemes (sharing a mode) that can match the same input, along
+/// with the shortest example.
+///
+/// ```
+/// # use parce::prelude::*;
+/// use parce::lexer::LexemeOverlap;
+///
+/// #[lexer(OverlapLexer)]
+/// #[overlap_matrix]
+/// enum OverlapLexemes {
+///     Let = "'let'",
+///     Ident = " [a-z]+ "
+/// }
+///
+/// assert_eq!(OverlapLexer::OVERLAPS, &[
+///     LexemeOverlap { first: "Let", second: "Ident", example: "let" }
+/// ]);
+/// ```
+///
+/// The search only tries ASCII characters and characters that appear in literals, so overlaps that need
+/// other unicode characters to happen are not found. Recursive lexemes are left out. It also doesn't
+/// take `#[ignore_case]` into account.
+///
+/// ## Case-Insensitive Lexemes
+///
+/// Lexemes marked `#[ignore_case]` match their literals and character classes in any case, without
+/// spelling out every combination like `[sS][eE][lL]...`. Put the attribute on the enum to make every
+/// lexeme case-insensitive. The original text is still what the spans point to, and
+/// [normalized_text](crate::lexer::SpannedLexeme::normalized_text) gives the lowercase version for
+/// comparing names:
+///
+/// ```
+/// # use parce::prelude::*;
+/// #[lexer(SqlLexer)]
+/// enum SqlLexemes {
+///     #[ignore_case] Select = "'select'",
+///     #[ignore_case] Ident = "[a-z_]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// let input = "SELECT Name";
+/// let lexemes = SqlLexer::default().lex(input).unwrap();
+/// assert!(lexemes[0].is(SqlLexemes::Select));
+/// assert_eq!(lexemes[1].text(input), "Name");
+/// assert_eq!(lexemes[1].normalized_text(input), "name");
+/// ```
+///
+/// ## Categories
+///
+/// `#[category(...)]` puts a lexeme in one or more named classes, like keywords or operators. Each
+/// category gets a predicate on the lexeme enum, named `is_` and the category in snake case, so
+/// highlighters and formatters don't need their own match over every lexeme.
+/// [categories](crate::lexer::Lexeme::categories) lists them for a lexeme:
+///
+/// ```
+/// # use parce::prelude::*;
+/// #[lexer(CategoryLexer)]
+/// enum CategoryLexemes {
+///     #[category(Keyword)] If = "'if'",
+///     #[category(Keyword)] Else = "'else'",
+///     Ident = "[a-z]+",
+///     #[category(Operator, BinaryOperator)] Plus = '+',
+///     #[category(Operator)] Not = '!',
+///     #[category(Trivia)] Space = ' '
+/// }
+///
+/// use CategoryLexemes::*;
+/// assert!(If.is_keyword() && !Ident.is_keyword());
+/// assert!(Plus.is_binary_operator() && !Not.is_binary_operator());
+/// assert_eq!(Plus.categories(), &["Operator", "BinaryOperator"]);
+/// assert!(Ident.categories().is_empty());
+/// ```
+///
+/// Categories are names, not strings:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// #[lexer(BadCategoryLexer)]
+/// enum BadCategoryLexemes {
+///     #[category("keyword")] If = "'if'"
+/// }
+/// ```
+///
+/// ## Versioned Lexemes
+///
+/// A lexeme marked `#[since(...)]` only matches in that version of the language and later, and one
+/// marked `#[cfg_grammar(...)]` only when those features are enabled. Where they don't match, the
+/// other lexemes get their chance, so a keyword added in a later version is still an identifier in
+/// earlier ones. The version comes from a [GrammarVersion](crate::version::GrammarVersion), and
+/// everything matches by default:
+///
+/// ```
+/// # use parce::prelude::*;
+/// use parce::version::GrammarVersion;
+///
+/// #[lexer(EditionLexer)]
+/// enum EditionLexemes {
+///     #[since("1.5")] Async = "'async'",
+///     #[cfg_grammar(try_blocks)] Try = "'try'",
+///     Ident = "[a-z]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// let lex = |version: GrammarVersion| version.run(|| {
+///     EditionLexer::default().lex("async try").unwrap().iter().map(|l| l.data).collect::<Vec<_>>()
+/// });
+/// use EditionLexemes::*;
+/// assert_eq!(lex(GrammarVersion::all()), vec![Async, Try]);
+/// assert_eq!(lex(GrammarVersion::at(&[1, 4])), vec![Ident, Ident]);
+/// assert_eq!(lex(GrammarVersion::at(&[1, 5])), vec![Async, Ident]);
+/// assert_eq!(lex(GrammarVersion::at(&[1, 5]).with_feature("try_blocks")), vec![Async, Try]);
+/// ```
+///
+/// ## Dialect Flags
+///
+/// A lexeme or production marked `#[flag(...)]` is left out unless every one of its flags is set,
+/// even when everything else is enabled. Flags are chosen at runtime, with
+/// [GrammarVersion::with_flag](crate::version::GrammarVersion::with_flag) or
+/// [ParseSession::with_flag](crate::parser::ParseSession::with_flag), so one compiled grammar can
+/// parse close dialects of a language, like JSON and JSON5:
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// # use parce::prelude::*;
+/// #[lexer(JsonLexer)]
+/// enum JsonLexemes {
+///     Number = "[0-9]+",
+///     Comma = ',',
+///     LBracket = '[',
+///     RBracket = ']',
+///     #[flag(json5)] Hex = "'0x' [0-9a-fA-F]+",
+///     #[skip] Space = ' ',
+///     #[skip] #[flag(json5)] Comment = "'//' [^\n]*"
+/// }
+///
+/// #[parser(JsonLexer)]
+/// enum Array {
+///     Array(Vec<Value>) = "LBracket (0 Comma?)* RBracket"
+/// }
+///
+/// #[parser(JsonLexer)]
+/// enum Value {
+///     Number = "Number",
+///     #[flag(json5)] Hex = "Hex"
+/// }
+///
+/// let mut json = ParseSession::new(JsonLexer::default());
+/// assert!(json.parse_all::<Array>("[1, 0x2]").is_err());
+/// assert!(json.parse_all::<Array>("[1, 2] // numbers").is_err());
+///
+/// let mut json5 = ParseSession::new(JsonLexer::default()).with_flag("json5");
+/// assert_eq!(json5.parse_all::<Array>("[1, 0x2] // numbers"), Ok(Array::Array(vec![Value::Number, Value::Hex])));
+/// ```
+///
+/// ## Deprecated Syntax
+///
+/// A lexeme or production marked `#[deprecated_syntax(message = "...")]` still matches, but a
+/// [ParseSession](crate::parser::ParseSession) records a [warning](crate::parser::ParseSession::warnings)
+/// with its span and the message wherever it is used, so a language can steer users off old syntax
+/// before removing it:
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// # use parce::prelude::*;
+/// #[lexer(PrintLexer)]
+/// enum PrintLexemes {
+///     #[deprecated_syntax(message = "use print(...) instead")] PrintStatement = "'print '",
+///     Word = "[a-z]+"
+/// }
+///
+/// let mut session = ParseSession::new(PrintLexer::default());
+/// # #[parser(PrintLexer)] enum Print { Print = "PrintStatement? Word" }
+/// session.parse_all::<Print>("print hello").unwrap();
+/// assert_eq!(session.warnings()[0].span, parce::span::Span::new(0, 6));
+/// assert_eq!(PrintLexemes::PrintStatement.deprecation(), Some("use print(...) instead"));
+/// ```
+///
+/// The message is required:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// #[lexer(PrintLexer)]
+/// enum PrintLexemes {
+///     #[deprecated_syntax] PrintStatement = "'print '"
+/// }
+/// ```
+///
+/// ## Paired Delimiters
+///
+/// `#[pair(Opener, Closer)]` on the enum declares lexemes that open and close each other, like
+/// braces. When the input ends with one left open, and would parse if it were closed, the error is
+/// an [Unclosed](crate::error::ParsePhaseFailure::Unclosed) error at the opener, instead of at the
+/// end of the input where the closer was noticed missing.
+/// [ParseSession::parse_all_closed](crate::parser::ParseSession::parse_all_closed) can also close
+/// them, and give back the value anyway:
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// # use parce::prelude::*;
+/// #[lexer(CodeLexer)]
+/// #[pair(LBrace, RBrace)]
+/// #[pair(LParen, RParen)]
+/// enum CodeLexemes {
+///     Ident = "[a-z]+",
+///     LBrace = '{',
+///     RBrace = '}',
+///     LParen = '(',
+///     RParen = ')',
+///     Semicolon = ';',
+///     #[skip] Space = "[ \n]"
+/// }
+///
+/// #[parser(CodeLexer)]
+/// enum Statement {
+///     Call(String) = "0=Ident LParen RParen Semicolon",
+///     Block(Vec<Statement>) = "LBrace 0* RBrace"
+/// }
+///
+/// let text = "{\n  f();\n  {\n    g();\n}";
+/// let error = text.parse::<Statement>().unwrap_err();
+/// assert_eq!(error.start, 0);
+/// assert!(error.display_plain().to_string().starts_with("Parser Error: unclosed LBrace opened here, the input ended before its RBrace"));
+/// ```
+///
+/// Both lexemes have to be declared:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// #[lexer(CodeLexer)]
+/// #[pair(LBrace, RBrace)]
+/// enum CodeLexemes {
+///     LBrace = '{'
+/// }
+/// ```
 pub use parce_macros::lexer;
 
 /// Generates an implementation of the [Parseable](crate::parser::Parseable) and [FromStr](std::str::FromStr)
-/// traits for an enum.
+/// traits for an enum, [Foldable](crate::parser::fold::Foldable) for rewriting the parsed tree, and
+/// [Validate](crate::parser::validate::Validate) for checking a tree against the grammar.
 ///
 /// Must be applied to an enum with patterns as the discriminants. Requires name of *lexer* to be
 /// passed as argument.
@@ -218,9 +640,537 @@ pub use parce_macros::lexer;
 /// ## Basic Example
 ///
 ///
+/// # Grammar Checks
+///
+/// Some grammars would hang or overflow the stack at runtime, so the macro rejects them at compile time
+/// instead. A rule where every production requires another copy of the same rule can never finish matching:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a' }
+/// #[parser(MyLexer)]
+/// enum Forever {
+///     Nest(Box<Forever>) = "A 0" // needs a `| A` or a `0?` to ever stop
+/// }
+/// ```
+///
+/// And a repetition over a pattern that can match zero lexemes would loop forever without
+/// moving through the input:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a' }
+/// #[parser(MyLexer)]
+/// enum Loop {
+///     Thing = "(A?)*" // should be A*
+/// }
+/// ```
+///
+/// These checks only look at one rule at a time. Rules referenced from other enums are assumed to be
+/// well behaved.
+///
+/// A rule marked `#[deterministic]` is also checked for choices that need more than the next
+/// lexeme to make, like two productions that start with the same lexeme, or a repetition followed
+/// by something it could also repeat. In a deterministic grammar the parser never has to follow
+/// more than one alternative until the others fail.
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a', B = 'b' }
+/// #[parser(MyLexer)]
+/// #[deterministic]
+/// enum Guess {
+///     Thing = "A* A B" // can't tell which A is the last one
+/// }
+/// ```
+///
+/// Like the other checks, this one can't see into other rules. Use
+/// [determinism_report](crate::report::determinism_report) in a test to check those too.
+///
+/// # Owned and Borrowed Fields
+///
+/// Assigned fields (like `0=(A B)`) are normally owned, and built from the matched text with
+/// [FromStr](std::str::FromStr), so a `String` field copies the text and the result can outlive the input.
+/// This is the default, and can be written explicitly as `#[parser(MyLexer, owned)]`.
+///
+/// To avoid the copies, `#[parser(MyLexer, borrowed)]` rules take a single lifetime parameter, and
+/// `&str` fields borrow the matched text straight from the input:
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a', B = 'b' }
+/// #[parser(MyLexer, borrowed)]
+/// enum Borrowed<'a> {
+///     Pair(&'a str, Box<Borrowed<'a>>) = "0=(A B+) 1",
+///     End(&'a str) = "0=A"
+/// }
+///
+/// let input = String::from("abba");
+/// let parsed = ParseSession::new(MyLexer::default()).parse_all::<Borrowed>(&input).unwrap();
+/// assert_eq!(parsed, Borrowed::Pair("abb", Box::new(Borrowed::End("a"))));
+/// ```
+///
+/// Since the result borrows the input, borrowed rules can only be parsed through a [ParseSession](crate::parser::ParseSession),
+/// and don't implement [FromStr](std::str::FromStr), [Parse](crate::parser::Parse), or [Foldable](crate::parser::fold::Foldable):
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a' }
+/// #[parser(MyLexer, borrowed)]
+/// enum Borrowed<'a> {
+///     End(&'a str) = "0=A"
+/// }
+///
+/// let parsed: Borrowed = "a".parse_all().unwrap();
+/// ```
+///
+/// # Recovery Anchors
+///
+/// `#[sync(...)]` lists lexemes that usually end a rule, like a semicolon after a statement. When a
+/// rule fails to parse, error recovery skips ahead to one of these to find where the broken rule stops.
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Let = "'let'", Ident = "[a-z]", Semicolon = ';', LBrace = '{', RBrace = '}' }
+/// #[parser(MyLexer)]
+/// #[sync(Semicolon, RBrace)]
+/// enum Statement {
+///     Let = "Let Ident Semicolon",
+///     Block = "LBrace #Statement* RBrace"
+/// }
+///
+/// use parce::parser::Parseable;
+/// assert_eq!(Statement::rule_info().sync, &["Semicolon", "RBrace"]);
+/// ```
+///
+/// They go on the whole rule. Recovery skips a broken rule without knowing which of its productions
+/// it was, so anchors on a single production are an error:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Let = "'let'", Ident = "[a-z]", Semicolon = ';', LBrace = '{', RBrace = '}' }
+/// #[parser(MyLexer)]
+/// #[sync(Semicolon)]
+/// enum Statement {
+///     Let = "Let Ident Semicolon",
+///     #[sync(RBrace)]
+///     Block = "LBrace #Statement* RBrace"
+/// }
+/// ```
+///
+/// The anchors must be lexemes of the rule's lexer:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a', Semicolon = ';' }
+/// #[parser(MyLexer)]
+/// #[sync(Semicolom)]
+/// enum Statement {
+///     Thing = "A Semicolon"
+/// }
+/// ```
+///
+/// A rule field of type [Recovered] holds either the parsed rule, or an
+/// [ErrorNode](crate::parser::recovery::ErrorNode) for the input skipped in its place, up to and
+/// including the next of the rule's own anchors. So one broken statement doesn't lose the whole
+/// block around it:
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Let = "'let'", Ident = "[a-z]", Semicolon = ';', LBrace = '{', RBrace = '}', #[skip] Space = ' ' }
+/// #[parser(MyLexer)]
+/// #[sync(Semicolon)]
+/// enum Statement {
+///     Let = "Let Ident Semicolon",
+///     Block(Vec<Recovered<Statement>>) = "LBrace 0* RBrace"
+/// }
+///
+/// let Statement::Block(statements) = "{ let a; let; let c; }".parse().unwrap() else { panic!() };
+/// assert_eq!(statements.iter().map(|s| s.is_error()).collect::<Vec<_>>(), vec![false, true, false]);
+/// assert_eq!(statements[1].error().unwrap().expected, vec!["Ident"]);
+/// ```
+///
+/// # Ambiguity
+///
+/// Some grammars can parse the same input more than one way. The classic example is the dangling
+/// `else`: in `if a if b x else y`, the `else` could belong to either `if`. Put `#[resolve(longest)]`
+/// (or its alias `#[prefer_shift]`) on a production to prefer the parse where it matches as much as
+/// possible, or `#[resolve(shortest)]` to prefer the one where it stops as early as possible. When
+/// several preferred productions are involved, the innermost one decides.
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { If = "'if'", Else = "'else'", Var = "[a-z]", #[skip] Space = ' ' }
+/// #[parser(MyLexer)]
+/// enum Statement {
+///     #[prefer_shift] // the else goes with the nearest if
+///     If { then: Box<Statement>, otherwise: Option<Box<Statement>> } = "If Var then (Else otherwise)?",
+///     Var = "Var"
+/// }
+///
+/// let parsed: Statement = "if a if b x else y".parse().unwrap();
+/// assert_eq!(parsed, Statement::If {
+///     then: Box::new(Statement::If {
+///         then: Box::new(Statement::Var),
+///         otherwise: Some(Box::new(Statement::Var))
+///     }),
+///     otherwise: None
+/// });
+/// ```
+///
+/// Without a preference, ties go to whichever parse the engine happens to finish last.
+///
+/// The attribute can also go on the whole rule, where it applies to every production that doesn't
+/// have its own. This is useful when a rule has optional trailing parts that could otherwise be left
+/// for whatever comes after it:
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Ident = "[a-z]+", Colon = ':', #[skip] Space = ' ' }
+/// #[parser(MyLexer)]
+/// #[resolve(longest)]
+/// enum Param {
+///     Typed = "Ident Colon Ident",
+///     Untyped = "Ident"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Params {
+///     Params(Vec<Param>) = "(0 Colon?)*"
+/// }
+///
+/// assert_eq!("x: y".parse(), Ok(Params::Params(vec![Param::Typed])));
+/// ```
+///
+/// # Cuts
+///
+/// A `!` in a production is a cut. Once the production has matched everything before the cut, the
+/// other productions of the same rule that started at the same lexeme are dropped, even ones that
+/// already finished. Cuts stop the engine from carrying alternatives that can't be what the input
+/// meant, and keep errors pointing at the production that was actually being written.
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Ident = "[a-z]+", Equals = '=', #[skip] Space = ' ' }
+/// #[parser(MyLexer)]
+/// enum Statement {
+///     Let = "Ident('let') ! Ident Equals Ident",
+///     Call = "Ident+"
+/// }
+///
+/// assert_eq!("let x = y".parse(), Ok(Statement::Let));
+/// assert_eq!("print x".parse(), Ok(Statement::Call));
+/// // Without the cut, this would be a call to `let`.
+/// assert!(("let x".parse() as Result<Statement, _>).is_err());
+/// ```
+///
+/// A production can only have one cut, and it has to be at the top level, between two parts:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a', B = 'b' }
+/// #[parser(MyLexer)]
+/// enum Bad {
+///     Thing = "A (B ! A)*"
+/// }
+/// ```
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a', B = 'b' }
+/// #[parser(MyLexer)]
+/// enum Bad {
+///     Thing = "A B !"
+/// }
+/// ```
+///
+/// # Inlined Rules
+///
+/// A rule whose productions each just hold one other rule, like an `Expression` that is either a
+/// `Literal` or a `Call`, costs an extra automaton for every production at every place it is
+/// referenced, and most of them die with the rule inside. `#[inline_rule]` makes the parser start
+/// the inner rules directly, and only build the wrapper's automaton for the one that matches. The
+/// parse result, spans, and error stacks are the same as without it.
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Number = "[0-9]+", Ident = "[a-z]+", LParen = '(', RParen = ')', #[skip] Space = ' ' }
+/// #[parser(MyLexer)]
+/// enum Literal {
+///     Number = "Number"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Call {
+///     Call { args: Vec<Expression> } = "Ident LParen args* RParen"
+/// }
+///
+/// #[parser(MyLexer)]
+/// #[inline_rule]
+/// enum Expression {
+///     Literal(Literal) = "0",
+///     Call(Box<Call>) = "0"
+/// }
+///
+/// assert_eq!("f(1 g())".parse(), Ok(Call::Call { args: vec![
+///     Expression::Literal(Literal::Number),
+///     Expression::Call(Box::new(Call::Call { args: vec![] }))
+/// ]}));
+/// ```
+///
+/// Every production has to be a single rule, with no cut and no `#[no_skip]`:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a' }
+/// # #[parser(MyLexer)]
+/// # enum Inner { A = "A" }
+/// #[parser(MyLexer)]
+/// #[inline_rule]
+/// enum Wrapper {
+///     Thing(Inner) = "A 0"
+/// }
+/// ```
+///
+/// # Whitespace-Sensitive Productions
+///
+/// Skipped lexemes are usually allowed between any two lexemes. Inside a `#[no_skip]` production,
+/// including inside the rules it contains, they are ordinary lexemes instead, so they can only
+/// appear where its pattern names them. It can go on single productions, or on the whole rule:
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Ident = "[a-z]+", LParen = '(', RParen = ')', #[skip] Space = ' ' }
+/// #[parser(MyLexer)]
+/// enum Statement {
+///     #[no_skip] // no space allowed between the name and the parentheses
+///     Call = "Ident LParen RParen",
+///     Return = "Ident Ident"
+/// }
+///
+/// assert_eq!("f()".parse(), Ok(Statement::Call));
+/// assert_eq!("return x".parse(), Ok(Statement::Return));
+/// assert!(("f ()".parse() as Result<Statement, _>).is_err());
+/// ```
+///
+/// That also makes them significant, like newlines that end statements. Skipped lexemes outside of
+/// the production, like blank lines between statements, are still skipped:
+///
+/// ```
+/// # use parce::prelude::*;
+/// #[lexer(MyLexer)]
+/// enum MyLexeme {
+///     Ident = "[a-z]+",
+///     Number = "[0-9]+",
+///     Eq = '=',
+///     #[skip] Space = ' ',
+///     #[skip] Newline = '\n'
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Program {
+///     Program(Vec<Statement>) = "0*"
+/// }
+///
+/// #[parser(MyLexer)]
+/// #[no_skip]
+/// enum Statement {
+///     Assign { name: String, value: u32 } = "name=Ident Space* Eq Space* value=Number Newline"
+/// }
+///
+/// let program: Program = "x = 1\n\ny = 2\n".parse().unwrap();
+/// assert_eq!(program, Program::Program(vec![
+///     Statement::Assign { name: "x".to_string(), value: 1 },
+///     Statement::Assign { name: "y".to_string(), value: 2 }
+/// ]));
+/// assert!(("x = 1 y = 2\n".parse() as Result<Program, _>).is_err());
+/// ```
+///
+/// # Soft Keywords
+///
+/// Some words are only keywords in certain places, and ordinary identifiers everywhere else. Making
+/// them lexemes of their own would stop them from being used as identifiers, so instead a pattern can
+/// require a lexeme to have specific text, like `Ident('union')`. Double quotes work too, for raw
+/// string patterns.
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Ident = "[a-z]+", LBrace = '{', RBrace = '}', #[skip] Space = ' ' }
+/// #[parser(MyLexer)]
+/// enum Item {
+///     Union { name: Box<Name> } = "Ident('union') name LBrace RBrace",
+///     Call { name: Box<Name> } = "name"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Name {
+///     Name(String) = "0=Ident"
+/// }
+///
+/// let parsed: Item = "union foo {}".parse().unwrap();
+/// assert_eq!(parsed, Item::Union { name: Box::new(Name::Name("foo".to_string())) });
+/// let parsed: Item = "union".parse().unwrap();
+/// assert_eq!(parsed, Item::Call { name: Box::new(Name::Name("union".to_string())) });
+/// ```
+///
+/// # Versioned Grammars
+///
+/// Like lexemes, productions can be marked `#[since(...)]` or `#[cfg_grammar(...)]`, so one grammar
+/// can parse every version of a language. Parsing with a [ParseSession](crate::parser::ParseSession)
+/// made [with_version](crate::parser::ParseSession::with_version) leaves out the productions that
+/// version doesn't have, as if they weren't written:
+///
+/// ```
+/// # use parce::prelude::*;
+/// use parce::version::GrammarVersion;
+///
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { Ident = "[a-z]+", Dot = '.', #[skip] Space = ' ' }
+/// #[parser(MyLexer)]
+/// enum Call {
+///     Call(String) = "0=Ident",
+///     #[since(2018)] Await(String) = "0=Ident Dot Ident('await')",
+///     #[since(2015)] #[cfg_grammar(postfix_macros)] Postfix(String, String) = "0=Ident Dot 1=Ident"
+/// }
+///
+/// let mut session = ParseSession::new(MyLexer::default()).with_version(GrammarVersion::at(&[2015]));
+/// assert!(session.parse_all::<Call>("f.await").is_err());
+///
+/// let mut session = ParseSession::new(MyLexer::default()).with_version(GrammarVersion::at(&[2018]));
+/// assert_eq!(session.parse_all::<Call>("f.await"), Ok(Call::Await("f".to_string())));
+/// ```
+///
+/// Productions can also be marked `#[flag(...)]`, for dialects chosen at runtime; see
+/// [Dialect Flags](crate::prelude::lexer#dialect-flags). Old productions that still parse can be
+/// marked `#[deprecated_syntax(message = "...")]`, like [lexemes](crate::prelude::lexer#deprecated-syntax).
+///
+/// Versions are numbers, or a string of them separated by dots:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { A = 'a' }
+/// #[parser(MyLexer)]
+/// enum Edition {
+///     #[since(next)] A = "A"
+/// }
+/// ```
+///
+/// # Character Patterns
+///
+/// Quoted literals and character classes, like `'let'` and `[a-z]`, match lexemes by their text
+/// instead of their name. A class matches one lexeme whose text is a single character in the class,
+/// and a literal matches one lexeme per character. They're meant for the [CharLexer](crate::scannerless::CharLexer),
+/// which makes every character its own lexeme, so small grammars can skip writing a lexer:
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::scannerless::*;
+///
+/// #[parser(CharLexer)]
+/// enum Setting {
+///     Setting { key: String, value: u32 } = "key=([a-z_]+) ' '* '=' ' '* value=([0-9]+)"
+/// }
+///
+/// assert_eq!("max_depth = 20".parse(), Ok(Setting::Setting { key: "max_depth".to_string(), value: 20 }));
+/// ```
+///
+/// # Lexeme Paths
+///
+/// A lexeme can also be named by a path to its variant, like `tokens::Lexemes::Integer`. The
+/// path is checked by the compiler, so renaming or removing the lexeme is an error in the grammar
+/// instead of a pattern that silently stops matching. In [p!](crate::p) patterns the path keeps
+/// its own span, so editors can find and rename it like any other path.
+///
+/// ```
+/// use parce::prelude::*;
+///
+/// mod tokens {
+///     use parce::prelude::*;
+///
+///     #[lexer(PathLexer)]
+///     pub enum Lexemes {
+///         Integer = "[0-9]+",
+///         Plus = '+',
+///         #[skip] Space = ' '
+///     }
+/// }
+///
+/// #[parser(tokens::PathLexer)]
+/// enum Sum {
+///     Sum(u32, u32) = p!(0 = tokens::Lexemes::Integer tokens::Lexemes::Plus 1 = tokens::Lexemes::Integer),
+///     Number(u32) = "0=tokens::Lexemes::Integer"
+/// }
+///
+/// assert_eq!("1 + 2".parse(), Ok(Sum::Sum(1, 2)));
+/// assert_eq!("3".parse(), Ok(Sum::Number(3)));
+/// ```
+///
+/// The path has to be a lexeme of the rule's lexer:
+///
+/// ```compile_fail
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexemes { Integer = "[0-9]+" }
+/// # #[lexer(OtherLexer)]
+/// # enum OtherLexemes { Integer = "[0-9]+" }
+/// #[parser(MyLexer)]
+/// enum Number {
+///     Number = p!(OtherLexemes::Integer)
+/// }
+/// ```
+///
+/// # Constructors
+///
+/// `#[parser(MyLexer, constructors)]` also generates a function for each production, named after it in
+/// snake case, that takes the fields in order and converts each one with [Into]. This is handy for tools
+/// that build trees instead of parsing them, since boxes, options, and strings don't need to be wrapped
+/// by hand. Productions named after keywords, like `If`, get raw names like `r#if`.
+///
+/// ```
+/// # use parce::prelude::*;
+/// # #[lexer(MyLexer)]
+/// # enum MyLexeme { If = "'if'", Num = "[0-9]+", Minus = '-', #[skip] Space = ' ' }
+/// #[parser(MyLexer, constructors)]
+/// enum Expr {
+///     If { condition: Box<Expr>, then: Box<Expr> } = "If condition then",
+///     NegativeNum(u32) = "Minus 0=Num",
+///     Num(u32) = "0=Num"
+/// }
+///
+/// let built = Expr::r#if(Expr::negative_num(1u8), Expr::num(2u8));
+/// assert_eq!(built, "if -1 2".parse().unwrap());
+/// ```
+#[cfg(feature = "parser")]
 pub use parce_macros::parser;
 
-pub use crate::lexer::Lexer;
-pub use crate::parser::Parse;
-pub use crate::parser::ParseCompletion;
-pub use crate::error::{ParceError};
\ No newline at end of file
+pub use crate::lexer::{Lexer, Lexeme};
+pub use crate::lexer::cursor::{Cursor, StrCursor, IterCursor};
+#[cfg(feature = "parser")]
+pub use crate::parser::{Parse, ParseSession};
+#[cfg(feature = "parser")]
+pub use crate::parser::{ParseCompletion, ParseOutcome};
+#[cfg(feature = "parser")]
+pub use crate::parser::recovery::Recovered;
+pub use crate::error::{ParceError};
+pub use crate::p;
+#[cfg(feature = "parser")]
+pub use crate::node;
\ No newline at end of file
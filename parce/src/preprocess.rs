@@ -0,0 +1,268 @@
+//! Contains the [Preprocessor] trait, for expanding includes and macros before lexing, and the
+//! [Expansion] it builds, which maps the expanded text back to where it came from.
+
+use std::ops::Range;
+use crate::error::{ParceError, ParceErrorInfo};
+
+/// A stage that runs before lexing, turning a source into the text that actually gets parsed.
+///
+/// Implementations build the expanded text out of pieces of the original sources with
+/// [Expansion::copy], and text they made up (like the body of a macro) with [Expansion::generate].
+/// Includes are expanded by calling [preprocess](Preprocessor::preprocess) again on the included
+/// source. Every piece remembers where it came from, so errors found in the expanded text can be
+/// reported at the right place in the right source with [Expansion::remap].
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// use parce::prelude::*;
+/// use parce::preprocess::{Expansion, Preprocessor, Source, SourceError, expand};
+/// use std::collections::HashMap;
+///
+/// /// Replaces `#include name;` with the contents of `name`.
+/// struct Includes(HashMap<&'static str, &'static str>);
+///
+/// impl Preprocessor for Includes {
+///     fn preprocess(&mut self, source: Source, out: &mut Expansion) -> Result<(), SourceError> {
+///         let mut copied = 0;
+///         while let Some(at) = source.text[copied..].find("#include ").map(|i| i + copied) {
+///             out.copy(source, copied..at);
+///             let name_start = at + "#include ".len();
+///             let end = match source.text[name_start..].find(';') {
+///                 Some(i) => name_start + i,
+///                 None => return Err(source.error(at, "#include is missing a ;"))
+///             };
+///             let name = &source.text[name_start..end];
+///             match self.0.get(name).copied() {
+///                 Some(text) => self.preprocess(Source { name, text }, out)?,
+///                 None => return Err(source.error(name_start, format!("{} doesn't exist", name)))
+///             }
+///             copied = end + 1;
+///         }
+///         out.copy(source, copied..source.text.len());
+///         Ok(())
+///     }
+/// }
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes { A = 'a', B = 'b', #[skip] Space = ' ' }
+///
+/// #[parser(MyLexer)]
+/// enum MyGrammar { Rule = "A+ B" }
+///
+/// let mut includes = Includes(HashMap::from([("as", "a a"), ("bad", "a c")]));
+///
+/// let expansion = expand(&mut includes, "main", "#include as; a b").unwrap();
+/// assert_eq!(expansion.text(), "a a a b");
+/// assert_eq!(expansion.parse_all::<MyGrammar>(), Ok(MyGrammar::Rule));
+///
+/// // The error is in the included file, so it's reported there.
+/// let expansion = expand(&mut includes, "main", "a #include bad; b").unwrap();
+/// let error = expansion.parse_all::<MyGrammar>().unwrap_err();
+/// assert_eq!(error.source, "bad");
+/// assert_eq!(error.error.input, "a c");
+/// assert_eq!(error.error.start, 2);
+/// ```
+pub trait Preprocessor {
+    /// Expands `source` onto the end of `out`.
+    fn preprocess(&mut self, source: Source, out: &mut Expansion) -> Result<(), SourceError>;
+}
+
+/// Runs `preprocessor` on the source called `name`, and returns the expansion.
+pub fn expand(preprocessor: &mut impl Preprocessor, name: &str, text: &str) -> Result<Expansion, SourceError> {
+    let mut expansion = Expansion::default();
+    preprocessor.preprocess(Source { name, text }, &mut expansion)?;
+    Ok(expansion)
+}
+
+/// One input to a [Preprocessor], like a file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Source<'a> {
+    /// The name that errors in this source are reported with.
+    pub name: &'a str,
+    /// The original text.
+    pub text: &'a str
+}
+
+impl Source<'_> {
+    /// Creates a preprocessing error at `at`, an index into this source's text.
+    pub fn error(&self, at: usize, message: impl ToString) -> SourceError {
+        SourceError {
+            source: self.name.to_string(),
+            error: ParceError {
+                input: self.text.to_string(),
+                start: at,
+                info: ParceErrorInfo::preprocess(message.to_string())
+            }
+        }
+    }
+}
+
+/// A [ParceError] along with the name of the [Source] it happened in.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SourceError {
+    /// The [name](Source::name) of the source.
+    pub source: String,
+    /// The error, with its input and start pointing into the original source.
+    pub error: ParceError
+}
+
+impl std::fmt::Display for SourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "In {}:\n{}", self.source, self.error)
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+/// The output of a [Preprocessor]: the expanded text, and where each part of it came from.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Expansion {
+    text: String,
+    /// The name and original text of every source that contributed to the expansion.
+    sources: Vec<(String, String)>,
+    segments: Vec<Segment>
+}
+
+/// A piece of the expanded text that came from one place.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Segment {
+    start: usize,
+    len: usize,
+    source: usize,
+    /// Where the piece starts in its source. Generated pieces map every index here.
+    origin: usize,
+    generated: bool
+}
+
+impl Expansion {
+    /// The expanded text, ready to be lexed and parsed.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Appends `source.text[range]` unchanged.
+    pub fn copy(&mut self, source: Source, range: Range<usize>) {
+        self.push(source, &source.text[range.clone()], range.start, false);
+    }
+
+    /// Appends text that isn't in any source, like a macro's expansion. Errors inside it are
+    /// reported at `at`, an index into `source`'s text, like the position of the macro call.
+    pub fn generate(&mut self, source: Source, at: usize, text: &str) {
+        self.push(source, text, at, true);
+    }
+
+    fn push(&mut self, source: Source, text: &str, origin: usize, generated: bool) {
+        if text.is_empty() {
+            return;
+        }
+        let index = match self.sources.iter().position(|(name, _)| name == source.name) {
+            Some(i) => i,
+            None => {
+                self.sources.push((source.name.to_string(), source.text.to_string()));
+                self.sources.len() - 1
+            }
+        };
+        self.segments.push(Segment {
+            start: self.text.len(),
+            len: text.len(),
+            source: index,
+            origin,
+            generated
+        });
+        self.text.push_str(text);
+    }
+
+    /// Finds the source name and index in that source's text that `offset` in the expanded text
+    /// came from. The end of the expanded text maps to the end of the last piece.
+    pub fn origin(&self, offset: usize) -> Option<(&str, usize)> {
+        self.locate(offset).map(|(source, mapped)| (self.sources[source].0.as_str(), mapped))
+    }
+
+    /// Moves an error found in the expanded text to the source it came from.
+    pub fn remap(&self, error: ParceError) -> SourceError {
+        match self.locate(error.start) {
+            Some((source, start)) => {
+                let (name, text) = &self.sources[source];
+                SourceError {
+                    source: name.clone(),
+                    error: ParceError {
+                        input: text.clone(),
+                        start,
+                        info: error.info
+                    }
+                }
+            }
+            None => SourceError {
+                source: String::new(),
+                error
+            }
+        }
+    }
+
+    /// The index of the source `offset` came from, and the index in that source's text.
+    fn locate(&self, offset: usize) -> Option<(usize, usize)> {
+        let i = self.segments.partition_point(|s| s.start <= offset).checked_sub(1)?;
+        let segment = &self.segments[i];
+        let within = offset - segment.start;
+        if within > segment.len || (within == segment.len && i + 1 != self.segments.len()) {
+            return None;
+        }
+        Some((segment.source, if segment.generated { segment.origin } else { segment.origin + within }))
+    }
+
+    /// Parses the expanded text with a new [ParseSession](crate::parser::ParseSession), and remaps
+    /// any error back to its source.
+    #[cfg(feature = "parser")]
+    pub fn parse_all<O>(&self) -> Result<O, SourceError> where O: for<'t> crate::parser::Parseable<Output<'t> = O> {
+        crate::parser::ParseSession::new(O::default_lexer())
+            .parse_all::<O>(&self.text)
+            .map_err(|e| self.remap(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replaces every `$` with `ab`.
+    struct Dollars;
+
+    impl Preprocessor for Dollars {
+        fn preprocess(&mut self, source: Source, out: &mut Expansion) -> Result<(), SourceError> {
+            let mut copied = 0;
+            for (i, c) in source.text.char_indices() {
+                if c == '$' {
+                    out.copy(source, copied..i);
+                    out.generate(source, i, "ab");
+                    copied = i + 1;
+                } else if c == '!' {
+                    return Err(source.error(i, "no !s allowed"));
+                }
+            }
+            out.copy(source, copied..source.text.len());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn origins() {
+        let expansion = expand(&mut Dollars, "main", "x$y$").unwrap();
+        assert_eq!(expansion.text(), "xabyab");
+        let origins: Vec<_> = (0..=6).map(|i| expansion.origin(i).unwrap().1).collect();
+        assert_eq!(origins, vec![0, 1, 1, 2, 3, 3, 3]);
+        assert_eq!(expansion.origin(7), None);
+
+        let error = expansion.remap(ParceError {
+            input: expansion.text().to_string(),
+            start: 4,
+            info: ParceErrorInfo::assemble()
+        });
+        assert_eq!(error.source, "main");
+        assert_eq!(error.error.input, "x$y$");
+        assert_eq!(error.error.start, 3);
+
+        let error = expand(&mut Dollars, "main", "a!").unwrap_err();
+        assert_eq!(error.error.start, 1);
+        assert_eq!(error.error.info, ParceErrorInfo::Preprocess { message: "no !s allowed".to_string() });
+    }
+}
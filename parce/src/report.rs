@@ -0,0 +1,1298 @@
+//! Grammar-wide analysis that can't be done inside a single macro invocation.
+//!
+//! The [parser](parce_macros::parser) macro only sees one rule at a time, so checks that span
+//! multiple rules are done at runtime instead, using a static description of each rule
+//! that the macro generates.
+
+use crate::fingerprint::combine_fingerprints;
+use crate::lexer::Lexer;
+use crate::parser::Parseable;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Static description of a grammar rule, generated by the [parser](parce_macros::parser) macro.
+#[derive(Debug)]
+pub struct RuleInfo {
+    /// The name of the rule's enum.
+    pub name: &'static str,
+    /// The module the rule's enum was declared in.
+    pub module: &'static str,
+    /// One entry for each variant of the enum, in declaration order.
+    pub productions: &'static [Production],
+    /// What each state of each route can match next, indexed by route, then state. See
+    /// [Parseable::commands] for what routes and states are.
+    pub expected: &'static [&'static [Expected]],
+    /// One entry for each route, indexed by route number.
+    pub routes: &'static [Route],
+    /// Lexemes from the rule's `#[sync(...)]` attribute, which usually end it. Error recovery
+    /// skips ahead to these to find where a broken rule stops.
+    pub sync: &'static [&'static str],
+    /// Set by `#[deterministic]`. See [determinism_report].
+    pub deterministic: bool,
+    /// A hash of the rule's declaration, without its doc comments or visibility. See
+    /// [GrammarInfo::fingerprint].
+    pub fingerprint: u64
+}
+
+impl RuleInfo {
+    /// What an automaton of this rule on `route` in `state` can match next, or `None` if the route
+    /// doesn't exist. Repeating routes only list one cycle of states, so `state` wraps around.
+    pub fn expected(&self, route: u32, state: u32) -> Option<&'static Expected> {
+        let states = self.expected.get(route as usize)?;
+        states.get(state as usize % states.len().max(1))
+    }
+
+    /// Names the grammar construct an automaton on `route` is matching, like `Expr::Call` for a
+    /// main route, or `"(Comma #Expr)*" in Expr::Call` for a route inside a production. Returns `None`
+    /// if the route doesn't exist.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    /// use parce::parser::Parseable;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     A = 'a',
+    ///     B = 'b',
+    ///     C = 'c'
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum MyGrammar {
+    ///     Rule = "A (B | C) A", // route 0, with B and C on routes 2 and 3
+    ///     Other = "C+"          // route 1, with each C on route 4
+    /// }
+    ///
+    /// let info = MyGrammar::rule_info();
+    /// assert_eq!(info.route_name(0).unwrap(), "MyGrammar::Rule");
+    /// assert_eq!(info.route_name(2).unwrap(), "\"B\" in MyGrammar::Rule");
+    /// assert_eq!(info.route_name(4).unwrap(), "\"C\" in MyGrammar::Other");
+    /// assert!(info.route_name(5).is_none());
+    /// ```
+    pub fn route_name(&self, route: u32) -> Option<String> {
+        let info = self.routes.get(route as usize)?;
+        let production = self.productions.get(info.production)?.name;
+        if (route as usize) < self.productions.len() {
+            Some(format!("{}::{}", self.name, production))
+        } else {
+            Some(format!("\"{}\" in {}::{}", info.pattern, self.name, production))
+        }
+    }
+}
+
+/// What an automaton can match next, from the tables the [parser](parce_macros::parser) macro
+/// generates for every state of every route.
+#[derive(Debug)]
+pub struct Expected {
+    /// Lexemes matched by this state, or by routes it spawns.
+    pub lexemes: &'static [&'static str],
+    /// Whether any lexeme can be matched, because of a `.`
+    pub any: bool,
+    /// Other rules this state spawns, whose first lexemes can also be matched.
+    pub rules: &'static [fn() -> &'static RuleInfo],
+    /// Whether the route can finish here without matching anything else, in which case whatever
+    /// comes after the rule can also come next.
+    pub can_end: bool
+}
+
+impl Expected {
+    /// Every lexeme that can come next, including the ones other rules can start with. `"."`
+    /// means any lexeme.
+    ///
+    /// Rules are looked up in their own tables, and are assumed to match at least one lexeme.
+    pub fn lexemes(&self) -> Vec<&'static str> {
+        let mut lexemes: BTreeSet<&'static str> = self.lexemes.iter().copied().collect();
+        let mut any = self.any;
+        let mut seen: Vec<&'static RuleInfo> = vec![];
+        let mut todo: Vec<&'static RuleInfo> = self.rules.iter().map(|r| r()).collect();
+        while let Some(rule) = todo.pop() {
+            if seen.iter().any(|r| std::ptr::eq(*r, rule)) {
+                continue;
+            }
+            seen.push(rule);
+            for route in 0..rule.productions.len() {
+                if let Some(first) = rule.expected(route as u32, 0) {
+                    lexemes.extend(first.lexemes.iter());
+                    any |= first.any;
+                    todo.extend(first.rules.iter().map(|r| r()));
+                }
+            }
+        }
+        let mut lexemes: Vec<_> = lexemes.into_iter().collect();
+        if any {
+            lexemes.push(".");
+        }
+        lexemes
+    }
+}
+
+/// Static description of one production (enum variant) of a rule.
+#[derive(Debug)]
+pub struct Production {
+    /// The name of the variant.
+    pub name: &'static str,
+    /// The variant's pattern.
+    pub pattern: Pattern,
+    /// How ambiguities involving this production are resolved.
+    pub resolve: Resolve,
+    /// Set by `#[no_skip]`. Skipped lexemes are ordinary lexemes inside the production, including
+    /// inside the rules it contains, so they can only appear where its pattern names them.
+    pub no_skip: bool,
+    /// If the production has a cut (`!`), the state its automaton is in once it has matched
+    /// everything before the cut.
+    pub cut: Option<u32>,
+    /// The message of the production's `#[deprecated_syntax(...)]` attribute, if it has one.
+    pub deprecated: Option<&'static str>
+}
+
+/// Static description of one route of a rule. See [Parseable::commands] for what routes are.
+#[derive(Debug)]
+pub struct Route {
+    /// The index of the production the route is part of.
+    pub production: usize,
+    /// The part of the production's pattern the route matches. For a main route this is the whole
+    /// pattern, as written.
+    pub pattern: &'static str
+}
+
+/// How a production breaks ties when the same input can be parsed more than one way, set with the
+/// `#[resolve(...)]` and `#[prefer_shift]` attributes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Resolve {
+    /// No preference. Whichever parse the engine finishes last wins.
+    #[default]
+    Unspecified,
+    /// Prefer the parse where this production matches as much as it can, like a shift in LR terms.
+    /// This binds a dangling `else` to the nearest `if`.
+    Longest,
+    /// Prefer the parse where this production stops as early as it can, like a reduce.
+    Shortest
+}
+
+/// The structure of a parser pattern. Fields are replaced by references to the rule they
+/// hold, and assignments by the pattern being assigned from.
+#[derive(Debug)]
+pub enum Pattern {
+    /// A single lexeme, by name.
+    Lexeme(&'static str),
+    /// A lexeme whose text must also be exactly the second string, like `Ident('union')`.
+    Keyword(&'static str, &'static str),
+    /// A character matched against the text of any lexeme, written as a quoted character or a
+    /// class, like `'a'` or `[a-z]`.
+    Text(&'static str),
+    /// Another rule.
+    Rule(fn() -> &'static RuleInfo),
+    /// The `.` operator, which matches any lexeme.
+    Dot,
+    /// A sequence of patterns.
+    And(&'static [Pattern]),
+    /// A choice between patterns.
+    Or(&'static [Pattern]),
+    /// A pattern repeated between a minimum and an optional maximum number of times. `*`, `+`,
+    /// `?`, and the range operators are all represented by this.
+    Repeat(&'static Pattern, usize, Option<usize>)
+}
+
+impl Pattern {
+    fn visit_rules(&self, f: &mut impl FnMut(&'static RuleInfo)) {
+        match self {
+            Pattern::Rule(r) => f(r()),
+            Pattern::And(ps) | Pattern::Or(ps) => {
+                for p in ps.iter() {
+                    p.visit_rules(f);
+                }
+            }
+            Pattern::Repeat(p, _, _) => p.visit_rules(f),
+            Pattern::Lexeme(_) | Pattern::Keyword(_, _) | Pattern::Text(_) | Pattern::Dot => {}
+        }
+    }
+
+    /// The most alternatives of any or group in the pattern, or 0 if it has none.
+    fn widest_or(&self) -> usize {
+        match self {
+            Pattern::Or(ps) => ps.iter().map(Pattern::widest_or).fold(ps.len(), usize::max),
+            Pattern::And(ps) => ps.iter().map(Pattern::widest_or).max().unwrap_or(0),
+            Pattern::Repeat(p, _, _) => p.widest_or(),
+            Pattern::Lexeme(_) | Pattern::Keyword(_, _) | Pattern::Text(_) | Pattern::Rule(_) | Pattern::Dot => 0
+        }
+    }
+
+    fn visit_lexemes(&self, f: &mut impl FnMut(Option<&'static str>)) {
+        match self {
+            Pattern::Lexeme(l) | Pattern::Keyword(l, _) => f(Some(l)),
+            Pattern::Dot | Pattern::Text(_) => f(None),
+            Pattern::And(ps) | Pattern::Or(ps) => {
+                for p in ps.iter() {
+                    p.visit_lexemes(f);
+                }
+            }
+            Pattern::Repeat(p, _, _) => p.visit_lexemes(f),
+            Pattern::Rule(_) => {}
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    /// Writes the pattern back out in the syntax used by the [parser](parce_macros::parser) macro.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn grouped(p: &Pattern) -> String {
+            match p {
+                Pattern::And(_) | Pattern::Or(_) => format!("({})", p),
+                _ => p.to_string()
+            }
+        }
+
+        match self {
+            Pattern::Lexeme(l) => write!(f, "{}", l),
+            Pattern::Keyword(l, text) => write!(f, "{}('{}')", l, text),
+            Pattern::Text(t) => write!(f, "{}", t),
+            Pattern::Rule(r) => write!(f, "#{}", r().name),
+            Pattern::Dot => write!(f, "."),
+            Pattern::And(ps) => write!(f, "{}", ps.iter().map(|p| match p {
+                Pattern::Or(_) => grouped(p),
+                _ => p.to_string()
+            }).collect::<Vec<_>>().join(" ")),
+            Pattern::Or(ps) => write!(f, "{}", ps.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(" | ")),
+            Pattern::Repeat(p, min, max) => {
+                let p = grouped(p);
+                match (min, max) {
+                    (0, None) => write!(f, "{}*", p),
+                    (1, None) => write!(f, "{}+", p),
+                    (0, Some(1)) => write!(f, "{}?", p),
+                    (min, None) => write!(f, "{}{{{},}}", p, min),
+                    (min, Some(max)) if min == max => write!(f, "{}{{{}}}", p, min),
+                    (min, Some(max)) => write!(f, "{}{{{},{}}}", p, min, max)
+                }
+            }
+        }
+    }
+}
+
+/// Static description of a whole grammar: a root rule, every rule it can reach, and the lexemes
+/// of its lexer. Generated as [Parseable::GRAMMAR] for every rule.
+///
+/// # Example
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::Parseable;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     A = 'a',
+///     B = 'b'
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Root {
+///     Thing = "A+ #Child?"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Child {
+///     Thing = "B"
+/// }
+///
+/// let grammar = &Root::GRAMMAR;
+/// assert_eq!(grammar.rules().iter().map(|r| r.name).collect::<Vec<_>>(), vec!["Root", "Child"]);
+/// assert_eq!(grammar.lexemes, &["A", "B"]);
+/// assert_eq!(grammar.rule("Root").unwrap().productions[0].pattern.to_string(), "A+ #Child?");
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct GrammarInfo {
+    /// Returns the rule the grammar starts at.
+    pub root: fn() -> &'static RuleInfo,
+    /// The lexemes the lexer outputs. Skipped and fragment lexemes are not included.
+    pub lexemes: &'static [&'static str],
+    /// The lexer's [fingerprint](Lexer::FINGERPRINT).
+    pub lexer_fingerprint: u64
+}
+
+impl GrammarInfo {
+    /// The rule the grammar starts at.
+    pub fn root(&self) -> &'static RuleInfo {
+        (self.root)()
+    }
+
+    /// Every rule reachable from the root, including the root itself (first), in the order
+    /// they are first referenced.
+    pub fn rules(&self) -> Vec<&'static RuleInfo> {
+        reachable_from(self.root())
+    }
+
+    /// Finds a reachable rule by name. If more than one rule has the name, returns the first.
+    pub fn rule(&self, name: &str) -> Option<&'static RuleInfo> {
+        self.rules().into_iter().find(|r| r.name == name)
+    }
+
+    /// A hash of the whole grammar: the lexer's declaration, and the declaration of every reachable
+    /// rule, without doc comments or visibility. It is the same on every build and every platform until the
+    /// grammar changes, so it can be stored next to cached parse results or serialized trees, to
+    /// tell when they are out of date.
+    ///
+    /// Changes that can't change what is parsed, like renaming a lexer mode, still change the
+    /// fingerprint. [Parseable::FINGERPRINT] is the same for just one rule and its lexer, and can
+    /// be used in `const` items.
+    ///
+    /// ```
+    /// use parce::prelude::*;
+    /// use parce::parser::Parseable;
+    ///
+    /// #[lexer(MyLexer)]
+    /// enum MyLexemes {
+    ///     A = 'a',
+    ///     B = 'b'
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Root {
+    ///     Thing = "A #Child"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum Child {
+    ///     /// Docs don't count.
+    ///     Thing = "B"
+    /// }
+    ///
+    /// #[parser(MyLexer)]
+    /// enum OtherChild {
+    ///     Thing = "B"
+    /// }
+    ///
+    /// let fingerprint = Root::GRAMMAR.fingerprint();
+    /// assert_eq!(fingerprint, Root::GRAMMAR.fingerprint());
+    /// assert_ne!(fingerprint, Child::GRAMMAR.fingerprint());
+    /// assert_ne!(Child::FINGERPRINT, OtherChild::FINGERPRINT);
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        self.rules().iter().fold(self.lexer_fingerprint, |hash, rule| combine_fingerprints(hash, rule.fingerprint))
+    }
+
+    /// The most automata the parser spawns at once for one choice in the grammar: the most
+    /// productions of any rule, or alternatives of any or group. If this is more than
+    /// [INLINE_SPAWNS](crate::parser::automata::INLINE_SPAWNS), those choices allocate every time
+    /// they're tried, and the `wide-automata` feature may make the grammar faster.
+    pub fn widest_choice(&self) -> usize {
+        self.rules().iter()
+            .flat_map(|rule| std::iter::once(rule.productions.len()).chain(rule.productions.iter().map(|p| p.pattern.widest_or())))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl std::fmt::Display for GrammarInfo {
+    /// Lists every reachable rule and its productions, one per line.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut first = true;
+        for rule in self.rules() {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            write!(f, "{}:", rule.name)?;
+            for production in rule.productions {
+                write!(f, "\n    {} = \"{}\"", production.name, production.pattern)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn reachable_from(root: &'static RuleInfo) -> Vec<&'static RuleInfo> {
+    let mut reachable = vec![root];
+    let mut i = 0;
+    while i < reachable.len() {
+        let mut found = vec![];
+        for production in reachable[i].productions {
+            production.pattern.visit_rules(&mut |r| found.push(r));
+        }
+        for r in found {
+            if !reachable.iter().any(|seen| std::ptr::eq(*seen, r)) {
+                reachable.push(r);
+            }
+        }
+        i += 1;
+    }
+    reachable
+}
+
+type RuleKey = *const RuleInfo;
+
+fn key(rule: &'static RuleInfo) -> RuleKey {
+    rule
+}
+
+/// A set of lexemes that can start (or follow) a pattern.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+struct LexemeSet {
+    lexemes: BTreeSet<&'static str>,
+    /// Contains every lexeme, because of a `.` or a character pattern.
+    any: bool
+}
+
+impl LexemeSet {
+    fn extend(&mut self, other: &LexemeSet) {
+        self.lexemes.extend(other.lexemes.iter());
+        self.any |= other.any;
+    }
+
+    fn shared(&self, other: &LexemeSet) -> Vec<&'static str> {
+        let mut shared: Vec<&'static str> = match (self.any, other.any) {
+            (true, _) => other.lexemes.iter().copied().collect(),
+            (false, true) => self.lexemes.iter().copied().collect(),
+            (false, false) => self.lexemes.intersection(&other.lexemes).copied().collect()
+        };
+        if self.any && other.any {
+            shared.push(".");
+        }
+        shared
+    }
+}
+
+/// Whole-grammar facts computed from the reachable rules.
+struct Analysis {
+    reachable: Vec<&'static RuleInfo>,
+    productive: HashSet<RuleKey>,
+    nullable: HashSet<RuleKey>,
+    first: HashMap<RuleKey, LexemeSet>,
+    follow: HashMap<RuleKey, LexemeSet>
+}
+
+impl Analysis {
+    fn new(root: &'static RuleInfo) -> Analysis {
+        let mut analysis = Analysis {
+            reachable: reachable_from(root),
+            productive: HashSet::new(),
+            nullable: HashSet::new(),
+            first: HashMap::new(),
+            follow: HashMap::new()
+        };
+
+        // Each of these only grows, so iterate until nothing changes.
+        loop {
+            let mut changed = false;
+            for rule in analysis.reachable.clone() {
+                if !analysis.productive.contains(&key(rule)) && rule.productions.iter().any(|p| analysis.terminates(&p.pattern)) {
+                    analysis.productive.insert(key(rule));
+                    changed = true;
+                }
+                if !analysis.nullable.contains(&key(rule)) && rule.productions.iter().any(|p| analysis.nullable(&p.pattern)) {
+                    analysis.nullable.insert(key(rule));
+                    changed = true;
+                }
+                let mut first = LexemeSet::default();
+                for production in rule.productions {
+                    first.extend(&analysis.first_of(&production.pattern));
+                }
+                if analysis.first.get(&key(rule)) != Some(&first) {
+                    analysis.first.insert(key(rule), first);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        loop {
+            let before = analysis.follow.clone();
+            for rule in analysis.reachable.clone() {
+                let after = analysis.follow.get(&key(rule)).cloned().unwrap_or_default();
+                for production in rule.productions {
+                    let mut follow = std::mem::take(&mut analysis.follow);
+                    analysis.walk(&production.pattern, &after, &mut follow, &mut |_, _| {});
+                    analysis.follow = follow;
+                }
+            }
+            if analysis.follow == before {
+                break;
+            }
+        }
+
+        analysis
+    }
+
+    fn terminates(&self, pattern: &Pattern) -> bool {
+        match pattern {
+            Pattern::Lexeme(_) | Pattern::Keyword(_, _) | Pattern::Text(_) | Pattern::Dot => true,
+            Pattern::Rule(r) => self.productive.contains(&key(r())),
+            Pattern::And(ps) => ps.iter().all(|p| self.terminates(p)),
+            Pattern::Or(ps) => ps.iter().any(|p| self.terminates(p)),
+            Pattern::Repeat(p, min, _) => *min == 0 || self.terminates(p)
+        }
+    }
+
+    fn nullable(&self, pattern: &Pattern) -> bool {
+        match pattern {
+            Pattern::Lexeme(_) | Pattern::Keyword(_, _) | Pattern::Text(_) | Pattern::Dot => false,
+            Pattern::Rule(r) => self.nullable.contains(&key(r())),
+            Pattern::And(ps) => ps.iter().all(|p| self.nullable(p)),
+            Pattern::Or(ps) => ps.iter().any(|p| self.nullable(p)),
+            Pattern::Repeat(p, min, _) => *min == 0 || self.nullable(p)
+        }
+    }
+
+    fn first_of(&self, pattern: &Pattern) -> LexemeSet {
+        let mut set = LexemeSet::default();
+        match pattern {
+            Pattern::Lexeme(l) | Pattern::Keyword(l, _) => {
+                set.lexemes.insert(l);
+            }
+            // Character patterns match the text of any lexeme, like a `.` does.
+            Pattern::Dot | Pattern::Text(_) => set.any = true,
+            Pattern::Rule(r) => {
+                if let Some(first) = self.first.get(&key(r())) {
+                    set.extend(first);
+                }
+            }
+            Pattern::And(ps) => {
+                for p in ps.iter() {
+                    set.extend(&self.first_of(p));
+                    if !self.nullable(p) {
+                        break;
+                    }
+                }
+            }
+            Pattern::Or(ps) => {
+                for p in ps.iter() {
+                    set.extend(&self.first_of(p));
+                }
+            }
+            Pattern::Repeat(p, _, _) => set.extend(&self.first_of(p))
+        }
+        set
+    }
+
+    /// The lexemes that could be next when choosing whether to take `pattern`, given what
+    /// can come `after` it.
+    fn lookahead(&self, pattern: &Pattern, after: &LexemeSet) -> LexemeSet {
+        let mut set = self.first_of(pattern);
+        if self.nullable(pattern) {
+            set.extend(after);
+        }
+        set
+    }
+
+    /// Walks a pattern knowing which lexemes can come `after` it. Grows the follow sets of every
+    /// rule referenced, and calls `on_conflict` with the lexemes shared by two alternatives of an or
+    /// group (by index), or by going around a repetition again and moving past it (`None`).
+    fn walk(
+        &self,
+        pattern: &Pattern,
+        after: &LexemeSet,
+        follow: &mut HashMap<RuleKey, LexemeSet>,
+        on_conflict: &mut impl FnMut(Option<(usize, usize)>, Vec<&'static str>)
+    ) {
+        match pattern {
+            Pattern::Lexeme(_) | Pattern::Keyword(_, _) | Pattern::Text(_) | Pattern::Dot => {}
+            Pattern::Rule(r) => follow.entry(key(r())).or_default().extend(after),
+            Pattern::And(ps) => {
+                let mut rest = after.clone();
+                for p in ps.iter().rev() {
+                    self.walk(p, &rest, follow, on_conflict);
+                    rest = self.lookahead(p, &rest);
+                }
+            }
+            Pattern::Or(ps) => {
+                let lookaheads: Vec<_> = ps.iter().map(|p| self.lookahead(p, after)).collect();
+                for i in 0..ps.len() {
+                    for j in i+1..ps.len() {
+                        let shared = lookaheads[i].shared(&lookaheads[j]);
+                        if !shared.is_empty() {
+                            on_conflict(Some((i, j)), shared);
+                        }
+                    }
+                }
+                for p in ps.iter() {
+                    self.walk(p, after, follow, on_conflict);
+                }
+            }
+            Pattern::Repeat(p, min, max) => {
+                if *max != Some(*min) {
+                    let shared = self.first_of(p).shared(after);
+                    if !shared.is_empty() {
+                        on_conflict(None, shared);
+                    }
+                }
+                if *max == Some(1) {
+                    self.walk(p, after, follow, on_conflict);
+                } else {
+                    let mut again = self.first_of(p);
+                    again.extend(after);
+                    self.walk(p, &again, follow, on_conflict);
+                }
+            }
+        }
+    }
+}
+
+/// The result of [grammar_report].
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct GrammarReport {
+    /// Rules registered with the lexer that can't be reached from the root rule.
+    pub unreachable_rules: Vec<&'static str>,
+    /// Reachable rules that can never finish matching, because every production eventually
+    /// requires a rule that can't finish matching.
+    pub unproductive_rules: Vec<&'static str>,
+    /// Lexemes the lexer outputs that no reachable rule ever matches. Skipped and fragment lexemes
+    /// are not included.
+    pub unused_lexemes: Vec<&'static str>,
+    /// Names shared by more than one rule or lexeme of the lexer.
+    pub name_collisions: Vec<NameCollision>
+}
+
+impl GrammarReport {
+    /// Returns true if the report didn't find anything.
+    pub fn is_clean(&self) -> bool {
+        self.unreachable_rules.is_empty() && self.unproductive_rules.is_empty() && self.unused_lexemes.is_empty()
+            && self.name_collisions.is_empty()
+    }
+}
+
+/// A name that refers to more than one thing in grammars built on the same lexer.
+///
+/// These always compile, because rules are distinct Rust types, but they make patterns and
+/// reports ambiguous to the people reading them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NameCollision {
+    /// Rules with the same name, declared in different modules.
+    Rules {
+        name: &'static str,
+        /// The module of each rule with the name.
+        modules: Vec<&'static str>
+    },
+    /// A rule with the same name as one of the lexemes.
+    LexemeAndRule {
+        name: &'static str,
+        /// The module of the rule.
+        module: &'static str
+    }
+}
+
+impl std::fmt::Display for NameCollision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameCollision::Rules { name, modules } => write!(
+                f,
+                "rule {} is declared in {}. Rename all but one of them, for example to {}{}, so \
+                reports and `#` references can't be confused.",
+                name,
+                modules.iter().map(|m| format!("{}::{}", m, name)).collect::<Vec<_>>().join(" and "),
+                modules[1].rsplit("::").next().unwrap_or(modules[1]).split('_').map(capitalize).collect::<String>(),
+                name
+            ),
+            NameCollision::LexemeAndRule { name, module } => write!(
+                f,
+                "{}::{} has the same name as the lexeme {}. In patterns `{}` always means the lexeme and `#{}` \
+                the rule, so rename one of them, for example to {}Rule.",
+                module, name, name, name, name, name
+            )
+        }
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+impl std::fmt::Display for GrammarReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_clean() {
+            return write!(f, "no problems found");
+        }
+        let mut sections = vec![];
+        for (title, list) in [
+            ("Unreachable rules", &self.unreachable_rules),
+            ("Unproductive rules", &self.unproductive_rules),
+            ("Unused lexemes", &self.unused_lexemes)
+        ] {
+            if !list.is_empty() {
+                sections.push(format!("{}: {}", title, list.join(", ")));
+            }
+        }
+        for collision in &self.name_collisions {
+            sections.push(format!("Name collision: {}", collision));
+        }
+        write!(f, "{}", sections.join("\n"))
+    }
+}
+
+/// Analyses the grammar rooted at `P`, and every other rule registered with the same lexer.
+///
+/// Rules are only registered with the `inventory` feature. Otherwise, rules that aren't reachable
+/// from `P` are unknown to the report, so use [grammar_report_with] to list them.
+///
+/// # Example
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::report::grammar_report;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     A = 'a',
+///     B = 'b',
+///     Unused = 'c'
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Root {
+///     Thing = "A #Child"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Child {
+///     Thing = "B"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Orphan {
+///     Thing = "A"
+/// }
+///
+/// let report = grammar_report::<Root>();
+/// assert_eq!(report.unused_lexemes, vec!["Unused"]);
+/// # #[cfg(feature = "inventory")]
+/// assert_eq!(report.unreachable_rules, vec!["Orphan"]);
+/// ```
+pub fn grammar_report<P: Parseable>() -> GrammarReport {
+    grammar_report_with::<P>(&[])
+}
+
+/// Like [grammar_report], but also checks `rules`, as if they were registered with the lexer. This
+/// is how to find unreachable rules and name collisions without the `inventory` feature. Rules that
+/// are also registered, or reachable from `P`, are only counted once.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::parser::Parseable;
+/// use parce::report::grammar_report_with;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     A = 'a',
+///     B = 'b'
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Root {
+///     Thing = "A #Child"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Child {
+///     Thing = "B"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Orphan {
+///     Thing = "A"
+/// }
+///
+/// let report = grammar_report_with::<Root>(&[Child::rule_info, Orphan::rule_info]);
+/// assert_eq!(report.unreachable_rules, vec!["Orphan"]);
+/// ```
+pub fn grammar_report_with<P: Parseable>(rules: &[fn() -> &'static RuleInfo]) -> GrammarReport {
+    let analysis = Analysis::new(P::rule_info());
+
+    let mut registered = <P::Lexer as Lexer>::registered_rules();
+    for rule in analysis.reachable.iter().copied().chain(rules.iter().map(|r| r())) {
+        if !registered.iter().any(|seen| std::ptr::eq(*seen, rule)) {
+            registered.push(rule);
+        }
+    }
+    registered.sort_by_key(|r| r.name);
+
+    let mut used = HashSet::new();
+    let mut any_lexeme = false;
+    for rule in &analysis.reachable {
+        for production in rule.productions {
+            production.pattern.visit_lexemes(&mut |l| match l {
+                Some(l) => { used.insert(l); }
+                None => any_lexeme = true
+            });
+        }
+    }
+
+    let mut name_collisions = vec![];
+    let mut i = 0;
+    while i < registered.len() {
+        let name = registered[i].name;
+        let mut modules = vec![];
+        while i < registered.len() && registered[i].name == name {
+            modules.push(registered[i].module);
+            i += 1;
+        }
+        if modules.len() > 1 {
+            modules.sort_unstable();
+            name_collisions.push(NameCollision::Rules { name, modules: modules.clone() });
+        }
+        if <P::Lexer as Lexer>::LEXEMES.contains(&name) {
+            for module in modules {
+                name_collisions.push(NameCollision::LexemeAndRule { name, module });
+            }
+        }
+    }
+
+    GrammarReport {
+        unreachable_rules: registered.iter()
+            .filter(|r| !analysis.reachable.iter().any(|seen| std::ptr::eq(*seen, **r)))
+            .map(|r| r.name)
+            .collect(),
+        unproductive_rules: analysis.reachable.iter()
+            .filter(|r| !analysis.productive.contains(&key(r)))
+            .map(|r| r.name)
+            .collect(),
+        unused_lexemes: <P::Lexer as Lexer>::LEXEMES.iter()
+            .filter(|l| !any_lexeme && !used.contains(*l))
+            .copied()
+            .collect(),
+        name_collisions
+    }
+}
+
+/// Where a [Conflict] was found.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ConflictSite {
+    /// Between two productions of the rule, by name.
+    Productions(&'static str, &'static str),
+    /// Between two alternatives (numbered from 0) of an or group inside a production.
+    OrGroup {
+        production: &'static str,
+        alternatives: (usize, usize)
+    },
+    /// Between going around a repetition (or optional part) inside a production again, and moving
+    /// past it.
+    Repetition {
+        production: &'static str
+    }
+}
+
+/// Two alternatives of a rule that can start with the same lexeme, so the parser has to
+/// spawn automata for both and see which survive.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Conflict {
+    /// The rule the conflict is in.
+    pub rule: &'static str,
+    /// Which alternatives conflict.
+    pub site: ConflictSite,
+    /// The lexemes both alternatives can start with. `"."` means any lexeme.
+    pub shared: Vec<&'static str>
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let shared = self.shared.iter()
+            .map(|l| if *l == "." { "any lexeme" } else { l })
+            .collect::<Vec<_>>()
+            .join(", ");
+        match &self.site {
+            ConflictSite::Productions(a, b) => write!(
+                f,
+                "{}: productions {} and {} can both start with {}. Consider left-factoring them into one \
+                production, like \"<shared prefix> (<rest of {}> | <rest of {}>)\".",
+                self.rule, a, b, shared, a, b
+            ),
+            ConflictSite::OrGroup { production, alternatives: (a, b) } => write!(
+                f,
+                "{}::{}: alternatives {} and {} of an or group can both start with {}. Consider moving \
+                their shared prefix out in front of the group.",
+                self.rule, production, a, b, shared
+            ),
+            ConflictSite::Repetition { production } => write!(
+                f,
+                "{}::{}: a repetition can both go on and stop at {}. Consider ending it with a lexeme \
+                that can't start another repeat.",
+                self.rule, production, shared
+            )
+        }
+    }
+}
+
+/// Finds every place in the grammar rooted at `P` where two alternatives can start with the same
+/// lexeme, including a repetition whose next repeat can start with what comes after it.
+///
+/// These aren't errors, the parser handles them by speculating on both alternatives. But each one
+/// multiplies the number of automata alive at once, so this is the place to look when tuning a
+/// grammar for speed. Alternatives that can match nothing are compared using what can follow them.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::report::{conflict_report, ConflictSite};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     A = 'a',
+///     B = 'b',
+///     C = 'c'
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Slow {
+///     AB = "A B",
+///     AC = "A C"
+/// }
+///
+/// let conflicts = conflict_report::<Slow>();
+/// assert_eq!(conflicts[0].site, ConflictSite::Productions("AB", "AC"));
+/// assert_eq!(conflicts[0].shared, vec!["A"]);
+/// ```
+pub fn conflict_report<P: Parseable>() -> Vec<Conflict> {
+    let analysis = Analysis::new(P::rule_info());
+    conflicts(&analysis, &analysis.reachable)
+}
+
+/// Finds the conflicts in every `#[deterministic]` rule reachable from `P`, and every rule reachable
+/// from those, which are the rules that must parse without speculating.
+///
+/// The [parser](parce_macros::parser) macro rejects conflicts between lexemes named in a deterministic
+/// rule itself, but it can't see into other rules. Checking that this is empty in a test extends
+/// the guarantee to the whole grammar.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::report::{determinism_report, ConflictSite};
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     A = 'a',
+///     B = 'b'
+/// }
+///
+/// #[parser(MyLexer)]
+/// #[deterministic]
+/// enum Fast {
+///     AB = "A B",
+///     Other = "#Slow"
+/// }
+///
+/// #[parser(MyLexer)]
+/// enum Slow {
+///     A = "A"
+/// }
+///
+/// let conflicts = determinism_report::<Fast>();
+/// assert_eq!(conflicts[0].site, ConflictSite::Productions("AB", "Other"));
+/// assert!(determinism_report::<Slow>().is_empty());
+/// ```
+pub fn determinism_report<P: Parseable>() -> Vec<Conflict> {
+    let analysis = Analysis::new(P::rule_info());
+    let mut rules: Vec<&'static RuleInfo> = vec![];
+    for rule in analysis.reachable.iter().filter(|r| r.deterministic) {
+        for rule in reachable_from(rule) {
+            if !rules.iter().any(|seen| std::ptr::eq(*seen, rule)) {
+                rules.push(rule);
+            }
+        }
+    }
+    // Keep the order conflict_report uses.
+    let rules: Vec<_> = analysis.reachable.iter().copied().filter(|r| rules.iter().any(|seen| std::ptr::eq(*seen, *r))).collect();
+    conflicts(&analysis, &rules)
+}
+
+fn conflicts(analysis: &Analysis, rules: &[&'static RuleInfo]) -> Vec<Conflict> {
+    let mut conflicts = vec![];
+    let mut follow = analysis.follow.clone();
+
+    for rule in rules {
+        let after = analysis.follow.get(&key(rule)).cloned().unwrap_or_default();
+        let lookaheads: Vec<_> = rule.productions.iter().map(|p| analysis.lookahead(&p.pattern, &after)).collect();
+        for i in 0..lookaheads.len() {
+            for j in i+1..lookaheads.len() {
+                let shared = lookaheads[i].shared(&lookaheads[j]);
+                if !shared.is_empty() {
+                    conflicts.push(Conflict {
+                        rule: rule.name,
+                        site: ConflictSite::Productions(rule.productions[i].name, rule.productions[j].name),
+                        shared
+                    });
+                }
+            }
+        }
+        for production in rule.productions {
+            analysis.walk(&production.pattern, &after, &mut follow, &mut |alternatives, shared| {
+                conflicts.push(Conflict {
+                    rule: rule.name,
+                    site: match alternatives {
+                        Some(alternatives) => ConflictSite::OrGroup {
+                            production: production.name,
+                            alternatives
+                        },
+                        None => ConflictSite::Repetition {
+                            production: production.name
+                        }
+                    },
+                    shared
+                })
+            });
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use super::*;
+
+    #[lexer(ReportLexer)]
+    enum ReportLexeme {
+        A = 'a',
+        B = 'b',
+        C = 'c',
+        Unused = 'u',
+        #[frag] Fragment = 'f',
+        #[skip] WhiteSpace = "[ \n\r\t]"
+    }
+
+    #[parser(ReportLexer)]
+    enum Root {
+        Good = "A #Child",
+        Bad = "B #Ping"
+    }
+
+    #[parser(ReportLexer)]
+    enum Child {
+        Thing = "B C?"
+    }
+
+    #[parser(ReportLexer)]
+    enum Ping {
+        Thing = "A #Pong"
+    }
+
+    #[parser(ReportLexer)]
+    enum Pong {
+        Thing = "B #Ping"
+    }
+
+    #[parser(ReportLexer)]
+    enum Orphan {
+        Thing = "C"
+    }
+
+    mod other {
+        use crate as parce;
+        use parce::prelude::*;
+
+        #[parser(super::ReportLexer)]
+        pub(super) enum Child {
+            Thing = "C"
+        }
+
+        #[parser(super::ReportLexer)]
+        pub(super) enum C {
+            Thing = "A"
+        }
+    }
+
+    mod documented {
+        use crate as parce;
+        use parce::prelude::*;
+
+        /// The same lexemes as [ReportLexer](super::ReportLexer), and so the same fingerprint, but
+        /// the copy of Orphan below isn't registered as one of its rules.
+        #[lexer(DocumentedLexer)]
+        pub(super) enum ReportLexeme {
+            A = 'a',
+            B = 'b',
+            C = 'c',
+            Unused = 'u',
+            #[frag] Fragment = 'f',
+            #[skip] WhiteSpace = "[ \n\r\t]"
+        }
+
+        /// A copy of [Orphan](super::Orphan), with docs.
+        #[parser(DocumentedLexer)]
+        pub(super) enum Orphan {
+            /// The only production.
+            Thing = "C"
+        }
+    }
+
+    /// Every rule of `ReportLexer`, for when they aren't registered automatically.
+    const RULES: &[fn() -> &'static RuleInfo] = &[
+        Root::rule_info, Child::rule_info, Ping::rule_info, Pong::rule_info, Orphan::rule_info,
+        other::Child::rule_info, other::C::rule_info, Wide::rule_info, Tail::rule_info, Shapes::rule_info
+    ];
+
+    #[test]
+    fn report() {
+        let report = grammar_report_with::<Root>(RULES);
+        assert_eq!(report.unreachable_rules, vec!["C", "Child", "Orphan", "Shapes", "Tail", "Wide"]);
+        assert_eq!(report.unproductive_rules, vec!["Ping", "Pong"]);
+        assert_eq!(report.unused_lexemes, vec!["Unused"]);
+        assert!(!report.is_clean());
+
+        assert!(grammar_report::<Child>().unproductive_rules.is_empty());
+    }
+
+    #[test]
+    fn name_collisions() {
+        let collisions = grammar_report_with::<Root>(RULES).name_collisions;
+        assert_eq!(collisions, vec![
+            NameCollision::LexemeAndRule {
+                name: "C",
+                module: "parce::report::tests::other"
+            },
+            NameCollision::Rules {
+                name: "Child",
+                modules: vec!["parce::report::tests", "parce::report::tests::other"]
+            }
+        ]);
+        assert_eq!(
+            collisions[1].to_string(),
+            "rule Child is declared in parce::report::tests::Child and parce::report::tests::other::Child. \
+            Rename all but one of them, for example to OtherChild, so reports and `#` references can't be confused."
+        );
+    }
+
+    #[parser(ReportLexer)]
+    enum Wide {
+        First = "A (B | B C | C)",
+        Second = "A #Tail",
+        Third = "C"
+    }
+
+    #[parser(ReportLexer)]
+    #[sync(A, C)]
+    enum Tail {
+        Maybe = "B?",
+        Then = "C A"
+    }
+
+    #[test]
+    fn conflicts() {
+        assert!(conflict_report::<Child>().is_empty());
+        assert_eq!(conflict_report::<Wide>(), vec![
+            Conflict {
+                rule: "Wide",
+                site: ConflictSite::Productions("First", "Second"),
+                shared: vec!["A"]
+            },
+            Conflict {
+                rule: "Wide",
+                site: ConflictSite::OrGroup {
+                    production: "First",
+                    alternatives: (0, 1)
+                },
+                shared: vec!["B"]
+            }
+        ]);
+        assert!(conflict_report::<Tail>().is_empty());
+    }
+
+    #[lexer(DeterminismLexer)]
+    enum DeterminismLexeme {
+        A = 'a',
+        B = 'b',
+        C = 'c',
+        #[skip] WhiteSpace = "[ \n\r\t]"
+    }
+
+    #[parser(DeterminismLexer)]
+    #[deterministic]
+    enum Steady {
+        Pair = "A #Greedy C",
+        Single = "B"
+    }
+
+    #[parser(DeterminismLexer)]
+    enum Greedy {
+        Thing = "B* C?"
+    }
+
+    #[test]
+    fn determinism() {
+        // Greedy can't tell whether a C ends it or is the one after it in Steady.
+        let conflicts = vec![Conflict {
+            rule: "Greedy",
+            site: ConflictSite::Repetition { production: "Thing" },
+            shared: vec!["C"]
+        }];
+        assert_eq!(determinism_report::<Steady>(), conflicts);
+        assert_eq!(conflict_report::<Steady>(), conflicts);
+        assert!(determinism_report::<Wide>().is_empty());
+        assert!(Steady::rule_info().deterministic && !Greedy::rule_info().deterministic);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "Greedy::Thing: a repetition can both go on and stop at C. Consider ending it with a lexeme \
+            that can't start another repeat."
+        );
+    }
+
+    #[parser(ReportLexer)]
+    enum Shapes {
+        Repeats = "A* B+ C? A{2} B{2,} C{2,3}",
+        Groups = "(A | B C) (. #Tail)+"
+    }
+
+    #[test]
+    fn grammar_info() {
+        let grammar = Shapes::GRAMMAR;
+        assert_eq!(grammar.root().name, "Shapes");
+        assert_eq!(grammar.lexemes, &["A", "B", "C", "Unused"]);
+        assert_eq!(grammar.rules().iter().map(|r| r.name).collect::<Vec<_>>(), vec!["Shapes", "Tail"]);
+        assert!(grammar.rule("Root").is_none());
+        assert_eq!(grammar.widest_choice(), 2);
+        assert_eq!(Wide::GRAMMAR.widest_choice(), 3);
+        assert_eq!(
+            grammar.to_string(),
+            "Shapes:\n    Repeats = \"A* B+ C? A{2} B{2,} C{2,3}\"\n    Groups = \"(A | B C) (. #Tail)+\"\n\
+            Tail:\n    Maybe = \"B?\"\n    Then = \"C A\""
+        );
+    }
+
+    #[test]
+    fn expected() {
+        use core::any::TypeId;
+        use crate::parser::RuleId;
+
+        // Shapes uses Tail, so it can describe automata of both.
+        let expected = |rule: RuleId, route: u32, state: u32| Shapes::expected(rule, route, state).unwrap();
+
+        let tail = Shapes::rule_id(TypeId::of::<Tail>()).unwrap();
+        assert_eq!(expected(tail, 0, 0).lexemes(), vec!["B"]);
+        assert!(expected(tail, 0, 0).can_end);
+        assert_eq!(expected(tail, 1, 1).lexemes(), vec!["A"]);
+        assert!(!expected(tail, 1, 1).can_end);
+        assert!(Shapes::expected(tail, 9, 0).is_none());
+        assert!(Root::rule_id(TypeId::of::<Tail>()).is_none());
+
+        assert_eq!(Root::expected(RuleId(0), 0, 1).unwrap().lexemes(), vec!["B"]);
+        assert_eq!(Wide::expected(RuleId(0), 0, 1).unwrap().lexemes(), vec!["B", "C"]);
+
+        let shapes = RuleId(0);
+        assert_eq!(expected(shapes, 0, 0).lexemes(), vec!["A", "B"]);
+        assert_eq!(expected(shapes, 0, 2).lexemes(), vec!["A", "C"]);
+        assert_eq!(expected(shapes, 1, 1).lexemes(), vec!["."]);
+        assert_eq!(expected(shapes, 1, 1).rules.len(), 0);
+    }
+
+    #[test]
+    fn sync() {
+        let tail = Tail::rule_info();
+        assert_eq!(tail.sync, &["A", "C"]);
+        assert!(Root::rule_info().sync.is_empty());
+    }
+
+    #[test]
+    fn fingerprints() {
+        assert_eq!(Orphan::FINGERPRINT, documented::Orphan::FINGERPRINT);
+        assert_eq!(Orphan::GRAMMAR.fingerprint(), documented::Orphan::GRAMMAR.fingerprint());
+        assert_ne!(Child::FINGERPRINT, other::Child::FINGERPRINT);
+        assert_ne!(Orphan::FINGERPRINT, other::Child::FINGERPRINT);
+        assert_eq!(Orphan::rule_info().fingerprint, documented::Orphan::rule_info().fingerprint);
+        assert_ne!(Orphan::FINGERPRINT, Orphan::rule_info().fingerprint);
+
+        // A grammar's fingerprint covers every rule it reaches, even through cycles.
+        assert_ne!(Root::GRAMMAR.fingerprint(), Root::FINGERPRINT);
+        assert_ne!(Ping::GRAMMAR.fingerprint(), Pong::GRAMMAR.fingerprint());
+        assert_eq!(Root::GRAMMAR.lexer_fingerprint, ReportLexer::FINGERPRINT);
+    }
+}
@@ -0,0 +1,75 @@
+//! Contains [CharLexer], which makes every character its own lexeme, for grammars small enough
+//! that a separate lexer isn't worth it.
+
+use crate as parce;
+use crate::prelude::*;
+
+/// The only lexeme of the [CharLexer]. Parser rules using it match characters with quoted literals
+/// and classes, like `'let'` and `[a-z]`, instead of lexeme names.
+///
+/// Nothing is skipped, so whitespace has to be matched explicitly too. Import the whole module, so
+/// the parser macro can find the lexer's generated items next to it.
+///
+#[cfg_attr(feature = "parser", doc = "```")]
+#[cfg_attr(not(feature = "parser"), doc = "```ignore")]
+/// use parce::prelude::*;
+/// use parce::scannerless::*;
+///
+/// #[parser(CharLexer)]
+/// enum Assignment {
+///     Let { name: String, value: Box<Number> } = "'let' ' '+ name=([a-z]+) ' '* '=' ' '* value"
+/// }
+///
+/// #[parser(CharLexer)]
+/// enum Number {
+///     Number(u32) = "0=[0-9]+"
+/// }
+///
+/// let parsed: Assignment = "let x = 42".parse().unwrap();
+/// assert_eq!(parsed, Assignment::Let { name: "x".to_string(), value: Box::new(Number::Number(42)) });
+/// ```
+#[lexer(CharLexer)]
+pub enum CharLexeme {
+    Char = "."
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use crate as parce;
+    use parce::prelude::*;
+    use parce::report::grammar_report;
+    use super::*;
+
+    #[parser(CharLexer)]
+    enum Escaped {
+        Quote = "'\\'' [a-z\\]]* '\\''",
+        Pipe(String) = "0=('|' | '(')"
+    }
+
+    #[test]
+    fn chars() {
+        assert_eq!("'ab]'".parse(), Ok(Escaped::Quote));
+        assert_eq!("''".parse(), Ok(Escaped::Quote));
+        assert_eq!("|".parse(), Ok(Escaped::Pipe("|".to_string())));
+        assert_eq!("(".parse(), Ok(Escaped::Pipe("(".to_string())));
+        assert!(("'aB'".parse() as Result<Escaped, _>).is_err());
+
+        let report = grammar_report::<Escaped>();
+        assert!(report.unused_lexemes.is_empty());
+    }
+
+    #[parser(CharLexer)]
+    enum Overlapping {
+        Letter = "('a' | [a-z]) ';'"
+    }
+
+    #[test]
+    fn conflicts() {
+        use parce::report::{conflict_report, ConflictSite};
+
+        // 'a' and [a-z] are different patterns, but both can match the same character.
+        let conflicts = conflict_report::<Overlapping>();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].site, ConflictSite::OrGroup { production: "Letter", alternatives: (0, 1) });
+    }
+}
@@ -0,0 +1,159 @@
+//! Contains [Span], a range of the input with the arithmetic that lexeme and rule spans both need.
+
+use std::fmt;
+use std::ops::Range;
+
+/// A range of byte offsets into the input, from `start` (inclusive) to `end` (exclusive).
+///
+/// Spans are ordered by where they start, and then by where they end, so sorting a list of spans
+/// puts them in input order.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::span::Span;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     Word = "[a-z]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// let lexemes = MyLexer::default().lex("one two three").unwrap();
+/// let (one, two, three) = (lexemes[0].span(), lexemes[1].span(), lexemes[2].span());
+/// assert!(one < two);
+/// assert_eq!(one.merge(three), Span::new(0, 13));
+/// assert!(one.merge(three).contains_span(two));
+/// assert_eq!(one.intersection(two), None);
+/// ```
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Span {
+    /// The index of the first byte in the span.
+    pub start: usize,
+    /// The index just after the last byte in the span.
+    pub end: usize
+}
+
+impl Span {
+    /// Creates a span from `start` to `end`.
+    ///
+    /// # Panics
+    ///
+    /// If `end` is before `start`.
+    pub fn new(start: usize, end: usize) -> Span {
+        assert!(start <= end, "span ends at {} before it starts at {}", end, start);
+        Span { start, end }
+    }
+
+    /// An empty span at `offset`, like the position of a cursor.
+    pub fn empty(offset: usize) -> Span {
+        Span { start: offset, end: offset }
+    }
+
+    /// The number of bytes in the span.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Whether the span covers no input.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Whether `offset` is inside the span. An empty span contains nothing.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    /// Whether `other` is entirely inside this span. Every span contains itself.
+    pub fn contains_span(&self, other: Span) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Whether the spans share at least one byte.
+    pub fn overlaps(&self, other: Span) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// The part of the input both spans cover, or `None` if they don't overlap.
+    pub fn intersection(&self, other: Span) -> Option<Span> {
+        if self.overlaps(other) {
+            Some(Span { start: self.start.max(other.start), end: self.end.min(other.end) })
+        } else {
+            None
+        }
+    }
+
+    /// The smallest span covering both spans, and anything between them.
+    pub fn merge(&self, other: Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+
+    /// The text the span covers.
+    ///
+    /// # Panics
+    ///
+    /// If the span isn't inside `input`, or doesn't start and end on character boundaries.
+    pub fn text<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.range()]
+    }
+
+    /// The span as a [Range], for indexing.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Span::new(range.start, range.end)
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.range()
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        let a = Span::new(2, 6);
+        let b = Span::from(4..9);
+        let empty = Span::empty(6);
+
+        assert_eq!((a.len(), empty.len()), (4, 0));
+        assert!(empty.is_empty());
+        assert!(a.contains(2) && !a.contains(6));
+        assert!(!empty.contains(6));
+        assert!(a.contains_span(Span::new(3, 6)) && a.contains_span(a) && a.contains_span(empty));
+        assert!(!a.contains_span(b));
+
+        assert_eq!(a.intersection(b), Some(Span::new(4, 6)));
+        assert_eq!(a.intersection(Span::new(6, 7)), None);
+        assert_eq!(a.merge(Span::new(8, 9)), Span::new(2, 9));
+
+        let mut spans = vec![b, Span::new(2, 3), a];
+        spans.sort();
+        assert_eq!(spans, vec![Span::new(2, 3), a, b]);
+
+        assert_eq!(a.text("abcdefgh"), "cdef");
+        assert_eq!(Range::from(a), 2..6);
+        assert_eq!(a.to_string(), "2..6");
+    }
+
+    #[test]
+    #[should_panic]
+    fn backwards() {
+        Span::new(3, 2);
+    }
+}
@@ -0,0 +1,241 @@
+//! Contains [GrammarVersion], which picks the version of the language to lex and parse, for
+//! grammars with lexemes and productions marked `#[since(...)]`, `#[cfg_grammar(...)]`, or
+//! `#[flag(...)]`.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+
+/// The version, features, and flags a lexeme or production needs, from its `#[since(...)]`,
+/// `#[cfg_grammar(...)]`, and `#[flag(...)]` attributes. Generated by the lexer and parser macros.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Gate {
+    /// The first version it is in, as numbers separated by dots, like `[1, 2]` for `"1.2"`. Empty
+    /// if it is in every version.
+    pub since: &'static [u32],
+    /// The features that all have to be enabled for it.
+    pub features: &'static [&'static str],
+    /// The flags that all have to be set for it.
+    pub flags: &'static [&'static str]
+}
+
+/// Which version of a grammar to use, and which of its optional features.
+///
+/// A lexeme or production marked `#[since(2018)]` only exists in versions 2018 and later, and one
+/// marked `#[cfg_grammar(async)]` only with the `async` feature. Everything else is always there.
+/// Versions are compared number by number, with missing numbers counting as 0, so `"1.2"` is
+/// the same version as `"1.2.0"`, and comes before `"1.10"`.
+///
+/// A lexeme or production marked `#[flag(json5)]` is different: it's left out unless the `json5`
+/// flag is [set](Self::with_flag), even in [all](Self::all) versions. Flags are for the small
+/// differences between dialects that are compiled into one grammar, like comments in JSON5,
+/// which a default parse shouldn't accept.
+///
+/// The default is [all](Self::all), which enables everything but the flags. A [ParseSession](crate::parser::ParseSession)
+/// uses the version it was given [with_version](crate::parser::ParseSession::with_version) for
+/// both lexing and parsing; a lexer used on its own uses the one it is [run](Self::run) in.
+///
+/// ```
+/// use parce::prelude::*;
+/// use parce::version::GrammarVersion;
+///
+/// #[lexer(MyLexer)]
+/// enum MyLexemes {
+///     #[since(2018)] Async = "'async'",
+///     Ident = "[a-z]+",
+///     #[skip] Space = ' '
+/// }
+///
+/// let edition = |version: &[u32]| GrammarVersion::at(version).run(|| MyLexer::default().lex("async").unwrap()[0].data);
+/// assert_eq!(edition(&[2018]), MyLexemes::Async);
+/// assert_eq!(edition(&[2015]), MyLexemes::Ident);
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct GrammarVersion {
+    version: Option<Vec<u32>>,
+    features: Option<Vec<String>>,
+    flags: Vec<String>
+}
+
+impl GrammarVersion {
+    /// Every version, with every feature.
+    pub fn all() -> Self {
+        GrammarVersion::default()
+    }
+
+    /// Version `version`, like `&[2018]` or `&[1, 2]`, without any features.
+    pub fn at(version: &[u32]) -> Self {
+        GrammarVersion {
+            version: Some(version.to_vec()),
+            features: Some(vec![]),
+            flags: vec![]
+        }
+    }
+
+    /// Enables `feature`. Naming any feature turns off the ones that aren't named, even for
+    /// [all](Self::all).
+    pub fn with_feature(mut self, feature: &str) -> Self {
+        self.features.get_or_insert_with(Vec::new).push(feature.to_string());
+        self
+    }
+
+    /// Sets `flag`, which lets the lexemes and productions marked `#[flag(...)]` with it match.
+    pub fn with_flag(mut self, flag: &str) -> Self {
+        self.flags.push(flag.to_string());
+        self
+    }
+
+    /// The flags that are set.
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// The version, if only one is allowed.
+    pub fn version(&self) -> Option<&[u32]> {
+        self.version.as_deref()
+    }
+
+    /// Whether something needing `gate` is in this version.
+    pub fn allows(&self, gate: &Gate) -> bool {
+        let version = match &self.version {
+            Some(version) => compare(version, gate.since) != Ordering::Less,
+            None => true
+        };
+        let features = match &self.features {
+            Some(features) => gate.features.iter().all(|f| features.iter().any(|enabled| enabled == f)),
+            None => true
+        };
+        let flags = gate.flags.iter().all(|f| self.flags.iter().any(|set| set == f));
+        version && features && flags
+    }
+
+    /// A hash of the version, features, and flags, which is the same on every build and every
+    /// platform, for telling apart values parsed with different versions. Versions that only differ
+    /// by trailing zeros, like `"1.2"` and `"1.2.0"`, have the same fingerprint, and so do the same
+    /// features or flags in a different order.
+    pub fn fingerprint(&self) -> u64 {
+        use crate::fingerprint::{FNV_OFFSET, combine_fingerprints, fnv1a};
+
+        let mut hash = FNV_OFFSET;
+        if let Some(version) = &self.version {
+            let significant = version.iter().rposition(|&n| n != 0).map_or(0, |last| last + 1);
+            hash = fnv1a(hash, b"version");
+            for &number in &version[..significant] {
+                hash = combine_fingerprints(hash, number as u64);
+            }
+        }
+        if let Some(features) = &self.features {
+            let mut features: Vec<&String> = features.iter().collect();
+            features.sort();
+            features.dedup();
+            hash = fnv1a(hash, b"features");
+            for feature in features {
+                hash = fnv1a(hash, feature.as_bytes());
+                hash = fnv1a(hash, &[0]);
+            }
+        }
+        if !self.flags.is_empty() {
+            let mut flags: Vec<&String> = self.flags.iter().collect();
+            flags.sort();
+            flags.dedup();
+            hash = fnv1a(hash, b"flags");
+            for flag in flags {
+                hash = fnv1a(hash, flag.as_bytes());
+                hash = fnv1a(hash, &[0]);
+            }
+        }
+        hash
+    }
+
+    /// Runs `f` with this as the current thread's version, which generated lexers and parsers
+    /// check their gates against.
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _restore = Restore(Some(CURRENT.with(|current| current.replace(self.clone()))));
+        f()
+    }
+}
+
+/// Compares two versions number by number, with missing numbers counting as 0.
+fn compare(a: &[u32], b: &[u32]) -> Ordering {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+thread_local! {
+    static CURRENT: RefCell<GrammarVersion> = RefCell::new(GrammarVersion::default());
+}
+
+/// Puts the previous version back when dropped, even if `f` panics.
+struct Restore(Option<GrammarVersion>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+}
+
+/// Whether the current thread's version [allows](GrammarVersion::allows) `gate`. Used by the
+/// generated code.
+#[doc(hidden)]
+pub fn enabled(gate: &Gate) -> bool {
+    CURRENT.with(|current| current.borrow().allows(gate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows() {
+        let since = |since| Gate { since, features: &[], flags: &[] };
+        assert!(GrammarVersion::at(&[1, 2]).allows(&since(&[1, 2, 0])));
+        assert!(GrammarVersion::at(&[1, 10]).allows(&since(&[1, 2])));
+        assert!(!GrammarVersion::at(&[1]).allows(&since(&[1, 0, 1])));
+        assert!(GrammarVersion::at(&[]).allows(&since(&[])));
+        assert!(GrammarVersion::all().allows(&since(&[9999])));
+
+        let gate = Gate { since: &[2], features: &["a", "b"], flags: &[] };
+        assert!(!GrammarVersion::at(&[2]).with_feature("a").allows(&gate));
+        assert!(GrammarVersion::at(&[2]).with_feature("b").with_feature("a").allows(&gate));
+        assert!(!GrammarVersion::all().with_feature("a").allows(&gate));
+        assert!(GrammarVersion::all().allows(&gate));
+
+        let flagged = Gate { since: &[], features: &[], flags: &["json5"] };
+        assert!(!GrammarVersion::all().allows(&flagged));
+        assert!(GrammarVersion::all().with_flag("json5").allows(&flagged));
+        assert!(GrammarVersion::at(&[1]).with_flag("other").with_flag("json5").allows(&flagged));
+    }
+
+    #[test]
+    fn fingerprints() {
+        let fingerprint = |version: GrammarVersion| version.fingerprint();
+        assert_eq!(fingerprint(GrammarVersion::at(&[1, 2])), fingerprint(GrammarVersion::at(&[1, 2, 0])));
+        assert_ne!(fingerprint(GrammarVersion::at(&[1, 2])), fingerprint(GrammarVersion::at(&[1, 2, 1])));
+        assert_eq!(
+            fingerprint(GrammarVersion::at(&[1]).with_feature("a").with_feature("b")),
+            fingerprint(GrammarVersion::at(&[1]).with_feature("b").with_feature("a"))
+        );
+        assert_ne!(fingerprint(GrammarVersion::at(&[1])), fingerprint(GrammarVersion::at(&[1]).with_feature("a")));
+        assert_ne!(fingerprint(GrammarVersion::all()), fingerprint(GrammarVersion::at(&[])));
+        assert_ne!(fingerprint(GrammarVersion::all()), fingerprint(GrammarVersion::all().with_feature("a")));
+        assert_ne!(fingerprint(GrammarVersion::all()), fingerprint(GrammarVersion::all().with_flag("a")));
+        assert_eq!(
+            fingerprint(GrammarVersion::all().with_flag("a").with_flag("b")),
+            fingerprint(GrammarVersion::all().with_flag("b").with_flag("a"))
+        );
+    }
+
+    #[test]
+    fn run_restores() {
+        let gate = Gate { since: &[2], features: &[], flags: &[] };
+        let inner = GrammarVersion::at(&[1]).run(|| {
+            let nested = GrammarVersion::at(&[3]).run(|| enabled(&gate));
+            (nested, enabled(&gate))
+        });
+        assert_eq!(inner, (true, false));
+        assert!(enabled(&gate));
+    }
+}